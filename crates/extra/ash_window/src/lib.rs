@@ -1,5 +1,6 @@
 #![warn(trivial_casts, trivial_numeric_casts)]
 
+#[cfg(target_os = "windows")]
 use std::ffi::c_void;
 use std::os::raw::c_char;
 
@@ -8,11 +9,15 @@ use ash::{
     prelude::*,
     vk, Entry, Instance,
 };
+#[cfg(all(unix, not(any(target_os = "android", target_os = "macos", target_os = "ios"))))]
 use ash::vk::Display;
 use raw_window_handle::{RawDisplayHandle, RawWindowHandle};
 
 /// A copy of https://github.com/ash-rs/ash/blob/master/ash-window/src/lib.rs
 /// as ash-window using winit 5.x but this project required winit 6.* to use pump_events
+///
+/// Platform surfaces are only compiled for the targets providing them,
+/// other handles fail with `ERROR_EXTENSION_NOT_PRESENT`.
 
 /// Create a surface from a raw surface handle.
 ///
@@ -45,6 +50,7 @@ pub unsafe fn create_surface(
     allocation_callbacks: Option<&vk::AllocationCallbacks<>>,
 ) -> VkResult<vk::SurfaceKHR> {
     match (display_handle, window_handle) {
+        #[cfg(target_os = "windows")]
         (RawDisplayHandle::Windows(_), RawWindowHandle::Win32(window)) => {
             let surface_desc = vk::Win32SurfaceCreateInfoKHR::builder()
                 .hinstance(vk::HINSTANCE::from(window.hinstance.unwrap().get() as *const c_void))
@@ -54,6 +60,7 @@ pub unsafe fn create_surface(
             surface_fn.create_win32_surface(&surface_desc, allocation_callbacks)
         }
 
+        #[cfg(all(unix, not(any(target_os = "android", target_os = "macos", target_os = "ios"))))]
         (RawDisplayHandle::Wayland(display), RawWindowHandle::Wayland(window)) => {
             let surface_desc = vk::WaylandSurfaceCreateInfoKHR::builder()
                 .display(display.display.as_ptr())
@@ -63,6 +70,7 @@ pub unsafe fn create_surface(
             surface_fn.create_wayland_surface(&surface_desc, allocation_callbacks)
         }
 
+        #[cfg(all(unix, not(any(target_os = "android", target_os = "macos", target_os = "ios"))))]
         (RawDisplayHandle::Xlib(display), RawWindowHandle::Xlib(window)) => {
             let surface_desc = vk::XlibSurfaceCreateInfoKHR::builder()
                 .dpy(display.display.unwrap().as_ptr() as *mut Display)
@@ -72,6 +80,7 @@ pub unsafe fn create_surface(
             surface_fn.create_xlib_surface(&surface_desc, allocation_callbacks)
         }
 
+        #[cfg(all(unix, not(any(target_os = "android", target_os = "macos", target_os = "ios"))))]
         (RawDisplayHandle::Xcb(display), RawWindowHandle::Xcb(window)) => {
             let surface_desc = vk::XcbSurfaceCreateInfoKHR::builder()
                 .connection(display.connection.unwrap().as_ptr())
@@ -81,6 +90,7 @@ pub unsafe fn create_surface(
             surface_fn.create_xcb_surface(&surface_desc, allocation_callbacks)
         }
 
+        #[cfg(target_os = "android")]
         (RawDisplayHandle::Android(_), RawWindowHandle::AndroidNdk(window)) => {
             let surface_desc =
                 vk::AndroidSurfaceCreateInfoKHR::builder().window(window.a_native_window.as_ptr()).build();
@@ -131,6 +141,7 @@ pub fn enumerate_required_extensions(
     display_handle: RawDisplayHandle,
 ) -> VkResult<&'static [*const c_char]> {
     let extensions = match display_handle {
+        #[cfg(target_os = "windows")]
         RawDisplayHandle::Windows(_) => {
             const WINDOWS_EXTS: [*const c_char; 2] = [
                 khr::Surface::name().as_ptr(),
@@ -139,6 +150,7 @@ pub fn enumerate_required_extensions(
             &WINDOWS_EXTS
         }
 
+        #[cfg(all(unix, not(any(target_os = "android", target_os = "macos", target_os = "ios"))))]
         RawDisplayHandle::Wayland(_) => {
             const WAYLAND_EXTS: [*const c_char; 2] = [
                 khr::Surface::name().as_ptr(),
@@ -147,18 +159,21 @@ pub fn enumerate_required_extensions(
             &WAYLAND_EXTS
         }
 
+        #[cfg(all(unix, not(any(target_os = "android", target_os = "macos", target_os = "ios"))))]
         RawDisplayHandle::Xlib(_) => {
             const XLIB_EXTS: [*const c_char; 2] =
                 [khr::Surface::name().as_ptr(), khr::XlibSurface::name().as_ptr()];
             &XLIB_EXTS
         }
 
+        #[cfg(all(unix, not(any(target_os = "android", target_os = "macos", target_os = "ios"))))]
         RawDisplayHandle::Xcb(_) => {
             const XCB_EXTS: [*const c_char; 2] =
                 [khr::Surface::name().as_ptr(), khr::XcbSurface::name().as_ptr()];
             &XCB_EXTS
         }
 
+        #[cfg(target_os = "android")]
         RawDisplayHandle::Android(_) => {
             const ANDROID_EXTS: [*const c_char; 2] = [
                 khr::Surface::name().as_ptr(),
@@ -0,0 +1,131 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+use async_channel::Receiver;
+use bevy_ecs::prelude::{Event, EventWriter, Res, ResMut, Resource};
+use bevy_utils::HashMap;
+use crate::{Asset, AssetId, AssetServer, Handle, HandleProvider};
+
+/// Sent by [`Assets`] when its content changes.
+#[derive(Event)]
+pub enum AssetEvent<T: Asset> {
+    Added { id: AssetId<T> },
+    Modified { id: AssetId<T> },
+    Removed { id: AssetId<T> },
+}
+
+impl<T: Asset> Clone for AssetEvent<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Asset> Copy for AssetEvent<T> {}
+
+impl<T: Asset> Debug for AssetEvent<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added { id } => f.debug_struct("Added").field("id", id).finish(),
+            Self::Modified { id } => f.debug_struct("Modified").field("id", id).finish(),
+            Self::Removed { id } => f.debug_struct("Removed").field("id", id).finish(),
+        }
+    }
+}
+
+impl<T: Asset> AssetEvent<T> {
+    pub fn id(&self) -> AssetId<T> {
+        match self {
+            Self::Added { id } | Self::Modified { id } | Self::Removed { id } => *id,
+        }
+    }
+}
+
+/// Every loaded asset of type `T`, assets are removed once their last [`Handle`] is dropped.
+#[derive(Resource)]
+pub struct Assets<T: Asset> {
+    assets: HashMap<u32, T>,
+    provider: Arc<HandleProvider>,
+    drop_receiver: Receiver<u32>,
+    queued_events: Vec<AssetEvent<T>>,
+}
+
+impl<T: Asset> Assets<T> {
+    pub(crate) fn new(provider: Arc<HandleProvider>, drop_receiver: Receiver<u32>) -> Self {
+        Self {
+            assets: HashMap::default(),
+            provider,
+            drop_receiver,
+            queued_events: Vec::new(),
+        }
+    }
+
+    /// Add an asset created at runtime.
+    pub fn add(&mut self, asset: T) -> Handle<T> {
+        let handle = Handle::from_strong(self.provider.reserve(None));
+        self.insert(handle.id(), asset);
+        handle
+    }
+
+    /// Insert or replace the asset behind `id`.
+    pub fn insert(&mut self, id: impl Into<AssetId<T>>, asset: T) {
+        let id = id.into();
+        let event = match self.assets.insert(id.index(), asset) {
+            Some(_) => AssetEvent::Modified { id },
+            None => AssetEvent::Added { id },
+        };
+        self.queued_events.push(event);
+    }
+
+    #[inline]
+    pub fn get(&self, id: impl Into<AssetId<T>>) -> Option<&T> {
+        self.assets.get(&id.into().index())
+    }
+
+    /// Flags the asset as modified.
+    pub fn get_mut(&mut self, id: impl Into<AssetId<T>>) -> Option<&mut T> {
+        let id = id.into();
+        let asset = self.assets.get_mut(&id.index())?;
+        self.queued_events.push(AssetEvent::Modified { id });
+        Some(asset)
+    }
+
+    #[inline]
+    pub fn contains(&self, id: impl Into<AssetId<T>>) -> bool {
+        self.assets.contains_key(&id.into().index())
+    }
+
+    /// Remove the asset, handles to it stay valid but resolve to nothing.
+    pub fn remove(&mut self, id: impl Into<AssetId<T>>) -> Option<T> {
+        let id = id.into();
+        let asset = self.assets.remove(&id.index())?;
+        self.queued_events.push(AssetEvent::Removed { id });
+        Some(asset)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (AssetId<T>, &T)> {
+        self.assets.iter().map(|(index, asset)| (AssetId::new(*index), asset))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+}
+
+/// Frees assets whose handles were all dropped and sends the queued [`AssetEvent`]s.
+pub(crate) fn track_assets<T: Asset>(
+    mut assets: ResMut<Assets<T>>,
+    mut events: EventWriter<AssetEvent<T>>,
+    server: Res<AssetServer>,
+) {
+    while let Ok(index) = assets.drop_receiver.try_recv() {
+        let id = AssetId::<T>::new(index);
+        assets.remove(id);
+        server.forget(id.untyped());
+    }
+    events.send_batch(assets.queued_events.drain(..));
+}
@@ -0,0 +1,193 @@
+use std::any::TypeId;
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use async_channel::Sender;
+use bevy_ecs::component::{Component, TableStorage};
+use crate::Asset;
+
+/// Identifies an asset inside its [`Assets`](crate::Assets) collection.
+pub struct AssetId<T: Asset> {
+    index: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Asset> AssetId<T> {
+    pub(crate) fn new(index: u32) -> Self {
+        Self {
+            index,
+            marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    #[inline]
+    pub fn untyped(&self) -> UntypedAssetId {
+        UntypedAssetId {
+            type_id: TypeId::of::<T>(),
+            index: self.index,
+        }
+    }
+}
+
+impl<T: Asset> Clone for AssetId<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Asset> Copy for AssetId<T> {}
+
+impl<T: Asset> PartialEq for AssetId<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T: Asset> Eq for AssetId<T> {}
+
+impl<T: Asset> Hash for AssetId<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T: Asset> Debug for AssetId<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AssetId<{}>({})", std::any::type_name::<T>(), self.index)
+    }
+}
+
+impl<T: Asset> From<&Handle<T>> for AssetId<T> {
+    fn from(value: &Handle<T>) -> Self {
+        value.id()
+    }
+}
+
+impl<T: Asset> From<AssetId<T>> for UntypedAssetId {
+    fn from(value: AssetId<T>) -> Self {
+        value.untyped()
+    }
+}
+
+/// An [`AssetId`] with its asset type erased.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UntypedAssetId {
+    pub type_id: TypeId,
+    pub index: u32,
+}
+
+/// Hands out the ids of one asset type and reports dropped handles to its [`Assets`](crate::Assets).
+pub(crate) struct HandleProvider {
+    type_id: TypeId,
+    next_index: AtomicU32,
+    drop_sender: Sender<u32>,
+}
+
+impl HandleProvider {
+    pub(crate) fn new(type_id: TypeId, drop_sender: Sender<u32>) -> Self {
+        Self {
+            type_id,
+            next_index: AtomicU32::new(0),
+            drop_sender,
+        }
+    }
+
+    pub(crate) fn reserve(&self, path: Option<PathBuf>) -> Arc<StrongHandle> {
+        Arc::new(StrongHandle {
+            id: UntypedAssetId {
+                type_id: self.type_id,
+                index: self.next_index.fetch_add(1, Ordering::Relaxed),
+            },
+            path,
+            drop_sender: self.drop_sender.clone(),
+        })
+    }
+}
+
+/// Shared by every clone of a [`Handle`], the asset is freed once it is dropped.
+pub(crate) struct StrongHandle {
+    pub(crate) id: UntypedAssetId,
+    pub(crate) path: Option<PathBuf>,
+    drop_sender: Sender<u32>,
+}
+
+impl Drop for StrongHandle {
+    fn drop(&mut self) {
+        // the receiver is gone when the app is being torn down
+        let _ = self.drop_sender.try_send(self.id.index);
+    }
+}
+
+/// Strong reference to an asset, which stays alive as long as a handle to it exists.
+///
+/// A handle is valid before the asset is loaded, see [`AssetServer::load_state`](crate::AssetServer::load_state).
+/// Reloading the asset keeps its id, so entities holding the handle pick up the new content.
+pub struct Handle<T: Asset> {
+    inner: Arc<StrongHandle>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Asset> Handle<T> {
+    pub(crate) fn from_strong(inner: Arc<StrongHandle>) -> Self {
+        Self {
+            inner,
+            marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn strong(&self) -> &Arc<StrongHandle> {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn id(&self) -> AssetId<T> {
+        AssetId::new(self.inner.id.index)
+    }
+
+    /// Path relative to the asset root, `None` for assets added at runtime.
+    #[inline]
+    pub fn path(&self) -> Option<&Path> {
+        self.inner.path.as_deref()
+    }
+}
+
+impl<T: Asset> Component for Handle<T> {
+    type Storage = TableStorage;
+}
+
+impl<T: Asset> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self::from_strong(self.inner.clone())
+    }
+}
+
+impl<T: Asset> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner.id == other.inner.id
+    }
+}
+
+impl<T: Asset> Eq for Handle<T> {}
+
+impl<T: Asset> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner.id.hash(state);
+    }
+}
+
+impl<T: Asset> Debug for Handle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Handle")
+            .field("id", &self.id())
+            .field("path", &self.inner.path)
+            .finish()
+    }
+}
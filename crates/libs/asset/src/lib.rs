@@ -0,0 +1,105 @@
+mod handle;
+mod assets;
+mod loader;
+mod server;
+mod watcher;
+
+pub use handle::*;
+pub use assets::*;
+pub use loader::*;
+pub use server::*;
+pub use watcher::*;
+
+use std::any::TypeId;
+use std::path::PathBuf;
+use std::sync::Arc;
+use bevy_app::{App, Plugin, PreUpdate};
+use bevy_ecs::prelude::{IntoSystemConfigs, IntoSystemSetConfigs, SystemSet};
+
+/// Anything which can be stored in [`Assets`] and referenced by a [`Handle`].
+pub trait Asset: Send + Sync + 'static {}
+
+/// Environment variable overriding the default asset root.
+pub const ASSET_ROOT_ENV: &str = "AVALANCHE_ASSET_ROOT";
+
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetSet {
+    /// Modified files are reloaded, see [`AssetWatcher`].
+    WatchForChanges,
+    /// Assets finished loading in the background are inserted.
+    LoadAssets,
+    /// Assets without handles are freed and [`AssetEvent`]s are sent.
+    TrackAssets,
+}
+
+/// Adds the [`AssetServer`], asset types are registered with [`AssetApp`].
+pub struct AssetPlugin {
+    /// Directory loaded paths are relative to.
+    pub asset_root: PathBuf,
+    /// Reload assets whose file changed, enabled in debug builds by default.
+    pub watch_for_changes: bool,
+}
+
+impl Default for AssetPlugin {
+    fn default() -> Self {
+        Self {
+            asset_root: std::env::var_os(ASSET_ROOT_ENV)
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("assets")),
+            watch_for_changes: cfg!(debug_assertions),
+        }
+    }
+}
+
+impl Plugin for AssetPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AssetServer::new(self.asset_root.clone()))
+            .add_event::<AssetLoadEvent>()
+            .configure_sets(
+                PreUpdate,
+                (AssetSet::WatchForChanges, AssetSet::LoadAssets, AssetSet::TrackAssets).chain(),
+            )
+            .add_systems(PreUpdate, process_load_results.in_set(AssetSet::LoadAssets));
+
+        if self.watch_for_changes {
+            app.init_resource::<AssetWatcher>()
+                .add_systems(PreUpdate, watch_for_changes.in_set(AssetSet::WatchForChanges));
+        }
+    }
+}
+
+pub trait AssetApp {
+    /// Add the [`Assets<T>`] resource and its [`AssetEvent<T>`], required before loading `T`.
+    fn init_asset<T: Asset>(&mut self) -> &mut Self;
+
+    /// Register an [`AssetLoader`] with the [`AssetServer`].
+    fn register_asset_loader<L: AssetLoader>(&mut self, loader: L) -> &mut Self;
+}
+
+impl AssetApp for App {
+    fn init_asset<T: Asset>(&mut self) -> &mut Self {
+        if self.world.contains_resource::<Assets<T>>() {
+            return self;
+        }
+
+        let server = self.world
+            .get_resource::<AssetServer>()
+            .expect("AssetPlugin must be added before initializing assets")
+            .clone();
+        let (drop_sender, drop_receiver) = async_channel::unbounded();
+        let provider = Arc::new(HandleProvider::new(TypeId::of::<T>(), drop_sender));
+        server.register_asset::<T>(provider.clone());
+
+        self.insert_resource(Assets::<T>::new(provider, drop_receiver))
+            .add_event::<AssetEvent<T>>()
+            .add_systems(PreUpdate, track_assets::<T>.in_set(AssetSet::TrackAssets))
+    }
+
+    fn register_asset_loader<L: AssetLoader>(&mut self, loader: L) -> &mut Self {
+        self.world
+            .get_resource::<AssetServer>()
+            .expect("AssetPlugin must be added before registering asset loaders")
+            .register_loader(loader);
+        self
+    }
+}
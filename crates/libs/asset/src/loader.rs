@@ -0,0 +1,42 @@
+use std::any::{Any, TypeId};
+use std::path::Path;
+use crate::Asset;
+
+/// Turns the bytes of a file into an asset, selected by the extension of the loaded path.
+///
+/// Loaders run on the [`IoTaskPool`](bevy_tasks::IoTaskPool), never on the main thread.
+pub trait AssetLoader: Send + Sync + 'static {
+    type Asset: Asset;
+
+    /// Extensions handled by this loader, without the leading dot.
+    fn extensions(&self) -> &[&str];
+
+    /// `path` is relative to the asset root.
+    fn load(&self, bytes: &[u8], path: &Path) -> anyhow::Result<Self::Asset>;
+}
+
+pub(crate) type BoxedAsset = Box<dyn Any + Send + Sync>;
+
+/// Object safe [`AssetLoader`] the [`AssetServer`](crate::AssetServer) keeps per extension.
+pub(crate) trait ErasedAssetLoader: Send + Sync + 'static {
+    fn asset_type_id(&self) -> TypeId;
+
+    fn asset_type_name(&self) -> &'static str;
+
+    fn load(&self, bytes: &[u8], path: &Path) -> anyhow::Result<BoxedAsset>;
+}
+
+impl<L: AssetLoader> ErasedAssetLoader for L {
+    fn asset_type_id(&self) -> TypeId {
+        TypeId::of::<L::Asset>()
+    }
+
+    fn asset_type_name(&self) -> &'static str {
+        std::any::type_name::<L::Asset>()
+    }
+
+    fn load(&self, bytes: &[u8], path: &Path) -> anyhow::Result<BoxedAsset> {
+        let asset = AssetLoader::load(self, bytes, path)?;
+        Ok(Box::new(asset))
+    }
+}
@@ -0,0 +1,267 @@
+use std::any::TypeId;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use async_channel::{Receiver, Sender};
+use bevy_ecs::prelude::{Event, Events, Resource, World};
+use bevy_log::error;
+use bevy_tasks::IoTaskPool;
+use bevy_utils::HashMap;
+use thiserror::Error;
+use crate::{Asset, AssetLoader, Assets, Handle, HandleProvider, StrongHandle, UntypedAssetId};
+use crate::loader::{BoxedAsset, ErasedAssetLoader};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LoadState {
+    #[default]
+    NotLoaded,
+    Loading,
+    Loaded,
+    Failed,
+}
+
+/// Sent when a load started by the [`AssetServer`] completes.
+#[derive(Event, Clone, Debug)]
+pub enum AssetLoadEvent {
+    Loaded { id: UntypedAssetId, path: PathBuf },
+    Failed { id: UntypedAssetId, path: PathBuf, error: String },
+}
+
+#[derive(Error, Debug)]
+pub enum AssetLoadError {
+    #[error("no asset loader registered for extension {extension:?} of {path:?}")]
+    MissingLoader { extension: String, path: PathBuf },
+    #[error("the loader for {path:?} produces {loader_asset} which is not the requested asset type")]
+    WrongAssetType { path: PathBuf, loader_asset: &'static str },
+    #[error("failed to read {path:?}: {source}")]
+    Io { path: PathBuf, source: std::io::Error },
+}
+
+/// Type-erased access to the [`Assets`] of a type, registered by [`AssetApp::init_asset`](crate::AssetApp::init_asset).
+struct AssetTypeInfo {
+    provider: Arc<HandleProvider>,
+    insert: fn(&mut World, u32, BoxedAsset),
+}
+
+struct LoadResult {
+    id: UntypedAssetId,
+    handle: Weak<StrongHandle>,
+    path: PathBuf,
+    result: anyhow::Result<BoxedAsset>,
+}
+
+struct AssetServerInner {
+    root: PathBuf,
+    loaders: RwLock<HashMap<String, Arc<dyn ErasedAssetLoader>>>,
+    asset_types: RwLock<HashMap<TypeId, AssetTypeInfo>>,
+    /// Handles of loaded paths, so loading a path twice gives the same asset
+    handles: RwLock<HashMap<(TypeId, PathBuf), Weak<StrongHandle>>>,
+    states: RwLock<HashMap<UntypedAssetId, LoadState>>,
+    result_sender: Sender<LoadResult>,
+    result_receiver: Receiver<LoadResult>,
+}
+
+/// Loads assets from files under the asset root in the background.
+///
+/// Cloning is cheap, every clone refers to the same server.
+#[derive(Resource, Clone)]
+pub struct AssetServer {
+    inner: Arc<AssetServerInner>,
+}
+
+impl AssetServer {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let (result_sender, result_receiver) = async_channel::unbounded();
+        Self {
+            inner: Arc::new(AssetServerInner {
+                root: root.into(),
+                loaders: Default::default(),
+                asset_types: Default::default(),
+                handles: Default::default(),
+                states: Default::default(),
+                result_sender,
+                result_receiver,
+            }),
+        }
+    }
+
+    #[inline]
+    pub fn root(&self) -> &Path {
+        &self.inner.root
+    }
+
+    /// Register `loader` for its extensions, replacing loaders previously registered for them.
+    pub fn register_loader<L: AssetLoader>(&self, loader: L) {
+        let extensions = loader
+            .extensions()
+            .iter()
+            .map(|extension| extension.to_ascii_lowercase())
+            .collect::<Vec<_>>();
+        let loader: Arc<dyn ErasedAssetLoader> = Arc::new(loader);
+
+        let mut loaders = self.inner.loaders.write().unwrap();
+        for extension in extensions {
+            loaders.insert(extension, loader.clone());
+        }
+    }
+
+    pub(crate) fn register_asset<T: Asset>(&self, provider: Arc<HandleProvider>) {
+        self.inner.asset_types.write().unwrap().insert(TypeId::of::<T>(), AssetTypeInfo {
+            provider,
+            insert: insert_loaded_asset::<T>,
+        });
+    }
+
+    /// Start loading the file at `path`, relative to the asset root.
+    ///
+    /// Loading an already loaded path returns a handle to the existing asset.
+    pub fn load<T: Asset>(&self, path: impl AsRef<Path>) -> Handle<T> {
+        let path = path.as_ref().to_path_buf();
+        let key = (TypeId::of::<T>(), path.clone());
+
+        let mut handles = self.inner.handles.write().unwrap();
+        if let Some(handle) = handles.get(&key).and_then(Weak::upgrade) {
+            return Handle::from_strong(handle);
+        }
+
+        let provider = self.inner.asset_types
+            .read()
+            .unwrap()
+            .get(&TypeId::of::<T>())
+            .map(|info| info.provider.clone())
+            .unwrap_or_else(|| panic!(
+                "Asset type {} was not initialized, call App::init_asset first",
+                std::any::type_name::<T>(),
+            ));
+        let handle = Handle::<T>::from_strong(provider.reserve(Some(path)));
+        handles.insert(key, Arc::downgrade(handle.strong()));
+        drop(handles);
+
+        self.start_load(handle.strong());
+        handle
+    }
+
+    /// Load `path` again into every asset loaded from it, keeping their ids and handles.
+    pub fn reload(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let handles = self.inner.handles
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|((_, handle_path), _)| handle_path == path)
+            .filter_map(|(_, handle)| handle.upgrade())
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            self.start_load(&handle);
+        }
+    }
+
+    /// Paths of the assets which are still referenced by a handle.
+    pub fn loaded_paths(&self) -> Vec<PathBuf> {
+        let mut handles = self.inner.handles.write().unwrap();
+        handles.retain(|_, handle| handle.strong_count() > 0);
+
+        let mut paths = handles.keys().map(|(_, path)| path.clone()).collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    pub fn load_state(&self, id: impl Into<UntypedAssetId>) -> LoadState {
+        self.inner.states
+            .read()
+            .unwrap()
+            .get(&id.into())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn start_load(&self, handle: &Arc<StrongHandle>) {
+        let id = handle.id;
+        let path = handle.path.clone().expect("Only handles with a path can be loaded");
+        self.inner.states.write().unwrap().insert(id, LoadState::Loading);
+
+        let sender = self.inner.result_sender.clone();
+        let weak_handle = Arc::downgrade(handle);
+        let loader = match self.loader_for(&path, id.type_id) {
+            Ok(loader) => loader,
+            Err(err) => {
+                let _ = sender.try_send(LoadResult { id, handle: weak_handle, path, result: Err(err.into()) });
+                return;
+            }
+        };
+
+        let full_path = self.inner.root.join(&path);
+        IoTaskPool::get()
+            .spawn(async move {
+                let result = match async_std::fs::read(&full_path).await {
+                    Ok(bytes) => {
+                        #[cfg(feature = "trace")]
+                        let _span = bevy_utils::tracing::info_span!("load asset", path = ?path).entered();
+
+                        loader.load(&bytes, &path)
+                    }
+                    Err(source) => Err(AssetLoadError::Io { path: full_path, source }.into()),
+                };
+                let _ = sender.send(LoadResult { id, handle: weak_handle, path, result }).await;
+            })
+            .detach();
+    }
+
+    fn loader_for(&self, path: &Path, type_id: TypeId) -> Result<Arc<dyn ErasedAssetLoader>, AssetLoadError> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let loader = self.inner.loaders
+            .read()
+            .unwrap()
+            .get(&extension)
+            .cloned()
+            .ok_or_else(|| AssetLoadError::MissingLoader { extension, path: path.to_path_buf() })?;
+
+        if loader.asset_type_id() != type_id {
+            return Err(AssetLoadError::WrongAssetType {
+                path: path.to_path_buf(),
+                loader_asset: loader.asset_type_name(),
+            });
+        }
+        Ok(loader)
+    }
+
+    pub(crate) fn forget(&self, id: UntypedAssetId) {
+        self.inner.states.write().unwrap().remove(&id);
+    }
+}
+
+fn insert_loaded_asset<T: Asset>(world: &mut World, index: u32, asset: BoxedAsset) {
+    let asset = *asset.downcast::<T>().expect("Asset loader produced an unexpected type");
+    world.resource_mut::<Assets<T>>().insert(crate::AssetId::new(index), asset);
+}
+
+/// Moves the assets loaded in the background into their [`Assets`] collection.
+pub(crate) fn process_load_results(world: &mut World) {
+    let server = world.resource::<AssetServer>().clone();
+    while let Ok(LoadResult { id, handle, path, result }) = server.inner.result_receiver.try_recv() {
+        // every handle was dropped while loading
+        if handle.upgrade().is_none() {
+            server.forget(id);
+            continue;
+        }
+
+        let event = match result {
+            Ok(asset) => {
+                let insert = server.inner.asset_types.read().unwrap()[&id.type_id].insert;
+                insert(world, id.index, asset);
+                server.inner.states.write().unwrap().insert(id, LoadState::Loaded);
+                AssetLoadEvent::Loaded { id, path }
+            }
+            Err(err) => {
+                error!("Failed to load asset {path:?}: {err:#}");
+                server.inner.states.write().unwrap().insert(id, LoadState::Failed);
+                AssetLoadEvent::Failed { id, path, error: format!("{err:#}") }
+            }
+        };
+        world.resource_mut::<Events<AssetLoadEvent>>().send(event);
+    }
+}
@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use bevy_ecs::prelude::{Res, ResMut, Resource};
+use bevy_log::info;
+use bevy_utils::HashMap;
+use crate::AssetServer;
+
+/// Polls the modification time of loaded files and reloads the ones which changed.
+///
+/// Polling avoids a platform file watcher and only touches files which are actually loaded.
+#[derive(Resource)]
+pub struct AssetWatcher {
+    pub poll_interval: Duration,
+    last_poll: Option<Instant>,
+    modified: HashMap<PathBuf, SystemTime>,
+}
+
+impl AssetWatcher {
+    pub fn new(poll_interval: Duration) -> Self {
+        Self {
+            poll_interval,
+            last_poll: None,
+            modified: HashMap::default(),
+        }
+    }
+}
+
+impl Default for AssetWatcher {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(500))
+    }
+}
+
+pub(crate) fn watch_for_changes(server: Res<AssetServer>, mut watcher: ResMut<AssetWatcher>) {
+    let now = Instant::now();
+    if matches!(watcher.last_poll, Some(last_poll) if now - last_poll < watcher.poll_interval) {
+        return;
+    }
+    watcher.last_poll = Some(now);
+
+    #[cfg(feature = "trace")]
+    let _span = bevy_utils::tracing::info_span!("watch asset changes").entered();
+
+    let paths = server.loaded_paths();
+    watcher.modified.retain(|path, _| paths.binary_search(path).is_ok());
+
+    for path in paths {
+        let Ok(modified) = std::fs::metadata(server.root().join(&path)).and_then(|metadata| metadata.modified()) else {
+            continue;
+        };
+
+        match watcher.modified.insert(path.clone(), modified) {
+            Some(previous) if previous != modified => {
+                info!("Reloading modified asset {path:?}");
+                server.reload(&path);
+            }
+            _ => {}
+        }
+    }
+}
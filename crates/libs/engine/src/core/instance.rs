@@ -1,6 +1,8 @@
 use std::sync::Mutex;
 use bevy_app::{App, AppExit};
-use bevy_ecs::prelude::AppTypeRegistry;
+use bevy_ecs::prelude::{AppTypeRegistry, Entity, Events, With};
+use avalanche_rendering::shutdown::shutdown_rendering;
+use avalanche_window::WindowComponent;
 use crate::core::task::{MainTaskPluginGroup, SchedulerMinimalPlugins};
 
 static INSTANCE_EXIT_FLAG: Mutex<bool> = Mutex::new(false);
@@ -25,15 +27,35 @@ impl Default for EngineInstance {
 impl EngineInstance {
     pub fn run(&mut self) -> EngineExitStatus {
         loop {
-            if INSTANCE_EXIT_FLAG.lock().unwrap().clone() {
+            if *INSTANCE_EXIT_FLAG.lock().unwrap() {
                 break;
             }
 
             self.app.run();
+
+            // the runner only returns once an `AppExit` was sent
+            if self.app.world.get_resource::<Events<AppExit>>().is_some_and(|events| !events.is_empty()) {
+                *INSTANCE_EXIT_FLAG.lock().unwrap() = true;
+            }
         }
 
+        self.teardown();
         EngineExitStatus::Normal
     }
+
+    /// Release the GPU resources in order, then the windows.
+    fn teardown(&mut self) {
+        shutdown_rendering(&mut self.app);
+
+        let world = &mut self.app.world;
+        let windows = world
+            .query_filtered::<Entity, With<WindowComponent>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        for window in windows {
+            world.despawn(window);
+        }
+    }
 }
 
 pub enum EngineExitStatus {
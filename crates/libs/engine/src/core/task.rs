@@ -1,6 +1,8 @@
 mod main;
+mod trace;
 
 pub use main::*;
+pub use trace::*;
 use bevy_ecs::schedule::ScheduleLabel;
 
 #[derive(ScheduleLabel, Hash, PartialEq, Eq, Debug, Clone)]
@@ -4,55 +4,79 @@ use std::sync::Arc;
 use bevy_app::{App, Plugin, PluginGroup, PluginGroupBuilder, Update};
 use bevy_ecs::prelude::{EventReader, IntoSystemSetConfigs, Query, Res, World};
 use chrono::Local;
-use ash::vk;
 use bevy_ecs::event::EventWriter;
 use env_logger::Env;
-use avalanche_hlvk::{ContextBuilder, DeviceFeatures, Swapchain};
-use avalanche_rendering::prelude::RenderingContext;
-use avalanche_rendering::{INIT_COMMAND_POOL_NUM, RenderingPipelinePlugin};
-use avalanche_window::{new_window_component, PrimaryWindowComponent, WindowComponent, WindowManager, WindowSystemPlugin, WindowSystemSet};
+use avalanche_hlvk::{ContextBuilder, DeviceFeatures, Swapchain, SwapchainOptions};
+use avalanche_asset::AssetPlugin;
+use avalanche_scene::ScenePlugin;
+use avalanche_rendering::prelude::{RenderingContext, RenderingContextHooks};
+use avalanche_rendering::RenderingPipelinePlugin;
+use avalanche_rendering::color::SrgbPolicy;
+use avalanche_rendering::pipelined_rendering::PipelinedRenderingPlugin;
+use avalanche_window::{new_window_component_with, PrimaryWindowComponent, WindowComponent, WindowDescriptor, WindowManager, WindowSystemPlugin, WindowSystemSet};
 use avalanche_window::event::WindowEventLoopClearedEvent;
+use avalanche_window::input::InputActionPlugin;
 use crate::core::event::BeginRenderWindowViewEvent;
+use crate::core::task::TracingPlugin;
 
 pub struct EngineContextSetupPlugin;
 
+/// Set to log the `debugPrintfEXT` calls of shaders in debug builds, requires the validation layers.
+pub const DEBUG_PRINTF_ENV: &str = "AVALANCHE_DEBUG_PRINTF";
+
 /// Exclusive system to force schedule in main thread
 fn start_rendering_system_with_window(world: &mut World) {
     let window_manager = world.get_non_send_resource::<WindowManager>().unwrap();
-    let mut first_window_component = new_window_component(window_manager.event_loop.read().unwrap().deref()).unwrap();
+    window_manager.wait_for_native_window();
+    let descriptor = world.get_resource::<WindowDescriptor>().cloned().unwrap_or_default();
+    let mut first_window_component = new_window_component_with(window_manager.event_loop.read().unwrap().deref(), &descriptor).unwrap();
     let window_ref = &first_window_component.window;
 
-    let vulkan_context = ContextBuilder::new(window_ref, window_ref)
-        .required_device_features(DeviceFeatures::full())
-        .with_raytracing_context(false)
+    let create_hooks = world.get_resource::<RenderingContextHooks>().cloned();
+    let device_extensions = vec!["VK_KHR_swapchain"];
+    // ray tracing is optional, cameras fall back to the deferred graph without it
+    let mut context_builder = ContextBuilder::new(window_ref, window_ref)
+        .required_device_features(DeviceFeatures {
+            ray_tracing_pipeline: false,
+            acceleration_structure: false,
+            ..DeviceFeatures::full()
+        })
+        .optional_device_features(DeviceFeatures {
+            ray_tracing_pipeline: true,
+            acceleration_structure: true,
+            ray_tracing_position_fetch: true,
+            fill_mode_non_solid: true,
+            multiview: true,
+            ..Default::default()
+        })
+        .with_raytracing_context(true)
         .app_name("Avalanche Engine")
-        .required_device_extensions(vec!["VK_KHR_swapchain"].deref())
+        .required_device_extensions(device_extensions.deref())
         .vulkan_version(avalanche_utils::VERSION_1_3)
-        .build().unwrap();
-
-    let command_pools = (0..INIT_COMMAND_POOL_NUM)
-        .map(|_| Arc::new(vulkan_context.create_command_pool(
-            vulkan_context.graphics_queue_family,
-            Some(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
-        ).unwrap()))
-        .collect::<Vec<_>>();
+        .debug_printf(std::env::var_os(DEBUG_PRINTF_ENV).is_some());
+    if let Some(create_hooks) = &create_hooks {
+        context_builder = context_builder.create_hooks(create_hooks.0.as_ref());
+    }
+    let vulkan_context = context_builder.build().unwrap();
+    let surface = vulkan_context.surface.clone().expect("the context is created for a window");
 
-    let swapchain = Swapchain::new(
+    let srgb_policy = world.get_resource::<SrgbPolicy>().copied().unwrap_or_default();
+    let swapchain = Swapchain::with_options(
         &vulkan_context,
+        surface.clone(),
         window_ref.inner_size().width,
         window_ref.inner_size().height,
+        SwapchainOptions {
+            transparent: descriptor.transparent,
+            srgb: srgb_policy.prefers_srgb_swapchain(),
+        },
     ).unwrap();
 
-    // TODO raytracing
-
     first_window_component.render_device = Some(vulkan_context.device.clone());
-    first_window_component.surface = Some(vulkan_context.surface.clone());
+    first_window_component.surface = Some(surface);
     first_window_component.swapchain = Some(Arc::new(swapchain));
 
-    let context = RenderingContext {
-        context: Arc::new(vulkan_context),
-        command_pools: Arc::new(command_pools),
-    };
+    let context = RenderingContext::new(vulkan_context);
 
     world.insert_resource(context);
     world.spawn((first_window_component, PrimaryWindowComponent));
@@ -80,8 +104,6 @@ impl Plugin for EngineContextSetupPlugin {
     }
 }
 
-pub struct LogSystemPlugin;
-
 fn _init_env_logger() {
     env_logger::Builder::from_env(Env::default().default_filter_or("info"))
         .format(|buf, record| {
@@ -98,23 +120,22 @@ fn _init_env_logger() {
         .init();
 }
 
-impl Plugin for LogSystemPlugin {
-    fn build(&self, app: &mut App) {
-        use bevy_log::LogPlugin;
-        app.add_plugins(LogPlugin::default());
-    }
-}
-
 pub struct MainTaskPluginGroup;
 
 impl PluginGroup for MainTaskPluginGroup {
     fn build(self) -> PluginGroupBuilder {
         #[allow(unused_mut)]
         let mut builder = PluginGroupBuilder::start::<Self>()
-            .add(LogSystemPlugin)
-            .add(WindowSystemPlugin)
+            .add(TracingPlugin::default())
+            .add(WindowSystemPlugin::default())
+            .add(InputActionPlugin::default())
             .add(EngineContextSetupPlugin)
-            .add(RenderingPipelinePlugin);
+            .add(bevy_hierarchy::HierarchyPlugin)
+            .add(bevy_transform::TransformPlugin)
+            .add(AssetPlugin::default())
+            .add(ScenePlugin)
+            .add(RenderingPipelinePlugin)
+            .add(PipelinedRenderingPlugin);
 
         #[cfg(feature = "renderdoc")]
         {
@@ -0,0 +1,183 @@
+use std::path::PathBuf;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{IntoSystemConfigs, Res};
+use bevy_log::Level;
+use bevy_utils::tracing::{info, warn};
+use tracing_log::LogTracer;
+use tracing_subscriber::filter::FilterFn;
+use tracing_subscriber::{prelude::*, registry::Registry, EnvFilter, Layer};
+use avalanche_rendering::{Render, RenderApp, RenderSet};
+use avalanche_rendering::budget::{SystemRuns, SystemTimingLayer};
+use avalanche_rendering::profiler::{read_gpu_frame, GpuProfiler};
+
+/// Target of the events carrying the GPU timestamps of a frame, left out of the formatted logs.
+pub const GPU_FRAME_TARGET: &str = "avalanche::gpu_frame";
+
+/// Where spans are exported to, next to the formatted logs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TracingBackend {
+    #[default]
+    None,
+    /// A JSON trace for chrome://tracing or Perfetto, needs the `trace_chrome` feature.
+    /// Written to `./trace-{timestamp}.json` without a path.
+    Chrome { path: Option<PathBuf> },
+    /// The Tracy profiler connecting to the app, needs the `trace_tracy` feature
+    Tracy,
+}
+
+/// Sets up the global logger and tracing subscriber, in place of bevy's `LogPlugin`.
+///
+/// Spans of the engine are only emitted with the `trace` feature. With [`gpu_timestamps`](Self::gpu_timestamps)
+/// the frame timestamps of the [`GpuProfiler`] are sent as [`GPU_FRAME_TARGET`] events once read,
+/// Tracy also gets them as a GPU zone.
+///
+/// System runs are timed for the reports of the [`FrameBudgetPlugin`](avalanche_rendering::budget::FrameBudgetPlugin).
+pub struct TracingPlugin {
+    /// Filters logs using the [`EnvFilter`] format, `RUST_LOG` takes precedence
+    pub filter: String,
+    /// Filters out logs that are "less than" the given level
+    pub level: Level,
+    pub backend: TracingBackend,
+    pub gpu_timestamps: bool,
+}
+
+impl Default for TracingPlugin {
+    fn default() -> Self {
+        Self {
+            filter: String::new(),
+            level: Level::INFO,
+            backend: TracingBackend::None,
+            gpu_timestamps: true,
+        }
+    }
+}
+
+type BackendLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+impl TracingPlugin {
+    #[cfg_attr(not(feature = "trace_chrome"), allow(unused_variables))]
+    fn backend_layer(&self, app: &mut App) -> Result<Option<BackendLayer>, &'static str> {
+        match &self.backend {
+            TracingBackend::None => Ok(None),
+            #[cfg(feature = "trace_chrome")]
+            TracingBackend::Chrome { path } => {
+                let mut builder = tracing_chrome::ChromeLayerBuilder::new().include_args(true);
+                if let Some(path) = path {
+                    builder = builder.file(path);
+                }
+                let (layer, guard) = builder.build();
+                // the trace is completed once the guard is dropped with the app
+                app.world.insert_non_send_resource(guard);
+                Ok(Some(Box::new(layer)))
+            }
+            #[cfg(not(feature = "trace_chrome"))]
+            TracingBackend::Chrome { .. } => Err("trace_chrome"),
+            #[cfg(feature = "trace_tracy")]
+            TracingBackend::Tracy => Ok(Some(Box::new(tracing_tracy::TracyLayer::new()))),
+            #[cfg(not(feature = "trace_tracy"))]
+            TracingBackend::Tracy => Err("trace_tracy"),
+        }
+    }
+}
+
+impl Plugin for TracingPlugin {
+    fn build(&self, app: &mut App) {
+        let default_filter = if self.filter.is_empty() {
+            self.level.to_string()
+        } else {
+            format!("{},{}", self.level, self.filter)
+        };
+        let filter_layer = EnvFilter::try_from_default_env()
+            .or_else(|_| EnvFilter::try_new(&default_filter))
+            .unwrap();
+        let fmt_layer = tracing_subscriber::fmt::Layer::default()
+            .with_writer(std::io::stderr)
+            .with_filter(FilterFn::new(|metadata| {
+                metadata.target() != GPU_FRAME_TARGET && metadata.fields().field("tracy.frame_mark").is_none()
+            }));
+
+        let (backend_layer, missing_feature) = match self.backend_layer(app) {
+            Ok(layer) => (layer, None),
+            Err(feature) => (None, Some(feature)),
+        };
+        let system_runs = SystemRuns::default();
+        let subscriber = Registry::default()
+            .with(backend_layer)
+            .with(filter_layer)
+            .with(fmt_layer)
+            .with(SystemTimingLayer::new(system_runs.clone()));
+        app.insert_resource(system_runs);
+
+        let logger_already_set = LogTracer::init().is_err();
+        let subscriber_already_set = bevy_utils::tracing::subscriber::set_global_default(subscriber).is_err();
+        if logger_already_set || subscriber_already_set {
+            warn!("Could not set the global logger or tracing subscriber as one is already set, consider removing other log plugins.");
+        }
+        if let Some(feature) = missing_feature {
+            warn!("The {:?} tracing backend needs the `{feature}` feature, spans are not exported", self.backend);
+        }
+    }
+
+    /// The render app only exists once the rendering plugins were built.
+    fn finish(&self, app: &mut App) {
+        let system_runs = app.world.resource::<SystemRuns>().clone();
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.insert_resource(system_runs);
+        if self.gpu_timestamps {
+            render_app.add_systems(Render, send_gpu_frame_timestamps.in_set(RenderSet::Cleanup).after(read_gpu_frame));
+            #[cfg(feature = "trace_tracy")]
+            if self.backend == TracingBackend::Tracy {
+                render_app.add_systems(Render, upload_tracy_gpu_frame.in_set(RenderSet::Cleanup).after(read_gpu_frame));
+            }
+        }
+        #[cfg(feature = "trace_tracy")]
+        if self.backend == TracingBackend::Tracy {
+            render_app.add_systems(Render, mark_tracy_frame.in_set(RenderSet::Cleanup));
+        }
+    }
+}
+
+fn send_gpu_frame_timestamps(profiler: Res<GpuProfiler>) {
+    let Some([begin, end]) = profiler.last_frame_timestamps() else {
+        return;
+    };
+    info!(
+        target: GPU_FRAME_TARGET,
+        gpu_begin_ns = begin,
+        gpu_end_ns = end,
+        gpu_time_us = end.saturating_sub(begin) / 1000,
+    );
+}
+
+/// Uploads the frame timestamps as a zone of a Tracy GPU context.
+///
+/// The context is aligned on the first frame read, its zones lag behind the CPU ones by the frame latency.
+#[cfg(feature = "trace_tracy")]
+fn upload_tracy_gpu_frame(profiler: Res<GpuProfiler>, mut context: bevy_ecs::prelude::Local<Option<tracy_client::GpuContext>>) {
+    let Some([begin, end]) = profiler.last_frame_timestamps() else {
+        return;
+    };
+    if context.is_none() {
+        let Some(client) = tracy_client::Client::running() else {
+            return;
+        };
+        // timestamps are already in nanoseconds
+        *context = client
+            .new_gpu_context(Some("frame"), tracy_client::GpuContextType::Vulkan, begin as i64, 1.0)
+            .ok();
+    }
+    let Some(context) = context.as_ref() else {
+        return;
+    };
+    if let Ok(mut span) = context.span_alloc("gpu_frame", "render_system", file!(), line!()) {
+        span.end_zone();
+        span.upload_timestamp(begin as i64, end as i64);
+    }
+}
+
+#[cfg(feature = "trace_tracy")]
+fn mark_tracy_frame() {
+    bevy_utils::tracing::event!(Level::INFO, message = "finished frame", tracy.frame_mark = true);
+}
@@ -75,6 +75,19 @@ impl Fence {
         Ok(())
     }
 
+    /// Query the fence state without blocking.
+    pub fn is_signaled(&self) -> anyhow::Result<bool> {
+        let signaled = unsafe {
+            self.device
+                .as_ref()
+                .context("Could not query null fence.")?
+                .inner
+                .get_fence_status(self.inner)?
+        };
+
+        Ok(signaled)
+    }
+
     pub fn is_null(&self) -> bool {
         self.device.is_none()
     }
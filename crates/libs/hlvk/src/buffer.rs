@@ -1,18 +1,37 @@
 use std::fmt::{Debug, Formatter};
 use std::mem::{align_of, size_of_val};
+use std::panic::Location;
 use std::sync::{Arc, Mutex};
 use ash::vk;
 use gpu_allocator::MemoryLocation;
 use gpu_allocator::vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator};
 use anyhow::Result;
 use ash::vk::Handle;
-use crate::Device;
+use derive_builder::Builder;
+use crate::{AllocationKind, Context, Device, LiveAllocation};
+use crate::memory_report::{default_allocation_name, rename_tracked_allocation, track_allocation, untrack_allocation};
+
+/// Description of a [`Buffer`], see [`Context::create_buffer_from_info`].
+#[derive(Builder, Clone, Copy, Debug)]
+pub struct BufferCreateInfo<'a> {
+    pub usage: vk::BufferUsageFlags,
+    pub memory_location: MemoryLocation,
+    pub size: vk::DeviceSize,
+    /// Queue families sharing the buffer concurrently, exclusive if less than two are given.
+    #[builder(default)]
+    pub queue_family_indices: &'a [u32],
+    /// Name of the allocation in the leak reports, the type and caller location by default.
+    #[builder(default)]
+    pub name: Option<&'a str>,
+}
 
 pub struct Buffer {
     device: Arc<Device>,
     allocator: Arc<Mutex<Allocator>>,
     pub(crate) inner: vk::Buffer,
     allocation: Option<Allocation>,
+    /// Id of the allocation in the [`MemoryLeakReport`](crate::MemoryLeakReport)s
+    allocation_id: u64,
     pub size: vk::DeviceSize,
 }
 
@@ -23,12 +42,33 @@ impl Buffer {
         usage: vk::BufferUsageFlags,
         memory_location: MemoryLocation,
         size: vk::DeviceSize,
+        name: &str,
+    ) -> Result<Self> {
+        Self::new_shared(device, allocator, usage, memory_location, size, &[], name)
+    }
+
+    /// Create a buffer which could be accessed from several queue families without ownership transfer.
+    ///
+    /// Sharing mode falls back to exclusive if less than two queue families are given.
+    pub fn new_shared(
+        device: Arc<Device>,
+        allocator: Arc<Mutex<Allocator>>,
+        usage: vk::BufferUsageFlags,
+        memory_location: MemoryLocation,
+        size: vk::DeviceSize,
+        queue_family_indices: &[u32],
+        name: &str,
     ) -> Result<Self> {
-        let create_info = vk::BufferCreateInfo::builder().size(size).usage(usage);
+        let mut create_info = vk::BufferCreateInfo::builder().size(size).usage(usage);
+        if queue_family_indices.len() > 1 {
+            create_info = create_info
+                .sharing_mode(vk::SharingMode::CONCURRENT)
+                .queue_family_indices(queue_family_indices);
+        }
         let inner = unsafe { device.inner.create_buffer(&create_info, None)? };
         let requirements = unsafe { device.inner.get_buffer_memory_requirements(inner) };
         let allocation = allocator.lock().unwrap().allocate(&AllocationCreateDesc {
-            name: "buffer",
+            name,
             requirements,
             location: memory_location,
             linear: true,
@@ -40,12 +80,19 @@ impl Buffer {
                 .inner
                 .bind_buffer_memory(inner, allocation.memory(), allocation.offset())?
         };
+        let allocation_id = track_allocation(&allocator, LiveAllocation {
+            name: name.to_owned(),
+            kind: AllocationKind::Buffer,
+            size: allocation.size(),
+            location: memory_location,
+        });
 
         Ok(Self {
             device,
             allocator,
             inner,
             allocation: Some(allocation),
+            allocation_id,
             size
         })
     }
@@ -67,12 +114,93 @@ impl Buffer {
         Ok(())
     }
 
+    /// Copy `data` into a host visible buffer, `offset` bytes from its start.
+    pub fn copy_data_to_buffer_at<T: Copy>(&self, offset: vk::DeviceSize, data: &[T]) -> Result<()> {
+        anyhow::ensure!(
+            offset + size_of_val(data) as vk::DeviceSize <= self.size,
+            "Could not copy {} bytes at {offset} into a buffer of {} bytes", size_of_val(data), self.size,
+        );
+        let mapped_ptr = self
+            .allocation
+            .as_ref()
+            .and_then(|allocation| allocation.mapped_ptr())
+            .ok_or_else(|| anyhow::anyhow!("Could not write a buffer which isn't host visible."))?;
+        unsafe {
+            let data_ptr = mapped_ptr.as_ptr().cast::<u8>().add(offset as usize);
+            std::ptr::copy_nonoverlapping(data.as_ptr().cast::<u8>(), data_ptr, size_of_val(data));
+        };
+
+        Ok(())
+    }
+
+    /// Copy the content of a host visible buffer, the writes of the device must have completed.
+    pub fn read_data_from_buffer(&self) -> Result<Vec<u8>> {
+        let data = self
+            .allocation
+            .as_ref()
+            .and_then(|allocation| allocation.mapped_slice())
+            .ok_or_else(|| anyhow::anyhow!("Could not read a buffer which isn't host visible."))?;
+
+        Ok(data[..self.size as usize].to_vec())
+    }
+
+    /// Name the allocation in the leak reports of the allocator and the [`Context`].
+    pub fn with_name(mut self, name: &str) -> Self {
+        if let Some(allocation) = self.allocation.as_mut() {
+            // only fails for allocations already freed
+            let _ = self.allocator.lock().unwrap().rename_allocation(allocation, name);
+        }
+        rename_tracked_allocation(self.allocation_id, name);
+        self
+    }
+
     pub fn get_device_address(&self) -> u64 {
         let addr_info = vk::BufferDeviceAddressInfo::builder().buffer(self.inner);
         unsafe { self.device.inner.get_buffer_device_address(&addr_info) }
     }
 }
 
+impl Context {
+    #[track_caller]
+    pub fn create_buffer_from_info(&self, create_info: &BufferCreateInfo) -> Result<Buffer> {
+        let default_name;
+        let name = match create_info.name {
+            Some(name) => name,
+            None => {
+                default_name = default_allocation_name::<Buffer>(Location::caller());
+                &default_name
+            }
+        };
+        Buffer::new_shared(
+            self.device.clone(),
+            self.allocator.clone(),
+            create_info.usage,
+            create_info.memory_location,
+            create_info.size,
+            create_info.queue_family_indices,
+            name,
+        )
+    }
+
+    /// The allocation is named after the caller location, see [`Buffer::with_name`].
+    #[track_caller]
+    pub fn create_buffer(
+        &self,
+        usage: vk::BufferUsageFlags,
+        memory_location: MemoryLocation,
+        size: vk::DeviceSize,
+    ) -> Result<Buffer> {
+        Buffer::new(
+            self.device.clone(),
+            self.allocator.clone(),
+            usage,
+            memory_location,
+            size,
+            &default_allocation_name::<Buffer>(Location::caller()),
+        )
+    }
+}
+
 impl Debug for Buffer {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Buffer {} (size: {})", self.inner.as_raw(), self.size)
@@ -82,6 +210,7 @@ impl Debug for Buffer {
 impl Drop for Buffer {
     fn drop(&mut self) {
         unsafe { self.device.inner.destroy_buffer(self.inner, None); }
+        untrack_allocation(self.allocation_id);
         self.allocator
             .lock()
             .unwrap()
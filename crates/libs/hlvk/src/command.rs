@@ -1,24 +1,26 @@
+use std::ffi::CString;
 use std::sync::Arc;
 
 use anyhow::Result;
 use ash::vk;
 
 use crate::{
-    device::Device, Buffer, Context, Image,
-    ImageView, QueueFamily,
-    TimestampQueryPool,
+    device::Device, Buffer, ComputePipeline, Context, DescriptorSet, Image,
+    ImageView, QueueFamily, RasterPipeline, RayTracingContext, RayTracingPipeline,
+    ShaderBindingTable, TimestampQueryPool,
 };
+use crate::layout::PipelineLayout;
 
 pub struct CommandPool {
     device: Arc<Device>,
-    // ray_tracing: Option<Arc<RayTracingContext>>, // TODO raytracing
+    ray_tracing: Option<Arc<RayTracingContext>>,
     pub inner: vk::CommandPool,
 }
 
 impl CommandPool {
     pub(crate) fn new(
         device: Arc<Device>,
-        // ray_tracing: Option<Arc<RayTracingContext>>, // TODO raytracing
+        ray_tracing: Option<Arc<RayTracingContext>>,
         queue_family: QueueFamily,
         flags: Option<vk::CommandPoolCreateFlags>,
     ) -> Result<Self> {
@@ -31,7 +33,7 @@ impl CommandPool {
 
         Ok(Self {
             device,
-            // ray_tracing, // TODO raytracing
+            ray_tracing,
             inner,
         })
     }
@@ -51,7 +53,7 @@ impl CommandPool {
             .into_iter()
             .map(|inner| CommandBuffer {
                 device: self.device.clone(),
-                // ray_tracing: self.ray_tracing.clone(), // TODO raytracing
+                ray_tracing: self.ray_tracing.clone(),
                 inner,
             })
             .collect();
@@ -77,6 +79,19 @@ impl CommandPool {
 
         Ok(())
     }
+
+    /// Reset every command buffer allocated from the pool to the initial state at once.
+    ///
+    /// None of them may be pending execution.
+    pub fn reset(&self) -> Result<()> {
+        unsafe {
+            self.device
+                .inner
+                .reset_command_pool(self.inner, vk::CommandPoolResetFlags::empty())?
+        };
+
+        Ok(())
+    }
 }
 
 impl Context {
@@ -87,7 +102,7 @@ impl Context {
     ) -> Result<CommandPool> {
         CommandPool::new(
             self.device.clone(),
-            // self.ray_tracing.clone(),
+            self.ray_tracing.clone(),
             queue_family,
             flags,
         )
@@ -102,10 +117,29 @@ impl Drop for CommandPool {
 
 pub struct CommandBuffer {
     device: Arc<Device>,
-    // ray_tracing: Option<Arc<RayTracingContext>>, // TODO raytracing
+    ray_tracing: Option<Arc<RayTracingContext>>,
     pub inner: vk::CommandBuffer,
 }
 
+/// A color attachment of [`CommandBuffer::begin_rendering_attachments`].
+pub struct RenderingAttachment<'a> {
+    pub view: &'a ImageView,
+    pub layout: vk::ImageLayout,
+    pub load_op: vk::AttachmentLoadOp,
+    /// Used when `load_op` is `CLEAR`
+    pub clear_color: [f32; 4],
+}
+
+/// The depth attachment of [`CommandBuffer::begin_rendering_with_depth`].
+pub struct RenderingDepthAttachment<'a> {
+    pub view: &'a ImageView,
+    pub layout: vk::ImageLayout,
+    pub load_op: vk::AttachmentLoadOp,
+    pub store_op: vk::AttachmentStoreOp,
+    /// Used when `load_op` is `CLEAR`
+    pub clear_depth: f32,
+}
+
 impl CommandBuffer {
     pub fn begin(&self, flags: Option<vk::CommandBufferUsageFlags>) -> Result<()> {
         let begin_info = vk::CommandBufferBeginInfo::builder()
@@ -135,38 +169,38 @@ impl CommandBuffer {
         Ok(())
     }
 
-    // TODO raytracing pipeline
-    // pub fn bind_rt_pipeline(&self, pipeline: &RayTracingPipeline) {
-    //     unsafe {
-    //         self.device.inner.cmd_bind_pipeline(
-    //             self.inner,
-    //             vk::PipelineBindPoint::RAY_TRACING_KHR,
-    //             pipeline.inner,
-    //         )
-    //     }
-    // }
-
-    // TODO graphics pipeline
-    // pub fn bind_graphics_pipeline(&self, pipeline: &GraphicsPipeline) {
-    //     unsafe {
-    //         self.device.inner.cmd_bind_pipeline(
-    //             self.inner,
-    //             vk::PipelineBindPoint::GRAPHICS,
-    //             pipeline.inner,
-    //         )
-    //     }
-    // }
-
-    // TODO computing pipeline
-    // pub fn bind_compute_pipeline(&self, pipeline: &ComputePipeline) {
-    //     unsafe {
-    //         self.device.inner.cmd_bind_pipeline(
-    //             self.inner,
-    //             vk::PipelineBindPoint::COMPUTE,
-    //             pipeline.inner,
-    //         )
-    //     }
-    // }
+    pub fn bind_rt_pipeline(&self, pipeline: &RayTracingPipeline) {
+        self.device.command_counters.bind_pipeline();
+        unsafe {
+            self.device.inner.cmd_bind_pipeline(
+                self.inner,
+                vk::PipelineBindPoint::RAY_TRACING_KHR,
+                pipeline.inner,
+            )
+        }
+    }
+
+    pub fn bind_raster_pipeline(&self, pipeline: &RasterPipeline) {
+        self.device.command_counters.bind_pipeline();
+        unsafe {
+            self.device.inner.cmd_bind_pipeline(
+                self.inner,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.inner,
+            )
+        }
+    }
+
+    pub fn bind_compute_pipeline(&self, pipeline: &ComputePipeline) {
+        self.device.command_counters.bind_pipeline();
+        unsafe {
+            self.device.inner.cmd_bind_pipeline(
+                self.inner,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.inner,
+            )
+        }
+    }
 
     pub fn bind_vertex_buffer(&self, vertex_buffer: &Buffer) {
         unsafe {
@@ -176,7 +210,19 @@ impl CommandBuffer {
         };
     }
 
+    /// Bind `vertex_buffers` to consecutive bindings from `first_binding`.
+    pub fn bind_vertex_buffers(&self, first_binding: u32, vertex_buffers: &[&Buffer]) {
+        let buffers = vertex_buffers.iter().map(|buffer| buffer.inner).collect::<Vec<_>>();
+        let offsets = vec![0; buffers.len()];
+        unsafe {
+            self.device
+                .inner
+                .cmd_bind_vertex_buffers(self.inner, first_binding, &buffers, &offsets)
+        };
+    }
+
     pub fn draw(&self, vertex_count: u32) {
+        self.device.command_counters.draw(vertex_count, 1);
         unsafe {
             self.device
                 .inner
@@ -184,7 +230,79 @@ impl CommandBuffer {
         };
     }
 
+    pub fn draw_instanced(&self, vertex_count: u32, instance_count: u32, first_instance: u32) {
+        self.device.command_counters.draw(vertex_count, instance_count);
+        unsafe {
+            self.device
+                .inner
+                .cmd_draw(self.inner, vertex_count, instance_count, 0, first_instance)
+        };
+    }
+
+    /// Draw with the `VkDrawIndirectCommand`s stored in `buffer` from `offset`.
+    pub fn draw_indirect(&self, buffer: &Buffer, offset: vk::DeviceSize, draw_count: u32) {
+        self.device.command_counters.draw_indirect(draw_count);
+        unsafe {
+            self.device.inner.cmd_draw_indirect(
+                self.inner,
+                buffer.inner,
+                offset,
+                draw_count,
+                std::mem::size_of::<vk::DrawIndirectCommand>() as _,
+            )
+        };
+    }
+
+    /// Draw with the `VkDrawIndexedIndirectCommand`s stored in `buffer` from `offset`.
+    pub fn draw_indexed_indirect(&self, buffer: &Buffer, offset: vk::DeviceSize, draw_count: u32) {
+        self.device.command_counters.draw_indirect(draw_count);
+        unsafe {
+            self.device.inner.cmd_draw_indexed_indirect(
+                self.inner,
+                buffer.inner,
+                offset,
+                draw_count,
+                std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as _,
+            )
+        };
+    }
+
+    /// Fill `size` bytes of `buffer` from `offset` with the repeated `data` word.
+    pub fn fill_buffer(&self, buffer: &Buffer, offset: vk::DeviceSize, size: vk::DeviceSize, data: u32) {
+        unsafe {
+            self.device
+                .inner
+                .cmd_fill_buffer(self.inner, buffer.inner, offset, size, data)
+        };
+    }
+
+    pub fn bind_index_buffer(&self, index_buffer: &Buffer, index_type: vk::IndexType) {
+        unsafe {
+            self.device
+                .inner
+                .cmd_bind_index_buffer(self.inner, index_buffer.inner, 0, index_type)
+        };
+    }
+
+    pub fn draw_indexed(&self, index_count: u32) {
+        self.device.command_counters.draw(index_count, 1);
+        unsafe {
+            self.device
+                .inner
+                .cmd_draw_indexed(self.inner, index_count, 1, 0, 0, 0)
+        };
+    }
+
+    pub fn push_constants(&self, layout: &PipelineLayout, stages: vk::ShaderStageFlags, offset: u32, constants: &[u8]) {
+        unsafe {
+            self.device
+                .inner
+                .cmd_push_constants(self.inner, layout.inner, stages, offset, constants)
+        };
+    }
+
     pub fn dispatch(&self, group_count_x: u32, group_count_y: u32, group_count_z: u32) {
+        self.device.command_counters.dispatch();
         unsafe {
             self.device
                 .inner
@@ -192,28 +310,29 @@ impl CommandBuffer {
         }
     }
 
-    // TODO rendering pipeline
-    // pub fn bind_descriptor_sets(
-    //     &self,
-    //     bind_point: vk::PipelineBindPoint,
-    //     layout: &PipelineLayout,
-    //     first_set: u32,
-    //     sets: &[&DescriptorSet],
-    // ) {
-    //     let sets = sets.iter().map(|s| s.inner).collect::<Vec<_>>();
-    //     unsafe {
-    //         self.device.inner.cmd_bind_descriptor_sets(
-    //             self.inner,
-    //             bind_point,
-    //             layout.inner,
-    //             first_set,
-    //             &sets,
-    //             &[],
-    //         )
-    //     }
-    // }
+    pub fn bind_descriptor_sets(
+        &self,
+        bind_point: vk::PipelineBindPoint,
+        layout: &PipelineLayout,
+        first_set: u32,
+        sets: &[&DescriptorSet],
+    ) {
+        self.device.command_counters.bind_descriptor_sets(sets.len());
+        let sets = sets.iter().map(|s| s.inner).collect::<Vec<_>>();
+        unsafe {
+            self.device.inner.cmd_bind_descriptor_sets(
+                self.inner,
+                bind_point,
+                layout.inner,
+                first_set,
+                &sets,
+                &[],
+            )
+        }
+    }
 
     pub fn pipeline_buffer_barriers(&self, barriers: &[BufferBarrier]) {
+        self.device.command_counters.barriers(barriers.len());
         let barriers = barriers
             .iter()
             .map(|b| {
@@ -251,6 +370,7 @@ impl CommandBuffer {
     }
 
     pub fn pipeline_image_barriers(&self, barriers: &[ImageBarrier]) {
+        self.device.command_counters.barriers(barriers.len());
         let barriers = barriers
             .iter()
             .map(|b| {
@@ -263,11 +383,11 @@ impl CommandBuffer {
                     .new_layout(b.new_layout)
                     .image(b.image.inner)
                     .subresource_range(vk::ImageSubresourceRange {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        aspect_mask: b.image.aspect_mask(),
                         base_mip_level: 0,
-                        level_count: 1,
+                        level_count: vk::REMAINING_MIP_LEVELS,
                         base_array_layer: 0,
-                        layer_count: 1,
+                        layer_count: vk::REMAINING_ARRAY_LAYERS,
                     })
                     .build()
             })
@@ -320,13 +440,157 @@ impl CommandBuffer {
         };
     }
 
+    /// Clear the first mip level and layer of a color `image` in `layout`, `GENERAL` or `TRANSFER_DST_OPTIMAL`.
+    pub fn clear_color_image(&self, image: &Image, layout: vk::ImageLayout, color: [f32; 4]) {
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let color = vk::ClearColorValue { float32: color };
+
+        unsafe {
+            self.device.inner.cmd_clear_color_image(
+                self.inner,
+                image.inner,
+                layout,
+                &color,
+                std::slice::from_ref(&range),
+            )
+        };
+    }
+
+    /// Scale the whole `src_image` into the whole `dst_image`.
+    pub fn blit_image(
+        &self,
+        src_image: &Image,
+        src_layout: vk::ImageLayout,
+        dst_image: &Image,
+        dst_layout: vk::ImageLayout,
+        filter: vk::Filter,
+    ) {
+        let dst_rect = vk::Rect2D {
+            offset: vk::Offset2D::default(),
+            extent: vk::Extent2D {
+                width: dst_image.extent.width,
+                height: dst_image.extent.height,
+            },
+        };
+        self.blit_image_to_rect(src_image, src_layout, dst_image, dst_layout, dst_rect, filter);
+    }
+
+    /// Scale the whole `src_image` into `dst_rect` of `dst_image`, leaving the rest of `dst_image` untouched.
+    pub fn blit_image_to_rect(
+        &self,
+        src_image: &Image,
+        src_layout: vk::ImageLayout,
+        dst_image: &Image,
+        dst_layout: vk::ImageLayout,
+        dst_rect: vk::Rect2D,
+        filter: vk::Filter,
+    ) {
+        let subresource = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_array_layer: 0,
+            mip_level: 0,
+            layer_count: 1,
+        };
+        let region = vk::ImageBlit::builder()
+            .src_subresource(subresource)
+            .src_offsets([
+                vk::Offset3D::default(),
+                vk::Offset3D {
+                    x: src_image.extent.width as i32,
+                    y: src_image.extent.height as i32,
+                    z: 1,
+                },
+            ])
+            .dst_subresource(subresource)
+            .dst_offsets([
+                vk::Offset3D {
+                    x: dst_rect.offset.x,
+                    y: dst_rect.offset.y,
+                    z: 0,
+                },
+                vk::Offset3D {
+                    x: dst_rect.offset.x + dst_rect.extent.width as i32,
+                    y: dst_rect.offset.y + dst_rect.extent.height as i32,
+                    z: 1,
+                },
+            ]);
+
+        unsafe {
+            self.device.inner.cmd_blit_image(
+                self.inner,
+                src_image.inner,
+                src_layout,
+                dst_image.inner,
+                dst_layout,
+                std::slice::from_ref(&region),
+                filter,
+            )
+        };
+    }
+
+    /// Scale the first `layer_count` layers of `src_image` into the same layers of `dst_image`.
+    pub fn blit_image_layers(
+        &self,
+        src_image: &Image,
+        src_layout: vk::ImageLayout,
+        dst_image: &Image,
+        dst_layout: vk::ImageLayout,
+        layer_count: u32,
+        filter: vk::Filter,
+    ) {
+        let subresource = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_array_layer: 0,
+            mip_level: 0,
+            layer_count,
+        };
+        let region = vk::ImageBlit::builder()
+            .src_subresource(subresource)
+            .src_offsets([
+                vk::Offset3D::default(),
+                vk::Offset3D {
+                    x: src_image.extent.width as i32,
+                    y: src_image.extent.height as i32,
+                    z: 1,
+                },
+            ])
+            .dst_subresource(subresource)
+            .dst_offsets([
+                vk::Offset3D::default(),
+                vk::Offset3D {
+                    x: dst_image.extent.width as i32,
+                    y: dst_image.extent.height as i32,
+                    z: 1,
+                },
+            ]);
+
+        unsafe {
+            self.device.inner.cmd_blit_image(
+                self.inner,
+                src_image.inner,
+                src_layout,
+                dst_image.inner,
+                dst_layout,
+                std::slice::from_ref(&region),
+                filter,
+            )
+        };
+    }
+
+    /// Copy tightly packed texels into every layer of `dst`, one layer after the other.
     pub fn copy_buffer_to_image(&self, src: &Buffer, dst: &Image, layout: vk::ImageLayout) {
         let region = vk::BufferImageCopy::builder()
             .image_subresource(vk::ImageSubresourceLayers {
                 aspect_mask: vk::ImageAspectFlags::COLOR,
                 mip_level: 0,
                 base_array_layer: 0,
-                layer_count: 1,
+                layer_count: dst.array_layers,
             })
             .image_extent(dst.extent);
 
@@ -341,47 +605,124 @@ impl CommandBuffer {
         };
     }
 
-    // TODO raytracing
-    // pub fn build_acceleration_structures(
-    //     &self,
-    //     as_build_geo_info: &vk::AccelerationStructureBuildGeometryInfoKHR,
-    //     as_build_range_info: &[vk::AccelerationStructureBuildRangeInfoKHR],
-    // ) {
-    //     let ray_tracing = self.ray_tracing.as_ref().expect(
-    //         "Cannot call CommandBuffer::build_acceleration_structures when ray tracing is not enabled",
-    //     );
-    //
-    //     unsafe {
-    //         ray_tracing
-    //             .acceleration_structure_fn
-    //             .cmd_build_acceleration_structures(
-    //                 self.inner,
-    //                 std::slice::from_ref(as_build_geo_info),
-    //                 std::slice::from_ref(&as_build_range_info),
-    //             )
-    //     };
-    // }
-
-    // TODO raytracing
-    // pub fn trace_rays(&self, shader_binding_table: &ShaderBindingTable, width: u32, height: u32) {
-    //     let ray_tracing = self
-    //         .ray_tracing
-    //         .as_ref()
-    //         .expect("Cannot call CommandBuffer::trace_rays when ray tracing is not enabled");
-    //
-    //     unsafe {
-    //         ray_tracing.pipeline_fn.cmd_trace_rays(
-    //             self.inner,
-    //             &shader_binding_table.raygen_region,
-    //             &shader_binding_table.miss_region,
-    //             &shader_binding_table.hit_region,
-    //             &vk::StridedDeviceAddressRegionKHR::builder(),
-    //             width,
-    //             height,
-    //             1,
-    //         )
-    //     };
-    // }
+    /// Copy tightly packed texels at `src_offset` into a region of the first mip level and layer of `dst`.
+    pub fn copy_buffer_to_image_region(
+        &self,
+        src: &Buffer,
+        src_offset: vk::DeviceSize,
+        dst: &Image,
+        layout: vk::ImageLayout,
+        offset: vk::Offset3D,
+        extent: vk::Extent3D,
+    ) {
+        let region = vk::BufferImageCopy::builder()
+            .buffer_offset(src_offset)
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_offset(offset)
+            .image_extent(extent);
+
+        unsafe {
+            self.device.inner.cmd_copy_buffer_to_image(
+                self.inner,
+                src.inner,
+                dst.inner,
+                layout,
+                std::slice::from_ref(&region),
+            );
+        };
+    }
+
+    /// Copy the first mip level of every layer of `src` into `dst`, tightly packed one layer after the other.
+    pub fn copy_image_to_buffer(&self, src: &Image, layout: vk::ImageLayout, dst: &Buffer) {
+        let region = vk::BufferImageCopy::builder()
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: src.aspect_mask(),
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: src.array_layers,
+            })
+            .image_extent(src.extent);
+
+        unsafe {
+            self.device.inner.cmd_copy_image_to_buffer(
+                self.inner,
+                src.inner,
+                layout,
+                dst.inner,
+                std::slice::from_ref(&region),
+            );
+        };
+    }
+
+    /// Record builds of several acceleration structures,
+    /// `as_build_range_infos[i]` describes the geometries of `as_build_geo_infos[i]`.
+    pub fn build_acceleration_structures(
+        &self,
+        as_build_geo_infos: &[vk::AccelerationStructureBuildGeometryInfoKHR],
+        as_build_range_infos: &[&[vk::AccelerationStructureBuildRangeInfoKHR]],
+    ) {
+        let ray_tracing = self.ray_tracing.as_ref().expect(
+            "Cannot call CommandBuffer::build_acceleration_structures when ray tracing is not enabled",
+        );
+
+        unsafe {
+            ray_tracing
+                .acceleration_structure_fn
+                .cmd_build_acceleration_structures(
+                    self.inner,
+                    as_build_geo_infos,
+                    as_build_range_infos,
+                )
+        };
+    }
+
+    /// Make finished acceleration structure builds visible to following builds and ray tracing shaders.
+    pub fn acceleration_structure_build_barrier(&self) {
+        self.device.command_counters.barriers(1);
+        let barrier = vk::MemoryBarrier2::builder()
+            .src_stage_mask(vk::PipelineStageFlags2::ACCELERATION_STRUCTURE_BUILD_KHR)
+            .src_access_mask(vk::AccessFlags2::ACCELERATION_STRUCTURE_WRITE_KHR)
+            .dst_stage_mask(
+                vk::PipelineStageFlags2::ACCELERATION_STRUCTURE_BUILD_KHR
+                    | vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
+            )
+            .dst_access_mask(vk::AccessFlags2::ACCELERATION_STRUCTURE_READ_KHR);
+
+        let dependency_info = vk::DependencyInfo::builder()
+            .memory_barriers(std::slice::from_ref(&barrier));
+
+        unsafe {
+            self.device
+                .inner
+                .cmd_pipeline_barrier2(self.inner, &dependency_info)
+        };
+    }
+
+    pub fn trace_rays(&self, shader_binding_table: &ShaderBindingTable, width: u32, height: u32) {
+        self.device.command_counters.trace_rays();
+        let ray_tracing = self
+            .ray_tracing
+            .as_ref()
+            .expect("Cannot call CommandBuffer::trace_rays when ray tracing is not enabled");
+
+        unsafe {
+            ray_tracing.pipeline_fn.cmd_trace_rays(
+                self.inner,
+                &shader_binding_table.raygen_region,
+                &shader_binding_table.miss_region,
+                &shader_binding_table.hit_region,
+                &vk::StridedDeviceAddressRegionKHR::builder(),
+                width,
+                height,
+                1,
+            )
+        };
+    }
 
     pub fn begin_rendering(
         &self,
@@ -390,16 +731,56 @@ impl CommandBuffer {
         load_op: vk::AttachmentLoadOp,
         clear_color: Option<[f32; 4]>,
     ) {
-        let color_attachment_info = vk::RenderingAttachmentInfo::builder()
-            .image_view(image_view.inner)
-            .image_layout(vk::ImageLayout::ATTACHMENT_OPTIMAL)
-            .load_op(load_op)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .clear_value(vk::ClearValue {
-                color: vk::ClearColorValue {
-                    float32: clear_color.unwrap_or([1.0; 4]),
-                },
-            });
+        self.begin_rendering_attachments(
+            &[RenderingAttachment {
+                view: image_view,
+                layout: vk::ImageLayout::ATTACHMENT_OPTIMAL,
+                load_op,
+                clear_color: clear_color.unwrap_or([1.0; 4]),
+            }],
+            extent,
+        );
+    }
+
+    /// Begin dynamic rendering into several color attachments, bound in order.
+    pub fn begin_rendering_attachments(&self, attachments: &[RenderingAttachment], extent: vk::Extent2D) {
+        self.begin_rendering_with_depth(attachments, None, extent);
+    }
+
+    /// Begin dynamic rendering into color attachments and optionally a depth attachment.
+    pub fn begin_rendering_with_depth(
+        &self,
+        attachments: &[RenderingAttachment],
+        depth_attachment: Option<&RenderingDepthAttachment>,
+        extent: vk::Extent2D,
+    ) {
+        self.begin_rendering_multiview(attachments, depth_attachment, extent, 0);
+    }
+
+    /// Begin dynamic rendering broadcast to the layers of the attachments set in `view_mask`,
+    /// the layer is `gl_ViewIndex` in the shaders. Requires [`DeviceFeatures::multiview`](crate::DeviceFeatures::multiview)
+    /// unless `view_mask` is 0, which renders into the first layer only.
+    pub fn begin_rendering_multiview(
+        &self,
+        attachments: &[RenderingAttachment],
+        depth_attachment: Option<&RenderingDepthAttachment>,
+        extent: vk::Extent2D,
+        view_mask: u32,
+    ) {
+        let color_attachment_infos = attachments
+            .iter()
+            .map(|attachment| vk::RenderingAttachmentInfo::builder()
+                .image_view(attachment.view.inner)
+                .image_layout(attachment.layout)
+                .load_op(attachment.load_op)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .clear_value(vk::ClearValue {
+                    color: vk::ClearColorValue {
+                        float32: attachment.clear_color,
+                    },
+                })
+                .build())
+            .collect::<Vec<_>>();
 
         let rendering_info = vk::RenderingInfo::builder()
             .render_area(vk::Rect2D {
@@ -407,7 +788,24 @@ impl CommandBuffer {
                 extent,
             })
             .layer_count(1)
-            .color_attachments(std::slice::from_ref(&color_attachment_info));
+            .view_mask(view_mask)
+            .color_attachments(&color_attachment_infos);
+        let depth_attachment_info = depth_attachment.map(|attachment| vk::RenderingAttachmentInfo::builder()
+            .image_view(attachment.view.inner)
+            .image_layout(attachment.layout)
+            .load_op(attachment.load_op)
+            .store_op(attachment.store_op)
+            .clear_value(vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: attachment.clear_depth,
+                    stencil: 0,
+                },
+            })
+            .build());
+        let rendering_info = match &depth_attachment_info {
+            Some(depth_attachment_info) => rendering_info.depth_attachment(depth_attachment_info),
+            None => rendering_info,
+        };
 
         unsafe {
             self.device
@@ -448,6 +846,32 @@ impl CommandBuffer {
         };
     }
 
+    /// Open a label named `name` around the next commands in captures, closed with [`CommandBuffer::end_label`].
+    ///
+    /// Does nothing without debug utils.
+    pub fn begin_label(&self, name: &str, color: [f32; 4]) {
+        let Some(debug_utils) = self.device.debug_utils() else {
+            return;
+        };
+        let Ok(name) = CString::new(name) else {
+            return;
+        };
+        let label = vk::DebugUtilsLabelEXT::builder().label_name(&name).color(color);
+        unsafe { debug_utils.cmd_begin_debug_utils_label(self.inner, &label) };
+    }
+
+    pub fn end_label(&self) {
+        if let Some(debug_utils) = self.device.debug_utils() {
+            unsafe { debug_utils.cmd_end_debug_utils_label(self.inner) };
+        }
+    }
+
+    /// A label closed once the returned scope is dropped, see [`CommandBuffer::begin_label`].
+    pub fn scoped_label(&self, name: &str, color: [f32; 4]) -> LabelScope<'_> {
+        self.begin_label(name, color);
+        LabelScope { command_buffer: self }
+    }
+
     pub fn reset_all_timestamp_queries_from_pool<const C: usize>(
         &self,
         pool: &TimestampQueryPool<C>,
@@ -475,6 +899,17 @@ impl CommandBuffer {
     }
 }
 
+/// Closes a label of its command buffer when dropped.
+pub struct LabelScope<'a> {
+    command_buffer: &'a CommandBuffer,
+}
+
+impl Drop for LabelScope<'_> {
+    fn drop(&mut self) {
+        self.command_buffer.end_label();
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct BufferBarrier<'a> {
     pub buffer: &'a Buffer,
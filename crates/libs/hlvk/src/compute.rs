@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use ash::vk;
+use anyhow::{ensure, Result};
+use crate::{Context, Device, StagedShader};
+use crate::layout::PipelineLayout;
+
+pub struct ComputePipeline {
+    device: Arc<Device>,
+    pub inner: vk::Pipeline,
+}
+
+impl ComputePipeline {
+    pub fn new(device: Arc<Device>, layout: &PipelineLayout, shader: &StagedShader) -> Result<Self> {
+        ensure!(shader.stage == vk::ShaderStageFlags::COMPUTE, "{:?} shader used for a compute pipeline", shader.stage);
+
+        let stage_info = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(shader.stage)
+            .module(shader.module.inner)
+            .name(&shader.entry_point_name)
+            .build();
+        let pipeline_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(stage_info)
+            .layout(layout.inner);
+
+        let inner = unsafe {
+            device
+                .inner
+                .create_compute_pipelines(
+                    vk::PipelineCache::null(),
+                    std::slice::from_ref(&pipeline_info),
+                    None,
+                )
+                .map_err(|e| e.1)?[0]
+        };
+
+        Ok(Self { device, inner })
+    }
+}
+
+impl Context {
+    pub fn create_compute_pipeline(&self, layout: &PipelineLayout, shader: &StagedShader) -> Result<ComputePipeline> {
+        ComputePipeline::new(self.device.clone(), layout, shader)
+    }
+}
+
+impl Drop for ComputePipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.inner.destroy_pipeline(self.inner, None)
+        };
+    }
+}
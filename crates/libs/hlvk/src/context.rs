@@ -2,36 +2,77 @@ use std::sync::{Arc, Mutex};
 use ash::{Entry, vk};
 use gpu_allocator::AllocatorDebugSettings;
 use gpu_allocator::vulkan::{Allocator, AllocatorCreateDesc};
-use log::info;
+use log::{error, info};
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
-use avalanche_utils::{Version, VERSION_1_0};
-use crate::{CommandPool, Device, DeviceFeatures, Instance, PhysicalDevice, Queue, QueueFamily, Surface};
+use avalanche_utils::{Version, VERSION_1_0, VERSION_1_3};
+use crate::{CommandPool, Device, DeviceFeatures, Instance, MemoryLeakReport, PhysicalDevice, Queue, QueueFamily, RayTracingContext, Surface};
+use crate::memory_report::{live_allocation_size, live_allocations};
 
 pub struct Context {
     pub allocator: Arc<Mutex<Allocator>>,
-    pub instance: Instance,
+    pub instance: Arc<Instance>,
     pub physical_device: PhysicalDevice,
     pub device: Arc<Device>,
     pub graphics_queue: Queue,
     pub graphics_queue_family: QueueFamily,
     pub present_queue: Queue,
     pub present_queue_family: QueueFamily,
+    /// Queue for async compute work.
+    /// It is the graphics queue if the device has no dedicated compute family.
+    pub compute_queue: Queue,
+    pub compute_queue_family: QueueFamily,
     /// main surface, other surface is keeping by [avalanche-window] crate
-    pub surface: Arc<Surface>,
+    ///
+    /// `None` for a context built with [`ContextBuilder::headless`].
+    pub surface: Option<Arc<Surface>>,
     pub command_pool: CommandPool,
-    // TODO raytracing
-    _entry: Entry,
+    pub ray_tracing: Option<Arc<RayTracingContext>>,
+    /// Features enabled on [`Context::device`].
+    pub device_features: DeviceFeatures,
+    entry: Entry,
 }
 
+/// Lets a runtime create the Vulkan objects of a [`Context`] itself, e.g. OpenXR which enables the extensions it needs.
+///
+/// The create infos are the ones the context would use, the returned objects are destroyed by the context.
+pub trait ContextCreateHooks {
+    fn create_instance(&self, entry: &Entry, create_info: &vk::InstanceCreateInfo) -> anyhow::Result<ash::Instance> {
+        Ok(unsafe { entry.create_instance(create_info, None)? })
+    }
+
+    /// The physical device the runtime requires, `None` selects the most suitable one.
+    fn physical_device(&self, _instance: &ash::Instance) -> anyhow::Result<Option<vk::PhysicalDevice>> {
+        Ok(None)
+    }
+
+    fn create_device(
+        &self,
+        _entry: &Entry,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        create_info: &vk::DeviceCreateInfo,
+    ) -> anyhow::Result<ash::Device> {
+        Ok(unsafe { instance.create_device(physical_device, create_info, None)? })
+    }
+}
+
+/// Creates the objects with the Vulkan functions.
+struct DefaultContextCreateHooks;
+
+impl ContextCreateHooks for DefaultContextCreateHooks {}
+
 pub struct ContextBuilder<'a> {
-    window_handle: &'a dyn HasWindowHandle,
-    display_handle: &'a dyn HasDisplayHandle,
+    /// Window of the main surface, `None` for a headless context
+    window: Option<(&'a dyn HasWindowHandle, &'a dyn HasDisplayHandle)>,
     vulkan_version: Version,
     app_name: &'a str,
     required_device_extensions: &'a [&'a str],
     required_device_features: DeviceFeatures,
+    optional_device_features: DeviceFeatures,
     /// Should we create raytracing context
     with_raytracing_context: bool,
+    create_hooks: Option<&'a dyn ContextCreateHooks>,
+    debug_printf: bool,
 }
 
 impl<'a> ContextBuilder<'a> {
@@ -40,13 +81,25 @@ impl<'a> ContextBuilder<'a> {
         display_handle: &'a dyn HasDisplayHandle,
     ) -> Self {
         Self {
-            window_handle,
-            display_handle,
+            window: Some((window_handle, display_handle)),
+            ..Self::headless()
+        }
+    }
+
+    /// Context without a main surface, e.g. to render offscreen in tests on machines without a display.
+    ///
+    /// The window system extensions aren't enabled, so no surface can be created from the context.
+    pub fn headless() -> Self {
+        Self {
+            window: None,
             vulkan_version: VERSION_1_0,
             app_name: "",
             required_device_extensions: &[],
             required_device_features: Default::default(),
+            optional_device_features: Default::default(),
             with_raytracing_context: false,
+            create_hooks: None,
+            debug_printf: false,
         }
     }
 
@@ -78,6 +131,18 @@ impl<'a> ContextBuilder<'a> {
         }
     }
 
+    /// Features enabled when the selected device supports them, they don't affect the device selection.
+    ///
+    /// Check [`Context::device_features`] before relying on one of them.
+    pub fn optional_device_features(self, optional_features: DeviceFeatures) -> Self {
+        Self {
+            optional_device_features: optional_features,
+            ..self
+        }
+    }
+
+    /// Create the [`Context::ray_tracing`] context, left `None` when the device didn't enable
+    /// the ray tracing pipeline and acceleration structure features.
     pub fn with_raytracing_context(self, with_raytracing_context: bool) -> Self {
         Self {
             with_raytracing_context,
@@ -85,6 +150,24 @@ impl<'a> ContextBuilder<'a> {
         }
     }
 
+    pub fn create_hooks(self, create_hooks: &'a dyn ContextCreateHooks) -> Self {
+        Self {
+            create_hooks: Some(create_hooks),
+            ..self
+        }
+    }
+
+    /// Log the `debugPrintfEXT` calls of shaders through the validation layers, in debug builds.
+    ///
+    /// Enables the validation layers with their debug printf feature, and `VK_KHR_shader_non_semantic_info`
+    /// before Vulkan 1.3. Ignored with a warning when the layers aren't installed.
+    pub fn debug_printf(self, debug_printf: bool) -> Self {
+        Self {
+            debug_printf,
+            ..self
+        }
+    }
+
     pub fn build(self) -> anyhow::Result<Context> {
         Context::new(self)
     }
@@ -93,47 +176,83 @@ impl<'a> ContextBuilder<'a> {
 impl Context {
     fn new(
         ContextBuilder {
-            window_handle,
-            display_handle,
+            window,
             vulkan_version,
             app_name,
             required_device_extensions,
             required_device_features,
+            optional_device_features,
             with_raytracing_context,
+            create_hooks,
+            debug_printf,
         }: ContextBuilder,
     ) -> anyhow::Result<Self> {
+        let create_hooks = create_hooks.unwrap_or(&DefaultContextCreateHooks);
         let entry = unsafe { Entry::load()? };
-        let mut instance = Instance::new(&entry, display_handle, vulkan_version, app_name)?;
+        let display_handle = window.map(|(_, display_handle)| display_handle);
+        let instance = Arc::new(Instance::new(&entry, display_handle, vulkan_version, app_name, debug_printf, create_hooks)?);
 
-        let mut surface = Surface::new(&entry, &instance, window_handle, display_handle)?;
-        surface.is_main_surface = true;
+        let surface = window
+            .map(|(window_handle, display_handle)| -> anyhow::Result<_> {
+                let mut surface = Surface::new(&entry, &instance, window_handle, display_handle)?;
+                surface.is_main_surface = true;
+                Ok(Arc::new(surface))
+            })
+            .transpose()?;
 
-        let physical_devices = instance.enumerate_physical_devices(&surface)?;
-        let (physical_device, graphics_queue_family, present_queue_family) =
+        let mut physical_devices = instance.enumerate_physical_devices(surface.as_deref())?;
+        if let Some(required) = create_hooks.physical_device(&instance.inner)? {
+            physical_devices.retain(|physical_device| physical_device.inner == required);
+        }
+        let (physical_device, graphics_queue_family, present_queue_family, compute_queue_family) =
             select_suitable_physical_device(
-                physical_devices,
+                &physical_devices,
                 required_device_extensions,
-                &required_device_features)?;
+                &required_device_features,
+                surface.is_none())?;
         info!("[Vulkan] Selected physical device: {:?}", physical_device.name);
 
-        let queue_families = [graphics_queue_family, present_queue_family];
+        let device_features = required_device_features
+            .union(&optional_device_features.intersection(&physical_device.supported_device_features));
+
+        let mut device_extensions = required_device_extensions.to_vec();
+        for extension in device_features.extensions() {
+            if !device_extensions.contains(&extension) {
+                device_extensions.push(extension);
+            }
+        }
+        // core since Vulkan 1.3
+        let non_semantic_info = "VK_KHR_shader_non_semantic_info";
+        if instance.debug_printf_enabled()
+            && vulkan_version < VERSION_1_3
+            && physical_device.supports_extensions(&[non_semantic_info])
+        {
+            device_extensions.push(non_semantic_info);
+        }
+
+        let queue_families = [graphics_queue_family, present_queue_family, compute_queue_family];
         let device = Arc::new(Device::new(
+            &entry,
             &instance,
             &physical_device,
             &queue_families,
-            required_device_extensions,
-            &required_device_features,
+            &device_extensions,
+            &device_features,
+            create_hooks,
         )?);
         let graphics_queue = device.get_queue(graphics_queue_family, 0);
         let present_queue = device.get_queue(present_queue_family, 0);
+        let compute_queue = device.get_queue(compute_queue_family, 0);
 
-        let _ray_tracing = with_raytracing_context.then(|| {
-            // TODO raytracing
+        // the ray tracing functions are only loaded with the extensions enabled
+        let ray_tracing_supported = device_features.ray_tracing_pipeline && device_features.acceleration_structure;
+        let ray_tracing = (with_raytracing_context && ray_tracing_supported).then(|| {
+            Arc::new(RayTracingContext::new(&instance, &physical_device, &device))
         });
 
         let command_pool = CommandPool::new(
             device.clone(),
-            // ray_tracing.clone(), // TODO raytracing
+            ray_tracing.clone(),
             graphics_queue_family,
             Some(vk::CommandPoolCreateFlags::TRANSIENT),
         )?;
@@ -150,7 +269,7 @@ impl Context {
                 log_stack_traces: true,
                 ..Default::default()
             },
-            buffer_device_address: required_device_features.buffer_device_address,
+            buffer_device_address: device_features.buffer_device_address,
             allocation_sizes: Default::default(),
         })?;
 
@@ -163,9 +282,13 @@ impl Context {
             graphics_queue_family,
             present_queue,
             present_queue_family,
-            surface: Arc::new(surface),
+            compute_queue,
+            compute_queue_family,
+            surface,
             command_pool,
-            _entry: entry,
+            ray_tracing,
+            device_features,
+            entry,
         })
     }
 
@@ -175,13 +298,19 @@ fn select_suitable_physical_device(
     devices: &[PhysicalDevice],
     required_extensions: &[&str],
     required_device_features: &DeviceFeatures,
-) -> anyhow::Result<(PhysicalDevice, QueueFamily, QueueFamily)> {
+    headless: bool,
+) -> anyhow::Result<(PhysicalDevice, QueueFamily, QueueFamily, QueueFamily)> {
     let mut graphics = None;
     let mut present = None;
+    let mut compute = None;
 
     let device = devices
         .iter()
         .find(|device| {
+            graphics = None;
+            present = None;
+            compute = None;
+
             for family in device.queue_families.iter().filter(|f| f.has_queues()) {
                 if family.supports_graphics()
                     && family.supports_compute()
@@ -194,25 +323,32 @@ fn select_suitable_physical_device(
                     present = Some(*family);
                 }
 
-                if graphics.is_some() && present.is_some() {
-                    break;
+                // prefer a dedicated family so async work doesn't contend with the graphics queue
+                if family.supports_compute() && !family.supports_graphics() && compute.is_none() {
+                    compute = Some(*family);
                 }
             }
 
             let extension_support = device.supports_extensions(required_extensions);
 
+            // nothing is presented without a surface
+            let presentable = headless || (
+                present.is_some()
+                    && !device.supported_surface_formats.is_empty()
+                    && !device.supported_present_modes.is_empty()
+            );
+
             graphics.is_some()
-                && present.is_some()
+                && presentable
                 && extension_support
-                && !device.supported_surface_formats.is_empty()
-                && !device.supported_present_modes.is_empty()
                 && device
                 .supported_device_features
                 .is_compatible_with(required_device_features)
         })
         .ok_or_else(|| anyhow::anyhow!("Could not find a suitable device"))?;
 
-    Ok((device.clone(), graphics.unwrap(), present.unwrap()))
+    let graphics = graphics.unwrap();
+    Ok((device.clone(), graphics, present.unwrap_or(graphics), compute.unwrap_or(graphics)))
 }
 
 impl Context {
@@ -221,4 +357,49 @@ impl Context {
 
         Ok(())
     }
+
+    /// Create a surface for another window, or for the same window once its native handle was recreated.
+    pub fn create_surface(
+        &self,
+        window_handle: &dyn HasWindowHandle,
+        display_handle: &dyn HasDisplayHandle,
+    ) -> anyhow::Result<Surface> {
+        anyhow::ensure!(self.surface.is_some(), "Surfaces can't be created from a headless context");
+        Surface::new(&self.entry, &self.instance, window_handle, display_handle)
+    }
+
+    /// Distinct queue family indices used by this context,
+    /// resources shared between queues are created concurrent on them.
+    pub fn unique_queue_family_indices(&self) -> Vec<u32> {
+        let mut indices = vec![
+            self.graphics_queue_family.index,
+            self.present_queue_family.index,
+            self.compute_queue_family.index,
+        ];
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    /// Buffers and images allocated from this context and not dropped yet, largest first.
+    ///
+    /// The device isn't used, so it is safe to call after the device was lost.
+    pub fn report_memory_leaks(&self) -> MemoryLeakReport {
+        live_allocations(&self.allocator)
+    }
+
+    /// Bytes of the buffers and images allocated from this context and not dropped yet.
+    pub fn allocated_memory(&self) -> u64 {
+        live_allocation_size(&self.allocator)
+    }
+}
+
+/// The device and instance are destroyed with their last reference,
+/// resources still alive at this point must not be in use by the GPU anymore.
+impl Drop for Context {
+    fn drop(&mut self) {
+        if let Err(err) = self.device_wait_idle() {
+            error!("[Vulkan] Failed to wait for the device to be idle: {err}");
+        }
+    }
 }
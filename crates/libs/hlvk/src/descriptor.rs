@@ -1,7 +1,7 @@
 use std::sync::Arc;
 use anyhow::Result;
 use ash::vk;
-use crate::{Buffer, Context, Device, ImageView, Sampler};
+use crate::{AccelerationStructure, Buffer, Context, Device, ImageView, Sampler};
 
 pub struct DescriptorSetLayout {
     device: Arc<Device>,
@@ -77,6 +77,17 @@ impl DescriptorPool {
     pub fn allocate_set(&self, layout: &DescriptorSetLayout) -> Result<DescriptorSet> {
         Ok(self.allocate_sets(layout, 1)?.into_iter().next().unwrap())
     }
+
+    /// Return every set allocated from the pool to it, the sets must not be used anymore.
+    pub fn reset(&self) -> Result<()> {
+        unsafe {
+            self.device
+                .inner
+                .reset_descriptor_pool(self.inner, vk::DescriptorPoolResetFlags::empty())?
+        };
+
+        Ok(())
+    }
 }
 
 impl Drop for DescriptorPool {
@@ -97,9 +108,10 @@ impl DescriptorSet {
         use WriteDescriptorSetKind::*;
 
         // these Vec are here to keep structure internal to WriteDescriptorSet (DescriptorImageInfo, DescriptorBufferInfo, ...) alive
-        let mut img_infos = vec![];
-        let mut buffer_infos = vec![];
-        // let mut as_infos = vec![];
+        // reserved up front as the writes keep pointers into them
+        let mut img_infos = Vec::with_capacity(writes.len());
+        let mut buffer_infos = Vec::with_capacity(writes.len());
+        let mut as_infos = Vec::with_capacity(writes.len());
 
         let descriptor_writes = writes
             .iter()
@@ -120,25 +132,25 @@ impl DescriptorSet {
                             .image_info(std::slice::from_ref(img_infos.last().unwrap()))
                             .build()
                     }
-                    // TODO raytracing
-                    // AccelerationStructure {
-                    //     acceleration_structure,
-                    // } => {
-                    //     let write_set_as = vk::WriteDescriptorSetAccelerationStructureKHR::builder()
-                    //         .acceleration_structures(std::slice::from_ref(
-                    //             &acceleration_structure.inner,
-                    //         ));
-                    //
-                    //     as_infos.push(write_set_as);
-                    //
-                    //     let mut write = vk::WriteDescriptorSet::builder()
-                    //         .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
-                    //         .push_next(as_infos.last_mut().unwrap())
-                    //         .build();
-                    //     write.descriptor_count = 1;
-                    //
-                    //     write
-                    // }
+                    AccelerationStructure {
+                        acceleration_structure,
+                    } => {
+                        let write_set_as = vk::WriteDescriptorSetAccelerationStructureKHR::builder()
+                            .acceleration_structures(std::slice::from_ref(
+                                &acceleration_structure.inner,
+                            ))
+                            .build();
+
+                        as_infos.push(write_set_as);
+
+                        let mut write = write_set_builder
+                            .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+                            .push_next(as_infos.last_mut().unwrap())
+                            .build();
+                        write.descriptor_count = 1;
+
+                        write
+                    }
                     UniformBuffer { buffer } => {
                         let buffer_info = vk::DescriptorBufferInfo::builder()
                             .buffer(buffer.inner)
@@ -221,10 +233,9 @@ pub enum WriteDescriptorSetKind<'a> {
         view: &'a ImageView,
         layout: vk::ImageLayout,
     },
-    // TODO raytracing
-    // AccelerationStructure {
-    //     acceleration_structure: &'a AccelerationStructure,
-    // },
+    AccelerationStructure {
+        acceleration_structure: &'a AccelerationStructure,
+    },
     UniformBuffer {
         buffer: &'a Buffer,
     },
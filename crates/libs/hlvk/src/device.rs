@@ -1,24 +1,32 @@
 use std::ffi::CString;
 use std::sync::Arc;
-use ash::{vk, Device as AshDevice};
-use crate::{Instance, PhysicalDevice, Queue, QueueFamily};
+use ash::extensions::ext::DebugUtils;
+use ash::{vk, Device as AshDevice, Entry};
+use crate::{CommandCounters, ContextCreateHooks, Instance, PhysicalDevice, Queue, QueueFamily};
 
 pub struct Device {
     pub inner: AshDevice,
+    /// Commands recorded by the command buffers of the device, see [`CommandCounters::take`]
+    pub command_counters: CommandCounters,
+    /// Destroying the instance before the device is invalid, whichever of the two is dropped last.
+    instance: Arc<Instance>,
 }
 
 impl Device {
     pub(crate) fn new(
-        instance: &Instance,
+        entry: &Entry,
+        instance: &Arc<Instance>,
         physical_device: &PhysicalDevice,
         queue_families: &[QueueFamily],
         required_extensions: &[&str],
         device_features: &DeviceFeatures,
+        create_hooks: &dyn ContextCreateHooks,
     ) -> anyhow::Result<Self> {
         let queue_priorities = [1.0f32];
 
         let queue_create_infos = {
             let mut indices = queue_families.iter().map(|f| f.index).collect::<Vec<_>>();
+            indices.sort_unstable();
             indices.dedup();
 
             indices
@@ -45,6 +53,16 @@ impl Device {
             .ray_tracing_pipeline(device_features.ray_tracing_pipeline);
         let mut acceleration_struct_feature = vk::PhysicalDeviceAccelerationStructureFeaturesKHR::builder()
             .acceleration_structure(device_features.acceleration_structure);
+        let mut position_fetch_feature = vk::PhysicalDeviceRayTracingPositionFetchFeaturesKHR::builder()
+            .ray_tracing_position_fetch(device_features.ray_tracing_position_fetch);
+        let mut swapchain_maintenance1_feature = vk::PhysicalDeviceSwapchainMaintenance1FeaturesEXT::builder()
+            .swapchain_maintenance1(device_features.swapchain_maintenance1);
+        let mut present_id_feature = vk::PhysicalDevicePresentIdFeaturesKHR::builder()
+            .present_id(device_features.present_id);
+        let mut present_wait_feature = vk::PhysicalDevicePresentWaitFeaturesKHR::builder()
+            .present_wait(device_features.present_wait);
+        let mut vulkan_11_features = vk::PhysicalDeviceVulkan11Features::builder()
+            .multiview(device_features.multiview);
         let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::builder()
             .runtime_descriptor_array(device_features.runtime_descriptor_array)
             .buffer_device_address(device_features.buffer_device_address);
@@ -53,30 +71,54 @@ impl Device {
             .synchronization2(device_features.synchronization2);
 
         let mut features = vk::PhysicalDeviceFeatures2::builder()
-            .features(vk::PhysicalDeviceFeatures::default())
+            .features(vk::PhysicalDeviceFeatures::builder()
+                .fill_mode_non_solid(device_features.fill_mode_non_solid)
+                .sparse_binding(device_features.sparse_binding)
+                .sparse_residency_image2_d(device_features.sparse_residency_image_2d)
+                .build())
             .push_next(&mut acceleration_struct_feature)
             .push_next(&mut ray_tracing_feature)
+            .push_next(&mut vulkan_11_features)
             .push_next(&mut vulkan_12_features)
             .push_next(&mut vulkan_13_features);
+        if device_features.ray_tracing_position_fetch {
+            // the feature struct is only valid in the chain if the extension is enabled
+            features = features.push_next(&mut position_fetch_feature);
+        }
+        if device_features.swapchain_maintenance1 {
+            features = features.push_next(&mut swapchain_maintenance1_feature);
+        }
+        if device_features.present_id {
+            features = features.push_next(&mut present_id_feature);
+        }
+        if device_features.present_wait {
+            features = features.push_next(&mut present_wait_feature);
+        }
 
         let device_create_info = vk::DeviceCreateInfo::builder()
             .queue_create_infos(&queue_create_infos)
             .enabled_extension_names(&device_extensions_ptrs)
             .push_next(&mut features);
 
-        let inner = unsafe {
-            instance
-                .inner
-                .create_device(physical_device.inner, &device_create_info, None)?
-        };
+        let inner = create_hooks.create_device(entry, &instance.inner, physical_device.inner, &device_create_info)?;
 
-        Ok(Self { inner })
+        Ok(Self {
+            inner,
+            command_counters: CommandCounters::default(),
+            instance: instance.clone(),
+        })
     }
 
     pub fn get_queue(self: &Arc<Self>, queue_family: QueueFamily, queue_index: u32) -> Queue {
         let inner = unsafe { self.inner.get_device_queue(queue_family.index, queue_index) };
         Queue::new(self.clone(), inner)
     }
+
+    /// Loaded in debug builds when the extension is available.
+    #[inline]
+    pub fn debug_utils(&self) -> Option<&DebugUtils> {
+        self.instance.debug_utils()
+    }
 }
 
 impl Drop for Device {
@@ -91,10 +133,34 @@ impl Drop for Device {
 pub struct DeviceFeatures {
     pub ray_tracing_pipeline: bool,
     pub acceleration_structure: bool,
+    /// Reading hit triangle positions in hit shaders (`VK_KHR_ray_tracing_position_fetch`).
+    /// Not part of [`DeviceFeatures::full`] as few drivers expose it yet.
+    pub ray_tracing_position_fetch: bool,
     pub runtime_descriptor_array: bool,
     pub buffer_device_address: bool,
     pub dynamic_rendering: bool,
     pub synchronization2: bool,
+    /// Line and point polygon modes, used to draw wireframes.
+    /// Not part of [`DeviceFeatures::full`] as wireframes are only a debug view, request it as an optional feature.
+    pub fill_mode_non_solid: bool,
+    /// Rendering into several layers at once with `gl_ViewIndex`, for stereo views.
+    /// Not part of [`DeviceFeatures::full`] as only stereo views use it, request it as an optional feature.
+    pub multiview: bool,
+    /// Fences signaled once a present completed (`VK_EXT_swapchain_maintenance1`).
+    /// Not part of [`DeviceFeatures::full`] as the swapchain alone throttles presents without it, request it as an optional feature.
+    pub swapchain_maintenance1: bool,
+    /// Identifying presents (`VK_KHR_present_id`), needed by [`DeviceFeatures::present_wait`].
+    /// Not part of [`DeviceFeatures::full`] as only waiting for the display uses it, request it as an optional feature.
+    pub present_id: bool,
+    /// Waiting for a present to be displayed (`VK_KHR_present_wait`).
+    /// Not part of [`DeviceFeatures::full`] as only waiting for the display uses it, request it as an optional feature.
+    pub present_wait: bool,
+    /// Binding the memory of resources after their creation, needed by the sparse features.
+    /// Not part of [`DeviceFeatures::full`] as virtual textures fall back to a page atlas without it.
+    pub sparse_binding: bool,
+    /// Partially resident 2D images, whose pages are bound with `vkQueueBindSparse`.
+    /// Not part of [`DeviceFeatures::full`] as virtual textures fall back to a page atlas without it.
+    pub sparse_residency_image_2d: bool,
 }
 
 impl DeviceFeatures {
@@ -102,19 +168,112 @@ impl DeviceFeatures {
         Self {
             ray_tracing_pipeline: true,
             acceleration_structure: true,
+            ray_tracing_position_fetch: false,
             runtime_descriptor_array: true,
             buffer_device_address: true,
             dynamic_rendering: true,
             synchronization2: true,
+            fill_mode_non_solid: false,
+            multiview: false,
+            swapchain_maintenance1: false,
+            present_id: false,
+            present_wait: false,
+            sparse_binding: false,
+            sparse_residency_image_2d: false,
+        }
+    }
+
+    /// Device extensions the enabled features need, the context enables them along with the features.
+    pub fn extensions(&self) -> Vec<&'static str> {
+        [
+            (self.ray_tracing_pipeline, "VK_KHR_ray_tracing_pipeline"),
+            (self.acceleration_structure, "VK_KHR_acceleration_structure"),
+            (self.acceleration_structure, "VK_KHR_deferred_host_operations"),
+            (self.ray_tracing_position_fetch, "VK_KHR_ray_tracing_position_fetch"),
+            (self.swapchain_maintenance1, "VK_EXT_swapchain_maintenance1"),
+            (self.present_id, "VK_KHR_present_id"),
+            (self.present_wait, "VK_KHR_present_wait"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, extension)| enabled.then_some(extension))
+        .collect()
+    }
+
+    /// Features enabled in both `self` and `other`.
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self {
+            ray_tracing_pipeline: self.ray_tracing_pipeline && other.ray_tracing_pipeline,
+            acceleration_structure: self.acceleration_structure && other.acceleration_structure,
+            ray_tracing_position_fetch: self.ray_tracing_position_fetch && other.ray_tracing_position_fetch,
+            runtime_descriptor_array: self.runtime_descriptor_array && other.runtime_descriptor_array,
+            buffer_device_address: self.buffer_device_address && other.buffer_device_address,
+            dynamic_rendering: self.dynamic_rendering && other.dynamic_rendering,
+            synchronization2: self.synchronization2 && other.synchronization2,
+            fill_mode_non_solid: self.fill_mode_non_solid && other.fill_mode_non_solid,
+            multiview: self.multiview && other.multiview,
+            swapchain_maintenance1: self.swapchain_maintenance1 && other.swapchain_maintenance1,
+            present_id: self.present_id && other.present_id,
+            present_wait: self.present_wait && other.present_wait,
+            sparse_binding: self.sparse_binding && other.sparse_binding,
+            sparse_residency_image_2d: self.sparse_residency_image_2d && other.sparse_residency_image_2d,
+        }
+    }
+
+    /// Features enabled in `self` or `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            ray_tracing_pipeline: self.ray_tracing_pipeline || other.ray_tracing_pipeline,
+            acceleration_structure: self.acceleration_structure || other.acceleration_structure,
+            ray_tracing_position_fetch: self.ray_tracing_position_fetch || other.ray_tracing_position_fetch,
+            runtime_descriptor_array: self.runtime_descriptor_array || other.runtime_descriptor_array,
+            buffer_device_address: self.buffer_device_address || other.buffer_device_address,
+            dynamic_rendering: self.dynamic_rendering || other.dynamic_rendering,
+            synchronization2: self.synchronization2 || other.synchronization2,
+            fill_mode_non_solid: self.fill_mode_non_solid || other.fill_mode_non_solid,
+            multiview: self.multiview || other.multiview,
+            swapchain_maintenance1: self.swapchain_maintenance1 || other.swapchain_maintenance1,
+            present_id: self.present_id || other.present_id,
+            present_wait: self.present_wait || other.present_wait,
+            sparse_binding: self.sparse_binding || other.sparse_binding,
+            sparse_residency_image_2d: self.sparse_residency_image_2d || other.sparse_residency_image_2d,
         }
     }
 
     pub fn is_compatible_with(&self, requirements: &Self) -> bool {
         (!requirements.ray_tracing_pipeline || self.ray_tracing_pipeline)
             && (!requirements.acceleration_structure || self.acceleration_structure)
+            && (!requirements.ray_tracing_position_fetch || self.ray_tracing_position_fetch)
             && (!requirements.runtime_descriptor_array || self.runtime_descriptor_array)
             && (!requirements.buffer_device_address || self.buffer_device_address)
             && (!requirements.dynamic_rendering || self.dynamic_rendering)
             && (!requirements.synchronization2 || self.synchronization2)
+            && (!requirements.fill_mode_non_solid || self.fill_mode_non_solid)
+            && (!requirements.multiview || self.multiview)
+            && (!requirements.swapchain_maintenance1 || self.swapchain_maintenance1)
+            && (!requirements.present_id || self.present_id)
+            && (!requirements.present_wait || self.present_wait)
+            && (!requirements.sparse_binding || self.sparse_binding)
+            && (!requirements.sparse_residency_image_2d || self.sparse_residency_image_2d)
     }
 }
+
+#[test]
+fn test_device_feature_extensions() {
+    let ray_tracing = DeviceFeatures {
+        ray_tracing_pipeline: true,
+        acceleration_structure: true,
+        ..Default::default()
+    };
+    assert_eq!(ray_tracing.extensions(), [
+        "VK_KHR_ray_tracing_pipeline",
+        "VK_KHR_acceleration_structure",
+        "VK_KHR_deferred_host_operations",
+    ]);
+
+    let raster = DeviceFeatures {
+        ray_tracing_pipeline: false,
+        acceleration_structure: false,
+        ..DeviceFeatures::full()
+    };
+    assert!(raster.extensions().is_empty());
+}
@@ -0,0 +1,59 @@
+use ash::vk;
+use crate::PhysicalDevice;
+
+/// Picks the first format of a preference list the device supports with the required features.
+///
+/// ```ignore
+/// let depth_format = FormatSelector::depth().select(&context.physical_device)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct FormatSelector {
+    pub formats: Vec<vk::Format>,
+    pub tiling: vk::ImageTiling,
+    pub features: vk::FormatFeatureFlags,
+}
+
+impl FormatSelector {
+    /// Optimally tiled formats supporting the `features`, preferred in order.
+    pub fn new(formats: &[vk::Format], features: vk::FormatFeatureFlags) -> Self {
+        Self {
+            formats: formats.to_vec(),
+            tiling: vk::ImageTiling::OPTIMAL,
+            features,
+        }
+    }
+
+    /// Depth attachment without stencil, the most precise first.
+    /// Vulkan guarantees `X8_D24_UNORM_PACK32` or `D32_SFLOAT` is supported.
+    pub fn depth() -> Self {
+        Self::new(
+            &[vk::Format::D32_SFLOAT, vk::Format::X8_D24_UNORM_PACK32, vk::Format::D16_UNORM],
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        )
+    }
+
+    /// Depth and stencil attachment, the most precise first.
+    pub fn depth_stencil() -> Self {
+        Self::new(
+            &[vk::Format::D32_SFLOAT_S8_UINT, vk::Format::D24_UNORM_S8_UINT, vk::Format::D16_UNORM_S8_UINT],
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+        )
+    }
+
+    pub fn tiling(self, tiling: vk::ImageTiling) -> Self {
+        Self { tiling, ..self }
+    }
+
+    /// Also require the `features`.
+    pub fn with_features(self, features: vk::FormatFeatureFlags) -> Self {
+        Self { features: self.features | features, ..self }
+    }
+
+    /// The first supported format, `None` if the device supports none of them.
+    pub fn select(&self, physical_device: &PhysicalDevice) -> Option<vk::Format> {
+        self.formats
+            .iter()
+            .copied()
+            .find(|&format| physical_device.supports_format(format, self.tiling, self.features))
+    }
+}
@@ -1,19 +1,26 @@
 use std::fmt::{Debug, Formatter};
+use std::panic::Location;
 use std::sync::{Arc, Mutex};
 use anyhow::Result;
 use ash::vk;
 use ash::vk::Handle;
 use gpu_allocator::MemoryLocation;
 use gpu_allocator::vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator};
-use crate::{Context, Device};
+use crate::{AllocationKind, Context, Device, LiveAllocation};
+use crate::memory_report::{default_allocation_name, rename_tracked_allocation, track_allocation, untrack_allocation};
 
 pub struct Image {
     device: Arc<Device>,
     allocator: Arc<Mutex<Allocator>>,
     pub(crate) inner: vk::Image,
     allocation: Option<Allocation>,
+    /// Id of the allocation in the [`MemoryLeakReport`](crate::MemoryLeakReport)s, swapchain images have none
+    allocation_id: Option<u64>,
     pub format: vk::Format,
     pub extent: vk::Extent3D,
+    pub array_layers: u32,
+    pub mip_levels: u32,
+    pub flags: vk::ImageCreateFlags,
     /// Preventing internal referenced Image been destroyed.
     is_external_referenced: bool,
 }
@@ -24,6 +31,7 @@ pub struct ImageView {
 }
 
 impl Image {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new_2d(
         device: Arc<Device>,
         allocator: Arc<Mutex<Allocator>>,
@@ -32,6 +40,66 @@ impl Image {
         format: vk::Format,
         width: u32,
         height: u32,
+        name: &str,
+    ) -> Result<Self> {
+        Self::new(device, allocator, usage, memory_location, format, width, height, 1, 1, vk::ImageCreateFlags::empty(), name)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new_2d_with_mips(
+        device: Arc<Device>,
+        allocator: Arc<Mutex<Allocator>>,
+        usage: vk::ImageUsageFlags,
+        memory_location: MemoryLocation,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+        name: &str,
+    ) -> Result<Self> {
+        Self::new(device, allocator, usage, memory_location, format, width, height, 1, mip_levels, vk::ImageCreateFlags::empty(), name)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new_layered(
+        device: Arc<Device>,
+        allocator: Arc<Mutex<Allocator>>,
+        usage: vk::ImageUsageFlags,
+        memory_location: MemoryLocation,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+        layers: u32,
+        name: &str,
+    ) -> Result<Self> {
+        Self::new(device, allocator, usage, memory_location, format, width, height, layers, 1, vk::ImageCreateFlags::empty(), name)
+    }
+
+    pub(crate) fn new_cube(
+        device: Arc<Device>,
+        allocator: Arc<Mutex<Allocator>>,
+        usage: vk::ImageUsageFlags,
+        memory_location: MemoryLocation,
+        format: vk::Format,
+        size: u32,
+        name: &str,
+    ) -> Result<Self> {
+        Self::new(device, allocator, usage, memory_location, format, size, size, 6, 1, vk::ImageCreateFlags::CUBE_COMPATIBLE, name)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: Arc<Device>,
+        allocator: Arc<Mutex<Allocator>>,
+        usage: vk::ImageUsageFlags,
+        memory_location: MemoryLocation,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+        array_layers: u32,
+        mip_levels: u32,
+        flags: vk::ImageCreateFlags,
+        name: &str,
     ) -> Result<Self> {
         let extent = vk::Extent3D {
             width,
@@ -40,11 +108,12 @@ impl Image {
         };
 
         let image_info = vk::ImageCreateInfo::builder()
+            .flags(flags)
             .image_type(vk::ImageType::TYPE_2D)
             .format(format)
             .extent(extent)
-            .mip_levels(1)
-            .array_layers(1)
+            .mip_levels(mip_levels)
+            .array_layers(array_layers)
             .samples(vk::SampleCountFlags::TYPE_1)
             .tiling(vk::ImageTiling::OPTIMAL)
             .usage(usage)
@@ -54,7 +123,7 @@ impl Image {
         let requirements = unsafe { device.inner.get_image_memory_requirements(inner) };
 
         let allocation = allocator.lock().unwrap().allocate(&AllocationCreateDesc {
-            name: "image",
+            name,
             requirements,
             location: memory_location,
             linear: true,
@@ -66,6 +135,12 @@ impl Image {
                 .inner
                 .bind_image_memory(inner, allocation.memory(), allocation.offset())?
         };
+        let allocation_id = track_allocation(&allocator, LiveAllocation {
+            name: name.to_owned(),
+            kind: AllocationKind::Image,
+            size: allocation.size(),
+            location: memory_location,
+        });
 
         Ok(
             Self {
@@ -73,8 +148,12 @@ impl Image {
                 allocator,
                 inner,
                 allocation: Some(allocation),
+                allocation_id: Some(allocation_id),
                 format,
                 extent,
+                array_layers,
+                mip_levels,
+                flags,
                 is_external_referenced: false,
             }
         )
@@ -98,23 +177,73 @@ impl Image {
             allocator,
             inner: swapchain_image,
             allocation: None,
+            allocation_id: None,
             format,
             extent,
+            array_layers: 1,
+            mip_levels: 1,
+            flags: vk::ImageCreateFlags::empty(),
             is_external_referenced: true,
         }
     }
 
+    /// Size in bytes of a texel of the common uncompressed formats, `None` for the other ones.
+    pub fn texel_size(&self) -> Option<u32> {
+        let size = match self.format {
+            vk::Format::R8_UNORM | vk::Format::R8_SNORM | vk::Format::R8_UINT | vk::Format::R8_SINT | vk::Format::S8_UINT => 1,
+            vk::Format::R8G8_UNORM | vk::Format::R8G8_UINT | vk::Format::R16_SFLOAT | vk::Format::R16_UNORM
+            | vk::Format::R16_UINT | vk::Format::D16_UNORM => 2,
+            vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB | vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB
+            | vk::Format::R8G8B8A8_UINT | vk::Format::A2B10G10R10_UNORM_PACK32 | vk::Format::B10G11R11_UFLOAT_PACK32
+            | vk::Format::R16G16_SFLOAT | vk::Format::R32_SFLOAT | vk::Format::R32_UINT | vk::Format::R32_SINT
+            | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => 4,
+            vk::Format::R16G16B16A16_SFLOAT | vk::Format::R16G16B16A16_UNORM | vk::Format::R32G32_SFLOAT
+            | vk::Format::R32G32_UINT => 8,
+            vk::Format::R32G32B32A32_SFLOAT | vk::Format::R32G32B32A32_UINT => 16,
+            _ => return None,
+        };
+        Some(size)
+    }
+
+    /// Aspects of the format, depth formats are not viewed as color.
+    pub fn aspect_mask(&self) -> vk::ImageAspectFlags {
+        match self.format {
+            vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => vk::ImageAspectFlags::DEPTH,
+            vk::Format::D16_UNORM_S8_UINT | vk::Format::D24_UNORM_S8_UINT | vk::Format::D32_SFLOAT_S8_UINT => {
+                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+            }
+            vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+            _ => vk::ImageAspectFlags::COLOR,
+        }
+    }
+
+    /// Cube view for cube compatible images, 2D otherwise, covering every mip level.
     pub fn create_image_view(&self) -> Result<ImageView> {
+        self.create_mip_range_view(0, self.mip_levels)
+    }
+
+    /// View of a single mip level, e.g. to write it as a storage image.
+    pub fn create_mip_view(&self, mip_level: u32) -> Result<ImageView> {
+        self.create_mip_range_view(mip_level, 1)
+    }
+
+    fn create_mip_range_view(&self, base_mip_level: u32, level_count: u32) -> Result<ImageView> {
         let view_info = vk::ImageViewCreateInfo::builder()
             .image(self.inner)
-            .view_type(vk::ImageViewType::TYPE_2D)
+            .view_type(if self.flags.contains(vk::ImageCreateFlags::CUBE_COMPATIBLE) {
+                vk::ImageViewType::CUBE
+            } else if self.array_layers > 1 {
+                vk::ImageViewType::TYPE_2D_ARRAY
+            } else {
+                vk::ImageViewType::TYPE_2D
+            })
             .format(self.format)
             .subresource_range(vk::ImageSubresourceRange {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                base_mip_level: 0,
-                level_count: 1,
+                aspect_mask: self.aspect_mask(),
+                base_mip_level,
+                level_count,
                 base_array_layer: 0,
-                layer_count: 1,
+                layer_count: self.array_layers,
             });
         let inner = unsafe { self.device.inner.create_image_view(&view_info, None)? };
 
@@ -124,6 +253,18 @@ impl Image {
         })
     }
 
+    /// Name the allocation in the leak reports of the allocator and the [`Context`], swapchain images have none.
+    pub fn with_name(mut self, name: &str) -> Self {
+        if let Some(allocation) = self.allocation.as_mut() {
+            // only fails for allocations already freed
+            let _ = self.allocator.lock().unwrap().rename_allocation(allocation, name);
+        }
+        if let Some(allocation_id) = self.allocation_id {
+            rename_tracked_allocation(allocation_id, name);
+        }
+        self
+    }
+
     pub fn clone_external(&self) -> Self {
         assert!(self.is_external_referenced, "Only external referenced Image can be clone. Use Arc<Image> instead.");
         assert!(self.allocation.is_none(), "Allocation of external referenced Image should be None.");
@@ -132,14 +273,20 @@ impl Image {
             allocator: self.allocator.clone(),
             inner: self.inner.clone(),
             allocation: None,
+            allocation_id: None,
             format: self.format,
             extent: self.extent,
+            array_layers: self.array_layers,
+            mip_levels: self.mip_levels,
+            flags: self.flags,
             is_external_referenced: true,
         }
     }
 }
 
 impl Context {
+    /// The allocation is named after the caller location, see [`Image::with_name`].
+    #[track_caller]
     pub fn create_image(
         &self,
         usage: vk::ImageUsageFlags,
@@ -155,9 +302,66 @@ impl Context {
             memory_location,
             format,
             width,
-            height
+            height,
+            &default_allocation_name::<Image>(Location::caller()),
         )
     }
+
+    /// 2D image with `mip_levels` levels, each half the size of the previous one.
+    #[track_caller]
+    pub fn create_image_with_mips(
+        &self,
+        usage: vk::ImageUsageFlags,
+        memory_location: MemoryLocation,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+    ) -> Result<Image> {
+        let name = default_allocation_name::<Image>(Location::caller());
+        Image::new_2d_with_mips(self.device.clone(), self.allocator.clone(), usage, memory_location, format, width, height, mip_levels, &name)
+    }
+
+    /// 2D image with `layers` layers, viewed as an array, e.g. one layer per eye of a multiview render target.
+    #[track_caller]
+    pub fn create_layered_image(
+        &self,
+        usage: vk::ImageUsageFlags,
+        memory_location: MemoryLocation,
+        format: vk::Format,
+        width: u32,
+        height: u32,
+        layers: u32,
+    ) -> Result<Image> {
+        let name = default_allocation_name::<Image>(Location::caller());
+        Image::new_layered(self.device.clone(), self.allocator.clone(), usage, memory_location, format, width, height, layers, &name)
+    }
+
+    /// Wrap a 2D image created by another API, e.g. an OpenXR swapchain image, it isn't destroyed with the wrapper.
+    pub fn import_image(&self, image: vk::Image, format: vk::Format, width: u32, height: u32, layers: u32) -> Image {
+        let mut image = Image::from_swapchain_image(
+            self.device.clone(),
+            self.allocator.clone(),
+            image,
+            format,
+            vk::Extent2D { width, height },
+        );
+        image.array_layers = layers;
+        image
+    }
+
+    /// Image with 6 square layers viewed as a cube, in the `+X, -X, +Y, -Y, +Z, -Z` face order.
+    #[track_caller]
+    pub fn create_cube_image(
+        &self,
+        usage: vk::ImageUsageFlags,
+        memory_location: MemoryLocation,
+        format: vk::Format,
+        size: u32,
+    ) -> Result<Image> {
+        let name = default_allocation_name::<Image>(Location::caller());
+        Image::new_cube(self.device.clone(), self.allocator.clone(), usage, memory_location, format, size, &name)
+    }
 }
 
 impl Debug for Image {
@@ -176,6 +380,9 @@ impl Drop for Image {
     fn drop(&mut self) {
         if !self.is_external_referenced {
             unsafe { self.device.inner.destroy_image(self.inner, None) };
+            if let Some(allocation_id) = self.allocation_id {
+                untrack_allocation(allocation_id);
+            }
             self.allocator
                 .lock()
                 .unwrap()
@@ -1,21 +1,31 @@
-use std::ffi::{c_void, CStr, CString};
+use std::ffi::{CStr, CString};
 use ash::extensions::ext::DebugUtils;
 use ash::{Entry, Instance as AshInstance, vk};
-use log::{debug, error, info, warn};
+use log::{debug, warn};
 use raw_window_handle::HasDisplayHandle;
 use avalanche_utils::{CURRENT_APPLICATION_NAME, CURRENT_APPLICATION_VERSION, Version};
-use crate::{PhysicalDevice, Surface};
+use crate::{validation_filter, vulkan_debug_callback, ContextCreateHooks, PhysicalDevice, Surface};
 use crate::util::IntoAshVersion;
 
+const VALIDATION_LAYER_NAME: &[u8] = b"VK_LAYER_KHRONOS_validation\0";
+
 pub struct Instance {
     pub(crate) inner: AshInstance,
+    /// Loaded in debug builds when available, also used to label commands and objects
     debug_utils: Option<DebugUtils>,
     debug_utils_messenger: Option<vk::DebugUtilsMessengerEXT>,
-    physical_devices: Vec<PhysicalDevice>,
+    debug_printf_enabled: bool,
 }
 
 impl Instance {
-    pub(crate) fn new(entry: &Entry, display_handle: &dyn HasDisplayHandle, api_version: Version, app_name: &str) -> anyhow::Result<Self> {
+    pub(crate) fn new(
+        entry: &Entry,
+        display_handle: Option<&dyn HasDisplayHandle>,
+        api_version: Version,
+        app_name: &str,
+        debug_printf: bool,
+        create_hooks: &dyn ContextCreateHooks,
+    ) -> anyhow::Result<Self> {
         let engine_name = CString::new(CURRENT_APPLICATION_NAME)?;
         let app_name = CString::new(app_name)?;
 
@@ -33,106 +43,171 @@ impl Instance {
             std::env::var("PROFILE").unwrap_or(String::new()).eq("debug")
         };
 
-        let mut extension_names = ash_window::enumerate_required_extensions(display_handle.display_handle()?.as_raw())?.to_vec();
-        if is_debug {
+        // only the surface extension of the display the window lives on, e.g. Wayland or X11
+        let mut extension_names = match display_handle {
+            Some(display_handle) => ash_window::enumerate_required_extensions(display_handle.display_handle()?.as_raw())?.to_vec(),
+            None => Vec::new(),
+        };
+        for &extension_name in &extension_names {
+            let extension_name = unsafe { CStr::from_ptr(extension_name) };
+            if !is_instance_extension_available(entry, extension_name) {
+                anyhow::bail!("Instance extension {extension_name:?} required by the window system is not supported");
+            }
+            debug!("[Vulkan] Enabled instance extension {extension_name:?}");
+        }
+        // required by VK_EXT_swapchain_maintenance1, which devices may enable for present fences
+        if display_handle.is_some() {
+            for extension_name in [vk::KhrGetSurfaceCapabilities2Fn::name(), vk::ExtSurfaceMaintenance1Fn::name()] {
+                if !is_instance_extension_available(entry, extension_name) {
+                    break;
+                }
+                extension_names.push(extension_name.as_ptr());
+            }
+        }
+        // Android only exposes debug utils when the validation layers are packaged with the app
+        let debug_utils_enabled = is_debug && is_instance_extension_available(entry, DebugUtils::name());
+        if debug_utils_enabled {
             extension_names.push(DebugUtils::name().as_ptr());
         }
 
-        let instance_create_info = vk::InstanceCreateInfo::builder()
+        // shader printf goes through the validation layers, which report it with the debug utils messenger
+        let mut layer_names = Vec::new();
+        let debug_printf_enabled = debug_printf && debug_utils_enabled && cfg!(feature = "validation") && {
+            let layer_name = CStr::from_bytes_with_nul(VALIDATION_LAYER_NAME).unwrap();
+            let available = is_instance_layer_available(entry, layer_name)
+                && is_layer_extension_available(entry, layer_name, vk::ExtValidationFeaturesFn::name());
+            if available {
+                layer_names.push(layer_name.as_ptr());
+                extension_names.push(vk::ExtValidationFeaturesFn::name().as_ptr());
+                debug!("[Vulkan] Enabled shader debug printf");
+            } else {
+                warn!("[Vulkan] Shader debug printf requires the {layer_name:?} layer with {:?}", vk::ExtValidationFeaturesFn::name());
+            }
+            available
+        };
+        let enabled_validation_features = [vk::ValidationFeatureEnableEXT::DEBUG_PRINTF];
+        let mut validation_features = vk::ValidationFeaturesEXT::builder()
+            .enabled_validation_features(&enabled_validation_features);
+
+        let mut instance_create_info = vk::InstanceCreateInfo::builder()
             .application_info(&app_info)
-            .enabled_extension_names(&extension_names)
-            .build();
+            .enabled_layer_names(&layer_names)
+            .enabled_extension_names(&extension_names);
+        if debug_printf_enabled {
+            instance_create_info = instance_create_info.push_next(&mut validation_features);
+        }
+        let instance_create_info = instance_create_info.build();
+
+        let inner = create_hooks.create_instance(entry, &instance_create_info)?;
 
-        let inner = unsafe { entry.create_instance(&instance_create_info, None)? };
+        let debug_utils = debug_utils_enabled.then(|| DebugUtils::new(entry, &inner));
 
         // Enable debug layer
-        Ok(if cfg!(feature = "validation") {
-            let create_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
-                .flags(vk::DebugUtilsMessengerCreateFlagsEXT::empty())
-                .message_severity(
-                    vk::DebugUtilsMessageSeverityFlagsEXT::INFO
-                        | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-                        | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
-                )
-                .message_type(
-                    vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
-                        | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-                        | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
-                )
-                .pfn_user_callback(Some(vulkan_debug_callback))
-                .build();
-
-            let debug_utils = DebugUtils::new(entry, &inner);
-            let debug_utils_messenger = unsafe { debug_utils.create_debug_utils_messenger(&create_info, None)? };
-
-            Self {
-                inner,
-                debug_utils: Some(debug_utils),
-                debug_utils_messenger: Some(debug_utils_messenger),
-                physical_devices: vec![],
-            }
-        }
-        else {
-            Self {
-                inner,
-                debug_utils: None,
-                debug_utils_messenger: None,
-                physical_devices: vec![],
+        let debug_utils_messenger = match &debug_utils {
+            Some(debug_utils) if cfg!(feature = "validation") => {
+                let create_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
+                    .flags(vk::DebugUtilsMessengerCreateFlagsEXT::empty())
+                    // filtered by the validation filter, its threshold may be lowered at runtime
+                    .message_severity(
+                        vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
+                            | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+                            | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                            | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+                    )
+                    .message_type(
+                        vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                            | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                            | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
+                    )
+                    .pfn_user_callback(Some(vulkan_debug_callback))
+                    .build();
+
+                Some(unsafe { debug_utils.create_debug_utils_messenger(&create_info, None)? })
             }
+            _ => None,
+        };
+
+        Ok(Self {
+            inner,
+            debug_utils,
+            debug_utils_messenger,
+            debug_printf_enabled,
         })
     }
 
-    pub(crate) fn enumerate_physical_devices(
-        &mut self,
-        surface: &Surface,
-    ) -> anyhow::Result<&[PhysicalDevice]> {
-        if self.physical_devices.is_empty() {
-            let physical_devices = unsafe { self.inner.enumerate_physical_devices()? };
-
-            let mut physical_devices = physical_devices
-                .into_iter()
-                .map(|physical_device| PhysicalDevice::new(&self.inner, surface, physical_device))
-                .collect::<anyhow::Result<Vec<_>>>()?;
-
-            physical_devices.sort_by_key(|physical_device| match physical_device.device_type {
-                vk::PhysicalDeviceType::DISCRETE_GPU => 0,
-                vk::PhysicalDeviceType::INTEGRATED_GPU => 1,
-                _ => 2,
-            });
-
-            self.physical_devices = physical_devices;
-        }
+    #[inline]
+    pub fn handle(&self) -> vk::Instance {
+        self.inner.handle()
+    }
 
-        Ok(&self.physical_devices)
+    /// Whether shaders may log with `debugPrintfEXT`, see [`ContextBuilder::debug_printf`](crate::ContextBuilder::debug_printf).
+    #[inline]
+    pub fn debug_printf_enabled(&self) -> bool {
+        self.debug_printf_enabled
     }
-}
 
-unsafe extern "system" fn vulkan_debug_callback(
-    severity_flag: vk::DebugUtilsMessageSeverityFlagsEXT,
-    type_flag: vk::DebugUtilsMessageTypeFlagsEXT,
-    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _: *mut c_void,
-) -> vk::Bool32 {
-    use vk::DebugUtilsMessageSeverityFlagsEXT as SeverityFlag;
-
-    let message = CStr::from_ptr((*p_callback_data).p_message);
-    match severity_flag {
-        SeverityFlag::VERBOSE => debug!("[Vulkan][{:?}] {:?}", type_flag, message),
-        SeverityFlag::INFO => info!("[Vulkan][{:?}] {:?}", type_flag, message),
-        SeverityFlag::WARNING => warn!("[Vulkan][{:?}] {:?}", type_flag, message),
-        _ => error!("[Vulkan][{:?}] {:?}", type_flag, message),
+    #[inline]
+    pub(crate) fn debug_utils(&self) -> Option<&DebugUtils> {
+        self.debug_utils.as_ref()
     }
 
-    vk::FALSE
+    /// Physical devices sorted by preference, discrete GPUs first.
+    pub(crate) fn enumerate_physical_devices(
+        &self,
+        surface: Option<&Surface>,
+    ) -> anyhow::Result<Vec<PhysicalDevice>> {
+        let physical_devices = unsafe { self.inner.enumerate_physical_devices()? };
+
+        let mut physical_devices = physical_devices
+            .into_iter()
+            .map(|physical_device| PhysicalDevice::new(&self.inner, surface, physical_device))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        physical_devices.sort_by_key(|physical_device| match physical_device.device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => 0,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => 1,
+            _ => 2,
+        });
+
+        Ok(physical_devices)
+    }
 }
 
 impl Drop for Instance {
     fn drop(&mut self) {
         unsafe {
-            if let Some(debug_utils) = self.debug_utils.take() && let Some(debug_utils_messenger) = self.debug_utils_messenger.take() {
+            if let (Some(debug_utils), Some(debug_utils_messenger)) = (self.debug_utils.as_ref(), self.debug_utils_messenger.take()) {
                 debug_utils.destroy_debug_utils_messenger(debug_utils_messenger, None);
+                validation_filter().report_suppressed();
             }
             self.inner.destroy_instance(None);
         }
     }
 }
+
+fn is_instance_extension_available(entry: &Entry, name: &CStr) -> bool {
+    entry
+        .enumerate_instance_extension_properties(None)
+        .map(|properties| properties
+            .iter()
+            .any(|property| unsafe { CStr::from_ptr(property.extension_name.as_ptr()) } == name))
+        .unwrap_or(false)
+}
+
+fn is_instance_layer_available(entry: &Entry, name: &CStr) -> bool {
+    entry
+        .enumerate_instance_layer_properties()
+        .map(|properties| properties
+            .iter()
+            .any(|property| unsafe { CStr::from_ptr(property.layer_name.as_ptr()) } == name))
+        .unwrap_or(false)
+}
+
+fn is_layer_extension_available(entry: &Entry, layer_name: &CStr, name: &CStr) -> bool {
+    entry
+        .enumerate_instance_extension_properties(Some(layer_name))
+        .map(|properties| properties
+            .iter()
+            .any(|property| unsafe { CStr::from_ptr(property.extension_name.as_ptr()) } == name))
+        .unwrap_or(false)
+}
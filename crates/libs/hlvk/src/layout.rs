@@ -1,31 +1,145 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
 use std::sync::Arc;
 use ash::vk;
-use anyhow::Result;
-use crate::{Context, DescriptorSetLayout, Device};
+use anyhow::{ensure, Result};
+use crate::{Context, DescriptorSetLayout, Device, StagedShader};
 
 pub struct PipelineLayout {
     device: Arc<Device>,
     pub inner: vk::PipelineLayout,
+    /// Set layouts owned by layouts created with [`PipelineLayout::from_shaders`]
+    descriptor_set_layouts: Vec<DescriptorSetLayout>,
 }
 
 impl PipelineLayout {
     pub fn new(
         device: Arc<Device>,
         descriptor_set_layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange],
     ) -> Result<Self> {
         let layouts = descriptor_set_layouts
             .iter()
             .map(|l| l.inner)
             .collect::<Vec<_>>();
 
-        let pipe_layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(&layouts);
+        let pipe_layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&layouts)
+            .push_constant_ranges(push_constant_ranges);
         let inner = unsafe {
             device
                 .inner
                 .create_pipeline_layout(&pipe_layout_info, None)?
         };
 
-        Ok(Self { device, inner })
+        Ok(Self { device, inner, descriptor_set_layouts: Vec::new() })
+    }
+
+    /// Create a layout with the descriptor bindings and push constants declared by the shaders,
+    /// see [`ShaderReflection`](crate::ShaderReflection).
+    ///
+    /// Bindings shared by several stages must agree on their type and count. A set layout is created for
+    /// every set up to the highest one used, allocate descriptor sets with [`PipelineLayout::descriptor_set_layouts`].
+    pub fn from_shaders(shaders: &[StagedShader]) -> Result<Self> {
+        Self::from_shaders_with_shared_sets(shaders, &[])
+    }
+
+    /// Like [`PipelineLayout::from_shaders`], with the layout of some sets given rather than reflected,
+    /// so a descriptor set allocated once is compatible with every pipeline using these sets.
+    ///
+    /// A shared set is only part of the layout when the shaders use it, with bindings it declares.
+    pub fn from_shaders_with_shared_sets(
+        shaders: &[StagedShader],
+        shared_sets: &[(u32, &[vk::DescriptorSetLayoutBinding])],
+    ) -> Result<Self> {
+        ensure!(!shaders.is_empty(), "A pipeline layout needs at least one shader");
+
+        let mut sets = BTreeMap::<u32, BTreeMap<u32, vk::DescriptorSetLayoutBinding>>::new();
+        let mut push_constant_range = vk::PushConstantRange::default();
+        for shader in shaders {
+            let reflection = &shader.module.reflection;
+            for binding in &reflection.bindings {
+                ensure!(
+                    binding.count > 0,
+                    "Binding {} of set {} is a runtime sized array, which needs an explicit layout",
+                    binding.binding,
+                    binding.set,
+                );
+
+                match sets.entry(binding.set).or_default().entry(binding.binding) {
+                    Entry::Occupied(mut entry) => {
+                        let existing = entry.get_mut();
+                        ensure!(
+                            existing.descriptor_type == binding.descriptor_type
+                                && existing.descriptor_count == binding.count,
+                            "Binding {} of set {} is declared differently by {:?} and {:?}",
+                            binding.binding,
+                            binding.set,
+                            existing.stage_flags,
+                            shader.stage,
+                        );
+                        existing.stage_flags |= shader.stage;
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(vk::DescriptorSetLayoutBinding::builder()
+                            .binding(binding.binding)
+                            .descriptor_type(binding.descriptor_type)
+                            .descriptor_count(binding.count)
+                            .stage_flags(shader.stage)
+                            .build());
+                    }
+                }
+            }
+
+            // one range shared by every stage, pushes have to use all of its stages
+            if let Some(size) = reflection.push_constant_size {
+                push_constant_range.stage_flags |= shader.stage;
+                push_constant_range.size = push_constant_range.size.max(size);
+            }
+        }
+
+        for (set, shared_bindings) in shared_sets {
+            let Some(bindings) = sets.get_mut(set) else {
+                continue;
+            };
+            for binding in bindings.values() {
+                ensure!(
+                    shared_bindings.iter().any(|shared| shared.binding == binding.binding
+                        && shared.descriptor_type == binding.descriptor_type
+                        && shared.descriptor_count == binding.descriptor_count),
+                    "Binding {} of the shared set {set} is declared differently by the shaders",
+                    binding.binding,
+                );
+            }
+            *bindings = shared_bindings.iter().map(|binding| (binding.binding, *binding)).collect();
+        }
+
+        let device = shaders[0].module.device.clone();
+        let set_count = sets.keys().next_back().map_or(0, |set| set + 1);
+        let descriptor_set_layouts = (0..set_count)
+            .map(|set| {
+                let bindings = sets.get(&set).map(|bindings| bindings.values().copied().collect::<Vec<_>>());
+                DescriptorSetLayout::new(device.clone(), &bindings.unwrap_or_default())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let push_constant_ranges = match push_constant_range.size {
+            0 => vec![],
+            _ => vec![push_constant_range],
+        };
+
+        let mut layout = Self::new(
+            device,
+            &descriptor_set_layouts.iter().collect::<Vec<_>>(),
+            &push_constant_ranges,
+        )?;
+        layout.descriptor_set_layouts = descriptor_set_layouts;
+        Ok(layout)
+    }
+
+    /// Set layouts of a layout created with [`PipelineLayout::from_shaders`], indexed by set.
+    #[inline]
+    pub fn descriptor_set_layouts(&self) -> &[DescriptorSetLayout] {
+        &self.descriptor_set_layouts
     }
 }
 
@@ -34,7 +148,15 @@ impl Context {
         &self,
         descriptor_set_layouts: &[&DescriptorSetLayout],
     ) -> Result<PipelineLayout> {
-        PipelineLayout::new(self.device.clone(), descriptor_set_layouts)
+        PipelineLayout::new(self.device.clone(), descriptor_set_layouts, &[])
+    }
+
+    pub fn create_pipeline_layout_with_push_constants(
+        &self,
+        descriptor_set_layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange],
+    ) -> Result<PipelineLayout> {
+        PipelineLayout::new(self.device.clone(), descriptor_set_layouts, push_constant_ranges)
     }
 }
 
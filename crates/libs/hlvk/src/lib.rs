@@ -17,10 +17,15 @@ mod buffer;
 mod descriptor;
 mod command;
 mod swapchain;
+mod format;
+mod memory_report;
 mod raster;
+mod compute;
 mod raytracing;
 mod shader;
 mod layout;
+mod statistics;
+mod validation;
 
 pub use instance::*;
 pub use util::*;
@@ -37,6 +42,12 @@ pub use buffer::*;
 pub use descriptor::*;
 pub use command::*;
 pub use swapchain::*;
+pub use format::*;
+pub use memory_report::*;
 pub use raster::*;
+pub use compute::*;
 pub use raytracing::*;
 pub use shader::*;
+pub use layout::*;
+pub use statistics::*;
+pub use validation::*;
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use gpu_allocator::MemoryLocation;
+use gpu_allocator::vulkan::Allocator;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AllocationKind {
+    Buffer,
+    Image,
+}
+
+/// An allocation of a [`Buffer`](crate::Buffer) or [`Image`](crate::Image) which wasn't freed yet.
+#[derive(Clone, Debug)]
+pub struct LiveAllocation {
+    pub name: String,
+    pub kind: AllocationKind,
+    /// Bytes of device memory
+    pub size: u64,
+    pub location: MemoryLocation,
+}
+
+/// Allocations still alive, see [`Context::report_memory_leaks`](crate::Context::report_memory_leaks).
+#[derive(Clone, Debug, Default)]
+pub struct MemoryLeakReport {
+    /// Largest first
+    pub allocations: Vec<LiveAllocation>,
+}
+
+impl MemoryLeakReport {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.allocations.is_empty()
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.allocations.iter().map(|allocation| allocation.size).sum()
+    }
+}
+
+impl Display for MemoryLeakReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} allocations leaked ({} bytes)", self.allocations.len(), self.total_size())?;
+        for allocation in &self.allocations {
+            write!(
+                f,
+                "\n  {:?} {} - {} bytes ({:?})",
+                allocation.kind, allocation.name, allocation.size, allocation.location,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Name of an allocation made at `caller` without an explicit name, e.g. `avalanche_hlvk::buffer::Buffer at src/lib.rs:12:5`.
+pub fn default_allocation_name<T>(caller: &Location) -> String {
+    format!("{} at {caller}", std::any::type_name::<T>())
+}
+
+struct TrackedAllocation {
+    /// Address of the allocator, which identifies the context
+    allocator: usize,
+    allocation: LiveAllocation,
+}
+
+/// Tracked outside the allocator, so the report only needs host memory, even once the device is lost.
+static LIVE_ALLOCATIONS: Mutex<BTreeMap<u64, TrackedAllocation>> = Mutex::new(BTreeMap::new());
+static NEXT_ALLOCATION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Track an allocation made by `allocator` until [`untrack_allocation`] is called with the returned id.
+pub(crate) fn track_allocation(allocator: &Arc<Mutex<Allocator>>, allocation: LiveAllocation) -> u64 {
+    let id = NEXT_ALLOCATION_ID.fetch_add(1, Ordering::Relaxed);
+    let allocator = Arc::as_ptr(allocator) as usize;
+    LIVE_ALLOCATIONS.lock().unwrap().insert(id, TrackedAllocation { allocator, allocation });
+    id
+}
+
+pub(crate) fn rename_tracked_allocation(id: u64, name: &str) {
+    if let Some(tracked) = LIVE_ALLOCATIONS.lock().unwrap().get_mut(&id) {
+        tracked.allocation.name = name.to_owned();
+    }
+}
+
+pub(crate) fn untrack_allocation(id: u64) {
+    LIVE_ALLOCATIONS.lock().unwrap().remove(&id);
+}
+
+pub(crate) fn live_allocations(allocator: &Arc<Mutex<Allocator>>) -> MemoryLeakReport {
+    let allocator = Arc::as_ptr(allocator) as usize;
+    let mut allocations = LIVE_ALLOCATIONS
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|tracked| tracked.allocator == allocator)
+        .map(|tracked| tracked.allocation.clone())
+        .collect::<Vec<_>>();
+    allocations.sort_by_key(|allocation| std::cmp::Reverse(allocation.size));
+    MemoryLeakReport { allocations }
+}
+
+pub(crate) fn live_allocation_size(allocator: &Arc<Mutex<Allocator>>) -> u64 {
+    let allocator = Arc::as_ptr(allocator) as usize;
+    LIVE_ALLOCATIONS
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|tracked| tracked.allocator == allocator)
+        .map(|tracked| tracked.allocation.size)
+        .sum()
+}
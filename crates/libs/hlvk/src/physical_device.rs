@@ -13,12 +13,16 @@ pub struct PhysicalDevice {
     pub(crate) supported_surface_formats: Vec<vk::SurfaceFormatKHR>,
     pub(crate) supported_present_modes: Vec<vk::PresentModeKHR>,
     pub(crate) supported_device_features: DeviceFeatures,
+    /// Bytes of the device local memory heaps
+    pub(crate) device_local_memory: u64,
+    /// Loaded from the instance to query the capabilities of formats once the device is selected
+    get_format_properties: vk::PFN_vkGetPhysicalDeviceFormatProperties,
 }
 
 impl PhysicalDevice {
     pub(crate) fn new(
         instance: &Instance,
-        surface: &Surface,
+        surface: Option<&Surface>,
         inner: vk::PhysicalDevice,
     ) -> anyhow::Result<Self> {
         let props = unsafe { instance.get_physical_device_properties(inner) };
@@ -40,12 +44,15 @@ impl PhysicalDevice {
             .into_iter()
             .enumerate()
             .map(|(i, prop)| {
-                let present_support = unsafe {
-                    surface.inner.get_physical_device_surface_support(
-                        inner,
-                        i as _,
-                        surface.surface_khr
-                    )?
+                let present_support = match surface {
+                    Some(surface) => unsafe {
+                        surface.inner.get_physical_device_surface_support(
+                            inner,
+                            i as _,
+                            surface.surface_khr
+                        )?
+                    },
+                    None => false,
                 };
 
                 Ok(QueueFamily::new(i as _, prop, present_support))
@@ -63,20 +70,23 @@ impl PhysicalDevice {
             })
             .collect::<Vec<_>>();
 
-        let supported_surface_formats = unsafe {
-            surface
-                .inner
-                .get_physical_device_surface_formats(inner, surface.surface_khr)?
-        };
-
-        let supported_present_modes = unsafe {
-            surface
-                .inner
-                .get_physical_device_surface_present_modes(inner, surface.surface_khr)?
+        let (supported_surface_formats, supported_present_modes) = match surface {
+            Some(surface) => unsafe {
+                (
+                    surface.inner.get_physical_device_surface_formats(inner, surface.surface_khr)?,
+                    surface.inner.get_physical_device_surface_present_modes(inner, surface.surface_khr)?,
+                )
+            },
+            None => Default::default(),
         };
 
         let mut ray_tracing_feature = vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::default();
         let mut acceleration_struct_feature = vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default();
+        let mut position_fetch_feature = vk::PhysicalDeviceRayTracingPositionFetchFeaturesKHR::default();
+        let mut swapchain_maintenance1_feature = vk::PhysicalDeviceSwapchainMaintenance1FeaturesEXT::default();
+        let mut present_id_feature = vk::PhysicalDevicePresentIdFeaturesKHR::default();
+        let mut present_wait_feature = vk::PhysicalDevicePresentWaitFeaturesKHR::default();
+        let mut features11 = vk::PhysicalDeviceVulkan11Features::default();
         let mut features12 = vk::PhysicalDeviceVulkan12Features::builder()
             .runtime_descriptor_array(true)
             .buffer_device_address(true)
@@ -85,19 +95,49 @@ impl PhysicalDevice {
         let mut features = vk::PhysicalDeviceFeatures2::builder()
             .push_next(&mut ray_tracing_feature)
             .push_next(&mut acceleration_struct_feature)
+            .push_next(&mut position_fetch_feature)
+            .push_next(&mut swapchain_maintenance1_feature)
+            .push_next(&mut present_id_feature)
+            .push_next(&mut present_wait_feature)
+            .push_next(&mut features11)
             .push_next(&mut features12)
             .push_next(&mut features13);
         unsafe { instance.get_physical_device_features2(inner, &mut features); };
+        let fill_mode_non_solid = features.features.fill_mode_non_solid == vk::TRUE;
+        let sparse_binding = features.features.sparse_binding == vk::TRUE;
+        let sparse_residency_image_2d = features.features.sparse_residency_image2_d == vk::TRUE;
+        // features of extensions the device doesn't expose can't be enabled
+        let has_extension = |name: &str| supported_extensions.iter().any(|extension| extension == name);
 
         let supported_device_features = DeviceFeatures {
-            ray_tracing_pipeline: ray_tracing_feature.ray_tracing_pipeline == vk::TRUE,
-            acceleration_structure: acceleration_struct_feature.acceleration_structure == vk::TRUE,
+            ray_tracing_pipeline: ray_tracing_feature.ray_tracing_pipeline == vk::TRUE
+                && has_extension("VK_KHR_ray_tracing_pipeline"),
+            acceleration_structure: acceleration_struct_feature.acceleration_structure == vk::TRUE
+                && has_extension("VK_KHR_acceleration_structure")
+                && has_extension("VK_KHR_deferred_host_operations"),
+            ray_tracing_position_fetch: position_fetch_feature.ray_tracing_position_fetch == vk::TRUE
+                && has_extension("VK_KHR_ray_tracing_position_fetch"),
             runtime_descriptor_array: features12.runtime_descriptor_array == vk::TRUE,
             buffer_device_address: features12.buffer_device_address == vk::TRUE,
             dynamic_rendering: features13.dynamic_rendering == vk::TRUE,
             synchronization2: features13.synchronization2 == vk::TRUE,
+            fill_mode_non_solid,
+            multiview: features11.multiview == vk::TRUE,
+            swapchain_maintenance1: swapchain_maintenance1_feature.swapchain_maintenance1 == vk::TRUE
+                && has_extension("VK_EXT_swapchain_maintenance1"),
+            present_id: present_id_feature.present_id == vk::TRUE && has_extension("VK_KHR_present_id"),
+            present_wait: present_wait_feature.present_wait == vk::TRUE && has_extension("VK_KHR_present_wait"),
+            sparse_binding,
+            sparse_residency_image_2d,
         };
 
+        let memory_properties = unsafe { instance.get_physical_device_memory_properties(inner) };
+        let device_local_memory = memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum();
+
         Ok(
             Self {
                 inner,
@@ -109,10 +149,23 @@ impl PhysicalDevice {
                 supported_surface_formats,
                 supported_present_modes,
                 supported_device_features,
+                device_local_memory,
+                get_format_properties: instance.fp_v1_0().get_physical_device_format_properties,
             }
         )
     }
 
+    #[inline]
+    pub fn handle(&self) -> vk::PhysicalDevice {
+        self.inner
+    }
+
+    /// Bytes of the device local memory heaps, shared with the host on integrated GPUs.
+    #[inline]
+    pub fn device_local_memory(&self) -> u64 {
+        self.device_local_memory
+    }
+
     pub fn supports_extensions(&self, extensions: &[&str]) -> bool {
         let supported_extensions = self
             .supported_extensions
@@ -121,4 +174,21 @@ impl PhysicalDevice {
             .collect::<Vec<_>>();
         extensions.iter().all(|e| supported_extensions.contains(e))
     }
+
+    pub fn format_properties(&self, format: vk::Format) -> vk::FormatProperties {
+        let mut properties = vk::FormatProperties::default();
+        unsafe { (self.get_format_properties)(self.inner, format, &mut properties) };
+        properties
+    }
+
+    /// Whether images of `format` with `tiling` support all the `features`.
+    pub fn supports_format(&self, format: vk::Format, tiling: vk::ImageTiling, features: vk::FormatFeatureFlags) -> bool {
+        let properties = self.format_properties(format);
+        let supported = match tiling {
+            vk::ImageTiling::LINEAR => properties.linear_tiling_features,
+            vk::ImageTiling::OPTIMAL => properties.optimal_tiling_features,
+            _ => vk::FormatFeatureFlags::empty(),
+        };
+        supported.contains(features)
+    }
 }
@@ -49,6 +49,26 @@ impl<const C: usize> TimestampQueryPool<C> {
         }
     }
 
+    /// Results in nanoseconds, `None` while some queries are not available yet.
+    pub fn try_get_all_results(&self) -> Result<Option<[u64; C]>> {
+        let mut data = [0u64; C];
+
+        let result = unsafe {
+            self.device.inner.get_query_pool_results(
+                self.inner,
+                0,
+                C as _,
+                &mut data,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        };
+        match result {
+            Ok(()) => Ok(Some(data.map(|timestamp| (timestamp as f64 * self.timestamp_period) as u64))),
+            Err(vk::Result::NOT_READY) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
     pub fn wait_for_all_results(&self) -> Result<[u64; C]> {
         let mut data = [0u64; C];
 
@@ -30,6 +30,11 @@ impl QueueFamily {
         self.inner.queue_flags.contains(vk::QueueFlags::GRAPHICS)
     }
 
+    /// Whether the queues of the family can bind the memory of sparse resources.
+    pub fn supports_sparse_binding(&self) -> bool {
+        self.inner.queue_flags.contains(vk::QueueFlags::SPARSE_BINDING)
+    }
+
     pub fn supports_present(&self) -> bool {
         self.support_present
     }
@@ -103,6 +108,44 @@ impl Queue {
         Ok(())
     }
 
+    /// Submit a single batch waiting and signaling several semaphores, it may have no command buffers
+    /// to only chain semaphores, e.g. from the graphics queue to the present queue.
+    pub fn submit_semaphores(
+        &self,
+        command_buffers: &[&CommandBuffer],
+        wait_semaphores: &[SemaphoreSubmitInfo],
+        signal_semaphores: &[SemaphoreSubmitInfo],
+        fence: Option<&Fence>,
+    ) -> anyhow::Result<()> {
+        let semaphore_submit_info = |s: &SemaphoreSubmitInfo| {
+            vk::SemaphoreSubmitInfo::builder()
+                .semaphore(s.semaphore.inner)
+                .stage_mask(s.stage_mask)
+                .build()
+        };
+        let wait_semaphore_infos = wait_semaphores.iter().map(semaphore_submit_info).collect::<Vec<_>>();
+        let signal_semaphore_infos = signal_semaphores.iter().map(semaphore_submit_info).collect::<Vec<_>>();
+        let command_buffer_infos = command_buffers
+            .iter()
+            .map(|buffer| vk::CommandBufferSubmitInfo::builder().command_buffer(buffer.inner).build())
+            .collect::<Vec<_>>();
+
+        let submit_info = vk::SubmitInfo2::builder()
+            .wait_semaphore_infos(&wait_semaphore_infos)
+            .command_buffer_infos(&command_buffer_infos)
+            .signal_semaphore_infos(&signal_semaphore_infos);
+
+        unsafe {
+            self.device.inner.queue_submit2(
+                self.inner,
+                std::slice::from_ref(&submit_info),
+                fence.map_or(vk::Fence::null(), |fence| fence.inner),
+            )?
+        };
+
+        Ok(())
+    }
+
     pub fn submit(
         &self,
         command_buffer: &Vec<CommandBuffer>,
@@ -1,3 +1,4 @@
+mod blend;
 mod vertex_stream;
 
 use std::sync::Arc;
@@ -6,6 +7,7 @@ use anyhow::Result;
 use derive_builder::Builder;
 use crate::{Context, Device, StagedShader};
 
+pub use blend::*;
 pub use vertex_stream::*;
 use crate::layout::PipelineLayout;
 
@@ -14,6 +16,20 @@ pub struct RasterPipeline {
     pub inner: vk::Pipeline,
 }
 
+/// Format and depth test of the depth attachment of a [`RasterPipeline`].
+#[derive(Clone, Copy, Debug)]
+pub struct RasterDepthAttachment {
+    pub format: vk::Format,
+    pub compare_op: vk::CompareOp,
+    pub write: bool,
+}
+
+impl RasterDepthAttachment {
+    pub fn new(format: vk::Format, compare_op: vk::CompareOp, write: bool) -> Self {
+        Self { format, compare_op, write }
+    }
+}
+
 #[derive(Builder, Clone, Copy)]
 pub struct RasterPipelineCreateInfo<'a> {
     pub shaders: &'a [StagedShader],
@@ -21,12 +37,13 @@ pub struct RasterPipelineCreateInfo<'a> {
     pub vertex_stream: &'a VertexStreamSet,
     pub viewport: Option<vk::Viewport>,
     pub scissor: Option<vk::Rect2D>,
-    pub color_attachment_format: vk::Format,
-    pub color_attachment_blend: Option<vk::PipelineColorBlendAttachmentState>,
+    pub color_attachments: &'a [RasterColorAttachment],
+    pub depth_attachment: Option<RasterDepthAttachment>,
     pub dynamic_states: Option<&'a [vk::DynamicState]>,
     pub polygon_mode: vk::PolygonMode,
     pub front_face: vk::FrontFace,
     pub cull_mode: vk::CullModeFlags,
+    pub samples: vk::SampleCountFlags,
 }
 
 impl RasterPipeline {
@@ -34,6 +51,16 @@ impl RasterPipeline {
         device: Arc<Device>,
         layout: &PipelineLayout,
         create_info: RasterPipelineCreateInfo,
+    ) -> Result<Self> {
+        Self::new_multiview(device, layout, create_info, 0)
+    }
+
+    /// Pipeline drawing into the layers of `view_mask`, see [`CommandBuffer::begin_rendering_multiview`](crate::CommandBuffer::begin_rendering_multiview).
+    pub fn new_multiview(
+        device: Arc<Device>,
+        layout: &PipelineLayout,
+        create_info: RasterPipelineCreateInfo,
+        view_mask: u32,
     ) -> Result<Self> {
         let _shader_modules = create_info.shaders.iter().map(|s| s.module.clone()).collect::<Vec<_>>();
         let shader_stages_info = create_info
@@ -79,17 +106,16 @@ impl RasterPipeline {
 
         let multisampling_info = vk::PipelineMultisampleStateCreateInfo::builder()
             .sample_shading_enable(false)
-            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
+            .rasterization_samples(create_info.samples)
             .min_sample_shading(1.0)
             .alpha_to_coverage_enable(false)
             .alpha_to_one_enable(false);
 
-        let color_blend_attachment = create_info
-            .color_attachment_blend
-            .unwrap_or(vk::PipelineColorBlendAttachmentState::builder()
-                .color_write_mask(vk::ColorComponentFlags::RGBA)
-                .build());
-        let color_blend_attachments = [color_blend_attachment];
+        let color_blend_attachments = create_info
+            .color_attachments
+            .iter()
+            .map(|attachment| attachment.blend.attachment_state())
+            .collect::<Vec<_>>();
         let color_blending_info = vk::PipelineColorBlendStateCreateInfo::builder()
             .logic_op_enable(false)
             .logic_op(vk::LogicOp::COPY)
@@ -99,9 +125,23 @@ impl RasterPipeline {
         let dynamic_state_info = vk::PipelineDynamicStateCreateInfo::builder()
             .dynamic_states(create_info.dynamic_states.unwrap_or(&[]));
 
-        let color_attachment_formats = [create_info.color_attachment_format];
+        let color_attachment_formats = create_info
+            .color_attachments
+            .iter()
+            .map(|attachment| attachment.format)
+            .collect::<Vec<_>>();
         let mut rendering_info = vk::PipelineRenderingCreateInfo::builder()
-            .color_attachment_formats(&color_attachment_formats);
+            .view_mask(view_mask)
+            .color_attachment_formats(&color_attachment_formats)
+            .depth_attachment_format(create_info.depth_attachment.map_or(vk::Format::UNDEFINED, |depth| depth.format));
+
+        let depth_stencil_info = match create_info.depth_attachment {
+            Some(depth) => vk::PipelineDepthStencilStateCreateInfo::builder()
+                .depth_test_enable(true)
+                .depth_write_enable(depth.write)
+                .depth_compare_op(depth.compare_op),
+            None => vk::PipelineDepthStencilStateCreateInfo::builder(),
+        };
 
         let pipeline_info = vk::GraphicsPipelineCreateInfo::builder()
             .stages(&shader_stages_info)
@@ -111,6 +151,7 @@ impl RasterPipeline {
             .rasterization_state(&rasterizer_info)
             .multisample_state(&multisampling_info)
             .color_blend_state(&color_blending_info)
+            .depth_stencil_state(&depth_stencil_info)
             .dynamic_state(&dynamic_state_info)
             .layout(layout.inner)
             .push_next(&mut rendering_info);
@@ -134,6 +175,15 @@ impl Context {
     pub fn create_graphics_pipeline(&self, layout: &PipelineLayout, create_info: RasterPipelineCreateInfo) -> Result<RasterPipeline> {
         RasterPipeline::new(self.device.clone(), layout, create_info)
     }
+
+    pub fn create_multiview_graphics_pipeline(
+        &self,
+        layout: &PipelineLayout,
+        create_info: RasterPipelineCreateInfo,
+        view_mask: u32,
+    ) -> Result<RasterPipeline> {
+        RasterPipeline::new_multiview(self.device.clone(), layout, create_info, view_mask)
+    }
 }
 
 impl Drop for RasterPipeline {
@@ -0,0 +1,69 @@
+use ash::vk;
+
+/// How the output of a fragment shader is combined with a color attachment.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum BlendMode {
+    /// Overwrite the attachment
+    #[default]
+    Opaque,
+    /// `src * src_alpha + dst * (1 - src_alpha)`, draw back to front
+    AlphaBlend,
+    /// `src + dst * (1 - src_alpha)`, colors are already multiplied by their alpha
+    PremultipliedAlpha,
+    /// `src + dst`, order independent
+    Additive,
+    Custom(vk::PipelineColorBlendAttachmentState),
+}
+
+impl BlendMode {
+    pub fn attachment_state(&self) -> vk::PipelineColorBlendAttachmentState {
+        let blend = |src_color, dst_color, src_alpha, dst_alpha| vk::PipelineColorBlendAttachmentState::builder()
+            .blend_enable(true)
+            .src_color_blend_factor(src_color)
+            .dst_color_blend_factor(dst_color)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(src_alpha)
+            .dst_alpha_blend_factor(dst_alpha)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .build();
+
+        match self {
+            BlendMode::Opaque => vk::PipelineColorBlendAttachmentState::builder()
+                .color_write_mask(vk::ColorComponentFlags::RGBA)
+                .build(),
+            BlendMode::AlphaBlend => blend(
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            BlendMode::PremultipliedAlpha => blend(
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            BlendMode::Additive => blend(
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE,
+            ),
+            BlendMode::Custom(state) => *state,
+        }
+    }
+}
+
+/// Format and blending of a color attachment of a [`RasterPipeline`](crate::RasterPipeline).
+#[derive(Clone, Copy, Debug)]
+pub struct RasterColorAttachment {
+    pub format: vk::Format,
+    pub blend: BlendMode,
+}
+
+impl RasterColorAttachment {
+    pub fn new(format: vk::Format, blend: BlendMode) -> Self {
+        Self { format, blend }
+    }
+}
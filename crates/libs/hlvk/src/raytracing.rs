@@ -0,0 +1,413 @@
+use std::sync::{Arc, Mutex};
+use anyhow::{Context as _, Result};
+use ash::extensions::khr::{
+    AccelerationStructure as AshAccelerationStructure,
+    RayTracingPipeline as AshRayTracingPipeline,
+};
+use ash::vk;
+use gpu_allocator::MemoryLocation;
+use gpu_allocator::vulkan::Allocator;
+use crate::{Buffer, Context, Device, Instance, PhysicalDevice, PipelineLayout, StagedShader};
+
+/// Properties of [`vk::PhysicalDeviceRayTracingPipelinePropertiesKHR`] we actually use.
+///
+/// The raw vulkan struct carries a `p_next` pointer so it can't be shared between threads.
+#[derive(Debug, Clone, Copy)]
+pub struct RayTracingPipelineProperties {
+    pub shader_group_handle_size: u32,
+    pub shader_group_base_alignment: u32,
+    pub shader_group_handle_alignment: u32,
+    pub max_ray_recursion_depth: u32,
+}
+
+pub struct RayTracingContext {
+    pub pipeline_properties: RayTracingPipelineProperties,
+    pub pipeline_fn: AshRayTracingPipeline,
+    /// Alignment required for the scratch buffer device address of an acceleration structure build
+    pub min_scratch_offset_alignment: u32,
+    pub acceleration_structure_fn: AshAccelerationStructure,
+}
+
+impl RayTracingContext {
+    pub(crate) fn new(instance: &Instance, physical_device: &PhysicalDevice, device: &Device) -> Self {
+        let mut pipeline_properties = vk::PhysicalDeviceRayTracingPipelinePropertiesKHR::default();
+        let mut acceleration_structure_properties = vk::PhysicalDeviceAccelerationStructurePropertiesKHR::default();
+        {
+            let mut properties = vk::PhysicalDeviceProperties2::builder()
+                .push_next(&mut pipeline_properties)
+                .push_next(&mut acceleration_structure_properties);
+            unsafe {
+                instance
+                    .inner
+                    .get_physical_device_properties2(physical_device.inner, &mut properties)
+            };
+        }
+
+        let pipeline_fn = AshRayTracingPipeline::new(&instance.inner, &device.inner);
+        let acceleration_structure_fn = AshAccelerationStructure::new(&instance.inner, &device.inner);
+
+        Self {
+            pipeline_properties: RayTracingPipelineProperties {
+                shader_group_handle_size: pipeline_properties.shader_group_handle_size,
+                shader_group_base_alignment: pipeline_properties.shader_group_base_alignment,
+                shader_group_handle_alignment: pipeline_properties.shader_group_handle_alignment,
+                max_ray_recursion_depth: pipeline_properties.max_ray_recursion_depth,
+            },
+            pipeline_fn,
+            min_scratch_offset_alignment: acceleration_structure_properties
+                .min_acceleration_structure_scratch_offset_alignment,
+            acceleration_structure_fn,
+        }
+    }
+
+    /// Query the acceleration structure and scratch sizes needed to build `build_info`.
+    pub fn get_build_sizes(
+        &self,
+        build_info: &vk::AccelerationStructureBuildGeometryInfoKHR,
+        max_primitive_counts: &[u32],
+    ) -> vk::AccelerationStructureBuildSizesInfoKHR {
+        unsafe {
+            self.acceleration_structure_fn
+                .get_acceleration_structure_build_sizes(
+                    vk::AccelerationStructureBuildTypeKHR::DEVICE,
+                    build_info,
+                    max_primitive_counts,
+                )
+        }
+    }
+}
+
+pub struct AccelerationStructure {
+    ray_tracing: Arc<RayTracingContext>,
+    pub(crate) inner: vk::AccelerationStructureKHR,
+    _buffer: Buffer,
+    pub level: vk::AccelerationStructureTypeKHR,
+    pub address: u64,
+    pub size: vk::DeviceSize,
+}
+
+impl AccelerationStructure {
+    /// Create an acceleration structure and its backing storage.
+    ///
+    /// The structure isn't built yet, record a build command with
+    /// [`CommandBuffer::build_acceleration_structures`](crate::CommandBuffer::build_acceleration_structures).
+    pub fn new(
+        device: Arc<Device>,
+        allocator: Arc<Mutex<Allocator>>,
+        ray_tracing: Arc<RayTracingContext>,
+        level: vk::AccelerationStructureTypeKHR,
+        size: vk::DeviceSize,
+        queue_family_indices: &[u32],
+    ) -> Result<Self> {
+        let buffer = Buffer::new_shared(
+            device,
+            allocator,
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            MemoryLocation::GpuOnly,
+            size,
+            queue_family_indices,
+            "acceleration structure",
+        )?;
+
+        let create_info = vk::AccelerationStructureCreateInfoKHR::builder()
+            .buffer(buffer.inner)
+            .size(size)
+            .ty(level);
+        let inner = unsafe {
+            ray_tracing
+                .acceleration_structure_fn
+                .create_acceleration_structure(&create_info, None)?
+        };
+
+        let address_info = vk::AccelerationStructureDeviceAddressInfoKHR::builder()
+            .acceleration_structure(inner);
+        let address = unsafe {
+            ray_tracing
+                .acceleration_structure_fn
+                .get_acceleration_structure_device_address(&address_info)
+        };
+
+        Ok(Self {
+            ray_tracing,
+            inner,
+            _buffer: buffer,
+            level,
+            address,
+            size,
+        })
+    }
+
+    #[inline]
+    pub fn handle(&self) -> vk::AccelerationStructureKHR {
+        self.inner
+    }
+}
+
+impl Context {
+    pub fn create_acceleration_structure(
+        &self,
+        level: vk::AccelerationStructureTypeKHR,
+        size: vk::DeviceSize,
+    ) -> Result<AccelerationStructure> {
+        let ray_tracing = self
+            .ray_tracing
+            .clone()
+            .context("Cannot create acceleration structure when ray tracing is not enabled")?;
+
+        AccelerationStructure::new(
+            self.device.clone(),
+            self.allocator.clone(),
+            ray_tracing,
+            level,
+            size,
+            &self.unique_queue_family_indices(),
+        )
+    }
+}
+
+impl Drop for AccelerationStructure {
+    fn drop(&mut self) {
+        unsafe {
+            self.ray_tracing
+                .acceleration_structure_fn
+                .destroy_acceleration_structure(self.inner, None);
+        }
+    }
+}
+
+/// A shader group of a [`RayTracingPipeline`], indices refer to [`RayTracingPipelineCreateInfo::shaders`].
+#[derive(Debug, Clone, Copy)]
+pub enum RayTracingShaderGroup {
+    RayGen(u32),
+    Miss(u32),
+    TriangleHit {
+        closest_hit: u32,
+        any_hit: Option<u32>,
+    },
+}
+
+#[derive(Clone, Copy)]
+pub struct RayTracingPipelineCreateInfo<'a> {
+    pub shaders: &'a [StagedShader],
+    /// Groups must be ordered as ray generation, miss then hit groups,
+    /// the shader binding table is laid out in the same order.
+    pub groups: &'a [RayTracingShaderGroup],
+    pub max_ray_recursion_depth: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RayTracingShaderGroupInfo {
+    pub group_count: u32,
+    pub raygen_shader_count: u32,
+    pub miss_shader_count: u32,
+    pub hit_shader_count: u32,
+}
+
+pub struct RayTracingPipeline {
+    device: Arc<Device>,
+    pub(crate) inner: vk::Pipeline,
+    pub shader_group_info: RayTracingShaderGroupInfo,
+}
+
+impl RayTracingPipeline {
+    pub fn new(
+        device: Arc<Device>,
+        ray_tracing: &RayTracingContext,
+        layout: &PipelineLayout,
+        create_info: RayTracingPipelineCreateInfo,
+    ) -> Result<Self> {
+        let shader_stages_info = create_info
+            .shaders
+            .iter()
+            .map(|s| vk::PipelineShaderStageCreateInfo::builder()
+                .stage(s.stage)
+                .module(s.module.inner)
+                .name(&s.entry_point_name)
+                .build())
+            .collect::<Vec<_>>();
+
+        let mut shader_group_info = RayTracingShaderGroupInfo {
+            group_count: create_info.groups.len() as u32,
+            ..Default::default()
+        };
+        let groups = create_info
+            .groups
+            .iter()
+            .map(|group| {
+                let builder = vk::RayTracingShaderGroupCreateInfoKHR::builder()
+                    .general_shader(vk::SHADER_UNUSED_KHR)
+                    .closest_hit_shader(vk::SHADER_UNUSED_KHR)
+                    .any_hit_shader(vk::SHADER_UNUSED_KHR)
+                    .intersection_shader(vk::SHADER_UNUSED_KHR);
+                match *group {
+                    RayTracingShaderGroup::RayGen(index) => {
+                        shader_group_info.raygen_shader_count += 1;
+                        builder
+                            .ty(vk::RayTracingShaderGroupTypeKHR::GENERAL)
+                            .general_shader(index)
+                    }
+                    RayTracingShaderGroup::Miss(index) => {
+                        shader_group_info.miss_shader_count += 1;
+                        builder
+                            .ty(vk::RayTracingShaderGroupTypeKHR::GENERAL)
+                            .general_shader(index)
+                    }
+                    RayTracingShaderGroup::TriangleHit { closest_hit, any_hit } => {
+                        shader_group_info.hit_shader_count += 1;
+                        builder
+                            .ty(vk::RayTracingShaderGroupTypeKHR::TRIANGLES_HIT_GROUP)
+                            .closest_hit_shader(closest_hit)
+                            .any_hit_shader(any_hit.unwrap_or(vk::SHADER_UNUSED_KHR))
+                    }
+                }
+                .build()
+            })
+            .collect::<Vec<_>>();
+
+        let pipeline_info = vk::RayTracingPipelineCreateInfoKHR::builder()
+            .stages(&shader_stages_info)
+            .groups(&groups)
+            .max_pipeline_ray_recursion_depth(
+                create_info
+                    .max_ray_recursion_depth
+                    .min(ray_tracing.pipeline_properties.max_ray_recursion_depth),
+            )
+            .layout(layout.inner);
+
+        let inner = unsafe {
+            ray_tracing.pipeline_fn.create_ray_tracing_pipelines(
+                vk::DeferredOperationKHR::null(),
+                vk::PipelineCache::null(),
+                std::slice::from_ref(&pipeline_info),
+                None,
+            )?[0]
+        };
+
+        Ok(Self {
+            device,
+            inner,
+            shader_group_info,
+        })
+    }
+}
+
+impl Context {
+    pub fn create_ray_tracing_pipeline(
+        &self,
+        layout: &PipelineLayout,
+        create_info: RayTracingPipelineCreateInfo,
+    ) -> Result<RayTracingPipeline> {
+        let ray_tracing = self
+            .ray_tracing
+            .as_ref()
+            .context("Cannot create ray tracing pipeline when ray tracing is not enabled")?;
+
+        RayTracingPipeline::new(self.device.clone(), ray_tracing, layout, create_info)
+    }
+}
+
+impl Drop for RayTracingPipeline {
+    fn drop(&mut self) {
+        unsafe { self.device.inner.destroy_pipeline(self.inner, None) };
+    }
+}
+
+pub struct ShaderBindingTable {
+    _buffer: Buffer,
+    pub(crate) raygen_region: vk::StridedDeviceAddressRegionKHR,
+    pub(crate) miss_region: vk::StridedDeviceAddressRegionKHR,
+    pub(crate) hit_region: vk::StridedDeviceAddressRegionKHR,
+}
+
+impl ShaderBindingTable {
+    pub fn new(
+        device: Arc<Device>,
+        allocator: Arc<Mutex<Allocator>>,
+        ray_tracing: &RayTracingContext,
+        pipeline: &RayTracingPipeline,
+    ) -> Result<Self> {
+        let properties = ray_tracing.pipeline_properties;
+        let handle_size = properties.shader_group_handle_size;
+        let handle_stride = align_up(handle_size, properties.shader_group_handle_alignment);
+        let base_alignment = properties.shader_group_base_alignment;
+        let group_info = pipeline.shader_group_info;
+
+        let handles = unsafe {
+            ray_tracing.pipeline_fn.get_ray_tracing_shader_group_handles(
+                pipeline.inner,
+                0,
+                group_info.group_count,
+                (group_info.group_count * handle_size) as usize,
+            )?
+        };
+
+        // the raygen region stride must equal its size
+        let raygen_size = align_up(group_info.raygen_shader_count * handle_stride, base_alignment);
+        let miss_size = align_up(group_info.miss_shader_count * handle_stride, base_alignment);
+        let hit_size = align_up(group_info.hit_shader_count * handle_stride, base_alignment);
+        let table_size = (raygen_size + miss_size + hit_size).max(1);
+
+        let mut table = vec![0u8; table_size as usize];
+        let region_offsets = [
+            (0, group_info.raygen_shader_count),
+            (raygen_size, group_info.miss_shader_count),
+            (raygen_size + miss_size, group_info.hit_shader_count),
+        ];
+        let mut group_index = 0;
+        for (region_offset, count) in region_offsets {
+            for i in 0..count {
+                let src = (group_index * handle_size) as usize;
+                let dst = (region_offset + i * handle_stride) as usize;
+                table[dst..dst + handle_size as usize]
+                    .copy_from_slice(&handles[src..src + handle_size as usize]);
+                group_index += 1;
+            }
+        }
+
+        let buffer = Buffer::new(
+            device,
+            allocator,
+            vk::BufferUsageFlags::SHADER_BINDING_TABLE_KHR | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            MemoryLocation::CpuToGpu,
+            table_size as _,
+            "shader binding table",
+        )?;
+        buffer.copy_data_to_buffer(&table)?;
+
+        let address = buffer.get_device_address();
+        let region = |offset: u32, stride: u32, size: u32| {
+            if size == 0 {
+                return vk::StridedDeviceAddressRegionKHR::default();
+            }
+            vk::StridedDeviceAddressRegionKHR::builder()
+                .device_address(address + offset as u64)
+                .stride(stride as _)
+                .size(size as _)
+                .build()
+        };
+
+        Ok(Self {
+            raygen_region: region(0, raygen_size, raygen_size),
+            miss_region: region(raygen_size, handle_stride, miss_size),
+            hit_region: region(raygen_size + miss_size, handle_stride, hit_size),
+            _buffer: buffer,
+        })
+    }
+}
+
+impl Context {
+    pub fn create_shader_binding_table(&self, pipeline: &RayTracingPipeline) -> Result<ShaderBindingTable> {
+        let ray_tracing = self
+            .ray_tracing
+            .as_ref()
+            .context("Cannot create shader binding table when ray tracing is not enabled")?;
+
+        ShaderBindingTable::new(self.device.clone(), self.allocator.clone(), ray_tracing, pipeline)
+    }
+}
+
+#[inline]
+fn align_up(value: u32, alignment: u32) -> u32 {
+    value.div_ceil(alignment.max(1)) * alignment.max(1)
+}
@@ -1,3 +1,7 @@
+mod reflect;
+
+pub use reflect::*;
+
 use std::ffi::CString;
 use std::sync::Arc;
 use ash::vk;
@@ -5,8 +9,9 @@ use anyhow::Result;
 use crate::{Context, Device};
 
 pub struct ShaderModule {
-    device: Arc<Device>,
+    pub(crate) device: Arc<Device>,
     pub inner: vk::ShaderModule,
+    pub reflection: ShaderReflection,
 }
 
 trait IntoStaged {
@@ -16,11 +21,12 @@ trait IntoStaged {
 impl ShaderModule {
     pub fn from_spv_bytes(device: Arc<Device>, source: &[u8]) -> Result<Self> {
         let source = read_shader_from_spv_bytes(source)?;
+        let reflection = ShaderReflection::from_spv(&source)?;
 
         let create_info = vk::ShaderModuleCreateInfo::builder().code(&source);
         let inner = unsafe { device.inner.create_shader_module(&create_info, None)? };
 
-        Ok(Self { device, inner })
+        Ok(Self { device, inner, reflection })
     }
 }
 
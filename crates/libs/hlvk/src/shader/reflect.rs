@@ -0,0 +1,458 @@
+use std::collections::{HashMap, HashSet};
+use anyhow::{bail, ensure, Context, Result};
+use ash::vk;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+const OP_TYPE_INT: u16 = 21;
+const OP_TYPE_FLOAT: u16 = 22;
+const OP_TYPE_VECTOR: u16 = 23;
+const OP_TYPE_MATRIX: u16 = 24;
+const OP_TYPE_IMAGE: u16 = 25;
+const OP_TYPE_SAMPLER: u16 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u16 = 27;
+const OP_TYPE_ARRAY: u16 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u16 = 29;
+const OP_TYPE_STRUCT: u16 = 30;
+const OP_TYPE_POINTER: u16 = 32;
+const OP_CONSTANT: u16 = 43;
+const OP_SPEC_CONSTANT_TRUE: u16 = 48;
+const OP_SPEC_CONSTANT_FALSE: u16 = 49;
+const OP_SPEC_CONSTANT: u16 = 50;
+const OP_SPEC_CONSTANT_COMPOSITE: u16 = 51;
+const OP_SPEC_CONSTANT_OP: u16 = 52;
+const OP_VARIABLE: u16 = 59;
+const OP_DECORATE: u16 = 71;
+const OP_MEMBER_DECORATE: u16 = 72;
+const OP_TYPE_ACCELERATION_STRUCTURE: u16 = 5341;
+
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// An input variable of a shader module, like a vertex attribute of a vertex shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderInput {
+    pub location: u32,
+    /// Format matching the declared type, `None` for types without a single format like matrices
+    pub format: Option<vk::Format>,
+}
+
+/// A resource variable of a shader module bound through a descriptor set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// Number of descriptors of an array binding, `0` for runtime sized arrays
+    pub count: u32,
+}
+
+/// Interface of a shader module read from its SPIR-V.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderReflection {
+    /// Input variables with a location sorted by location, built-ins are left out
+    pub inputs: Vec<ShaderInput>,
+    /// Descriptor bindings sorted by set and binding
+    pub bindings: Vec<ShaderBinding>,
+    /// Size in bytes of the push constant block, if one is declared
+    pub push_constant_size: Option<u32>,
+}
+
+#[derive(Clone)]
+enum SpirvType {
+    Int { width: u32, signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    AccelerationStructure,
+    Pointer { pointee: u32 },
+}
+
+/// Types and decorations of a module, indexed by result id.
+#[derive(Default)]
+struct SpirvModule {
+    types: HashMap<u32, SpirvType>,
+    constants: HashMap<u32, u32>,
+    /// Constants whose value is only known once the pipeline is created
+    spec_constants: HashSet<u32>,
+    /// Decorations with their first literal
+    decorations: HashMap<(u32, u32), u32>,
+    /// Member decorations of structs with their first literal
+    member_decorations: HashMap<(u32, u32, u32), u32>,
+}
+
+impl SpirvModule {
+    #[inline]
+    fn decoration(&self, id: u32, decoration: u32) -> Option<u32> {
+        self.decorations.get(&(id, decoration)).copied()
+    }
+
+    /// Descriptor type and count of a resource variable type, `None` if the type can't be bound.
+    fn descriptor(&self, ty: u32, storage_class: u32) -> Result<Option<(vk::DescriptorType, u32)>> {
+        let Some(ty_info) = self.types.get(&ty) else {
+            return Ok(None);
+        };
+        let descriptor_type = match ty_info {
+            SpirvType::Array { element, length } => {
+                let Some((descriptor_type, _)) = self.descriptor(*element, storage_class)? else {
+                    return Ok(None);
+                };
+                return Ok(Some((descriptor_type, self.array_length(*length)?)));
+            }
+            SpirvType::RuntimeArray { element } => {
+                let Some((descriptor_type, _)) = self.descriptor(*element, storage_class)? else {
+                    return Ok(None);
+                };
+                return Ok(Some((descriptor_type, 0)));
+            }
+            SpirvType::Image { dim: DIM_SUBPASS_DATA, .. } => vk::DescriptorType::INPUT_ATTACHMENT,
+            SpirvType::Image { dim: DIM_BUFFER, sampled: 2 } => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+            SpirvType::Image { dim: DIM_BUFFER, .. } => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+            SpirvType::Image { sampled: 2, .. } => vk::DescriptorType::STORAGE_IMAGE,
+            SpirvType::Image { .. } => vk::DescriptorType::SAMPLED_IMAGE,
+            SpirvType::Sampler => vk::DescriptorType::SAMPLER,
+            SpirvType::SampledImage => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            SpirvType::AccelerationStructure => vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+            SpirvType::Struct { .. } => match storage_class {
+                STORAGE_CLASS_STORAGE_BUFFER => vk::DescriptorType::STORAGE_BUFFER,
+                // storage buffers before SPIR-V 1.3
+                _ if self.decoration(ty, DECORATION_BUFFER_BLOCK).is_some() => vk::DescriptorType::STORAGE_BUFFER,
+                _ => vk::DescriptorType::UNIFORM_BUFFER,
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some((descriptor_type, 1)))
+    }
+
+    /// Value of the constant `length` of an array type.
+    fn array_length(&self, length: u32) -> Result<u32> {
+        if let Some(length) = self.constants.get(&length) {
+            return Ok(*length);
+        }
+        if self.spec_constants.contains(&length) {
+            bail!("Array length %{length} is a specialization constant, descriptor counts must be known before the pipeline is created");
+        }
+        bail!("Array length %{length} isn't a constant");
+    }
+
+    /// Size in bytes of a type with explicit layout, `stride` is the matrix stride of the member holding it.
+    fn size_of(&self, ty: u32, stride: Option<u32>) -> Option<u32> {
+        match self.types.get(&ty)? {
+            SpirvType::Int { width, .. } | SpirvType::Float { width } => Some(width / 8),
+            SpirvType::Vector { component, count } => Some(count * self.size_of(*component, None)?),
+            SpirvType::Matrix { column, count } => Some(count * stride.or(self.size_of(*column, None))?),
+            SpirvType::Array { element, length } => {
+                let stride = self.decoration(ty, DECORATION_ARRAY_STRIDE).or(self.size_of(*element, stride))?;
+                Some(self.constants.get(length)? * stride)
+            }
+            SpirvType::Struct { members } => members
+                .iter()
+                .enumerate()
+                .map(|(index, member)| {
+                    let index = index as u32;
+                    let offset = self.member_decorations.get(&(ty, index, DECORATION_OFFSET))?;
+                    let stride = self.member_decorations.get(&(ty, index, DECORATION_MATRIX_STRIDE)).copied();
+                    Some(offset + self.size_of(*member, stride)?)
+                })
+                .try_fold(0, |size, member_end| Some(size.max(member_end?))),
+            _ => None,
+        }
+    }
+}
+
+impl ShaderReflection {
+    pub fn from_spv(words: &[u32]) -> Result<Self> {
+        ensure!(words.len() >= HEADER_WORDS && words[0] == SPIRV_MAGIC, "Not a SPIR-V module");
+
+        let mut module = SpirvModule::default();
+        let mut variables = Vec::new();
+
+        let mut cursor = HEADER_WORDS;
+        while cursor < words.len() {
+            let word_count = (words[cursor] >> 16) as usize;
+            let opcode = (words[cursor] & 0xffff) as u16;
+            ensure!(word_count > 0 && cursor + word_count <= words.len(), "Truncated SPIR-V instruction at word {cursor}");
+            let operands = &words[cursor + 1..cursor + word_count];
+            cursor += word_count;
+
+            let ty = match (opcode, operands) {
+                (OP_DECORATE, &[target, decoration, ref literals @ ..]) => {
+                    module.decorations.insert((target, decoration), literals.first().copied().unwrap_or_default());
+                    continue;
+                }
+                (OP_MEMBER_DECORATE, &[target, member, decoration, ref literals @ ..]) => {
+                    module.member_decorations.insert((target, member, decoration), literals.first().copied().unwrap_or_default());
+                    continue;
+                }
+                (OP_CONSTANT, &[_, result, value, ..]) => {
+                    module.constants.insert(result, value);
+                    continue;
+                }
+                (
+                    OP_SPEC_CONSTANT_TRUE | OP_SPEC_CONSTANT_FALSE | OP_SPEC_CONSTANT
+                    | OP_SPEC_CONSTANT_COMPOSITE | OP_SPEC_CONSTANT_OP,
+                    &[_, result, ..],
+                ) => {
+                    module.spec_constants.insert(result);
+                    continue;
+                }
+                (OP_VARIABLE, &[result_type, result, storage_class, ..]) => {
+                    variables.push((result, result_type, storage_class));
+                    continue;
+                }
+                (OP_TYPE_INT, &[result, width, signedness]) => (result, SpirvType::Int { width, signed: signedness != 0 }),
+                (OP_TYPE_FLOAT, &[result, width, ..]) => (result, SpirvType::Float { width }),
+                (OP_TYPE_VECTOR, &[result, component, count]) => (result, SpirvType::Vector { component, count }),
+                (OP_TYPE_MATRIX, &[result, column, count]) => (result, SpirvType::Matrix { column, count }),
+                (OP_TYPE_IMAGE, &[result, _, dim, _, _, _, sampled, ..]) => (result, SpirvType::Image { dim, sampled }),
+                (OP_TYPE_SAMPLER, &[result]) => (result, SpirvType::Sampler),
+                (OP_TYPE_SAMPLED_IMAGE, &[result, _]) => (result, SpirvType::SampledImage),
+                (OP_TYPE_ARRAY, &[result, element, length]) => (result, SpirvType::Array { element, length }),
+                (OP_TYPE_RUNTIME_ARRAY, &[result, element]) => (result, SpirvType::RuntimeArray { element }),
+                (OP_TYPE_STRUCT, &[result, ref members @ ..]) => (result, SpirvType::Struct { members: members.to_vec() }),
+                (OP_TYPE_ACCELERATION_STRUCTURE, &[result]) => (result, SpirvType::AccelerationStructure),
+                (OP_TYPE_POINTER, &[result, _, pointee]) => (result, SpirvType::Pointer { pointee }),
+                _ => continue,
+            };
+            module.types.insert(ty.0, ty.1);
+        }
+
+        let mut reflection = Self::default();
+        for (variable, pointer, storage_class) in variables {
+            let Some(&SpirvType::Pointer { pointee }) = module.types.get(&pointer) else {
+                continue;
+            };
+
+            match storage_class {
+                STORAGE_CLASS_INPUT if module.decoration(variable, DECORATION_BUILT_IN).is_none() => {
+                    if let Some(location) = module.decoration(variable, DECORATION_LOCATION) {
+                        reflection.inputs.push(ShaderInput {
+                            location,
+                            format: input_format(&module.types, pointee),
+                        });
+                    }
+                }
+                STORAGE_CLASS_UNIFORM_CONSTANT | STORAGE_CLASS_UNIFORM | STORAGE_CLASS_STORAGE_BUFFER => {
+                    let (Some(set), Some(binding)) = (
+                        module.decoration(variable, DECORATION_DESCRIPTOR_SET),
+                        module.decoration(variable, DECORATION_BINDING),
+                    ) else {
+                        continue;
+                    };
+                    let Some((descriptor_type, count)) = module
+                        .descriptor(pointee, storage_class)
+                        .with_context(|| format!("Failed to reflect binding {binding} of set {set}"))? else {
+                        continue;
+                    };
+                    reflection.bindings.push(ShaderBinding { set, binding, descriptor_type, count });
+                }
+                STORAGE_CLASS_PUSH_CONSTANT => {
+                    reflection.push_constant_size = module.size_of(pointee, None);
+                }
+                _ => {}
+            }
+        }
+        reflection.inputs.sort_by_key(|input| input.location);
+        reflection.bindings.sort_by_key(|binding| (binding.set, binding.binding));
+
+        Ok(reflection)
+    }
+}
+
+/// Vertex format of a scalar or vector type.
+fn input_format(types: &HashMap<u32, SpirvType>, ty: u32) -> Option<vk::Format> {
+    let (scalar, count) = match types.get(&ty)? {
+        SpirvType::Vector { component, count } => (types.get(component)?, *count),
+        scalar => (scalar, 1),
+    };
+
+    let formats = match scalar {
+        SpirvType::Float { width: 32 } => [
+            vk::Format::R32_SFLOAT,
+            vk::Format::R32G32_SFLOAT,
+            vk::Format::R32G32B32_SFLOAT,
+            vk::Format::R32G32B32A32_SFLOAT,
+        ],
+        SpirvType::Float { width: 64 } => [
+            vk::Format::R64_SFLOAT,
+            vk::Format::R64G64_SFLOAT,
+            vk::Format::R64G64B64_SFLOAT,
+            vk::Format::R64G64B64A64_SFLOAT,
+        ],
+        SpirvType::Int { width: 32, signed: true } => [
+            vk::Format::R32_SINT,
+            vk::Format::R32G32_SINT,
+            vk::Format::R32G32B32_SINT,
+            vk::Format::R32G32B32A32_SINT,
+        ],
+        SpirvType::Int { width: 32, signed: false } => [
+            vk::Format::R32_UINT,
+            vk::Format::R32G32_UINT,
+            vk::Format::R32G32B32_UINT,
+            vk::Format::R32G32B32A32_UINT,
+        ],
+        _ => return None,
+    };
+    formats.get(count.checked_sub(1)? as usize).copied()
+}
+
+/// Words of a module with the instructions after the header, for hand written test modules.
+#[cfg(test)]
+fn assemble(instructions: &[(u16, &[u32])]) -> Vec<u32> {
+    let mut words = vec![SPIRV_MAGIC, 0x0001_0500, 0, 64, 0];
+    for (opcode, operands) in instructions {
+        words.push(((operands.len() as u32 + 1) << 16) | *opcode as u32);
+        words.extend_from_slice(operands);
+    }
+    words
+}
+
+#[test]
+fn test_reflect_bindings() {
+    let words = assemble(&[
+        (OP_DECORATE, &[5, DECORATION_DESCRIPTOR_SET, 0]),
+        (OP_DECORATE, &[5, DECORATION_BINDING, 0]),
+        (OP_DECORATE, &[12, DECORATION_DESCRIPTOR_SET, 1]),
+        (OP_DECORATE, &[12, DECORATION_BINDING, 2]),
+        (OP_DECORATE, &[16, DECORATION_DESCRIPTOR_SET, 1]),
+        (OP_DECORATE, &[16, DECORATION_BINDING, 0]),
+        (OP_DECORATE, &[19, DECORATION_DESCRIPTOR_SET, 0]),
+        (OP_DECORATE, &[19, DECORATION_BINDING, 1]),
+        (OP_MEMBER_DECORATE, &[3, 0, DECORATION_OFFSET, 0]),
+        (OP_TYPE_FLOAT, &[1, 32]),
+        (OP_TYPE_VECTOR, &[2, 1, 4]),
+        (OP_TYPE_STRUCT, &[3, 2]),
+        (OP_TYPE_POINTER, &[4, STORAGE_CLASS_UNIFORM, 3]),
+        (OP_VARIABLE, &[4, 5, STORAGE_CLASS_UNIFORM]),
+        // sampler2D[4]
+        (OP_TYPE_IMAGE, &[6, 1, 1, 0, 0, 0, 1, 0]),
+        (OP_TYPE_SAMPLED_IMAGE, &[7, 6]),
+        (OP_TYPE_INT, &[8, 32, 0]),
+        (OP_CONSTANT, &[8, 9, 4]),
+        (OP_TYPE_ARRAY, &[10, 7, 9]),
+        (OP_TYPE_POINTER, &[11, STORAGE_CLASS_UNIFORM_CONSTANT, 10]),
+        (OP_VARIABLE, &[11, 12, STORAGE_CLASS_UNIFORM_CONSTANT]),
+        // image2D[]
+        (OP_TYPE_IMAGE, &[13, 1, 1, 0, 0, 0, 2, 1]),
+        (OP_TYPE_RUNTIME_ARRAY, &[14, 13]),
+        (OP_TYPE_POINTER, &[15, STORAGE_CLASS_UNIFORM_CONSTANT, 14]),
+        (OP_VARIABLE, &[15, 16, STORAGE_CLASS_UNIFORM_CONSTANT]),
+        (OP_TYPE_ACCELERATION_STRUCTURE, &[17]),
+        (OP_TYPE_POINTER, &[18, STORAGE_CLASS_UNIFORM_CONSTANT, 17]),
+        (OP_VARIABLE, &[18, 19, STORAGE_CLASS_UNIFORM_CONSTANT]),
+    ]);
+    let reflection = ShaderReflection::from_spv(&words).unwrap();
+
+    let binding = |set, binding, descriptor_type, count| ShaderBinding { set, binding, descriptor_type, count };
+    assert_eq!(reflection.bindings, [
+        binding(0, 0, vk::DescriptorType::UNIFORM_BUFFER, 1),
+        binding(0, 1, vk::DescriptorType::ACCELERATION_STRUCTURE_KHR, 1),
+        binding(1, 0, vk::DescriptorType::STORAGE_IMAGE, 0),
+        binding(1, 2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 4),
+    ]);
+    assert!(reflection.inputs.is_empty());
+    assert_eq!(reflection.push_constant_size, None);
+}
+
+#[test]
+fn test_reflect_push_constant_size() {
+    // struct { mat4 at 0; vec4 at 64; float[3] at 80 with a stride of 16 }
+    let words = assemble(&[
+        (OP_DECORATE, &[5, DECORATION_ARRAY_STRIDE, 16]),
+        (OP_MEMBER_DECORATE, &[6, 0, DECORATION_OFFSET, 0]),
+        (OP_MEMBER_DECORATE, &[6, 0, DECORATION_MATRIX_STRIDE, 16]),
+        (OP_MEMBER_DECORATE, &[6, 1, DECORATION_OFFSET, 64]),
+        (OP_MEMBER_DECORATE, &[6, 2, DECORATION_OFFSET, 80]),
+        (OP_TYPE_FLOAT, &[1, 32]),
+        (OP_TYPE_VECTOR, &[2, 1, 4]),
+        (OP_TYPE_MATRIX, &[3, 2, 4]),
+        (OP_TYPE_INT, &[9, 32, 0]),
+        (OP_CONSTANT, &[9, 4, 3]),
+        (OP_TYPE_ARRAY, &[5, 1, 4]),
+        (OP_TYPE_STRUCT, &[6, 3, 2, 5]),
+        (OP_TYPE_POINTER, &[7, STORAGE_CLASS_PUSH_CONSTANT, 6]),
+        (OP_VARIABLE, &[7, 8, STORAGE_CLASS_PUSH_CONSTANT]),
+    ]);
+    let reflection = ShaderReflection::from_spv(&words).unwrap();
+
+    assert_eq!(reflection.push_constant_size, Some(128));
+    assert!(reflection.bindings.is_empty());
+}
+
+#[test]
+fn test_reflect_vertex_inputs() {
+    let words = assemble(&[
+        (OP_DECORATE, &[10, DECORATION_LOCATION, 1]),
+        (OP_DECORATE, &[11, DECORATION_LOCATION, 0]),
+        (OP_DECORATE, &[12, DECORATION_LOCATION, 2]),
+        // gl_VertexIndex
+        (OP_DECORATE, &[13, DECORATION_BUILT_IN, 42]),
+        (OP_TYPE_FLOAT, &[1, 32]),
+        (OP_TYPE_VECTOR, &[2, 1, 2]),
+        (OP_TYPE_VECTOR, &[3, 1, 3]),
+        (OP_TYPE_INT, &[4, 32, 0]),
+        (OP_TYPE_VECTOR, &[5, 4, 4]),
+        (OP_TYPE_INT, &[6, 32, 1]),
+        (OP_TYPE_POINTER, &[20, STORAGE_CLASS_INPUT, 2]),
+        (OP_TYPE_POINTER, &[21, STORAGE_CLASS_INPUT, 3]),
+        (OP_TYPE_POINTER, &[22, STORAGE_CLASS_INPUT, 5]),
+        (OP_TYPE_POINTER, &[23, STORAGE_CLASS_INPUT, 6]),
+        (OP_VARIABLE, &[20, 10, STORAGE_CLASS_INPUT]),
+        (OP_VARIABLE, &[21, 11, STORAGE_CLASS_INPUT]),
+        (OP_VARIABLE, &[22, 12, STORAGE_CLASS_INPUT]),
+        (OP_VARIABLE, &[23, 13, STORAGE_CLASS_INPUT]),
+    ]);
+    let reflection = ShaderReflection::from_spv(&words).unwrap();
+
+    assert_eq!(reflection.inputs, [
+        ShaderInput { location: 0, format: Some(vk::Format::R32G32B32_SFLOAT) },
+        ShaderInput { location: 1, format: Some(vk::Format::R32G32_SFLOAT) },
+        ShaderInput { location: 2, format: Some(vk::Format::R32G32B32A32_UINT) },
+    ]);
+}
+
+#[test]
+fn test_reflect_errors() {
+    assert!(ShaderReflection::from_spv(&[0; HEADER_WORDS]).is_err());
+
+    let truncated = assemble(&[(OP_TYPE_FLOAT, &[1, 32])]);
+    assert!(ShaderReflection::from_spv(&truncated[..truncated.len() - 1]).is_err());
+
+    // sampler2D[N] with `layout(constant_id = 0) const uint N`
+    let words = assemble(&[
+        (OP_DECORATE, &[8, DECORATION_DESCRIPTOR_SET, 0]),
+        (OP_DECORATE, &[8, DECORATION_BINDING, 3]),
+        (OP_TYPE_FLOAT, &[1, 32]),
+        (OP_TYPE_IMAGE, &[2, 1, 1, 0, 0, 0, 1, 0]),
+        (OP_TYPE_SAMPLED_IMAGE, &[3, 2]),
+        (OP_TYPE_INT, &[4, 32, 0]),
+        (OP_SPEC_CONSTANT, &[4, 5, 4]),
+        (OP_TYPE_ARRAY, &[6, 3, 5]),
+        (OP_TYPE_POINTER, &[7, STORAGE_CLASS_UNIFORM_CONSTANT, 6]),
+        (OP_VARIABLE, &[7, 8, STORAGE_CLASS_UNIFORM_CONSTANT]),
+    ]);
+    let err = ShaderReflection::from_spv(&words).unwrap_err();
+    assert!(format!("{err:#}").contains("specialization constant"));
+}
@@ -0,0 +1,82 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Commands recorded through the [`CommandBuffer`](crate::CommandBuffer) helpers since the last [`CommandCounters::take`].
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct CommandStatistics {
+    /// Direct and indirect draws, an indirect call counts as its `draw_count` draws
+    pub draw_calls: u64,
+    /// Draws whose instance and triangle counts are read by the GPU and missing from the other counters
+    pub indirect_draws: u64,
+    pub instances: u64,
+    /// Assuming triangle lists
+    pub triangles: u64,
+    pub dispatches: u64,
+    pub trace_rays: u64,
+    pub pipeline_binds: u64,
+    /// Descriptor sets bound, a call binding several counts each of them
+    pub descriptor_set_binds: u64,
+    /// Image and buffer memory barriers
+    pub barriers: u64,
+}
+
+/// Counters shared by the command buffers of a [`Device`](crate::Device), recording may happen on several threads.
+#[derive(Default, Debug)]
+pub struct CommandCounters {
+    draw_calls: AtomicU64,
+    indirect_draws: AtomicU64,
+    instances: AtomicU64,
+    triangles: AtomicU64,
+    dispatches: AtomicU64,
+    trace_rays: AtomicU64,
+    pipeline_binds: AtomicU64,
+    descriptor_set_binds: AtomicU64,
+    barriers: AtomicU64,
+}
+
+impl CommandCounters {
+    pub(crate) fn draw(&self, vertex_count: u32, instance_count: u32) {
+        self.draw_calls.fetch_add(1, Ordering::Relaxed);
+        self.instances.fetch_add(instance_count as u64, Ordering::Relaxed);
+        self.triangles.fetch_add((vertex_count / 3) as u64 * instance_count as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn draw_indirect(&self, draw_count: u32) {
+        self.draw_calls.fetch_add(draw_count as u64, Ordering::Relaxed);
+        self.indirect_draws.fetch_add(draw_count as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dispatch(&self) {
+        self.dispatches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn trace_rays(&self) {
+        self.trace_rays.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn bind_pipeline(&self) {
+        self.pipeline_binds.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn bind_descriptor_sets(&self, count: usize) {
+        self.descriptor_set_binds.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn barriers(&self, count: usize) {
+        self.barriers.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    /// Current counts, resetting the counters to zero.
+    pub fn take(&self) -> CommandStatistics {
+        CommandStatistics {
+            draw_calls: self.draw_calls.swap(0, Ordering::Relaxed),
+            indirect_draws: self.indirect_draws.swap(0, Ordering::Relaxed),
+            instances: self.instances.swap(0, Ordering::Relaxed),
+            triangles: self.triangles.swap(0, Ordering::Relaxed),
+            dispatches: self.dispatches.swap(0, Ordering::Relaxed),
+            trace_rays: self.trace_rays.swap(0, Ordering::Relaxed),
+            pipeline_binds: self.pipeline_binds.swap(0, Ordering::Relaxed),
+            descriptor_set_binds: self.descriptor_set_binds.swap(0, Ordering::Relaxed),
+            barriers: self.barriers.swap(0, Ordering::Relaxed),
+        }
+    }
+}
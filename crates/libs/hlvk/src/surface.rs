@@ -1,4 +1,5 @@
 
+use std::sync::Arc;
 use ash::{vk, extensions::khr::Surface as AshSurface, Entry};
 use log::debug;
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
@@ -8,12 +9,14 @@ pub struct Surface {
     pub(crate) inner: AshSurface,
     pub surface_khr: vk::SurfaceKHR,
     pub is_main_surface: bool,
+    /// The instance must outlive its surfaces.
+    _instance: Arc<Instance>,
 }
 
 impl Surface {
     pub(crate) fn new(
         entry: &Entry,
-        instance: &Instance,
+        instance: &Arc<Instance>,
         window_handle: &dyn HasWindowHandle,
         display_handle: &dyn HasDisplayHandle,
     ) -> anyhow::Result<Self> {
@@ -28,7 +31,7 @@ impl Surface {
             )?
         };
 
-        Ok(Self { inner, surface_khr, is_main_surface: false })
+        Ok(Self { inner, surface_khr, is_main_surface: false, _instance: instance.clone() })
     }
 }
 
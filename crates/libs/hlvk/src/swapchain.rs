@@ -1,11 +1,30 @@
 use std::sync::{Arc, RwLock};
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 use std::time::Duration;
 use anyhow::{anyhow, Error, Result};
-use ash::extensions::khr::Swapchain as AshSwapchain;
+use ash::extensions::khr::{PresentWait, Swapchain as AshSwapchain};
 use ash::vk;
 use log::debug;
-use crate::{Context, Device, Fence, Image, ImageView, Queue, Semaphore};
+use crate::{Context, Device, Fence, Image, ImageView, Queue, Semaphore, Surface};
+
+/// How a [`Swapchain`] is created, see [`Swapchain::with_options`].
+#[derive(Clone, Copy, Debug)]
+pub struct SwapchainOptions {
+    /// Blend the images with what is behind the window when the surface supports it, see [`Swapchain::is_transparent`]
+    pub transparent: bool,
+    /// Prefer `_SRGB` formats, which encode the linear color written to the images,
+    /// over `_UNORM` ones presenting the values as-is, see [`Swapchain::is_srgb`]
+    pub srgb: bool,
+}
+
+impl Default for SwapchainOptions {
+    fn default() -> Self {
+        Self {
+            transparent: false,
+            srgb: true,
+        }
+    }
+}
 
 #[derive(Debug, Copy, Clone)]
 pub struct AcquiredImage {
@@ -13,14 +32,43 @@ pub struct AcquiredImage {
     pub is_suboptimal: bool,
 }
 
+/// Optional requests of a present, see [`Swapchain::queue_present_with`].
+#[derive(Clone, Copy, Default)]
+pub struct PresentOptions<'a> {
+    /// Signaled once the present completed, requires `VK_EXT_swapchain_maintenance1`
+    pub fence: Option<&'a Fence>,
+    /// Id from [`Swapchain::next_present_id`] to wait for with [`Swapchain::wait_for_present`], requires `VK_KHR_present_id`
+    pub present_id: Option<u64>,
+}
+
 pub struct Swapchain {
     device: Arc<Device>,
+    /// Swapchains must be destroyed before their surface.
+    surface: Arc<Surface>,
     inner: AshSwapchain,
+    /// Loaded when the device enabled [`DeviceFeatures::present_wait`](crate::DeviceFeatures::present_wait)
+    present_wait: Option<PresentWait>,
+    next_present_id: AtomicU64,
+    /// Presents with a lower id were queued to a swapchain which was since recreated.
+    first_present_id: AtomicU64,
     swapchain_khr: RwLock<vk::SwapchainKHR>,
     pub extent: RwLock<vk::Extent2D>,
     pub format: vk::Format,
     pub color_space: vk::ColorSpaceKHR,
     pub present_mode: vk::PresentModeKHR,
+    /// Rotation the presentation engine expects the images to be rendered with,
+    /// the extent is in the native orientation of the display when it is rotated (Android).
+    pub pre_transform: RwLock<vk::SurfaceTransformFlagsKHR>,
+    /// How the compositor blends the images with what is behind the window.
+    pub composite_alpha: RwLock<vk::CompositeAlphaFlagsKHR>,
+    /// Whether a blending composite alpha was requested, kept when the swapchain is recreated.
+    transparent: bool,
+    /// What the surface supports, `None` once invalidated until queried again.
+    surface_info: RwLock<Option<SurfaceInfo>>,
+    /// An acquire or a present reported `ERROR_OUT_OF_DATE_KHR`, cleared by [`Swapchain::resize`].
+    out_of_date: AtomicBool,
+    /// An acquire or a present reported `SUBOPTIMAL_KHR`, cleared by [`Swapchain::resize`].
+    suboptimal: AtomicBool,
     pub images: RwLock<Vec<Image>>,
     pub views: RwLock<Vec<ImageView>>,
 
@@ -30,43 +78,32 @@ pub struct Swapchain {
 }
 
 impl Swapchain {
+    /// Swapchain of the main surface of `context`.
     pub fn new(context: &Context, width: u32, height: u32) -> Result<Self> {
+        let surface = context.surface.clone().ok_or_else(|| anyhow!("A headless context has no main surface"))?;
+        Self::with_surface(context, surface, width, height)
+    }
+
+    pub fn with_surface(context: &Context, surface: Arc<Surface>, width: u32, height: u32) -> Result<Self> {
+        Self::with_transparency(context, surface, width, height, false)
+    }
+
+    /// With `transparent`, the images are blended by the compositor with their alpha when the surface supports it,
+    /// see [`Swapchain::is_transparent`].
+    pub fn with_transparency(context: &Context, surface: Arc<Surface>, width: u32, height: u32, transparent: bool) -> Result<Self> {
+        Self::with_options(context, surface, width, height, SwapchainOptions { transparent, ..Default::default() })
+    }
+
+    pub fn with_options(context: &Context, surface: Arc<Surface>, width: u32, height: u32, options: SwapchainOptions) -> Result<Self> {
+        let SwapchainOptions { transparent, srgb } = options;
         let device = context.device.clone();
+        let surface_info = context.get_surface_info(&surface)?;
 
-        let format = {
-            let formats = unsafe {
-                context.surface.inner.get_physical_device_surface_formats(
-                    context.physical_device.inner,
-                    context.surface.surface_khr,
-                )?
-            };
-            if formats.len() == 1 && formats[0].format == vk::Format::UNDEFINED {
-                vk::SurfaceFormatKHR {
-                    format: vk::Format::B8G8R8A8_UNORM,
-                    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
-                }
-            } else {
-                *formats
-                    .iter()
-                    .find(|format| {
-                        format.format == vk::Format::B8G8R8A8_UNORM
-                            && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-                    })
-                    .unwrap_or(&formats[0])
-            }
-        };
+        let format = get_surface_format(&surface_info.formats, srgb);
         debug!("[Vulkan] Selected swapchain format is {format:?}");
 
         let present_mode = {
-            let present_modes = unsafe {
-                context
-                    .surface
-                    .inner
-                    .get_physical_device_surface_present_modes(
-                        context.physical_device.inner,
-                        context.surface.surface_khr,
-                    )?
-            };
+            let present_modes = &surface_info.present_modes;
             if present_modes.contains(&vk::PresentModeKHR::IMMEDIATE) {
                 vk::PresentModeKHR::IMMEDIATE
             } else {
@@ -75,12 +112,14 @@ impl Swapchain {
         };
         debug!("[Vulkan] Selected swapchain present mode is {present_mode:?}");
 
-        let capabilities = context.get_surface_capabilities()?;
+        let capabilities = surface_info.capabilities;
+        let composite_alpha = get_surface_composite_alpha(&capabilities, transparent);
+        debug!("[Vulkan] Selected swapchain composite alpha is {composite_alpha:?}");
 
         let extent = get_surface_suitable_extent(&capabilities, width, height);
         debug!("[Vulkan] Selected swapchain extent is {extent:?}");
 
-        let image_count = capabilities.min_image_count + 1;
+        let image_count = get_surface_image_count(&capabilities);
         debug!("[Vulkan] Selected swapchain image count is {image_count:?}");
 
         let families_indices = [
@@ -89,7 +128,7 @@ impl Swapchain {
         ];
         let create_info = {
             let mut builder = vk::SwapchainCreateInfoKHR::builder()
-                .surface(context.surface.surface_khr)
+                .surface(surface.surface_khr)
                 .min_image_count(image_count)
                 .image_format(format.format)
                 .image_color_space(format.color_space)
@@ -108,12 +147,14 @@ impl Swapchain {
 
             builder
                 .pre_transform(capabilities.current_transform)
-                .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+                .composite_alpha(composite_alpha)
                 .present_mode(present_mode)
                 .clipped(true)
         };
 
         let inner = AshSwapchain::new(&context.instance.inner, &context.device.inner);
+        let present_wait = (context.device_features.present_id && context.device_features.present_wait)
+            .then(|| PresentWait::new(&context.instance.inner, &context.device.inner));
         let swapchain_khr = unsafe { inner.create_swapchain(&create_info, None)? };
 
         let images = unsafe { inner.get_swapchain_images(swapchain_khr)? };
@@ -144,12 +185,22 @@ impl Swapchain {
 
         Ok(Self {
             device,
+            surface,
             inner,
+            present_wait,
+            next_present_id: AtomicU64::new(1),
+            first_present_id: AtomicU64::new(1),
             swapchain_khr: RwLock::new(swapchain_khr),
             extent: RwLock::new(extent),
             format: format.format,
             color_space: format.color_space,
             present_mode,
+            pre_transform: RwLock::new(capabilities.current_transform),
+            composite_alpha: RwLock::new(composite_alpha),
+            transparent,
+            surface_info: RwLock::new(Some(surface_info)),
+            out_of_date: AtomicBool::new(false),
+            suboptimal: AtomicBool::new(false),
             images: RwLock::new(images),
             views: RwLock::new(views),
             acquire_semaphores: RwLock::new(acquire_semaphores),
@@ -157,14 +208,17 @@ impl Swapchain {
         })
     }
 
+    /// Recreate the swapchain with the extent, the current one is passed as `old_swapchain`,
+    /// so the presentation engine may keep showing its images until the new ones are presented.
     pub fn resize(&self, context: &Context, width: u32, height: u32) -> Result<()> {
-        self.destroy();
+        let old_swapchain_khr = *self.swapchain_khr.read().unwrap();
 
-        let capabilities = context.get_surface_capabilities()?;
+        let capabilities = self.surface_info(context)?.capabilities;
+        let composite_alpha = get_surface_composite_alpha(&capabilities, self.transparent);
         let extent = get_surface_suitable_extent(&capabilities, width, height);
         debug!("[Vulkan] Resizing swapchain to {}x{}", extent.width, extent.height);
 
-        let image_count = capabilities.min_image_count + 1;
+        let image_count = get_surface_image_count(&capabilities);
 
         let families_indices = [
             context.graphics_queue_family.index,
@@ -172,7 +226,7 @@ impl Swapchain {
         ];
         let create_info = {
             let mut builder = vk::SwapchainCreateInfoKHR::builder()
-                .surface(context.surface.surface_khr)
+                .surface(self.surface.surface_khr)
                 .min_image_count(image_count)
                 .image_format(self.format)
                 .image_color_space(self.color_space)
@@ -191,12 +245,20 @@ impl Swapchain {
 
             builder
                 .pre_transform(capabilities.current_transform)
-                .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+                .composite_alpha(composite_alpha)
                 .present_mode(self.present_mode)
                 .clipped(true)
+                .old_swapchain(old_swapchain_khr)
         };
 
-        let swapchain_khr = unsafe { self.inner.create_swapchain(&create_info, None)? };
+        // the old swapchain is retired even when the creation fails, it can't be presented to anymore
+        let swapchain_khr = match unsafe { self.inner.create_swapchain(&create_info, None) } {
+            Ok(swapchain_khr) => swapchain_khr,
+            Err(err) => {
+                self.out_of_date.store(true, Ordering::Relaxed);
+                return Err(Error::from(err));
+            }
+        };
 
         // Swapchain images and image views
         let images = unsafe { self.inner.get_swapchain_images(swapchain_khr)? };
@@ -226,14 +288,36 @@ impl Swapchain {
             .collect::<Vec<_>>();
         self.current_semaphores_index.store(0u8, Ordering::Relaxed);
 
+        self.destroy();
         *self.swapchain_khr.write().unwrap() = swapchain_khr;
+        self.out_of_date.store(false, Ordering::Relaxed);
+        self.suboptimal.store(false, Ordering::Relaxed);
+        self.first_present_id.store(self.next_present_id.load(Ordering::Relaxed), Ordering::Relaxed);
         *self.extent.write().unwrap() = extent;
+        *self.pre_transform.write().unwrap() = capabilities.current_transform;
+        *self.composite_alpha.write().unwrap() = composite_alpha;
         *self.images.write().unwrap() = images;
         *self.views.write().unwrap() = views;
 
         Ok(())
     }
 
+    /// What the surface supports, cached until [`Swapchain::invalidate_surface_info`].
+    pub fn surface_info(&self, context: &Context) -> Result<SurfaceInfo> {
+        if let Some(surface_info) = self.surface_info.read().unwrap().as_ref() {
+            return Ok(surface_info.clone());
+        }
+        let surface_info = context.get_surface_info(&self.surface)?;
+        *self.surface_info.write().unwrap() = Some(surface_info.clone());
+        Ok(surface_info)
+    }
+
+    /// Query the surface again the next time its info is needed, after its window was resized
+    /// or moved to another display. Done when the swapchain is reported out of date or suboptimal too.
+    pub fn invalidate_surface_info(&self) {
+        *self.surface_info.write().unwrap() = None;
+    }
+
     fn next_semaphore(&self) -> Result<Arc<Semaphore>> {
         let images = self.images.read().unwrap();
         let index = self.current_semaphores_index.fetch_update(Ordering::Release, Ordering::Acquire, |value| Some((value + 1) % images.len() as u8)).unwrap() + 1;
@@ -241,6 +325,21 @@ impl Swapchain {
         Ok(self.current_acquire_semaphore())
     }
 
+    /// Whether the format of the images is `_SRGB`, linear color written to them is encoded by the hardware.
+    /// Otherwise the values are presented as-is and must be sRGB encoded already.
+    #[inline]
+    pub fn is_srgb(&self) -> bool {
+        is_srgb_format(self.format)
+    }
+
+    /// Whether the compositor blends the images with their alpha, which is premultiplied unless
+    /// the composite alpha is `POST_MULTIPLIED`.
+    pub fn is_transparent(&self) -> bool {
+        let composite_alpha = *self.composite_alpha.read().unwrap();
+        composite_alpha == vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED
+            || composite_alpha == vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED
+    }
+
     pub fn current_acquire_semaphore(&self) -> Arc<Semaphore> {
         self.acquire_semaphores.read().unwrap()[self.current_semaphores_index.load(Ordering::Relaxed) as usize].clone()
     }
@@ -254,8 +353,9 @@ impl Swapchain {
                 timeout,
                 semaphore.inner,
                 if let Some(fence) = fence { fence.inner } else { vk::Fence::null() },
-            )?
-        };
+            )
+        }.inspect_err(|err| self.track_error(*err))?;
+        self.track_suboptimal(is_suboptimal);
 
         Ok(AcquiredImage {
             index,
@@ -276,8 +376,9 @@ impl Swapchain {
                     .fence(if let Some(fence) = fence { fence.inner } else { vk::Fence::null() })
                     .semaphore(if let Some(semaphore) = semaphore { semaphore.inner } else { vk::Semaphore::null() })
                     .build()
-            )?
-        };
+            )
+        }.inspect_err(|err| self.track_error(*err))?;
+        self.track_suboptimal(is_suboptimal);
 
         Ok(AcquiredImage {
             index,
@@ -290,27 +391,117 @@ impl Swapchain {
         image_index: u32,
         wait_semaphores: &[&Semaphore],
         queue: &Queue,
+    ) -> Result<bool> {
+        self.queue_present_with(image_index, wait_semaphores, queue, PresentOptions::default())
+    }
+
+    /// Present with the [`PresentOptions`], the device must have enabled the extensions they require.
+    pub fn queue_present_with(
+        &self,
+        image_index: u32,
+        wait_semaphores: &[&Semaphore],
+        queue: &Queue,
+        options: PresentOptions,
     ) -> Result<bool> {
         let swapchains = [self.swapchain_khr.read().unwrap().clone()];
         let images_indices = [image_index];
         let wait_semaphores = wait_semaphores.iter().map(|s| s.inner).collect::<Vec<_>>();
-
-        let present_info = vk::PresentInfoKHR::builder()
+        let fences = options.fence.map(|fence| [fence.inner]);
+        let mut fence_info = fences
+            .as_ref()
+            .map(|fences| vk::SwapchainPresentFenceInfoEXT::builder().fences(fences));
+        let present_ids = options.present_id.map(|present_id| [present_id]);
+        let mut present_id_info = present_ids
+            .as_ref()
+            .map(|present_ids| vk::PresentIdKHR::builder().present_ids(present_ids));
+
+        let mut present_info = vk::PresentInfoKHR::builder()
             .wait_semaphores(&wait_semaphores)
             .swapchains(&swapchains)
             .image_indices(&images_indices);
+        if let Some(fence_info) = fence_info.as_mut() {
+            present_info = present_info.push_next(fence_info);
+        }
+        if let Some(present_id_info) = present_id_info.as_mut() {
+            present_info = present_info.push_next(present_id_info);
+        }
 
         match unsafe { self.inner.queue_present(queue.inner, &present_info) } {
-            Ok(result) => Ok(result),
+            Ok(is_suboptimal) => {
+                self.track_suboptimal(is_suboptimal);
+                Ok(is_suboptimal)
+            }
             Err(err)
             if err == vk::Result::ERROR_OUT_OF_DATE_KHR || err == vk::Result::SUBOPTIMAL_KHR =>
             {
+                self.track_error(err);
                 Ok(false)
             }
             Err(err) => Err(Error::from(err))
         }
     }
 
+    /// Whether an acquire or a present reported the swapchain out of date since it was last (re)created,
+    /// it can't be presented to until it is recreated with [`Swapchain::resize`].
+    #[inline]
+    pub fn is_out_of_date(&self) -> bool {
+        self.out_of_date.load(Ordering::Relaxed)
+    }
+
+    /// Whether an acquire or a present reported the swapchain suboptimal since it was last (re)created,
+    /// it can still be presented to but should be recreated.
+    #[inline]
+    pub fn is_suboptimal(&self) -> bool {
+        self.suboptimal.load(Ordering::Relaxed)
+    }
+
+    fn track_error(&self, err: vk::Result) {
+        if err == vk::Result::ERROR_OUT_OF_DATE_KHR {
+            self.out_of_date.store(true, Ordering::Relaxed);
+            self.invalidate_surface_info();
+        } else if err == vk::Result::SUBOPTIMAL_KHR {
+            self.track_suboptimal(true);
+        }
+    }
+
+    #[inline]
+    fn track_suboptimal(&self, is_suboptimal: bool) {
+        if is_suboptimal {
+            // e.g. the display rotated, the current transform changed
+            self.suboptimal.store(true, Ordering::Relaxed);
+            self.invalidate_surface_info();
+        }
+    }
+
+    /// Whether presents can be waited with [`Swapchain::wait_for_present`].
+    #[inline]
+    pub fn supports_present_wait(&self) -> bool {
+        self.present_wait.is_some()
+    }
+
+    /// Reserve the id of the next present, ids increase with every call.
+    pub fn next_present_id(&self) -> u64 {
+        self.next_present_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Wait until the present with `present_id` or a later one was displayed, returns false on timeout.
+    ///
+    /// Presents queued before the swapchain was recreated are considered displayed.
+    pub fn wait_for_present(&self, present_id: u64, timeout: Duration) -> Result<bool> {
+        let present_wait = self.present_wait.as_ref().ok_or_else(|| anyhow!("Present wait isn't enabled."))?;
+        if present_id < self.first_present_id.load(Ordering::Relaxed) {
+            return Ok(true);
+        }
+        let swapchain_khr = *self.swapchain_khr.read().unwrap();
+        match unsafe { present_wait.wait_for_present(swapchain_khr, present_id, timeout.as_nanos() as u64) } {
+            Ok(()) => Ok(true),
+            Err(vk::Result::TIMEOUT) => Ok(false),
+            // the present won't be displayed, waiting again is pointless
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(true),
+            Err(err) => Err(Error::from(err)),
+        }
+    }
+
     fn destroy(&self) {
         self.views
             .write()
@@ -336,7 +527,13 @@ unsafe impl Sync for Swapchain {}
 
 pub fn get_surface_suitable_extent(capabilities: &vk::SurfaceCapabilitiesKHR, target_width: u32, target_height: u32) -> vk::Extent2D {
     if capabilities.current_extent.width != u32::MAX {
-        capabilities.current_extent
+        let extent = capabilities.current_extent;
+        // the current extent is in the current orientation, swapchain images are in the native one
+        if is_rotated_transform(capabilities.current_transform) {
+            vk::Extent2D { width: extent.height, height: extent.width }
+        } else {
+            extent
+        }
     } else {
         let min = capabilities.min_image_extent;
         let max = capabilities.max_image_extent;
@@ -346,15 +543,126 @@ pub fn get_surface_suitable_extent(capabilities: &vk::SurfaceCapabilitiesKHR, ta
     }
 }
 
+/// Whether the transform swaps width and height.
+pub fn is_rotated_transform(transform: vk::SurfaceTransformFlagsKHR) -> bool {
+    transform.intersects(
+        vk::SurfaceTransformFlagsKHR::ROTATE_90
+            | vk::SurfaceTransformFlagsKHR::ROTATE_270
+            | vk::SurfaceTransformFlagsKHR::HORIZONTAL_MIRROR_ROTATE_90
+            | vk::SurfaceTransformFlagsKHR::HORIZONTAL_MIRROR_ROTATE_270
+    )
+}
+
+/// A format of `formats` in the sRGB color space, `_SRGB` or `_UNORM` ones first as preferred.
+pub fn get_surface_format(formats: &[vk::SurfaceFormatKHR], srgb: bool) -> vk::SurfaceFormatKHR {
+    let srgb_formats = [vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB];
+    let unorm_formats = [vk::Format::B8G8R8A8_UNORM, vk::Format::R8G8B8A8_UNORM];
+    let (preferred, fallback) = if srgb { (srgb_formats, unorm_formats) } else { (unorm_formats, srgb_formats) };
+
+    // any format may be used
+    if formats.len() == 1 && formats[0].format == vk::Format::UNDEFINED {
+        return vk::SurfaceFormatKHR {
+            format: preferred[0],
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        };
+    }
+    preferred
+        .into_iter()
+        .chain(fallback)
+        .find_map(|candidate| {
+            formats
+                .iter()
+                .find(|format| format.format == candidate && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR)
+        })
+        .copied()
+        .unwrap_or(formats[0])
+}
+
+/// Whether `format` is an `_SRGB` color format, encoding on writes and decoding on reads.
+pub fn is_srgb_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8_SRGB
+            | vk::Format::R8G8_SRGB
+            | vk::Format::R8G8B8_SRGB
+            | vk::Format::B8G8R8_SRGB
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::B8G8R8A8_SRGB
+            | vk::Format::A8B8G8R8_SRGB_PACK32
+    )
+}
+
+/// One more image than the minimum so the application doesn't wait on the presentation engine,
+/// within the maximum when the surface has one.
+pub fn get_surface_image_count(capabilities: &vk::SurfaceCapabilitiesKHR) -> u32 {
+    let image_count = capabilities.min_image_count + 1;
+    if capabilities.max_image_count > 0 {
+        image_count.min(capabilities.max_image_count)
+    } else {
+        image_count
+    }
+}
+
+/// Prefer opaque composition, or a blending one for transparent swapchains.
+/// Opaque when supported, Android surfaces often only support `INHERIT`.
+fn get_surface_composite_alpha(capabilities: &vk::SurfaceCapabilitiesKHR, transparent: bool) -> vk::CompositeAlphaFlagsKHR {
+    let opaque = [
+        vk::CompositeAlphaFlagsKHR::OPAQUE,
+        vk::CompositeAlphaFlagsKHR::INHERIT,
+        vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+        vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+    ];
+    let transparent_first = [
+        vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+        vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+        vk::CompositeAlphaFlagsKHR::INHERIT,
+        vk::CompositeAlphaFlagsKHR::OPAQUE,
+    ];
+    let preferences = if transparent { transparent_first } else { opaque };
+    preferences
+        .into_iter()
+        .find(|&composite_alpha| capabilities.supported_composite_alpha.contains(composite_alpha))
+        .unwrap_or(vk::CompositeAlphaFlagsKHR::OPAQUE)
+}
+
+/// Capabilities, formats and present modes of a surface on the physical device of a [`Context`].
+#[derive(Clone, Debug)]
+pub struct SurfaceInfo {
+    pub capabilities: vk::SurfaceCapabilitiesKHR,
+    pub formats: Vec<vk::SurfaceFormatKHR>,
+    pub present_modes: Vec<vk::PresentModeKHR>,
+}
+
 impl Context {
+    /// Query everything the `surface` supports, see [`Swapchain::surface_info`] for the cached info of a swapchain.
+    pub fn get_surface_info(&self, surface: &Surface) -> Result<SurfaceInfo> {
+        let physical_device = self.physical_device.inner;
+        let (formats, present_modes) = unsafe {
+            (
+                surface.inner.get_physical_device_surface_formats(physical_device, surface.surface_khr)?,
+                surface.inner.get_physical_device_surface_present_modes(physical_device, surface.surface_khr)?,
+            )
+        };
+        Ok(SurfaceInfo {
+            capabilities: self.get_capabilities_of_surface(surface)?,
+            formats,
+            present_modes,
+        })
+    }
+
+    /// Capabilities of the main surface, fails for a headless context.
     pub fn get_surface_capabilities(&self) -> Result<vk::SurfaceCapabilitiesKHR> {
+        let surface = self.surface.as_ref().ok_or_else(|| anyhow!("A headless context has no main surface"))?;
+        self.get_capabilities_of_surface(surface)
+    }
+
+    pub fn get_capabilities_of_surface(&self, surface: &Surface) -> Result<vk::SurfaceCapabilitiesKHR> {
         Ok(unsafe {
-            self
-                .surface
+            surface
                 .inner
                 .get_physical_device_surface_capabilities(
                     self.physical_device.inner,
-                    self.surface.surface_khr,
+                    surface.surface_khr,
                 )?
         })
     }
@@ -0,0 +1,145 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::{c_void, CStr};
+use std::sync::{Mutex, OnceLock};
+use ash::vk;
+use log::{debug, error, info, warn, Level};
+
+/// Times a message is logged before its repeats are only counted.
+const DEFAULT_REPEAT_LIMIT: u64 = 3;
+/// Suppressed repeats between two reminders that a message is still sent.
+const SUPPRESSED_REMINDER_INTERVAL: u64 = 1000;
+
+/// Identifies repeats of a message, by the message id and name, or by the text of messages without either.
+type MessageKey = (i32, String);
+
+struct FilterState {
+    min_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    repeat_limit: u64,
+    muted: HashSet<i32>,
+    /// Times each message was received, muted ones and those below the severity excluded
+    counts: HashMap<MessageKey, u64>,
+}
+
+/// Filters the messages of the validation layers before they are logged, shared by every instance.
+///
+/// Only the first [`repeat limit`](Self::set_repeat_limit) occurrences of a message are logged,
+/// the others are counted and reported every thousand repeats and when the instance is destroyed.
+pub struct ValidationFilter {
+    state: Mutex<FilterState>,
+}
+
+/// The filter applied to the messages of every instance, can be changed at runtime.
+pub fn validation_filter() -> &'static ValidationFilter {
+    static FILTER: OnceLock<ValidationFilter> = OnceLock::new();
+    FILTER.get_or_init(|| ValidationFilter {
+        state: Mutex::new(FilterState {
+            min_severity: vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+            repeat_limit: DEFAULT_REPEAT_LIMIT,
+            muted: HashSet::new(),
+            counts: HashMap::new(),
+        }),
+    })
+}
+
+impl ValidationFilter {
+    /// Drop the messages less severe than `severity`, `INFO` by default.
+    pub fn set_min_severity(&self, severity: vk::DebugUtilsMessageSeverityFlagsEXT) {
+        self.state.lock().unwrap().min_severity = severity;
+    }
+
+    /// Times a message is logged before its repeats are only counted, 0 counts every message.
+    pub fn set_repeat_limit(&self, repeat_limit: u64) {
+        self.state.lock().unwrap().repeat_limit = repeat_limit;
+    }
+
+    /// Drop the messages with the `messageIdNumber` of `id`.
+    pub fn mute(&self, id: i32) {
+        self.state.lock().unwrap().muted.insert(id);
+    }
+
+    pub fn unmute(&self, id: i32) {
+        self.state.lock().unwrap().muted.remove(&id);
+    }
+
+    /// Messages repeated past the repeat limit, with the number of repeats that weren't logged.
+    pub fn suppressed(&self) -> Vec<(i32, String, u64)> {
+        let state = self.state.lock().unwrap();
+        state
+            .counts
+            .iter()
+            .filter(|(_, count)| **count > state.repeat_limit)
+            .map(|((id, name), count)| (*id, name.clone(), count - state.repeat_limit))
+            .collect()
+    }
+
+    /// Log the repeats suppressed so far.
+    pub(crate) fn report_suppressed(&self) {
+        for (id, name, repeats) in self.suppressed() {
+            info!("[Vulkan] {name} ({id:#x}) was repeated {repeats} more times");
+        }
+    }
+
+    fn log(
+        &self,
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+        id: i32,
+        name: Option<&str>,
+        message: &str,
+    ) {
+        let level = match severity {
+            vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => Level::Debug,
+            vk::DebugUtilsMessageSeverityFlagsEXT::INFO => Level::Info,
+            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => Level::Warn,
+            _ => Level::Error,
+        };
+
+        let (count, repeat_limit) = {
+            let mut state = self.state.lock().unwrap();
+            if severity.as_raw() < state.min_severity.as_raw() || state.muted.contains(&id) {
+                return;
+            }
+            // shader debug printf output, every message is a different value
+            if name.is_some_and(|name| name.contains("DEBUG-PRINTF")) {
+                drop(state);
+                log_message(level, format_args!("[Vulkan][{message_type:?}] {message}"));
+                return;
+            }
+            let key = (id, name.unwrap_or(message).to_string());
+            let count = state.counts.entry(key).or_default();
+            *count += 1;
+            (*count, state.repeat_limit)
+        };
+
+        if count <= repeat_limit {
+            let suffix = if count == repeat_limit { " (further repeats are only counted)" } else { "" };
+            log_message(level, format_args!("[Vulkan][{message_type:?}] {message}{suffix}"));
+        } else if (count - repeat_limit) % SUPPRESSED_REMINDER_INTERVAL == 0 {
+            let name = name.unwrap_or("message");
+            log_message(level, format_args!("[Vulkan][{message_type:?}] {name} ({id:#x}) repeated {} more times", count - repeat_limit));
+        }
+    }
+}
+
+fn log_message(level: Level, message: std::fmt::Arguments) {
+    match level {
+        Level::Error => error!("{message}"),
+        Level::Warn => warn!("{message}"),
+        Level::Info => info!("{message}"),
+        _ => debug!("{message}"),
+    }
+}
+
+pub(crate) unsafe extern "system" fn vulkan_debug_callback(
+    severity_flag: vk::DebugUtilsMessageSeverityFlagsEXT,
+    type_flag: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    _: *mut c_void,
+) -> vk::Bool32 {
+    let data = &*p_callback_data;
+    let message = CStr::from_ptr(data.p_message).to_string_lossy();
+    let name = (!data.p_message_id_name.is_null()).then(|| CStr::from_ptr(data.p_message_id_name).to_string_lossy());
+    validation_filter().log(severity_flag, type_flag, data.message_id_number, name.as_deref(), &message);
+
+    vk::FALSE
+}
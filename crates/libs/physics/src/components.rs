@@ -0,0 +1,77 @@
+use bevy_ecs::prelude::{Component, ReflectComponent};
+use bevy_math::Vec3;
+use bevy_reflect::Reflect;
+
+/// How the physics moves an entity, its [`Transform`](bevy_transform::prelude::Transform) is the pose of the body.
+///
+/// Bodies are simulated in world space, so they should be root entities.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum RigidBody {
+    /// Moved by forces and contacts, its transform is written back after every step
+    #[default]
+    Dynamic,
+    /// Never moves, changes to its transform teleport it
+    Fixed,
+    /// Follows its transform every step, pushing the dynamic bodies on its way
+    Kinematic,
+}
+
+/// Shape of a [`Collider`], centered on its entity.
+#[derive(Reflect, Clone, Copy, Debug, PartialEq)]
+pub enum ColliderShape {
+    Ball { radius: f32 },
+    Cuboid { half_extents: Vec3 },
+    /// Along the local Y axis, `half_height` excludes the caps
+    Capsule { half_height: f32, radius: f32 },
+}
+
+/// Collision shape of a [`RigidBody`] on the same entity.
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct Collider {
+    pub shape: ColliderShape,
+    /// Mass per unit of volume
+    pub density: f32,
+    pub friction: f32,
+    /// Bounciness, from 0 to 1
+    pub restitution: f32,
+}
+
+impl Default for Collider {
+    fn default() -> Self {
+        Self::ball(0.5)
+    }
+}
+
+impl Collider {
+    pub fn new(shape: ColliderShape) -> Self {
+        Self {
+            shape,
+            density: 1.0,
+            friction: 0.5,
+            restitution: 0.0,
+        }
+    }
+
+    pub fn ball(radius: f32) -> Self {
+        Self::new(ColliderShape::Ball { radius })
+    }
+
+    pub fn cuboid(half_extents: Vec3) -> Self {
+        Self::new(ColliderShape::Cuboid { half_extents })
+    }
+
+    pub fn capsule(half_height: f32, radius: f32) -> Self {
+        Self::new(ColliderShape::Capsule { half_height, radius })
+    }
+}
+
+/// Velocity of a [`RigidBody::Dynamic`], the initial one when the body is created, then updated after every step.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq)]
+#[reflect(Component)]
+pub struct Velocity {
+    pub linear: Vec3,
+    /// Axis scaled by the speed in radians per second
+    pub angular: Vec3,
+}
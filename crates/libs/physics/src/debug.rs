@@ -0,0 +1,66 @@
+use bevy_ecs::prelude::{Res, ResMut, Resource};
+use bevy_math::Vec3;
+use rapier3d::prelude::*;
+use avalanche_rendering::color::Color;
+use avalanche_rendering::gizmos::{GizmoLine, Gizmos};
+use crate::world::from_point;
+use crate::PhysicsWorld;
+
+/// A segment of the outline of a collider, in world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DebugLine {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub color: Color,
+}
+
+/// Outlines of the colliders, joints and contacts, rebuilt every frame while `enabled`.
+///
+/// The [`lines`](Self::lines) are drawn as [`Gizmos`] when the rendering plugin is added.
+#[derive(Resource, Default)]
+pub struct PhysicsDebugRender {
+    pub enabled: bool,
+    pub lines: Vec<DebugLine>,
+    pipeline: DebugRenderPipeline,
+}
+
+struct DebugLines<'a>(&'a mut Vec<DebugLine>);
+
+impl DebugRenderBackend for DebugLines<'_> {
+    fn draw_line(&mut self, _object: DebugRenderObject, a: Point<Real>, b: Point<Real>, [hue, saturation, lightness, alpha]: [f32; 4]) {
+        self.0.push(DebugLine {
+            start: from_point(a),
+            end: from_point(b),
+            // rapier colors are hue in degrees, saturation, lightness and alpha
+            color: Color::hsla(hue, saturation, lightness, alpha),
+        });
+    }
+}
+
+pub(crate) fn render_physics_debug(
+    mut debug_render: ResMut<PhysicsDebugRender>,
+    world: Res<PhysicsWorld>,
+    gizmos: Option<ResMut<Gizmos>>,
+) {
+    let debug_render = debug_render.as_mut();
+    debug_render.lines.clear();
+    if !debug_render.enabled {
+        return;
+    }
+    debug_render.pipeline.render(
+        &mut DebugLines(&mut debug_render.lines),
+        &world.bodies,
+        &world.colliders,
+        &world.impulse_joints,
+        &world.multibody_joints,
+        &world.narrow_phase,
+    );
+
+    if let Some(mut gizmos) = gizmos {
+        gizmos.lines.extend(debug_render.lines.iter().map(|line| GizmoLine {
+            start: line.start,
+            end: line.end,
+            color: line.color,
+        }));
+    }
+}
@@ -0,0 +1,40 @@
+//! Rigid body physics with rapier, stepped in [`FixedUpdate`] and rendered interpolated between the steps.
+
+mod components;
+mod world;
+mod debug;
+
+pub use components::*;
+pub use world::*;
+pub use debug::*;
+
+pub use rapier3d;
+
+use bevy_app::{App, FixedUpdate, Plugin, PostUpdate};
+use bevy_ecs::prelude::IntoSystemConfigs;
+use avalanche_rendering::interpolation::FixedSimulationSet;
+
+/// Simulates the [`RigidBody`] entities in [`FixedSimulationSet::Simulate`], writing their pose back into their transform.
+///
+/// Dynamic bodies get a [`TransformInterpolation`](avalanche_rendering::interpolation::TransformInterpolation),
+/// so the renderer extracts them blended between the two last steps.
+#[derive(Default)]
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<RigidBody>()
+            .register_type::<Collider>()
+            .register_type::<Velocity>()
+            .init_resource::<PhysicsSettings>()
+            .init_resource::<PhysicsWorld>()
+            .init_resource::<PhysicsDebugRender>()
+            .add_systems(
+                FixedUpdate,
+                (sync_bodies, sync_kinematic_bodies, step_physics, write_back_bodies)
+                    .chain()
+                    .in_set(FixedSimulationSet::Simulate),
+            )
+            .add_systems(PostUpdate, (remove_bodies, render_physics_debug).chain());
+    }
+}
@@ -0,0 +1,213 @@
+use bevy_ecs::prelude::{Changed, Commands, Entity, Or, Query, RemovedComponents, Res, ResMut, Resource};
+use bevy_math::{Quat, Vec3};
+use bevy_time::{Fixed, Time};
+use bevy_transform::prelude::Transform;
+use bevy_utils::EntityHashMap;
+use rapier3d::na::{Quaternion, UnitQuaternion};
+use rapier3d::prelude::*;
+use avalanche_rendering::interpolation::TransformInterpolation;
+use crate::{Collider, ColliderShape, RigidBody, Velocity};
+
+/// Gravity and solver settings of the [`PhysicsWorld`].
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct PhysicsSettings {
+    pub gravity: Vec3,
+    /// Stops stepping the simulation, e.g. while the game is paused
+    pub paused: bool,
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        Self {
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+            paused: false,
+        }
+    }
+}
+
+/// The rapier simulation of the [`RigidBody`] entities.
+#[derive(Resource, Default)]
+pub struct PhysicsWorld {
+    pub bodies: RigidBodySet,
+    pub colliders: ColliderSet,
+    pub impulse_joints: ImpulseJointSet,
+    pub multibody_joints: MultibodyJointSet,
+    pub islands: IslandManager,
+    pub broad_phase: BroadPhase,
+    pub narrow_phase: NarrowPhase,
+    pub ccd_solver: CCDSolver,
+    pub query_pipeline: QueryPipeline,
+    pub integration_parameters: IntegrationParameters,
+    pipeline: PhysicsPipeline,
+    entities: EntityHashMap<Entity, RigidBodyHandle>,
+}
+
+impl PhysicsWorld {
+    /// Body simulating `entity`, once the next fixed step created it.
+    pub fn body(&self, entity: Entity) -> Option<&rapier3d::dynamics::RigidBody> {
+        self.entities.get(&entity).and_then(|&handle| self.bodies.get(handle))
+    }
+
+    pub fn body_mut(&mut self, entity: Entity) -> Option<&mut rapier3d::dynamics::RigidBody> {
+        self.entities.get(&entity).and_then(|&handle| self.bodies.get_mut(handle))
+    }
+
+    /// Entity simulated by a body, e.g. one hit by a ray cast through the [`query_pipeline`](Self::query_pipeline).
+    pub fn entity(&self, handle: RigidBodyHandle) -> Option<Entity> {
+        self.bodies.get(handle).map(|body| Entity::from_bits(body.user_data as u64))
+    }
+
+    fn insert(&mut self, entity: Entity, body: &RigidBody, collider: Option<&Collider>, transform: &Transform, velocity: Option<&Velocity>) {
+        self.remove(entity);
+
+        let builder = match body {
+            RigidBody::Dynamic => RigidBodyBuilder::dynamic(),
+            RigidBody::Fixed => RigidBodyBuilder::fixed(),
+            RigidBody::Kinematic => RigidBodyBuilder::kinematic_position_based(),
+        };
+        let velocity = velocity.copied().unwrap_or_default();
+        let handle = self.bodies.insert(builder
+            .position(to_isometry(transform))
+            .linvel(to_vector(velocity.linear))
+            .angvel(to_vector(velocity.angular))
+            .user_data(entity.to_bits() as u128)
+            .build());
+
+        if let Some(collider) = collider {
+            let builder = match collider.shape {
+                ColliderShape::Ball { radius } => ColliderBuilder::ball(radius),
+                ColliderShape::Cuboid { half_extents } => ColliderBuilder::cuboid(half_extents.x, half_extents.y, half_extents.z),
+                ColliderShape::Capsule { half_height, radius } => ColliderBuilder::capsule_y(half_height, radius),
+            };
+            let collider = builder
+                .density(collider.density)
+                .friction(collider.friction)
+                .restitution(collider.restitution)
+                .build();
+            self.colliders.insert_with_parent(collider, handle, &mut self.bodies);
+        }
+        self.entities.insert(entity, handle);
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        if let Some(handle) = self.entities.remove(&entity) {
+            self.bodies.remove(
+                handle,
+                &mut self.islands,
+                &mut self.colliders,
+                &mut self.impulse_joints,
+                &mut self.multibody_joints,
+                true,
+            );
+        }
+    }
+
+    fn step(&mut self, gravity: Vec3, dt: f32) {
+        self.integration_parameters.dt = dt;
+        self.pipeline.step(
+            &to_vector(gravity),
+            &self.integration_parameters,
+            &mut self.islands,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            &mut self.ccd_solver,
+            Some(&mut self.query_pipeline),
+            &(),
+            &(),
+        );
+    }
+}
+
+pub(crate) fn to_vector(vector: Vec3) -> Vector<Real> {
+    vector![vector.x, vector.y, vector.z]
+}
+
+pub(crate) fn from_point(point: Point<Real>) -> Vec3 {
+    Vec3::new(point.x, point.y, point.z)
+}
+
+fn to_isometry(transform: &Transform) -> Isometry<Real> {
+    let rotation = transform.rotation;
+    Isometry::from_parts(
+        to_vector(transform.translation).into(),
+        UnitQuaternion::new_normalize(Quaternion::new(rotation.w, rotation.x, rotation.y, rotation.z)),
+    )
+}
+
+type ChangedBodyQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static RigidBody,
+    Option<&'static Collider>,
+    &'static Transform,
+    Option<&'static Velocity>,
+), Or<(Changed<RigidBody>, Changed<Collider>)>>;
+
+/// Remove the bodies of despawned entities, every frame since fixed steps may skip some.
+pub(crate) fn remove_bodies(mut world: ResMut<PhysicsWorld>, mut removed: RemovedComponents<RigidBody>) {
+    for entity in removed.read() {
+        world.remove(entity);
+    }
+}
+
+/// Create the bodies of new entities and of entities whose body or collider changed.
+pub(crate) fn sync_bodies(mut commands: Commands, mut world: ResMut<PhysicsWorld>, bodies: ChangedBodyQuery) {
+    for (entity, body, collider, transform, velocity) in bodies.iter() {
+        world.insert(entity, body, collider, transform, velocity);
+        // dynamic bodies move in fixed steps, render them in between
+        if *body == RigidBody::Dynamic {
+            if let Some(mut entity) = commands.get_entity(entity) {
+                entity.insert(TransformInterpolation::default());
+            }
+        }
+    }
+}
+
+/// Move the kinematic bodies to their transform and teleport the fixed ones which were moved.
+pub(crate) fn sync_kinematic_bodies(mut world: ResMut<PhysicsWorld>, bodies: Query<(Entity, &RigidBody, &Transform), Changed<Transform>>) {
+    for (entity, body, transform) in bodies.iter() {
+        let Some(rigid_body) = world.body_mut(entity) else {
+            continue;
+        };
+        match body {
+            RigidBody::Kinematic => rigid_body.set_next_kinematic_position(to_isometry(transform)),
+            RigidBody::Fixed => rigid_body.set_position(to_isometry(transform), true),
+            RigidBody::Dynamic => {}
+        }
+    }
+}
+
+pub(crate) fn step_physics(mut world: ResMut<PhysicsWorld>, settings: Res<PhysicsSettings>, time: Res<Time<Fixed>>) {
+    if settings.paused {
+        return;
+    }
+    world.step(settings.gravity, time.delta_seconds());
+}
+
+/// Write the pose and velocity of the dynamic bodies back into their entities.
+pub(crate) fn write_back_bodies(world: Res<PhysicsWorld>, mut bodies: Query<(Entity, &RigidBody, &mut Transform, Option<&mut Velocity>)>) {
+    for (entity, body, mut transform, velocity) in bodies.iter_mut() {
+        if *body != RigidBody::Dynamic {
+            continue;
+        }
+        let Some(rigid_body) = world.body(entity) else {
+            continue;
+        };
+        if rigid_body.is_sleeping() {
+            continue;
+        }
+
+        let position = rigid_body.position();
+        let rotation = position.rotation;
+        transform.translation = Vec3::new(position.translation.x, position.translation.y, position.translation.z);
+        transform.rotation = Quat::from_xyzw(rotation.i, rotation.j, rotation.k, rotation.w);
+        if let Some(mut velocity) = velocity {
+            let (linear, angular) = (rigid_body.linvel(), rigid_body.angvel());
+            velocity.linear = Vec3::new(linear.x, linear.y, linear.z);
+            velocity.angular = Vec3::new(angular.x, angular.y, angular.z);
+        }
+    }
+}
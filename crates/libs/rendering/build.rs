@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SHADER_EXTENSIONS: &[&str] = &["rgen", "rmiss", "rchit", "rahit", "comp", "vert", "frag"];
+
+fn main() {
+    println!("cargo:rerun-if-changed=shaders");
+
+    let source_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("shaders");
+    let output_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap()).join("shaders");
+
+    if Command::new("glslc").arg("--version").output().is_err() {
+        println!("cargo:warning=glslc not found, shaders are not compiled. Set AVALANCHE_SHADER_DIR to prebuilt SPIR-V instead.");
+        return;
+    }
+
+    // binds the buffer of `shaders/debug/print.glsl`, like the globals layout of debug builds
+    let debug = std::env::var("PROFILE").is_ok_and(|profile| profile == "debug");
+
+    let mut sources = Vec::new();
+    collect_sources(&source_dir, &mut sources);
+
+    let mut success = true;
+    for source in sources {
+        let relative = source.strip_prefix(&source_dir).unwrap();
+        let mut output = output_dir.join(relative).into_os_string();
+        output.push(".spv");
+        let output = PathBuf::from(output);
+        std::fs::create_dir_all(output.parent().unwrap()).unwrap();
+
+        let status = Command::new("glslc")
+            .arg("--target-env=vulkan1.3")
+            .arg("-O")
+            .args(debug.then_some("-DAVALANCHE_DEBUG"))
+            .arg("-I")
+            .arg(&source_dir)
+            .arg(&source)
+            .arg("-o")
+            .arg(&output)
+            .status();
+        if !matches!(status, Ok(status) if status.success()) {
+            println!("cargo:warning=Failed to compile shader {}", source.display());
+            success = false;
+        }
+    }
+
+    if success {
+        println!("cargo:rustc-env=AVALANCHE_COMPILED_SHADER_DIR={}", output_dir.display());
+    }
+}
+
+fn collect_sources(dir: &Path, sources: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_sources(&path, sources);
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| SHADER_EXTENSIONS.contains(&ext)) {
+            sources.push(path);
+        }
+    }
+}
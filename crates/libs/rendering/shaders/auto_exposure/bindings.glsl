@@ -0,0 +1,24 @@
+#ifndef AUTO_EXPOSURE_BINDINGS
+#define AUTO_EXPOSURE_BINDINGS
+
+#include "auto_exposure/common.glsl"
+
+// Shared by the histogram and resolve passes
+layout(set = 0, binding = 1, std430) buffer Histogram {
+    uint bins[HISTOGRAM_BINS];
+} histogram;
+
+layout(set = 0, binding = 2, std430) buffer AdaptedExposure {
+    Exposure value;
+} adapted;
+
+// Matches `AutoExposurePushConstants` in src/auto_exposure/pipeline.rs
+layout(push_constant) uniform AutoExposurePushConstants {
+    uvec2 size;
+    float min_ev;
+    float max_ev;
+    float speed;
+    float delta_seconds;
+} settings;
+
+#endif
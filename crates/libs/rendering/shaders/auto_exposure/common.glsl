@@ -0,0 +1,17 @@
+#ifndef AUTO_EXPOSURE_COMMON
+#define AUTO_EXPOSURE_COMMON
+
+// Matches `LUMINANCE_HISTOGRAM_BINS` in src/auto_exposure.rs
+#define HISTOGRAM_BINS 256
+
+// Matches `GpuExposure` in src/auto_exposure.rs
+struct Exposure {
+    // adapted average luminance in stops
+    float log_luminance;
+    float exposure;
+    uint adapted;
+};
+
+const vec3 LUMINANCE = vec3(0.2126, 0.7152, 0.0722);
+
+#endif
@@ -0,0 +1,16 @@
+#ifndef DEBUG_COMMON
+#define DEBUG_COMMON
+
+// Matches `DebugViewUniform` in src/debug_view.rs
+layout(set = 0, binding = 0) uniform DebugViewUniform {
+    mat4 clip_from_world;
+    vec4 camera_position;
+} uniforms;
+
+// Matches `DebugViewPushConstants` in src/debug_view/pipeline.rs
+layout(push_constant) uniform DebugViewPushConstants {
+    mat4 world_from_object;
+    uint joint_offset;
+} object;
+
+#endif
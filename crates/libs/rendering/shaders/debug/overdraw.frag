@@ -0,0 +1,8 @@
+#version 460
+
+layout(location = 0) out vec4 out_color;
+
+// Added up by the blend state, hot colors are surfaces drawn many times
+void main() {
+    out_color = vec4(0.1, 0.04, 0.01, 1.0);
+}
@@ -0,0 +1,86 @@
+#ifndef DEBUG_PRINT
+#define DEBUG_PRINT
+
+// Values printed by shaders in debug builds, read back and logged every frame by `ShaderDebugPrint`
+// in src/debug_print.rs. `DEBUG_PRINT(value)` tags the value with the line it is printed from,
+// `debug_print(tag, value)` with a tag labeled by `ShaderDebugPrint::set_label`.
+//
+// Strings need the validation layers instead: with AVALANCHE_DEBUG_PRINTF set, `debugPrintfEXT`
+// from GL_EXT_debug_printf is logged by the validation filter.
+#ifdef AVALANCHE_DEBUG
+
+// Binding 1 of `GLOBALS_SET`, see `globals_bindings` in src/globals.rs
+layout(set = 3, binding = 1, std430) buffer DebugPrintBuffer {
+    // Words written, may exceed the capacity when records were dropped
+    uint count;
+    uint words[];
+} debug_print_buffer;
+
+// Kinds of the values of a record, matches `DebugPrintKind` in src/debug_print.rs
+#define DEBUG_PRINT_UINT 0u
+#define DEBUG_PRINT_INT 1u
+#define DEBUG_PRINT_FLOAT 2u
+
+// Reserve a record of `components` words after its header, returns the index of the first one or ~0u if full.
+uint debug_print_begin(uint tag, uint kind, uint components) {
+    uint offset = atomicAdd(debug_print_buffer.count, components + 1u);
+    if (offset + components + 1u > uint(debug_print_buffer.words.length())) {
+        return ~0u;
+    }
+    debug_print_buffer.words[offset] = (kind << 28) | (components << 24) | (tag & 0xffffffu);
+    return offset + 1u;
+}
+
+void debug_print(uint tag, uvec4 value, uint components) {
+    uint offset = debug_print_begin(tag, DEBUG_PRINT_UINT, components);
+    if (offset == ~0u) {
+        return;
+    }
+    for (uint i = 0u; i < components; i++) {
+        debug_print_buffer.words[offset + i] = value[i];
+    }
+}
+
+void debug_print(uint tag, ivec4 value, uint components) {
+    uint offset = debug_print_begin(tag, DEBUG_PRINT_INT, components);
+    if (offset == ~0u) {
+        return;
+    }
+    for (uint i = 0u; i < components; i++) {
+        debug_print_buffer.words[offset + i] = uint(value[i]);
+    }
+}
+
+void debug_print(uint tag, vec4 value, uint components) {
+    uint offset = debug_print_begin(tag, DEBUG_PRINT_FLOAT, components);
+    if (offset == ~0u) {
+        return;
+    }
+    for (uint i = 0u; i < components; i++) {
+        debug_print_buffer.words[offset + i] = floatBitsToUint(value[i]);
+    }
+}
+
+void debug_print(uint tag, uint value) { debug_print(tag, uvec4(value, 0u, 0u, 0u), 1u); }
+void debug_print(uint tag, uvec2 value) { debug_print(tag, uvec4(value, 0u, 0u), 2u); }
+void debug_print(uint tag, uvec3 value) { debug_print(tag, uvec4(value, 0u), 3u); }
+void debug_print(uint tag, uvec4 value) { debug_print(tag, value, 4u); }
+void debug_print(uint tag, int value) { debug_print(tag, ivec4(value, 0, 0, 0), 1u); }
+void debug_print(uint tag, ivec2 value) { debug_print(tag, ivec4(value, 0, 0), 2u); }
+void debug_print(uint tag, ivec3 value) { debug_print(tag, ivec4(value, 0), 3u); }
+void debug_print(uint tag, ivec4 value) { debug_print(tag, value, 4u); }
+void debug_print(uint tag, float value) { debug_print(tag, vec4(value, 0.0, 0.0, 0.0), 1u); }
+void debug_print(uint tag, vec2 value) { debug_print(tag, vec4(value, 0.0, 0.0), 2u); }
+void debug_print(uint tag, vec3 value) { debug_print(tag, vec4(value, 0.0), 3u); }
+void debug_print(uint tag, vec4 value) { debug_print(tag, value, 4u); }
+
+#define DEBUG_PRINT(value) debug_print(uint(__LINE__), value)
+
+#else
+
+#define DEBUG_PRINT(value)
+#define debug_print(tag, value)
+
+#endif
+
+#endif
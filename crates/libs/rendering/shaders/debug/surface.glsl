@@ -0,0 +1,19 @@
+#ifndef DEBUG_SURFACE
+#define DEBUG_SURFACE
+
+#include "debug/common.glsl"
+
+layout(set = 0, binding = 1, rgba16f) uniform readonly image2D gbuffer_normal;
+
+layout(location = 0) in vec3 world_position;
+layout(location = 1) in vec3 world_normal;
+layout(location = 2) in vec2 uv;
+
+// Whether the fragment lies on the closest opaque surface of the G-buffer, there is no depth buffer
+bool visible() {
+    const float opaque_distance = imageLoad(gbuffer_normal, ivec2(gl_FragCoord.xy)).w;
+    const float fragment_distance = distance(world_position, uniforms.camera_position.xyz);
+    return opaque_distance == 0.0 || fragment_distance <= opaque_distance * 1.001 + 0.01;
+}
+
+#endif
@@ -0,0 +1,14 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "debug/surface.glsl"
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    if (!visible()) {
+        discard;
+    }
+
+    out_color = vec4(fract(uv), 0.0, 1.0);
+}
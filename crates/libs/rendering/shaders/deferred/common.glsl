@@ -0,0 +1,48 @@
+#ifndef DEFERRED_COMMON
+#define DEFERRED_COMMON
+
+#include "raytracing/lighting.glsl"
+
+// Matches `DEFERRED_WORKGROUP_SIZE` in src/deferred.rs
+#define WORKGROUP_SIZE 8
+
+// Matches `DeferredUniform` in src/deferred.rs
+layout(set = 0, binding = 3) uniform DeferredUniform {
+    mat4 world_from_view;
+    mat4 view_from_clip;
+    mat4 clip_from_world;
+    // the camera of the previous frame, for motion vectors
+    mat4 previous_clip_from_world;
+    vec2 view_size;
+    float environment_intensity;
+    uint environment_map;
+    // whether the sun shadow mask was traced this frame
+    uint sun_shadows;
+} uniforms;
+
+layout(set = 0, binding = 9) uniform samplerCube environment_cube;
+
+// Radiance of the environment map in a direction, the procedural sky without a cube map
+vec3 environment(vec3 direction) {
+    const vec3 radiance = uniforms.environment_map != 0u ? texture(environment_cube, direction).rgb : sky(direction);
+    return radiance * uniforms.environment_intensity;
+}
+
+vec3 camera_position() {
+    return (uniforms.world_from_view * vec4(0.0, 0.0, 0.0, 1.0)).xyz;
+}
+
+// World space direction of the primary ray through the center of a pixel
+vec3 primary_ray_direction(uvec2 pixel, uvec2 size) {
+    const vec2 uv = (vec2(pixel) + 0.5) / vec2(size) * 2.0 - 1.0;
+    const vec4 target = uniforms.view_from_clip * vec4(uv, 1.0, 1.0);
+    return normalize((uniforms.world_from_view * vec4(normalize(target.xyz / target.w), 0.0)).xyz);
+}
+
+// Screen space motion in pixels from the previous frame of a fragment at `pixel`
+vec2 motion_vector(vec2 pixel, vec4 previous_clip) {
+    const vec2 previous_pixel = (previous_clip.xy / previous_clip.w * 0.5 + 0.5) * uniforms.view_size;
+    return pixel - previous_pixel;
+}
+
+#endif
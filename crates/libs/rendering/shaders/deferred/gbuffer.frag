@@ -0,0 +1,28 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "deferred/mesh.glsl"
+
+layout(location = 0) in vec3 world_position;
+layout(location = 1) in vec3 world_normal;
+layout(location = 2) in vec4 previous_clip_position;
+
+// Matches the G-buffer formats in src/deferred.rs
+layout(location = 0) out vec4 out_albedo;
+layout(location = 1) out vec4 out_normal;
+layout(location = 2) out vec4 out_material;
+layout(location = 3) out vec2 out_velocity;
+
+void main() {
+    const vec3 to_camera = camera_position() - world_position;
+    vec3 normal = normalize(world_normal);
+    // back faces of double sided materials
+    if (dot(normal, to_camera) < 0.0) {
+        normal = -normal;
+    }
+
+    out_albedo = vec4(object.base_color.rgb, object.metallic);
+    out_normal = vec4(normal, length(to_camera));
+    out_material = object.emissive;
+    out_velocity = motion_vector(gl_FragCoord.xy, previous_clip_position);
+}
@@ -0,0 +1,28 @@
+#ifndef DEFERRED_MESH
+#define DEFERRED_MESH
+
+#include "deferred/common.glsl"
+
+// Matches `DeferredPushConstants` in src/deferred/pipeline.rs
+layout(push_constant) uniform DeferredPushConstants {
+    mat4 world_from_object;
+    vec4 base_color;
+    // emissive radiance in rgb, roughness in alpha
+    vec4 emissive;
+    float metallic;
+    uint joint_offset;
+    // index of the instance in `previous_transforms`
+    uint instance_index;
+} object;
+
+// Matches `GpuInstanceMotion` in src/raytracing/gpu_scene.rs, in the order of the ray tracing scene
+layout(set = 0, binding = 6, std430) readonly buffer InstanceMotions {
+    layout(row_major) mat4x3 previous_transforms[];
+};
+
+// Object to world matrix of the previous frame, the last row is filled in from the identity
+mat4 previous_world_from_object() {
+    return mat4(previous_transforms[object.instance_index]);
+}
+
+#endif
@@ -0,0 +1,19 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "deferred/mesh.glsl"
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 world_position;
+layout(location = 1) out vec3 world_normal;
+layout(location = 2) out vec4 previous_clip_position;
+
+void main() {
+    const vec4 world = object.world_from_object * vec4(position, 1.0);
+    world_position = world.xyz;
+    world_normal = mat3(object.world_from_object) * normal;
+    previous_clip_position = uniforms.previous_clip_from_world * previous_world_from_object() * vec4(position, 1.0);
+    gl_Position = uniforms.clip_from_world * world;
+}
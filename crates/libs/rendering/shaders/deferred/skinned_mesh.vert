@@ -0,0 +1,28 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#define SKIN_PALETTE_SET 0
+#define SKIN_PALETTE_BINDING 4
+
+#include "deferred/mesh.glsl"
+#include "skinning/skinning.glsl"
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 3) in uvec4 joints;
+layout(location = 4) in vec4 weights;
+
+layout(location = 0) out vec3 world_position;
+layout(location = 1) out vec3 world_normal;
+layout(location = 2) out vec4 previous_clip_position;
+
+void main() {
+    const mat4 mesh_from_vertex = skin_matrix(object.joint_offset, joints, weights);
+    const mat4 world_from_mesh = object.world_from_object * mesh_from_vertex;
+    const vec4 world = world_from_mesh * vec4(position, 1.0);
+    world_position = world.xyz;
+    world_normal = mat3(world_from_mesh) * normal;
+    // the palette only holds the current pose, the motion of the joints is not tracked
+    previous_clip_position = uniforms.previous_clip_from_world * previous_world_from_object() * mesh_from_vertex * vec4(position, 1.0);
+    gl_Position = uniforms.clip_from_world * world;
+}
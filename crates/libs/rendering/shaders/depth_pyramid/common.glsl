@@ -0,0 +1,18 @@
+#ifndef DEPTH_PYRAMID_COMMON
+#define DEPTH_PYRAMID_COMMON
+
+// Matches `DEPTH_PYRAMID_WORKGROUP_SIZE` in src/depth_pyramid.rs
+#define WORKGROUP_SIZE 8
+
+// Nearest distance in r, farthest in g
+layout(set = 0, binding = 1, rg32f) uniform writeonly image2D level;
+
+// Matches `DepthPyramidPushConstants` in src/depth_pyramid/pipeline.rs
+layout(push_constant) uniform DepthPyramidPushConstants {
+    uvec2 source_size;
+    uvec2 size;
+} pyramid;
+
+const float INFINITE_DISTANCE = uintBitsToFloat(0x7f800000u);
+
+#endif
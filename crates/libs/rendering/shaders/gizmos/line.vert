@@ -0,0 +1,17 @@
+#version 460
+
+// Matches `GizmoPushConstants` in src/gizmos/pipeline.rs
+layout(push_constant) uniform GizmoPushConstants {
+    mat4 clip_from_world;
+} view;
+
+// Matches `GizmoVertex` in src/gizmos.rs
+layout(location = 0) in vec4 position;
+layout(location = 1) in vec4 color;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    out_color = color;
+    gl_Position = view.clip_from_world * position;
+}
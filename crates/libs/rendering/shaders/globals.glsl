@@ -0,0 +1,16 @@
+#ifndef GLOBALS
+#define GLOBALS
+
+// Matches `GlobalsUniform` in src/globals.rs, std140 layout.
+// Bound at `GLOBALS_SET` by every pass whose pipeline layout comes from `globals::pipeline_layout`.
+layout(set = 3, binding = 0) uniform Globals {
+    // Seconds since startup, wrapped every hour
+    float time;
+    float delta_time;
+    uint frame_count;
+    uint random_seed;
+    // Pixels of the primary window
+    vec2 resolution;
+} globals;
+
+#endif
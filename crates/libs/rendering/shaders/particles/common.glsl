@@ -0,0 +1,38 @@
+#ifndef PARTICLES_COMMON
+#define PARTICLES_COMMON
+
+#define WORKGROUP_SIZE 64
+
+// Matches `GpuParticle` in src/particles.rs
+struct Particle {
+    // position in xyz, age in w
+    vec4 position_age;
+    // velocity in xyz, lifetime in w
+    vec4 velocity_lifetime;
+};
+
+// Matches `ParticleDrawKey` in src/particles.rs
+struct DrawKey {
+    float depth;
+    uint index;
+};
+
+// The draw shaders only read the pools
+#ifndef PARTICLE_BUFFER_ACCESS
+#define PARTICLE_BUFFER_ACCESS
+#endif
+
+layout(set = 0, binding = 0, std430) PARTICLE_BUFFER_ACCESS buffer Particles {
+    Particle particles[];
+};
+
+layout(set = 0, binding = 1, std430) PARTICLE_BUFFER_ACCESS buffer DrawKeys {
+    DrawKey keys[];
+};
+
+// Dead particles, never spawned ones included, have no lifetime left
+bool alive(Particle particle) {
+    return particle.position_age.w < particle.velocity_lifetime.w;
+}
+
+#endif
@@ -0,0 +1,22 @@
+#ifndef PARTICLES_DRAW
+#define PARTICLES_DRAW
+
+#define PARTICLE_BUFFER_ACCESS readonly
+#include "particles/common.glsl"
+
+// Matches `ParticleEmitterUniform` in src/particles.rs
+layout(set = 0, binding = 2) uniform ParticleEmitterUniform {
+    vec4 start_color;
+    vec4 end_color;
+    float size;
+} emitter;
+
+// Matches `ParticleDrawPushConstants` in src/particles/pipeline.rs
+layout(push_constant) uniform ParticleDrawPushConstants {
+    mat4 clip_from_world;
+    vec4 camera_position;
+    vec4 camera_right;
+    vec4 camera_up;
+} view;
+
+#endif
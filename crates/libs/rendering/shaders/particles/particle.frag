@@ -0,0 +1,28 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "particles/draw.glsl"
+
+layout(set = 1, binding = 0, rgba16f) uniform readonly image2D gbuffer_normal;
+
+layout(location = 0) in vec3 world_position;
+layout(location = 1) in vec2 corner;
+layout(location = 2) in float normalized_age;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    // there is no depth buffer, the G-buffer stores the distance of the opaque surface
+    const float opaque_distance = imageLoad(gbuffer_normal, ivec2(gl_FragCoord.xy)).w;
+    if (opaque_distance != 0.0 && distance(world_position, view.camera_position.xyz) > opaque_distance) {
+        discard;
+    }
+
+    // a soft disc
+    const float falloff = 1.0 - smoothstep(0.5, 1.0, length(corner));
+    const vec4 color = mix(emitter.start_color, emitter.end_color, clamp(normalized_age, 0.0, 1.0));
+    if (color.a * falloff <= 0.0) {
+        discard;
+    }
+    out_color = vec4(color.rgb, color.a * falloff);
+}
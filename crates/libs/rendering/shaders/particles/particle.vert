@@ -0,0 +1,25 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "particles/draw.glsl"
+
+layout(location = 0) out vec3 world_position;
+layout(location = 1) out vec2 corner;
+layout(location = 2) out float normalized_age;
+
+// Two triangles of a quad, drawn without vertex buffer
+const vec2 CORNERS[6] = vec2[](
+    vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+    vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+);
+
+void main() {
+    // instances are the alive particles, sorted back to front
+    const Particle particle = particles[keys[gl_InstanceIndex].index];
+
+    corner = CORNERS[gl_VertexIndex];
+    normalized_age = particle.position_age.w / particle.velocity_lifetime.w;
+    world_position = particle.position_age.xyz
+        + (view.camera_right.xyz * corner.x + view.camera_up.xyz * corner.y) * emitter.size;
+    gl_Position = view.clip_from_world * vec4(world_position, 1.0);
+}
@@ -0,0 +1,49 @@
+#ifndef PATH_TRACING_COMMON
+#define PATH_TRACING_COMMON
+
+#include "raytracing/lighting.glsl"
+
+// Matches `PathTracingUniform` in src/path_tracing.rs
+layout(set = 0, binding = 3) uniform PathTracingUniform {
+    mat4 world_from_view;
+    mat4 view_from_clip;
+    mat4 previous_clip_from_world;
+    uint frame_index;
+    uint samples_per_frame;
+    uint max_bounces;
+    uint write_motion_vectors;
+} uniforms;
+
+struct RayPayload {
+    vec3 radiance;
+    vec3 throughput;
+    vec3 origin;
+    vec3 direction;
+    uint seed;
+    bool done;
+    // set by the raygen shader for the first ray of a pixel, the hit shader then fills previous_position
+    bool primary;
+    // hit position in the previous frame, or the ray direction with w = 0 when nothing was hit
+    vec4 previous_position;
+};
+
+// PCG hash, see "Hash Functions for GPU Rendering" (Jarzynski & Olano)
+uint pcg(inout uint state) {
+    state = state * 747796405u + 2891336453u;
+    uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+float random(inout uint state) {
+    return float(pcg(state)) / 4294967296.0;
+}
+
+vec3 cosine_sample_hemisphere(vec3 normal, inout uint seed) {
+    float r = sqrt(random(seed));
+    float phi = 2.0 * PI * random(seed);
+    vec3 tangent = normalize(abs(normal.x) > 0.9 ? cross(normal, vec3(0.0, 1.0, 0.0)) : cross(normal, vec3(1.0, 0.0, 0.0)));
+    vec3 bitangent = cross(normal, tangent);
+    return normalize(r * cos(phi) * tangent + r * sin(phi) * bitangent + sqrt(max(0.0, 1.0 - r * r)) * normal);
+}
+
+#endif
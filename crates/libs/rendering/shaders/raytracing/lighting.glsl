@@ -0,0 +1,14 @@
+#ifndef RAYTRACING_LIGHTING
+#define RAYTRACING_LIGHTING
+
+#define PI 3.14159265359
+
+const vec3 SUN_DIRECTION = normalize(vec3(0.4, 1.0, 0.3));
+const vec3 SUN_RADIANCE = vec3(3.0);
+
+vec3 sky(vec3 direction) {
+    float t = 0.5 * (direction.y + 1.0);
+    return mix(vec3(1.0), vec3(0.5, 0.7, 1.0), t);
+}
+
+#endif
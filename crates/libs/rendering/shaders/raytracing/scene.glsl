@@ -0,0 +1,95 @@
+#ifndef RAYTRACING_SCENE
+#define RAYTRACING_SCENE
+
+#extension GL_EXT_buffer_reference2 : require
+#extension GL_EXT_scalar_block_layout : require
+#extension GL_EXT_shader_explicit_arithmetic_types_int64 : require
+
+#ifndef SCENE_SET
+#define SCENE_SET 0
+#endif
+#ifndef SCENE_INSTANCE_BINDING
+#define SCENE_INSTANCE_BINDING 4
+#endif
+#ifndef SCENE_MATERIAL_BINDING
+#define SCENE_MATERIAL_BINDING 5
+#endif
+#ifndef SCENE_MOTION_BINDING
+#define SCENE_MOTION_BINDING 6
+#endif
+
+// Matches `MeshVertex` in src/mesh.rs
+struct MeshVertex {
+    vec3 position;
+    vec3 normal;
+    vec2 uv;
+};
+
+// Matches `GpuMaterial` in src/raytracing/gpu_scene.rs
+struct GpuMaterial {
+    vec4 base_color;
+    vec3 emissive;
+    float roughness;
+    float metallic;
+};
+
+// Matches `GpuSceneInstance` in src/raytracing/gpu_scene.rs
+struct GpuSceneInstance {
+    uint64_t vertex_address;
+    uint64_t index_address;
+    uint material_index;
+    uint _padding;
+};
+
+// Matches `GpuInstanceMotion` in src/raytracing/gpu_scene.rs
+struct GpuInstanceMotion {
+    // row-major like the TLAS instance transform
+    layout(row_major) mat4x3 previous_world_from_object;
+};
+
+layout(buffer_reference, scalar) readonly buffer MeshVertices { MeshVertex vertices[]; };
+layout(buffer_reference, scalar) readonly buffer MeshIndices { uint indices[]; };
+
+layout(set = SCENE_SET, binding = SCENE_INSTANCE_BINDING, scalar) readonly buffer SceneInstances {
+    GpuSceneInstance scene_instances[];
+};
+layout(set = SCENE_SET, binding = SCENE_MATERIAL_BINDING, std430) readonly buffer SceneMaterials {
+    GpuMaterial scene_materials[];
+};
+layout(set = SCENE_SET, binding = SCENE_MOTION_BINDING, scalar) readonly buffer SceneInstanceMotions {
+    GpuInstanceMotion scene_instance_motions[];
+};
+
+bool instance_has_geometry(GpuSceneInstance instance) {
+    return instance.vertex_address != 0ul;
+}
+
+uvec3 triangle_indices(GpuSceneInstance instance, uint primitive) {
+    if (instance.index_address == 0ul) {
+        return uvec3(primitive * 3u, primitive * 3u + 1u, primitive * 3u + 2u);
+    }
+    MeshIndices indices = MeshIndices(instance.index_address);
+    return uvec3(
+        indices.indices[primitive * 3u],
+        indices.indices[primitive * 3u + 1u],
+        indices.indices[primitive * 3u + 2u]
+    );
+}
+
+// Interpolated object space vertex of the hit triangle
+MeshVertex interpolate_vertex(GpuSceneInstance instance, uint primitive, vec2 attribs) {
+    const uvec3 index = triangle_indices(instance, primitive);
+    MeshVertices vertices = MeshVertices(instance.vertex_address);
+    const MeshVertex v0 = vertices.vertices[index.x];
+    const MeshVertex v1 = vertices.vertices[index.y];
+    const MeshVertex v2 = vertices.vertices[index.z];
+    const vec3 barycentrics = vec3(1.0 - attribs.x - attribs.y, attribs.x, attribs.y);
+
+    MeshVertex result;
+    result.position = v0.position * barycentrics.x + v1.position * barycentrics.y + v2.position * barycentrics.z;
+    result.normal = normalize(v0.normal * barycentrics.x + v1.normal * barycentrics.y + v2.normal * barycentrics.z);
+    result.uv = v0.uv * barycentrics.x + v1.uv * barycentrics.y + v2.uv * barycentrics.z;
+    return result;
+}
+
+#endif
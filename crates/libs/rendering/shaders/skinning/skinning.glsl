@@ -0,0 +1,18 @@
+#ifndef SKINNING
+#define SKINNING
+
+// The including shader defines SKIN_PALETTE_SET and SKIN_PALETTE_BINDING.
+// Matches `SkinPalettes` in src/skinning.rs
+layout(std430, set = SKIN_PALETTE_SET, binding = SKIN_PALETTE_BINDING) readonly buffer SkinPalette {
+    mat4 joint_matrices[];
+} skin_palette;
+
+// Blend of the joint matrices influencing a vertex, weights sum to 1.
+mat4 skin_matrix(uint joint_offset, uvec4 joints, vec4 weights) {
+    return weights.x * skin_palette.joint_matrices[joint_offset + joints.x]
+        + weights.y * skin_palette.joint_matrices[joint_offset + joints.y]
+        + weights.z * skin_palette.joint_matrices[joint_offset + joints.z]
+        + weights.w * skin_palette.joint_matrices[joint_offset + joints.w];
+}
+
+#endif
@@ -0,0 +1,12 @@
+#version 460
+
+layout(set = 1, binding = 0) uniform sampler2D sprite_texture;
+
+layout(location = 0) in vec2 uv;
+layout(location = 1) in vec4 color;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    out_color = texture(sprite_texture, uv) * color;
+}
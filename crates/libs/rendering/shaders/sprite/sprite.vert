@@ -0,0 +1,33 @@
+#version 460
+
+// Matches `SpriteViewUniform` in src/sprite.rs
+layout(set = 0, binding = 0) uniform SpriteViewUniform {
+    mat4 clip_from_world;
+} view;
+
+// Matches `SpriteInstance` in src/sprite.rs
+layout(location = 0) in vec4 world_from_quad_x;
+layout(location = 1) in vec4 world_from_quad_y;
+layout(location = 2) in vec4 world_from_quad_z;
+layout(location = 3) in vec4 uv_rect;
+layout(location = 4) in vec4 color;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_color;
+
+// Two triangles of the unit quad, as texture corners from the top left
+const vec2 CORNERS[6] = vec2[](
+    vec2(0.0, 0.0), vec2(0.0, 1.0), vec2(1.0, 1.0),
+    vec2(0.0, 0.0), vec2(1.0, 1.0), vec2(1.0, 0.0)
+);
+
+void main() {
+    const vec2 corner = CORNERS[gl_VertexIndex];
+    // textures go down, the sprite plane goes up
+    const vec4 position = vec4(corner.x - 0.5, 0.5 - corner.y, 0.0, 1.0);
+    const vec3 world_position = position * mat3x4(world_from_quad_x, world_from_quad_y, world_from_quad_z);
+
+    out_uv = mix(uv_rect.xy, uv_rect.zw, corner);
+    out_color = color;
+    gl_Position = view.clip_from_world * vec4(world_position, 1.0);
+}
@@ -0,0 +1,27 @@
+#ifndef TERRAIN_COMMON
+#define TERRAIN_COMMON
+
+// Matches `TerrainUniform` in src/terrain.rs
+layout(set = 0, binding = 2) uniform TerrainUniform {
+    mat4 world_from_terrain;
+    mat4 normal_from_terrain;
+    // base color in rgb, roughness in alpha
+    vec4 layers[4];
+    vec2 size;
+    float height_scale;
+    uint splat_map;
+    uvec2 heightmap_size;
+} terrain;
+
+// Matches `TerrainViewUniform` in src/terrain.rs
+layout(set = 1, binding = 0) uniform TerrainViewUniform {
+    mat4 clip_from_world;
+    mat4 view_from_clip;
+    mat4 clip_from_view;
+    vec4 camera_position;
+    // the camera of the previous frame, for motion vectors
+    mat4 previous_clip_from_world;
+    vec2 view_size;
+} view;
+
+#endif
@@ -0,0 +1,20 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "terrain/common.glsl"
+
+// Displaced on the CPU in terrain space
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+
+layout(location = 0) out vec3 world_position;
+layout(location = 1) out vec3 world_normal;
+layout(location = 2) out vec2 terrain_uv;
+
+void main() {
+    world_position = (terrain.world_from_terrain * vec4(position, 1.0)).xyz;
+    world_normal = mat3(terrain.normal_from_terrain) * normal;
+    terrain_uv = uv;
+    gl_Position = view.clip_from_world * vec4(world_position, 1.0);
+}
@@ -0,0 +1,50 @@
+#ifndef TERRAIN_CULL
+#define TERRAIN_CULL
+
+#include "terrain/culling.glsl"
+
+// Matches `TERRAIN_CULL_WORKGROUP_SIZE` in src/terrain/occlusion.rs
+layout(local_size_x = 64) in;
+
+layout(set = 0, binding = 0, std430) readonly buffer Chunks {
+    CulledTerrainChunk chunks[];
+};
+
+layout(set = 0, binding = 1, std430) writeonly buffer DrawList {
+    uint draw_list[];
+};
+
+// 1 for the slots of the chunks seen by the view last frame
+layout(set = 0, binding = 2, std430) buffer Visibility {
+    uint visibility[];
+};
+
+// Matches `vk::DrawIndexedIndirectCommand`, a draw per batch of the first phase then of the second one
+struct DrawIndexedIndirectCommand {
+    uint index_count;
+    uint instance_count;
+    uint first_index;
+    int vertex_offset;
+    uint first_instance;
+};
+
+layout(set = 0, binding = 3, std430) buffer Draws {
+    DrawIndexedIndirectCommand draws[];
+};
+
+// Matches `TerrainCullPushConstants` in src/terrain/occlusion.rs
+layout(push_constant) uniform TerrainCullPushConstants {
+    mat4 clip_from_world;
+    vec4 camera_position;
+    uint chunk_count;
+    uint batch_count;
+} cull;
+
+// Append a chunk to the draw of its batch in a phase
+void draw_chunk(uint phase, uint index) {
+    const uint draw = phase * cull.batch_count + chunks[index].batch;
+    const uint instance = atomicAdd(draws[draw].instance_count, 1u);
+    draw_list[draws[draw].first_instance + instance] = index;
+}
+
+#endif
@@ -0,0 +1,19 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "terrain/grid.glsl"
+#include "terrain/culling.glsl"
+
+layout(set = 2, binding = 0, std430) readonly buffer Chunks {
+    CulledTerrainChunk chunks[];
+};
+
+// Chunks drawn by each phase, the first instance of a draw points at its batch
+layout(set = 2, binding = 1, std430) readonly buffer DrawList {
+    uint draw_list[];
+};
+
+void main() {
+    const CulledTerrainChunk chunk = chunks[draw_list[gl_InstanceIndex]];
+    displace_grid(chunk.offset, chunk.extent, chunk.skirt_depth);
+}
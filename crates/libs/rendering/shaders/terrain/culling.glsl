@@ -0,0 +1,20 @@
+#ifndef TERRAIN_CULLING
+#define TERRAIN_CULLING
+
+// Matches `GpuCulledTerrainChunk` in src/terrain/occlusion.rs
+struct CulledTerrainChunk {
+    vec3 aabb_min;
+    // index of the chunk in the visibility of the view, stable across frames
+    uint slot;
+    vec3 aabb_max;
+    // index of the terrain of the chunk in the draws of a phase
+    uint batch;
+    vec2 offset;
+    float extent;
+    float skirt_depth;
+    // 1 when the chunk was not selected last frame, its slot is stale
+    uint fresh;
+    uint _padding[3];
+};
+
+#endif
@@ -0,0 +1,21 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "terrain/common.glsl"
+
+layout(set = 1, binding = 1, rgba16f) uniform readonly image2D gbuffer_normal;
+
+// Depth of the surface stored in the G-buffer, along the primary ray of the pixel
+void main() {
+    const ivec2 pixel = ivec2(gl_FragCoord.xy);
+    const float hit_distance = imageLoad(gbuffer_normal, pixel).w;
+    if (hit_distance == 0.0) {
+        gl_FragDepth = 1.0;
+        return;
+    }
+
+    const vec2 ndc = (vec2(pixel) + 0.5) / vec2(imageSize(gbuffer_normal)) * 2.0 - 1.0;
+    const vec4 target = view.view_from_clip * vec4(ndc, 1.0, 1.0);
+    const vec4 clip = view.clip_from_view * vec4(normalize(target.xyz / target.w) * hit_distance, 1.0);
+    gl_FragDepth = clip.z / clip.w;
+}
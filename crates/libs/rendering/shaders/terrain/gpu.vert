@@ -0,0 +1,15 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "terrain/grid.glsl"
+
+// Matches `TerrainChunkPushConstants` in src/terrain/pipeline.rs
+layout(push_constant) uniform TerrainChunkPushConstants {
+    vec2 offset;
+    float extent;
+    float skirt_depth;
+} chunk;
+
+void main() {
+    displace_grid(chunk.offset, chunk.extent, chunk.skirt_depth);
+}
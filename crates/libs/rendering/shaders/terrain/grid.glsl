@@ -0,0 +1,49 @@
+#ifndef TERRAIN_GRID
+#define TERRAIN_GRID
+
+#include "terrain/common.glsl"
+
+layout(set = 0, binding = 0, std430) readonly buffer Heights {
+    float heights[];
+};
+
+// grid position in xy, 1 in z on skirt vertices
+layout(location = 0) in vec3 grid;
+
+layout(location = 0) out vec3 world_position;
+layout(location = 1) out vec3 world_normal;
+layout(location = 2) out vec2 terrain_uv;
+
+float texel(ivec2 position) {
+    position = clamp(position, ivec2(0), ivec2(terrain.heightmap_size) - 1);
+    return heights[position.y * terrain.heightmap_size.x + position.x];
+}
+
+// Bilinearly filtered height at a normalized terrain position, matches `Heightmap::sample`
+float height(vec2 uv) {
+    const vec2 position = clamp(uv, 0.0, 1.0) * vec2(terrain.heightmap_size - 1u);
+    const ivec2 corner = ivec2(position);
+    const vec2 fraction = fract(position);
+    const float top = mix(texel(corner), texel(corner + ivec2(1, 0)), fraction.x);
+    const float bottom = mix(texel(corner + ivec2(0, 1)), texel(corner + ivec2(1, 1)), fraction.x);
+    return mix(top, bottom, fraction.y);
+}
+
+// Displace the grid vertex of the chunk at `offset` with an `extent` by the heightmap
+void displace_grid(vec2 offset, float extent, float skirt_depth) {
+    const vec2 uv = offset + grid.xy * extent;
+    const vec2 step = 1.0 / vec2(terrain.heightmap_size);
+    const float dx = (height(uv + vec2(step.x, 0.0)) - height(uv - vec2(step.x, 0.0))) * terrain.height_scale;
+    const float dz = (height(uv + vec2(0.0, step.y)) - height(uv - vec2(0.0, step.y))) * terrain.height_scale;
+    const vec3 normal = vec3(-dx / (2.0 * step.x * terrain.size.x), 1.0, -dz / (2.0 * step.y * terrain.size.y));
+
+    const float y = height(uv) * terrain.height_scale - grid.z * skirt_depth;
+    const vec3 position = vec3(uv.x * terrain.size.x, y, uv.y * terrain.size.y);
+
+    world_position = (terrain.world_from_terrain * vec4(position, 1.0)).xyz;
+    world_normal = mat3(terrain.normal_from_terrain) * normal;
+    terrain_uv = uv;
+    gl_Position = view.clip_from_world * vec4(world_position, 1.0);
+}
+
+#endif
@@ -0,0 +1,39 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "terrain/common.glsl"
+
+layout(set = 0, binding = 1) uniform sampler2D splat_map;
+
+layout(location = 0) in vec3 world_position;
+layout(location = 1) in vec3 world_normal;
+layout(location = 2) in vec2 terrain_uv;
+
+// Matches the G-buffer formats in src/deferred.rs
+layout(location = 0) out vec4 out_albedo;
+layout(location = 1) out vec4 out_normal;
+layout(location = 2) out vec4 out_material;
+layout(location = 3) out vec2 out_velocity;
+
+void main() {
+    vec4 weights = terrain.splat_map != 0u ? texture(splat_map, terrain_uv) : vec4(1.0, 0.0, 0.0, 0.0);
+    weights /= max(dot(weights, vec4(1.0)), 0.0001);
+    const vec4 layer = weights.x * terrain.layers[0]
+        + weights.y * terrain.layers[1]
+        + weights.z * terrain.layers[2]
+        + weights.w * terrain.layers[3];
+
+    vec3 normal = normalize(world_normal);
+    if (dot(normal, view.camera_position.xyz - world_position) < 0.0) {
+        normal = -normal;
+    }
+
+    out_albedo = vec4(layer.rgb, 0.0);
+    out_normal = vec4(normal, distance(world_position, view.camera_position.xyz));
+    out_material = vec4(vec3(0.0), layer.a);
+
+    // terrains are treated as static, only the motion of the camera is written
+    const vec4 previous_clip = view.previous_clip_from_world * vec4(world_position, 1.0);
+    const vec2 previous_pixel = (previous_clip.xy / previous_clip.w * 0.5 + 0.5) * view.view_size;
+    out_velocity = gl_FragCoord.xy - previous_pixel;
+}
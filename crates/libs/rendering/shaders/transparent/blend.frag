@@ -0,0 +1,14 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "transparent/shading.glsl"
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    if (behind_opaque()) {
+        discard;
+    }
+
+    out_color = vec4(shade(), object.base_color.a);
+}
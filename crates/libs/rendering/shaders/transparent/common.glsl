@@ -0,0 +1,20 @@
+#ifndef TRANSPARENT_COMMON
+#define TRANSPARENT_COMMON
+
+#include "raytracing/lighting.glsl"
+
+// Matches `TransparentUniform` in src/transparent.rs
+layout(set = 0, binding = 0) uniform TransparentUniform {
+    mat4 clip_from_world;
+    vec4 camera_position;
+} uniforms;
+
+// Matches `TransparentPushConstants` in src/transparent/pipeline.rs
+layout(push_constant) uniform TransparentPushConstants {
+    mat4 world_from_object;
+    vec4 base_color;
+    vec4 emissive;
+    uint joint_offset;
+} object;
+
+#endif
@@ -0,0 +1,17 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "transparent/common.glsl"
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+
+layout(location = 0) out vec3 world_position;
+layout(location = 1) out vec3 world_normal;
+
+void main() {
+    const vec4 world = object.world_from_object * vec4(position, 1.0);
+    world_position = world.xyz;
+    world_normal = mat3(object.world_from_object) * normal;
+    gl_Position = uniforms.clip_from_world * world;
+}
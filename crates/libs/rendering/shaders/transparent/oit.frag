@@ -0,0 +1,21 @@
+#version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include "transparent/shading.glsl"
+
+layout(location = 0) out vec4 out_accumulation;
+layout(location = 1) out float out_revealage;
+
+void main() {
+    if (behind_opaque()) {
+        discard;
+    }
+
+    // weighted blended OIT (McGuire and Bavoil 2013), closer and more opaque surfaces weigh more
+    const float alpha = object.base_color.a;
+    const float depth = distance(world_position, uniforms.camera_position.xyz);
+    const float weight = clamp(pow(min(1.0, alpha * 10.0) + 0.01, 3.0) * 1e3 / (1e-5 + pow(depth / 200.0, 4.0)), 1e-2, 3e3);
+
+    out_accumulation = vec4(shade() * alpha, alpha) * weight;
+    out_revealage = alpha;
+}
@@ -0,0 +1,17 @@
+#version 460
+
+layout(set = 0, binding = 2, rgba16f) uniform readonly image2D oit_accumulation;
+layout(set = 0, binding = 3, r16f) uniform readonly image2D oit_revealage;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    const ivec2 pixel = ivec2(gl_FragCoord.xy);
+    const float revealage = imageLoad(oit_revealage, pixel).r;
+    if (revealage >= 1.0) {
+        discard;
+    }
+
+    const vec4 accumulation = imageLoad(oit_accumulation, pixel);
+    out_color = vec4(accumulation.rgb / max(accumulation.a, 1e-5), 1.0 - revealage);
+}
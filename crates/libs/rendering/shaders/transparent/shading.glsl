@@ -0,0 +1,29 @@
+#ifndef TRANSPARENT_SHADING
+#define TRANSPARENT_SHADING
+
+#include "transparent/common.glsl"
+
+layout(set = 0, binding = 1, rgba16f) uniform readonly image2D gbuffer_normal;
+
+layout(location = 0) in vec3 world_position;
+layout(location = 1) in vec3 world_normal;
+
+// Whether the fragment is hidden by the opaque surface stored in the G-buffer, there is no depth buffer
+bool behind_opaque() {
+    const float opaque_distance = imageLoad(gbuffer_normal, ivec2(gl_FragCoord.xy)).w;
+    return opaque_distance != 0.0 && distance(world_position, uniforms.camera_position.xyz) > opaque_distance;
+}
+
+// Sun and sky lighting of the surface, unshadowed
+vec3 shade() {
+    vec3 normal = normalize(world_normal);
+    const vec3 view = normalize(uniforms.camera_position.xyz - world_position);
+    if (dot(normal, view) < 0.0) {
+        normal = -normal;
+    }
+
+    const vec3 diffuse = SUN_RADIANCE * max(dot(normal, SUN_DIRECTION), 0.0) / PI + sky(normal) * 0.3;
+    return object.base_color.rgb * diffuse + object.emissive.rgb;
+}
+
+#endif
@@ -0,0 +1,14 @@
+#ifndef VIEW
+#define VIEW
+
+// Matches `ViewUniform` in src/view.rs, std140 layout.
+// Declare the uniform block with it, e.g. `layout(set = 1, binding = 0) uniform ViewUniformBlock { ViewUniform view; };`,
+// and index the matrices with `gl_ViewIndex` in multiview passes.
+struct ViewUniform {
+    mat4 clip_from_world[2];
+    mat4 world_from_view[2];
+    mat4 clip_from_view[2];
+    uint view_count;
+};
+
+#endif
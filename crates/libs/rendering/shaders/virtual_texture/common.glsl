@@ -0,0 +1,50 @@
+#ifndef VIRTUAL_TEXTURE_COMMON
+#define VIRTUAL_TEXTURE_COMMON
+
+// Sampling a virtual texture from a material shader, define before including:
+//   VT_SET: descriptor set of the virtual texture
+//   VT_BINDING: first of its 3 bindings, see `VirtualTextureState::descriptor_writes` in src/virtual_texture.rs
+
+// Matches `VIRTUAL_TEXTURE_PAGE_SIZE` in src/virtual_texture.rs
+#define VT_PAGE_SIZE 128
+#define VT_RESIDENT_BIT 0x80000000u
+
+#ifdef VT_SET
+
+// A texel per page, the slot of the page in the atlas when resident, else the average color of the page
+layout(set = VT_SET, binding = VT_BINDING) uniform usampler2D vt_page_table;
+layout(set = VT_SET, binding = VT_BINDING + 1) uniform sampler2D vt_atlas;
+// Pages sampled since the last analysis, written by `vt_request`
+layout(set = VT_SET, binding = VT_BINDING + 2, std430) buffer VirtualTextureFeedback {
+    uint pages[];
+} vt_feedback;
+
+uvec2 vt_page_of(vec2 uv) {
+    const uvec2 pages = uvec2(textureSize(vt_page_table, 0));
+    return min(uvec2(clamp(uv, 0.0, 1.0) * vec2(pages)), pages - 1);
+}
+
+// Ask for the page of `uv` to be streamed in, cheap enough to call on every sample
+void vt_request(vec2 uv) {
+    const uvec2 page = vt_page_of(uv);
+    vt_feedback.pages[page.y * uint(textureSize(vt_page_table, 0).x) + page.x] = 1;
+}
+
+vec4 vt_sample(vec2 uv) {
+    const uvec2 page = vt_page_of(uv);
+    const uint entry = texelFetch(vt_page_table, ivec2(page), 0).r;
+    if ((entry & VT_RESIDENT_BIT) == 0) {
+        return vec4(unpackUnorm4x8(entry).rgb, 1.0);
+    }
+
+    const uvec2 slot = uvec2((entry >> 16) & 0x7fff, entry & 0xffff);
+    const vec2 pages = vec2(textureSize(vt_page_table, 0));
+    // pages have no border, keep the bilinear footprint inside the page
+    const vec2 in_page = clamp(fract(clamp(uv, 0.0, 1.0) * pages) * VT_PAGE_SIZE, 0.5, VT_PAGE_SIZE - 0.5);
+    const vec2 atlas_uv = (vec2(slot * VT_PAGE_SIZE) + in_page) / vec2(textureSize(vt_atlas, 0));
+    return textureLod(vt_atlas, atlas_uv, 0.0);
+}
+
+#endif
+
+#endif
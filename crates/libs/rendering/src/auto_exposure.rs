@@ -0,0 +1,245 @@
+mod node;
+mod pipeline;
+
+pub use node::*;
+pub use pipeline::*;
+
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Component, Entity, IntoSystemConfigs, Query, ReflectComponent, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_reflect::Reflect;
+use bevy_time::Time;
+use bevy_utils::{EntityHashMap, HashSet};
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{Buffer, Context, DescriptorPool, DescriptorSet, WriteDescriptorSet, WriteDescriptorSetKind};
+use crate::{ExtractSchedule, Render, RenderApp, RenderSet};
+use crate::camera::ExtractedCamera;
+use crate::extract::{ExtractComponent, ExtractComponentPlugin, FrameContext};
+use crate::graph::RenderGraphApp;
+use crate::graph::node::ViewNodeRunner;
+use crate::prelude::Extract;
+use crate::tonemapping::{TONEMAPPED_GRAPHS, TONEMAPPING_NODE};
+use crate::upscaling::UPSCALE_NODE;
+use crate::view::ViewTarget;
+
+/// Node counting the pixels of a view per luminance, see [`LuminanceHistogramNode`].
+pub const LUMINANCE_HISTOGRAM_NODE: &str = "luminance_histogram";
+/// Node adapting the exposure of a view to its histogram, see [`AutoExposureNode`].
+pub const AUTO_EXPOSURE_NODE: &str = "auto_exposure";
+
+/// Bins of a luminance histogram, the first one counts black pixels.
+pub const LUMINANCE_HISTOGRAM_BINS: u32 = 256;
+/// Threads along each axis of a workgroup of the histogram shader, one per bin.
+pub const LUMINANCE_HISTOGRAM_WORKGROUP_SIZE: u32 = 16;
+
+/// Adapt the exposure of a camera to the average luminance it sees, like an eye getting used to the dark.
+///
+/// The adapted exposure multiplies the [`ViewExposure`](crate::view::ViewExposure) and the
+/// [`ColorGrading`](crate::tonemapping::ColorGrading) compensation in the tonemapper.
+#[derive(Component, ExtractComponent, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct AutoExposure {
+    /// Darkest average luminance adapted to, in stops, darker scenes stay dark
+    pub min_ev: f32,
+    /// Brightest average luminance adapted to, in stops
+    pub max_ev: f32,
+    /// Rate of the exponential adaptation per second, higher adapts faster
+    pub speed: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            min_ev: -8.0,
+            max_ev: 8.0,
+            speed: 1.5,
+        }
+    }
+}
+
+pub struct AutoExposurePlugin;
+
+impl Plugin for AutoExposurePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<AutoExposure>()
+            .add_plugins(ExtractComponentPlugin::<AutoExposure>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<AutoExposurePipeline>()
+                .init_resource::<AutoExposureTime>()
+                .init_resource::<AutoExposureViews>()
+                .add_systems(ExtractSchedule, extract_auto_exposure_time)
+                .add_systems(
+                    Render, (
+                        prepare_auto_exposure_pipeline.in_set(RenderSet::PrepareResources),
+                        prepare_auto_exposure_views.in_set(RenderSet::PrepareBindGroups),
+                    )
+                );
+            for graph in TONEMAPPED_GRAPHS {
+                render_app
+                    .add_render_graph_node::<ViewNodeRunner<LuminanceHistogramNode>>(graph, LUMINANCE_HISTOGRAM_NODE)
+                    .add_render_graph_node::<ViewNodeRunner<AutoExposureNode>>(graph, AUTO_EXPOSURE_NODE)
+                    .add_render_graph_edges(graph, &[UPSCALE_NODE, LUMINANCE_HISTOGRAM_NODE, AUTO_EXPOSURE_NODE, TONEMAPPING_NODE]);
+            }
+        }
+    }
+}
+
+/// Seconds the exposure adapts by this frame, the delta of the virtual [`Time`] of the main world.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct AutoExposureTime {
+    pub delta_seconds: f32,
+}
+
+fn extract_auto_exposure_time(mut adaptation_time: ResMut<AutoExposureTime>, time: Extract<Res<Time>>) {
+    adaptation_time.delta_seconds = time.delta_seconds();
+}
+
+/// Matches `Exposure` in `shaders/auto_exposure/common.glsl`, kept on the GPU across frames.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct GpuExposure {
+    /// Adapted average luminance in stops
+    pub log_luminance: f32,
+    /// Linear scale of the HDR color read by the tonemapper
+    pub exposure: f32,
+    /// 0 until the first adaptation, which jumps straight to the metered luminance
+    pub adapted: u32,
+}
+
+impl Default for GpuExposure {
+    fn default() -> Self {
+        Self {
+            log_luminance: 0.0,
+            exposure: 1.0,
+            adapted: 0,
+        }
+    }
+}
+
+// SAFETY: plain 32 bit fields without implicit padding
+unsafe impl Zeroable for GpuExposure {}
+unsafe impl Pod for GpuExposure {}
+
+/// Histogram and adapted exposure of a view, lost when the view stops using [`AutoExposure`].
+pub struct AutoExposureViewState {
+    pub push_constants: AutoExposurePushConstants,
+    /// [`LUMINANCE_HISTOGRAM_BINS`] pixel counts, cleared again by the resolve pass
+    pub histogram: Buffer,
+    /// A [`GpuExposure`]
+    pub exposure: Buffer,
+    _descriptor_pool: DescriptorPool,
+    pub(crate) descriptor_set: DescriptorSet,
+}
+
+impl AutoExposureViewState {
+    fn new(context: &Context, pipeline: &AutoExposurePipelineResources) -> anyhow::Result<Self> {
+        let histogram = context.create_buffer(
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            MemoryLocation::CpuToGpu,
+            (LUMINANCE_HISTOGRAM_BINS as usize * std::mem::size_of::<u32>()) as _,
+        )?;
+        histogram.copy_data_to_buffer(&[0u32; LUMINANCE_HISTOGRAM_BINS as usize])?;
+        let exposure = context.create_buffer(
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of::<GpuExposure>() as _,
+        )?;
+        exposure.copy_data_to_buffer(&[GpuExposure::default()])?;
+
+        let descriptor_pool = context.create_descriptor_pool(1, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 2,
+            },
+        ])?;
+        let descriptor_set = descriptor_pool.allocate_set(pipeline.descriptor_set_layout())?;
+
+        Ok(Self {
+            push_constants: AutoExposurePushConstants::default(),
+            histogram,
+            exposure,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct AutoExposureViews(pub(crate) EntityHashMap<Entity, AutoExposureViewState>);
+
+impl AutoExposureViews {
+    pub fn get(&self, entity: Entity) -> Option<&AutoExposureViewState> {
+        self.0.get(&entity)
+    }
+}
+
+pub(crate) fn prepare_auto_exposure_views(
+    mut views: ResMut<AutoExposureViews>,
+    pipeline: Res<AutoExposurePipeline>,
+    time: Res<AutoExposureTime>,
+    cameras: Query<(Entity, &ExtractedCamera, &ViewTarget, &AutoExposure)>,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(pipeline) = pipeline.resources() else {
+        views.0.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+
+    for (entity, camera, target, auto_exposure) in cameras.iter() {
+        if !TONEMAPPED_GRAPHS.contains(&camera.render_graph) {
+            continue;
+        }
+        alive.insert(entity);
+        if !views.0.contains_key(&entity) {
+            match AutoExposureViewState::new(context, pipeline) {
+                Ok(state) => {
+                    views.0.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create auto exposure view resources: {err}");
+                    continue;
+                }
+            }
+        }
+        let state = views.0.get_mut(&entity).unwrap();
+
+        state.push_constants = AutoExposurePushConstants {
+            size: target.size.to_array(),
+            min_ev: auto_exposure.min_ev,
+            // an empty range would divide by zero when binning
+            max_ev: auto_exposure.max_ev.max(auto_exposure.min_ev + 1.0),
+            speed: auto_exposure.speed.max(0.0),
+            delta_seconds: time.delta_seconds,
+        };
+        // the view target is recreated on resize, rewrite the set every frame
+        state.descriptor_set.update(&[
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &target.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: &state.histogram },
+            },
+            WriteDescriptorSet {
+                binding: 2,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: &state.exposure },
+            },
+        ]);
+    }
+
+    views.0.retain(|entity, _| alive.contains(entity));
+}
@@ -0,0 +1,116 @@
+use ash::vk;
+use bevy_ecs::prelude::World;
+use avalanche_hlvk::{BufferBarrier, ImageBarrier};
+use crate::auto_exposure::{AutoExposure, AutoExposurePipeline, AutoExposureViews, LUMINANCE_HISTOGRAM_WORKGROUP_SIZE};
+use crate::extract::FrameContext;
+use crate::globals::Globals;
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::ViewNode;
+use crate::view::ViewTarget;
+
+/// Counts the pixels of the [`ViewTarget`] of a view with [`AutoExposure`] per luminance.
+#[derive(Default)]
+pub struct LuminanceHistogramNode;
+
+impl ViewNode for LuminanceHistogramNode {
+    type ViewQuery = &'static ViewTarget;
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        target: &ViewTarget,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pipeline) = world.resource::<AutoExposurePipeline>().resources() else {
+            return Ok(());
+        };
+        let Some(state) = world.resource::<AutoExposureViews>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.layout);
+
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &target.image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::MEMORY_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+        }]);
+        command_buffer.bind_compute_pipeline(&pipeline.histogram);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::COMPUTE, &pipeline.layout, 0, &[&state.descriptor_set]);
+        command_buffer.push_constants(
+            &pipeline.layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            bytemuck::bytes_of(&state.push_constants),
+        );
+        let [width, height] = state.push_constants.size;
+        command_buffer.dispatch(
+            width.div_ceil(LUMINANCE_HISTOGRAM_WORKGROUP_SIZE),
+            height.div_ceil(LUMINANCE_HISTOGRAM_WORKGROUP_SIZE),
+            1,
+        );
+        command_buffer.pipeline_buffer_barriers(&[BufferBarrier {
+            buffer: &state.histogram,
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+        }]);
+
+        Ok(())
+    }
+}
+
+/// Moves the adapted exposure of a view towards the average luminance of its histogram, read by the tonemapper.
+#[derive(Default)]
+pub struct AutoExposureNode;
+
+impl ViewNode for AutoExposureNode {
+    type ViewQuery = &'static AutoExposure;
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        _auto_exposure: &AutoExposure,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pipeline) = world.resource::<AutoExposurePipeline>().resources() else {
+            return Ok(());
+        };
+        let Some(state) = world.resource::<AutoExposureViews>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.layout);
+
+        command_buffer.bind_compute_pipeline(&pipeline.resolve);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::COMPUTE, &pipeline.layout, 0, &[&state.descriptor_set]);
+        command_buffer.push_constants(
+            &pipeline.layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            bytemuck::bytes_of(&state.push_constants),
+        );
+        // a single workgroup with a thread per bin
+        command_buffer.dispatch(1, 1, 1);
+        command_buffer.pipeline_buffer_barriers(&[BufferBarrier {
+            buffer: &state.exposure,
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+        }]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::prelude::{Query, Res, ResMut, Resource, With};
+use bevy_log::error;
+use avalanche_hlvk::{ComputePipeline, Context, DescriptorSetLayout, PipelineLayout};
+use crate::auto_exposure::AutoExposure;
+use crate::extract::FrameContext;
+use crate::globals;
+use crate::shader::ShaderDirectory;
+
+pub(crate) const HISTOGRAM_SHADER: &str = "auto_exposure/histogram.comp";
+pub(crate) const RESOLVE_SHADER: &str = "auto_exposure/resolve.comp";
+
+/// Matches `AutoExposurePushConstants` in `shaders/auto_exposure/bindings.glsl`, shared by both passes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AutoExposurePushConstants {
+    /// Pixels of the view target
+    pub size: [u32; 2],
+    /// Luminance range of the histogram in stops, also the range adapted to
+    pub min_ev: f32,
+    pub max_ev: f32,
+    pub speed: f32,
+    pub delta_seconds: f32,
+}
+
+// SAFETY: plain 32 bit fields without implicit padding
+unsafe impl Zeroable for AutoExposurePushConstants {}
+unsafe impl Pod for AutoExposurePushConstants {}
+
+pub struct AutoExposurePipelineResources {
+    /// Reflected from both shaders, set 0 binds the target, histogram and exposure of a view
+    pub layout: PipelineLayout,
+    pub histogram: ComputePipeline,
+    pub resolve: ComputePipeline,
+}
+
+impl AutoExposurePipelineResources {
+    #[inline]
+    pub fn descriptor_set_layout(&self) -> &DescriptorSetLayout {
+        &self.layout.descriptor_set_layouts()[0]
+    }
+}
+
+/// The auto exposure pipelines, created the first time a camera uses [`AutoExposure`].
+#[derive(Resource, Default)]
+pub enum AutoExposurePipeline {
+    #[default]
+    Uninitialized,
+    Ready(Box<AutoExposurePipelineResources>),
+    /// Creation failed, usually shaders are missing.
+    Failed,
+}
+
+impl AutoExposurePipeline {
+    pub fn resources(&self) -> Option<&AutoExposurePipelineResources> {
+        match self {
+            AutoExposurePipeline::Ready(resources) => Some(resources),
+            _ => None,
+        }
+    }
+}
+
+fn create_resources(context: &Context, shaders: &ShaderDirectory) -> anyhow::Result<AutoExposurePipelineResources> {
+    let stages = [
+        shaders.load(context, HISTOGRAM_SHADER, vk::ShaderStageFlags::COMPUTE)?,
+        shaders.load(context, RESOLVE_SHADER, vk::ShaderStageFlags::COMPUTE)?,
+    ];
+    let layout = globals::pipeline_layout(&stages)?;
+    anyhow::ensure!(globals::pass_set_count(&stages) == 1, "auto exposure shaders must only use set 0");
+    let [histogram, resolve] = &stages;
+
+    Ok(AutoExposurePipelineResources {
+        histogram: context.create_compute_pipeline(&layout, histogram)?,
+        resolve: context.create_compute_pipeline(&layout, resolve)?,
+        layout,
+    })
+}
+
+pub(crate) fn prepare_auto_exposure_pipeline(
+    mut pipeline: ResMut<AutoExposurePipeline>,
+    shaders: Res<ShaderDirectory>,
+    cameras: Query<(), With<AutoExposure>>,
+    frame_context: Res<FrameContext>,
+) {
+    if !matches!(*pipeline, AutoExposurePipeline::Uninitialized) || cameras.is_empty() {
+        return;
+    }
+
+    *pipeline = match create_resources(frame_context.render_context(), &shaders) {
+        Ok(resources) => AutoExposurePipeline::Ready(Box::new(resources)),
+        Err(err) => {
+            error!("Failed to create auto exposure pipeline: {err}");
+            AutoExposurePipeline::Failed
+        }
+    };
+}
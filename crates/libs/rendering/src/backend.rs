@@ -0,0 +1,124 @@
+//! Backend independent interface of the GPU.
+//!
+//! Code written against these traits doesn't depend on Vulkan, so another backend (wgpu, Metal)
+//! only has to implement them. [`ActiveBackend`] is the backend selected by cargo features.
+
+mod vulkan;
+
+pub use vulkan::*;
+
+use bitflags::bitflags;
+
+#[cfg(not(feature = "vulkan"))]
+compile_error!("a render backend must be enabled, only the `vulkan` feature is available for now");
+
+/// Backend chosen at compile time.
+#[cfg(feature = "vulkan")]
+pub type ActiveBackend = VulkanBackend;
+
+pub trait RenderBackend: Send + Sync + Sized + 'static {
+    type Device: RenderDevice<Self>;
+    type Buffer: Send + Sync;
+    type Image: Send + Sync;
+    /// Pipelines are created through the backend specific API and are opaque to the others.
+    type Pipeline: Send + Sync;
+    type CommandEncoder<'a>: CommandEncoder<Self>;
+
+    const NAME: &'static str;
+}
+
+pub trait RenderDevice<B: RenderBackend>: Send + Sync {
+    fn create_buffer(&self, descriptor: &BufferDescriptor) -> anyhow::Result<B::Buffer>;
+
+    fn create_image(&self, descriptor: &ImageDescriptor) -> anyhow::Result<B::Image>;
+
+    /// Copy `data` at the start of a buffer created with [`MemoryLocation::CpuToGpu`].
+    fn write_buffer(&self, buffer: &B::Buffer, data: &[u8]) -> anyhow::Result<()>;
+
+    /// Block until all submitted work is finished.
+    fn wait_idle(&self) -> anyhow::Result<()>;
+}
+
+/// Records the commands of a frame.
+pub trait CommandEncoder<B: RenderBackend> {
+    fn copy_buffer(&mut self, src: &B::Buffer, dst: &B::Buffer);
+
+    /// The whole image is written, it must be ready to be copied to.
+    fn copy_buffer_to_image(&mut self, src: &B::Buffer, dst: &B::Image);
+
+    fn bind_pipeline(&mut self, pipeline: &B::Pipeline);
+
+    fn draw(&mut self, vertex_count: u32);
+
+    fn dispatch(&mut self, group_count_x: u32, group_count_y: u32, group_count_z: u32);
+}
+
+bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct BufferUsages: u32 {
+        const COPY_SRC = 1 << 0;
+        const COPY_DST = 1 << 1;
+        const VERTEX = 1 << 2;
+        const INDEX = 1 << 3;
+        const UNIFORM = 1 << 4;
+        const STORAGE = 1 << 5;
+        const INDIRECT = 1 << 6;
+        /// The GPU address of the buffer can be queried, required by ray tracing inputs and bindless tables.
+        const SHADER_DEVICE_ADDRESS = 1 << 7;
+        /// Read as geometry or instances when building acceleration structures.
+        const ACCELERATION_STRUCTURE_INPUT = 1 << 8;
+        /// Backing memory of acceleration structures.
+        const ACCELERATION_STRUCTURE_STORAGE = 1 << 9;
+    }
+}
+
+bitflags! {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct ImageUsages: u32 {
+        const COPY_SRC = 1 << 0;
+        const COPY_DST = 1 << 1;
+        const SAMPLED = 1 << 2;
+        const STORAGE = 1 << 3;
+        const COLOR_ATTACHMENT = 1 << 4;
+        const DEPTH_STENCIL_ATTACHMENT = 1 << 5;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MemoryLocation {
+    #[default]
+    GpuOnly,
+    /// Host visible memory written by the CPU, e.g. staging and uniform buffers.
+    CpuToGpu,
+    /// Host visible memory read back by the CPU.
+    GpuToCpu,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    Rgba8Unorm,
+    Rgba8Srgb,
+    Bgra8Unorm,
+    Bgra8Srgb,
+    Rgba16Float,
+    Rgba32Float,
+    R32Float,
+    Depth32Float,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferDescriptor {
+    pub size: u64,
+    pub usage: BufferUsages,
+    pub memory_location: MemoryLocation,
+}
+
+/// Two dimensional image with a single mip level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImageDescriptor {
+    pub width: u32,
+    pub height: u32,
+    pub format: ImageFormat,
+    pub usage: ImageUsages,
+    pub memory_location: MemoryLocation,
+}
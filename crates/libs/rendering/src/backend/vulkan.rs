@@ -0,0 +1,199 @@
+use std::sync::Arc;
+use ash::vk;
+use avalanche_hlvk::{CommandBuffer, ComputePipeline, RasterPipeline, RayTracingPipeline};
+use crate::backend::{BufferDescriptor, BufferUsages, CommandEncoder, ImageDescriptor, ImageFormat, ImageUsages, MemoryLocation, RenderBackend, RenderDevice};
+use crate::context::RenderingContext;
+use crate::extract::FrameContext;
+use crate::prelude::{Buffer, Image};
+
+pub struct VulkanBackend;
+
+impl RenderBackend for VulkanBackend {
+    type Device = RenderingContext;
+    type Buffer = Buffer;
+    type Image = Image;
+    type Pipeline = VulkanPipeline;
+    type CommandEncoder<'a> = VulkanCommandEncoder<'a>;
+
+    const NAME: &'static str = "Vulkan";
+}
+
+#[derive(Clone)]
+pub enum VulkanPipeline {
+    Raster(Arc<RasterPipeline>),
+    Compute(Arc<ComputePipeline>),
+    RayTracing(Arc<RayTracingPipeline>),
+}
+
+impl RenderDevice<VulkanBackend> for RenderingContext {
+    fn create_buffer(&self, descriptor: &BufferDescriptor) -> anyhow::Result<Buffer> {
+        let buffer = self.context.create_buffer(
+            descriptor.usage.into(),
+            descriptor.memory_location.into(),
+            descriptor.size,
+        )?;
+        Ok(buffer.into())
+    }
+
+    fn create_image(&self, descriptor: &ImageDescriptor) -> anyhow::Result<Image> {
+        let image = self.context.create_image(
+            descriptor.usage.into(),
+            descriptor.memory_location.into(),
+            descriptor.format.into(),
+            descriptor.width,
+            descriptor.height,
+        )?;
+        Ok(image.into())
+    }
+
+    fn write_buffer(&self, buffer: &Buffer, data: &[u8]) -> anyhow::Result<()> {
+        buffer.copy_data_to_buffer(data)
+    }
+
+    fn wait_idle(&self) -> anyhow::Result<()> {
+        self.context.device_wait_idle()
+    }
+}
+
+pub struct VulkanCommandEncoder<'a> {
+    command_buffer: &'a CommandBuffer,
+}
+
+impl<'a> VulkanCommandEncoder<'a> {
+    pub fn new(command_buffer: &'a CommandBuffer) -> Self {
+        Self { command_buffer }
+    }
+
+    /// Escape hatch for commands without a backend independent equivalent.
+    pub fn command_buffer(&self) -> &'a CommandBuffer {
+        self.command_buffer
+    }
+}
+
+impl CommandEncoder<VulkanBackend> for VulkanCommandEncoder<'_> {
+    fn copy_buffer(&mut self, src: &Buffer, dst: &Buffer) {
+        self.command_buffer.copy_buffer(src, dst);
+    }
+
+    fn copy_buffer_to_image(&mut self, src: &Buffer, dst: &Image) {
+        self.command_buffer.copy_buffer_to_image(src, dst, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+    }
+
+    fn bind_pipeline(&mut self, pipeline: &VulkanPipeline) {
+        match pipeline {
+            VulkanPipeline::Raster(pipeline) => self.command_buffer.bind_raster_pipeline(pipeline),
+            VulkanPipeline::Compute(pipeline) => self.command_buffer.bind_compute_pipeline(pipeline),
+            VulkanPipeline::RayTracing(pipeline) => self.command_buffer.bind_rt_pipeline(pipeline),
+        }
+    }
+
+    fn draw(&mut self, vertex_count: u32) {
+        self.command_buffer.draw(vertex_count);
+    }
+
+    fn dispatch(&mut self, group_count_x: u32, group_count_y: u32, group_count_z: u32) {
+        self.command_buffer.dispatch(group_count_x, group_count_y, group_count_z);
+    }
+}
+
+impl FrameContext {
+    /// Encoder recording into the main command buffer of the frame.
+    pub fn command_encoder(&self) -> Option<VulkanCommandEncoder<'_>> {
+        self.command_buffer(0).map(VulkanCommandEncoder::new)
+    }
+}
+
+impl From<BufferUsages> for vk::BufferUsageFlags {
+    fn from(value: BufferUsages) -> Self {
+        let mut flags = vk::BufferUsageFlags::empty();
+        for (usage, flag) in [
+            (BufferUsages::COPY_SRC, vk::BufferUsageFlags::TRANSFER_SRC),
+            (BufferUsages::COPY_DST, vk::BufferUsageFlags::TRANSFER_DST),
+            (BufferUsages::VERTEX, vk::BufferUsageFlags::VERTEX_BUFFER),
+            (BufferUsages::INDEX, vk::BufferUsageFlags::INDEX_BUFFER),
+            (BufferUsages::UNIFORM, vk::BufferUsageFlags::UNIFORM_BUFFER),
+            (BufferUsages::STORAGE, vk::BufferUsageFlags::STORAGE_BUFFER),
+            (BufferUsages::INDIRECT, vk::BufferUsageFlags::INDIRECT_BUFFER),
+            (BufferUsages::SHADER_DEVICE_ADDRESS, vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS),
+            (BufferUsages::ACCELERATION_STRUCTURE_INPUT, vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR),
+            (BufferUsages::ACCELERATION_STRUCTURE_STORAGE, vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR),
+        ] {
+            if value.contains(usage) {
+                flags |= flag;
+            }
+        }
+        flags
+    }
+}
+
+impl From<ImageUsages> for vk::ImageUsageFlags {
+    fn from(value: ImageUsages) -> Self {
+        let mut flags = vk::ImageUsageFlags::empty();
+        for (usage, flag) in [
+            (ImageUsages::COPY_SRC, vk::ImageUsageFlags::TRANSFER_SRC),
+            (ImageUsages::COPY_DST, vk::ImageUsageFlags::TRANSFER_DST),
+            (ImageUsages::SAMPLED, vk::ImageUsageFlags::SAMPLED),
+            (ImageUsages::STORAGE, vk::ImageUsageFlags::STORAGE),
+            (ImageUsages::COLOR_ATTACHMENT, vk::ImageUsageFlags::COLOR_ATTACHMENT),
+            (ImageUsages::DEPTH_STENCIL_ATTACHMENT, vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT),
+        ] {
+            if value.contains(usage) {
+                flags |= flag;
+            }
+        }
+        flags
+    }
+}
+
+impl From<MemoryLocation> for gpu_allocator::MemoryLocation {
+    fn from(value: MemoryLocation) -> Self {
+        match value {
+            MemoryLocation::GpuOnly => gpu_allocator::MemoryLocation::GpuOnly,
+            MemoryLocation::CpuToGpu => gpu_allocator::MemoryLocation::CpuToGpu,
+            MemoryLocation::GpuToCpu => gpu_allocator::MemoryLocation::GpuToCpu,
+        }
+    }
+}
+
+impl From<ImageFormat> for vk::Format {
+    fn from(value: ImageFormat) -> Self {
+        match value {
+            ImageFormat::Rgba8Unorm => vk::Format::R8G8B8A8_UNORM,
+            ImageFormat::Rgba8Srgb => vk::Format::R8G8B8A8_SRGB,
+            ImageFormat::Bgra8Unorm => vk::Format::B8G8R8A8_UNORM,
+            ImageFormat::Bgra8Srgb => vk::Format::B8G8R8A8_SRGB,
+            ImageFormat::Rgba16Float => vk::Format::R16G16B16A16_SFLOAT,
+            ImageFormat::Rgba32Float => vk::Format::R32G32B32A32_SFLOAT,
+            ImageFormat::R32Float => vk::Format::R32_SFLOAT,
+            ImageFormat::Depth32Float => vk::Format::D32_SFLOAT,
+        }
+    }
+}
+
+#[test]
+fn test_buffer_usage_flags() {
+    let usages = [
+        (BufferUsages::COPY_SRC, vk::BufferUsageFlags::TRANSFER_SRC),
+        (BufferUsages::COPY_DST, vk::BufferUsageFlags::TRANSFER_DST),
+        (BufferUsages::VERTEX, vk::BufferUsageFlags::VERTEX_BUFFER),
+        (BufferUsages::INDEX, vk::BufferUsageFlags::INDEX_BUFFER),
+        (BufferUsages::UNIFORM, vk::BufferUsageFlags::UNIFORM_BUFFER),
+        (BufferUsages::STORAGE, vk::BufferUsageFlags::STORAGE_BUFFER),
+        (BufferUsages::INDIRECT, vk::BufferUsageFlags::INDIRECT_BUFFER),
+        (BufferUsages::SHADER_DEVICE_ADDRESS, vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS),
+        (BufferUsages::ACCELERATION_STRUCTURE_INPUT, vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR),
+        (BufferUsages::ACCELERATION_STRUCTURE_STORAGE, vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR),
+    ];
+    for (usage, flag) in usages {
+        assert_eq!(vk::BufferUsageFlags::from(usage), flag, "{usage:?}");
+    }
+    // every usage is mapped
+    assert_eq!(usages.iter().fold(BufferUsages::empty(), |all, (usage, _)| all | *usage), BufferUsages::all());
+
+    let combined = BufferUsages::STORAGE | BufferUsages::SHADER_DEVICE_ADDRESS;
+    assert_eq!(
+        vk::BufferUsageFlags::from(combined),
+        vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+    );
+    assert_eq!(vk::BufferUsageFlags::from(BufferUsages::empty()), vk::BufferUsageFlags::empty());
+}
@@ -0,0 +1,274 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{IntoSystemConfigs, Local, Res, ResMut, Resource};
+use bevy_ecs::schedule::Schedules;
+use bevy_log::warn;
+use bevy_utils::HashSet;
+use bevy_utils::tracing::field::{Field, Visit};
+use bevy_utils::tracing::span::{Attributes, Id};
+use bevy_utils::tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+use crate::{ExtractSchedule, Render, RenderApp, RenderSet};
+
+/// Sets of the [`Render`] schedule whose CPU time is measured, in the order they run.
+const TIMED_SETS: [RenderSet; 7] = [
+    RenderSet::ExtractCommands,
+    RenderSet::ManageViews,
+    RenderSet::Queue,
+    RenderSet::PhaseSort,
+    RenderSet::Prepare,
+    RenderSet::Render,
+    RenderSet::Cleanup,
+];
+
+/// Runs kept by the [`SystemTimingLayer`] until the watchdog reads them, older ones are dropped.
+const MAX_SYSTEM_RUNS: usize = 8192;
+
+/// CPU time the render app may spend extracting and preparing a frame, lives in the render world.
+///
+/// Extract blocks the main app, it has to stay short for rendering to overlap the next update.
+#[derive(Resource, Clone, Debug)]
+pub struct FrameBudget {
+    pub extract: Duration,
+    pub prepare: Duration,
+    /// Systems listed in a report, slowest first
+    pub top_systems: usize,
+    /// Reports are logged at most this often
+    pub report_interval: Duration,
+}
+
+impl Default for FrameBudget {
+    fn default() -> Self {
+        Self {
+            extract: Duration::from_millis(2),
+            prepare: Duration::from_millis(4),
+            top_systems: 5,
+            report_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// CPU time of the extract and of the [`Render`] sets of the last frame, lives in the render world.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct RenderSetTimings {
+    extract: Option<(Instant, Duration)>,
+    /// Instants the timed sets started at, and the last one finished at
+    boundaries: [Option<Instant>; TIMED_SETS.len() + 1],
+}
+
+impl RenderSetTimings {
+    /// Start and duration of the last [`ExtractSchedule`] run.
+    #[inline]
+    pub fn extract(&self) -> Option<(Instant, Duration)> {
+        self.extract
+    }
+
+    /// Start and duration of a top level [`RenderSet`] in the last frame, sub sets aren't timed.
+    pub fn set(&self, set: &RenderSet) -> Option<(Instant, Duration)> {
+        let index = TIMED_SETS.iter().position(|timed| timed == set)?;
+        let (Some(start), Some(end)) = (self.boundaries[index], self.boundaries[index + 1]) else {
+            return None;
+        };
+        Some((start, end.saturating_duration_since(start)))
+    }
+
+    pub(crate) fn record_extract(&mut self, start: Instant) {
+        self.extract = Some((start, start.elapsed()));
+    }
+}
+
+/// A system run recorded by the [`SystemTimingLayer`].
+#[derive(Clone, Debug)]
+pub struct SystemRun {
+    pub name: String,
+    pub start: Instant,
+    pub duration: Duration,
+}
+
+/// System runs recorded by a [`SystemTimingLayer`] since the watchdog last read them.
+///
+/// Insert a clone into the render world for budget reports to list the slowest systems.
+#[derive(Resource, Clone, Default)]
+pub struct SystemRuns(Arc<Mutex<Vec<SystemRun>>>);
+
+impl SystemRuns {
+    pub fn take(&self) -> Vec<SystemRun> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// Times the `system` spans bevy emits around every system run with its `trace` feature.
+pub struct SystemTimingLayer {
+    runs: SystemRuns,
+}
+
+impl SystemTimingLayer {
+    pub fn new(runs: SystemRuns) -> Self {
+        Self { runs }
+    }
+}
+
+/// Extension of the `system` spans.
+struct SystemSpan {
+    name: String,
+    entered_at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct SystemNameVisitor(Option<String>);
+
+impl Visit for SystemNameVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "name" && self.0.is_none() {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SystemTimingLayer {
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, context: Context<'_, S>) {
+        if attributes.metadata().name() != "system" {
+            return;
+        }
+        let mut visitor = SystemNameVisitor::default();
+        attributes.record(&mut visitor);
+        if let (Some(name), Some(span)) = (visitor.0, context.span(id)) {
+            span.extensions_mut().insert(SystemSpan { name, entered_at: None });
+        }
+    }
+
+    fn on_enter(&self, id: &Id, context: Context<'_, S>) {
+        if let Some(span) = context.span(id) {
+            if let Some(system) = span.extensions_mut().get_mut::<SystemSpan>() {
+                system.entered_at = Some(Instant::now());
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &Id, context: Context<'_, S>) {
+        let Some(span) = context.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        let Some(system) = extensions.get_mut::<SystemSpan>() else {
+            return;
+        };
+        let Some(start) = system.entered_at.take() else {
+            return;
+        };
+
+        let mut runs = self.runs.0.lock().unwrap();
+        if runs.len() >= MAX_SYSTEM_RUNS {
+            // nothing reads them, e.g. before the render app runs
+            runs.clear();
+        }
+        runs.push(SystemRun {
+            name: system.name.clone(),
+            start,
+            duration: start.elapsed(),
+        });
+    }
+}
+
+/// Names of the systems of the [`Render`] schedule, collected while it isn't running.
+#[derive(Resource, Default)]
+struct RenderSystemNames(HashSet<String>);
+
+/// Measures the CPU time of the render app stages and logs a report when extract or prepare exceed their [`FrameBudget`].
+///
+/// Reports list the slowest systems when the runs are recorded by a [`SystemTimingLayer`],
+/// which needs the `trace` feature of bevy.
+pub struct FrameBudgetPlugin;
+
+impl Plugin for FrameBudgetPlugin {
+    fn build(&self, app: &mut App) {
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<FrameBudget>()
+            .init_resource::<RenderSetTimings>()
+            .init_resource::<RenderSystemNames>()
+            .add_systems(ExtractSchedule, collect_render_system_names);
+
+        for (boundary, set) in TIMED_SETS.iter().enumerate() {
+            let system = move |mut timings: ResMut<RenderSetTimings>| {
+                timings.boundaries[boundary] = Some(Instant::now());
+            };
+            let system = match boundary.checked_sub(1) {
+                Some(previous) => system.after(TIMED_SETS[previous].clone()).before(set.clone()),
+                None => system.before(set.clone()),
+            };
+            render_app.add_systems(Render, system);
+        }
+        render_app.add_systems(Render, check_frame_budget.after(RenderSet::Cleanup));
+    }
+}
+
+fn collect_render_system_names(schedules: Res<Schedules>, mut names: ResMut<RenderSystemNames>) {
+    let Some(schedule) = schedules.get(Render) else {
+        return;
+    };
+    if schedule.graph().systems().count() == names.0.len() {
+        return;
+    }
+    names.0 = schedule
+        .graph()
+        .systems()
+        .map(|(_, system, _)| system.name().to_string())
+        .collect();
+}
+
+fn check_frame_budget(
+    budget: Res<FrameBudget>,
+    mut timings: ResMut<RenderSetTimings>,
+    names: Res<RenderSystemNames>,
+    runs: Option<Res<SystemRuns>>,
+    mut last_report: Local<Option<Instant>>,
+) {
+    timings.boundaries[TIMED_SETS.len()] = Some(Instant::now());
+    let runs = runs.map(|runs| runs.take()).unwrap_or_default();
+    if last_report.is_some_and(|last_report| last_report.elapsed() < budget.report_interval) {
+        return;
+    }
+
+    // main app systems run during prepare, not while extracting
+    let stages = [
+        ("Extract", timings.extract(), budget.extract, false),
+        ("Prepare", timings.set(&RenderSet::Prepare), budget.prepare, true),
+    ];
+    for (stage, timing, limit, render_systems_only) in stages {
+        let Some((start, duration)) = timing.filter(|(_, duration)| *duration > limit) else {
+            continue;
+        };
+        *last_report = Some(Instant::now());
+
+        let end = start + duration;
+        let mut slowest = runs
+            .iter()
+            .filter(|run| run.start >= start && run.start < end)
+            .filter(|run| !render_systems_only || names.0.contains(&run.name))
+            .collect::<Vec<_>>();
+        slowest.sort_by_key(|run| std::cmp::Reverse(run.duration));
+        let slowest = slowest
+            .iter()
+            .take(budget.top_systems)
+            .map(|run| format!("{} ({:.2?})", run.name, run.duration))
+            .collect::<Vec<_>>();
+
+        if slowest.is_empty() {
+            warn!("{stage} took {duration:.2?}, over its {limit:.2?} budget");
+        } else {
+            warn!("{stage} took {duration:.2?}, over its {limit:.2?} budget, slowest systems: {}", slowest.join(", "));
+        }
+    }
+}
@@ -0,0 +1,389 @@
+mod clear_color;
+mod driver;
+
+pub use clear_color::*;
+pub use driver::*;
+
+use std::borrow::Cow;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{AnyOf, Commands, Component, Entity, Has, Local, Query, ReflectComponent, Res};
+use bevy_log::warn;
+use bevy_math::{Mat4, URect, UVec2, Vec2, Vec3};
+use bevy_reflect::Reflect;
+use bevy_time::{Fixed, Time};
+use bevy_transform::prelude::{GlobalTransform, Transform};
+use avalanche_asset::Assets;
+use avalanche_window::{PrimaryWindowComponent, WindowComponent};
+use crate::color::Color;
+use crate::{ExtractSchedule, RenderApp};
+use crate::graph::{RenderGraph, RenderLabel};
+use crate::deferred::DEFERRED_GRAPH;
+use crate::path_tracing::{path_tracing_supported, PATH_TRACING_GRAPH};
+use crate::sprite::SPRITE_GRAPH;
+use crate::render_target::{RenderTargetImage, WindowTarget};
+use crate::texture::Texture;
+use crate::interpolation::{interpolated_transform, interpolation_alpha, TransformInterpolation};
+use crate::prelude::{Extract, RenderingContext};
+
+/// Name of the [`CameraDriverNode`] inside the main [`RenderGraph`].
+pub const CAMERA_DRIVER: &str = "camera_driver";
+
+/// A view into the scene, rendered into the primary window, the window of a [`WindowTarget`] or a [`RenderTargetImage`].
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct Camera {
+    pub is_active: bool,
+    /// Cameras with a higher order are rendered later.
+    pub order: isize,
+    /// Region of the target the camera renders into, the whole target when `None`
+    pub viewport: Option<Viewport>,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            is_active: true,
+            order: 0,
+            viewport: None,
+        }
+    }
+}
+
+/// A rectangle of a camera target from its top left corner, e.g. a half of the window for split-screen.
+#[derive(Reflect, Clone, Copy, Debug, PartialEq)]
+pub enum Viewport {
+    /// In physical pixels
+    Pixels { origin: UVec2, size: UVec2 },
+    /// In fractions of the target size, kept across resizes
+    Normalized { origin: Vec2, size: Vec2 },
+}
+
+impl Viewport {
+    /// The rectangle in pixels of a target of `target_size`, clamped to the target.
+    pub fn to_pixels(&self, target_size: UVec2) -> URect {
+        let (origin, size) = match *self {
+            Viewport::Pixels { origin, size } => (origin, size),
+            Viewport::Normalized { origin, size } => {
+                let target_size = target_size.as_vec2();
+                ((origin * target_size).round().as_uvec2(), (size * target_size).round().as_uvec2())
+            }
+        };
+        let origin = origin.min(target_size);
+        URect::from_corners(origin, (origin + size).min(target_size))
+    }
+}
+
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct PerspectiveProjection {
+    /// Vertical field of view in radians
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for PerspectiveProjection {
+    fn default() -> Self {
+        Self {
+            fov: std::f32::consts::FRAC_PI_4,
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+}
+
+impl PerspectiveProjection {
+    /// Right handed projection with vulkan clip space (y down, depth in `[0, 1]`).
+    pub fn get_projection_matrix(&self, aspect_ratio: f32) -> Mat4 {
+        let mut projection = Mat4::perspective_rh(self.fov, aspect_ratio, self.near, self.far);
+        projection.y_axis.y *= -1.0;
+        projection
+    }
+}
+
+/// Orthographic projection of a [`Camera`], taking precedence over a [`PerspectiveProjection`] on the same entity.
+///
+/// The view spans the camera viewport size in pixels times `scale` world units, centered on the camera.
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct OrthographicProjection {
+    /// World units per pixel of the camera viewport
+    pub scale: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for OrthographicProjection {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            near: -1000.0,
+            far: 1000.0,
+        }
+    }
+}
+
+impl OrthographicProjection {
+    /// Right handed projection with vulkan clip space (y down, depth in `[0, 1]`).
+    pub fn get_projection_matrix(&self, viewport_size: UVec2) -> Mat4 {
+        let half_extent = viewport_size.as_vec2() * self.scale * 0.5;
+        let mut projection = Mat4::orthographic_rh(
+            -half_extent.x,
+            half_extent.x,
+            -half_extent.y,
+            half_extent.y,
+            self.near,
+            self.far,
+        );
+        projection.y_axis.y *= -1.0;
+        projection
+    }
+}
+
+/// Projection matrix of a camera rendering into a viewport of `viewport_size`, the orthographic projection taking precedence.
+pub fn camera_projection(
+    perspective: Option<&PerspectiveProjection>,
+    orthographic: Option<&OrthographicProjection>,
+    viewport_size: UVec2,
+) -> Option<Mat4> {
+    match (orthographic, perspective) {
+        (Some(orthographic), _) => Some(orthographic.get_projection_matrix(viewport_size)),
+        (None, Some(perspective)) => Some(perspective.get_projection_matrix(viewport_size.x as f32 / viewport_size.y as f32)),
+        (None, None) => None,
+    }
+}
+
+/// Renders a [`Camera`] from two eyes into the layers of a [`StereoViewTarget`](crate::view::StereoViewTarget),
+/// the left eye in layer 0.
+///
+/// The eyes are offset along the local X axis of the camera and share its projection, unless the camera has [`StereoEyePoses`].
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct StereoCamera {
+    /// Distance between the eyes in world units
+    pub interpupillary_distance: f32,
+}
+
+impl Default for StereoCamera {
+    fn default() -> Self {
+        Self {
+            interpupillary_distance: 0.063,
+        }
+    }
+}
+
+impl StereoCamera {
+    /// Position of the eyes relative to the camera, left first.
+    pub fn eye_offsets(&self) -> [Vec3; 2] {
+        let half = self.interpupillary_distance * 0.5;
+        [Vec3::new(-half, 0.0, 0.0), Vec3::new(half, 0.0, 0.0)]
+    }
+}
+
+/// Poses and projections of the eyes of a [`StereoCamera`] given by a runtime like OpenXR,
+/// replacing its offsets and the projection of the camera.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct StereoEyePoses {
+    /// Eye poses relative to the camera, left first
+    pub camera_from_eye: [Mat4; 2],
+    pub projection: [Mat4; 2],
+}
+
+/// The render sub graph driven for a [`Camera`].
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct CameraRenderGraph(Cow<'static, str>);
+
+/// The [`PATH_TRACING_GRAPH`] is the default camera graph,
+/// replaced by the [`DEFERRED_GRAPH`] on devices the path tracer can't run on.
+impl Default for CameraRenderGraph {
+    fn default() -> Self {
+        Self::new(PATH_TRACING_GRAPH.as_str())
+    }
+}
+
+impl CameraRenderGraph {
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        Self(name.into())
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Selects the built-in render graph of a camera, overriding its [`CameraRenderGraph`].
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum RenderPath {
+    #[default]
+    PathTracing,
+    /// Rasterized G-buffer shaded by compute passes, see [`DEFERRED_GRAPH`].
+    Deferred,
+    /// Batched [`Sprite`](crate::sprite::Sprite)s only, see [`SPRITE_GRAPH`].
+    Sprite2d,
+}
+
+impl RenderPath {
+    pub fn graph_name(&self) -> RenderLabel {
+        match self {
+            RenderPath::PathTracing => PATH_TRACING_GRAPH,
+            RenderPath::Deferred => DEFERRED_GRAPH,
+            RenderPath::Sprite2d => SPRITE_GRAPH,
+        }
+    }
+}
+
+/// Camera data of the current frame, living on the camera entity of the render world.
+#[derive(Component, Clone, Debug)]
+pub struct ExtractedCamera {
+    pub target_size: UVec2,
+    /// Region of the target rendered, the views of the camera have its size
+    pub viewport: URect,
+    pub world_from_view: Mat4,
+    pub projection: Mat4,
+    pub render_graph: RenderLabel,
+    pub order: isize,
+    /// Color the target is cleared to, its contents are kept when `None`
+    pub clear_color: Option<Color>,
+}
+
+/// Matrices of the eyes of a [`StereoCamera`], on the camera entity of the render world, left eye first.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ExtractedStereoViews {
+    pub world_from_view: [Mat4; 2],
+    pub projection: [Mat4; 2],
+}
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Camera>()
+            .register_type::<Viewport>()
+            .register_type::<PerspectiveProjection>()
+            .register_type::<OrthographicProjection>()
+            .register_type::<CameraRenderGraph>()
+            .register_type::<RenderPath>()
+            .register_type::<StereoCamera>()
+            .register_type::<ClearColor>()
+            .register_type::<ClearColorConfig>()
+            .init_resource::<ClearColor>();
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.add_systems(ExtractSchedule, extract_cameras);
+
+            let driver = CameraDriverNode::new(&mut render_app.world);
+            render_app
+                .world
+                .resource_mut::<RenderGraph>()
+                .add_node(CAMERA_DRIVER, driver);
+        }
+    }
+}
+
+type ExtractCameraQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static Camera,
+    &'static CameraRenderGraph,
+    Option<&'static RenderPath>,
+    AnyOf<(&'static PerspectiveProjection, &'static OrthographicProjection)>,
+    Option<&'static RenderTargetImage>,
+    Option<&'static WindowTarget>,
+    &'static GlobalTransform,
+    Option<(&'static Transform, &'static TransformInterpolation)>,
+    Option<&'static ClearColorConfig>,
+    Option<&'static StereoCamera>,
+    Option<&'static StereoEyePoses>,
+)>;
+
+#[allow(clippy::too_many_arguments)]
+fn extract_cameras(
+    mut commands: Commands,
+    cameras: Extract<ExtractCameraQuery>,
+    windows: Extract<Query<(Entity, &WindowComponent, Has<PrimaryWindowComponent>)>>,
+    textures: Extract<Res<Assets<Texture>>>,
+    fixed_time: Extract<Option<Res<Time<Fixed>>>>,
+    clear_color: Extract<Option<Res<ClearColor>>>,
+    context: Extract<Option<Res<RenderingContext>>>,
+    mut has_warned_fallback: Local<bool>,
+) {
+    let path_tracing_unsupported = context.as_deref().is_some_and(|context| !path_tracing_supported(context));
+    let clear_color = clear_color.as_deref().copied().unwrap_or_default();
+    let primary_window = windows.iter().find(|(_, _, is_primary)| *is_primary).map(|(entity, ..)| entity);
+    let window_size = |window: Entity| {
+        windows
+            .get(window)
+            .ok()
+            .map(|(_, window, _)| window.window.inner_size())
+            .map(|size| UVec2::new(size.width, size.height))
+    };
+    let alpha = interpolation_alpha(fixed_time.as_deref());
+
+    for (entity, camera, render_graph, render_path, (perspective, orthographic), target, window_target, transform, interpolation, clear_color_config, stereo, eye_poses) in cameras.iter() {
+        if !camera.is_active {
+            continue;
+        }
+        let window = match target {
+            Some(_) => None,
+            None => window_target.map(|target| target.window).or(primary_window),
+        };
+        let target_size = match target {
+            Some(target) => textures.get(&target.texture).map(|texture| UVec2::new(texture.width, texture.height)),
+            None => window.and_then(window_size),
+        };
+        let Some(target_size) = target_size.filter(|size| size.x > 0 && size.y > 0) else {
+            continue;
+        };
+        let viewport = camera
+            .viewport
+            .map_or(URect::from_corners(UVec2::ZERO, target_size), |viewport| viewport.to_pixels(target_size));
+        let viewport_size = viewport.size();
+        if viewport_size.x == 0 || viewport_size.y == 0 {
+            continue;
+        }
+        let Some(projection) = camera_projection(perspective, orthographic, viewport_size) else {
+            continue;
+        };
+
+        let mut render_graph = render_path.map_or_else(|| (&render_graph.0).into(), |path| path.graph_name());
+        if render_graph == PATH_TRACING_GRAPH && path_tracing_unsupported {
+            if !*has_warned_fallback {
+                warn!("The device can't run the path tracer, cameras using it are rendered with the deferred graph");
+                *has_warned_fallback = true;
+            }
+            render_graph = DEFERRED_GRAPH;
+        }
+
+        let world_from_view = interpolated_transform(transform, interpolation, alpha).compute_matrix();
+        let mut entity_commands = commands.get_or_spawn(entity);
+        if let Some(window) = window {
+            entity_commands.insert(WindowTarget { window });
+        }
+        match (stereo, eye_poses) {
+            (Some(_), Some(eye_poses)) => {
+                entity_commands.insert(ExtractedStereoViews {
+                    world_from_view: eye_poses.camera_from_eye.map(|camera_from_eye| world_from_view * camera_from_eye),
+                    projection: eye_poses.projection,
+                });
+            }
+            (Some(stereo), None) => {
+                entity_commands.insert(ExtractedStereoViews {
+                    world_from_view: stereo.eye_offsets().map(|offset| world_from_view * Mat4::from_translation(offset)),
+                    projection: [projection; 2],
+                });
+            }
+            (None, _) => {}
+        }
+        entity_commands.insert(ExtractedCamera {
+            target_size,
+            viewport,
+            world_from_view,
+            projection,
+            render_graph,
+            order: camera.order,
+            clear_color: clear_color_config.copied().unwrap_or_default().resolve(&clear_color),
+        });
+    }
+}
@@ -0,0 +1,39 @@
+use bevy_ecs::prelude::{Component, ReflectComponent, ReflectResource, Resource};
+use bevy_reflect::Reflect;
+use crate::color::Color;
+
+/// Color the targets of the cameras are cleared to, unless their [`ClearColorConfig`] overrides it.
+///
+/// Windows created transparent show what is behind them where the alpha is below 1.
+#[derive(Resource, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Resource)]
+pub struct ClearColor(pub Color);
+
+impl Default for ClearColor {
+    fn default() -> Self {
+        Self(Color::BLACK)
+    }
+}
+
+/// How a [`Camera`](super::Camera) clears its target before rendering.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq)]
+#[reflect(Component)]
+pub enum ClearColorConfig {
+    /// Use the [`ClearColor`] resource
+    #[default]
+    Inherit,
+    Color(Color),
+    /// Keep the contents of the target, e.g. to draw over the camera rendered before
+    None,
+}
+
+impl ClearColorConfig {
+    /// The color the target is cleared to, `None` when it is kept.
+    pub fn resolve(&self, clear_color: &ClearColor) -> Option<Color> {
+        match *self {
+            ClearColorConfig::Inherit => Some(clear_color.0),
+            ClearColorConfig::Color(color) => Some(color),
+            ClearColorConfig::None => None,
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use bevy_ecs::prelude::{Entity, QueryState, World};
+use crate::camera::ExtractedCamera;
+use crate::extract::FrameContext;
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::Node;
+
+/// Runs the render sub graph of every extracted camera, ordered by [`Camera::order`](super::Camera::order).
+pub struct CameraDriverNode {
+    cameras: QueryState<(Entity, &'static ExtractedCamera)>,
+}
+
+impl CameraDriverNode {
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            cameras: world.query(),
+        }
+    }
+}
+
+impl Node for CameraDriverNode {
+    fn update(&mut self, world: &mut World) {
+        self.cameras.update_archetypes(world);
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        _rendering_context: &FrameContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let mut cameras = self.cameras.iter_manual(world).collect::<Vec<_>>();
+        cameras.sort_by_key(|(_, camera)| camera.order);
+
+        for (entity, camera) in cameras {
+            graph.run_sub_graph(camera.render_graph, vec![], Some(entity))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use ash::vk;
+use bevy_ecs::prelude::Resource;
+use bevy_reflect::Reflect;
+
+/// An RGBA color, stored in linear space with straight alpha.
+///
+/// Shaders work in linear space, colors picked in an image editor or a color picker are sRGB encoded
+/// and have to be built with [`srgb`](Self::srgb) or [`srgb_u8`](Self::srgb_u8).
+#[derive(Reflect, Clone, Copy, Debug, PartialEq)]
+pub struct Color {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub alpha: f32,
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+impl Color {
+    pub const WHITE: Color = Color::linear_rgb(1.0, 1.0, 1.0);
+    pub const BLACK: Color = Color::linear_rgb(0.0, 0.0, 0.0);
+    pub const RED: Color = Color::linear_rgb(1.0, 0.0, 0.0);
+    pub const GREEN: Color = Color::linear_rgb(0.0, 1.0, 0.0);
+    pub const BLUE: Color = Color::linear_rgb(0.0, 0.0, 1.0);
+    pub const NONE: Color = Color::linear_rgba(0.0, 0.0, 0.0, 0.0);
+
+    #[inline]
+    pub const fn linear_rgba(red: f32, green: f32, blue: f32, alpha: f32) -> Self {
+        Self { red, green, blue, alpha }
+    }
+
+    #[inline]
+    pub const fn linear_rgb(red: f32, green: f32, blue: f32) -> Self {
+        Self::linear_rgba(red, green, blue, 1.0)
+    }
+
+    /// From sRGB encoded channels in `[0, 1]`, alpha is linear.
+    pub fn srgba(red: f32, green: f32, blue: f32, alpha: f32) -> Self {
+        Self::linear_rgba(srgb_to_linear(red), srgb_to_linear(green), srgb_to_linear(blue), alpha)
+    }
+
+    pub fn srgb(red: f32, green: f32, blue: f32) -> Self {
+        Self::srgba(red, green, blue, 1.0)
+    }
+
+    /// From 8 bit sRGB encoded channels, e.g. `#ff8000` is `srgb_u8(0xff, 0x80, 0x00)`.
+    pub fn srgba_u8(red: u8, green: u8, blue: u8, alpha: u8) -> Self {
+        let [red, green, blue, alpha] = [red, green, blue, alpha].map(|channel| channel as f32 / 255.0);
+        Self::srgba(red, green, blue, alpha)
+    }
+
+    pub fn srgb_u8(red: u8, green: u8, blue: u8) -> Self {
+        Self::srgba_u8(red, green, blue, 255)
+    }
+
+    /// From a hue in degrees, a saturation and a lightness in `[0, 1]`, in sRGB space like color pickers.
+    pub fn hsla(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Self {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let hue = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (hue % 2.0 - 1.0).abs());
+        let (red, green, blue) = match hue as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        let m = lightness - chroma / 2.0;
+        Self::srgba(red + m, green + m, blue + m, alpha)
+    }
+
+    pub fn hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
+        Self::hsla(hue, saturation, lightness, 1.0)
+    }
+
+    #[inline]
+    pub const fn with_alpha(self, alpha: f32) -> Self {
+        Self { alpha, ..self }
+    }
+
+    #[inline]
+    pub const fn to_linear_rgba(self) -> [f32; 4] {
+        [self.red, self.green, self.blue, self.alpha]
+    }
+
+    #[inline]
+    pub const fn to_linear_rgb(self) -> [f32; 3] {
+        [self.red, self.green, self.blue]
+    }
+
+    /// sRGB encoded channels in `[0, 1]`, alpha is linear.
+    pub fn to_srgba(self) -> [f32; 4] {
+        [
+            linear_to_srgb(self.red),
+            linear_to_srgb(self.green),
+            linear_to_srgb(self.blue),
+            self.alpha,
+        ]
+    }
+
+    /// Hue in degrees, saturation, lightness and alpha, the inverse of [`hsla`](Self::hsla).
+    pub fn to_hsla(self) -> [f32; 4] {
+        let [red, green, blue, alpha] = self.to_srgba();
+        let max = red.max(green).max(blue);
+        let min = red.min(green).min(blue);
+        let chroma = max - min;
+        let lightness = (max + min) / 2.0;
+        if chroma <= f32::EPSILON {
+            return [0.0, 0.0, lightness, alpha];
+        }
+
+        let hue = if max == red {
+            (green - blue) / chroma
+        } else if max == green {
+            (blue - red) / chroma + 2.0
+        } else {
+            (red - green) / chroma + 4.0
+        };
+        let saturation = chroma / (1.0 - (2.0 * lightness - 1.0).abs());
+        [(hue * 60.0).rem_euclid(360.0), saturation, lightness, alpha]
+    }
+
+    /// The channels written into an attachment or texture of `format`, e.g. as a clear color.
+    ///
+    /// `_SRGB` and float formats take linear values, the hardware encodes the former. 8 bit `_UNORM`
+    /// formats hold display values as-is, they are presented as sRGB and take encoded values.
+    pub fn for_format(self, format: vk::Format) -> [f32; 4] {
+        if holds_srgb_encoded(format) {
+            self.to_srgba()
+        } else {
+            self.to_linear_rgba()
+        }
+    }
+
+    /// Blend towards `other` by `t` in linear space.
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let [red, green, blue, alpha] = [
+            (self.red, other.red),
+            (self.green, other.green),
+            (self.blue, other.blue),
+            (self.alpha, other.alpha),
+        ]
+        .map(|(from, to)| from + (to - from) * t);
+        Color::linear_rgba(red, green, blue, alpha)
+    }
+}
+
+/// Linear channels.
+impl From<[f32; 4]> for Color {
+    fn from([red, green, blue, alpha]: [f32; 4]) -> Self {
+        Self::linear_rgba(red, green, blue, alpha)
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> Self {
+        color.to_linear_rgba()
+    }
+}
+
+/// Whether the values of `format` are sRGB encoded without the hardware encoding them, the 8 bit `_UNORM` formats
+/// presented as sRGB. The color written to them must be encoded by the shaders, see [`SrgbPolicy`].
+pub fn holds_srgb_encoded(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8G8B8A8_UNORM | vk::Format::B8G8R8A8_UNORM | vk::Format::A8B8G8R8_UNORM_PACK32
+    )
+}
+
+/// Where the linear color of the views is sRGB encoded for display, read when window swapchains are created.
+///
+/// Every intermediate target holds linear color, the conversion happens when the final image of a view reaches
+/// its window or [`RenderTargetImage`](crate::render_target::RenderTargetImage). Views copied into a target
+/// [`holds_srgb_encoded`] are encoded by the [`TonemappingNode`](crate::tonemapping::TonemappingNode) as its last step,
+/// whatever the policy, as surfaces may lack the preferred formats. Views of graphs outside of the
+/// [`TONEMAPPED_GRAPHS`](crate::tonemapping::TONEMAPPED_GRAPHS) are copied as-is and only display correctly in `_SRGB` targets.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SrgbPolicy {
+    /// Prefer `_SRGB` swapchain formats, the hardware encodes the color when views are copied into them.
+    #[default]
+    Hardware,
+    /// Prefer `_UNORM` swapchain formats, the tonemapping node encodes the color.
+    Tonemapping,
+}
+
+impl SrgbPolicy {
+    /// Whether swapchains prefer `_SRGB` formats, see [`SwapchainOptions::srgb`](avalanche_hlvk::SwapchainOptions::srgb).
+    #[inline]
+    pub fn prefers_srgb_swapchain(&self) -> bool {
+        *self == SrgbPolicy::Hardware
+    }
+}
+
+/// Decode an sRGB channel with the piecewise sRGB transfer function.
+pub fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a linear channel with the piecewise sRGB transfer function.
+pub fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.0031308 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    }
+}
@@ -0,0 +1,128 @@
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::thread::ThreadId;
+use ash::vk;
+use bevy_utils::HashMap;
+use avalanche_hlvk::{CommandBuffer, CommandPool, Context, QueueFamily};
+use crate::INIT_COMMAND_POOL_NUM;
+
+/// Command buffers given back to a pool, with their level.
+type ReturnedCommandBuffers = Arc<Mutex<Vec<(vk::CommandBufferLevel, CommandBuffer)>>>;
+
+/// A command buffer allocated by a [`CommandPoolManager`], returned to its pool when dropped.
+///
+/// The pool hands it out again once it is reset for a later frame, which happens after the fence of
+/// the frame it was recorded for signaled. Drop it once the commands it recorded were submitted.
+pub struct PooledCommandBuffer {
+    command_buffer: Option<CommandBuffer>,
+    level: vk::CommandBufferLevel,
+    returned: ReturnedCommandBuffers,
+}
+
+impl Deref for PooledCommandBuffer {
+    type Target = CommandBuffer;
+
+    fn deref(&self) -> &Self::Target {
+        self.command_buffer.as_ref().unwrap()
+    }
+}
+
+impl Drop for PooledCommandBuffer {
+    fn drop(&mut self) {
+        if let Some(command_buffer) = self.command_buffer.take() {
+            self.returned.lock().unwrap().push((self.level, command_buffer));
+        }
+    }
+}
+
+/// The pool of a thread for a frame, with the command buffers it can hand out again.
+struct ThreadCommandPool {
+    pool: Arc<CommandPool>,
+    /// Reset with the pool and ready to be handed out
+    free: Vec<(vk::CommandBufferLevel, CommandBuffer)>,
+    /// Dropped since the pool was reset, they may still be pending until the pool is reset again
+    returned: ReturnedCommandBuffers,
+}
+
+/// Command pools of the threads recording commands, one set per frame in flight.
+///
+/// The pools of a frame are reset at once when the frame starts, so command buffers don't need
+/// `RESET_COMMAND_BUFFER`. Each thread records into its own pools, which allows parallel recording.
+/// Command buffers are kept by their pool and reused from frame to frame, rather than freed.
+pub struct CommandPoolManager {
+    context: Arc<Context>,
+    queue_family: QueueFamily,
+    frames: [Mutex<HashMap<ThreadId, ThreadCommandPool>>; INIT_COMMAND_POOL_NUM],
+}
+
+impl CommandPoolManager {
+    pub fn new(context: Arc<Context>, queue_family: QueueFamily) -> Self {
+        Self {
+            context,
+            queue_family,
+            frames: Default::default(),
+        }
+    }
+
+    #[inline]
+    pub fn queue_family(&self) -> QueueFamily {
+        self.queue_family
+    }
+
+    /// The pool of `thread` for the `frame`, created on first use.
+    pub fn pool(&self, frame: usize, thread: ThreadId) -> anyhow::Result<Arc<CommandPool>> {
+        let mut pools = self.frames[frame % INIT_COMMAND_POOL_NUM].lock().unwrap();
+        Ok(self.thread_pool(&mut pools, thread)?.pool.clone())
+    }
+
+    /// The pool of the calling thread for the `frame`.
+    pub fn current_thread_pool(&self, frame: usize) -> anyhow::Result<Arc<CommandPool>> {
+        self.pool(frame, std::thread::current().id())
+    }
+
+    /// A command buffer of the pool of the calling thread for the `frame`, in the initial state.
+    ///
+    /// A command buffer the pool got back is reused, otherwise a new one is allocated.
+    pub fn allocate_command_buffer(&self, frame: usize, level: vk::CommandBufferLevel) -> anyhow::Result<PooledCommandBuffer> {
+        let mut pools = self.frames[frame % INIT_COMMAND_POOL_NUM].lock().unwrap();
+        let thread_pool = self.thread_pool(&mut pools, std::thread::current().id())?;
+        let command_buffer = match thread_pool.free.iter().position(|(free_level, _)| *free_level == level) {
+            Some(index) => thread_pool.free.swap_remove(index).1,
+            None => thread_pool.pool.allocate_command_buffer(level)?,
+        };
+        Ok(PooledCommandBuffer {
+            command_buffer: Some(command_buffer),
+            level,
+            returned: thread_pool.returned.clone(),
+        })
+    }
+
+    /// Reset the pools of the `frame`, the command buffers they got back can be handed out again.
+    ///
+    /// The commands previously recorded for the frame must have completed.
+    pub fn reset_pools(&self, frame: usize) -> anyhow::Result<()> {
+        let mut pools = self.frames[frame % INIT_COMMAND_POOL_NUM].lock().unwrap();
+        for thread_pool in pools.values_mut() {
+            thread_pool.pool.reset()?;
+            let returned = std::mem::take(&mut *thread_pool.returned.lock().unwrap());
+            thread_pool.free.extend(returned);
+        }
+        Ok(())
+    }
+
+    fn thread_pool<'a>(
+        &self,
+        pools: &'a mut HashMap<ThreadId, ThreadCommandPool>,
+        thread: ThreadId,
+    ) -> anyhow::Result<&'a mut ThreadCommandPool> {
+        if !pools.contains_key(&thread) {
+            let pool = self.context.create_command_pool(self.queue_family, Some(vk::CommandPoolCreateFlags::TRANSIENT))?;
+            pools.insert(thread, ThreadCommandPool {
+                pool: Arc::new(pool),
+                free: Vec::new(),
+                returned: Default::default(),
+            });
+        }
+        Ok(pools.get_mut(&thread).unwrap())
+    }
+}
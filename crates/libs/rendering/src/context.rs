@@ -1,12 +1,15 @@
 use std::ops::Deref;
 use std::sync::Arc;
 use bevy_ecs::prelude::Resource;
-use avalanche_hlvk::{CommandPool, Context};
+use avalanche_hlvk::{Context, ContextCreateHooks};
+use crate::command_pool::CommandPoolManager;
 
 #[derive(Resource)]
 pub struct RenderingContext {
     pub context: Arc<Context>,
-    pub command_pools: Arc<Vec<Arc<CommandPool>>>,
+    pub command_pools: Arc<CommandPoolManager>,
+    /// Pools of the compute queue family, `None` when it is the graphics one and there is no async compute
+    pub compute_command_pools: Option<Arc<CommandPoolManager>>,
 }
 
 impl Clone for RenderingContext {
@@ -14,6 +17,7 @@ impl Clone for RenderingContext {
         Self {
             context: self.context.clone(),
             command_pools: self.command_pools.clone(),
+            compute_command_pools: self.compute_command_pools.clone(),
         }
     }
 }
@@ -27,4 +31,22 @@ impl Deref for RenderingContext {
 }
 
 impl RenderingContext {
+    /// Command buffers are allocated from pools of the graphics queue family,
+    /// and of the compute queue family if it is another one.
+    pub fn new(context: Context) -> Self {
+        let context = Arc::new(context);
+        let command_pools = CommandPoolManager::new(context.clone(), context.graphics_queue_family);
+        let compute_command_pools = (context.compute_queue_family.index != context.graphics_queue_family.index)
+            .then(|| Arc::new(CommandPoolManager::new(context.clone(), context.compute_queue_family)));
+        Self {
+            context,
+            command_pools: Arc::new(command_pools),
+            compute_command_pools,
+        }
+    }
 }
+
+/// Creates the Vulkan instance and device of the [`RenderingContext`] through a runtime like OpenXR,
+/// it must be inserted before the context is created.
+#[derive(Resource, Clone)]
+pub struct RenderingContextHooks(pub Arc<dyn ContextCreateHooks + Send + Sync>);
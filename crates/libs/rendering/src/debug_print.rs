@@ -0,0 +1,205 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{IntoSystemConfigs, Res, ResMut, Resource};
+use bevy_log::{error, info, warn};
+use bevy_utils::HashMap;
+use avalanche_hlvk::{Buffer, BufferBarrier, CommandBuffer};
+use crate::extract::FrameContext;
+use crate::globals::{prepare_globals, Globals};
+use crate::readback::{ReadbackBuffer, ReadbackSource};
+use crate::{Render, RenderApp, RenderSet};
+
+/// Kind of the values of a record, matches the `DEBUG_PRINT_*` defines of `shaders/debug/print.glsl`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugPrintKind {
+    Uint,
+    Int,
+    Float,
+}
+
+impl DebugPrintKind {
+    fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            0 => Some(Self::Uint),
+            1 => Some(Self::Int),
+            2 => Some(Self::Float),
+            _ => None,
+        }
+    }
+}
+
+/// A value printed by `debug_print(tag, value)` in a shader.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DebugPrintRecord {
+    /// The line printing it with `DEBUG_PRINT`, or the tag passed to `debug_print`
+    pub tag: u32,
+    pub kind: DebugPrintKind,
+    /// One word per component, reinterpret by `kind`
+    pub words: Vec<u32>,
+}
+
+impl DebugPrintRecord {
+    fn format_values(&self) -> String {
+        let mut values = String::new();
+        for (index, &word) in self.words.iter().enumerate() {
+            if index > 0 {
+                values.push_str(", ");
+            }
+            let _ = match self.kind {
+                DebugPrintKind::Uint => write!(values, "{word}"),
+                DebugPrintKind::Int => write!(values, "{}", word as i32),
+                DebugPrintKind::Float => write!(values, "{}", f32::from_bits(word)),
+            };
+        }
+        values
+    }
+}
+
+/// Split the words written to the debug print buffer into records, the first word is the count written.
+///
+/// Returns the records and whether some were dropped because the buffer was full.
+pub fn parse_debug_print(bytes: &[u8]) -> (Vec<DebugPrintRecord>, bool) {
+    let mut words = bytes.chunks_exact(4).map(|word| u32::from_ne_bytes(word.try_into().unwrap()));
+    let count = words.next().unwrap_or(0) as usize;
+    let words = words.collect::<Vec<_>>();
+    let overflowed = count > words.len();
+
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset < count.min(words.len()) {
+        let header = words[offset];
+        let components = ((header >> 24) & 0xf) as usize;
+        let Some(kind) = DebugPrintKind::from_bits(header >> 28) else { break };
+        let Some(values) = words.get(offset + 1..offset + 1 + components) else { break };
+        records.push(DebugPrintRecord {
+            tag: header & 0xff_ffff,
+            kind,
+            words: values.to_vec(),
+        });
+        offset += 1 + components;
+    }
+    (records, overflowed)
+}
+
+/// Logs the values printed by shaders with `shaders/debug/print.glsl` in debug builds, a frame late.
+///
+/// Shaders print through the set of the [`Globals`], so only passes whose layout comes from
+/// [`globals::pipeline_layout`](crate::globals::pipeline_layout) can print.
+pub struct ShaderDebugPrintPlugin;
+
+impl Plugin for ShaderDebugPrintPlugin {
+    fn build(&self, app: &mut App) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ShaderDebugPrint>()
+                .add_systems(Render, prepare_debug_print.in_set(RenderSet::PrepareResources).after(prepare_globals));
+        }
+    }
+}
+
+/// Readbacks of the debug print buffer, with the labels of the tags printed to.
+#[derive(Resource)]
+pub struct ShaderDebugPrint {
+    /// Logs the printed values, otherwise they are only kept in [`Self::records`]
+    pub log: bool,
+    labels: HashMap<u32, String>,
+    records: Vec<DebugPrintRecord>,
+    readbacks: VecDeque<ReadbackBuffer>,
+    /// The buffer is cleared once before the first readback, its memory is uninitialized
+    cleared: bool,
+}
+
+impl Default for ShaderDebugPrint {
+    fn default() -> Self {
+        Self {
+            log: true,
+            labels: HashMap::default(),
+            records: Vec::new(),
+            readbacks: VecDeque::new(),
+            cleared: false,
+        }
+    }
+}
+
+impl ShaderDebugPrint {
+    /// Name the values printed with `tag` in the log, instead of the line printing them.
+    pub fn set_label(&mut self, tag: u32, label: impl Into<String>) {
+        self.labels.insert(tag, label.into());
+    }
+
+    /// The records of the last frame read back.
+    #[inline]
+    pub fn records(&self) -> &[DebugPrintRecord] {
+        &self.records
+    }
+
+    fn read_back(&mut self) {
+        while self.readbacks.front().is_some_and(ReadbackBuffer::is_ready) {
+            let readback = self.readbacks.pop_front().unwrap();
+            let bytes = match readback.read() {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => continue,
+                Err(err) => {
+                    error!("Failed to read back the shader debug prints: {err}");
+                    continue;
+                }
+            };
+
+            let (records, overflowed) = parse_debug_print(&bytes);
+            if overflowed {
+                warn!("Shader debug print buffer is full, some values were dropped");
+            }
+            if self.log {
+                for record in &records {
+                    let values = record.format_values();
+                    match self.labels.get(&record.tag) {
+                        Some(label) => info!("[Shader] {label}: {values}"),
+                        None => info!("[Shader] line {}: {values}", record.tag),
+                    }
+                }
+            }
+            self.records = records;
+        }
+    }
+
+    /// Read back what the previous frame printed, then clear the buffer for this one.
+    fn record(&mut self, frame_context: &FrameContext, command_buffer: &CommandBuffer, buffer: &Buffer) -> anyhow::Result<()> {
+        if self.cleared {
+            self.readbacks.push_back(ReadbackBuffer::new(frame_context, ReadbackSource::Buffer(buffer))?);
+        }
+        self.cleared = true;
+
+        command_buffer.pipeline_buffer_barriers(&[BufferBarrier {
+            buffer,
+            src_access_mask: vk::AccessFlags2::TRANSFER_READ,
+            dst_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+        }]);
+        command_buffer.fill_buffer(buffer, 0, std::mem::size_of::<u32>() as _, 0);
+        command_buffer.pipeline_buffer_barriers(&[BufferBarrier {
+            buffer,
+            src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_READ | vk::AccessFlags2::SHADER_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+        Ok(())
+    }
+}
+
+fn prepare_debug_print(mut debug_print: ResMut<ShaderDebugPrint>, globals: Res<Globals>, frame_context: Res<FrameContext>) {
+    debug_print.read_back();
+
+    let (Some(buffer), Some(command_buffer)) = (globals.debug_print_buffer(), frame_context.command_buffer(0)) else {
+        return;
+    };
+    if let Err(err) = debug_print.record(&frame_context, command_buffer, buffer) {
+        error!("Failed to read back the shader debug prints: {err}");
+    }
+}
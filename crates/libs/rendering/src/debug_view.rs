@@ -0,0 +1,234 @@
+mod node;
+mod pipeline;
+
+pub use node::*;
+pub use pipeline::*;
+
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Component, Entity, IntoSystemConfigs, Local, Query, ReflectComponent, ReflectResource, Res, ResMut, Resource};
+use bevy_log::{error, warn};
+use bevy_reflect::Reflect;
+use bevy_utils::{EntityHashMap, HashSet};
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{Buffer, Context, DescriptorPool, DescriptorSet, WriteDescriptorSet, WriteDescriptorSetKind};
+use crate::{Render, RenderApp, RenderSet};
+use crate::camera::ExtractedCamera;
+use crate::deferred::{prepare_deferred_views, DeferredViews, DEFERRED_GRAPH};
+use crate::extract::{ExtractComponent, ExtractComponentPlugin, ExtractResource, ExtractResourcePlugin, FrameContext};
+use crate::graph::RenderGraphApp;
+use crate::graph::node::ViewNodeRunner;
+use crate::skinning::SkinPalettes;
+use crate::specialized_pipeline::SpecializedPipelines;
+use crate::transparent::TRANSPARENT_OIT_NODE;
+use crate::upscaling::UPSCALE_NODE;
+
+pub const DEBUG_VIEW_NODE: &str = "debug_view_pass";
+
+/// Visualization replacing or overlaying the shaded image of cameras rendered with the [`DEFERRED_GRAPH`].
+///
+/// Insert it as a resource to apply it to every camera, or on a camera to override the resource.
+#[derive(Resource, Component, ExtractResource, ExtractComponent, Reflect, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[reflect(Resource, Component)]
+pub enum DebugRenderMode {
+    #[default]
+    Off,
+    /// Edges of the visible triangles over the shaded image, requires the `fill_mode_non_solid` device feature
+    /// and is turned off without it
+    Wireframe,
+    /// World space normals of the visible surfaces
+    Normals,
+    /// Texture coordinates of the visible surfaces, wrapped into `[0, 1)`
+    Uvs,
+    /// How many times each pixel is covered by a triangle, brighter is more
+    Overdraw,
+}
+
+impl DebugRenderMode {
+    /// Mode of a view, `view` is the [`DebugRenderMode`] component of the camera.
+    #[inline]
+    pub fn of_view(view: Option<&DebugRenderMode>, global: &DebugRenderMode) -> DebugRenderMode {
+        view.copied().unwrap_or(*global)
+    }
+
+    /// Whether the device of `context` can draw the mode.
+    #[inline]
+    pub fn is_supported(&self, context: &Context) -> bool {
+        *self != DebugRenderMode::Wireframe || context.device_features.fill_mode_non_solid
+    }
+}
+
+pub struct DebugViewPlugin;
+
+impl Plugin for DebugViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<DebugRenderMode>()
+            .init_resource::<DebugRenderMode>()
+            .add_plugins((
+                ExtractResourcePlugin::<DebugRenderMode>::default(),
+                ExtractComponentPlugin::<DebugRenderMode>::default(),
+            ));
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<DebugRenderMode>()
+                .init_resource::<DebugViewPipeline>()
+                .init_resource::<SpecializedPipelines<DebugViewPipelineKey>>()
+                .init_resource::<DebugViews>()
+                .add_systems(
+                    Render, (
+                        prepare_debug_view_pipelines.in_set(RenderSet::PrepareResources),
+                        prepare_debug_views
+                            .after(prepare_deferred_views)
+                            .in_set(RenderSet::PrepareBindGroups),
+                    )
+                )
+                .add_render_graph_node::<ViewNodeRunner<DebugViewNode>>(DEFERRED_GRAPH, DEBUG_VIEW_NODE)
+                .add_render_graph_edges(DEFERRED_GRAPH, &[TRANSPARENT_OIT_NODE, DEBUG_VIEW_NODE, UPSCALE_NODE]);
+        }
+    }
+}
+
+/// Matches `DebugViewUniform` in `shaders/debug/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DebugViewUniform {
+    clip_from_world: [f32; 16],
+    camera_position: [f32; 4],
+}
+
+/// Bindings of a camera with a [`DebugRenderMode`], kept across frames.
+pub struct DebugViewState {
+    pub mode: DebugRenderMode,
+    uniform_buffer: Buffer,
+    _descriptor_pool: DescriptorPool,
+    pub(crate) descriptor_set: DescriptorSet,
+}
+
+impl DebugViewState {
+    fn new(context: &Context, layout: &DebugViewLayout, mode: DebugRenderMode) -> anyhow::Result<Self> {
+        let uniform_buffer = context.create_buffer(
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of::<DebugViewUniform>() as _,
+        )?;
+        let descriptor_pool = context.create_descriptor_pool(1, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+            },
+        ])?;
+        let descriptor_set = descriptor_pool.allocate_set(&layout.descriptor_set_layout)?;
+
+        Ok(Self {
+            mode,
+            uniform_buffer,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct DebugViews(pub(crate) EntityHashMap<Entity, DebugViewState>);
+
+impl DebugViews {
+    pub fn get(&self, entity: Entity) -> Option<&DebugViewState> {
+        self.0.get(&entity)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_debug_views(
+    mut views: ResMut<DebugViews>,
+    pipeline: Res<DebugViewPipeline>,
+    deferred_views: Res<DeferredViews>,
+    global_mode: Res<DebugRenderMode>,
+    palettes: Res<SkinPalettes>,
+    cameras: Query<(Entity, &ExtractedCamera, Option<&DebugRenderMode>)>,
+    frame_context: Res<FrameContext>,
+    mut has_warned_unsupported: Local<bool>,
+) {
+    let Some(layout) = pipeline.layout() else {
+        views.0.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+
+    for (entity, camera, mode) in cameras.iter() {
+        let mode = DebugRenderMode::of_view(mode, &global_mode);
+        let Some(deferred) = deferred_views.get(entity) else {
+            continue;
+        };
+        if mode == DebugRenderMode::Off {
+            continue;
+        }
+        if !mode.is_supported(context) {
+            if !*has_warned_unsupported {
+                warn!("{mode:?} debug render mode isn't supported by the device, it is turned off");
+                *has_warned_unsupported = true;
+            }
+            continue;
+        }
+        alive.insert(entity);
+
+        if !views.0.contains_key(&entity) {
+            match DebugViewState::new(context, layout, mode) {
+                Ok(state) => {
+                    views.0.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create debug view resources: {err}");
+                    continue;
+                }
+            }
+        }
+        let state = views.0.get_mut(&entity).unwrap();
+        state.mode = mode;
+
+        let uniform = DebugViewUniform {
+            clip_from_world: (camera.projection * camera.world_from_view.inverse()).to_cols_array(),
+            camera_position: camera.world_from_view.w_axis.to_array(),
+        };
+        if let Err(err) = state.uniform_buffer.copy_data_to_buffer(std::slice::from_ref(&uniform)) {
+            error!("Failed to upload debug view uniform: {err}");
+        }
+
+        // the G-buffer is recreated with the view target, rewrite the set every frame
+        let mut writes = vec![
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::UniformBuffer {
+                    buffer: &state.uniform_buffer,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &deferred.normal.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+        ];
+        if let Some(palette) = palettes.buffer() {
+            writes.push(WriteDescriptorSet {
+                binding: 2,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: palette,
+                },
+            });
+        }
+        state.descriptor_set.update(&writes);
+    }
+
+    views.0.retain(|entity, _| alive.contains(entity));
+}
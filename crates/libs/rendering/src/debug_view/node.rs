@@ -0,0 +1,144 @@
+use ash::vk;
+use bevy_ecs::prelude::World;
+use avalanche_hlvk::{ImageBarrier, RenderingAttachment};
+use crate::camera::ExtractedCamera;
+use crate::debug_view::{DebugRenderMode, DebugViewPipeline, DebugViewPipelineKey, DebugViewPushConstants, DebugViews};
+use crate::deferred::DeferredViews;
+use crate::extract::FrameContext;
+use crate::mesh::MeshMaterialFlags;
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::ViewNode;
+use crate::raytracing::{RayTracingGpuScene, RayTracingScene};
+use crate::skinning::SkinPalettes;
+use crate::specialized_pipeline::SpecializedPipelines;
+use crate::transparent::world_from_object;
+use crate::view::{ViewTarget, VIEW_TARGET_FORMAT};
+
+/// Rasterizes every mesh of the scene with the [`DebugViewPipelineKey`] variant of the [`DebugRenderMode`] of a view.
+///
+/// Wireframes are drawn over the shaded image, the other modes replace it.
+#[derive(Default)]
+pub struct DebugViewNode;
+
+impl ViewNode for DebugViewNode {
+    type ViewQuery = (&'static ExtractedCamera, &'static ViewTarget);
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (camera, target): (&ExtractedCamera, &ViewTarget),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(state) = world.resource::<DebugViews>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+        let Some(layout) = world.resource::<DebugViewPipeline>().layout() else {
+            return Ok(());
+        };
+        let Some(deferred) = world.resource::<DeferredViews>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image: &deferred.normal.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+                src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                dst_stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            },
+            ImageBarrier {
+                image: &target.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::MEMORY_WRITE,
+                dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            },
+        ]);
+
+        let extent = vk::Extent2D {
+            width: target.size.x,
+            height: target.size.y,
+        };
+        let (load_op, clear_color) = match (state.mode, camera.clear_color) {
+            (DebugRenderMode::Wireframe, _) | (_, None) => (vk::AttachmentLoadOp::LOAD, [0.0; 4]),
+            (_, Some(clear_color)) => (vk::AttachmentLoadOp::CLEAR, clear_color.for_format(VIEW_TARGET_FORMAT)),
+        };
+        command_buffer.begin_rendering_attachments(
+            &[RenderingAttachment {
+                view: &target.view,
+                layout: vk::ImageLayout::GENERAL,
+                load_op,
+                clear_color,
+            }],
+            extent,
+        );
+        command_buffer.set_viewport(extent);
+        command_buffer.set_scissor(extent);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, &layout.layout, 0, &[&state.descriptor_set]);
+
+        let gpu_scene = world.resource::<RayTracingGpuScene>();
+        let pipelines = world.resource::<SpecializedPipelines<DebugViewPipelineKey>>();
+        let palettes = world.resource::<SkinPalettes>();
+        let mut bound = None;
+        for (entity, instance) in world.resource::<RayTracingScene>().iter() {
+            let Some((mesh, material)) = gpu_scene.geometry(entity) else {
+                continue;
+            };
+            let vertex_layout = palettes.vertex_layout(entity, mesh);
+            let key = DebugViewPipelineKey::new(state.mode, vertex_layout, MeshMaterialFlags::from_material(material));
+            if bound != Some(key) {
+                let Some(pipeline) = pipelines.get(&key) else {
+                    continue;
+                };
+                command_buffer.bind_raster_pipeline(pipeline);
+                bound = Some(key);
+            }
+
+            let push_constants = DebugViewPushConstants {
+                world_from_object: world_from_object(&instance.transform).to_cols_array(),
+                joint_offset: palettes.offset(entity).unwrap_or_default(),
+            };
+            command_buffer.push_constants(
+                &layout.layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                bytemuck::bytes_of(&push_constants),
+            );
+
+            match mesh.skin_buffer.as_ref().filter(|_| vertex_layout.is_skinned()) {
+                Some(skin_buffer) => command_buffer.bind_vertex_buffers(0, &[&mesh.vertex_buffer, skin_buffer]),
+                None => command_buffer.bind_vertex_buffer(&mesh.vertex_buffer),
+            }
+            match &mesh.index_buffer {
+                Some(index_buffer) => {
+                    command_buffer.bind_index_buffer(index_buffer, vk::IndexType::UINT32);
+                    command_buffer.draw_indexed(mesh.index_count);
+                }
+                None => command_buffer.draw(mesh.vertex_count),
+            }
+        }
+
+        command_buffer.end_rendering();
+        // later passes expect the view target written by shaders
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &target.image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+
+        Ok(())
+    }
+}
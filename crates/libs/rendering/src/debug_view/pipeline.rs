@@ -0,0 +1,224 @@
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::prelude::{Query, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_utils::HashSet;
+use avalanche_hlvk::{
+    BlendMode, Context, DescriptorSetLayout, PipelineLayout, RasterColorAttachment, RasterPipeline,
+    RasterPipelineCreateInfo,
+};
+use crate::camera::ExtractedCamera;
+use crate::debug_view::DebugRenderMode;
+use crate::deferred::DEFERRED_GRAPH;
+use crate::extract::FrameContext;
+use crate::mesh::{MeshMaterialFlags, MeshPipelineKey, MeshVertexLayout};
+use crate::raytracing::{RayTracingGpuScene, RayTracingScene};
+use crate::shader::ShaderDirectory;
+use crate::skinning::SkinPalettes;
+use crate::specialized_pipeline::{SpecializedPipelineKey, SpecializedPipelines};
+use crate::view::VIEW_TARGET_FORMAT;
+
+pub(crate) const DEBUG_VERTEX_SHADER: &str = "debug/mesh.vert";
+pub(crate) const SKINNED_DEBUG_VERTEX_SHADER: &str = "debug/skinned_mesh.vert";
+pub(crate) const WIREFRAME_FRAGMENT_SHADER: &str = "debug/wireframe.frag";
+pub(crate) const NORMALS_FRAGMENT_SHADER: &str = "debug/normals.frag";
+pub(crate) const UVS_FRAGMENT_SHADER: &str = "debug/uvs.frag";
+pub(crate) const OVERDRAW_FRAGMENT_SHADER: &str = "debug/overdraw.frag";
+
+/// Matches `DebugViewPushConstants` in `shaders/debug/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DebugViewPushConstants {
+    pub world_from_object: [f32; 16],
+    /// First matrix of the instance in the [`SkinPalettes`], skinned meshes only
+    pub joint_offset: u32,
+}
+
+// SAFETY: plain `f32` and `u32` fields without implicit padding
+unsafe impl Zeroable for DebugViewPushConstants {}
+unsafe impl Pod for DebugViewPushConstants {}
+
+/// Layout shared by every [`DebugRenderMode`] pipeline.
+pub struct DebugViewLayout {
+    pub descriptor_set_layout: DescriptorSetLayout,
+    pub layout: PipelineLayout,
+}
+
+impl DebugViewLayout {
+    fn new(context: &Context) -> anyhow::Result<Self> {
+        let bindings = [
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                .build(),
+            // G-buffer normal and distance
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+                .build(),
+            // joint matrices of skinned meshes
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(2)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .build(),
+        ];
+        let descriptor_set_layout = context.create_descriptor_set_layout(&bindings)?;
+        let layout = context.create_pipeline_layout_with_push_constants(
+            &[&descriptor_set_layout],
+            &[vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::VERTEX,
+                offset: 0,
+                size: std::mem::size_of::<DebugViewPushConstants>() as _,
+            }],
+        )?;
+
+        Ok(Self { descriptor_set_layout, layout })
+    }
+}
+
+/// Layout of the debug view pipelines, created the first time a camera uses a [`DebugRenderMode`].
+///
+/// The pipelines are [`DebugViewPipelineKey`] variants.
+#[derive(Resource, Default)]
+pub struct DebugViewPipeline {
+    layout: Option<DebugViewLayout>,
+    /// Creation of the layout failed, not retried
+    failed: bool,
+}
+
+impl DebugViewPipeline {
+    #[inline]
+    pub fn layout(&self) -> Option<&DebugViewLayout> {
+        self.layout.as_ref()
+    }
+}
+
+/// A pipeline variant drawing meshes with a [`DebugRenderMode`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DebugViewPipelineKey {
+    pub mesh: MeshPipelineKey,
+    pub mode: DebugRenderMode,
+}
+
+impl DebugViewPipelineKey {
+    pub fn new(mode: DebugRenderMode, vertex_layout: MeshVertexLayout, material: MeshMaterialFlags) -> Self {
+        Self {
+            mesh: MeshPipelineKey {
+                vertex_layout,
+                // blending does not apply to debug views
+                material: material & MeshMaterialFlags::DOUBLE_SIDED,
+                samples: vk::SampleCountFlags::TYPE_1,
+                target_format: VIEW_TARGET_FORMAT,
+            },
+            mode,
+        }
+    }
+}
+
+impl SpecializedPipelineKey for DebugViewPipelineKey {
+    type Specializer = DebugViewLayout;
+
+    fn specialize(
+        &self,
+        context: &Context,
+        shaders: &ShaderDirectory,
+        specializer: &DebugViewLayout,
+    ) -> anyhow::Result<RasterPipeline> {
+        let (fragment_shader, polygon_mode, blend) = match self.mode {
+            DebugRenderMode::Off => anyhow::bail!("no pipeline is used without debug render mode"),
+            DebugRenderMode::Wireframe => {
+                anyhow::ensure!(
+                    context.device_features.fill_mode_non_solid,
+                    "wireframes require the fill mode non solid device feature",
+                );
+                (WIREFRAME_FRAGMENT_SHADER, vk::PolygonMode::LINE, BlendMode::Opaque)
+            }
+            DebugRenderMode::Normals => (NORMALS_FRAGMENT_SHADER, vk::PolygonMode::FILL, BlendMode::Opaque),
+            DebugRenderMode::Uvs => (UVS_FRAGMENT_SHADER, vk::PolygonMode::FILL, BlendMode::Opaque),
+            DebugRenderMode::Overdraw => (OVERDRAW_FRAGMENT_SHADER, vk::PolygonMode::FILL, BlendMode::Additive),
+        };
+        // overdraw counts hidden back faces too
+        let cull_mode = match self.mode {
+            DebugRenderMode::Overdraw => vk::CullModeFlags::NONE,
+            _ => self.mesh.cull_mode(),
+        };
+
+        let vertex_shader = match self.mesh.vertex_layout.is_skinned() {
+            true => SKINNED_DEBUG_VERTEX_SHADER,
+            false => DEBUG_VERTEX_SHADER,
+        };
+        let vertex_shader = shaders.load(context, vertex_shader, vk::ShaderStageFlags::VERTEX)?;
+        self.mesh.vertex_layout.validate(&vertex_shader)?;
+
+        context.create_graphics_pipeline(&specializer.layout, RasterPipelineCreateInfo {
+            shaders: &[
+                vertex_shader,
+                shaders.load(context, fragment_shader, vk::ShaderStageFlags::FRAGMENT)?,
+            ],
+            primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            vertex_stream: &self.mesh.vertex_layout.vertex_stream(),
+            viewport: None,
+            scissor: None,
+            color_attachments: &[RasterColorAttachment::new(self.mesh.target_format, blend)],
+            depth_attachment: None,
+            dynamic_states: Some(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]),
+            polygon_mode,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            samples: self.mesh.samples,
+            cull_mode,
+        })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn prepare_debug_view_pipelines(
+    mut pipeline: ResMut<DebugViewPipeline>,
+    mut pipelines: ResMut<SpecializedPipelines<DebugViewPipelineKey>>,
+    shaders: Res<ShaderDirectory>,
+    global_mode: Res<DebugRenderMode>,
+    cameras: Query<(&ExtractedCamera, Option<&DebugRenderMode>)>,
+    scene: Res<RayTracingScene>,
+    gpu_scene: Res<RayTracingGpuScene>,
+    palettes: Res<SkinPalettes>,
+    frame_context: Res<FrameContext>,
+) {
+    let context = frame_context.render_context();
+    let modes = cameras
+        .iter()
+        .filter(|(camera, _)| camera.render_graph == DEFERRED_GRAPH)
+        .map(|(_, mode)| DebugRenderMode::of_view(mode, &global_mode))
+        .filter(|mode| *mode != DebugRenderMode::Off && mode.is_supported(context))
+        .collect::<HashSet<_>>();
+    if modes.is_empty() || pipeline.failed {
+        return;
+    }
+
+    if pipeline.layout.is_none() {
+        match DebugViewLayout::new(context) {
+            Ok(layout) => pipeline.layout = Some(layout),
+            Err(err) => {
+                error!("Failed to create debug view pipeline layout: {err}");
+                pipeline.failed = true;
+                return;
+            }
+        }
+    }
+    let layout = pipeline.layout.as_ref().unwrap();
+
+    let meshes = scene
+        .iter()
+        .filter_map(|(entity, _)| Some((entity, gpu_scene.geometry(entity)?)))
+        .map(|(entity, (mesh, material))| (palettes.vertex_layout(entity, mesh), MeshMaterialFlags::from_material(material)))
+        .collect::<HashSet<_>>();
+    for mode in modes {
+        for (vertex_layout, material) in &meshes {
+            pipelines.specialize(context, &shaders, layout, &DebugViewPipelineKey::new(mode, *vertex_layout, *material));
+        }
+    }
+}
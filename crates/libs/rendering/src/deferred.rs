@@ -0,0 +1,520 @@
+mod node;
+mod pipeline;
+
+pub use node::*;
+pub use pipeline::*;
+
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Commands, Entity, IntoSystemConfigs, Query, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_math::{Mat4, UVec2, Vec3};
+use bevy_utils::{EntityHashMap, FloatOrd, HashSet};
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{Buffer, Context, DescriptorPool, DescriptorSet, WriteDescriptorSet, WriteDescriptorSetKind};
+use crate::{Render, RenderApp, RenderSet};
+use crate::camera::ExtractedCamera;
+use crate::environment::{Cubemap, EnvironmentMap, EnvironmentMapBindings};
+use crate::extract::FrameContext;
+use crate::graph::{RenderGraphApp, RenderLabel};
+use crate::graph::node::ViewNodeRunner;
+use crate::lod::ViewLodMeshes;
+use crate::mesh::MeshMaterialFlags;
+use crate::prelude::{Image, ImageView};
+use crate::raytracing::{AccelerationStructureBuilder, RayTracingGpuScene, RayTracingScene};
+use crate::render_asset::RenderAssets;
+use crate::render_phase::{sort_phase_system, PhaseItem, RenderPhase};
+use crate::skinning::SkinPalettes;
+use crate::spatial::{Frustum, SceneBvh};
+use crate::specialized_pipeline::SpecializedPipelines;
+use crate::view::{ViewMotionVectors, ViewTarget, MOTION_VECTORS_FORMAT};
+
+/// Sub graph rendering a camera through a G-buffer, select it with [`RenderPath::Deferred`](crate::camera::RenderPath).
+pub const DEFERRED_GRAPH: RenderLabel = RenderLabel::new("deferred");
+pub const DEFERRED_GBUFFER_NODE: &str = "deferred_gbuffer_pass";
+pub const DEFERRED_LIGHTING_NODE: &str = "deferred_lighting_pass";
+pub const DEFERRED_SKYBOX_NODE: &str = "deferred_skybox_pass";
+
+/// Base color in rgb, metallic in alpha.
+pub const GBUFFER_ALBEDO_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+/// World space normal in rgb, distance from the camera in alpha, 0 where nothing was drawn.
+pub const GBUFFER_NORMAL_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// Emissive radiance in rgb, roughness in alpha.
+pub const GBUFFER_MATERIAL_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// Depth formats of the G-buffer pass in order of preference.
+pub const GBUFFER_DEPTH_FORMATS: &[vk::Format] = &[vk::Format::D32_SFLOAT, vk::Format::X8_D24_UNORM_PACK32];
+/// Visibility of the sun, 0 in shadow. Traced against the TLAS when the device supports ray tracing.
+pub const SUN_SHADOW_FORMAT: vk::Format = vk::Format::R8_UNORM;
+/// Matches `WORKGROUP_SIZE` in `shaders/deferred/common.glsl`.
+pub const DEFERRED_WORKGROUP_SIZE: u32 = 8;
+
+/// Rasterizes the opaque instances of the [`RayTracingScene`] into a G-buffer and shades it in compute passes.
+///
+/// Sun shadows are ray traced when the device supports ray tracing and the scene has a TLAS, the rest of
+/// the graph only needs a raster capable device.
+pub struct DeferredPlugin;
+
+impl Plugin for DeferredPlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<DeferredPipeline>()
+                .init_resource::<SpecializedPipelines<DeferredPipelineKey>>()
+                .init_resource::<DeferredViews>()
+                .add_systems(
+                    Render, (
+                        queue_deferred_meshes.in_set(RenderSet::Queue),
+                        sort_phase_system::<Opaque3d>.in_set(RenderSet::PhaseSort),
+                        (prepare_deferred_pipeline, specialize_deferred_pipelines)
+                            .chain()
+                            .in_set(RenderSet::PrepareResources),
+                        prepare_deferred_views.in_set(RenderSet::PrepareBindGroups),
+                    )
+                )
+                .add_render_sub_graph(DEFERRED_GRAPH)
+                .add_render_graph_node::<ViewNodeRunner<DeferredGBufferNode>>(DEFERRED_GRAPH, DEFERRED_GBUFFER_NODE)
+                .add_render_graph_node::<ViewNodeRunner<DeferredLightingNode>>(DEFERRED_GRAPH, DEFERRED_LIGHTING_NODE)
+                .add_render_graph_node::<ViewNodeRunner<SkyboxNode>>(DEFERRED_GRAPH, DEFERRED_SKYBOX_NODE)
+                .add_render_graph_edges(DEFERRED_GRAPH, &[DEFERRED_GBUFFER_NODE, DEFERRED_LIGHTING_NODE, DEFERRED_SKYBOX_NODE]);
+        }
+    }
+}
+
+/// An opaque instance seen by a view, drawn into its G-buffer.
+pub struct Opaque3d {
+    pub entity: Entity,
+    /// Distance of the instance origin in front of the camera
+    pub view_depth: f32,
+    /// Position of the instance in the [`RayTracingScene`], indexes the previous frame transforms
+    pub instance_index: u32,
+    pub pipeline: DeferredPipelineKey,
+}
+
+impl PhaseItem for Opaque3d {
+    /// Front to back, so the depth test rejects hidden fragments early
+    type SortKey = FloatOrd;
+
+    #[inline]
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    #[inline]
+    fn sort_key(&self) -> Self::SortKey {
+        FloatOrd(self.view_depth)
+    }
+}
+
+fn queue_deferred_meshes(
+    mut commands: Commands,
+    scene: Res<RayTracingScene>,
+    gpu_scene: Res<RayTracingGpuScene>,
+    palettes: Res<SkinPalettes>,
+    bvh: Res<SceneBvh>,
+    cameras: Query<(Entity, &ExtractedCamera, Option<&ViewLodMeshes>)>,
+) {
+    for (entity, camera, lods) in cameras.iter() {
+        if camera.render_graph != DEFERRED_GRAPH {
+            continue;
+        }
+
+        let view_from_world = camera.world_from_view.inverse();
+        let frustum = Frustum::from_view_projection(&(camera.projection * view_from_world));
+        let mut phase = RenderPhase::<Opaque3d>::default();
+        for (instance_index, (instance_entity, instance)) in scene.iter().enumerate() {
+            if !bvh.may_be_visible(instance_entity, &frustum) {
+                continue;
+            }
+            // instances without mesh buffers are only known to the acceleration structures
+            let Some((mesh, material)) = gpu_scene.geometry(instance_entity) else {
+                continue;
+            };
+            let mesh = lods.map_or(mesh, |lods| lods.mesh(instance_entity, mesh));
+            let material = MeshMaterialFlags::from_material(material);
+            if material.contains(MeshMaterialFlags::ALPHA_BLEND) {
+                continue;
+            }
+
+            let origin = Vec3::new(instance.transform[3], instance.transform[7], instance.transform[11]);
+            phase.add(Opaque3d {
+                entity: instance_entity,
+                view_depth: -view_from_world.transform_point3(origin).z,
+                instance_index: instance_index as u32,
+                pipeline: DeferredPipelineKey::new(palettes.vertex_layout(instance_entity, mesh), material),
+            });
+        }
+        commands.entity(entity).insert(phase);
+    }
+}
+
+/// Matches `DeferredUniform` in `shaders/deferred/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct DeferredUniform {
+    world_from_view: [f32; 16],
+    view_from_clip: [f32; 16],
+    clip_from_world: [f32; 16],
+    previous_clip_from_world: [f32; 16],
+    view_size: [f32; 2],
+    environment_intensity: f32,
+    /// Whether the environment cube map is bound, the procedural sky is used otherwise
+    environment_map: u32,
+    /// Whether the sun shadow mask is traced this frame, the sun is unoccluded otherwise
+    sun_shadows: u32,
+    _padding: [u32; 3],
+}
+
+/// A G-buffer attachment of a [`DeferredViewState`], rasterized into and read by shaders.
+pub struct GBufferImage {
+    pub image: Image,
+    pub view: ImageView,
+}
+
+impl GBufferImage {
+    fn new(context: &Context, usage: vk::ImageUsageFlags, format: vk::Format, size: UVec2) -> anyhow::Result<Self> {
+        let image = context.create_image(usage, MemoryLocation::GpuOnly, format, size.x, size.y)?;
+        let view = image.create_image_view()?;
+        Ok(Self {
+            image: image.into(),
+            view: view.into(),
+        })
+    }
+
+    fn color(context: &Context, format: vk::Format, size: UVec2) -> anyhow::Result<Self> {
+        Self::new(
+            context,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            format,
+            size,
+        )
+    }
+}
+
+impl From<&ViewMotionVectors> for GBufferImage {
+    fn from(motion_vectors: &ViewMotionVectors) -> Self {
+        Self {
+            image: motion_vectors.image.clone(),
+            view: motion_vectors.view.clone(),
+        }
+    }
+}
+
+/// Bindings of the sun shadow pass, on devices supporting ray tracing.
+pub struct SunShadowBindings {
+    _descriptor_pool: DescriptorPool,
+    pub(crate) descriptor_set: DescriptorSet,
+}
+
+impl SunShadowBindings {
+    fn new(context: &Context, shadow: &SunShadowPipeline) -> anyhow::Result<Self> {
+        let descriptor_pool = context.create_descriptor_pool(1, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 2,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+            },
+        ])?;
+        let descriptor_set = descriptor_pool.allocate_set(&shadow.descriptor_set_layout)?;
+        Ok(Self {
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+        })
+    }
+}
+
+/// G-buffer and bindings of a camera rendered with the [`DEFERRED_GRAPH`], kept across frames.
+pub struct DeferredViewState {
+    pub albedo: GBufferImage,
+    pub normal: GBufferImage,
+    pub material: GBufferImage,
+    pub depth: GBufferImage,
+    pub sun_shadow: GBufferImage,
+    /// Screen space motion in pixels from the previous frame, the [`ViewMotionVectors`] of cameras having them
+    pub velocity: GBufferImage,
+    /// Whether the [`velocity`](Self::velocity) attachment is owned by the state rather than the camera
+    owns_velocity: bool,
+    /// Camera of the previous frame
+    previous_clip_from_world: Option<Mat4>,
+    uniform_buffer: Buffer,
+    _descriptor_pool: DescriptorPool,
+    pub(crate) descriptor_set: DescriptorSet,
+    /// `None` without ray tracing support
+    pub(crate) shadow: Option<SunShadowBindings>,
+    /// Whether the sun shadow mask is traced this frame
+    pub(crate) sun_shadows: bool,
+    size: UVec2,
+}
+
+impl DeferredViewState {
+    fn new(
+        context: &Context,
+        pipeline: &DeferredPipelineResources,
+        motion_vectors: Option<&ViewMotionVectors>,
+        size: UVec2,
+    ) -> anyhow::Result<Self> {
+        let velocity = match motion_vectors {
+            Some(motion_vectors) => motion_vectors.into(),
+            None => GBufferImage::new(
+                context,
+                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                MOTION_VECTORS_FORMAT,
+                size,
+            )?,
+        };
+        let uniform_buffer = context.create_buffer(
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of::<DeferredUniform>() as _,
+        )?;
+        let descriptor_pool = context.create_descriptor_pool(1, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 6,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 2,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1,
+            },
+        ])?;
+        let descriptor_set = descriptor_pool.allocate_set(pipeline.descriptor_set_layout())?;
+        let shadow = pipeline
+            .shadow
+            .as_ref()
+            .map(|shadow| SunShadowBindings::new(context, shadow))
+            .transpose()?;
+
+        Ok(Self {
+            albedo: GBufferImage::color(context, GBUFFER_ALBEDO_FORMAT, size)?,
+            normal: GBufferImage::color(context, GBUFFER_NORMAL_FORMAT, size)?,
+            material: GBufferImage::color(context, GBUFFER_MATERIAL_FORMAT, size)?,
+            depth: GBufferImage::new(context, vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT, pipeline.depth_format, size)?,
+            sun_shadow: GBufferImage::new(context, vk::ImageUsageFlags::STORAGE, SUN_SHADOW_FORMAT, size)?,
+            velocity,
+            owns_velocity: motion_vectors.is_none(),
+            previous_clip_from_world: None,
+            uniform_buffer,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            shadow,
+            sun_shadows: false,
+            size,
+        })
+    }
+
+    /// Color attachments of the geometry passes, in the order of the G-buffer fragment shader outputs
+    #[inline]
+    pub fn attachments(&self) -> [&GBufferImage; 4] {
+        [&self.albedo, &self.normal, &self.material, &self.velocity]
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct DeferredViews(pub(crate) EntityHashMap<Entity, DeferredViewState>);
+
+impl DeferredViews {
+    pub fn get(&self, entity: Entity) -> Option<&DeferredViewState> {
+        self.0.get(&entity)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn prepare_deferred_views(
+    mut views: ResMut<DeferredViews>,
+    pipeline: Res<DeferredPipeline>,
+    builder: Res<AccelerationStructureBuilder>,
+    gpu_scene: Res<RayTracingGpuScene>,
+    palettes: Res<SkinPalettes>,
+    environment_map: Res<EnvironmentMap>,
+    environment_bindings: Res<EnvironmentMapBindings>,
+    cubemaps: Res<RenderAssets<Cubemap>>,
+    cameras: Query<(Entity, &ExtractedCamera, &ViewTarget, Option<&ViewMotionVectors>)>,
+    frame_context: Res<FrameContext>,
+) {
+    let (
+        Some(pipeline),
+        Some((environment_view, has_environment_map)),
+        Some(environment_sampler),
+    ) = (
+        pipeline.resources(),
+        environment_bindings.view(&environment_map, &cubemaps),
+        environment_bindings.sampler(),
+    ) else {
+        views.0.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+
+    for (entity, camera, target, motion_vectors) in cameras.iter() {
+        if camera.render_graph != DEFERRED_GRAPH {
+            continue;
+        }
+        alive.insert(entity);
+
+        let up_to_date = matches!(
+            views.0.get(&entity),
+            Some(state) if state.size == target.size && state.owns_velocity == motion_vectors.is_none()
+        );
+        if !up_to_date {
+            match DeferredViewState::new(context, pipeline, motion_vectors, target.size) {
+                Ok(state) => {
+                    views.0.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create deferred G-buffer: {err}");
+                    views.0.remove(&entity);
+                    continue;
+                }
+            }
+        }
+        let state = views.0.get_mut(&entity).unwrap();
+        if let Some(motion_vectors) = motion_vectors {
+            state.velocity = motion_vectors.into();
+        }
+
+        // the TLAS may be swapped by a rebuild, rewrite the shadow set every frame
+        state.sun_shadows = false;
+        if let (Some(shadow), Some(tlas)) = (&state.shadow, builder.tlas()) {
+            shadow.descriptor_set.update(&[
+                WriteDescriptorSet {
+                    binding: 0,
+                    kind: WriteDescriptorSetKind::AccelerationStructure {
+                        acceleration_structure: tlas,
+                    },
+                },
+                WriteDescriptorSet {
+                    binding: 2,
+                    kind: WriteDescriptorSetKind::StorageImage {
+                        view: &state.normal.view,
+                        layout: vk::ImageLayout::GENERAL,
+                    },
+                },
+                WriteDescriptorSet {
+                    binding: 3,
+                    kind: WriteDescriptorSetKind::UniformBuffer {
+                        buffer: &state.uniform_buffer,
+                    },
+                },
+                WriteDescriptorSet {
+                    binding: 10,
+                    kind: WriteDescriptorSetKind::StorageImage {
+                        view: &state.sun_shadow.view,
+                        layout: vk::ImageLayout::GENERAL,
+                    },
+                },
+            ]);
+            state.sun_shadows = true;
+        }
+
+        let clip_from_world = camera.projection * camera.world_from_view.inverse();
+        let previous_clip_from_world = state.previous_clip_from_world.replace(clip_from_world).unwrap_or(clip_from_world);
+        let uniform = DeferredUniform {
+            world_from_view: camera.world_from_view.to_cols_array(),
+            view_from_clip: camera.projection.inverse().to_cols_array(),
+            clip_from_world: clip_from_world.to_cols_array(),
+            previous_clip_from_world: previous_clip_from_world.to_cols_array(),
+            view_size: target.size.as_vec2().to_array(),
+            environment_intensity: environment_map.intensity,
+            environment_map: has_environment_map as u32,
+            sun_shadows: state.sun_shadows as u32,
+            _padding: [0; 3],
+        };
+        if let Err(err) = state.uniform_buffer.copy_data_to_buffer(std::slice::from_ref(&uniform)) {
+            error!("Failed to upload deferred uniform: {err}");
+        }
+
+        // the view target is recreated on resize, the palette and motion buffers when they grow
+        let mut writes = vec![
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &state.albedo.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 2,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &state.normal.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 3,
+                kind: WriteDescriptorSetKind::UniformBuffer {
+                    buffer: &state.uniform_buffer,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 7,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &state.material.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 8,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &target.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 9,
+                kind: WriteDescriptorSetKind::CombinedImageSampler {
+                    view: environment_view,
+                    sampler: environment_sampler,
+                    layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 10,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &state.sun_shadow.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 11,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &state.velocity.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+        ];
+        if let Some(motion_buffer) = gpu_scene.motion_buffer() {
+            writes.push(WriteDescriptorSet {
+                binding: 6,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: motion_buffer,
+                },
+            });
+        }
+        if let Some(palette) = palettes.buffer() {
+            writes.push(WriteDescriptorSet {
+                binding: 4,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: palette,
+                },
+            });
+        }
+        state.descriptor_set.update(&writes);
+    }
+
+    views.0.retain(|entity, _| alive.contains(entity));
+}
@@ -0,0 +1,342 @@
+use ash::vk;
+use bevy_ecs::prelude::World;
+use avalanche_hlvk::{ImageBarrier, RenderingAttachment, RenderingDepthAttachment};
+use crate::backend::CommandEncoder;
+use crate::deferred::{
+    DeferredPipeline, DeferredPipelineKey, DeferredPushConstants, DeferredViews, Opaque3d, DEFERRED_WORKGROUP_SIZE,
+};
+use crate::extract::FrameContext;
+use crate::globals::Globals;
+use crate::lod::ViewLodMeshes;
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::ViewNode;
+use crate::raytracing::{RayTracingGpuScene, RayTracingScene};
+use crate::render_phase::{PhaseItem, RenderPhase};
+use crate::skinning::SkinPalettes;
+use crate::specialized_pipeline::SpecializedPipelines;
+use crate::transparent::world_from_object;
+use crate::view::ViewTarget;
+
+/// Rasterizes the [`Opaque3d`] items of the view entity of the [`DEFERRED_GRAPH`](super::DEFERRED_GRAPH) into its G-buffer.
+#[derive(Default)]
+pub struct DeferredGBufferNode;
+
+impl ViewNode for DeferredGBufferNode {
+    type ViewQuery = (&'static ViewTarget, &'static RenderPhase<Opaque3d>, Option<&'static ViewLodMeshes>);
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (target, phase, lods): (&ViewTarget, &RenderPhase<Opaque3d>, Option<&ViewLodMeshes>),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pipeline) = world.resource::<DeferredPipeline>().resources() else {
+            return Ok(());
+        };
+        let Some(state) = world.resource::<DeferredViews>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, &pipeline.layout);
+
+        // the G-buffer is cleared every frame
+        let [albedo, normal, material, velocity] = state.attachments().map(|attachment| ImageBarrier {
+            image: &attachment.image,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        });
+        command_buffer.pipeline_image_barriers(&[
+            albedo,
+            normal,
+            material,
+            velocity,
+            ImageBarrier {
+                image: &state.depth.image,
+                old_layout: vk::ImageLayout::UNDEFINED,
+                new_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                src_access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                dst_stage_mask: vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS,
+            },
+        ]);
+
+        let extent = vk::Extent2D {
+            width: target.size.x,
+            height: target.size.y,
+        };
+        // a distance of 0 marks the pixels nothing was drawn to
+        let attachments = state.attachments().map(|attachment| RenderingAttachment {
+            view: &attachment.view,
+            layout: vk::ImageLayout::GENERAL,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            clear_color: [0.0; 4],
+        });
+        command_buffer.begin_rendering_with_depth(
+            &attachments,
+            Some(&RenderingDepthAttachment {
+                view: &state.depth.view,
+                layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::DONT_CARE,
+                clear_depth: 1.0,
+            }),
+            extent,
+        );
+        command_buffer.set_viewport(extent);
+        command_buffer.set_scissor(extent);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, &pipeline.layout, 0, &[&state.descriptor_set]);
+
+        let scene = world.resource::<RayTracingScene>();
+        let gpu_scene = world.resource::<RayTracingGpuScene>();
+        let pipelines = world.resource::<SpecializedPipelines<DeferredPipelineKey>>();
+        let palettes = world.resource::<SkinPalettes>();
+        let mut bound = None;
+
+        for item in &phase.items {
+            let (Some(instance), Some((mesh, material))) = (scene.get(item.entity()), gpu_scene.geometry(item.entity())) else {
+                continue;
+            };
+            let mesh = lods.map_or(mesh, |lods| lods.mesh(item.entity(), mesh));
+            // skinned variants need the joint influences and the pose the item was queued with
+            let skin = match item.pipeline.mesh.vertex_layout.is_skinned() {
+                true => match (&mesh.skin_buffer, palettes.offset(item.entity())) {
+                    (Some(skin_buffer), Some(joint_offset)) => Some((skin_buffer, joint_offset)),
+                    _ => continue,
+                },
+                false => None,
+            };
+            if bound != Some(item.pipeline) {
+                let Some(pipeline) = pipelines.get(&item.pipeline) else {
+                    continue;
+                };
+                command_buffer.bind_raster_pipeline(pipeline);
+                bound = Some(item.pipeline);
+            }
+
+            let [r, g, b] = material.emissive;
+            let push_constants = DeferredPushConstants {
+                world_from_object: world_from_object(&instance.transform).to_cols_array(),
+                base_color: material.base_color.to_linear_rgba(),
+                emissive: [r, g, b, material.roughness],
+                metallic: material.metallic,
+                joint_offset: skin.map_or(0, |(_, joint_offset)| joint_offset),
+                instance_index: item.instance_index,
+            };
+            command_buffer.push_constants(
+                &pipeline.layout,
+                vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                0,
+                bytemuck::bytes_of(&push_constants),
+            );
+
+            match skin {
+                Some((skin_buffer, _)) => command_buffer.bind_vertex_buffers(0, &[&mesh.vertex_buffer, skin_buffer]),
+                None => command_buffer.bind_vertex_buffer(&mesh.vertex_buffer),
+            }
+            match &mesh.index_buffer {
+                Some(index_buffer) => {
+                    command_buffer.bind_index_buffer(index_buffer, vk::IndexType::UINT32);
+                    command_buffer.draw_indexed(mesh.index_count);
+                }
+                None => command_buffer.draw(mesh.vertex_count),
+            }
+        }
+        command_buffer.end_rendering();
+
+        Ok(())
+    }
+}
+
+/// Shades the G-buffer written by the [`DeferredGBufferNode`] and the raster passes after it into the [`ViewTarget`].
+///
+/// Traces the sun shadow mask first when the device supports ray tracing.
+#[derive(Default)]
+pub struct DeferredLightingNode;
+
+impl ViewNode for DeferredLightingNode {
+    type ViewQuery = &'static ViewTarget;
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        target: &ViewTarget,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pipeline) = world.resource::<DeferredPipeline>().resources() else {
+            return Ok(());
+        };
+        let Some(state) = world.resource::<DeferredViews>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+        let Some(mut encoder) = rendering_context.command_encoder() else {
+            return Ok(());
+        };
+        // barriers and descriptor sets have no backend independent equivalent yet
+        let command_buffer = encoder.command_buffer();
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.layout);
+
+        let shadow = pipeline
+            .shadow
+            .as_ref()
+            .zip(state.shadow.as_ref())
+            .filter(|_| state.sun_shadows);
+        if let Some((shadow, bindings)) = shadow {
+            command_buffer.pipeline_image_barriers(&[
+                ImageBarrier {
+                    image: &state.normal.image,
+                    old_layout: vk::ImageLayout::GENERAL,
+                    new_layout: vk::ImageLayout::GENERAL,
+                    src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                    dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+                    src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                    dst_stage_mask: vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
+                },
+                ImageBarrier {
+                    image: &state.sun_shadow.image,
+                    old_layout: vk::ImageLayout::UNDEFINED,
+                    new_layout: vk::ImageLayout::GENERAL,
+                    src_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+                    dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                    src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+                    dst_stage_mask: vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
+                },
+            ]);
+            command_buffer.bind_rt_pipeline(&shadow.pipeline);
+            command_buffer.bind_descriptor_sets(
+                vk::PipelineBindPoint::RAY_TRACING_KHR,
+                &shadow.layout,
+                0,
+                &[&bindings.descriptor_set],
+            );
+            command_buffer.trace_rays(&shadow.shader_binding_table, target.size.x, target.size.y);
+        }
+
+        let [albedo, normal, material] = [&state.albedo, &state.normal, &state.material].map(|attachment| ImageBarrier {
+            image: &attachment.image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+        });
+        // without the shadow pass the mask is never read, but it is bound in the general layout
+        let sun_shadow = match shadow {
+            Some(_) => ImageBarrier {
+                image: &state.sun_shadow.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+                src_stage_mask: vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
+                dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            },
+            None => ImageBarrier {
+                image: &state.sun_shadow.image,
+                old_layout: vk::ImageLayout::UNDEFINED,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+                src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            },
+        };
+        command_buffer.pipeline_image_barriers(&[
+            albedo,
+            normal,
+            material,
+            sun_shadow,
+            // the output is fully overwritten every frame
+            ImageBarrier {
+                image: &target.image,
+                old_layout: vk::ImageLayout::UNDEFINED,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::MEMORY_READ,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            },
+        ]);
+
+        encoder.bind_pipeline(&pipeline.lighting_pipeline);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::COMPUTE, &pipeline.layout, 0, &[&state.descriptor_set]);
+        encoder.dispatch(
+            target.size.x.div_ceil(DEFERRED_WORKGROUP_SIZE),
+            target.size.y.div_ceil(DEFERRED_WORKGROUP_SIZE),
+            1,
+        );
+
+        Ok(())
+    }
+}
+
+/// Draws the [`EnvironmentMap`](crate::environment::EnvironmentMap) behind the geometry shaded by the [`DeferredLightingNode`].
+#[derive(Default)]
+pub struct SkyboxNode;
+
+impl ViewNode for SkyboxNode {
+    type ViewQuery = &'static ViewTarget;
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        target: &ViewTarget,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pipeline) = world.resource::<DeferredPipeline>().resources() else {
+            return Ok(());
+        };
+        let Some(state) = world.resource::<DeferredViews>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+        let Some(mut encoder) = rendering_context.command_encoder() else {
+            return Ok(());
+        };
+        // barriers and descriptor sets have no backend independent equivalent yet
+        let command_buffer = encoder.command_buffer();
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.layout);
+
+        // background pixels are left untouched by the lighting pass and the geometry passes
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image: &target.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            },
+            ImageBarrier {
+                image: &state.velocity.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            },
+        ]);
+
+        encoder.bind_pipeline(&pipeline.skybox_pipeline);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::COMPUTE, &pipeline.layout, 0, &[&state.descriptor_set]);
+        encoder.dispatch(
+            target.size.x.div_ceil(DEFERRED_WORKGROUP_SIZE),
+            target.size.y.div_ceil(DEFERRED_WORKGROUP_SIZE),
+            1,
+        );
+
+        Ok(())
+    }
+}
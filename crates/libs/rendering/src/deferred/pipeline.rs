@@ -0,0 +1,284 @@
+use std::sync::Arc;
+use anyhow::Context as _;
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::prelude::{Query, Res, ResMut, Resource};
+use bevy_log::error;
+use avalanche_hlvk::{
+    BlendMode, Context, DescriptorSetLayout, FormatSelector, PipelineLayout, RasterColorAttachment,
+    RasterDepthAttachment, RasterPipeline, RasterPipelineCreateInfo, RayTracingPipeline, RayTracingPipelineCreateInfo,
+    RayTracingShaderGroup, ShaderBindingTable,
+};
+use crate::backend::VulkanPipeline;
+use crate::camera::ExtractedCamera;
+use crate::deferred::{
+    Opaque3d, DEFERRED_GRAPH, GBUFFER_ALBEDO_FORMAT, GBUFFER_DEPTH_FORMATS, GBUFFER_MATERIAL_FORMAT,
+    GBUFFER_NORMAL_FORMAT,
+};
+use crate::extract::FrameContext;
+use crate::globals;
+use crate::mesh::{MeshMaterialFlags, MeshPipelineKey, MeshVertexLayout};
+use crate::path_tracing::SHADOW_MISS_SHADER;
+use crate::render_phase::RenderPhase;
+use crate::shader::ShaderDirectory;
+use crate::specialized_pipeline::{SpecializedPipelineKey, SpecializedPipelines};
+use crate::view::MOTION_VECTORS_FORMAT;
+
+pub(crate) const MESH_VERTEX_SHADER: &str = "deferred/mesh.vert";
+pub(crate) const SKINNED_MESH_VERTEX_SHADER: &str = "deferred/skinned_mesh.vert";
+pub(crate) const GBUFFER_FRAGMENT_SHADER: &str = "deferred/gbuffer.frag";
+pub(crate) const LIGHTING_COMPUTE_SHADER: &str = "deferred/lighting.comp";
+pub(crate) const SKYBOX_COMPUTE_SHADER: &str = "deferred/skybox.comp";
+pub(crate) const SHADOW_RAYGEN_SHADER: &str = "deferred/shadow.rgen";
+
+/// Matches `DeferredPushConstants` in `shaders/deferred/mesh.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DeferredPushConstants {
+    pub world_from_object: [f32; 16],
+    /// Base color in rgb, alpha is unused
+    pub base_color: [f32; 4],
+    /// Emissive radiance in rgb, roughness in alpha
+    pub emissive: [f32; 4],
+    pub metallic: f32,
+    /// First matrix of the instance in the [`SkinPalettes`](crate::skinning::SkinPalettes), skinned meshes only
+    pub joint_offset: u32,
+    /// Index of the previous frame transform of the instance, see [`Opaque3d::instance_index`]
+    pub instance_index: u32,
+}
+
+// SAFETY: plain `f32` and `u32` fields without implicit padding
+unsafe impl Zeroable for DeferredPushConstants {}
+unsafe impl Pod for DeferredPushConstants {}
+
+/// Traces the sun visibility of the G-buffer into the [`SUN_SHADOW_FORMAT`](super::SUN_SHADOW_FORMAT) mask.
+pub struct SunShadowPipeline {
+    pub descriptor_set_layout: DescriptorSetLayout,
+    pub layout: PipelineLayout,
+    pub pipeline: RayTracingPipeline,
+    pub shader_binding_table: ShaderBindingTable,
+}
+
+/// Layout shared by the deferred passes, the G-buffer pipelines are [`DeferredPipelineKey`] variants.
+pub struct DeferredPipelineResources {
+    /// Reflected from the G-buffer, lighting and skybox shaders
+    pub layout: PipelineLayout,
+    /// Compute pipelines, bound through the [`CommandEncoder`](crate::backend::CommandEncoder)
+    pub lighting_pipeline: VulkanPipeline,
+    pub skybox_pipeline: VulkanPipeline,
+    pub depth_format: vk::Format,
+    /// `None` on devices without ray tracing support, the sun is never shadowed then
+    pub shadow: Option<SunShadowPipeline>,
+}
+
+/// The deferred pipelines, created the first time a camera uses the deferred graph.
+#[derive(Resource, Default)]
+pub enum DeferredPipeline {
+    #[default]
+    Uninitialized,
+    Ready(Box<DeferredPipelineResources>),
+    /// Creation failed, usually shaders are missing.
+    Failed,
+}
+
+impl DeferredPipelineResources {
+    #[inline]
+    pub fn descriptor_set_layout(&self) -> &DescriptorSetLayout {
+        &self.layout.descriptor_set_layouts()[0]
+    }
+}
+
+impl DeferredPipeline {
+    pub fn resources(&self) -> Option<&DeferredPipelineResources> {
+        match self {
+            DeferredPipeline::Ready(resources) => Some(resources),
+            _ => None,
+        }
+    }
+}
+
+/// A mesh pipeline variant of the G-buffer pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DeferredPipelineKey {
+    pub mesh: MeshPipelineKey,
+}
+
+impl DeferredPipelineKey {
+    pub fn new(vertex_layout: MeshVertexLayout, material: MeshMaterialFlags) -> Self {
+        Self {
+            mesh: MeshPipelineKey {
+                vertex_layout,
+                material,
+                samples: vk::SampleCountFlags::TYPE_1,
+                target_format: GBUFFER_ALBEDO_FORMAT,
+            },
+        }
+    }
+}
+
+impl SpecializedPipelineKey for DeferredPipelineKey {
+    type Specializer = DeferredPipelineResources;
+
+    fn specialize(
+        &self,
+        context: &Context,
+        shaders: &ShaderDirectory,
+        specializer: &DeferredPipelineResources,
+    ) -> anyhow::Result<RasterPipeline> {
+        let vertex_shader = match self.mesh.vertex_layout.is_skinned() {
+            true => SKINNED_MESH_VERTEX_SHADER,
+            false => MESH_VERTEX_SHADER,
+        };
+        let vertex_shader = shaders.load(context, vertex_shader, vk::ShaderStageFlags::VERTEX)?;
+        self.mesh.vertex_layout.validate(&vertex_shader)?;
+
+        context.create_graphics_pipeline(&specializer.layout, RasterPipelineCreateInfo {
+            shaders: &[
+                vertex_shader,
+                shaders.load(context, GBUFFER_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT)?,
+            ],
+            primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            vertex_stream: &self.mesh.vertex_layout.vertex_stream(),
+            viewport: None,
+            scissor: None,
+            color_attachments: &[
+                RasterColorAttachment::new(self.mesh.target_format, BlendMode::Opaque),
+                RasterColorAttachment::new(GBUFFER_NORMAL_FORMAT, BlendMode::Opaque),
+                RasterColorAttachment::new(GBUFFER_MATERIAL_FORMAT, BlendMode::Opaque),
+                RasterColorAttachment::new(MOTION_VECTORS_FORMAT, BlendMode::Opaque),
+            ],
+            depth_attachment: Some(RasterDepthAttachment::new(specializer.depth_format, vk::CompareOp::LESS, true)),
+            dynamic_states: Some(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]),
+            polygon_mode: vk::PolygonMode::FILL,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            samples: self.mesh.samples,
+            cull_mode: self.mesh.cull_mode(),
+        })
+    }
+}
+
+fn create_shadow_pipeline(context: &Context, shaders: &ShaderDirectory) -> anyhow::Result<SunShadowPipeline> {
+    let storage_image = |binding: u32| vk::DescriptorSetLayoutBinding::builder()
+        .binding(binding)
+        .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::RAYGEN_KHR)
+        .build();
+    // binding numbers match the set of the other deferred passes
+    let bindings = [
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::RAYGEN_KHR)
+            .build(),
+        storage_image(2),
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(3)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::RAYGEN_KHR)
+            .build(),
+        storage_image(10),
+    ];
+    let descriptor_set_layout = context.create_descriptor_set_layout(&bindings)?;
+    let layout = context.create_pipeline_layout(&[&descriptor_set_layout])?;
+
+    // shadow rays skip closest hit shaders, so the pass has no hit group
+    let shadow_shaders = [
+        shaders.load(context, SHADOW_RAYGEN_SHADER, vk::ShaderStageFlags::RAYGEN_KHR)?,
+        shaders.load(context, SHADOW_MISS_SHADER, vk::ShaderStageFlags::MISS_KHR)?,
+    ];
+    let pipeline = context.create_ray_tracing_pipeline(&layout, RayTracingPipelineCreateInfo {
+        shaders: &shadow_shaders,
+        groups: &[
+            RayTracingShaderGroup::RayGen(0),
+            RayTracingShaderGroup::Miss(1),
+        ],
+        max_ray_recursion_depth: 1,
+    })?;
+    let shader_binding_table = context.create_shader_binding_table(&pipeline)?;
+
+    Ok(SunShadowPipeline {
+        descriptor_set_layout,
+        layout,
+        pipeline,
+        shader_binding_table,
+    })
+}
+
+fn create_resources(context: &Context, shaders: &ShaderDirectory) -> anyhow::Result<DeferredPipelineResources> {
+    // the shaders of every deferred pass but the shadow pass together declare the layout
+    let stages = [
+        (MESH_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX),
+        (SKINNED_MESH_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX),
+        (GBUFFER_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT),
+        (LIGHTING_COMPUTE_SHADER, vk::ShaderStageFlags::COMPUTE),
+        (SKYBOX_COMPUTE_SHADER, vk::ShaderStageFlags::COMPUTE),
+    ];
+    let stages = stages
+        .into_iter()
+        .map(|(path, stage)| shaders.load(context, path, stage))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let layout = globals::pipeline_layout(&stages)?;
+    anyhow::ensure!(globals::pass_set_count(&stages) == 1, "deferred shaders must only use set 0");
+
+    let depth_format = FormatSelector::new(GBUFFER_DEPTH_FORMATS, vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        .select(&context.physical_device)
+        .context("No G-buffer depth format is supported")?;
+
+    let lighting_pipeline = VulkanPipeline::Compute(Arc::new(context.create_compute_pipeline(&layout, &stages[3])?));
+    let skybox_pipeline = VulkanPipeline::Compute(Arc::new(context.create_compute_pipeline(&layout, &stages[4])?));
+
+    let features = &context.device_features;
+    let shadow = match features.ray_tracing_pipeline && features.acceleration_structure {
+        true => Some(create_shadow_pipeline(context, shaders)?),
+        false => None,
+    };
+
+    Ok(DeferredPipelineResources {
+        layout,
+        lighting_pipeline,
+        skybox_pipeline,
+        depth_format,
+        shadow,
+    })
+}
+
+pub(crate) fn prepare_deferred_pipeline(
+    mut pipeline: ResMut<DeferredPipeline>,
+    shaders: Res<ShaderDirectory>,
+    cameras: Query<&ExtractedCamera>,
+    frame_context: Res<FrameContext>,
+) {
+    if !matches!(*pipeline, DeferredPipeline::Uninitialized)
+        || !cameras.iter().any(|camera| camera.render_graph == DEFERRED_GRAPH) {
+        return;
+    }
+
+    *pipeline = match create_resources(frame_context.render_context(), &shaders) {
+        Ok(resources) => DeferredPipeline::Ready(Box::new(resources)),
+        Err(err) => {
+            error!("Failed to create deferred pipeline: {err}");
+            DeferredPipeline::Failed
+        }
+    };
+}
+
+/// Create the pipeline variants of the opaque items queued this frame.
+pub(crate) fn specialize_deferred_pipelines(
+    mut pipelines: ResMut<SpecializedPipelines<DeferredPipelineKey>>,
+    pipeline: Res<DeferredPipeline>,
+    shaders: Res<ShaderDirectory>,
+    phases: Query<&RenderPhase<Opaque3d>>,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(resources) = pipeline.resources() else {
+        return;
+    };
+    let context = frame_context.render_context();
+
+    for item in phases.iter().flat_map(|phase| &phase.items) {
+        pipelines.specialize(context, &shaders, resources, &item.pipeline);
+    }
+}
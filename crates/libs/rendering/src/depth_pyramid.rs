@@ -0,0 +1,219 @@
+mod node;
+mod pipeline;
+
+pub use node::*;
+pub use pipeline::*;
+
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Entity, IntoSystemConfigs, Query, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_math::UVec2;
+use bevy_utils::{EntityHashMap, HashSet};
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{
+    Context, DescriptorPool, DescriptorSet, Image, ImageView, WriteDescriptorSet, WriteDescriptorSetKind,
+};
+use crate::{Render, RenderApp, RenderSet};
+use crate::deferred::{prepare_deferred_views, DeferredViews, DEFERRED_GRAPH, DEFERRED_LIGHTING_NODE};
+use crate::extract::FrameContext;
+use crate::graph::RenderGraphApp;
+use crate::resource;
+use crate::terrain::TERRAIN_NODE;
+use crate::view::ViewTarget;
+
+/// Node of the [`DEFERRED_GRAPH`] building the depth pyramid of a view, see [`DepthPyramidNode`].
+pub const DEPTH_PYRAMID_NODE: &str = "depth_pyramid";
+
+/// Texels of a level holding the nearest distance in r and the farthest in g, in world units from the camera.
+pub const DEPTH_PYRAMID_FORMAT: vk::Format = vk::Format::R32G32_SFLOAT;
+/// Threads along each axis of a workgroup of the depth pyramid shaders.
+pub const DEPTH_PYRAMID_WORKGROUP_SIZE: u32 = 8;
+
+pub struct DepthPyramidPlugin;
+
+impl Plugin for DepthPyramidPlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<DepthPyramidPipeline>()
+                .init_resource::<DepthPyramidViews>()
+                .add_systems(
+                    Render, (
+                        prepare_depth_pyramid_pipeline.in_set(RenderSet::PrepareResources),
+                        prepare_depth_pyramid_views
+                            .after(prepare_deferred_views)
+                            .in_set(RenderSet::PrepareBindGroups),
+                    )
+                )
+                .add_render_graph_node::<DepthPyramidNode>(DEFERRED_GRAPH, DEPTH_PYRAMID_NODE)
+                .add_render_graph_edges(DEFERRED_GRAPH, &[TERRAIN_NODE, DEPTH_PYRAMID_NODE, DEFERRED_LIGHTING_NODE]);
+        }
+    }
+}
+
+/// Levels of a pyramid whose first level is `size`, down to a single texel.
+#[inline]
+pub fn depth_pyramid_levels(size: UVec2) -> u32 {
+    32 - size.x.max(size.y).max(1).leading_zeros()
+}
+
+/// Size of a level, each one covers the previous one with half the texels rounded down.
+#[inline]
+pub fn depth_pyramid_level_size(size: UVec2, level: u32) -> UVec2 {
+    (size >> level).max(UVec2::ONE)
+}
+
+/// A set per level, the first one reads the G-buffer and the others the previous level.
+pub struct DepthPyramidBindings {
+    _descriptor_pool: DescriptorPool,
+    pub(crate) descriptor_sets: Vec<DescriptorSet>,
+}
+
+/// Min/max depth pyramid of a view at the [`ViewTarget`] size, kept across frames.
+///
+/// The image stays in the `GENERAL` layout, its content is undefined while the pyramid pipeline is unavailable.
+pub struct DepthPyramidViewState {
+    pub image: Image,
+    /// Every level of the pyramid, the value of the [`DepthPyramidNode::OUT_PYRAMID`] slot
+    pub view: resource::ImageView,
+    /// A storage view per level
+    pub level_views: Vec<ImageView>,
+    pub size: UVec2,
+    pub(crate) bindings: Option<DepthPyramidBindings>,
+}
+
+impl DepthPyramidViewState {
+    fn new(context: &Context, size: UVec2) -> anyhow::Result<Self> {
+        let levels = depth_pyramid_levels(size);
+        let image = context.create_image_with_mips(
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+            MemoryLocation::GpuOnly,
+            DEPTH_PYRAMID_FORMAT,
+            size.x,
+            size.y,
+            levels,
+        )?;
+        let view = image.create_image_view()?.into();
+        let level_views = (0..levels)
+            .map(|level| image.create_mip_view(level))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            image,
+            view,
+            level_views,
+            size,
+            bindings: None,
+        })
+    }
+
+    fn create_bindings(&self, context: &Context, pipeline: &DepthPyramidPipelineResources) -> anyhow::Result<DepthPyramidBindings> {
+        let levels = self.level_views.len() as u32;
+        let descriptor_pool = context.create_descriptor_pool(levels, &[vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::STORAGE_IMAGE,
+            descriptor_count: levels * 2,
+        }])?;
+        let descriptor_sets = (0..levels)
+            .map(|_| descriptor_pool.allocate_set(pipeline.descriptor_set_layout()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        // the first set reads the G-buffer, written every frame
+        for (level, descriptor_set) in descriptor_sets.iter().enumerate().skip(1) {
+            descriptor_set.update(&[
+                WriteDescriptorSet {
+                    binding: 0,
+                    kind: WriteDescriptorSetKind::StorageImage {
+                        view: &self.level_views[level - 1],
+                        layout: vk::ImageLayout::GENERAL,
+                    },
+                },
+                WriteDescriptorSet {
+                    binding: 1,
+                    kind: WriteDescriptorSetKind::StorageImage {
+                        view: &self.level_views[level],
+                        layout: vk::ImageLayout::GENERAL,
+                    },
+                },
+            ]);
+        }
+
+        Ok(DepthPyramidBindings {
+            _descriptor_pool: descriptor_pool,
+            descriptor_sets,
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct DepthPyramidViews(pub(crate) EntityHashMap<Entity, DepthPyramidViewState>);
+
+impl DepthPyramidViews {
+    pub fn get(&self, entity: Entity) -> Option<&DepthPyramidViewState> {
+        self.0.get(&entity)
+    }
+}
+
+pub(crate) fn prepare_depth_pyramid_views(
+    mut views: ResMut<DepthPyramidViews>,
+    pipeline: Res<DepthPyramidPipeline>,
+    deferred_views: Res<DeferredViews>,
+    cameras: Query<(Entity, &ViewTarget)>,
+    frame_context: Res<FrameContext>,
+) {
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+
+    for (entity, target) in cameras.iter() {
+        let Some(deferred) = deferred_views.get(entity) else {
+            continue;
+        };
+        alive.insert(entity);
+
+        // the pyramid is created without the pipeline, the slot of the node always has a value
+        if !matches!(views.0.get(&entity), Some(state) if state.size == target.size) {
+            match DepthPyramidViewState::new(context, target.size) {
+                Ok(state) => {
+                    views.0.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create depth pyramid: {err}");
+                    views.0.remove(&entity);
+                    continue;
+                }
+            }
+        }
+        let state = views.0.get_mut(&entity).unwrap();
+
+        let Some(pipeline) = pipeline.resources() else {
+            continue;
+        };
+        if state.bindings.is_none() {
+            match state.create_bindings(context, pipeline) {
+                Ok(bindings) => state.bindings = Some(bindings),
+                Err(err) => {
+                    error!("Failed to create depth pyramid bindings: {err}");
+                    continue;
+                }
+            }
+        }
+        // the G-buffer is recreated with the view target, rewrite the set every frame
+        state.bindings.as_ref().unwrap().descriptor_sets[0].update(&[
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &deferred.normal.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &state.level_views[0],
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+        ]);
+    }
+
+    views.0.retain(|entity, _| alive.contains(entity));
+}
@@ -0,0 +1,148 @@
+use ash::vk;
+use bevy_ecs::prelude::World;
+use avalanche_hlvk::{Image, ImageBarrier};
+use crate::deferred::DeferredViews;
+use crate::depth_pyramid::{
+    depth_pyramid_level_size, DepthPyramidPipeline, DepthPyramidPushConstants, DepthPyramidViews,
+    DEPTH_PYRAMID_WORKGROUP_SIZE,
+};
+use crate::extract::FrameContext;
+use crate::globals::Globals;
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::Node;
+use crate::prelude::node_slot::{SlotInfo, SlotType};
+
+/// Makes the `src_access_mask` accesses of an image kept in the `GENERAL` layout visible to the `dst_access_mask` ones.
+fn general_barrier(
+    image: &Image,
+    src_access_mask: vk::AccessFlags2,
+    src_stage_mask: vk::PipelineStageFlags2,
+    dst_access_mask: vk::AccessFlags2,
+    dst_stage_mask: vk::PipelineStageFlags2,
+) -> ImageBarrier<'_> {
+    ImageBarrier {
+        image,
+        old_layout: vk::ImageLayout::GENERAL,
+        new_layout: vk::ImageLayout::GENERAL,
+        src_access_mask,
+        dst_access_mask,
+        src_stage_mask,
+        dst_stage_mask,
+    }
+}
+
+/// Builds the min/max depth pyramid of a view from the distances of its G-buffer, terrains included,
+/// and outputs it for occlusion culling and screen space effects.
+///
+/// Sky pixels are infinitely far. Texels of a level cover the texels of the previous one
+/// they overlap, so a level is never closer than the pixels below it.
+#[derive(Default)]
+pub struct DepthPyramidNode;
+
+impl DepthPyramidNode {
+    /// The [`ImageView`](crate::resource::ImageView) of every level, in the `GENERAL` layout.
+    pub const OUT_PYRAMID: &'static str = "pyramid";
+}
+
+impl Node for DepthPyramidNode {
+    fn output(&self) -> Vec<SlotInfo> {
+        vec![SlotInfo::new(Self::OUT_PYRAMID, SlotType::ImageView)]
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(state) = world.resource::<DepthPyramidViews>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+        graph.set_output(Self::OUT_PYRAMID, state.view.clone())?;
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+
+        // every level is rewritten, the previous pyramid may still be read by the last frame
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &state.image,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::MEMORY_READ,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+        }]);
+        let (Some(pipeline), Some(bindings)) = (world.resource::<DepthPyramidPipeline>().resources(), &state.bindings) else {
+            return Ok(());
+        };
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.layout);
+        let Some(deferred) = world.resource::<DeferredViews>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+
+        let mut source_size = state.size;
+        for (level, descriptor_set) in bindings.descriptor_sets.iter().enumerate() {
+            let size = depth_pyramid_level_size(state.size, level as u32);
+            let push_constants = DepthPyramidPushConstants {
+                source_size: source_size.to_array(),
+                size: size.to_array(),
+            };
+
+            if level == 0 {
+                // the G-buffer is written by the mesh and terrain raster passes
+                command_buffer.pipeline_image_barriers(&[general_barrier(
+                    &deferred.normal.image,
+                    vk::AccessFlags2::MEMORY_WRITE,
+                    vk::PipelineStageFlags2::ALL_COMMANDS,
+                    vk::AccessFlags2::SHADER_STORAGE_READ,
+                    vk::PipelineStageFlags2::COMPUTE_SHADER,
+                )]);
+                command_buffer.bind_compute_pipeline(&pipeline.seed);
+            } else {
+                command_buffer.pipeline_image_barriers(&[general_barrier(
+                    &state.image,
+                    vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                    vk::PipelineStageFlags2::COMPUTE_SHADER,
+                    vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                    vk::PipelineStageFlags2::COMPUTE_SHADER,
+                )]);
+                if level == 1 {
+                    command_buffer.bind_compute_pipeline(&pipeline.downsample);
+                }
+            }
+            command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::COMPUTE, &pipeline.layout, 0, &[descriptor_set]);
+            command_buffer.push_constants(
+                &pipeline.layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&push_constants),
+            );
+            command_buffer.dispatch(
+                size.x.div_ceil(DEPTH_PYRAMID_WORKGROUP_SIZE),
+                size.y.div_ceil(DEPTH_PYRAMID_WORKGROUP_SIZE),
+                1,
+            );
+            source_size = size;
+        }
+
+        command_buffer.pipeline_image_barriers(&[
+            general_barrier(
+                &state.image,
+                vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                vk::PipelineStageFlags2::COMPUTE_SHADER,
+                vk::AccessFlags2::MEMORY_READ,
+                vk::PipelineStageFlags2::ALL_COMMANDS,
+            ),
+            general_barrier(
+                &deferred.normal.image,
+                vk::AccessFlags2::SHADER_STORAGE_READ,
+                vk::PipelineStageFlags2::COMPUTE_SHADER,
+                vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+                vk::PipelineStageFlags2::ALL_COMMANDS,
+            ),
+        ]);
+
+        Ok(())
+    }
+}
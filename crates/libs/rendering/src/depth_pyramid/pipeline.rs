@@ -0,0 +1,98 @@
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::prelude::{Query, Res, ResMut, Resource};
+use bevy_log::error;
+use avalanche_hlvk::{ComputePipeline, Context, DescriptorSetLayout, PipelineLayout};
+use crate::camera::ExtractedCamera;
+use crate::deferred::DEFERRED_GRAPH;
+use crate::extract::FrameContext;
+use crate::globals;
+use crate::shader::ShaderDirectory;
+
+pub(crate) const SEED_SHADER: &str = "depth_pyramid/seed.comp";
+pub(crate) const DOWNSAMPLE_SHADER: &str = "depth_pyramid/downsample.comp";
+
+/// Matches `DepthPyramidPushConstants` in `shaders/depth_pyramid/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DepthPyramidPushConstants {
+    /// Texels of the level read
+    pub source_size: [u32; 2],
+    /// Texels of the level written
+    pub size: [u32; 2],
+}
+
+// SAFETY: plain `u32` fields without implicit padding
+unsafe impl Zeroable for DepthPyramidPushConstants {}
+unsafe impl Pod for DepthPyramidPushConstants {}
+
+pub struct DepthPyramidPipelineResources {
+    /// Reflected from both shaders, set 0 binds the level read and the level written
+    pub layout: PipelineLayout,
+    /// Copies the G-buffer distance into the first level
+    pub seed: ComputePipeline,
+    /// Reduces a level into the next one
+    pub downsample: ComputePipeline,
+}
+
+impl DepthPyramidPipelineResources {
+    #[inline]
+    pub fn descriptor_set_layout(&self) -> &DescriptorSetLayout {
+        &self.layout.descriptor_set_layouts()[0]
+    }
+}
+
+/// The depth pyramid pipelines, created the first time a camera uses the deferred graph.
+#[derive(Resource, Default)]
+pub enum DepthPyramidPipeline {
+    #[default]
+    Uninitialized,
+    Ready(Box<DepthPyramidPipelineResources>),
+    /// Creation failed, usually shaders are missing.
+    Failed,
+}
+
+impl DepthPyramidPipeline {
+    pub fn resources(&self) -> Option<&DepthPyramidPipelineResources> {
+        match self {
+            DepthPyramidPipeline::Ready(resources) => Some(resources),
+            _ => None,
+        }
+    }
+}
+
+fn create_resources(context: &Context, shaders: &ShaderDirectory) -> anyhow::Result<DepthPyramidPipelineResources> {
+    let stages = [
+        shaders.load(context, SEED_SHADER, vk::ShaderStageFlags::COMPUTE)?,
+        shaders.load(context, DOWNSAMPLE_SHADER, vk::ShaderStageFlags::COMPUTE)?,
+    ];
+    let layout = globals::pipeline_layout(&stages)?;
+    anyhow::ensure!(globals::pass_set_count(&stages) == 1, "depth pyramid shaders must only use set 0");
+    let [seed, downsample] = &stages;
+
+    Ok(DepthPyramidPipelineResources {
+        seed: context.create_compute_pipeline(&layout, seed)?,
+        downsample: context.create_compute_pipeline(&layout, downsample)?,
+        layout,
+    })
+}
+
+pub(crate) fn prepare_depth_pyramid_pipeline(
+    mut pipeline: ResMut<DepthPyramidPipeline>,
+    shaders: Res<ShaderDirectory>,
+    cameras: Query<&ExtractedCamera>,
+    frame_context: Res<FrameContext>,
+) {
+    if !matches!(*pipeline, DepthPyramidPipeline::Uninitialized)
+        || !cameras.iter().any(|camera| camera.render_graph == DEFERRED_GRAPH) {
+        return;
+    }
+
+    *pipeline = match create_resources(frame_context.render_context(), &shaders) {
+        Ok(resources) => DepthPyramidPipeline::Ready(Box::new(resources)),
+        Err(err) => {
+            error!("Failed to create depth pyramid pipeline: {err}");
+            DepthPyramidPipeline::Failed
+        }
+    };
+}
@@ -0,0 +1,228 @@
+use ash::vk;
+use anyhow::ensure;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{IntoSystemConfigs, Res, ResMut, Resource};
+use bevy_log::error;
+use gpu_allocator::MemoryLocation;
+use avalanche_asset::{Asset, AssetApp, Handle};
+use avalanche_hlvk::{ImageBarrier, Sampler};
+use crate::{Render, RenderApp, RenderSet};
+use crate::extract::{ExtractResource, ExtractResourcePlugin, FrameContext};
+use crate::prelude::{Image, ImageView};
+use crate::render_asset::{RenderAsset, RenderAssetContext, RenderAssetPlugin, RenderAssets};
+use crate::texture::Texture;
+
+/// Six square faces on the CPU in the `+X, -X, +Y, -Y, +Z, -Z` order, uploaded into a [`GpuCubemap`].
+#[derive(Clone, Debug)]
+pub struct Cubemap {
+    /// Width and height of a face
+    pub size: u32,
+    /// Only formats with 4 bytes per texel are supported
+    pub format: vk::Format,
+    pub data: Vec<u8>,
+}
+
+impl Asset for Cubemap {}
+
+impl Cubemap {
+    /// Faces stacked vertically in a texture 6 times as high as wide.
+    pub fn from_vertical_strip(texture: &Texture) -> anyhow::Result<Self> {
+        ensure!(
+            texture.height == texture.width * 6,
+            "A cube map strip must be 6 times as high as wide, got {}x{}", texture.width, texture.height,
+        );
+        Ok(Self {
+            size: texture.width,
+            format: texture.format,
+            data: texture.data.clone(),
+        })
+    }
+}
+
+/// Sampled cube image of a [`Cubemap`], in `SHADER_READ_ONLY_OPTIMAL` layout.
+pub struct GpuCubemap {
+    pub image: Image,
+    pub view: ImageView,
+    pub size: u32,
+}
+
+impl RenderAsset for Cubemap {
+    type ExtractedAsset = Cubemap;
+    type PreparedAsset = GpuCubemap;
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(cubemap: Self::ExtractedAsset, context: &mut RenderAssetContext) -> anyhow::Result<Self::PreparedAsset> {
+        ensure!(cubemap.size > 0, "Cube map is empty");
+        ensure!(
+            cubemap.data.len() == cubemap.size as usize * cubemap.size as usize * 4 * 6,
+            "Cube map data doesn't match 6 faces of {0}x{0}", cubemap.size,
+        );
+
+        let staging_buffer = context.context().create_buffer(
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            MemoryLocation::CpuToGpu,
+            cubemap.data.len() as _,
+        )?;
+        staging_buffer.copy_data_to_buffer(&cubemap.data)?;
+
+        let image = context.context().create_cube_image(
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuOnly,
+            cubemap.format,
+            cubemap.size,
+        )?;
+
+        let command_buffer = context.command_buffer();
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &image,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            src_access_mask: vk::AccessFlags2::NONE,
+            dst_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::NONE,
+            dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+        }]);
+        command_buffer.copy_buffer_to_image(&staging_buffer, &image, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &image,
+            old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_READ,
+            src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+        context.keep_until_frame_end(staging_buffer);
+
+        let view = image.create_image_view()?;
+        Ok(GpuCubemap {
+            image: image.into(),
+            view: view.into(),
+            size: cubemap.size,
+        })
+    }
+}
+
+/// Surroundings of the scene, drawn as the background by the skybox node and lighting surfaces.
+///
+/// Without a cube map the procedural sky is used.
+#[derive(Resource, ExtractResource, Clone, Debug)]
+pub struct EnvironmentMap {
+    pub cubemap: Option<Handle<Cubemap>>,
+    pub intensity: f32,
+}
+
+impl Default for EnvironmentMap {
+    fn default() -> Self {
+        Self {
+            cubemap: None,
+            intensity: 1.0,
+        }
+    }
+}
+
+/// Sampler and the black cube bound while no [`Cubemap`] of the [`EnvironmentMap`] is ready.
+pub struct EnvironmentMapResources {
+    pub sampler: Sampler,
+    _fallback_image: Image,
+    fallback_view: ImageView,
+}
+
+/// Render world bindings of the [`EnvironmentMap`], created the first time a frame is prepared.
+#[derive(Resource, Default)]
+pub struct EnvironmentMapBindings(Option<EnvironmentMapResources>);
+
+impl EnvironmentMapBindings {
+    /// The cube map to sample and whether it is the one of the [`EnvironmentMap`].
+    pub fn view<'a>(
+        &'a self,
+        environment_map: &EnvironmentMap,
+        cubemaps: &'a RenderAssets<Cubemap>,
+    ) -> Option<(&'a ImageView, bool)> {
+        let resources = self.0.as_ref()?;
+        let cubemap = environment_map
+            .cubemap
+            .as_ref()
+            .and_then(|handle| cubemaps.get(handle.id()));
+        Some(match cubemap {
+            Some(cubemap) => (&cubemap.view, true),
+            None => (&resources.fallback_view, false),
+        })
+    }
+
+    #[inline]
+    pub fn sampler(&self) -> Option<&Sampler> {
+        self.0.as_ref().map(|resources| &resources.sampler)
+    }
+}
+
+pub struct EnvironmentMapPlugin;
+
+impl Plugin for EnvironmentMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Cubemap>()
+            .init_resource::<EnvironmentMap>()
+            .add_plugins((
+                RenderAssetPlugin::<Cubemap>::default(),
+                ExtractResourcePlugin::<EnvironmentMap>::default(),
+            ));
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<EnvironmentMap>()
+                .init_resource::<EnvironmentMapBindings>()
+                .add_systems(Render, prepare_environment_map_bindings.in_set(RenderSet::PrepareResources));
+        }
+    }
+}
+
+fn create_environment_map_resources(frame_context: &FrameContext) -> anyhow::Result<EnvironmentMapResources> {
+    let context = frame_context.render_context();
+    let sampler = context.create_sampler(
+        &vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+    )?;
+    let fallback_image = context.create_cube_image(
+        vk::ImageUsageFlags::SAMPLED,
+        MemoryLocation::GpuOnly,
+        vk::Format::R8G8B8A8_UNORM,
+        1,
+    )?;
+    let fallback_view = fallback_image.create_image_view()?;
+
+    // never written, the fallback is only bound while the shaders use the procedural sky
+    if let Some(command_buffer) = frame_context.command_buffer(0) {
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &fallback_image,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            src_access_mask: vk::AccessFlags2::NONE,
+            dst_access_mask: vk::AccessFlags2::SHADER_READ,
+            src_stage_mask: vk::PipelineStageFlags2::NONE,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+    }
+
+    Ok(EnvironmentMapResources {
+        sampler,
+        _fallback_image: fallback_image.into(),
+        fallback_view: fallback_view.into(),
+    })
+}
+
+fn prepare_environment_map_bindings(mut bindings: ResMut<EnvironmentMapBindings>, frame_context: Res<FrameContext>) {
+    if bindings.0.is_some() {
+        return;
+    }
+    match create_environment_map_resources(&frame_context) {
+        Ok(resources) => bindings.0 = Some(resources),
+        Err(err) => error!("Failed to create environment map resources: {err}"),
+    }
+}
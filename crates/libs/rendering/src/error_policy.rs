@@ -0,0 +1,74 @@
+use bevy_ecs::prelude::{Event, Events, ResMut, Resource};
+use bevy_utils::HashSet;
+use crate::MainWorld;
+use crate::graph::RenderLabel;
+use crate::prelude::node::NodeId;
+
+/// What the render system does when a node of the render graph fails, lives in the render world.
+///
+/// With the skipping policies a failing node is disabled for the next frames and a [`RenderNodeFailed`] is sent
+/// to the main world, errors of the graph itself like a missing input skip the frame.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderGraphErrorPolicy {
+    #[default]
+    PanicOnError,
+    /// Skip the node and the nodes needing its outputs, the rest of the graph still runs
+    SkipNode,
+    /// Stop running the graph for the frame, the commands recorded so far are still submitted and presented
+    SkipFrame,
+}
+
+/// Nodes skipped by the graph runner after they failed, lives in the render world.
+///
+/// Replacing a node gives it a new id, which isn't disabled.
+#[derive(Resource, Default, Debug)]
+pub struct DisabledRenderNodes(HashSet<NodeId>);
+
+impl DisabledRenderNodes {
+    #[inline]
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.0.contains(&node)
+    }
+
+    pub fn disable(&mut self, node: NodeId) {
+        self.0.insert(node);
+    }
+
+    /// Run the node again from the next frame, returns false if it wasn't disabled.
+    pub fn enable(&mut self, node: NodeId) -> bool {
+        self.0.remove(&node)
+    }
+
+    pub fn enable_all(&mut self) {
+        self.0.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+/// Sent in the main world when a node failed and was disabled, see [`RenderGraphErrorPolicy`].
+#[derive(Event, Clone, Debug)]
+pub struct RenderNodeFailed {
+    pub node: NodeId,
+    pub node_name: Option<RenderLabel>,
+    /// Sub graph of the node, `None` for the main graph
+    pub graph_name: Option<RenderLabel>,
+    /// The error with its sources
+    pub error: String,
+}
+
+/// Failures of the last frames not sent to the main world yet.
+#[derive(Resource, Default)]
+pub(crate) struct RenderNodeFailures(pub(crate) Vec<RenderNodeFailed>);
+
+pub(crate) fn send_render_node_failures(mut failures: ResMut<RenderNodeFailures>, mut main_world: ResMut<MainWorld>) {
+    if failures.0.is_empty() {
+        return;
+    }
+    let failures = std::mem::take(&mut failures.0);
+    if let Some(mut events) = main_world.get_resource_mut::<Events<RenderNodeFailed>>() {
+        events.extend(failures);
+    }
+}
@@ -1,13 +1,26 @@
 mod frame;
+mod component;
+mod resource;
+mod changed;
 pub use frame::*;
+pub use component::*;
+pub use resource::*;
+pub use changed::*;
 
 use bevy_ecs::prelude::{World};
+use avalanche_window::AppLifecycle;
 use crate::MainWorld;
 use crate::prelude::RenderingContext;
 
+/// No [`FrameContext`] is inserted while the app is suspended, which pauses the render schedule.
 pub(crate) fn extract_rendering_context(render_world: &mut World) {
     let main_world = render_world.resource::<MainWorld>();
-    let rendering_context = main_world.get_resource::<RenderingContext>().unwrap();
+    if main_world.get_resource::<AppLifecycle>().is_some_and(|lifecycle| *lifecycle == AppLifecycle::Suspended) {
+        return;
+    }
+    let Some(rendering_context) = main_world.get_resource::<RenderingContext>() else {
+        return;
+    };
     let rendering_context = rendering_context.clone();
     // SAFETY: running in exclusive system
     unsafe {
@@ -18,7 +31,9 @@ pub(crate) fn extract_rendering_context(render_world: &mut World) {
 pub(crate) fn _extract_scene() {}
 
 pub(crate) fn release_referenced_rendering_context(world: &mut World) {
-    let context = world.remove_resource::<FrameContext>().unwrap();
+    let Some(context) = world.remove_resource::<FrameContext>() else {
+        return;
+    };
     let _ = context.sync_fence_ref().wait(None);
     //context.render_context.device_wait_idle().unwrap();
 }
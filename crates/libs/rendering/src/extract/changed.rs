@@ -0,0 +1,147 @@
+use std::marker::PhantomData;
+use bevy_app::{App, Plugin};
+use bevy_ecs::change_detection::DetectChanges;
+use bevy_ecs::prelude::{Entity, Query, Ref, ResMut, Resource};
+use bevy_utils::{EntityHashMap, HashSet};
+use crate::{ExtractSchedule, RenderApp};
+use crate::extract::ExtractComponent;
+use crate::prelude::Extract;
+
+/// Components extracted by [`ExtractChangedComponentPlugin`], kept in the render world across frames.
+///
+/// Unlike [`ExtractComponentPlugin`](super::ExtractComponentPlugin) the values are not inserted on
+/// the render entities, which are cleared every frame, but stored here so unchanged ones don't have to be copied again.
+#[derive(Resource)]
+pub struct ExtractedComponents<C: ExtractComponent> {
+    items: EntityHashMap<Entity, C::Out>,
+    changed: Vec<Entity>,
+    removed: Vec<Entity>,
+}
+
+impl<C: ExtractComponent> Default for ExtractedComponents<C> {
+    fn default() -> Self {
+        Self {
+            items: Default::default(),
+            changed: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+
+impl<C: ExtractComponent> ExtractedComponents<C> {
+    #[inline]
+    pub fn get(&self, entity: Entity) -> Option<&C::Out> {
+        self.items.get(&entity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &C::Out)> {
+        self.items.iter().map(|(entity, item)| (*entity, item))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Entities added or changed by the last extraction.
+    #[inline]
+    pub fn changed(&self) -> &[Entity] {
+        &self.changed
+    }
+
+    /// Entities which lost the component since the last extraction.
+    #[inline]
+    pub fn removed(&self) -> &[Entity] {
+        &self.removed
+    }
+
+    /// Whether anything changed in the last extraction,
+    /// prepare systems could skip updating their GPU data otherwise.
+    #[inline]
+    pub fn has_changes(&self) -> bool {
+        !self.changed.is_empty() || !self.removed.is_empty()
+    }
+}
+
+/// Extracts `C` into [`ExtractedComponents<C>`] only when it changed since the previous extraction.
+///
+/// Only changes to `C` itself are detected, an [`ExtractComponent::Query`]
+/// reading other components won't be re-extracted when those change.
+pub struct ExtractChangedComponentPlugin<C> {
+    marker: PhantomData<fn() -> C>,
+}
+
+impl<C> Default for ExtractChangedComponentPlugin<C> {
+    fn default() -> Self {
+        Self {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<C: ExtractComponent> Plugin for ExtractChangedComponentPlugin<C>
+    where
+        C::Out: Send + Sync,
+{
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ExtractedComponents<C>>()
+                .add_systems(ExtractSchedule, extract_changed_components::<C>);
+        }
+    }
+}
+
+type ExtractChangedComponentQuery<'w, 's, C> = Query<
+    'w,
+    's,
+    (Entity, Ref<'static, C>, <C as ExtractComponent>::Query),
+    <C as ExtractComponent>::Filter,
+>;
+
+fn extract_changed_components<C: ExtractComponent>(
+    mut extracted: ResMut<ExtractedComponents<C>>,
+    query: Extract<ExtractChangedComponentQuery<C>>,
+) where
+    C::Out: Send + Sync,
+{
+    let extracted = extracted.as_mut();
+    extracted.changed.clear();
+    extracted.removed.clear();
+
+    let mut alive = HashSet::with_capacity(extracted.items.len());
+    for (entity, component, query_item) in &query {
+        alive.insert(entity);
+        if !component.is_changed() && extracted.items.contains_key(&entity) {
+            continue;
+        }
+
+        match C::extract_component(query_item) {
+            Some(item) => {
+                extracted.items.insert(entity, item);
+                extracted.changed.push(entity);
+            }
+            None => {
+                if extracted.items.remove(&entity).is_some() {
+                    extracted.removed.push(entity);
+                }
+            }
+        }
+    }
+
+    if alive.len() != extracted.items.len() {
+        let removed = &mut extracted.removed;
+        extracted.items.retain(|entity, _| {
+            let keep = alive.contains(entity);
+            if !keep {
+                removed.push(*entity);
+            }
+            keep
+        });
+    }
+}
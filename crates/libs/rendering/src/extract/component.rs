@@ -0,0 +1,74 @@
+use std::marker::PhantomData;
+use bevy_app::{App, Plugin};
+use bevy_ecs::bundle::Bundle;
+use bevy_ecs::component::Component;
+use bevy_ecs::prelude::{Commands, Entity, Local, Query};
+use bevy_ecs::query::{QueryItem, ReadOnlyWorldQuery, WorldQuery};
+pub use avalanche_rendering_macros::ExtractComponent;
+use crate::{ExtractSchedule, RenderApp};
+use crate::prelude::Extract;
+
+/// Describes how a component gets extracted for rendering.
+///
+/// Therefore the component is transferred from the "main world" into the "render world"
+/// in the [`ExtractSchedule`] step.
+pub trait ExtractComponent: Component {
+    /// ECS [`WorldQuery`] to fetch the components to extract.
+    type Query: WorldQuery + ReadOnlyWorldQuery;
+    /// Filters the entities with additional constraints.
+    type Filter: WorldQuery + ReadOnlyWorldQuery;
+    /// The output from extraction.
+    ///
+    /// Returning `None` based on the queried item will remove the component from the entity in
+    /// the render world.
+    type Out: Bundle;
+
+    /// Defines how the component is transferred into the "render world".
+    fn extract_component(item: QueryItem<'_, Self::Query>) -> Option<Self::Out>;
+}
+
+/// This plugin extracts the components into the "render world".
+///
+/// Therefore it sets up the [`ExtractSchedule`] step for the specified [`ExtractComponent`].
+pub struct ExtractComponentPlugin<C> {
+    marker: PhantomData<fn() -> C>,
+}
+
+impl<C> Default for ExtractComponentPlugin<C> {
+    fn default() -> Self {
+        Self {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<C: ExtractComponent> Plugin for ExtractComponentPlugin<C> {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.add_systems(ExtractSchedule, extract_components::<C>);
+        }
+    }
+}
+
+type ExtractComponentQuery<'w, 's, C> = Query<
+    'w,
+    's,
+    (Entity, <C as ExtractComponent>::Query),
+    <C as ExtractComponent>::Filter,
+>;
+
+/// This system extracts all components of the corresponding [`ExtractComponent`] type.
+fn extract_components<C: ExtractComponent>(
+    mut commands: Commands,
+    mut previous_len: Local<usize>,
+    query: Extract<ExtractComponentQuery<C>>,
+) {
+    let mut values = Vec::with_capacity(*previous_len);
+    for (entity, query_item) in &query {
+        if let Some(component) = C::extract_component(query_item) {
+            values.push((entity, component));
+        }
+    }
+    *previous_len = values.len();
+    commands.insert_or_spawn_batch(values);
+}
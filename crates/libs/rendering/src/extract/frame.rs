@@ -1,18 +1,124 @@
-use std::sync::Arc;
-use anyhow::Context;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use anyhow::{bail, Context};
 use ash::vk;
 use bevy_ecs::prelude::Resource;
 use bevy_log::error;
-use avalanche_hlvk::{CommandBuffer, CommandPool, Device, Fence, Queue, Semaphore};
+use avalanche_hlvk::{CommandBuffer, CommandPool, Device, Fence, Queue, Semaphore, SemaphoreSubmitInfo};
+use crate::command_pool::{CommandPoolManager, PooledCommandBuffer};
 use crate::context::RenderingContext;
-use crate::INIT_COMMAND_POOL_NUM;
+use crate::prelude::node::QueueType;
+
+/// Command buffers a queue may be split into during a frame, one more each time it waits for the other queue.
+const MAX_QUEUE_SEGMENTS: usize = 16;
+
+/// Command buffers of a queue for a frame, recorded one after the other.
+///
+/// A segment ends when the other queue waits for it, or before the queue waits for the other one.
+/// Segments are only added while the frame is recorded, so references to their command buffers stay valid.
+/// The command buffers go back to their pool with the frame context, once the frame completed.
+struct QueueSegments {
+    command_buffers: [OnceLock<PooledCommandBuffer>; MAX_QUEUE_SEGMENTS],
+    /// Signaled when a segment completes, only for the segments the other queue waits for
+    semaphores: [OnceLock<Semaphore>; MAX_QUEUE_SEGMENTS],
+    state: Mutex<QueueSegmentsState>,
+}
+
+#[derive(Default)]
+struct QueueSegmentsState {
+    /// Segments begun so far, the last one is recorded while `open`
+    count: usize,
+    open: bool,
+    /// Segment of the other queue each segment waits for before running
+    waits: [Option<usize>; MAX_QUEUE_SEGMENTS],
+}
+
+impl QueueSegments {
+    fn new() -> Self {
+        Self {
+            command_buffers: std::array::from_fn(|_| OnceLock::new()),
+            semaphores: std::array::from_fn(|_| OnceLock::new()),
+            state: Default::default(),
+        }
+    }
+
+    fn wait(&self, index: usize) -> Option<usize> {
+        self.state.lock().unwrap().waits[index]
+    }
+
+    fn current(&self) -> Option<&CommandBuffer> {
+        let state = self.state.lock().unwrap();
+        state.open.then(|| self.command_buffers[state.count - 1].get().map(Deref::deref)).flatten()
+    }
+
+    /// Latest segment of the other queue waited by a segment of this one.
+    fn waited(&self) -> Option<usize> {
+        self.state.lock().unwrap().waits.iter().flatten().copied().max()
+    }
+
+    /// Index of the segment recorded now, or of the last one recorded.
+    fn last(&self) -> Option<usize> {
+        self.state.lock().unwrap().count.checked_sub(1)
+    }
+
+    fn begin(&self, pools: &CommandPoolManager, frame: usize, wait: Option<usize>) -> anyhow::Result<&CommandBuffer> {
+        let mut state = self.state.lock().unwrap();
+        if state.count == MAX_QUEUE_SEGMENTS {
+            bail!("the frame crossed queues more than {MAX_QUEUE_SEGMENTS} times");
+        }
+        let command_buffer = pools.allocate_command_buffer(frame, vk::CommandBufferLevel::PRIMARY)?;
+        command_buffer.begin(None)?;
+        let index = state.count;
+        state.waits[index] = wait;
+        state.count += 1;
+        state.open = true;
+        let _ = self.command_buffers[index].set(command_buffer);
+        self.command_buffers[index].get().map(Deref::deref).context("Unexpected error.")
+    }
+
+    fn create_semaphore(&self, index: usize, device: Arc<Device>) -> anyhow::Result<()> {
+        if self.semaphores[index].get().is_none() {
+            let _ = self.semaphores[index].set(Semaphore::new(device)?);
+        }
+        Ok(())
+    }
+
+    /// End the segment recorded now, returns its index.
+    fn end(&self) -> anyhow::Result<Option<usize>> {
+        let mut state = self.state.lock().unwrap();
+        if !state.open {
+            return Ok(None);
+        }
+        state.open = false;
+        let index = state.count - 1;
+        self.command_buffers[index].get().context("Unexpected error.")?.end()?;
+        Ok(Some(index))
+    }
+}
+
+fn other_queue(queue: QueueType) -> QueueType {
+    match queue {
+        QueueType::Graphics => QueueType::AsyncCompute,
+        QueueType::AsyncCompute => QueueType::Graphics,
+    }
+}
 
 #[derive(Resource)]
 pub struct FrameContext {
     render_context: RenderingContext,
     /// Cyclic frame counter
     current_frame: usize,
-    command_buffers: Vec<CommandBuffer>,
+    graphics: QueueSegments,
+    /// Segments of the compute queue, `None` without a compute only queue family
+    compute: Option<QueueSegments>,
+    /// Segments of both queues in the order they ended, which is the order they are submitted in
+    ended: Mutex<Vec<(QueueType, usize)>>,
+    /// Semaphores the first graphics segment waits for, e.g. of the acquired swapchain images,
+    /// kept alive with the frame
+    waits: Mutex<Vec<(Arc<Semaphore>, vk::PipelineStageFlags2)>>,
+    /// Waits already submitted, the first ones
+    submitted_waits: AtomicUsize,
     frame_finish_semaphore: Arc<Semaphore>,
     sync_fence: Arc<Fence>,
     /// in-frame semaphore container
@@ -25,49 +131,56 @@ impl FrameContext {
     ///
     /// **SAFETY of any Operation ISN'T PERFORMED in Main Thread is NOT GUARANTEED!**
     pub(crate) unsafe fn new(render_context: RenderingContext) -> Self {
-        static mut COUNTER: usize = 0;
-        let current_frame = COUNTER.wrapping_add(1);
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let current_frame = COUNTER.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+        // the previous frame using the pools waited for its fence when released
+        let pools = std::iter::once(&render_context.command_pools).chain(render_context.compute_command_pools.as_ref());
+        for pools in pools {
+            if let Err(err) = pools.reset_pools(current_frame) {
+                error!("Failed to reset the command pools of frame {current_frame}: {err}");
+            }
+        }
         let frame_finish_semaphore = Arc::new(Semaphore::new(render_context.context.device.clone()).unwrap());
         let sync_fence = Arc::new(Fence::new(render_context.context.device.clone(), None).unwrap());
         // TODO: try to use Timeline Semaphore introduced in vk 1.2?
         // let sync_fence = Arc::new(Fence::null());
-        let mut frame_context = FrameContext {
+        let compute = render_context.compute_command_pools.as_ref().map(|_| QueueSegments::new());
+        let frame_context = FrameContext {
             render_context,
             current_frame,
-            command_buffers: Vec::new(),
+            graphics: QueueSegments::new(),
+            compute,
+            ended: Mutex::new(Vec::new()),
+            waits: Mutex::new(Vec::new()),
+            submitted_waits: AtomicUsize::new(0),
             frame_finish_semaphore,
             sync_fence,
             semaphores: Vec::new(),
         };
 
-        match frame_context.allocate_command_buffer(None) {
-            Err(err) => {
-                error!("Failed to allocate default command when creating new [`FrameContext`]: {err}");
-            },
-            Ok(buffer) => {
-                let _ = buffer.begin(None);
-            }
+        if let Err(err) = frame_context.graphics.begin(&frame_context.render_context.command_pools, current_frame, None) {
+            error!("Failed to allocate default command when creating new [`FrameContext`]: {err}");
         }
 
         frame_context
     }
 
-    pub fn active_command_pool(&self) -> Arc<CommandPool> {
-        let index = self.current_frame % INIT_COMMAND_POOL_NUM;
-        self.render_context.command_pools.get(index).unwrap().clone()
+    /// The command pool of the calling thread for the frame.
+    pub fn active_command_pool(&self) -> anyhow::Result<Arc<CommandPool>> {
+        self.render_context.command_pools.current_thread_pool(self.current_frame)
     }
 
-    pub fn active_command_pool_ref(&self) -> &CommandPool {
-        let index = self.current_frame % INIT_COMMAND_POOL_NUM;
-        self.render_context.command_pools.get(index).unwrap()
+    /// Allocate a command buffer from the pool of the calling thread, for recording in parallel.
+    ///
+    /// It isn't submitted with the frame, e.g. record it as a secondary buffer and execute it from a frame buffer.
+    /// Keep it until the frame is submitted, it goes back to the pool when dropped.
+    pub fn allocate_thread_command_buffer(&self, level: vk::CommandBufferLevel) -> anyhow::Result<PooledCommandBuffer> {
+        self.render_context.command_pools.allocate_command_buffer(self.current_frame, level)
     }
 
-    /// [`CommandBuffer`] doesn't use RAII,
-    /// caller has duty to trace and free them.
-    pub fn allocate_command_buffer(&mut self, level: Option<vk::CommandBufferLevel>) -> anyhow::Result<&CommandBuffer> {
-        let command_buffer = self.active_command_pool_ref().allocate_command_buffer(level.unwrap_or(vk::CommandBufferLevel::PRIMARY))?;
-        self.command_buffers.push(command_buffer);
-        self.command_buffers.last().context("Unexpected error.")
+    #[inline]
+    pub fn current_frame(&self) -> usize {
+        self.current_frame
     }
 
     #[inline]
@@ -80,9 +193,168 @@ impl FrameContext {
         self.render_context.graphics_queue.clone()
     }
 
+    /// Whether [`QueueType::AsyncCompute`] work is submitted to a dedicated compute queue.
+    #[inline]
+    pub fn has_async_compute(&self) -> bool {
+        self.compute.is_some()
+    }
+
+    /// The queue `queue` work is submitted to, async compute falls back to graphics without a compute queue.
+    #[inline]
+    pub fn resolve_queue(&self, queue: QueueType) -> QueueType {
+        if self.has_async_compute() { queue } else { QueueType::Graphics }
+    }
+
+    /// The command buffer of the graphics queue recorded now, at index 0.
+    ///
+    /// It changes when the graphics queue waits for async compute work, don't keep it across nodes.
+    #[inline]
+    pub fn command_buffer(&self, index: usize) -> Option<&CommandBuffer> {
+        (index == 0).then(|| self.graphics.current()).flatten()
+    }
+
+    /// The command buffer of the `queue` recorded now, the graphics one without async compute.
+    pub fn queue_command_buffer(&self, queue: QueueType) -> Option<&CommandBuffer> {
+        let (Some(compute), QueueType::AsyncCompute) = (&self.compute, queue) else {
+            return self.command_buffer(0);
+        };
+        if let Some(command_buffer) = compute.current() {
+            return Some(command_buffer);
+        }
+        match self.begin_segment(QueueType::AsyncCompute, None) {
+            Ok(command_buffer) => Some(command_buffer),
+            Err(err) => {
+                error!("Failed to begin a compute command buffer: {err}");
+                None
+            }
+        }
+    }
+
+    /// A debug label around the commands of the `queue` recorded until the returned scope is dropped.
+    ///
+    /// Unlike [`CommandBuffer::scoped_label`], the label may span segments of the queue.
+    pub fn scoped_label(&self, queue: QueueType, name: &str, color: [f32; 4]) -> Option<FrameLabelScope<'_>> {
+        let queue = self.resolve_queue(queue);
+        self.queue_command_buffer(queue)?.begin_label(name, color);
+        Some(FrameLabelScope { frame_context: self, queue })
+    }
+
+    /// Index of the segment of the `queue` recorded now, begun if there is none.
+    pub(crate) fn queue_segment(&self, queue: QueueType) -> anyhow::Result<usize> {
+        let segments = self.segments(queue);
+        if segments.current().is_none() {
+            self.begin_segment(queue, None)?;
+        }
+        segments.last().context("Unexpected error.")
+    }
+
+    /// Make the work of `queue` recorded from now on wait for `segment` of the other queue to complete.
+    pub(crate) fn wait_for_queue(&self, queue: QueueType, segment: usize) -> anyhow::Result<()> {
+        let other_queue = other_queue(queue);
+        // the commands of the other queue recorded from now on can't be part of the segment waited
+        if self.segments(other_queue).last() == Some(segment) {
+            self.end_segment(other_queue)?;
+        }
+        if self.segments(queue).waited() >= Some(segment) {
+            return Ok(());
+        }
+        self.segments(other_queue).create_semaphore(segment, self.device())?;
+        self.end_segment(queue)?;
+        self.begin_segment(queue, Some(segment))?;
+        Ok(())
+    }
+
+    fn segments(&self, queue: QueueType) -> &QueueSegments {
+        match (queue, &self.compute) {
+            (QueueType::AsyncCompute, Some(compute)) => compute,
+            _ => &self.graphics,
+        }
+    }
+
+    fn begin_segment(&self, queue: QueueType, wait: Option<usize>) -> anyhow::Result<&CommandBuffer> {
+        let pools = match queue {
+            QueueType::AsyncCompute => self.render_context.compute_command_pools.as_ref().context("No async compute queue")?,
+            QueueType::Graphics => &self.render_context.command_pools,
+        };
+        self.segments(queue).begin(pools, self.current_frame, wait)
+    }
+
+    fn end_segment(&self, queue: QueueType) -> anyhow::Result<()> {
+        if let Some(index) = self.segments(queue).end()? {
+            self.ended.lock().unwrap().push((queue, index));
+        }
+        Ok(())
+    }
+
+    /// Make the graphics work of the frame wait for `semaphore` at `stage_mask`, e.g. until a swapchain image is acquired.
+    ///
+    /// Only the work before `stage_mask` may start, the semaphore is waited once by the first graphics submission.
+    pub fn wait_semaphore(&self, semaphore: Arc<Semaphore>, stage_mask: vk::PipelineStageFlags2) {
+        self.waits.lock().unwrap().push((semaphore, stage_mask));
+    }
+
+    /// The semaphores given to [`FrameContext::wait_semaphore`] which weren't waited by a submission,
+    /// their waits must be submitted by the caller as they are signaled already or about to be.
+    pub(crate) fn take_pending_waits(&self) -> Vec<(Arc<Semaphore>, vk::PipelineStageFlags2)> {
+        let waits = self.waits.lock().unwrap();
+        let first = self.submitted_waits.swap(waits.len(), Ordering::Relaxed);
+        waits[first..].to_vec()
+    }
+
+    /// End the command buffers still recorded and submit the segments of both queues in the order they ended,
+    /// with a semaphore between the segments of different queues waiting for each other.
+    ///
+    /// The first graphics segment waits for the semaphores of [`FrameContext::wait_semaphore`].
+    /// The last graphics segment waits for the compute work, it signals the frame finish semaphore and the fence.
     pub fn submit(&self, queue: &Queue) -> anyhow::Result<()> {
-        let signal_semaphore = self.frame_finish_semaphore.as_ref();
-        queue.submit(&self.command_buffers, &[], std::slice::from_ref(signal_semaphore), self.sync_fence.as_ref())
+        if let Some(compute) = &self.compute {
+            self.end_segment(QueueType::AsyncCompute)?;
+            if let Some(last) = compute.last() {
+                self.wait_for_queue(QueueType::Graphics, last)?;
+            }
+        }
+        self.end_segment(QueueType::Graphics)?;
+
+        let ended = std::mem::take(&mut *self.ended.lock().unwrap());
+        let mut waits = Some(self.take_pending_waits());
+        for (position, &(queue_type, index)) in ended.iter().enumerate() {
+            let segments = self.segments(queue_type);
+            let command_buffer: &CommandBuffer = segments.command_buffers[index].get().context("Unexpected error.")?;
+            let external_waits = match queue_type {
+                QueueType::Graphics => waits.take().unwrap_or_default(),
+                QueueType::AsyncCompute => Vec::new(),
+            };
+            let wait_semaphores = segments
+                .wait(index)
+                .and_then(|wait| self.segments(other_queue(queue_type)).semaphores[wait].get())
+                .map(|semaphore| SemaphoreSubmitInfo {
+                    semaphore,
+                    stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                })
+                .into_iter()
+                .chain(external_waits.iter().map(|(semaphore, stage_mask)| SemaphoreSubmitInfo {
+                    semaphore: semaphore.as_ref(),
+                    stage_mask: *stage_mask,
+                }))
+                .collect::<Vec<_>>();
+            let last = position == ended.len() - 1;
+            let signal_semaphores = segments.semaphores[index]
+                .get()
+                .into_iter()
+                .chain(last.then_some(self.frame_finish_semaphore.as_ref()))
+                .map(|semaphore| SemaphoreSubmitInfo {
+                    semaphore,
+                    stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                })
+                .collect::<Vec<_>>();
+            let queue = match queue_type {
+                QueueType::Graphics => queue,
+                QueueType::AsyncCompute => &self.render_context.compute_queue,
+            };
+            let fence = last.then_some(self.sync_fence.as_ref());
+            queue.submit_semaphores(&[command_buffer], &wait_semaphores, &signal_semaphores, fence)?;
+        }
+        Ok(())
     }
 
     pub fn frame_finish_semaphore(&self) -> Arc<Semaphore> {
@@ -94,11 +366,6 @@ impl FrameContext {
         self.render_context.device.clone()
     }
 
-    #[inline]
-    pub fn command_buffer(&self, index: usize) -> Option<&CommandBuffer> {
-        self.command_buffers.get(index)
-    }
-
     #[inline]
     pub fn sync_fence(&self) -> Arc<Fence> {
         self.sync_fence.clone()
@@ -127,14 +394,16 @@ impl FrameContext {
     }
 }
 
-impl Drop for FrameContext {
+/// Closes a label of [`FrameContext::scoped_label`] in the segment of its queue recorded when dropped.
+pub struct FrameLabelScope<'a> {
+    frame_context: &'a FrameContext,
+    queue: QueueType,
+}
+
+impl Drop for FrameLabelScope<'_> {
     fn drop(&mut self) {
-        let command_buffer = self.command_buffers
-            .iter()
-            .map(|buffer| buffer.inner)
-            .collect::<Vec<_>>();
-        unsafe {
-            self.device().inner.free_command_buffers(self.active_command_pool_ref().inner, command_buffer.as_slice());
+        if let Some(command_buffer) = self.frame_context.queue_command_buffer(self.queue) {
+            command_buffer.end_label();
         }
     }
 }
@@ -0,0 +1,288 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use bevy_app::{App, Last, Plugin};
+use bevy_ecs::prelude::{IntoSystemConfigs, Local, Res, ResMut, Resource};
+use bevy_log::warn;
+use bevy_time::{Real, Time};
+use avalanche_hlvk::{Fence, Swapchain};
+use avalanche_window::NextFrameDeadline;
+use crate::{Render, RenderApp, RenderSet};
+use crate::extract::{ExtractResource, ExtractResourcePlugin, FrameContext};
+use crate::runner::system::render_system;
+
+/// How [`FramePacer`] waits for the next frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FramePacingStrategy {
+    /// Sleep the main thread, waiting for window events meanwhile. Cheap but only as precise as the OS scheduler.
+    #[default]
+    Sleep,
+    /// Sleep most of the remaining time and busy wait the last [`FramePacer::spin_threshold`].
+    Spin,
+    /// Hold the frame back on the render thread before it is submitted and presented,
+    /// the main thread is throttled by waiting on the render app.
+    PresentThrottled,
+}
+
+/// Caps the frame rate of the app.
+///
+/// Frame times are measured against [`Time<Real>`],
+/// which follows the instants sent by the render app at the end of every frame.
+#[derive(Resource, ExtractResource, Clone, Debug)]
+pub struct FramePacer {
+    /// `None` runs uncapped.
+    pub target_frame_rate: Option<f64>,
+    pub strategy: FramePacingStrategy,
+    /// Remaining time busy waited by [`FramePacingStrategy::Spin`].
+    pub spin_threshold: Duration,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self {
+            target_frame_rate: None,
+            strategy: FramePacingStrategy::default(),
+            spin_threshold: Duration::from_millis(2),
+        }
+    }
+}
+
+impl FramePacer {
+    pub fn with_target_frame_rate(frame_rate: f64, strategy: FramePacingStrategy) -> Self {
+        Self {
+            target_frame_rate: Some(frame_rate),
+            strategy,
+            ..Default::default()
+        }
+    }
+
+    /// Minimum duration of a frame, `None` when uncapped.
+    pub fn frame_duration(&self) -> Option<Duration> {
+        self.target_frame_rate
+            .filter(|frame_rate| frame_rate.is_finite() && *frame_rate > 0.0)
+            .map(|frame_rate| Duration::from_secs_f64(1.0 / frame_rate))
+    }
+
+    fn wait_until(&self, deadline: Instant) {
+        let spin_threshold = match self.strategy {
+            FramePacingStrategy::Spin => self.spin_threshold,
+            _ => Duration::ZERO,
+        };
+
+        let now = Instant::now();
+        if deadline > now + spin_threshold {
+            std::thread::sleep(deadline - now - spin_threshold);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+/// Limits how many images may be queued for presentation ahead of the display, lower values reduce input latency.
+///
+/// Each frame waits for its own fence once submitted, so at most one frame is rendered ahead.
+/// Presents are only tracked with fences when the device enabled
+/// [`swapchain_maintenance1`](avalanche_hlvk::DeviceFeatures::swapchain_maintenance1), otherwise the
+/// presentation engine queues as many images as the swapchain holds.
+#[derive(Resource, ExtractResource, Clone, Debug)]
+pub struct FrameLatency {
+    /// Presents not completed before acquiring another image waits for the oldest one, at least 1.
+    pub max_queued_presents: usize,
+    /// Longest wait for a present, the present is forgotten afterwards.
+    pub present_timeout: Duration,
+    /// Wait for the presents of the primary window to be displayed rather than completed, which starts the
+    /// frames right after a refresh of the display and measures the display timing in the [`RenderStatistics`].
+    ///
+    /// Requires the [`present_id`](avalanche_hlvk::DeviceFeatures::present_id) and
+    /// [`present_wait`](avalanche_hlvk::DeviceFeatures::present_wait) device features, ignored otherwise.
+    ///
+    /// [`RenderStatistics`]: crate::statistics::RenderStatistics
+    pub wait_for_display: bool,
+}
+
+impl Default for FrameLatency {
+    fn default() -> Self {
+        Self {
+            max_queued_presents: 2,
+            present_timeout: Duration::from_millis(100),
+            wait_for_display: false,
+        }
+    }
+}
+
+/// Fences of the presents not known to be completed, oldest first, lives in the render world.
+#[derive(Resource, Default)]
+pub(crate) struct QueuedPresents(Mutex<VecDeque<Arc<Fence>>>);
+
+impl QueuedPresents {
+    /// Wait until another image may be acquired, returns the fence to give to its present if fences are supported.
+    pub(crate) fn wait_for_present(&self, latency: Option<&FrameLatency>, frame_context: &FrameContext) -> Option<Arc<Fence>> {
+        if !frame_context.render_context().device_features.swapchain_maintenance1 {
+            return None;
+        }
+        let default_latency = FrameLatency::default();
+        let latency = latency.unwrap_or(&default_latency);
+
+        let mut presents = self.0.lock().unwrap();
+        while presents.len() >= latency.max_queued_presents.max(1) {
+            let fence = presents.pop_front().unwrap();
+            #[cfg(feature = "trace")]
+            let _span = bevy_utils::tracing::info_span!("wait queued present").entered();
+
+            if let Err(err) = fence.wait(Some(latency.present_timeout)) {
+                warn!("Stopped waiting for a queued present: {err}");
+            }
+        }
+        match Fence::new(frame_context.device(), None) {
+            Ok(fence) => Some(Arc::new(fence)),
+            Err(err) => {
+                warn!("Failed to create a present fence: {err}");
+                None
+            }
+        }
+    }
+
+    /// Track the fence of a present which was queued.
+    pub(crate) fn push(&self, fence: Arc<Fence>) {
+        self.0.lock().unwrap().push_back(fence);
+    }
+}
+
+/// Presents of the primary window queued with an id, and the display timing measured by waiting for them.
+#[derive(Default)]
+struct PresentTimingState {
+    queued: VecDeque<(u64, Instant)>,
+    last_displayed: Option<(u64, Instant)>,
+    present_margin: Option<Duration>,
+    display_interval: Option<Duration>,
+}
+
+/// Display timing of the primary window measured with present waits, lives in the render world.
+#[derive(Resource, Default)]
+pub(crate) struct PresentTiming(Mutex<PresentTimingState>);
+
+impl PresentTiming {
+    /// Wait for the queued presents of `swapchain` to be displayed until another one may be queued,
+    /// returns the id to give to the next present. `None` when the presents aren't waited for display.
+    pub(crate) fn wait_for_display(&self, latency: Option<&FrameLatency>, swapchain: &Swapchain) -> Option<u64> {
+        let latency = latency.filter(|latency| latency.wait_for_display)?;
+        if !swapchain.supports_present_wait() {
+            return None;
+        }
+
+        let mut state = self.0.lock().unwrap();
+        while state.queued.len() >= latency.max_queued_presents.max(1) {
+            let (present_id, queued_at) = state.queued.pop_front().unwrap();
+            #[cfg(feature = "trace")]
+            let _span = bevy_utils::tracing::info_span!("wait for display").entered();
+
+            match swapchain.wait_for_present(present_id, latency.present_timeout) {
+                Ok(true) => state.displayed(present_id, queued_at, Instant::now()),
+                Ok(false) => warn!("Stopped waiting for present {present_id} to be displayed"),
+                Err(err) => warn!("Failed to wait for present {present_id} to be displayed: {err}"),
+            }
+        }
+        Some(swapchain.next_present_id())
+    }
+
+    /// Track a present queued with an id given by [`PresentTiming::wait_for_display`].
+    pub(crate) fn push(&self, present_id: u64) {
+        self.0.lock().unwrap().queued.push_back((present_id, Instant::now()));
+    }
+
+    /// Estimated present margin and display interval, see [`RenderStatistics`](crate::statistics::RenderStatistics).
+    pub(crate) fn estimates(&self) -> (Option<Duration>, Option<Duration>) {
+        let state = self.0.lock().unwrap();
+        (state.present_margin, state.display_interval)
+    }
+}
+
+impl PresentTimingState {
+    fn displayed(&mut self, present_id: u64, queued_at: Instant, displayed_at: Instant) {
+        self.present_margin = Some(smooth(self.present_margin, displayed_at - queued_at));
+        if let Some((last_id, last_displayed_at)) = self.last_displayed {
+            // presents given up on after a timeout are in between
+            let presents = present_id.saturating_sub(last_id).max(1) as u32;
+            let interval = displayed_at.saturating_duration_since(last_displayed_at) / presents;
+            self.display_interval = Some(smooth(self.display_interval, interval));
+        }
+        self.last_displayed = Some((present_id, displayed_at));
+    }
+}
+
+/// Exponential moving average, smoothing out the jitter of the waits.
+fn smooth(average: Option<Duration>, sample: Duration) -> Duration {
+    match average {
+        Some(average) => average.mul_f64(0.9) + sample.mul_f64(0.1),
+        None => sample,
+    }
+}
+
+pub struct FramePacingPlugin;
+
+impl Plugin for FramePacingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FramePacer>()
+            .init_resource::<FrameLatency>()
+            .add_plugins((
+                ExtractResourcePlugin::<FramePacer>::default(),
+                ExtractResourcePlugin::<FrameLatency>::default(),
+            ))
+            .add_systems(Last, pace_frame);
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<QueuedPresents>()
+                .init_resource::<PresentTiming>()
+                .add_systems(
+                    Render,
+                    throttle_present
+                        .before(render_system)
+                        .in_set(RenderSet::Render),
+                );
+        }
+    }
+}
+
+/// With [`FramePacingStrategy::Sleep`] the wait is handed to the window event loop,
+/// which keeps handling events until the deadline.
+fn pace_frame(pacer: Res<FramePacer>, time: Res<Time<Real>>, next_frame_deadline: Option<ResMut<NextFrameDeadline>>) {
+    let deadline = match (pacer.frame_duration(), time.last_update()) {
+        (Some(frame_duration), Some(last_update)) if pacer.strategy != FramePacingStrategy::PresentThrottled => {
+            Some(last_update + frame_duration)
+        }
+        _ => None,
+    };
+
+    if let Some(mut next_frame_deadline) = next_frame_deadline {
+        if pacer.strategy == FramePacingStrategy::Sleep {
+            next_frame_deadline.0 = deadline;
+            return;
+        }
+        next_frame_deadline.0 = None;
+    }
+    let Some(deadline) = deadline else {
+        return;
+    };
+
+    #[cfg(feature = "trace")]
+    let _span = bevy_utils::tracing::info_span!("frame pacing").entered();
+
+    pacer.wait_until(deadline);
+}
+
+fn throttle_present(pacer: Option<Res<FramePacer>>, mut last_present: Local<Option<Instant>>) {
+    let Some(pacer) = pacer else {
+        return;
+    };
+    if pacer.strategy == FramePacingStrategy::PresentThrottled {
+        if let (Some(frame_duration), Some(last_present)) = (pacer.frame_duration(), *last_present) {
+            #[cfg(feature = "trace")]
+            let _span = bevy_utils::tracing::info_span!("present throttling").entered();
+
+            pacer.wait_until(last_present + frame_duration);
+        }
+    }
+    *last_present = Some(Instant::now());
+}
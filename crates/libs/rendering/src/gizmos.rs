@@ -0,0 +1,131 @@
+mod node;
+mod pipeline;
+
+pub use node::*;
+pub use pipeline::*;
+
+use ash::vk;
+use bevy_app::{App, First, Plugin};
+use bevy_ecs::prelude::{IntoSystemConfigs, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_math::Vec3;
+use bytemuck::{Pod, Zeroable};
+use crate::color::Color;
+use crate::{ExtractSchedule, Render, RenderApp, RenderSet};
+use crate::debug_view::DEBUG_VIEW_NODE;
+use crate::deferred::DEFERRED_GRAPH;
+use crate::extract::FrameContext;
+use crate::graph::RenderGraphApp;
+use crate::graph::node::ViewNodeRunner;
+use crate::particles::PARTICLE_NODE;
+use crate::path_tracing::{PATH_TRACING_GRAPH, PATH_TRACING_NODE};
+use crate::prelude::Extract;
+use crate::resource::TypedBuffer;
+use crate::upscaling::UPSCALE_NODE;
+
+/// Node drawing the [`Gizmos`] over the view target, before it is upscaled.
+pub const GIZMO_NODE: &str = "gizmo_pass";
+
+/// A segment drawn for a single frame, in world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GizmoLine {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub color: Color,
+}
+
+/// Lines drawn over the cameras of the path tracing and deferred graphs, cleared at the start of every frame.
+///
+/// Lines are not depth tested, they stay visible behind the geometry.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct Gizmos {
+    pub lines: Vec<GizmoLine>,
+}
+
+impl Gizmos {
+    #[inline]
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: Color) {
+        self.lines.push(GizmoLine { start, end, color });
+    }
+}
+
+/// Matches the vertex inputs of `shaders/gizmos/line.vert`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GizmoVertex {
+    /// World space position in xyz, w is 1
+    pub position: [f32; 4],
+    pub color: [f32; 4],
+}
+
+// SAFETY: plain `f32` fields without implicit padding
+unsafe impl Zeroable for GizmoVertex {}
+unsafe impl Pod for GizmoVertex {}
+
+/// Vertices of the [`Gizmos`] extracted this frame, two per line, shared by the views.
+#[derive(Resource)]
+pub struct GizmoVertices {
+    vertices: Vec<GizmoVertex>,
+    pub buffer: TypedBuffer<GizmoVertex>,
+}
+
+impl Default for GizmoVertices {
+    fn default() -> Self {
+        Self {
+            vertices: Vec::new(),
+            buffer: TypedBuffer::new(vk::BufferUsageFlags::VERTEX_BUFFER),
+        }
+    }
+}
+
+pub struct GizmoPlugin;
+
+impl Plugin for GizmoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Gizmos>()
+            .add_systems(First, clear_gizmos);
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<GizmoPipeline>()
+                .init_resource::<GizmoVertices>()
+                .add_systems(ExtractSchedule, extract_gizmos)
+                .add_systems(
+                    Render, (
+                        prepare_gizmo_pipeline.in_set(RenderSet::PrepareResources),
+                        prepare_gizmo_vertices.in_set(RenderSet::PrepareResources),
+                    )
+                )
+                .add_render_graph_node::<ViewNodeRunner<GizmoNode>>(PATH_TRACING_GRAPH, GIZMO_NODE)
+                .add_render_graph_edges(PATH_TRACING_GRAPH, &[PATH_TRACING_NODE, GIZMO_NODE, UPSCALE_NODE])
+                .add_render_graph_node::<ViewNodeRunner<GizmoNode>>(DEFERRED_GRAPH, GIZMO_NODE)
+                .add_render_graph_edges(DEFERRED_GRAPH, &[PARTICLE_NODE, GIZMO_NODE, UPSCALE_NODE])
+                .add_render_graph_edge(DEFERRED_GRAPH, DEBUG_VIEW_NODE, GIZMO_NODE);
+        }
+    }
+}
+
+fn clear_gizmos(mut gizmos: ResMut<Gizmos>) {
+    gizmos.lines.clear();
+}
+
+fn extract_gizmos(mut vertices: ResMut<GizmoVertices>, gizmos: Extract<Res<Gizmos>>) {
+    vertices.vertices.clear();
+    vertices.vertices.extend(gizmos.lines.iter().flat_map(|line| {
+        let color = line.color.to_linear_rgba();
+        [line.start, line.end].map(|position| GizmoVertex {
+            position: position.extend(1.0).to_array(),
+            color,
+        })
+    }));
+}
+
+fn prepare_gizmo_vertices(mut vertices: ResMut<GizmoVertices>, frame_context: Res<FrameContext>) {
+    let vertices = vertices.as_mut();
+    if vertices.vertices.is_empty() && vertices.buffer.is_empty() {
+        return;
+    }
+    if let Err(err) = vertices.buffer.write(frame_context.render_context(), &vertices.vertices) {
+        error!("Failed to upload gizmo vertices: {err}");
+    }
+}
@@ -0,0 +1,82 @@
+use ash::vk;
+use bevy_ecs::prelude::World;
+use avalanche_hlvk::{ImageBarrier, RenderingAttachment};
+use crate::camera::ExtractedCamera;
+use crate::extract::FrameContext;
+use crate::gizmos::{GizmoPipeline, GizmoPushConstants, GizmoVertices};
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::ViewNode;
+use crate::view::ViewTarget;
+
+/// Draws the [`Gizmos`](super::Gizmos) of the frame over the [`ViewTarget`] of a view.
+#[derive(Default)]
+pub struct GizmoNode;
+
+impl ViewNode for GizmoNode {
+    type ViewQuery = (&'static ExtractedCamera, &'static ViewTarget);
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (camera, target): (&ExtractedCamera, &ViewTarget),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let vertices = &world.resource::<GizmoVertices>().buffer;
+        if vertices.is_empty() {
+            return Ok(());
+        }
+        let (Some(pipeline), Some(vertex_buffer)) = (world.resource::<GizmoPipeline>().resources(), vertices.buffer()) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+
+        let extent = vk::Extent2D {
+            width: target.size.x,
+            height: target.size.y,
+        };
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &target.image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        }]);
+        command_buffer.begin_rendering_attachments(
+            &[RenderingAttachment {
+                view: &target.view,
+                layout: vk::ImageLayout::GENERAL,
+                load_op: vk::AttachmentLoadOp::LOAD,
+                clear_color: [0.0; 4],
+            }],
+            extent,
+        );
+        command_buffer.bind_raster_pipeline(&pipeline.pipeline);
+        command_buffer.set_viewport(extent);
+        command_buffer.set_scissor(extent);
+        let push_constants = GizmoPushConstants {
+            clip_from_world: (camera.projection * camera.world_from_view.inverse()).to_cols_array(),
+        };
+        command_buffer.push_constants(&pipeline.layout, vk::ShaderStageFlags::VERTEX, 0, bytemuck::bytes_of(&push_constants));
+        command_buffer.bind_vertex_buffer(vertex_buffer);
+        command_buffer.draw(vertices.len() as u32);
+        command_buffer.end_rendering();
+
+        // later passes expect the view target written by shaders
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &target.image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+
+        Ok(())
+    }
+}
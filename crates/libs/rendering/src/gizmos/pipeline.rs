@@ -0,0 +1,111 @@
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::prelude::{Res, ResMut, Resource};
+use bevy_log::error;
+use avalanche_hlvk::{
+    BlendMode, Context, PipelineLayout, RasterColorAttachment, RasterPipeline, RasterPipelineCreateInfo, VertexStreamSet,
+};
+use crate::extract::FrameContext;
+use crate::gizmos::{GizmoVertex, GizmoVertices};
+use crate::globals;
+use crate::shader::ShaderDirectory;
+use crate::view::VIEW_TARGET_FORMAT;
+
+pub(crate) const GIZMO_VERTEX_SHADER: &str = "gizmos/line.vert";
+pub(crate) const GIZMO_FRAGMENT_SHADER: &str = "gizmos/line.frag";
+
+/// Matches `GizmoPushConstants` in `shaders/gizmos/line.vert`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GizmoPushConstants {
+    pub clip_from_world: [f32; 16],
+}
+
+// SAFETY: plain `f32` fields without implicit padding
+unsafe impl Zeroable for GizmoPushConstants {}
+unsafe impl Pod for GizmoPushConstants {}
+
+pub struct GizmoPipelineResources {
+    /// Reflected from the line shaders, only a push constant range
+    pub layout: PipelineLayout,
+    /// Draws a line per pair of [`GizmoVertex`]es of the bound vertex buffer
+    pub pipeline: RasterPipeline,
+}
+
+/// The gizmo pipeline, created the first time lines are drawn.
+#[derive(Resource, Default)]
+pub enum GizmoPipeline {
+    #[default]
+    Uninitialized,
+    Ready(Box<GizmoPipelineResources>),
+    /// Creation failed, usually shaders are missing.
+    Failed,
+}
+
+impl GizmoPipeline {
+    pub fn resources(&self) -> Option<&GizmoPipelineResources> {
+        match self {
+            GizmoPipeline::Ready(resources) => Some(resources),
+            _ => None,
+        }
+    }
+}
+
+/// Both fields of a [`GizmoVertex`] are `vec4` inputs advanced per vertex.
+fn vertex_stream() -> VertexStreamSet {
+    let stride = std::mem::size_of::<GizmoVertex>() as u32;
+    (0..stride / 16).fold(VertexStreamSet::empty(), |streams, location| {
+        streams.add_stream(
+            stride,
+            vk::VertexInputRate::VERTEX,
+            location,
+            vk::Format::R32G32B32A32_SFLOAT,
+            Some(location * 16),
+        )
+    })
+}
+
+fn create_resources(context: &Context, shaders: &ShaderDirectory) -> anyhow::Result<GizmoPipelineResources> {
+    let stages = [
+        shaders.load(context, GIZMO_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX)?,
+        shaders.load(context, GIZMO_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT)?,
+    ];
+    let layout = globals::pipeline_layout(&stages)?;
+    anyhow::ensure!(globals::pass_set_count(&stages) == 0, "gizmo shaders must not use descriptor sets");
+
+    let pipeline = context.create_graphics_pipeline(&layout, RasterPipelineCreateInfo {
+        shaders: &stages,
+        primitive_topology: vk::PrimitiveTopology::LINE_LIST,
+        vertex_stream: &vertex_stream(),
+        viewport: None,
+        scissor: None,
+        color_attachments: &[RasterColorAttachment::new(VIEW_TARGET_FORMAT, BlendMode::AlphaBlend)],
+        depth_attachment: None,
+        dynamic_states: Some(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]),
+        polygon_mode: vk::PolygonMode::FILL,
+        front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+        samples: vk::SampleCountFlags::TYPE_1,
+        cull_mode: vk::CullModeFlags::NONE,
+    })?;
+
+    Ok(GizmoPipelineResources { layout, pipeline })
+}
+
+pub(crate) fn prepare_gizmo_pipeline(
+    mut pipeline: ResMut<GizmoPipeline>,
+    shaders: Res<ShaderDirectory>,
+    vertices: Res<GizmoVertices>,
+    frame_context: Res<FrameContext>,
+) {
+    if !matches!(*pipeline, GizmoPipeline::Uninitialized) || vertices.vertices.is_empty() {
+        return;
+    }
+
+    *pipeline = match create_resources(frame_context.render_context(), &shaders) {
+        Ok(resources) => GizmoPipeline::Ready(Box::new(resources)),
+        Err(err) => {
+            error!("Failed to create gizmo pipeline: {err}");
+            GizmoPipeline::Failed
+        }
+    };
+}
@@ -0,0 +1,223 @@
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{IntoSystemConfigs, Query, Res, ResMut, Resource, With};
+use bevy_log::error;
+use bevy_time::Time;
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{
+    Buffer, CommandBuffer, Context, DescriptorPool, DescriptorSet, DescriptorSetLayout, PipelineLayout, StagedShader,
+    WriteDescriptorSet, WriteDescriptorSetKind,
+};
+use avalanche_window::{PrimaryWindowComponent, WindowComponent};
+use crate::{ExtractSchedule, Render, RenderApp, RenderSet};
+use crate::extract::FrameContext;
+use crate::prelude::Extract;
+
+/// Set of the [`GlobalsUniform`] in the pipelines whose shaders include `shaders/globals.glsl`,
+/// after the sets used by the passes.
+pub const GLOBALS_SET: u32 = 3;
+
+/// Bindings of the [`GLOBALS_SET`], visible to every stage.
+///
+/// Debug builds also bind the [`ShaderDebugPrint`](crate::debug_print::ShaderDebugPrint) buffer at binding 1.
+pub fn globals_bindings() -> Vec<vk::DescriptorSetLayoutBinding> {
+    let mut bindings = vec![vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::ALL)
+        .build()];
+    if cfg!(debug_assertions) {
+        bindings.push(vk::DescriptorSetLayoutBinding::builder()
+            .binding(1)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::ALL)
+            .build());
+    }
+    bindings
+}
+
+/// Reflect the layout of a pipeline from its shaders, with the [`GLOBALS_SET`] bindable by [`Globals::bind`].
+pub fn pipeline_layout(shaders: &[StagedShader]) -> anyhow::Result<PipelineLayout> {
+    let bindings = globals_bindings();
+    PipelineLayout::from_shaders_with_shared_sets(shaders, &[(GLOBALS_SET, bindings.as_slice())])
+}
+
+/// Sets used by the shaders of a pass, besides the [`GLOBALS_SET`].
+pub fn pass_set_count(shaders: &[StagedShader]) -> usize {
+    shaders
+        .iter()
+        .flat_map(|shader| &shader.module.reflection.bindings)
+        .map(|binding| binding.set)
+        .filter(|&set| set != GLOBALS_SET)
+        .max()
+        .map_or(0, |set| set as usize + 1)
+}
+
+/// Bytes of the buffer bound at binding 1 of the [`GLOBALS_SET`] in debug builds, see `shaders/debug/print.glsl`.
+pub const DEBUG_PRINT_BUFFER_SIZE: u64 = 64 * 1024;
+
+/// Matches `Globals` in `shaders/globals.glsl`, written with the std140 layout.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GlobalsUniform {
+    /// Seconds since startup, wrapped every hour to keep its precision
+    pub time: f32,
+    /// Seconds since the previous frame
+    pub delta_time: f32,
+    /// Frames rendered before this one
+    pub frame_count: u32,
+    /// Differs every frame, e.g. to animate noise
+    pub random_seed: u32,
+    /// Pixels of the primary window
+    pub resolution: [f32; 2],
+    _padding: [f32; 2],
+}
+
+// SAFETY: plain 32 bit fields without implicit padding
+unsafe impl Zeroable for GlobalsUniform {}
+unsafe impl Pod for GlobalsUniform {}
+
+/// Prepares the [`GlobalsUniform`] every frame, so shaders can animate without per-material plumbing.
+pub struct GlobalsPlugin;
+
+impl Plugin for GlobalsPlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<Globals>()
+                .add_systems(ExtractSchedule, extract_globals)
+                .add_systems(Render, prepare_globals.in_set(RenderSet::PrepareResources));
+        }
+    }
+}
+
+struct GlobalsResources {
+    buffer: Buffer,
+    debug_print_buffer: Option<Buffer>,
+    _descriptor_set_layout: DescriptorSetLayout,
+    _descriptor_pool: DescriptorPool,
+    descriptor_set: DescriptorSet,
+}
+
+impl GlobalsResources {
+    fn new(context: &Context) -> anyhow::Result<Self> {
+        let buffer = context.create_buffer(
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of::<GlobalsUniform>() as _,
+        )?;
+        let debug_print_buffer = cfg!(debug_assertions)
+            .then(|| context.create_buffer(
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST,
+                MemoryLocation::GpuOnly,
+                DEBUG_PRINT_BUFFER_SIZE,
+            ))
+            .transpose()?;
+        let descriptor_set_layout = context.create_descriptor_set_layout(&globals_bindings())?;
+        let descriptor_pool = context.create_descriptor_pool(1, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+            },
+        ])?;
+        let descriptor_set = descriptor_pool.allocate_set(&descriptor_set_layout)?;
+        let mut writes = vec![WriteDescriptorSet {
+            binding: 0,
+            kind: WriteDescriptorSetKind::UniformBuffer { buffer: &buffer },
+        }];
+        if let Some(debug_print_buffer) = &debug_print_buffer {
+            writes.push(WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: debug_print_buffer },
+            });
+        }
+        descriptor_set.update(&writes);
+
+        Ok(Self {
+            buffer,
+            debug_print_buffer,
+            _descriptor_set_layout: descriptor_set_layout,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+        })
+    }
+}
+
+/// The [`GlobalsUniform`] of the frame and the descriptor set binding it, see [`GLOBALS_SET`].
+#[derive(Resource, Default)]
+pub struct Globals {
+    pub uniform: GlobalsUniform,
+    resources: Option<GlobalsResources>,
+}
+
+impl Globals {
+    /// Set binding the uniform, `None` until the first frame is prepared.
+    pub fn descriptor_set(&self) -> Option<&DescriptorSet> {
+        self.resources.as_ref().map(|resources| &resources.descriptor_set)
+    }
+
+    /// Buffer the shaders of debug builds print to, `None` in release builds and until the first frame is prepared.
+    pub fn debug_print_buffer(&self) -> Option<&Buffer> {
+        self.resources.as_ref().and_then(|resources| resources.debug_print_buffer.as_ref())
+    }
+
+    /// Bind the uniform to the [`GLOBALS_SET`] of `layout`, if its shaders use it.
+    pub fn bind(&self, command_buffer: &CommandBuffer, bind_point: vk::PipelineBindPoint, layout: &PipelineLayout) {
+        if layout.descriptor_set_layouts().len() <= GLOBALS_SET as usize {
+            return;
+        }
+        if let Some(descriptor_set) = self.descriptor_set() {
+            command_buffer.bind_descriptor_sets(bind_point, layout, GLOBALS_SET, &[descriptor_set]);
+        }
+    }
+}
+
+fn extract_globals(
+    mut globals: ResMut<Globals>,
+    time: Extract<Res<Time>>,
+    primary_window: Extract<Query<&WindowComponent, With<PrimaryWindowComponent>>>,
+) {
+    globals.uniform.time = time.elapsed_seconds_wrapped();
+    globals.uniform.delta_time = time.delta_seconds();
+    if let Ok(window) = primary_window.get_single() {
+        let size = window.window.inner_size();
+        globals.uniform.resolution = [size.width as f32, size.height as f32];
+    }
+}
+
+pub(crate) fn prepare_globals(mut globals: ResMut<Globals>, frame_context: Res<FrameContext>) {
+    let globals = globals.as_mut();
+    if globals.resources.is_none() {
+        match GlobalsResources::new(frame_context.render_context()) {
+            Ok(resources) => globals.resources = Some(resources),
+            Err(err) => {
+                error!("Failed to create the globals uniform: {err}");
+                return;
+            }
+        }
+    }
+
+    let uniform = &mut globals.uniform;
+    uniform.random_seed = hash(uniform.frame_count);
+    if let Err(err) = globals.resources.as_ref().unwrap().buffer.copy_data_to_buffer(&[*uniform]) {
+        error!("Failed to write the globals uniform: {err}");
+    }
+    uniform.frame_count = uniform.frame_count.wrapping_add(1);
+}
+
+/// Integer hash by Chris Wellons (lowbias32), spreads consecutive frames over the whole range.
+fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
@@ -5,8 +5,16 @@ mod graph;
 mod error;
 pub mod context;
 pub mod app;
+mod edits;
+mod inputs;
+mod label;
+mod graphs;
 
 pub use graph::*;
 pub use error::*;
 pub use context::*;
 pub use app::*;
+pub use edits::*;
+pub use inputs::*;
+pub use label::*;
+pub use graphs::*;
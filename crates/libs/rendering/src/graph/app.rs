@@ -1,93 +1,216 @@
-use bevy_app::App;
-use bevy_ecs::world::FromWorld;
+use bevy_app::{App, SubApp};
+use bevy_ecs::prelude::Resource;
+use bevy_ecs::world::{FromWorld, World};
 use bevy_log::warn;
 use crate::prelude::node::Node;
+use crate::prelude::RenderGraphError;
 
-use super::{RenderGraph};
+use super::{find_graph_mut, RenderGraph, RenderGraphs, RenderLabel};
 
-/// Adds common [`RenderGraph`] operations to [`App`].
+/// Adds common [`RenderGraph`] operations to [`App`] and [`SubApp`].
+///
+/// Graphs are named like views select them, a sub graph of the main graph or else a top level graph
+/// of the [`RenderGraphs`].
+///
+/// Edges may name nodes registered by plugins built later,
+/// such edges are kept aside and added once every plugin is built.
 pub trait RenderGraphApp {
     // Add a sub graph to the [`RenderGraph`]
-    fn add_render_sub_graph(&mut self, sub_graph_name: &'static str) -> &mut Self;
+    fn add_render_sub_graph(&mut self, sub_graph_name: impl Into<RenderLabel>) -> &mut Self;
+    /// Add a top level graph to the [`RenderGraphs`], which may run instead of the main graph
+    fn add_render_graph(&mut self, graph_name: impl Into<RenderLabel>) -> &mut Self;
     /// Add a [`Node`] to the [`RenderGraph`]:
     /// * Create the [`Node`] using the [`FromWorld`] implementation
     /// * Add it to the graph
     fn add_render_graph_node<T: Node + FromWorld>(
         &mut self,
-        sub_graph_name: &'static str,
-        node_name: &'static str,
+        sub_graph_name: impl Into<RenderLabel>,
+        node_name: impl Into<RenderLabel>,
     ) -> &mut Self;
     /// Automatically add the required node edges based on the given ordering
     fn add_render_graph_edges(
         &mut self,
-        sub_graph_name: &'static str,
+        sub_graph_name: impl Into<RenderLabel>,
         edges: &[&'static str],
     ) -> &mut Self;
     /// Add node edge to the specified graph
     fn add_render_graph_edge(
         &mut self,
-        sub_graph_name: &'static str,
-        output_edge: &'static str,
-        input_edge: &'static str,
+        sub_graph_name: impl Into<RenderLabel>,
+        output_edge: impl Into<RenderLabel>,
+        input_edge: impl Into<RenderLabel>,
     ) -> &mut Self;
 }
 
 impl RenderGraphApp for App {
-    fn add_render_sub_graph(&mut self, sub_graph_name: &'static str) -> &mut Self {
-        let mut render_graph = self.world.get_resource_mut::<RenderGraph>().expect(
-            "RenderGraph not found. Make sure you are using add_render_sub_graph on the RenderApp",
-        );
-        render_graph.add_sub_graph(sub_graph_name, RenderGraph::default());
+    fn add_render_sub_graph(&mut self, sub_graph_name: impl Into<RenderLabel>) -> &mut Self {
+        add_sub_graph(&mut self.world, sub_graph_name.into());
+        self
+    }
+
+    fn add_render_graph(&mut self, graph_name: impl Into<RenderLabel>) -> &mut Self {
+        add_graph(&mut self.world, graph_name.into());
         self
     }
 
     fn add_render_graph_node<T: Node + FromWorld>(
         &mut self,
-        sub_graph_name: &'static str,
-        node_name: &'static str,
+        sub_graph_name: impl Into<RenderLabel>,
+        node_name: impl Into<RenderLabel>,
     ) -> &mut Self {
-        let node = T::from_world(&mut self.world);
-        let mut render_graph = self.world.get_resource_mut::<RenderGraph>().expect(
-            "RenderGraph not found. Make sure you are using add_render_graph_node on the RenderApp",
-        );
-        if let Some(graph) = render_graph.get_sub_graph_mut(sub_graph_name) {
-            graph.add_node(node_name, node);
-        } else {
-            warn!("Tried adding a render graph node to {sub_graph_name} but the sub graph doesn't exist");
-        }
+        add_node::<T>(&mut self.world, sub_graph_name.into(), node_name.into());
         self
     }
 
     fn add_render_graph_edges(
         &mut self,
-        sub_graph_name: &'static str,
+        sub_graph_name: impl Into<RenderLabel>,
         edges: &[&'static str],
     ) -> &mut Self {
-        let mut render_graph = self.world.get_resource_mut::<RenderGraph>().expect(
-            "RenderGraph not found. Make sure you are using add_render_graph_edges on the RenderApp",
-        );
-        if let Some(graph) = render_graph.get_sub_graph_mut(sub_graph_name) {
-            graph.add_node_edges(edges);
-        } else {
-            warn!("Tried adding render graph edges to {sub_graph_name} but the sub graph doesn't exist");
+        let sub_graph_name = sub_graph_name.into();
+        for window in edges.windows(2) {
+            add_edge(&mut self.world, sub_graph_name, window[0].into(), window[1].into());
         }
         self
     }
 
     fn add_render_graph_edge(
         &mut self,
-        sub_graph_name: &'static str,
-        output_edge: &'static str,
-        input_edge: &'static str,
+        sub_graph_name: impl Into<RenderLabel>,
+        output_edge: impl Into<RenderLabel>,
+        input_edge: impl Into<RenderLabel>,
     ) -> &mut Self {
-        let mut render_graph = self.world.get_resource_mut::<RenderGraph>().expect(
-            "RenderGraph not found. Make sure you are using add_render_graph_edge on the RenderApp",
-        );
-        if let Some(graph) = render_graph.get_sub_graph_mut(sub_graph_name) {
-            graph.add_node_edge(output_edge, input_edge);
-        } else {
-            warn!("Tried adding a render graph edge to {sub_graph_name} but the sub graph doesn't exist");
-        }
+        add_edge(&mut self.world, sub_graph_name.into(), output_edge.into(), input_edge.into());
+        self
+    }
+}
+
+impl RenderGraphApp for SubApp {
+    fn add_render_sub_graph(&mut self, sub_graph_name: impl Into<RenderLabel>) -> &mut Self {
+        self.app.add_render_sub_graph(sub_graph_name);
+        self
+    }
+
+    fn add_render_graph(&mut self, graph_name: impl Into<RenderLabel>) -> &mut Self {
+        self.app.add_render_graph(graph_name);
+        self
+    }
+
+    fn add_render_graph_node<T: Node + FromWorld>(
+        &mut self,
+        sub_graph_name: impl Into<RenderLabel>,
+        node_name: impl Into<RenderLabel>,
+    ) -> &mut Self {
+        self.app.add_render_graph_node::<T>(sub_graph_name, node_name);
+        self
+    }
+
+    fn add_render_graph_edges(
+        &mut self,
+        sub_graph_name: impl Into<RenderLabel>,
+        edges: &[&'static str],
+    ) -> &mut Self {
+        self.app.add_render_graph_edges(sub_graph_name, edges);
+        self
+    }
+
+    fn add_render_graph_edge(
+        &mut self,
+        sub_graph_name: impl Into<RenderLabel>,
+        output_edge: impl Into<RenderLabel>,
+        input_edge: impl Into<RenderLabel>,
+    ) -> &mut Self {
+        self.app.add_render_graph_edge(sub_graph_name, output_edge, input_edge);
         self
     }
 }
+
+/// Edges whose sub graph or nodes were not registered yet when they were added.
+#[derive(Resource, Default)]
+pub(crate) struct PendingRenderGraphEdges(Vec<PendingEdge>);
+
+#[derive(Clone, Copy)]
+struct PendingEdge {
+    sub_graph_name: RenderLabel,
+    output_node: RenderLabel,
+    input_node: RenderLabel,
+}
+
+fn render_graph_mut(world: &mut World) -> bevy_ecs::world::Mut<'_, RenderGraph> {
+    world.get_resource_mut::<RenderGraph>().expect(
+        "RenderGraph not found. Make sure you are using RenderGraphApp on the RenderApp",
+    )
+}
+
+fn add_sub_graph(world: &mut World, sub_graph_name: RenderLabel) {
+    let mut render_graph = render_graph_mut(world);
+    if render_graph.get_sub_graph(sub_graph_name).is_none() {
+        render_graph.add_sub_graph(sub_graph_name, RenderGraph::default());
+    }
+    apply_pending_edges(world, false);
+}
+
+fn add_graph(world: &mut World, graph_name: RenderLabel) {
+    let mut graphs = world.get_resource_mut::<RenderGraphs>().expect(
+        "RenderGraphs not found. Make sure you are using RenderGraphApp on the RenderApp",
+    );
+    if graphs.get_graph(graph_name).is_none() {
+        graphs.add_graph(graph_name, RenderGraph::default());
+    }
+    apply_pending_edges(world, false);
+}
+
+fn add_node<T: Node + FromWorld>(world: &mut World, sub_graph_name: RenderLabel, node_name: RenderLabel) {
+    let node = T::from_world(world);
+    if let Some(mut graph) = find_graph_mut(world, sub_graph_name) {
+        graph.add_node(node_name, node);
+    } else {
+        warn!("Tried adding a render graph node to {sub_graph_name} but the sub graph doesn't exist");
+        return;
+    }
+    apply_pending_edges(world, false);
+}
+
+fn add_edge(world: &mut World, sub_graph_name: RenderLabel, output_node: RenderLabel, input_node: RenderLabel) {
+    let edge = PendingEdge {
+        sub_graph_name,
+        output_node,
+        input_node,
+    };
+    if !try_add_edge(world, edge) {
+        world.get_resource_or_insert_with(PendingRenderGraphEdges::default).0.push(edge);
+    }
+}
+
+/// Returns false if the edge has to wait for its sub graph or nodes.
+fn try_add_edge(world: &mut World, edge: PendingEdge) -> bool {
+    let Some(mut graph) = find_graph_mut(world, edge.sub_graph_name) else {
+        return false;
+    };
+
+    match graph.try_add_node_edge(edge.output_node, edge.input_node) {
+        Ok(()) | Err(RenderGraphError::EdgeAlreadyExists(_)) => true,
+        Err(RenderGraphError::InvalidNode(_)) => false,
+        Err(err) => panic!("Failed to add render graph edge {} -> {}: {err:?}", edge.output_node, edge.input_node),
+    }
+}
+
+/// Retry the edges kept aside, with `finalize` the ones still missing a node are reported and dropped.
+pub(crate) fn apply_pending_edges(world: &mut World, finalize: bool) {
+    let Some(mut pending) = world.remove_resource::<PendingRenderGraphEdges>() else {
+        return;
+    };
+
+    pending.0.retain(|edge| !try_add_edge(world, *edge));
+
+    if finalize {
+        for edge in pending.0.drain(..) {
+            warn!(
+                "Render graph edge {} -> {} in {} was dropped as a node doesn't exist",
+                edge.output_node, edge.input_node, edge.sub_graph_name,
+            );
+        }
+    } else if !pending.0.is_empty() {
+        world.insert_resource(pending);
+    }
+}
@@ -1,20 +1,21 @@
-use std::borrow::Cow;
 use bevy_ecs::prelude::Entity;
 use crate::prelude::node::NodeState;
-use crate::prelude::node_slot::{SlotInfos, SlotLabel, SlotType, SlotValue};
-use crate::prelude::{ImageView, InputSlotError, OutputSlotError, RenderGraph, RunSubGraphError};
+use crate::prelude::node_slot::{AnySlot, SlotInfos, SlotLabel, SlotType, SlotValue};
+use crate::prelude::{ImageView, InputSlotError, OutputSlotError, RenderGraph, RenderGraphs, RenderLabel, RunSubGraphError};
 use crate::resource::{Buffer, Sampler};
 
 /// A command that signals the graph runner to run the sub graph corresponding to the `name`
 /// with the specified `inputs` next.
 pub struct RunSubGraph {
-    pub name: Cow<'static, str>,
+    pub name: RenderLabel,
     pub inputs: Vec<SlotValue>,
     pub view_entity: Option<Entity>,
 }
 
 pub struct RenderGraphContext<'a> {
     graph: &'a RenderGraph,
+    /// Top level graphs the node may run like sub graphs
+    graphs: Option<&'a RenderGraphs>,
     node: &'a NodeState,
     inputs: &'a [SlotValue],
     outputs: &'a mut [Option<SlotValue>],
@@ -36,6 +37,7 @@ impl<'a> RenderGraphContext<'a> {
     ) -> Self {
         Self {
             graph,
+            graphs: None,
             node,
             inputs,
             outputs,
@@ -129,6 +131,17 @@ impl<'a> RenderGraphContext<'a> {
         }
     }
 
+    /// Retrieves the input slot value referenced by the `label` as a custom `T`.
+    pub fn get_input_custom<T: AnySlot>(&self, label: impl Into<SlotLabel>) -> Result<&T, InputSlotError> {
+        let label = label.into();
+        let value = self.get_input(label.clone())?;
+        value.downcast_ref::<T>().ok_or_else(|| InputSlotError::MismatchedSlotType {
+            label,
+            actual: value.slot_type(),
+            expected: SlotType::custom::<T>(),
+        })
+    }
+
     /// Sets the output slot value referenced by the `label`.
     pub fn set_output(
         &mut self,
@@ -168,18 +181,22 @@ impl<'a> RenderGraphContext<'a> {
         self.view_entity = Some(view_entity);
     }
 
+    pub fn set_render_graphs(&mut self, graphs: &'a RenderGraphs) {
+        self.graphs = Some(graphs);
+    }
+
     /// Queues up a sub graph for execution after the node has finished running.
+    ///
+    /// The `name` may be a top level graph of the [`RenderGraphs`] too, e.g. selected per view.
     pub fn run_sub_graph(
         &mut self,
-        name: impl Into<Cow<'static, str>>,
+        name: impl Into<RenderLabel>,
         inputs: Vec<SlotValue>,
         view_entity: Option<Entity>,
     ) -> Result<(), RunSubGraphError> {
         let name = name.into();
-        let sub_graph = self
-            .graph
-            .get_sub_graph(&name)
-            .ok_or_else(|| RunSubGraphError::MissingSubGraph(name.clone()))?;
+        let sub_graph = RenderGraphs::find(self.graphs, self.graph, name)
+            .ok_or(RunSubGraphError::MissingSubGraph(name))?;
         if let Some(input_node) = sub_graph.get_input_node() {
             for (i, input_slot) in input_node.input_slots.iter().enumerate() {
                 if let Some(input_value) = inputs.get(i) {
@@ -0,0 +1,225 @@
+use std::sync::{Arc, Mutex};
+use anyhow::{bail, ensure};
+use bevy_ecs::prelude::{Res, ResMut, Resource, World};
+use bevy_log::error;
+use bevy_utils::{HashMap, HashSet};
+use crate::prelude::Extract;
+use crate::prelude::edge::Edge;
+use crate::prelude::node::Node;
+use super::{find_graph_mut, is_in_group, RenderGraph, RenderGraphError, RenderLabel};
+
+type AddNode = Box<dyn FnOnce(&mut RenderGraph, &'static str) + Send + Sync>;
+
+enum RenderGraphEdit {
+    AddNode { name: &'static str, add: AddNode },
+    RemoveNode(&'static str),
+    AddNodeEdge(&'static str, &'static str),
+    RemoveNodeEdge(&'static str, &'static str),
+    RemoveGroup(&'static str),
+    SetGroupEnabled(&'static str, bool),
+}
+
+/// Edits of a sub graph queued with [`RenderGraphEdits::edit`], applied in order.
+pub struct SubGraphEdits {
+    sub_graph_name: RenderLabel,
+    edits: Vec<RenderGraphEdit>,
+}
+
+impl SubGraphEdits {
+    /// Add the `node` with the `name`, a node already in the sub graph with the name is removed with its edges.
+    pub fn add_node(&mut self, name: &'static str, node: impl Node) -> &mut Self {
+        let add = move |graph: &mut RenderGraph, name: &'static str| {
+            graph.add_node(name, node);
+        };
+        self.edits.push(RenderGraphEdit::AddNode { name, add: Box::new(add) });
+        self
+    }
+
+    pub fn remove_node(&mut self, name: &'static str) -> &mut Self {
+        self.edits.push(RenderGraphEdit::RemoveNode(name));
+        self
+    }
+
+    /// Run `output_node` before `input_node`, an edge that already exists is kept.
+    pub fn add_node_edge(&mut self, output_node: &'static str, input_node: &'static str) -> &mut Self {
+        self.edits.push(RenderGraphEdit::AddNodeEdge(output_node, input_node));
+        self
+    }
+
+    /// Add the node edges following the order of `nodes`.
+    pub fn add_node_edges(&mut self, nodes: &[&'static str]) -> &mut Self {
+        for window in nodes.windows(2) {
+            self.add_node_edge(window[0], window[1]);
+        }
+        self
+    }
+
+    pub fn remove_node_edge(&mut self, output_node: &'static str, input_node: &'static str) -> &mut Self {
+        self.edits.push(RenderGraphEdit::RemoveNodeEdge(output_node, input_node));
+        self
+    }
+
+    /// Remove the nodes named in `group` with their edges, see [`RenderGraph::remove_group`].
+    pub fn remove_group(&mut self, group: &'static str) -> &mut Self {
+        self.edits.push(RenderGraphEdit::RemoveGroup(group));
+        self
+    }
+
+    /// Skip or run again the nodes named in `group`, see [`RenderGraph::set_group_enabled`].
+    pub fn set_group_enabled(&mut self, group: &'static str, enabled: bool) -> &mut Self {
+        self.edits.push(RenderGraphEdit::SetGroupEnabled(group, enabled));
+        self
+    }
+
+    /// Check the edits would succeed and leave the sub graph without cycles, without changing it.
+    fn validate(&self, graph: &RenderGraph) -> anyhow::Result<()> {
+        let names = graph
+            .iter_nodes()
+            .filter_map(|node| Some((node.id, node.name?.as_str())))
+            .collect::<HashMap<_, _>>();
+        let mut nodes = names.values().copied().collect::<HashSet<_>>();
+        let mut node_edges = HashSet::new();
+        // slot edges can't be edited, they only order the nodes
+        let mut slot_edges = HashSet::new();
+        for node in graph.iter_nodes() {
+            for edge in node.edges.output_edges() {
+                let (Some(output_node), Some(input_node)) =
+                    (names.get(&edge.get_output_node()), names.get(&edge.get_input_node())) else {
+                    continue;
+                };
+                match edge {
+                    Edge::NodeEdge { .. } => node_edges.insert((*output_node, *input_node)),
+                    Edge::SlotEdge { .. } => slot_edges.insert((*output_node, *input_node)),
+                };
+            }
+        }
+
+        for edit in &self.edits {
+            match *edit {
+                RenderGraphEdit::AddNode { name, .. } => {
+                    node_edges.retain(|(output_node, input_node)| *output_node != name && *input_node != name);
+                    slot_edges.retain(|(output_node, input_node)| *output_node != name && *input_node != name);
+                    nodes.insert(name);
+                }
+                RenderGraphEdit::RemoveNode(name) => {
+                    ensure!(nodes.remove(name), "node {name} doesn't exist");
+                    node_edges.retain(|(output_node, input_node)| *output_node != name && *input_node != name);
+                    slot_edges.retain(|(output_node, input_node)| *output_node != name && *input_node != name);
+                }
+                RenderGraphEdit::AddNodeEdge(output_node, input_node) => {
+                    ensure!(nodes.contains(output_node), "node {output_node} doesn't exist");
+                    ensure!(nodes.contains(input_node), "node {input_node} doesn't exist");
+                    node_edges.insert((output_node, input_node));
+                }
+                RenderGraphEdit::RemoveNodeEdge(output_node, input_node) => {
+                    ensure!(
+                        node_edges.remove(&(output_node, input_node)),
+                        "node edge {output_node} -> {input_node} doesn't exist",
+                    );
+                }
+                RenderGraphEdit::RemoveGroup(group) => {
+                    let count = nodes.len();
+                    nodes.retain(|name| !is_in_group(name, group));
+                    ensure!(nodes.len() != count, "group {group} has no nodes");
+                    node_edges.retain(|(output_node, input_node)| nodes.contains(output_node) && nodes.contains(input_node));
+                    slot_edges.retain(|(output_node, input_node)| nodes.contains(output_node) && nodes.contains(input_node));
+                }
+                RenderGraphEdit::SetGroupEnabled(..) => {}
+            }
+        }
+
+        // remove the nodes without inputs until none are left, the nodes remaining are in a cycle
+        let mut inputs = nodes.iter().map(|node| (*node, 0usize)).collect::<HashMap<_, _>>();
+        for (_, input_node) in node_edges.iter().chain(slot_edges.iter()) {
+            *inputs.get_mut(input_node).unwrap() += 1;
+        }
+        let mut ready = inputs.iter().filter(|(_, count)| **count == 0).map(|(node, _)| *node).collect::<Vec<_>>();
+        let mut visited = 0;
+        while let Some(node) = ready.pop() {
+            visited += 1;
+            for (_, input_node) in node_edges.iter().chain(slot_edges.iter()).filter(|(output_node, _)| *output_node == node) {
+                let count = inputs.get_mut(input_node).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(*input_node);
+                }
+            }
+        }
+        if visited != nodes.len() {
+            bail!("the edges would form a cycle");
+        }
+        Ok(())
+    }
+
+    fn apply(self, graph: &mut RenderGraph) -> Result<(), RenderGraphError> {
+        for edit in self.edits {
+            match edit {
+                RenderGraphEdit::AddNode { name, add } => {
+                    if graph.get_node_id(name).is_ok() {
+                        graph.remove_node(name)?;
+                    }
+                    add(graph, name);
+                }
+                RenderGraphEdit::RemoveNode(name) => graph.remove_node(name)?,
+                RenderGraphEdit::AddNodeEdge(output_node, input_node) => match graph.try_add_node_edge(output_node, input_node) {
+                    Ok(()) | Err(RenderGraphError::EdgeAlreadyExists(_)) => {}
+                    Err(err) => return Err(err),
+                },
+                RenderGraphEdit::RemoveNodeEdge(output_node, input_node) => graph.remove_node_edge(output_node, input_node)?,
+                RenderGraphEdit::RemoveGroup(group) => graph.remove_group(group)?,
+                RenderGraphEdit::SetGroupEnabled(group, enabled) => graph.set_group_enabled(group, enabled),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Changes of the [`RenderGraph`] queued while the app runs, lives in the main world.
+///
+/// The edits of a sub graph are validated once extracted and applied between frames, all or none of them,
+/// so effects can be toggled without rebuilding the app. Clones share the queue.
+#[derive(Resource, Clone, Default)]
+pub struct RenderGraphEdits(Arc<Mutex<Vec<SubGraphEdits>>>);
+
+impl RenderGraphEdits {
+    /// Queue edits of the sub graph named `sub_graph_name`, rejected together if one would fail.
+    ///
+    /// Top level graphs of the [`RenderGraphs`](super::RenderGraphs) are edited the same way.
+    pub fn edit(&self, sub_graph_name: impl Into<RenderLabel>, edit: impl FnOnce(&mut SubGraphEdits)) {
+        let mut edits = SubGraphEdits {
+            sub_graph_name: sub_graph_name.into(),
+            edits: Vec::new(),
+        };
+        edit(&mut edits);
+        if !edits.edits.is_empty() {
+            self.0.lock().unwrap().push(edits);
+        }
+    }
+}
+
+/// Edits moved out of the [`RenderGraphEdits`] of the main world.
+#[derive(Resource, Default)]
+pub(crate) struct ExtractedRenderGraphEdits(Vec<SubGraphEdits>);
+
+pub(crate) fn extract_render_graph_edits(edits: Extract<Res<RenderGraphEdits>>, mut extracted: ResMut<ExtractedRenderGraphEdits>) {
+    extracted.0.append(&mut edits.0.lock().unwrap());
+}
+
+/// Applies the extracted edits before the graph runs, rejected ones are reported.
+pub(crate) fn apply_render_graph_edits(world: &mut World) {
+    let edits = std::mem::take(&mut world.resource_mut::<ExtractedRenderGraphEdits>().0);
+    for edits in edits {
+        let sub_graph_name = edits.sub_graph_name;
+        let Some(mut graph) = find_graph_mut(world, sub_graph_name) else {
+            error!("Render graph edits were rejected as the sub graph {sub_graph_name} doesn't exist");
+            continue;
+        };
+        if let Err(err) = edits.validate(&graph) {
+            error!("Render graph edits of {sub_graph_name} were rejected: {err}");
+            continue;
+        }
+        if let Err(err) = edits.apply(&mut graph) {
+            error!("Failed to apply the render graph edits of {sub_graph_name}: {err:?}");
+        }
+    }
+}
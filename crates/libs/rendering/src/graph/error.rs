@@ -3,6 +3,7 @@ use thiserror::Error;
 use crate::prelude::edge::Edge;
 use crate::prelude::node::{NodeId, NodeLabel};
 use crate::prelude::node_slot::{SlotLabel, SlotType};
+use crate::graph::RenderLabel;
 
 
 #[derive(Error, Debug, Eq, PartialEq)]
@@ -18,18 +19,18 @@ pub enum NodeRunError {
 #[derive(Error, Debug, Eq, PartialEq)]
 pub enum RunSubGraphError {
     #[error("attempted to run sub-graph `{0}`, but it does not exist")]
-    MissingSubGraph(Cow<'static, str>),
+    MissingSubGraph(RenderLabel),
     #[error("attempted to pass inputs to sub-graph `{0}`, which has no input slots")]
-    SubGraphHasNoInputs(Cow<'static, str>),
+    SubGraphHasNoInputs(RenderLabel),
     #[error("sub graph (name: `{graph_name:?}`) could not be run because slot `{slot_name}` at index {slot_index} has no value")]
     MissingInput {
         slot_index: usize,
         slot_name: Cow<'static, str>,
-        graph_name: Cow<'static, str>,
+        graph_name: RenderLabel,
     },
     #[error("attempted to use the wrong type for input slot")]
     MismatchedInputSlotType {
-        graph_name: Cow<'static, str>,
+        graph_name: RenderLabel,
         slot_index: usize,
         label: SlotLabel,
         expected: SlotType,
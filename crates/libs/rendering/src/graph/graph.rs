@@ -1,9 +1,9 @@
-use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::BTreeSet;
 use std::fmt::Debug;
 use bevy_ecs::prelude::{Resource, World};
 use crate::extract::FrameContext;
-use crate::graph::NodeRunError;
+use avalanche_utils::PreHashedMap;
+use crate::graph::{is_in_group, NodeRunError, RenderLabel, GROUP_SEPARATOR};
 use crate::prelude::node::{Node, NodeId, NodeLabel, NodeState};
 use crate::prelude::node_slot::{SlotInfo, SlotLabel};
 use crate::prelude::{RenderGraphContext, RenderGraphError};
@@ -46,12 +46,16 @@ use crate::prelude::edge::{Edge, EdgeExistence};
 /// graph.add_node("output_node", MyNode);
 /// graph.add_node_edge("output_node", "input_node");
 /// ```
+///
+/// Nodes and sub graphs are named by [`RenderLabel`]s, strings are converted on the fly.
+/// Declare the names used every frame as `RenderLabel` constants to hash them at compile time.
 #[derive(Resource, Default)]
 pub struct RenderGraph {
-    nodes: HashMap<NodeId, NodeState>,
-    node_names: HashMap<Cow<'static, str>, NodeId>,
-    sub_graphs: HashMap<Cow<'static, str>, RenderGraph>,
+    nodes: PreHashedMap<NodeId, NodeState>,
+    node_names: PreHashedMap<RenderLabel, NodeId>,
+    sub_graphs: PreHashedMap<RenderLabel, RenderGraph>,
     input_node: Option<NodeId>,
+    disabled_groups: Vec<String>,
 }
 
 impl RenderGraph {
@@ -104,14 +108,18 @@ impl RenderGraph {
 
     /// Adds the `node` with the `name` to the graph.
     /// If the name is already present replaces it instead.
-    pub fn add_node<T>(&mut self, name: impl Into<Cow<'static, str>>, node: T) -> NodeId
+    pub fn add_node<T>(&mut self, name: impl Into<RenderLabel>, node: T) -> NodeId
         where
             T: Node,
     {
         let id = NodeId::new();
         let name = name.into();
+        if let Some((existing, _)) = self.node_names.get_key_value(&name) {
+            existing.assert_same_name(name.as_str());
+        }
         let mut node_state = NodeState::new(id, node);
-        node_state.name = Some(name.clone());
+        node_state.name = Some(name);
+        node_state.enabled = !self.disabled_groups.iter().any(|group| name.is_in_group(group));
         self.nodes.insert(id, node_state);
         self.node_names.insert(name, id);
         id
@@ -141,7 +149,7 @@ impl RenderGraph {
     /// If the name is does not exist, nothing happens.
     pub fn remove_node(
         &mut self,
-        name: impl Into<Cow<'static, str>>,
+        name: impl Into<RenderLabel>,
     ) -> Result<(), RenderGraphError> {
         let name = name.into();
         if let Some(id) = self.node_names.remove(&name) {
@@ -187,13 +195,76 @@ impl RenderGraph {
         Ok(())
     }
 
+    /// Returns an iterator over the nodes named in `group`, nested groups included.
+    pub fn iter_group<'a>(&'a self, group: &'a str) -> impl Iterator<Item = &'a NodeState> {
+        self.nodes
+            .values()
+            .filter(move |node| node.name.is_some_and(|name| name.is_in_group(group)))
+    }
+
+    /// Every group with nodes in it and their parent groups, sorted so that groups precede their nested groups.
+    pub fn groups(&self) -> BTreeSet<&'static str> {
+        let mut groups = BTreeSet::new();
+        for name in self.node_names.keys() {
+            let mut group = name.group();
+            while let Some(name) = group {
+                if !groups.insert(name) {
+                    break;
+                }
+                group = name.rsplit_once(GROUP_SEPARATOR).map(|(parent, _)| parent);
+            }
+        }
+        groups
+    }
+
+    /// Removes the nodes named in `group` with their edges, nested groups included.
+    pub fn remove_group(&mut self, group: &str) -> Result<(), RenderGraphError> {
+        let names = self
+            .node_names
+            .keys()
+            .filter(|name| name.is_in_group(group))
+            .copied()
+            .collect::<Vec<_>>();
+        for name in names {
+            self.remove_node(name)?;
+        }
+        self.disabled_groups.retain(|disabled| !is_in_group(disabled, group));
+        Ok(())
+    }
+
+    /// Skip the nodes named in `group` until it is enabled again, nodes added to the group later included.
+    ///
+    /// A node runs while none of its groups is disabled, enabling a group keeps the nodes of its disabled
+    /// parent or nested groups skipped. Nodes reading the outputs of a skipped node are skipped too.
+    pub fn set_group_enabled(&mut self, group: &str, enabled: bool) {
+        let group = group.trim_end_matches(GROUP_SEPARATOR);
+        if enabled {
+            self.disabled_groups.retain(|disabled| disabled != group);
+        } else if !self.is_group_disabled(group) {
+            self.disabled_groups.push(group.to_owned());
+        }
+
+        let disabled_groups = &self.disabled_groups;
+        for node in self.nodes.values_mut() {
+            if let Some(name) = node.name {
+                node.enabled = !disabled_groups.iter().any(|group| name.is_in_group(group));
+            }
+        }
+    }
+
+    /// Whether `group` itself was disabled with [`set_group_enabled`](Self::set_group_enabled).
+    pub fn is_group_disabled(&self, group: &str) -> bool {
+        let group = group.trim_end_matches(GROUP_SEPARATOR);
+        self.disabled_groups.iter().any(|disabled| disabled == group)
+    }
+
     /// Retrieves the [`NodeState`] referenced by the `label`.
     pub fn get_node_state(
         &self,
         label: impl Into<NodeLabel>,
     ) -> Result<&NodeState, RenderGraphError> {
         let label = label.into();
-        let node_id = self.get_node_id(&label)?;
+        let node_id = self.get_node_id(label)?;
         self.nodes
             .get(&node_id)
             .ok_or(RenderGraphError::InvalidNode(label))
@@ -205,7 +276,7 @@ impl RenderGraph {
         label: impl Into<NodeLabel>,
     ) -> Result<&mut NodeState, RenderGraphError> {
         let label = label.into();
-        let node_id = self.get_node_id(&label)?;
+        let node_id = self.get_node_id(label)?;
         self.nodes
             .get_mut(&node_id)
             .ok_or(RenderGraphError::InvalidNode(label))
@@ -216,10 +287,10 @@ impl RenderGraph {
         let label = label.into();
         match label {
             NodeLabel::Id(id) => Ok(id),
-            NodeLabel::Name(ref name) => self
+            NodeLabel::Name(name) => self
                 .node_names
-                .get(name)
-                .cloned()
+                .get(&name)
+                .copied()
                 .ok_or(RenderGraphError::InvalidNode(label)),
         }
     }
@@ -538,14 +609,14 @@ impl RenderGraph {
     pub fn iter_sub_graphs(&self) -> impl Iterator<Item = (&str, &RenderGraph)> {
         self.sub_graphs
             .iter()
-            .map(|(name, graph)| (name.as_ref(), graph))
+            .map(|(name, graph)| (name.as_str(), graph))
     }
 
     /// Returns an iterator over the sub graphs, that allows modifying each value.
     pub fn iter_sub_graphs_mut(&mut self) -> impl Iterator<Item = (&str, &mut RenderGraph)> {
         self.sub_graphs
             .iter_mut()
-            .map(|(name, graph)| (name.as_ref(), graph))
+            .map(|(name, graph)| (name.as_str(), graph))
     }
 
     /// Returns an iterator over a tuple of the input edges and the corresponding output nodes
@@ -582,24 +653,28 @@ impl RenderGraph {
 
     /// Adds the `sub_graph` with the `name` to the graph.
     /// If the name is already present replaces it instead.
-    pub fn add_sub_graph(&mut self, name: impl Into<Cow<'static, str>>, sub_graph: RenderGraph) {
-        self.sub_graphs.insert(name.into(), sub_graph);
+    pub fn add_sub_graph(&mut self, name: impl Into<RenderLabel>, sub_graph: RenderGraph) {
+        let name = name.into();
+        if let Some((existing, _)) = self.sub_graphs.get_key_value(&name) {
+            existing.assert_same_name(name.as_str());
+        }
+        self.sub_graphs.insert(name, sub_graph);
     }
 
     /// Removes the `sub_graph` with the `name` from the graph.
     /// If the name does not exist then nothing happens.
-    pub fn remove_sub_graph(&mut self, name: impl Into<Cow<'static, str>>) {
+    pub fn remove_sub_graph(&mut self, name: impl Into<RenderLabel>) {
         self.sub_graphs.remove(&name.into());
     }
 
     /// Retrieves the sub graph corresponding to the `name`.
-    pub fn get_sub_graph(&self, name: impl AsRef<str>) -> Option<&RenderGraph> {
-        self.sub_graphs.get(name.as_ref())
+    pub fn get_sub_graph(&self, name: impl Into<RenderLabel>) -> Option<&RenderGraph> {
+        self.sub_graphs.get(&name.into())
     }
 
     /// Retrieves the sub graph corresponding to the `name` mutably.
-    pub fn get_sub_graph_mut(&mut self, name: impl AsRef<str>) -> Option<&mut RenderGraph> {
-        self.sub_graphs.get_mut(name.as_ref())
+    pub fn get_sub_graph_mut(&mut self, name: impl Into<RenderLabel>) -> Option<&mut RenderGraph> {
+        self.sub_graphs.get_mut(&name.into())
     }
 
     /// Retrieves the sub graph corresponding to the `name`.
@@ -611,10 +686,11 @@ impl RenderGraph {
     /// # See also
     ///
     /// - [`get_sub_graph`](Self::get_sub_graph) for a fallible version.
-    pub fn sub_graph(&self, name: impl AsRef<str>) -> &RenderGraph {
+    pub fn sub_graph(&self, name: impl Into<RenderLabel>) -> &RenderGraph {
+        let name = name.into();
         self.sub_graphs
-            .get(name.as_ref())
-            .unwrap_or_else(|| panic!("Node {} not found in sub_graph", name.as_ref()))
+            .get(&name)
+            .unwrap_or_else(|| panic!("Node {name} not found in sub_graph"))
     }
 
     /// Retrieves the sub graph corresponding to the `name` mutably.
@@ -626,10 +702,11 @@ impl RenderGraph {
     /// # See also
     ///
     /// - [`get_sub_graph_mut`](Self::get_sub_graph_mut) for a fallible version.
-    pub fn sub_graph_mut(&mut self, name: impl AsRef<str>) -> &mut RenderGraph {
+    pub fn sub_graph_mut(&mut self, name: impl Into<RenderLabel>) -> &mut RenderGraph {
+        let name = name.into();
         self.sub_graphs
-            .get_mut(name.as_ref())
-            .unwrap_or_else(|| panic!("Node {} not found in sub_graph", name.as_ref()))
+            .get_mut(&name)
+            .unwrap_or_else(|| panic!("Node {name} not found in sub_graph"))
     }
 }
 
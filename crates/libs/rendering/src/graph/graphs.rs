@@ -0,0 +1,129 @@
+use bevy_ecs::change_detection::DetectChanges;
+use bevy_ecs::prelude::{Mut, Res, ResMut, Resource, World};
+use bevy_log::error;
+use avalanche_utils::PreHashedMap;
+use crate::prelude::Extract;
+use super::{RenderGraph, RenderLabel};
+
+/// Name of the main [`RenderGraph`], the resource run unless another top level graph is active.
+pub const MAIN_GRAPH: RenderLabel = RenderLabel::new("main");
+
+/// Alternative top level graphs, e.g. `forward`, `deferred` and `path_traced`, lives in the render world.
+///
+/// The [`active`](Self::active) graph runs each frame instead of the main [`RenderGraph`], so switching render
+/// paths doesn't require editing the graph. Views may run any of them too, the graph named by a camera
+/// is looked up in the sub graphs of the running graph first, then here.
+#[derive(Resource)]
+pub struct RenderGraphs {
+    graphs: PreHashedMap<RenderLabel, RenderGraph>,
+    active: RenderLabel,
+}
+
+impl Default for RenderGraphs {
+    fn default() -> Self {
+        Self {
+            graphs: PreHashedMap::default(),
+            active: MAIN_GRAPH,
+        }
+    }
+}
+
+impl RenderGraphs {
+    /// Add the top level `graph` with the `name`, replacing the graph already added with it.
+    pub fn add_graph(&mut self, name: impl Into<RenderLabel>, graph: RenderGraph) {
+        let name = name.into();
+        assert_ne!(name, MAIN_GRAPH, "the main render graph is the RenderGraph resource");
+        if let Some((existing, _)) = self.graphs.get_key_value(&name) {
+            existing.assert_same_name(name.as_str());
+        }
+        self.graphs.insert(name, graph);
+    }
+
+    /// Removes the graph with the `name`, the main graph runs again if it was active.
+    pub fn remove_graph(&mut self, name: impl Into<RenderLabel>) -> Option<RenderGraph> {
+        let name = name.into();
+        if self.active == name {
+            self.active = MAIN_GRAPH;
+        }
+        self.graphs.remove(&name)
+    }
+
+    pub fn get_graph(&self, name: impl Into<RenderLabel>) -> Option<&RenderGraph> {
+        self.graphs.get(&name.into())
+    }
+
+    pub fn get_graph_mut(&mut self, name: impl Into<RenderLabel>) -> Option<&mut RenderGraph> {
+        self.graphs.get_mut(&name.into())
+    }
+
+    /// Returns an iterator over the names and graphs, the main graph excluded.
+    pub fn iter_graphs(&self) -> impl Iterator<Item = (RenderLabel, &RenderGraph)> {
+        self.graphs.iter().map(|(name, graph)| (*name, graph))
+    }
+
+    /// The graph run each frame, [`MAIN_GRAPH`] by default.
+    #[inline]
+    pub fn active(&self) -> RenderLabel {
+        self.active
+    }
+
+    /// Run the graph with the `name` from the next frame on, [`MAIN_GRAPH`] for the main graph.
+    pub fn set_active(&mut self, name: impl Into<RenderLabel>) -> anyhow::Result<()> {
+        let name = name.into();
+        anyhow::ensure!(name == MAIN_GRAPH || self.graphs.contains_key(&name), "render graph {name} doesn't exist");
+        self.active = name;
+        Ok(())
+    }
+
+    /// The active graph, `None` while the main graph is.
+    pub fn active_graph(&self) -> Option<&RenderGraph> {
+        self.graphs.get(&self.active)
+    }
+
+    /// The sub graph `name` of `graph`, or else the top level graph `name`.
+    pub fn find<'a>(graphs: Option<&'a RenderGraphs>, graph: &'a RenderGraph, name: RenderLabel) -> Option<&'a RenderGraph> {
+        graph
+            .get_sub_graph(name)
+            .or_else(|| graphs.and_then(|graphs| graphs.get_graph(name)))
+    }
+
+    pub fn update(&mut self, world: &mut World) {
+        for graph in self.graphs.values_mut() {
+            graph.update(world);
+        }
+    }
+}
+
+/// The sub graph `name` of the main [`RenderGraph`], or else the top level graph `name` of the [`RenderGraphs`].
+pub(crate) fn find_graph_mut(world: &mut World, name: RenderLabel) -> Option<Mut<'_, RenderGraph>> {
+    let render_graph = world.get_resource::<RenderGraph>().expect(
+        "RenderGraph not found. Make sure you are using RenderGraphApp on the RenderApp",
+    );
+    if render_graph.get_sub_graph(name).is_some() {
+        let render_graph = world.resource_mut::<RenderGraph>();
+        return Some(render_graph.map_unchanged(|graph| graph.get_sub_graph_mut(name).unwrap()));
+    }
+
+    let graphs = world.get_resource_mut::<RenderGraphs>()?;
+    graphs.get_graph(name)?;
+    Some(graphs.map_unchanged(|graphs| graphs.get_graph_mut(name).unwrap()))
+}
+
+/// Selects the top level graph of the [`RenderGraphs`] run each frame, lives in the main world.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ActiveRenderGraph(pub RenderLabel);
+
+impl Default for ActiveRenderGraph {
+    fn default() -> Self {
+        Self(MAIN_GRAPH)
+    }
+}
+
+pub(crate) fn extract_active_render_graph(active: Extract<Res<ActiveRenderGraph>>, mut graphs: ResMut<RenderGraphs>) {
+    if !active.is_changed() || graphs.active() == active.0 {
+        return;
+    }
+    if let Err(err) = graphs.set_active(active.0) {
+        error!("Failed to switch the active render graph, {} keeps running: {err}", graphs.active());
+    }
+}
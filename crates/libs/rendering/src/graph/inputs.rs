@@ -0,0 +1,50 @@
+use bevy_ecs::prelude::{ResMut, Resource};
+use bevy_utils::HashMap;
+use crate::prelude::node_slot::{SlotLabel, SlotValue};
+use super::RenderGraph;
+
+/// Values of the input slots of the top level [`RenderGraph`] run this frame, lives in the render world.
+///
+/// Set them before [`RenderSet::Render`](crate::RenderSet::Render), e.g. the acquired swapchain image.
+/// They are cleared once the frame is rendered, a slot left without a value fails the graph run.
+#[derive(Resource, Default)]
+pub struct RenderGraphInputs {
+    values: HashMap<SlotLabel, SlotValue>,
+}
+
+impl RenderGraphInputs {
+    /// Set the value of the input slot referenced by the `label`, by name or index.
+    pub fn set(&mut self, label: impl Into<SlotLabel>, value: impl Into<SlotValue>) {
+        self.values.insert(label.into(), value.into());
+    }
+
+    pub fn get(&self, label: impl Into<SlotLabel>) -> Option<&SlotValue> {
+        self.values.get(&label.into())
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// The values in the order of the input slots of the `graph`, up to the first slot without one.
+    pub fn values(&self, graph: &RenderGraph) -> Vec<SlotValue> {
+        let Some(input_node) = graph.get_input_node() else {
+            return Vec::new();
+        };
+        input_node
+            .input_slots
+            .iter()
+            .enumerate()
+            .map_while(|(index, slot)| {
+                self.values
+                    .get(&SlotLabel::Name(slot.name.clone()))
+                    .or_else(|| self.values.get(&SlotLabel::Index(index)))
+                    .cloned()
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn clear_render_graph_inputs(mut inputs: ResMut<RenderGraphInputs>) {
+    inputs.clear();
+}
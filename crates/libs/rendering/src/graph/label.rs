@@ -0,0 +1,150 @@
+use std::borrow::Cow;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock};
+use bevy_utils::HashMap;
+use avalanche_utils::str_id;
+
+/// Interned name of a node or a sub graph of the [`RenderGraph`](super::RenderGraph).
+///
+/// Labels are compared and hashed by the [`str_id`] of their name, computed once when the label is created.
+/// [`new`](Self::new) is a `const fn`, so labels can be constants hashed at compile time:
+///
+/// ```ignore
+/// pub const BLOOM_NODE: RenderLabel = RenderLabel::new("bloom");
+/// ```
+///
+/// Strings convert into labels, owned ones are interned for the lifetime of the app.
+///
+/// Node names may be namespaced with [`GROUP_SEPARATOR`], e.g. `post/bloom/downsample_0` is in the groups
+/// `post/bloom` and `post`, so plugins can toggle or remove their nodes together.
+#[derive(Clone, Copy)]
+pub struct RenderLabel {
+    id: u64,
+    name: &'static str,
+}
+
+/// Separates the groups of a namespaced node name.
+pub const GROUP_SEPARATOR: char = '/';
+
+impl RenderLabel {
+    #[inline]
+    pub const fn new(name: &'static str) -> Self {
+        Self { id: str_id(name), name }
+    }
+
+    /// The label of a name only known at runtime, its string is leaked the first time it is interned.
+    pub fn intern(name: &str) -> Self {
+        static INTERNED: OnceLock<Mutex<HashMap<u64, &'static str>>> = OnceLock::new();
+
+        let id = str_id(name);
+        let interned = *INTERNED
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_insert_with(|| Box::leak(name.into()));
+        let label = Self { id, name: interned };
+        label.assert_same_name(name);
+        label
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The innermost group of the name, `None` outside groups.
+    pub fn group(&self) -> Option<&'static str> {
+        self.name.rsplit_once(GROUP_SEPARATOR).map(|(group, _)| group)
+    }
+
+    /// See [`is_in_group`].
+    #[inline]
+    pub fn is_in_group(&self, group: &str) -> bool {
+        is_in_group(self.name, group)
+    }
+
+    /// Panics if a label with the same id has another name, two names hashing to the same id can't be told apart.
+    pub(crate) fn assert_same_name(&self, name: &str) {
+        assert_eq!(self.name, name, "render labels {:?} and {name:?} have the same id", self.name);
+    }
+}
+
+/// Whether `name` is `group` itself or nested in it at any depth.
+pub fn is_in_group(name: &str, group: &str) -> bool {
+    let group = group.trim_end_matches(GROUP_SEPARATOR);
+    name.strip_prefix(group)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(GROUP_SEPARATOR))
+}
+
+impl PartialEq for RenderLabel {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for RenderLabel {}
+
+impl Hash for RenderLabel {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.id);
+    }
+}
+
+impl Debug for RenderLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.name, f)
+    }
+}
+
+impl Display for RenderLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+impl From<&RenderLabel> for RenderLabel {
+    #[inline]
+    fn from(label: &RenderLabel) -> Self {
+        *label
+    }
+}
+
+impl From<&'static str> for RenderLabel {
+    #[inline]
+    fn from(name: &'static str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for RenderLabel {
+    fn from(name: String) -> Self {
+        Self::intern(&name)
+    }
+}
+
+impl From<Cow<'static, str>> for RenderLabel {
+    fn from(name: Cow<'static, str>) -> Self {
+        match name {
+            Cow::Borrowed(name) => Self::new(name),
+            Cow::Owned(name) => Self::intern(&name),
+        }
+    }
+}
+
+impl From<&Cow<'static, str>> for RenderLabel {
+    fn from(name: &Cow<'static, str>) -> Self {
+        match name {
+            Cow::Borrowed(name) => Self::new(name),
+            Cow::Owned(name) => Self::intern(name),
+        }
+    }
+}
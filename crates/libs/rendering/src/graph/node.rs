@@ -1,15 +1,27 @@
-use std::borrow::Cow;
 use std::fmt::{Debug, Formatter};
-use bevy_ecs::world::World;
+use bevy_ecs::prelude::{Component, QueryState, Resource};
+use bevy_ecs::query::{QueryItem, ReadOnlyWorldQuery};
+use bevy_ecs::world::{FromWorld, World};
 use downcast_rs::{Downcast, impl_downcast};
 use avalanche_utils::define_atomic_id;
 use crate::extract::FrameContext;
 use crate::prelude::node_slot::{SlotInfo, SlotInfos};
-use crate::prelude::{NodeRunError, RenderGraphContext, RenderGraphError};
+use crate::prelude::{NodeRunError, RenderGraphContext, RenderGraphError, RenderLabel};
 use crate::prelude::edge::EdgeInfo;
 
 define_atomic_id!(NodeId);
 
+/// Queue the commands of a [`Node`] are submitted to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum QueueType {
+    #[default]
+    Graphics,
+    /// The dedicated compute queue, the node overlaps the graphics work it doesn't depend on.
+    ///
+    /// Falls back to the graphics queue on devices without a compute only queue family.
+    AsyncCompute,
+}
+
 pub trait Node: Downcast + Send + Sync + 'static {
     /// Describing the input slots of this node.
     ///
@@ -26,6 +38,22 @@ pub trait Node: Downcast + Send + Sync + 'static {
     /// Updating internal node state using current render [`World`] prior to the [`Node::run`] function;
     fn update(&mut self, _world: &mut World) {}
 
+    /// Queue the node records its commands for, read once when the node is added to the graph.
+    ///
+    /// [`QueueType::AsyncCompute`] nodes record into [`FrameContext::queue_command_buffer`]. The runner inserts
+    /// semaphores where edges cross queues, so the resources they share with graphics nodes only need
+    /// to be created concurrent on the [`unique_queue_family_indices`](avalanche_hlvk::Context::unique_queue_family_indices).
+    fn queue(&self) -> QueueType {
+        QueueType::Graphics
+    }
+
+    /// Whether [`Node::run`] is called this frame, checked with the inputs set.
+    ///
+    /// A node skipped this way sets no outputs, the nodes reading them are skipped too.
+    fn should_run(&self, _graph: &RenderGraphContext, _world: &World) -> bool {
+        true
+    }
+
     /// Run a pass.
     ///
     /// A **Pass** issues draw calls, updates output slots and
@@ -41,15 +69,21 @@ impl_downcast!(Node);
 
 /// A [`NodeLabel`] is used to reference a [`NodeState`] by either its name or [`NodeId`]
 /// inside the [`RenderGraph`](super::RenderGraph).
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum NodeLabel {
     Id(NodeId),
-    Name(Cow<'static, str>),
+    Name(RenderLabel),
 }
 
 impl From<&NodeLabel> for NodeLabel {
     fn from(value: &NodeLabel) -> Self {
-        value.clone()
+        *value
+    }
+}
+
+impl From<RenderLabel> for NodeLabel {
+    fn from(value: RenderLabel) -> Self {
+        NodeLabel::Name(value)
     }
 }
 
@@ -77,9 +111,12 @@ impl From<NodeId> for NodeLabel {
 /// The `input_slots` and `output_slots` are provided by the `node`.
 pub struct NodeState {
     pub id: NodeId,
-    pub name: Option<Cow<'static, str>>,
+    pub name: Option<RenderLabel>,
+    /// Cleared while a group of the node is disabled, the node is then skipped like a failed one
+    pub enabled: bool,
     /// The name of the type that implements [`Node`].
     pub type_name: &'static str,
+    pub queue: QueueType,
     pub node: Box<dyn Node>,
     pub input_slots: SlotInfos,
     pub output_slots: SlotInfos,
@@ -101,8 +138,10 @@ impl NodeState {
         NodeState {
             id,
             name: None,
+            enabled: true,
             input_slots: node.input().into(),
             output_slots: node.output().into(),
+            queue: node.queue(),
             node: Box::new(node),
             type_name: std::any::type_name::<T>(),
             edges: EdgeInfo {
@@ -164,3 +203,153 @@ impl Node for EmptyNode {
         Ok(())
     }
 }
+
+type RunCondition = Box<dyn Fn(&RenderGraphContext, &World) -> bool + Send + Sync>;
+
+/// Runs the wrapped [`Node`] only while its condition holds, e.g. to skip an effect cheaply when it's disabled.
+pub struct ConditionalNode<N: Node> {
+    node: N,
+    condition: RunCondition,
+}
+
+impl<N: Node> ConditionalNode<N> {
+    pub fn new(node: N, condition: impl Fn(&RenderGraphContext, &World) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            node,
+            condition: Box::new(condition),
+        }
+    }
+
+    /// Run while the resource `R` exists in the render world.
+    pub fn if_resource_exists<R: Resource>(node: N) -> Self {
+        Self::new(node, |_, world| world.contains_resource::<R>())
+    }
+
+    /// Run while the resource `R` of the render world matches the `predicate`.
+    pub fn if_resource<R: Resource>(node: N, predicate: impl Fn(&R) -> bool + Send + Sync + 'static) -> Self {
+        Self::new(node, move |_, world| world.get_resource::<R>().is_some_and(&predicate))
+    }
+
+    /// Run for the views with the component `C`.
+    pub fn if_view_has<C: Component>(node: N) -> Self {
+        Self::new(node, |graph, world| {
+            graph.get_view_entity().is_some_and(|view_entity| world.get::<C>(view_entity).is_some())
+        })
+    }
+
+    #[inline]
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+}
+
+impl<N: Node> Node for ConditionalNode<N> {
+    fn input(&self) -> Vec<SlotInfo> {
+        self.node.input()
+    }
+
+    fn output(&self) -> Vec<SlotInfo> {
+        self.node.output()
+    }
+
+    fn update(&mut self, world: &mut World) {
+        self.node.update(world);
+    }
+
+    fn should_run(&self, graph: &RenderGraphContext, world: &World) -> bool {
+        (self.condition)(graph, world) && self.node.should_run(graph, world)
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        self.node.run(graph, rendering_context, world)
+    }
+}
+
+/// [`Node`] running on the view entity of the graph, receiving the item of its [`ViewNode::ViewQuery`].
+///
+/// Add it to a graph wrapped in a [`ViewNodeRunner`].
+pub trait ViewNode {
+    /// The query that will be used on the view entity.
+    /// It is guaranteed to run on the view entity, so there's no need for a filter
+    type ViewQuery: ReadOnlyWorldQuery;
+
+    /// Updates internal node state using the current render [`World`] prior to the run method.
+    fn update(&mut self, _world: &mut World) {}
+
+    /// Same as [`Node::queue`].
+    fn queue(&self) -> QueueType {
+        QueueType::Graphics
+    }
+
+    /// Same as [`Node::run`], skipped if the view entity doesn't match [`ViewNode::ViewQuery`].
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        view_query: QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError>;
+}
+
+/// This [`Node`] can be used to run any [`ViewNode`].
+/// It will take care of updating the view query in `update()` and running the query in `run()`.
+pub struct ViewNodeRunner<N: ViewNode> {
+    view_query: QueryState<N::ViewQuery>,
+    node: N,
+}
+
+impl<N: ViewNode> ViewNodeRunner<N> {
+    pub fn new(node: N, world: &mut World) -> Self {
+        Self {
+            view_query: world.query_filtered(),
+            node,
+        }
+    }
+}
+
+impl<N: ViewNode + FromWorld> FromWorld for ViewNodeRunner<N> {
+    fn from_world(world: &mut World) -> Self {
+        Self::new(N::from_world(world), world)
+    }
+}
+
+impl<N> Node for ViewNodeRunner<N>
+    where
+        N: ViewNode + Send + Sync + 'static,
+{
+    fn update(&mut self, world: &mut World) {
+        self.view_query.update_archetypes(world);
+        self.node.update(world);
+    }
+
+    fn queue(&self) -> QueueType {
+        self.node.queue()
+    }
+
+    fn should_run(&self, graph: &RenderGraphContext, world: &World) -> bool {
+        graph
+            .get_view_entity()
+            .is_some_and(|view_entity| self.view_query.get_manual(world, view_entity).is_ok())
+    }
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(view_entity) = graph.get_view_entity() else {
+            return Ok(());
+        };
+        let Ok(view) = self.view_query.get_manual(world, view_entity) else {
+            return Ok(());
+        };
+
+        self.node.run(graph, rendering_context, view, world)
+    }
+}
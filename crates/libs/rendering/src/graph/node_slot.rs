@@ -1,7 +1,9 @@
 use core::fmt;
 use std::borrow::Cow;
-use std::fmt::Formatter;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
 use bevy_ecs::prelude::Entity;
+use downcast_rs::{DowncastSync, impl_downcast};
 use crate::resource::{Buffer, ImageView, Sampler};
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -10,6 +12,17 @@ pub enum SlotType {
     ImageView,
     Sampler,
     Entity,
+    /// A [`SlotValue::Custom`] holding a value of the type with this name.
+    ///
+    /// Only the name is kept for the errors to stay small, values are downcast with their type id.
+    Custom(&'static str),
+}
+
+impl SlotType {
+    /// The type of slots holding a custom `T`.
+    pub fn custom<T: AnySlot>() -> Self {
+        SlotType::Custom(std::any::type_name::<T>())
+    }
 }
 
 impl fmt::Display for SlotType {
@@ -20,12 +33,26 @@ impl fmt::Display for SlotType {
             ImageView => "ImageView",
             Sampler => "Sampler",
             Entity => "Entity",
+            Custom(type_name) => type_name,
         };
 
         f.write_str(s)
     }
 }
 
+/// A value of a type defined outside the crate passed between nodes, e.g. an acceleration structure.
+pub trait AnySlot: DowncastSync + Debug {
+    fn slot_type_name(&self) -> &'static str;
+}
+
+impl_downcast!(sync AnySlot);
+
+impl<T: Send + Sync + Debug + 'static> AnySlot for T {
+    fn slot_type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum SlotValue {
     /// A GPU-accessible [`Buffer`].
@@ -36,9 +63,24 @@ pub enum SlotValue {
     Sampler(Sampler),
     /// An entity in render ECS world.
     Entity(Entity),
+    /// Any other value, see [`SlotValue::custom`].
+    Custom(Arc<dyn AnySlot>),
 }
 
 impl SlotValue {
+    pub fn custom<T: AnySlot>(value: T) -> Self {
+        SlotValue::Custom(Arc::new(value))
+    }
+
+    /// The value of a [`SlotValue::Custom`] if it holds a `T`.
+    pub fn downcast_ref<T: AnySlot>(&self) -> Option<&T> {
+        match self {
+            // deref the arc, which also implements the trait
+            SlotValue::Custom(value) => (**value).downcast_ref::<T>(),
+            _ => None,
+        }
+    }
+
     pub fn slot_type(&self) -> SlotType {
         use SlotValue::*;
 
@@ -47,6 +89,7 @@ impl SlotValue {
             ImageView(_) => SlotType::ImageView,
             Sampler(_) => SlotType::Sampler,
             Entity(_) => SlotType::Entity,
+            Custom(value) => SlotType::Custom((**value).slot_type_name()),
         }
     }
 }
@@ -75,7 +118,7 @@ impl From<Entity> for SlotValue {
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub enum SlotLabel {
     Index(usize),
     Name(Cow<'static, str>),
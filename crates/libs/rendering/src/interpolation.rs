@@ -0,0 +1,90 @@
+use bevy_app::{App, FixedUpdate, Plugin};
+use bevy_ecs::prelude::{Component, IntoSystemConfigs, IntoSystemSetConfigs, Query, SystemSet};
+use bevy_math::Affine3A;
+use bevy_time::{Fixed, Time};
+use bevy_transform::prelude::{GlobalTransform, Transform};
+
+/// Systems of [`FixedUpdate`] related to transform interpolation.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FixedSimulationSet {
+    /// Remembers the transforms before the step is simulated.
+    SavePrevious,
+    /// Simulation systems moving [`TransformInterpolation`] entities should run in this set.
+    Simulate,
+}
+
+/// Renders the [`Transform`] of an entity moved in [`FixedUpdate`]
+/// blended between the two last fixed steps, according to the time left in the accumulator.
+///
+/// The rendered state lags up to one fixed step behind the simulation.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct TransformInterpolation {
+    previous: Option<Transform>,
+}
+
+impl TransformInterpolation {
+    /// Transform before the last fixed step, `None` until the first step ran.
+    #[inline]
+    pub fn previous(&self) -> Option<&Transform> {
+        self.previous.as_ref()
+    }
+
+    /// Whether the last fixed step moved the entity.
+    pub fn is_moving(&self, transform: &Transform) -> bool {
+        matches!(self.previous, Some(previous) if previous != *transform)
+    }
+
+    /// Blend `global` between the previous and the current fixed step,
+    /// `transform` being the local transform `global` was propagated from.
+    pub fn interpolate(&self, global: &GlobalTransform, transform: &Transform, alpha: f32) -> GlobalTransform {
+        let Some(previous) = self.previous.filter(|_| self.is_moving(transform)) else {
+            return *global;
+        };
+
+        let blended = Transform {
+            translation: previous.translation.lerp(transform.translation, alpha),
+            rotation: previous.rotation.slerp(transform.rotation, alpha),
+            scale: previous.scale.lerp(transform.scale, alpha),
+        };
+        let parent: Affine3A = global.affine() * transform.compute_affine().inverse();
+        GlobalTransform::from(parent * blended.compute_affine())
+    }
+}
+
+/// How far the accumulator went into the next fixed step, in `[0, 1]`.
+pub fn interpolation_alpha(time: Option<&Time<Fixed>>) -> f32 {
+    time.map_or(1.0, |time| time.overstep_percentage().clamp(0.0, 1.0))
+}
+
+/// Interpolated world transform of an entity, see [`TransformInterpolation::interpolate`].
+pub fn interpolated_transform(
+    global: &GlobalTransform,
+    interpolation: Option<(&Transform, &TransformInterpolation)>,
+    alpha: f32,
+) -> GlobalTransform {
+    match interpolation {
+        Some((transform, interpolation)) => interpolation.interpolate(global, transform, alpha),
+        None => *global,
+    }
+}
+
+pub struct TransformInterpolationPlugin;
+
+impl Plugin for TransformInterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            FixedUpdate,
+            (FixedSimulationSet::SavePrevious, FixedSimulationSet::Simulate).chain(),
+        )
+        .add_systems(
+            FixedUpdate,
+            save_previous_transforms.in_set(FixedSimulationSet::SavePrevious),
+        );
+    }
+}
+
+fn save_previous_transforms(mut query: Query<(&Transform, &mut TransformInterpolation)>) {
+    for (transform, mut interpolation) in query.iter_mut() {
+        interpolation.previous = Some(*transform);
+    }
+}
@@ -2,21 +2,108 @@ extern crate core;
 
 use std::ops::{Deref, DerefMut};
 use bevy_app::{App, AppLabel, Plugin, SubApp};
-use bevy_ecs::prelude::{IntoSystemConfigs, IntoSystemSetConfigs, Mut, Resource, Schedule, Schedules, SystemSet};
+use bevy_ecs::prelude::{IntoSystemConfigs, IntoSystemSetConfigs, Mut, resource_exists, Resource, Schedule, Schedules, SystemSet};
 use bevy_ecs::schedule::ScheduleLabel;
 use bevy_ecs::world::World;
-use crate::extract::{extract_rendering_context, release_referenced_rendering_context};
+use crate::camera::CameraPlugin;
+use crate::globals::GlobalsPlugin;
+use crate::debug_print::ShaderDebugPrintPlugin;
+use crate::extract::{extract_rendering_context, FrameContext, release_referenced_rendering_context};
+use crate::path_tracing::PathTracingPlugin;
+use crate::deferred::DeferredPlugin;
+use crate::environment::EnvironmentMapPlugin;
 use crate::prelude::window::WindowRenderPlugin;
-use crate::runner::system::render_system;
-
-mod extract;
+use crate::raytracing::RayTracingPlugin;
+use crate::frame_pacing::FramePacingPlugin;
+use crate::interpolation::TransformInterpolationPlugin;
+use crate::mesh::MeshPlugin;
+use crate::lod::LodPlugin;
+use crate::spatial::SpatialPlugin;
+use crate::picking::PickingPlugin;
+use crate::streaming::StreamingPlugin;
+use crate::virtual_texture::VirtualTexturePlugin;
+use crate::render_asset::{release_render_asset_staging_buffers, RenderAssetStagingBuffers};
+use crate::texture::TexturePlugin;
+use crate::render_command::RenderCommandPlugin;
+use crate::runner::system::{render_system, time_system};
+use crate::shader::ShaderDirectory;
+use crate::statistics::RenderStatistics;
+use crate::budget::{FrameBudgetPlugin, RenderSetTimings};
+use crate::error_policy::{DisabledRenderNodes, RenderGraphErrorPolicy, RenderNodeFailed, RenderNodeFailures, send_render_node_failures};
+use crate::view::ViewPlugin;
+use crate::profiler::GpuProfilerPlugin;
+use crate::render_scale::RenderScalePlugin;
+use crate::upscaling::UpscalingPlugin;
+use crate::transparent::TransparentPlugin;
+use crate::debug_view::DebugViewPlugin;
+use crate::particles::ParticlePlugin;
+use crate::gizmos::GizmoPlugin;
+use crate::terrain::TerrainPlugin;
+use crate::sprite::SpritePlugin;
+use crate::ui::UiLayoutPlugin;
+use crate::render_target::RenderTargetPlugin;
+use crate::tonemapping::TonemappingPlugin;
+use crate::auto_exposure::AutoExposurePlugin;
+use crate::depth_pyramid::DepthPyramidPlugin;
+use crate::skinning::SkinningPlugin;
+
+pub mod extract;
 pub mod context;
+pub mod command_pool;
 pub mod prelude;
 pub mod present;
 pub mod mock;
 pub mod extra;
 pub mod graph;
 pub mod resource;
+pub mod raytracing;
+pub mod mesh;
+pub mod texture;
+pub mod render_asset;
+pub mod shader;
+pub mod color;
+pub mod camera;
+pub mod view;
+pub mod path_tracing;
+pub mod deferred;
+pub mod environment;
+pub mod pipelined_rendering;
+pub mod frame_pacing;
+pub mod interpolation;
+pub mod shutdown;
+pub mod backend;
+pub mod profiler;
+pub mod render_scale;
+pub mod upscaling;
+pub mod render_phase;
+pub mod specialized_pipeline;
+pub mod transparent;
+pub mod debug_view;
+pub mod particles;
+pub mod gizmos;
+pub mod terrain;
+pub mod sprite;
+pub mod ui;
+pub mod render_target;
+pub mod tonemapping;
+pub mod auto_exposure;
+pub mod depth_pyramid;
+pub mod skinning;
+pub mod lod;
+pub mod spatial;
+pub mod picking;
+pub mod statistics;
+pub mod budget;
+pub mod render_command;
+pub mod error_policy;
+pub mod transient_descriptors;
+pub mod readback;
+pub mod render_test;
+pub mod parallel;
+pub mod streaming;
+pub mod globals;
+pub mod debug_print;
+pub mod virtual_texture;
 pub(crate) mod runner;
 
 /// Cached command pool when setup rendering system.
@@ -97,6 +184,11 @@ impl Render {
         );
 
         schedule.configure_sets((ExtractCommands, PrepareAssets, Prepare).chain());
+        // paused while there is no frame to record, e.g. when the app is suspended
+        schedule.configure_sets(
+            (ManageViews, Queue, PhaseSort, Prepare, PrepareAssets, Render)
+                .run_if(resource_exists::<FrameContext>()),
+        );
         schedule.configure_sets(QueueMeshes.in_set(Queue)); //.after(prepare_assets::<Mesh>));
         schedule.configure_sets(
             (PrepareResources, PrepareResourcesFlush, PrepareBindGroups)
@@ -146,17 +238,55 @@ impl Plugin for RenderingPipelinePlugin {
 
         app.add_plugins((
             WindowRenderPlugin,
+            RayTracingPlugin,
+            CameraPlugin,
+            (ViewPlugin, GlobalsPlugin, ShaderDebugPrintPlugin),
+            // render paths and the passes added to their graphs
+            (
+                PathTracingPlugin,
+                DeferredPlugin,
+                TransparentPlugin,
+                DebugViewPlugin,
+                ParticlePlugin,
+                GizmoPlugin,
+                TerrainPlugin,
+                SpritePlugin,
+                UiLayoutPlugin,
+                DepthPyramidPlugin,
+            ),
+            FramePacingPlugin,
+            TransformInterpolationPlugin,
+            // meshes with their levels of detail, skins, bounds and picking
+            (MeshPlugin, LodPlugin, SkinningPlugin, SpatialPlugin, PickingPlugin),
+            (TexturePlugin, StreamingPlugin, VirtualTexturePlugin),
+            RenderCommandPlugin,
+            EnvironmentMapPlugin,
+            GpuProfilerPlugin,
+            RenderScalePlugin,
+            FrameBudgetPlugin,
+            // passes run on the final image of every view
+            (UpscalingPlugin, AutoExposurePlugin, TonemappingPlugin, RenderTargetPlugin),
         ));
     }
 
     fn ready(&self, _app: &App) -> bool {
         true
     }
+
+    fn finish(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            graph::apply_pending_edges(&mut render_app.world, true);
+        }
+    }
 }
 
 /// SAFETY: must be called in main thread
 unsafe fn initialize_render_app(app: &mut App) {
-    app.init_resource::<ScratchMainWorld>();
+    app.init_resource::<ScratchMainWorld>()
+        .init_resource::<graph::RenderGraphEdits>()
+        .init_resource::<graph::ActiveRenderGraph>()
+        .add_event::<RenderNodeFailed>()
+        .add_event::<readback::ReadbackComplete>();
 
     let mut render_app = App::empty();
     render_app.main_schedule_label = Render.intern();
@@ -168,20 +298,43 @@ unsafe fn initialize_render_app(app: &mut App) {
         .add_schedule(extract_schedule)
         .add_schedule(Render::base_schedule())
         .init_resource::<graph::RenderGraph>()
+        .init_resource::<graph::RenderGraphs>()
+        .init_resource::<graph::ExtractedRenderGraphEdits>()
+        .init_resource::<graph::RenderGraphInputs>()
+        .init_resource::<ShaderDirectory>()
+        .init_resource::<RenderAssetStagingBuffers>()
+        .init_resource::<RenderStatistics>()
+        .init_resource::<RenderGraphErrorPolicy>()
+        .init_resource::<DisabledRenderNodes>()
+        .init_resource::<RenderNodeFailures>()
+        .init_resource::<transient_descriptors::TransientDescriptorAllocator>()
+        .init_resource::<readback::Readback>()
         .add_systems(
             ExtractSchedule, (
                 extract_rendering_context,
+                graph::extract_render_graph_edits,
+                graph::extract_active_render_graph,
+                send_render_node_failures,
+                readback::send_readback_results,
             ),
         )
         .add_systems(
             Render, (
                 apply_extract_commands.in_set(RenderSet::ExtractCommands),
+                graph::apply_render_graph_edits.in_set(RenderSet::ExtractCommands).after(apply_extract_commands),
+                transient_descriptors::reset_transient_descriptor_pools
+                    .in_set(RenderSet::ExtractCommands)
+                    .run_if(resource_exists::<FrameContext>()),
                 (
                     render_system,
                 ).in_set(RenderSet::Render),
                 (
                     World::clear_entities,
                     release_referenced_rendering_context,
+                    time_system.after(release_referenced_rendering_context),
+                    graph::clear_render_graph_inputs,
+                    release_render_asset_staging_buffers.after(release_referenced_rendering_context),
+                    readback::poll_readbacks.after(release_referenced_rendering_context),
                 ).in_set(RenderSet::Cleanup),
             )
         );
@@ -223,7 +376,11 @@ fn tick(main_world: &mut World, render_app: &mut App) {
     let inserted_world = std::mem::replace(main_world, scratch_world.0);
     render_app.world.insert_resource(MainWorld(inserted_world));
 
+    let extract_start = std::time::Instant::now();
     render_app.world.run_schedule(ExtractSchedule);
+    if let Some(mut timings) = render_app.world.get_resource_mut::<RenderSetTimings>() {
+        timings.record_extract(extract_start);
+    }
 
     let inserted_world = render_app.world.remove_resource::<MainWorld>().unwrap();
     let scratch_world = std::mem::replace(main_world, inserted_world.0);
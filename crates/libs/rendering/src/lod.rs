@@ -0,0 +1,255 @@
+use bevy_app::{App, Plugin, PostUpdate};
+use bevy_ecs::prelude::{Commands, Component, Entity, IntoSystemConfigs, Query};
+use bevy_math::Vec3;
+use bevy_transform::prelude::GlobalTransform;
+use bevy_transform::TransformSystem;
+use bevy_utils::EntityHashMap;
+use crate::{ExtractSchedule, RenderApp};
+use crate::camera::{Camera, PerspectiveProjection};
+use crate::mesh::MeshBuffers;
+use crate::prelude::Extract;
+use crate::raytracing::{BlasHandle, RayTracingInstance};
+use avalanche_utils::math::Sphere;
+
+/// A level of detail of a [`Lod`].
+#[derive(Clone, Debug)]
+pub struct LodLevel {
+    pub mesh: MeshBuffers,
+    /// Acceleration structure built from [`mesh`](Self::mesh)
+    pub blas: BlasHandle,
+    /// The level is used while the bounds cover at least this fraction of the view height
+    pub min_screen_coverage: f32,
+}
+
+/// Switches the [`MeshBuffers`] and the [`RayTracingInstance`] BLAS of an entity between levels of detail
+/// by the screen coverage of its bounding sphere.
+///
+/// Every view rasterizes the level it needs, kept in its [`ViewLods`]. The views share the ray traced
+/// scene so the entity itself holds the most detailed one. Only cameras with a [`PerspectiveProjection`]
+/// select levels, an entity no such camera looks at keeps its level.
+#[derive(Component, Clone, Debug)]
+pub struct Lod {
+    /// From the most to the least detailed, with decreasing [`min_screen_coverage`](LodLevel::min_screen_coverage)
+    pub levels: Vec<LodLevel>,
+    /// Radius of the bounding sphere around the entity origin, in local space
+    pub radius: f32,
+    /// Fraction the coverage has to cross a switch threshold by before the level changes, avoids popping
+    pub hysteresis: f32,
+    active: Option<usize>,
+}
+
+impl Lod {
+    pub fn new(levels: Vec<LodLevel>, radius: f32) -> Self {
+        Self {
+            levels,
+            radius,
+            hysteresis: 0.1,
+            active: None,
+        }
+    }
+
+    /// Index of the level in the [`MeshBuffers`] and the BLAS of the entity, `None` until the first selection.
+    #[inline]
+    pub fn active(&self) -> Option<usize> {
+        self.active
+    }
+
+    /// Level for a `coverage` of the view height, moving away from `current` only past the hysteresis band.
+    pub fn select(&self, current: Option<usize>, coverage: f32) -> usize {
+        select_level(
+            self.levels.len(),
+            |level| self.levels[level].min_screen_coverage,
+            self.hysteresis,
+            current,
+            coverage,
+        )
+    }
+}
+
+/// [`Lod::select`] over `count` levels with the `min_screen_coverage` of each.
+fn select_level(
+    count: usize,
+    min_screen_coverage: impl Fn(usize) -> f32,
+    hysteresis: f32,
+    current: Option<usize>,
+    coverage: f32,
+) -> usize {
+    let level_above = |scale: f32| {
+        (0..count)
+            .position(|level| coverage >= min_screen_coverage(level) * scale)
+            .unwrap_or(count.saturating_sub(1))
+    };
+    let Some(current) = current else {
+        return level_above(1.0);
+    };
+
+    let finer = level_above(1.0 + hysteresis);
+    if finer < current {
+        return finer;
+    }
+    level_above(1.0 - hysteresis).max(current)
+}
+
+/// Fraction of the view height a sphere covers seen from `viewpoint` through a vertical field of view, above 1 up close.
+pub fn screen_coverage(sphere: &Sphere, viewpoint: Vec3, fov: f32) -> f32 {
+    let distance = viewpoint.distance(sphere.center);
+    if distance <= sphere.radius {
+        return f32::INFINITY;
+    }
+    sphere.radius / (distance * (fov * 0.5).tan())
+}
+
+/// Level of detail a camera rasterizes every [`Lod`] entity with, inserted by [`select_lods`].
+#[derive(Component, Debug, Default)]
+pub struct ViewLods {
+    pub levels: EntityHashMap<Entity, usize>,
+}
+
+/// Meshes of the [`ViewLods`] of a view in the render world, for the entities whose level differs
+/// from their [`MeshBuffers`].
+#[derive(Component, Default)]
+pub struct ViewLodMeshes(EntityHashMap<Entity, MeshBuffers>);
+
+impl ViewLodMeshes {
+    /// Mesh the view draws an entity with, `shared` is the [`MeshBuffers`] of the entity.
+    #[inline]
+    pub fn mesh<'a>(&'a self, entity: Entity, shared: &'a MeshBuffers) -> &'a MeshBuffers {
+        self.0.get(&entity).unwrap_or(shared)
+    }
+}
+
+/// Selects the level of detail of every [`Lod`] entity from the active cameras.
+pub struct LodPlugin;
+
+impl Plugin for LodPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostUpdate, select_lods.after(TransformSystem::TransformPropagate));
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.add_systems(ExtractSchedule, extract_view_lods);
+        }
+    }
+}
+
+type LodCameraQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static Camera, &'static PerspectiveProjection, &'static GlobalTransform, Option<&'static mut ViewLods>)>;
+type LodEntityQuery<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static mut Lod, &'static GlobalTransform, Option<&'static mut MeshBuffers>, Option<&'static mut RayTracingInstance>),
+>;
+
+pub fn select_lods(mut commands: Commands, mut cameras: LodCameraQuery, mut entities: LodEntityQuery) {
+    let mut finest = EntityHashMap::<Entity, usize>::default();
+    for (view, camera, projection, camera_transform, view_lods) in cameras.iter_mut() {
+        if !camera.is_active {
+            continue;
+        }
+
+        let previous = view_lods.as_ref().map(|view_lods| &view_lods.levels);
+        let mut levels = EntityHashMap::default();
+        for (entity, lod, transform, _, _) in entities.iter() {
+            if lod.levels.is_empty() {
+                continue;
+            }
+            let sphere = Sphere::new(Vec3::ZERO, lod.radius).transformed(&transform.compute_matrix());
+            let coverage = screen_coverage(&sphere, camera_transform.translation(), projection.fov);
+            let level = lod.select(previous.and_then(|levels| levels.get(&entity).copied()), coverage);
+            levels.insert(entity, level);
+            finest
+                .entry(entity)
+                .and_modify(|finest| *finest = (*finest).min(level))
+                .or_insert(level);
+        }
+
+        match view_lods {
+            Some(mut view_lods) => view_lods.levels = levels,
+            None => {
+                commands.entity(view).insert(ViewLods { levels });
+            }
+        }
+    }
+
+    for (entity, mut lod, _, mesh, instance) in entities.iter_mut() {
+        let Some(&level) = finest.get(&entity) else {
+            continue;
+        };
+        if lod.active == Some(level) {
+            continue;
+        }
+
+        // only write on a switch, the change rebuilds the TLAS and the scene tables
+        lod.active = Some(level);
+        let level = &lod.levels[level];
+        if let Some(mut mesh) = mesh {
+            *mesh = level.mesh.clone();
+        }
+        if let Some(mut instance) = instance {
+            instance.blas = level.blas;
+        }
+    }
+}
+
+fn extract_view_lods(mut commands: Commands, views: Extract<Query<(Entity, &ViewLods)>>, lods: Extract<Query<&Lod>>) {
+    for (view, view_lods) in views.iter() {
+        let meshes = view_lods
+            .levels
+            .iter()
+            .filter_map(|(&entity, &level)| {
+                let lod = lods.get(entity).ok()?;
+                if lod.active == Some(level) {
+                    return None;
+                }
+                Some((entity, lod.levels.get(level)?.mesh.clone()))
+            })
+            .collect();
+        commands.get_or_spawn(view).insert(ViewLodMeshes(meshes));
+    }
+}
+
+#[test]
+fn test_select_without_current_level() {
+    let thresholds = [0.5, 0.2, 0.05];
+    let select = |coverage| select_level(3, |level| thresholds[level], 0.1, None, coverage);
+
+    assert_eq!(select(f32::INFINITY), 0);
+    assert_eq!(select(0.5), 0);
+    assert_eq!(select(0.3), 1);
+    assert_eq!(select(0.1), 2);
+}
+
+#[test]
+fn test_select_below_last_threshold() {
+    let thresholds = [0.5, 0.2, 0.05];
+
+    assert_eq!(select_level(3, |level| thresholds[level], 0.1, None, 0.01), 2);
+    assert_eq!(select_level(3, |level| thresholds[level], 0.1, Some(0), 0.0), 2);
+    assert_eq!(select_level(0, |_| unreachable!(), 0.1, None, 0.01), 0);
+}
+
+#[test]
+fn test_select_hysteresis() {
+    let thresholds = [0.5, 0.2, 0.05];
+    let select = |current, coverage| select_level(3, |level| thresholds[level], 0.1, Some(current), coverage);
+
+    // coarser only once the coverage drops below the band under the threshold
+    assert_eq!(select(0, 0.46), 0);
+    assert_eq!(select(0, 0.44), 1);
+    // finer only once the coverage rises above the band over the threshold
+    assert_eq!(select(1, 0.54), 1);
+    assert_eq!(select(1, 0.56), 0);
+    // inside the band the current level is kept either way
+    assert_eq!(select(1, 0.19), 1);
+    assert_eq!(select(2, 0.21), 2);
+}
+
+#[test]
+fn test_screen_coverage() {
+    let sphere = Sphere::new(Vec3::new(0.0, 0.0, -10.0), 1.0);
+
+    assert_eq!(screen_coverage(&sphere, Vec3::new(0.0, 0.0, -10.5), 1.0), f32::INFINITY);
+    assert_eq!(screen_coverage(&sphere, Vec3::new(0.0, 0.0, -9.0), 1.0), f32::INFINITY);
+    // a 90 degree field of view spans the distance twice
+    let coverage = screen_coverage(&sphere, Vec3::ZERO, std::f32::consts::FRAC_PI_2);
+    assert!((coverage - 0.1).abs() < 1e-6);
+}
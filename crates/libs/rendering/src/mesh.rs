@@ -0,0 +1,188 @@
+mod obj;
+mod pipeline_key;
+mod vertex_layout;
+
+pub use obj::*;
+pub use pipeline_key::*;
+pub use vertex_layout::*;
+
+use ash::vk;
+use anyhow::ensure;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::Component;
+use bevy_math::Vec3;
+use gpu_allocator::MemoryLocation;
+use avalanche_asset::{Asset, AssetApp};
+use avalanche_scene::SceneApp;
+use crate::prelude::Buffer;
+use crate::raytracing::{BlasBuildRequest, BlasGeometry};
+use crate::render_asset::{RenderAsset, RenderAssetContext, RenderAssetPlugin};
+use crate::spatial::Aabb;
+
+/// Vertex layout of the buffers referenced by [`MeshBuffers`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+/// Joint influences of a vertex of a skinned [`Mesh`], in the second vertex buffer of [`MeshBuffers`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeshSkinVertex {
+    /// Indices into the joints of the [`Skeleton`](crate::skinning::Skeleton) of the mesh
+    pub joints: [u16; 4],
+    /// Weights of `joints`, summing to 1
+    pub weights: [f32; 4],
+}
+
+/// Buffer usages required to use a buffer in [`MeshBuffers`].
+pub const MESH_BUFFER_USAGE: vk::BufferUsageFlags = vk::BufferUsageFlags::from_raw(
+    vk::BufferUsageFlags::VERTEX_BUFFER.as_raw()
+        | vk::BufferUsageFlags::INDEX_BUFFER.as_raw()
+        | vk::BufferUsageFlags::STORAGE_BUFFER.as_raw()
+        | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS.as_raw()
+        | vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR.as_raw(),
+);
+
+/// GPU geometry of an entity, vertices are [`MeshVertex`] and indices are `u32`.
+///
+/// Buffers must be created with [`MESH_BUFFER_USAGE`].
+#[derive(Component, Clone, Debug)]
+pub struct MeshBuffers {
+    pub vertex_buffer: Buffer,
+    pub vertex_count: u32,
+    pub index_buffer: Option<Buffer>,
+    pub index_count: u32,
+    /// [`MeshSkinVertex`]es of skinned meshes
+    pub skin_buffer: Option<Buffer>,
+}
+
+impl MeshBuffers {
+    #[inline]
+    pub fn triangle_count(&self) -> u32 {
+        match self.index_buffer {
+            Some(_) => self.index_count / 3,
+            None => self.vertex_count / 3,
+        }
+    }
+
+    /// [`MeshVertex`] attributes, and the joint attributes of skinned meshes.
+    #[inline]
+    pub fn vertex_layout(&self) -> MeshVertexLayout {
+        match self.skin_buffer {
+            Some(_) => MeshVertexLayout::new(MeshAttributes::VERTEX | MeshAttributes::SKIN),
+            None => MeshVertexLayout::default(),
+        }
+    }
+
+    pub fn blas_geometry(&self, opaque: bool) -> BlasGeometry {
+        BlasGeometry {
+            vertex_buffer: self.vertex_buffer.clone(),
+            vertex_format: vk::Format::R32G32B32_SFLOAT,
+            vertex_stride: std::mem::size_of::<MeshVertex>() as _,
+            vertex_count: self.vertex_count,
+            index_buffer: self.index_buffer.clone(),
+            index_type: vk::IndexType::UINT32,
+            primitive_count: self.triangle_count(),
+            opaque,
+        }
+    }
+
+    /// A build request for an opaque BLAS of this mesh.
+    pub fn blas_request(&self) -> BlasBuildRequest {
+        BlasBuildRequest::new(vec![self.blas_geometry(true)])
+    }
+}
+
+/// Geometry asset on the CPU, uploaded into [`MeshBuffers`] stored in [`RenderAssets<Mesh>`](crate::render_asset::RenderAssets).
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<MeshVertex>,
+    /// Triangle list, vertices are used in order when `None`
+    pub indices: Option<Vec<u32>>,
+    /// One per vertex for meshes deformed by a [`Skeleton`](crate::skinning::Skeleton)
+    pub skin: Option<Vec<MeshSkinVertex>>,
+}
+
+impl Asset for Mesh {}
+
+impl Mesh {
+    /// Local bounds of the vertices, `None` without vertices.
+    pub fn compute_aabb(&self) -> Option<Aabb> {
+        Aabb::from_points(self.vertices.iter().map(|vertex| Vec3::from(vertex.position)))
+    }
+}
+
+impl RenderAsset for Mesh {
+    type ExtractedAsset = Mesh;
+    type PreparedAsset = MeshBuffers;
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(mesh: Self::ExtractedAsset, context: &mut RenderAssetContext) -> anyhow::Result<Self::PreparedAsset> {
+        ensure!(!mesh.vertices.is_empty(), "Mesh has no vertices");
+        ensure!(
+            !mesh.skin.as_ref().is_some_and(|skin| skin.len() != mesh.vertices.len()),
+            "Mesh skin doesn't match its {} vertices", mesh.vertices.len(),
+        );
+
+        let context = context.context();
+        let vertex_buffer = context.create_buffer(
+            MESH_BUFFER_USAGE,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of_val(mesh.vertices.as_slice()) as _,
+        )?;
+        vertex_buffer.copy_data_to_buffer(&mesh.vertices)?;
+
+        let index_buffer = match mesh.indices.as_ref().filter(|indices| !indices.is_empty()) {
+            Some(indices) => {
+                let index_buffer = context.create_buffer(
+                    MESH_BUFFER_USAGE,
+                    MemoryLocation::CpuToGpu,
+                    std::mem::size_of_val(indices.as_slice()) as _,
+                )?;
+                index_buffer.copy_data_to_buffer(indices)?;
+                Some(index_buffer.into())
+            }
+            None => None,
+        };
+
+        let skin_buffer = match &mesh.skin {
+            Some(skin) => {
+                let skin_buffer = context.create_buffer(
+                    vk::BufferUsageFlags::VERTEX_BUFFER,
+                    MemoryLocation::CpuToGpu,
+                    std::mem::size_of_val(skin.as_slice()) as _,
+                )?;
+                skin_buffer.copy_data_to_buffer(skin)?;
+                Some(skin_buffer.into())
+            }
+            None => None,
+        };
+
+        Ok(MeshBuffers {
+            vertex_buffer: vertex_buffer.into(),
+            vertex_count: mesh.vertices.len() as _,
+            index_count: mesh.indices.as_ref().map_or(0, |indices| indices.len() as _),
+            index_buffer,
+            skin_buffer,
+        })
+    }
+}
+
+/// Registers the [`Mesh`] asset with its loaders.
+pub struct MeshPlugin;
+
+impl Plugin for MeshPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Mesh>()
+            .register_asset_loader(ObjLoader)
+            .register_scene_handle::<Mesh>()
+            .add_plugins(RenderAssetPlugin::<Mesh>::default());
+    }
+}
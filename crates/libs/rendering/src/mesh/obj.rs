@@ -0,0 +1,128 @@
+use std::path::Path;
+use anyhow::{anyhow, bail, Context};
+use bevy_math::Vec3;
+use bevy_utils::HashMap;
+use avalanche_asset::AssetLoader;
+use crate::mesh::{Mesh, MeshVertex};
+
+/// Loads the polygons of Wavefront `.obj` files as a single indexed [`Mesh`].
+///
+/// Faces are triangulated as fans, materials and groups are ignored.
+/// Flat normals are generated for faces without normals.
+pub struct ObjLoader;
+
+impl AssetLoader for ObjLoader {
+    type Asset = Mesh;
+
+    fn extensions(&self) -> &[&str] {
+        &["obj"]
+    }
+
+    fn load(&self, bytes: &[u8], _path: &Path) -> anyhow::Result<Mesh> {
+        let source = std::str::from_utf8(bytes).context("OBJ file is not valid UTF-8")?;
+        parse_obj(source)
+    }
+}
+
+/// Indices of a face corner into the position, uv and normal lists
+type ObjCorner = (usize, Option<usize>, Option<usize>);
+
+fn parse_obj(source: &str) -> anyhow::Result<Mesh> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut corner_indices = HashMap::<ObjCorner, u32>::default();
+
+    for (line_number, line) in source.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let result = match keyword {
+            "v" => parse_floats::<3>(tokens).map(|[x, y, z]| positions.push([x, y, z])),
+            "vt" => parse_floats::<2>(tokens).map(|[u, v]| uvs.push([u, 1.0 - v])),
+            "vn" => parse_floats::<3>(tokens).map(|[x, y, z]| normals.push([x, y, z])),
+            "f" => {
+                let corners = tokens
+                    .map(|token| parse_corner(token, positions.len(), uvs.len(), normals.len()))
+                    .collect::<anyhow::Result<Vec<_>>>();
+                corners.and_then(|corners| {
+                    if corners.len() < 3 {
+                        bail!("face with less than 3 vertices");
+                    }
+                    let face_normal = flat_normal(&positions, &corners);
+                    let mut face_indices = Vec::with_capacity(corners.len());
+                    for corner in corners {
+                        let (position, uv, normal) = corner;
+                        let vertex = MeshVertex {
+                            position: positions[position],
+                            normal: normal.map_or(face_normal, |normal| normals[normal]),
+                            uv: uv.map_or([0.0; 2], |uv| uvs[uv]),
+                        };
+                        // corners with generated normals can't be shared with other faces
+                        let index = match normal {
+                            Some(_) => *corner_indices.entry(corner).or_insert_with(|| {
+                                vertices.push(vertex);
+                                vertices.len() as u32 - 1
+                            }),
+                            None => {
+                                vertices.push(vertex);
+                                vertices.len() as u32 - 1
+                            }
+                        };
+                        face_indices.push(index);
+                    }
+                    for i in 1..face_indices.len() - 1 {
+                        indices.extend_from_slice(&[face_indices[0], face_indices[i], face_indices[i + 1]]);
+                    }
+                    Ok(())
+                })
+            }
+            _ => Ok(()),
+        };
+        result.with_context(|| format!("Invalid OBJ line {}: {line}", line_number + 1))?;
+    }
+
+    Ok(Mesh {
+        vertices,
+        indices: Some(indices),
+        skin: None,
+    })
+}
+
+fn parse_floats<'a, const N: usize>(mut tokens: impl Iterator<Item = &'a str>) -> anyhow::Result<[f32; N]> {
+    let mut values = [0.0; N];
+    for value in values.iter_mut() {
+        *value = tokens.next().ok_or_else(|| anyhow!("missing component"))?.parse()?;
+    }
+    Ok(values)
+}
+
+/// Parse `v`, `v/vt`, `v//vn` or `v/vt/vn`, indices are 1-based or negative relative to the end.
+fn parse_corner(token: &str, position_count: usize, uv_count: usize, normal_count: usize) -> anyhow::Result<ObjCorner> {
+    let resolve = |value: Option<&str>, count: usize| -> anyhow::Result<Option<usize>> {
+        let Some(value) = value.filter(|value| !value.is_empty()) else {
+            return Ok(None);
+        };
+        let index: isize = value.parse()?;
+        let resolved = if index < 0 { count as isize + index } else { index - 1 };
+        if resolved < 0 || resolved as usize >= count {
+            bail!("index {index} out of range");
+        }
+        Ok(Some(resolved as usize))
+    };
+
+    let mut parts = token.split('/');
+    let position = resolve(parts.next(), position_count)?.ok_or_else(|| anyhow!("missing position index"))?;
+    let uv = resolve(parts.next(), uv_count)?;
+    let normal = resolve(parts.next(), normal_count)?;
+    Ok((position, uv, normal))
+}
+
+fn flat_normal(positions: &[[f32; 3]], corners: &[ObjCorner]) -> [f32; 3] {
+    let [a, b, c] = [corners[0], corners[1], corners[2]].map(|(position, _, _)| Vec3::from(positions[position]));
+    (b - a).cross(c - a).normalize_or_zero().to_array()
+}
@@ -0,0 +1,42 @@
+use ash::vk;
+use bitflags::bitflags;
+use crate::mesh::MeshVertexLayout;
+use crate::raytracing::RayTracingMaterial;
+
+bitflags! {
+    /// Material state changing the pipeline a mesh is drawn with.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct MeshMaterialFlags: u8 {
+        const ALPHA_BLEND = 1 << 0;
+        const DOUBLE_SIDED = 1 << 1;
+    }
+}
+
+impl MeshMaterialFlags {
+    pub fn from_material(material: &RayTracingMaterial) -> Self {
+        let mut flags = Self::empty();
+        flags.set(Self::ALPHA_BLEND, material.base_color.alpha < 1.0);
+        flags.set(Self::DOUBLE_SIDED, material.double_sided);
+        flags
+    }
+}
+
+/// State every mesh pipeline variant depends on, part of the
+/// [`SpecializedPipelineKey`](crate::specialized_pipeline::SpecializedPipelineKey) of mesh passes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshPipelineKey {
+    pub vertex_layout: MeshVertexLayout,
+    pub material: MeshMaterialFlags,
+    pub samples: vk::SampleCountFlags,
+    /// Format of the color attachment of the view
+    pub target_format: vk::Format,
+}
+
+impl MeshPipelineKey {
+    pub fn cull_mode(&self) -> vk::CullModeFlags {
+        match self.material.contains(MeshMaterialFlags::DOUBLE_SIDED) {
+            true => vk::CullModeFlags::NONE,
+            false => vk::CullModeFlags::BACK,
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::mem::offset_of;
+use ash::vk;
+use anyhow::{bail, ensure};
+use bitflags::bitflags;
+use avalanche_hlvk::{StagedShader, VertexStreamSet};
+use crate::mesh::{MeshSkinVertex, MeshVertex};
+
+bitflags! {
+    /// Attributes of a [`MeshVertex`] and of a [`MeshSkinVertex`].
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct MeshAttributes: u8 {
+        const POSITION = 1 << 0;
+        const NORMAL = 1 << 1;
+        const UV = 1 << 2;
+        const JOINTS = 1 << 3;
+        const WEIGHTS = 1 << 4;
+
+        /// Every attribute of a [`MeshVertex`]
+        const VERTEX = Self::POSITION.bits() | Self::NORMAL.bits() | Self::UV.bits();
+        /// Every attribute of a [`MeshSkinVertex`]
+        const SKIN = Self::JOINTS.bits() | Self::WEIGHTS.bits();
+    }
+}
+
+/// Where a [`MeshAttributes`] flag is read from and how shaders declare it.
+#[derive(Clone, Copy)]
+struct MeshVertexAttribute {
+    attribute: MeshAttributes,
+    location: u32,
+    format: vk::Format,
+    /// Format of the declared input type, differs from `format` for narrower integers
+    input_format: vk::Format,
+    stride: usize,
+    offset: usize,
+}
+
+impl MeshVertexAttribute {
+    const fn new(attribute: MeshAttributes, location: u32, format: vk::Format, stride: usize, offset: usize) -> Self {
+        Self { attribute, location, format, input_format: format, stride, offset }
+    }
+}
+
+const MESH_VERTEX_ATTRIBUTES: [MeshVertexAttribute; 5] = [
+    MeshVertexAttribute::new(
+        MeshAttributes::POSITION, 0, vk::Format::R32G32B32_SFLOAT,
+        std::mem::size_of::<MeshVertex>(), offset_of!(MeshVertex, position),
+    ),
+    MeshVertexAttribute::new(
+        MeshAttributes::NORMAL, 1, vk::Format::R32G32B32_SFLOAT,
+        std::mem::size_of::<MeshVertex>(), offset_of!(MeshVertex, normal),
+    ),
+    MeshVertexAttribute::new(
+        MeshAttributes::UV, 2, vk::Format::R32G32_SFLOAT,
+        std::mem::size_of::<MeshVertex>(), offset_of!(MeshVertex, uv),
+    ),
+    MeshVertexAttribute {
+        input_format: vk::Format::R32G32B32A32_UINT,
+        ..MeshVertexAttribute::new(
+            MeshAttributes::JOINTS, 3, vk::Format::R16G16B16A16_UINT,
+            std::mem::size_of::<MeshSkinVertex>(), offset_of!(MeshSkinVertex, joints),
+        )
+    },
+    MeshVertexAttribute::new(
+        MeshAttributes::WEIGHTS, 4, vk::Format::R32G32B32A32_SFLOAT,
+        std::mem::size_of::<MeshSkinVertex>(), offset_of!(MeshSkinVertex, weights),
+    ),
+];
+
+/// The attributes a mesh provides, bound at fixed shader locations:
+///
+/// | attribute  | location | type    |
+/// |------------|----------|---------|
+/// | `position` | 0        | `vec3`  |
+/// | `normal`   | 1        | `vec3`  |
+/// | `uv`       | 2        | `vec2`  |
+/// | `joints`   | 3        | `uvec4` |
+/// | `weights`  | 4        | `vec4`  |
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshVertexLayout {
+    pub attributes: MeshAttributes,
+}
+
+impl MeshVertexLayout {
+    #[inline]
+    pub const fn new(attributes: MeshAttributes) -> Self {
+        Self { attributes }
+    }
+
+    /// Skinned layouts are drawn with a joint palette.
+    #[inline]
+    pub fn is_skinned(&self) -> bool {
+        self.attributes.intersects(MeshAttributes::SKIN)
+    }
+
+    /// Streams of the provided attributes, interleaved in a binding of [`MeshVertex`]es
+    /// followed by a binding of [`MeshSkinVertex`]es for skinned layouts.
+    pub fn vertex_stream(&self) -> VertexStreamSet {
+        self.provided().fold(VertexStreamSet::empty(), |streams, attribute| {
+            streams.add_stream(
+                attribute.stride as u32,
+                vk::VertexInputRate::VERTEX,
+                attribute.location,
+                attribute.format,
+                Some(attribute.offset as u32),
+            )
+        })
+    }
+
+    /// Check that every input of a vertex shader is provided with the declared type.
+    pub fn validate(&self, shader: &StagedShader) -> anyhow::Result<()> {
+        ensure!(shader.stage == vk::ShaderStageFlags::VERTEX, "{:?} shaders have no vertex inputs", shader.stage);
+
+        for input in &shader.module.reflection.inputs {
+            let Some(provided) = self.provided().find(|attribute| attribute.location == input.location) else {
+                bail!("Vertex input at location {} is not provided by the mesh layout {:?}", input.location, self.attributes);
+            };
+            if let Some(input_format) = input.format {
+                ensure!(
+                    input_format == provided.input_format,
+                    "Vertex input at location {} is {input_format:?} but {:?} is {:?}",
+                    input.location,
+                    provided.attribute,
+                    provided.input_format,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn provided(&self) -> impl Iterator<Item = MeshVertexAttribute> + '_ {
+        MESH_VERTEX_ATTRIBUTES
+            .into_iter()
+            .filter(|attribute| self.attributes.contains(attribute.attribute))
+    }
+}
+
+impl Default for MeshVertexLayout {
+    /// Every attribute of [`MeshVertex`].
+    fn default() -> Self {
+        Self::new(MeshAttributes::VERTEX)
+    }
+}
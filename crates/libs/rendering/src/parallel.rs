@@ -0,0 +1,66 @@
+//! Helpers spreading the CPU side of extraction and preparation over the [`ComputeTaskPool`].
+//!
+//! Small inputs are processed on the calling thread, spawning the tasks would cost more than the work.
+
+use bytemuck::Pod;
+use bevy_tasks::{ComputeTaskPool, ParallelSlice, ParallelSliceMut, TaskPool};
+use avalanche_hlvk::Context;
+use crate::prelude::TypedBuffer;
+
+/// Inputs shorter than this are processed on the calling thread.
+pub const MIN_PARALLEL_LEN: usize = 1024;
+
+/// The compute task pool of the app, a default one is created when there is none, e.g. in render tests.
+pub fn compute_task_pool() -> &'static TaskPool {
+    ComputeTaskPool::get_or_init(TaskPool::default)
+}
+
+/// Map `items` in order, with a chunk per thread of the [`ComputeTaskPool`].
+pub fn par_map<T, U, F>(items: &[T], f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send + 'static,
+    F: Fn(&T) -> U + Send + Sync,
+{
+    if items.len() < MIN_PARALLEL_LEN {
+        return items.iter().map(f).collect();
+    }
+
+    let chunks = items.par_splat_map(compute_task_pool(), None, |chunk| chunk.iter().map(&f).collect::<Vec<_>>());
+    let mut mapped = Vec::with_capacity(items.len());
+    for chunk in chunks {
+        mapped.extend(chunk);
+    }
+    mapped
+}
+
+/// Build the elements of `buffer` from `items` in parallel, then upload them at once.
+///
+/// Returns whether the buffer was reallocated, like [`TypedBuffer::write`].
+pub fn par_chunks_upload<T, U, F>(
+    buffer: &mut TypedBuffer<U>,
+    context: &Context,
+    items: &[T],
+    f: F,
+) -> anyhow::Result<bool>
+where
+    T: Sync,
+    U: Pod + Send,
+    F: Fn(&T) -> U + Send + Sync,
+{
+    buffer.write(context, &par_map(items, f))
+}
+
+/// Stable sort of `items` by `key`, sorting a chunk per thread in parallel before merging them.
+pub fn par_sort_by_key<T, K, F>(items: &mut [T], key: F)
+where
+    T: Send,
+    K: Ord,
+    F: Fn(&T) -> K + Send + Sync,
+{
+    if items.len() >= MIN_PARALLEL_LEN {
+        (&mut *items).par_splat_map_mut(compute_task_pool(), None, |chunk| chunk.sort_by_key(&key));
+    }
+    // the stable sort detects the sorted chunks and only merges them
+    items.sort_by_key(key);
+}
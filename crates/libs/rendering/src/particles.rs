@@ -0,0 +1,452 @@
+mod node;
+mod pipeline;
+
+pub use node::*;
+pub use pipeline::*;
+
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Component, Entity, IntoSystemConfigs, Query, ReflectComponent, Res, ResMut, Resource};
+use bevy_ecs::query::QueryItem;
+use bevy_log::error;
+use bevy_math::{Mat4, Vec3};
+use bevy_reflect::Reflect;
+use bevy_time::Time;
+use bevy_transform::prelude::GlobalTransform;
+use bevy_utils::{EntityHashMap, HashSet};
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{Buffer, Context, DescriptorPool, DescriptorSet, WriteDescriptorSet, WriteDescriptorSetKind};
+use crate::color::Color;
+use crate::{ExtractSchedule, Render, RenderApp, RenderSet};
+use crate::camera::{ExtractedCamera, CAMERA_DRIVER};
+use crate::debug_view::DEBUG_VIEW_NODE;
+use crate::deferred::{prepare_deferred_views, DeferredViews, DEFERRED_GRAPH};
+use crate::extract::{ExtractComponent, ExtractComponentPlugin, FrameContext};
+use crate::graph::{RenderGraph, RenderGraphApp};
+use crate::graph::node::ViewNodeRunner;
+use crate::prelude::Extract;
+use crate::transparent::TRANSPARENT_OIT_NODE;
+
+/// Main graph node ageing and moving the particles of every emitter, see [`ParticleUpdateNode`].
+pub const PARTICLE_UPDATE_NODE: &str = "particle_update";
+/// Main graph node spawning the particles of every emitter, see [`ParticleEmitNode`].
+pub const PARTICLE_EMIT_NODE: &str = "particle_emit";
+/// Node of the [`DEFERRED_GRAPH`] sorting and drawing the particles seen by a view, see [`ParticleNode`].
+pub const PARTICLE_NODE: &str = "particle_pass";
+
+/// Threads of a workgroup of the particle compute shaders.
+pub const PARTICLE_WORKGROUP_SIZE: u32 = 64;
+
+/// Spawns camera facing particles simulated on the GPU at the origin of its entity.
+///
+/// Particles are drawn alpha blended back to front over cameras rendered with the [`DEFERRED_GRAPH`].
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct ParticleEmitter {
+    /// Particles alive at once, the oldest ones are replaced once exceeded
+    pub capacity: u32,
+    /// Particles spawned per second
+    pub rate: f32,
+    /// Seconds a particle lives
+    pub lifetime: f32,
+    /// Initial velocity in the space of the emitter
+    pub velocity: Vec3,
+    /// Random deviation of the initial velocity relative to its length
+    pub velocity_spread: f32,
+    /// World space acceleration, e.g. gravity
+    pub acceleration: Vec3,
+    /// Half extent of a particle in world units
+    pub size: f32,
+    /// Color at spawn
+    pub start_color: Color,
+    /// Color at the end of the lifetime
+    pub end_color: Color,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            rate: 64.0,
+            lifetime: 2.0,
+            velocity: Vec3::Y,
+            velocity_spread: 0.3,
+            acceleration: Vec3::new(0.0, -9.81, 0.0),
+            size: 0.05,
+            start_color: Color::WHITE,
+            end_color: Color::WHITE.with_alpha(0.0),
+        }
+    }
+}
+
+/// A [`ParticleEmitter`] in the render world.
+#[derive(Component, Clone, Debug)]
+pub struct ExtractedParticleEmitter {
+    pub emitter: ParticleEmitter,
+    pub world_from_emitter: Mat4,
+}
+
+impl ExtractComponent for ParticleEmitter {
+    type Query = (&'static ParticleEmitter, &'static GlobalTransform);
+    type Filter = ();
+    type Out = ExtractedParticleEmitter;
+
+    fn extract_component((emitter, transform): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some(ExtractedParticleEmitter {
+            emitter: emitter.clone(),
+            world_from_emitter: transform.compute_matrix(),
+        })
+    }
+}
+
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ParticleEmitter>()
+            .add_plugins(ExtractComponentPlugin::<ParticleEmitter>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ParticlePipeline>()
+                .init_resource::<ParticleSimulationTime>()
+                .init_resource::<ParticleSystems>()
+                .init_resource::<ParticleViews>()
+                .add_systems(ExtractSchedule, extract_particle_simulation_time)
+                .add_systems(
+                    Render, (
+                        (prepare_particle_pipeline, prepare_particle_systems)
+                            .chain()
+                            .in_set(RenderSet::PrepareResources),
+                        prepare_particle_views
+                            .after(prepare_deferred_views)
+                            .in_set(RenderSet::PrepareBindGroups),
+                    )
+                )
+                .add_render_graph_node::<ViewNodeRunner<ParticleNode>>(DEFERRED_GRAPH, PARTICLE_NODE)
+                .add_render_graph_edges(DEFERRED_GRAPH, &[TRANSPARENT_OIT_NODE, PARTICLE_NODE, DEBUG_VIEW_NODE]);
+
+            let mut graph = render_app.world.resource_mut::<RenderGraph>();
+            graph.add_node(PARTICLE_UPDATE_NODE, ParticleUpdateNode);
+            graph.add_node(PARTICLE_EMIT_NODE, ParticleEmitNode);
+            graph.add_node_edges(&[PARTICLE_UPDATE_NODE, PARTICLE_EMIT_NODE, CAMERA_DRIVER]);
+        }
+    }
+}
+
+/// Seconds the particles are advanced by this frame, the delta of the virtual [`Time`] of the main world.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct ParticleSimulationTime {
+    pub delta_seconds: f32,
+}
+
+fn extract_particle_simulation_time(mut simulation_time: ResMut<ParticleSimulationTime>, time: Extract<Res<Time>>) {
+    simulation_time.delta_seconds = time.delta_seconds();
+}
+
+/// Matches `Particle` in `shaders/particles/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuParticle {
+    /// Position in xyz, age in seconds in w
+    pub position_age: [f32; 4],
+    /// Velocity in xyz, lifetime in seconds in w, the particle is dead once its age reaches it
+    pub velocity_lifetime: [f32; 4],
+}
+
+/// Matches `DrawKey` in `shaders/particles/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ParticleDrawKey {
+    pub depth: f32,
+    pub index: u32,
+}
+
+/// Matches `ParticleEmitterUniform` in `shaders/particles/draw.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ParticleEmitterUniform {
+    start_color: [f32; 4],
+    end_color: [f32; 4],
+    size: f32,
+}
+
+/// GPU particles of a [`ParticleEmitter`], kept across frames.
+pub struct ParticlePool {
+    pub capacity: u32,
+    /// Keys sorted per view, the capacity rounded up to a power of two for the bitonic sort
+    pub sort_size: u32,
+    /// [`GpuParticle`]s, spawned into as a ring
+    pub particles: Buffer,
+    /// [`ParticleDrawKey`]s of the view drawn last
+    pub draw_keys: Buffer,
+    /// A `VkDrawIndirectCommand` drawing a quad per alive particle
+    pub indirect: Buffer,
+    emitter_uniform: Buffer,
+    _descriptor_pool: DescriptorPool,
+    /// Bound by the compute passes
+    pub(crate) compute_set: DescriptorSet,
+    /// Set 0 of the draw pipeline
+    pub(crate) draw_set: DescriptorSet,
+    pub emitter: ExtractedParticleEmitter,
+    /// Particles spawned this frame from `first_slot`
+    pub emit_count: u32,
+    pub first_slot: u32,
+    /// Seeds the random velocities of the particles spawned this frame
+    pub seed: u32,
+    /// Fraction of a particle left over by the previous frames
+    spawn_remainder: f32,
+}
+
+impl ParticlePool {
+    fn new(context: &Context, pipeline: &ParticlePipelineResources, emitter: &ExtractedParticleEmitter) -> anyhow::Result<Self> {
+        let capacity = emitter.emitter.capacity.max(1);
+        let sort_size = capacity.next_power_of_two();
+
+        let particles = context.create_buffer(
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            MemoryLocation::CpuToGpu,
+            (capacity as usize * std::mem::size_of::<GpuParticle>()) as _,
+        )?;
+        // zeroed particles have no lifetime left
+        particles.copy_data_to_buffer(&vec![GpuParticle::default(); capacity as usize])?;
+        let draw_keys = context.create_buffer(
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            MemoryLocation::GpuOnly,
+            (sort_size as usize * std::mem::size_of::<ParticleDrawKey>()) as _,
+        )?;
+        let indirect = context.create_buffer(
+            vk::BufferUsageFlags::INDIRECT_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of::<vk::DrawIndirectCommand>() as _,
+        )?;
+        // two triangles per particle, the instance count is written by the keys pass
+        indirect.copy_data_to_buffer(&[vk::DrawIndirectCommand {
+            vertex_count: 6,
+            instance_count: 0,
+            first_vertex: 0,
+            first_instance: 0,
+        }])?;
+        let emitter_uniform = context.create_buffer(
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of::<ParticleEmitterUniform>() as _,
+        )?;
+
+        let descriptor_pool = context.create_descriptor_pool(2, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 5,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+            },
+        ])?;
+        let compute_set = descriptor_pool.allocate_set(pipeline.compute_set_layout())?;
+        let draw_set = descriptor_pool.allocate_set(pipeline.draw_set_layout())?;
+        compute_set.update(&[
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: &particles },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: &draw_keys },
+            },
+            WriteDescriptorSet {
+                binding: 2,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: &indirect },
+            },
+        ]);
+        draw_set.update(&[
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: &particles },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: &draw_keys },
+            },
+            WriteDescriptorSet {
+                binding: 2,
+                kind: WriteDescriptorSetKind::UniformBuffer { buffer: &emitter_uniform },
+            },
+        ]);
+
+        Ok(Self {
+            capacity,
+            sort_size,
+            particles,
+            draw_keys,
+            indirect,
+            emitter_uniform,
+            _descriptor_pool: descriptor_pool,
+            compute_set,
+            draw_set,
+            emitter: emitter.clone(),
+            emit_count: 0,
+            first_slot: 0,
+            seed: 0,
+            spawn_remainder: 0.0,
+        })
+    }
+
+    /// Spawn the particles due after `delta_seconds` into the slots following the last spawned ones.
+    fn advance(&mut self, delta_seconds: f32) {
+        let due = self.spawn_remainder + self.emitter.emitter.rate.max(0.0) * delta_seconds;
+        let emit_count = due.floor();
+        self.spawn_remainder = due - emit_count;
+
+        self.first_slot = (self.first_slot + self.emit_count) % self.capacity;
+        self.emit_count = (emit_count as u32).min(self.capacity);
+        self.seed = self.seed.wrapping_add(1);
+    }
+
+    /// Distance of the emitter in front of a camera.
+    pub fn view_depth(&self, camera: &ExtractedCamera) -> f32 {
+        let origin = self.emitter.world_from_emitter.w_axis.truncate();
+        -camera.world_from_view.inverse().transform_point3(origin).z
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ParticleSystems(pub(crate) EntityHashMap<Entity, ParticlePool>);
+
+impl ParticleSystems {
+    pub fn get(&self, entity: Entity) -> Option<&ParticlePool> {
+        self.0.get(&entity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &ParticlePool)> {
+        self.0.iter().map(|(entity, pool)| (*entity, pool))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+fn prepare_particle_systems(
+    mut systems: ResMut<ParticleSystems>,
+    pipeline: Res<ParticlePipeline>,
+    time: Res<ParticleSimulationTime>,
+    emitters: Query<(Entity, &ExtractedParticleEmitter)>,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(pipeline) = pipeline.resources() else {
+        systems.0.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+
+    for (entity, emitter) in emitters.iter() {
+        alive.insert(entity);
+
+        if systems.0.get(&entity).map(|pool| pool.capacity) != Some(emitter.emitter.capacity.max(1)) {
+            match ParticlePool::new(context, pipeline, emitter) {
+                Ok(pool) => {
+                    systems.0.insert(entity, pool);
+                }
+                Err(err) => {
+                    error!("Failed to create particle pool: {err}");
+                    systems.0.remove(&entity);
+                    continue;
+                }
+            }
+        }
+        let pool = systems.0.get_mut(&entity).unwrap();
+        pool.emitter = emitter.clone();
+        pool.advance(time.delta_seconds);
+
+        let uniform = ParticleEmitterUniform {
+            start_color: emitter.emitter.start_color.to_linear_rgba(),
+            end_color: emitter.emitter.end_color.to_linear_rgba(),
+            size: emitter.emitter.size,
+        };
+        if let Err(err) = pool.emitter_uniform.copy_data_to_buffer(std::slice::from_ref(&uniform)) {
+            error!("Failed to upload particle emitter uniform: {err}");
+        }
+    }
+
+    systems.0.retain(|entity, _| alive.contains(entity));
+}
+
+/// Bindings of a camera drawing particles, kept across frames.
+pub struct ParticleViewState {
+    _descriptor_pool: DescriptorPool,
+    /// Set 1 of the draw pipeline
+    pub(crate) descriptor_set: DescriptorSet,
+}
+
+impl ParticleViewState {
+    fn new(context: &Context, pipeline: &ParticlePipelineResources) -> anyhow::Result<Self> {
+        let descriptor_pool = context.create_descriptor_pool(1, &[vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::STORAGE_IMAGE,
+            descriptor_count: 1,
+        }])?;
+        let descriptor_set = descriptor_pool.allocate_set(pipeline.view_set_layout())?;
+
+        Ok(Self {
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ParticleViews(pub(crate) EntityHashMap<Entity, ParticleViewState>);
+
+impl ParticleViews {
+    pub fn get(&self, entity: Entity) -> Option<&ParticleViewState> {
+        self.0.get(&entity)
+    }
+}
+
+fn prepare_particle_views(
+    mut views: ResMut<ParticleViews>,
+    pipeline: Res<ParticlePipeline>,
+    systems: Res<ParticleSystems>,
+    deferred_views: Res<DeferredViews>,
+    cameras: Query<Entity, bevy_ecs::query::With<ExtractedCamera>>,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(pipeline) = pipeline.resources().filter(|_| !systems.is_empty()) else {
+        views.0.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+
+    for entity in cameras.iter() {
+        let Some(deferred) = deferred_views.get(entity) else {
+            continue;
+        };
+        alive.insert(entity);
+
+        if !views.0.contains_key(&entity) {
+            match ParticleViewState::new(context, pipeline) {
+                Ok(state) => {
+                    views.0.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create particle view resources: {err}");
+                    continue;
+                }
+            }
+        }
+
+        // the G-buffer is recreated with the view target, rewrite the set every frame
+        views.0[&entity].descriptor_set.update(&[WriteDescriptorSet {
+            binding: 0,
+            kind: WriteDescriptorSetKind::StorageImage {
+                view: &deferred.normal.view,
+                layout: vk::ImageLayout::GENERAL,
+            },
+        }]);
+    }
+
+    views.0.retain(|entity, _| alive.contains(entity));
+}
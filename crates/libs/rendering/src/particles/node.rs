@@ -0,0 +1,348 @@
+use ash::vk;
+use bevy_ecs::prelude::World;
+use bevy_utils::FloatOrd;
+use avalanche_hlvk::{Buffer, BufferBarrier, CommandBuffer, ImageBarrier, RenderingAttachment};
+use crate::camera::ExtractedCamera;
+use crate::deferred::DeferredViews;
+use crate::extract::FrameContext;
+use crate::globals::Globals;
+use crate::particles::{
+    EmitPushConstants, KeysPushConstants, ParticleDrawPushConstants, ParticlePipeline, ParticlePipelineResources,
+    ParticlePool, ParticleSimulationTime, ParticleSystems, ParticleViews, SortPushConstants, UpdatePushConstants,
+    PARTICLE_WORKGROUP_SIZE,
+};
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::{Node, ViewNode};
+use crate::view::ViewTarget;
+
+/// Compute writes of the particle passes made visible to the next compute pass.
+fn compute_barrier(buffer: &Buffer) -> BufferBarrier<'_> {
+    BufferBarrier {
+        buffer,
+        src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+        dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+        src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+        dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+    }
+}
+
+/// Wait for the previous draw of a pool before its buffers are written again.
+fn after_draw_barrier(buffer: &Buffer, dst_stage_mask: vk::PipelineStageFlags2) -> BufferBarrier<'_> {
+    BufferBarrier {
+        buffer,
+        src_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ
+            | vk::AccessFlags2::SHADER_STORAGE_WRITE
+            | vk::AccessFlags2::INDIRECT_COMMAND_READ,
+        dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ
+            | vk::AccessFlags2::SHADER_STORAGE_WRITE
+            | vk::AccessFlags2::TRANSFER_WRITE,
+        src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER
+            | vk::PipelineStageFlags2::VERTEX_SHADER
+            | vk::PipelineStageFlags2::DRAW_INDIRECT,
+        dst_stage_mask,
+    }
+}
+
+#[inline]
+fn workgroups(invocations: u32) -> u32 {
+    invocations.div_ceil(PARTICLE_WORKGROUP_SIZE)
+}
+
+fn bind_compute(command_buffer: &CommandBuffer, pipeline: &ParticlePipelineResources, pool: &ParticlePool) {
+    command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::COMPUTE, &pipeline.compute_layout, 0, &[&pool.compute_set]);
+}
+
+/// Ages the particles of every [`ParticlePool`] and integrates their motion.
+pub struct ParticleUpdateNode;
+
+impl Node for ParticleUpdateNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let systems = world.resource::<ParticleSystems>();
+        if systems.is_empty() {
+            return Ok(());
+        }
+        let Some(pipeline) = world.resource::<ParticlePipeline>().resources() else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.compute_layout);
+        let delta_seconds = world.resource::<ParticleSimulationTime>().delta_seconds;
+
+        command_buffer.bind_compute_pipeline(&pipeline.update);
+        for (_, pool) in systems.iter() {
+            let [x, y, z] = pool.emitter.emitter.acceleration.to_array();
+            let push_constants = UpdatePushConstants {
+                acceleration: [x, y, z, delta_seconds],
+                capacity: pool.capacity,
+            };
+
+            command_buffer.pipeline_buffer_barriers(&[after_draw_barrier(
+                &pool.particles,
+                vk::PipelineStageFlags2::COMPUTE_SHADER,
+            )]);
+            bind_compute(command_buffer, pipeline, pool);
+            command_buffer.push_constants(
+                &pipeline.compute_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&push_constants),
+            );
+            command_buffer.dispatch(workgroups(pool.capacity), 1, 1);
+        }
+
+        Ok(())
+    }
+}
+
+/// Spawns the particles due this frame into every [`ParticlePool`].
+pub struct ParticleEmitNode;
+
+impl Node for ParticleEmitNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let systems = world.resource::<ParticleSystems>();
+        if systems.is_empty() {
+            return Ok(());
+        }
+        let Some(pipeline) = world.resource::<ParticlePipeline>().resources() else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.compute_layout);
+
+        command_buffer.bind_compute_pipeline(&pipeline.emit);
+        for (_, pool) in systems.iter() {
+            if pool.emit_count == 0 {
+                continue;
+            }
+
+            let emitter = &pool.emitter.emitter;
+            let [x, y, z] = emitter.velocity.to_array();
+            let push_constants = EmitPushConstants {
+                world_from_emitter: pool.emitter.world_from_emitter.to_cols_array(),
+                velocity: [x, y, z, emitter.velocity_spread],
+                first_slot: pool.first_slot,
+                count: pool.emit_count,
+                capacity: pool.capacity,
+                lifetime: emitter.lifetime,
+                seed: pool.seed,
+            };
+
+            command_buffer.pipeline_buffer_barriers(&[compute_barrier(&pool.particles)]);
+            bind_compute(command_buffer, pipeline, pool);
+            command_buffer.push_constants(
+                &pipeline.compute_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&push_constants),
+            );
+            command_buffer.dispatch(workgroups(pool.emit_count), 1, 1);
+        }
+
+        Ok(())
+    }
+}
+
+/// Sort the alive particles of a pool back to front for a camera and count them into its indirect draw.
+fn sort_particles(
+    command_buffer: &CommandBuffer,
+    pipeline: &ParticlePipelineResources,
+    pool: &ParticlePool,
+    camera: &ExtractedCamera,
+) {
+    command_buffer.pipeline_buffer_barriers(&[
+        compute_barrier(&pool.particles),
+        after_draw_barrier(&pool.draw_keys, vk::PipelineStageFlags2::COMPUTE_SHADER),
+        after_draw_barrier(&pool.indirect, vk::PipelineStageFlags2::TRANSFER),
+    ]);
+    // reset the instance count, counted again by the keys pass
+    command_buffer.fill_buffer(&pool.indirect, 4, 4, 0);
+    command_buffer.pipeline_buffer_barriers(&[BufferBarrier {
+        buffer: &pool.indirect,
+        src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+        dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+        src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+        dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+    }]);
+
+    bind_compute(command_buffer, pipeline, pool);
+    let keys = KeysPushConstants {
+        camera_position: camera.world_from_view.w_axis.to_array(),
+        camera_forward: (-camera.world_from_view.z_axis).to_array(),
+        capacity: pool.capacity,
+        sort_size: pool.sort_size,
+    };
+    command_buffer.bind_compute_pipeline(&pipeline.keys);
+    command_buffer.push_constants(&pipeline.compute_layout, vk::ShaderStageFlags::COMPUTE, 0, bytemuck::bytes_of(&keys));
+    command_buffer.dispatch(workgroups(pool.sort_size), 1, 1);
+
+    command_buffer.bind_compute_pipeline(&pipeline.sort);
+    let mut block = 2;
+    while block <= pool.sort_size {
+        let mut step = block / 2;
+        while step > 0 {
+            command_buffer.pipeline_buffer_barriers(&[compute_barrier(&pool.draw_keys)]);
+            command_buffer.push_constants(
+                &pipeline.compute_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                bytemuck::bytes_of(&SortPushConstants { block, step }),
+            );
+            command_buffer.dispatch(workgroups(pool.sort_size), 1, 1);
+            step /= 2;
+        }
+        block *= 2;
+    }
+
+    command_buffer.pipeline_buffer_barriers(&[
+        BufferBarrier {
+            buffer: &pool.draw_keys,
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::VERTEX_SHADER,
+        },
+        BufferBarrier {
+            buffer: &pool.indirect,
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            dst_access_mask: vk::AccessFlags2::INDIRECT_COMMAND_READ,
+            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::DRAW_INDIRECT,
+        },
+        BufferBarrier {
+            buffer: &pool.particles,
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::VERTEX_SHADER,
+        },
+    ]);
+}
+
+/// Sorts the particles of every [`ParticlePool`] for a view and alpha blends them over its [`ViewTarget`],
+/// emitters further away first.
+#[derive(Default)]
+pub struct ParticleNode;
+
+impl ViewNode for ParticleNode {
+    type ViewQuery = (&'static ExtractedCamera, &'static ViewTarget);
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (camera, target): (&ExtractedCamera, &ViewTarget),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let systems = world.resource::<ParticleSystems>();
+        if systems.is_empty() {
+            return Ok(());
+        }
+        let Some(pipeline) = world.resource::<ParticlePipeline>().resources() else {
+            return Ok(());
+        };
+        let (Some(state), Some(deferred)) = (
+            world.resource::<ParticleViews>().get(graph.view_entity()),
+            world.resource::<DeferredViews>().get(graph.view_entity()),
+        ) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        let globals = world.resource::<Globals>();
+        globals.bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.compute_layout);
+        globals.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, &pipeline.draw_layout);
+
+        let mut pools = systems.iter().map(|(_, pool)| pool).collect::<Vec<_>>();
+        pools.sort_by_key(|pool| std::cmp::Reverse(FloatOrd(pool.view_depth(camera))));
+        for pool in &pools {
+            sort_particles(command_buffer, pipeline, pool, camera);
+        }
+
+        let extent = vk::Extent2D {
+            width: target.size.x,
+            height: target.size.y,
+        };
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image: &deferred.normal.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+                src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                dst_stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            },
+            ImageBarrier {
+                image: &target.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::MEMORY_WRITE,
+                dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            },
+        ]);
+        command_buffer.begin_rendering_attachments(
+            &[RenderingAttachment {
+                view: &target.view,
+                layout: vk::ImageLayout::GENERAL,
+                load_op: vk::AttachmentLoadOp::LOAD,
+                clear_color: [0.0; 4],
+            }],
+            extent,
+        );
+        command_buffer.bind_raster_pipeline(&pipeline.draw_pipeline);
+        command_buffer.set_viewport(extent);
+        command_buffer.set_scissor(extent);
+
+        let push_constants = ParticleDrawPushConstants {
+            clip_from_world: (camera.projection * camera.world_from_view.inverse()).to_cols_array(),
+            camera_position: camera.world_from_view.w_axis.to_array(),
+            camera_right: camera.world_from_view.x_axis.to_array(),
+            camera_up: camera.world_from_view.y_axis.to_array(),
+        };
+        command_buffer.push_constants(
+            &pipeline.draw_layout,
+            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            0,
+            bytemuck::bytes_of(&push_constants),
+        );
+        for pool in &pools {
+            command_buffer.bind_descriptor_sets(
+                vk::PipelineBindPoint::GRAPHICS,
+                &pipeline.draw_layout,
+                0,
+                &[&pool.draw_set, &state.descriptor_set],
+            );
+            command_buffer.draw_indirect(&pool.indirect, 0, 1);
+        }
+        command_buffer.end_rendering();
+
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &target.image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::prelude::{Query, Res, ResMut, Resource};
+use bevy_log::error;
+use avalanche_hlvk::{
+    BlendMode, ComputePipeline, Context, DescriptorSetLayout, PipelineLayout, RasterColorAttachment, RasterPipeline,
+    RasterPipelineCreateInfo, VertexStreamSet,
+};
+use crate::camera::ExtractedCamera;
+use crate::deferred::DEFERRED_GRAPH;
+use crate::extract::FrameContext;
+use crate::globals;
+use crate::shader::ShaderDirectory;
+use crate::view::VIEW_TARGET_FORMAT;
+
+pub(crate) const EMIT_SHADER: &str = "particles/emit.comp";
+pub(crate) const UPDATE_SHADER: &str = "particles/update.comp";
+pub(crate) const KEYS_SHADER: &str = "particles/keys.comp";
+pub(crate) const SORT_SHADER: &str = "particles/sort.comp";
+pub(crate) const PARTICLE_VERTEX_SHADER: &str = "particles/particle.vert";
+pub(crate) const PARTICLE_FRAGMENT_SHADER: &str = "particles/particle.frag";
+
+/// Matches `EmitPushConstants` in `shaders/particles/emit.comp`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EmitPushConstants {
+    pub world_from_emitter: [f32; 16],
+    /// Initial velocity in the space of the emitter in xyz, relative spread in w
+    pub velocity: [f32; 4],
+    pub first_slot: u32,
+    pub count: u32,
+    pub capacity: u32,
+    pub lifetime: f32,
+    pub seed: u32,
+}
+
+/// Matches `UpdatePushConstants` in `shaders/particles/update.comp`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UpdatePushConstants {
+    /// World space acceleration in xyz, seconds to advance in w
+    pub acceleration: [f32; 4],
+    pub capacity: u32,
+}
+
+/// Matches `KeysPushConstants` in `shaders/particles/keys.comp`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeysPushConstants {
+    pub camera_position: [f32; 4],
+    pub camera_forward: [f32; 4],
+    pub capacity: u32,
+    pub sort_size: u32,
+}
+
+/// Matches `SortPushConstants` in `shaders/particles/sort.comp`, a merge step of a bitonic sort.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SortPushConstants {
+    /// Size of the sequences being merged
+    pub block: u32,
+    /// Distance of the compared keys
+    pub step: u32,
+}
+
+/// Matches `ParticleDrawPushConstants` in `shaders/particles/draw.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ParticleDrawPushConstants {
+    pub clip_from_world: [f32; 16],
+    pub camera_position: [f32; 4],
+    pub camera_right: [f32; 4],
+    pub camera_up: [f32; 4],
+}
+
+// SAFETY: plain 32 bit fields without implicit padding
+unsafe impl Zeroable for EmitPushConstants {}
+unsafe impl Pod for EmitPushConstants {}
+unsafe impl Zeroable for UpdatePushConstants {}
+unsafe impl Pod for UpdatePushConstants {}
+unsafe impl Zeroable for KeysPushConstants {}
+unsafe impl Pod for KeysPushConstants {}
+unsafe impl Zeroable for SortPushConstants {}
+unsafe impl Pod for SortPushConstants {}
+unsafe impl Zeroable for ParticleDrawPushConstants {}
+unsafe impl Pod for ParticleDrawPushConstants {}
+
+pub struct ParticlePipelineResources {
+    /// Reflected from the compute shaders, set 0 binds the buffers of a [`ParticlePool`](super::ParticlePool)
+    pub compute_layout: PipelineLayout,
+    pub emit: ComputePipeline,
+    pub update: ComputePipeline,
+    pub keys: ComputePipeline,
+    pub sort: ComputePipeline,
+    /// Set 0 binds the buffers of a pool, set 1 the G-buffer of the view
+    pub draw_layout: PipelineLayout,
+    /// Expands a camera facing quad per sorted particle
+    pub draw_pipeline: RasterPipeline,
+}
+
+impl ParticlePipelineResources {
+    #[inline]
+    pub fn compute_set_layout(&self) -> &DescriptorSetLayout {
+        &self.compute_layout.descriptor_set_layouts()[0]
+    }
+
+    #[inline]
+    pub fn draw_set_layout(&self) -> &DescriptorSetLayout {
+        &self.draw_layout.descriptor_set_layouts()[0]
+    }
+
+    #[inline]
+    pub fn view_set_layout(&self) -> &DescriptorSetLayout {
+        &self.draw_layout.descriptor_set_layouts()[1]
+    }
+}
+
+/// The particle pipelines, created the first time a camera uses the deferred graph.
+#[derive(Resource, Default)]
+pub enum ParticlePipeline {
+    #[default]
+    Uninitialized,
+    Ready(Box<ParticlePipelineResources>),
+    /// Creation failed, usually shaders are missing.
+    Failed,
+}
+
+impl ParticlePipeline {
+    pub fn resources(&self) -> Option<&ParticlePipelineResources> {
+        match self {
+            ParticlePipeline::Ready(resources) => Some(resources),
+            _ => None,
+        }
+    }
+}
+
+fn create_resources(context: &Context, shaders: &ShaderDirectory) -> anyhow::Result<ParticlePipelineResources> {
+    let [emit, update, keys, sort] = [EMIT_SHADER, UPDATE_SHADER, KEYS_SHADER, SORT_SHADER]
+        .map(|path| shaders.load(context, path, vk::ShaderStageFlags::COMPUTE));
+    let compute_stages = [emit?, update?, keys?, sort?];
+    let compute_layout = globals::pipeline_layout(&compute_stages)?;
+    anyhow::ensure!(globals::pass_set_count(&compute_stages) == 1, "particle compute shaders must only use set 0");
+    let [emit, update, keys, sort] = &compute_stages;
+
+    let draw_stages = [
+        shaders.load(context, PARTICLE_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX)?,
+        shaders.load(context, PARTICLE_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT)?,
+    ];
+    let draw_layout = globals::pipeline_layout(&draw_stages)?;
+    anyhow::ensure!(globals::pass_set_count(&draw_stages) == 2, "particle draw shaders must use sets 0 and 1");
+
+    let draw_pipeline = context.create_graphics_pipeline(&draw_layout, RasterPipelineCreateInfo {
+        shaders: &draw_stages,
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        vertex_stream: &VertexStreamSet::empty(),
+        viewport: None,
+        scissor: None,
+        color_attachments: &[RasterColorAttachment::new(VIEW_TARGET_FORMAT, BlendMode::AlphaBlend)],
+        depth_attachment: None,
+        dynamic_states: Some(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]),
+        polygon_mode: vk::PolygonMode::FILL,
+        front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+        samples: vk::SampleCountFlags::TYPE_1,
+        cull_mode: vk::CullModeFlags::NONE,
+    })?;
+
+    Ok(ParticlePipelineResources {
+        emit: context.create_compute_pipeline(&compute_layout, emit)?,
+        update: context.create_compute_pipeline(&compute_layout, update)?,
+        keys: context.create_compute_pipeline(&compute_layout, keys)?,
+        sort: context.create_compute_pipeline(&compute_layout, sort)?,
+        compute_layout,
+        draw_layout,
+        draw_pipeline,
+    })
+}
+
+pub(crate) fn prepare_particle_pipeline(
+    mut pipeline: ResMut<ParticlePipeline>,
+    shaders: Res<ShaderDirectory>,
+    cameras: Query<&ExtractedCamera>,
+    frame_context: Res<FrameContext>,
+) {
+    if !matches!(*pipeline, ParticlePipeline::Uninitialized)
+        || !cameras.iter().any(|camera| camera.render_graph == DEFERRED_GRAPH) {
+        return;
+    }
+
+    *pipeline = match create_resources(frame_context.render_context(), &shaders) {
+        Ok(resources) => ParticlePipeline::Ready(Box::new(resources)),
+        Err(err) => {
+            error!("Failed to create particle pipeline: {err}");
+            ParticlePipeline::Failed
+        }
+    };
+}
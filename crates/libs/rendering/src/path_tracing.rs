@@ -0,0 +1,341 @@
+mod node;
+mod pipeline;
+
+pub use node::*;
+pub use pipeline::*;
+
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Component, Entity, IntoSystemConfigs, Query, ReflectComponent, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_math::{Mat4, UVec2};
+use bevy_reflect::Reflect;
+use bevy_utils::{EntityHashMap, HashSet};
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{
+    Buffer, Context, DescriptorPool, DescriptorSet, Image, ImageView, WriteDescriptorSet, WriteDescriptorSetKind,
+};
+use crate::{Render, RenderApp, RenderSet};
+use crate::camera::ExtractedCamera;
+use crate::extract::{ExtractComponent, ExtractComponentPlugin, FrameContext};
+use crate::graph::{RenderGraphApp, RenderLabel};
+use crate::graph::node::ViewNodeRunner;
+use crate::raytracing::{AccelerationStructureBuilder, RayTracingGpuScene, RayTracingScene};
+use crate::view::{MOTION_VECTORS_FORMAT, ViewMotionVectors, ViewTarget};
+
+/// Sub graph rendering a camera with the path tracer,
+/// select it with `CameraRenderGraph::new(PATH_TRACING_GRAPH)`.
+///
+/// Cameras using it are rendered with the [`DEFERRED_GRAPH`](crate::deferred::DEFERRED_GRAPH) on devices
+/// the path tracer can't run on, see [`path_tracing_supported`].
+pub const PATH_TRACING_GRAPH: RenderLabel = RenderLabel::new("path_tracing");
+pub const PATH_TRACING_NODE: &str = "path_tracing_pass";
+
+/// Progressive path tracing of a camera, used as the ground truth to compare rasterized output against.
+///
+/// Samples accumulate over frames while nothing changes,
+/// any camera movement, resize or scene change restarts the accumulation.
+/// Requires the `VK_KHR_ray_tracing_position_fetch` device extension and feature.
+/// Cameras of the [`PATH_TRACING_GRAPH`] without it use the default settings.
+#[derive(Component, ExtractComponent, Reflect, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct PathTracingSettings {
+    pub max_bounces: u32,
+    pub samples_per_frame: u32,
+    /// Stop accumulating after this many samples, 0 accumulates forever.
+    pub max_samples: u32,
+}
+
+impl Default for PathTracingSettings {
+    fn default() -> Self {
+        Self {
+            max_bounces: 4,
+            samples_per_frame: 1,
+            max_samples: 0,
+        }
+    }
+}
+
+pub struct PathTracingPlugin;
+
+impl Plugin for PathTracingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<PathTracingSettings>()
+            .add_plugins(ExtractComponentPlugin::<PathTracingSettings>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<PathTracingPipeline>()
+                .init_resource::<PathTracingAccumulation>()
+                .add_systems(
+                    Render, (
+                        prepare_path_tracing_pipeline.in_set(RenderSet::PrepareResources),
+                        prepare_path_tracing_accumulation.in_set(RenderSet::PrepareBindGroups),
+                    )
+                )
+                .add_render_sub_graph(PATH_TRACING_GRAPH)
+                .add_render_graph_node::<ViewNodeRunner<PathTracingNode>>(PATH_TRACING_GRAPH, PATH_TRACING_NODE);
+        }
+    }
+}
+
+/// Whether the device can run the [`PATH_TRACING_GRAPH`], which traces rays and fetches the hit triangle positions.
+pub fn path_tracing_supported(context: &Context) -> bool {
+    context.ray_tracing.is_some() && context.device_features.ray_tracing_position_fetch
+}
+
+/// Matches `PathTracingUniform` in `shaders/path_tracing/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PathTracingUniform {
+    world_from_view: [f32; 16],
+    view_from_clip: [f32; 16],
+    previous_clip_from_world: [f32; 16],
+    frame_index: u32,
+    samples_per_frame: u32,
+    max_bounces: u32,
+    write_motion_vectors: u32,
+}
+
+/// Per camera accumulation state, kept across frames.
+pub struct AccumulationState {
+    pub(crate) accumulation: Image,
+    pub(crate) accumulation_view: ImageView,
+    /// Bound instead of the [`ViewMotionVectors`] of cameras without them
+    pub(crate) fallback_motion_vectors: Image,
+    fallback_motion_vectors_view: ImageView,
+    uniform_buffer: Buffer,
+    _descriptor_pool: DescriptorPool,
+    pub(crate) descriptor_set: DescriptorSet,
+    size: UVec2,
+    world_from_view: Mat4,
+    projection: Mat4,
+    settings: PathTracingSettings,
+    scene_generation: (u64, u64),
+    /// Samples accumulated so far
+    pub frame_index: u32,
+    /// The accumulation restarts this frame, its previous content is discarded.
+    pub(crate) reset: bool,
+}
+
+impl AccumulationState {
+    fn new(context: &Context, pipeline: &PathTracingPipelineResources, size: UVec2) -> anyhow::Result<Self> {
+        let accumulation = context.create_image(
+            vk::ImageUsageFlags::STORAGE,
+            MemoryLocation::GpuOnly,
+            vk::Format::R32G32B32A32_SFLOAT,
+            size.x,
+            size.y,
+        )?;
+        let accumulation_view = accumulation.create_image_view()?;
+        let fallback_motion_vectors = context.create_image(
+            vk::ImageUsageFlags::STORAGE,
+            MemoryLocation::GpuOnly,
+            MOTION_VECTORS_FORMAT,
+            1,
+            1,
+        )?;
+        let fallback_motion_vectors_view = fallback_motion_vectors.create_image_view()?;
+        let uniform_buffer = context.create_buffer(
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of::<PathTracingUniform>() as _,
+        )?;
+        let descriptor_pool = context.create_descriptor_pool(1, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 3,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 3,
+            },
+        ])?;
+        let descriptor_set = descriptor_pool.allocate_set(&pipeline.descriptor_set_layout)?;
+
+        Ok(Self {
+            accumulation,
+            accumulation_view,
+            fallback_motion_vectors,
+            fallback_motion_vectors_view,
+            uniform_buffer,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            size,
+            world_from_view: Mat4::NAN,
+            projection: Mat4::NAN,
+            settings: PathTracingSettings::default(),
+            scene_generation: (0, 0),
+            frame_index: 0,
+            reset: true,
+        })
+    }
+
+    /// Whether enough samples were accumulated and tracing can be skipped.
+    pub fn is_converged(&self) -> bool {
+        self.settings.max_samples != 0 && self.frame_index >= self.settings.max_samples
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct PathTracingAccumulation(pub(crate) EntityHashMap<Entity, AccumulationState>);
+
+impl PathTracingAccumulation {
+    pub fn get(&self, entity: Entity) -> Option<&AccumulationState> {
+        self.0.get(&entity)
+    }
+}
+
+type PathTracingCameraQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static ExtractedCamera,
+    &'static ViewTarget,
+    Option<&'static PathTracingSettings>,
+    Option<&'static ViewMotionVectors>,
+)>;
+
+fn prepare_path_tracing_accumulation(
+    mut accumulation: ResMut<PathTracingAccumulation>,
+    pipeline: Res<PathTracingPipeline>,
+    builder: Res<AccelerationStructureBuilder>,
+    scene: Res<RayTracingScene>,
+    gpu_scene: Res<RayTracingGpuScene>,
+    cameras: PathTracingCameraQuery,
+    frame_context: Res<FrameContext>,
+) {
+    let (Some(pipeline), Some(tlas), Some(instance_buffer), Some(material_buffer), Some(motion_buffer)) = (
+        pipeline.resources(),
+        builder.tlas(),
+        gpu_scene.instance_buffer(),
+        gpu_scene.material_buffer(),
+        gpu_scene.motion_buffer(),
+    ) else {
+        accumulation.0.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let scene_generation = (scene.generation(), gpu_scene.generation());
+    let mut alive = HashSet::default();
+
+    for (entity, camera, target, settings, motion_vectors) in cameras.iter() {
+        if camera.render_graph != PATH_TRACING_GRAPH {
+            continue;
+        }
+        let settings = &settings.copied().unwrap_or_default();
+        alive.insert(entity);
+
+        if !matches!(accumulation.0.get(&entity), Some(state) if state.size == target.size) {
+            match AccumulationState::new(context, pipeline, target.size) {
+                Ok(state) => {
+                    accumulation.0.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create path tracing accumulation: {err}");
+                    accumulation.0.remove(&entity);
+                    continue;
+                }
+            }
+        }
+        let state = accumulation.0.get_mut(&entity).unwrap();
+
+        // the state still holds the camera of the previous frame
+        let clip_from_world = camera.projection * camera.world_from_view.inverse();
+        let previous_clip_from_world = if state.world_from_view.is_nan() {
+            clip_from_world
+        } else {
+            state.projection * state.world_from_view.inverse()
+        };
+
+        state.reset = state.world_from_view != camera.world_from_view
+            || state.projection != camera.projection
+            || state.settings != *settings
+            || state.scene_generation != scene_generation;
+        if state.reset {
+            state.world_from_view = camera.world_from_view;
+            state.projection = camera.projection;
+            state.settings = *settings;
+            state.scene_generation = scene_generation;
+            state.frame_index = 0;
+        } else if !state.is_converged() {
+            state.frame_index += 1;
+        }
+
+        let uniform = PathTracingUniform {
+            world_from_view: camera.world_from_view.to_cols_array(),
+            view_from_clip: camera.projection.inverse().to_cols_array(),
+            previous_clip_from_world: previous_clip_from_world.to_cols_array(),
+            frame_index: state.frame_index,
+            samples_per_frame: settings.samples_per_frame.max(1),
+            max_bounces: settings.max_bounces,
+            write_motion_vectors: motion_vectors.is_some() as u32,
+        };
+        if let Err(err) = state.uniform_buffer.copy_data_to_buffer(std::slice::from_ref(&uniform)) {
+            error!("Failed to upload path tracing uniform: {err}");
+        }
+
+        // the TLAS may be swapped by a rebuild, rewrite the whole set every frame
+        state.descriptor_set.update(&[
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::AccelerationStructure {
+                    acceleration_structure: tlas,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &state.accumulation_view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 2,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &target.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 3,
+                kind: WriteDescriptorSetKind::UniformBuffer {
+                    buffer: &state.uniform_buffer,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 4,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: instance_buffer,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 5,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: material_buffer,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 6,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: motion_buffer,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 7,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: motion_vectors.map_or(&state.fallback_motion_vectors_view, |motion_vectors| &motion_vectors.view),
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+        ]);
+    }
+
+    accumulation.0.retain(|entity, _| alive.contains(entity));
+}
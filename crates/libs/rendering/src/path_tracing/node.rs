@@ -0,0 +1,86 @@
+use ash::vk;
+use bevy_ecs::prelude::World;
+use avalanche_hlvk::ImageBarrier;
+use crate::extract::FrameContext;
+use crate::path_tracing::{PathTracingAccumulation, PathTracingPipeline};
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::ViewNode;
+use crate::view::{ViewMotionVectors, ViewTarget};
+
+/// Traces the view entity of the [`PATH_TRACING_GRAPH`](super::PATH_TRACING_GRAPH),
+/// accumulating into the camera's accumulation image and writing the average into its [`ViewTarget`].
+#[derive(Default)]
+pub struct PathTracingNode;
+
+impl ViewNode for PathTracingNode {
+    type ViewQuery = (&'static ViewTarget, Option<&'static ViewMotionVectors>);
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (target, motion_vectors): (&ViewTarget, Option<&ViewMotionVectors>),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pipeline) = world.resource::<PathTracingPipeline>().resources() else {
+            return Ok(());
+        };
+        let Some(state) = world.resource::<PathTracingAccumulation>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+        if state.is_converged() {
+            return Ok(());
+        }
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+
+        let accumulation_old_layout = if state.reset {
+            vk::ImageLayout::UNDEFINED
+        } else {
+            vk::ImageLayout::GENERAL
+        };
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image: &state.accumulation,
+                old_layout: accumulation_old_layout,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
+                dst_stage_mask: vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
+            },
+            // the output is fully overwritten every frame
+            ImageBarrier {
+                image: &target.image,
+                old_layout: vk::ImageLayout::UNDEFINED,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::MEMORY_READ,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
+            },
+            // written along with the output, or only bound when the camera has no motion vectors
+            ImageBarrier {
+                image: motion_vectors.map_or(&state.fallback_motion_vectors, |motion_vectors| &motion_vectors.image),
+                old_layout: vk::ImageLayout::UNDEFINED,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::MEMORY_READ,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::RAY_TRACING_SHADER_KHR,
+            },
+        ]);
+
+        command_buffer.bind_rt_pipeline(&pipeline.pipeline);
+        command_buffer.bind_descriptor_sets(
+            vk::PipelineBindPoint::RAY_TRACING_KHR,
+            &pipeline.layout,
+            0,
+            &[&state.descriptor_set],
+        );
+        command_buffer.trace_rays(&pipeline.shader_binding_table, target.size.x, target.size.y);
+
+        Ok(())
+    }
+}
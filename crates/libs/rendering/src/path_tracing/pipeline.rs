@@ -0,0 +1,155 @@
+use ash::vk;
+use bevy_ecs::prelude::{Query, Res, ResMut, Resource};
+use bevy_log::error;
+use avalanche_hlvk::{
+    Context, DescriptorSetLayout, PipelineLayout, RayTracingPipeline, RayTracingPipelineCreateInfo,
+    RayTracingShaderGroup, ShaderBindingTable,
+};
+use crate::camera::ExtractedCamera;
+use crate::extract::FrameContext;
+use crate::path_tracing::PATH_TRACING_GRAPH;
+use crate::shader::ShaderDirectory;
+
+pub(crate) const RAYGEN_SHADER: &str = "path_tracing/raygen.rgen";
+pub(crate) const MISS_SHADER: &str = "path_tracing/miss.rmiss";
+pub(crate) const SHADOW_MISS_SHADER: &str = "path_tracing/shadow.rmiss";
+pub(crate) const CLOSEST_HIT_SHADER: &str = "path_tracing/closest_hit.rchit";
+
+pub struct PathTracingPipelineResources {
+    pub descriptor_set_layout: DescriptorSetLayout,
+    pub layout: PipelineLayout,
+    pub pipeline: RayTracingPipeline,
+    pub shader_binding_table: ShaderBindingTable,
+}
+
+/// The path tracing pipeline, created the first time a camera uses the path tracing graph.
+#[derive(Resource, Default)]
+pub enum PathTracingPipeline {
+    #[default]
+    Uninitialized,
+    Ready(Box<PathTracingPipelineResources>),
+    /// Creation failed, usually the device lacks ray tracing support or shaders are missing.
+    Failed,
+}
+
+impl PathTracingPipeline {
+    pub fn resources(&self) -> Option<&PathTracingPipelineResources> {
+        match self {
+            PathTracingPipeline::Ready(resources) => Some(resources),
+            _ => None,
+        }
+    }
+}
+
+fn create_pipeline(context: &Context, shaders: &ShaderDirectory) -> anyhow::Result<PathTracingPipelineResources> {
+    // surface normals are computed from the fetched hit triangle positions
+    anyhow::ensure!(
+        context.device_features.ray_tracing_position_fetch,
+        "the path tracer requires the ray tracing position fetch device feature",
+    );
+
+    let stages = vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR;
+    let bindings = [
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+            .descriptor_count(1)
+            .stage_flags(stages)
+            .build(),
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(1)
+            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::RAYGEN_KHR)
+            .build(),
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(2)
+            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::RAYGEN_KHR)
+            .build(),
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(3)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(stages | vk::ShaderStageFlags::MISS_KHR)
+            .build(),
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(4)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::CLOSEST_HIT_KHR)
+            .build(),
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(5)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::CLOSEST_HIT_KHR)
+            .build(),
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(6)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::CLOSEST_HIT_KHR)
+            .build(),
+        vk::DescriptorSetLayoutBinding::builder()
+            .binding(7)
+            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::RAYGEN_KHR)
+            .build(),
+    ];
+    let descriptor_set_layout = context.create_descriptor_set_layout(&bindings)?;
+    let layout = context.create_pipeline_layout(&[&descriptor_set_layout])?;
+
+    let shader_stages = [
+        shaders.load(context, RAYGEN_SHADER, vk::ShaderStageFlags::RAYGEN_KHR)?,
+        shaders.load(context, MISS_SHADER, vk::ShaderStageFlags::MISS_KHR)?,
+        shaders.load(context, SHADOW_MISS_SHADER, vk::ShaderStageFlags::MISS_KHR)?,
+        shaders.load(context, CLOSEST_HIT_SHADER, vk::ShaderStageFlags::CLOSEST_HIT_KHR)?,
+    ];
+    let groups = [
+        RayTracingShaderGroup::RayGen(0),
+        RayTracingShaderGroup::Miss(1),
+        RayTracingShaderGroup::Miss(2),
+        RayTracingShaderGroup::TriangleHit {
+            closest_hit: 3,
+            any_hit: None,
+        },
+    ];
+
+    let pipeline = context.create_ray_tracing_pipeline(&layout, RayTracingPipelineCreateInfo {
+        shaders: &shader_stages,
+        groups: &groups,
+        // bounces are iterated in the raygen shader
+        max_ray_recursion_depth: 1,
+    })?;
+    let shader_binding_table = context.create_shader_binding_table(&pipeline)?;
+
+    Ok(PathTracingPipelineResources {
+        descriptor_set_layout,
+        layout,
+        pipeline,
+        shader_binding_table,
+    })
+}
+
+pub(crate) fn prepare_path_tracing_pipeline(
+    mut pipeline: ResMut<PathTracingPipeline>,
+    shaders: Res<ShaderDirectory>,
+    cameras: Query<&ExtractedCamera>,
+    frame_context: Res<FrameContext>,
+) {
+    if !matches!(*pipeline, PathTracingPipeline::Uninitialized)
+        || !cameras.iter().any(|camera| camera.render_graph == PATH_TRACING_GRAPH) {
+        return;
+    }
+
+    *pipeline = match create_pipeline(frame_context.render_context(), &shaders) {
+        Ok(resources) => PathTracingPipeline::Ready(Box::new(resources)),
+        Err(err) => {
+            error!("Failed to create path tracing pipeline: {err}");
+            PathTracingPipeline::Failed
+        }
+    };
+}
@@ -0,0 +1,174 @@
+use std::cmp::Reverse;
+use bevy_app::{App, Plugin, PostUpdate};
+use bevy_ecs::prelude::{AnyOf, Entity, Event, EventReader, EventWriter, IntoSystemConfigs, Query, Res, ResMut, Resource, With, Without};
+use bevy_math::{Mat4, URect, UVec2, Vec2, Vec3};
+use bevy_transform::prelude::GlobalTransform;
+use bevy_transform::TransformSystem;
+use winit::event::WindowEvent;
+use avalanche_window::{PrimaryWindowComponent, WindowComponent};
+use avalanche_window::event::WinitWindowEvent;
+use crate::camera::{camera_projection, Camera, OrthographicProjection, PerspectiveProjection};
+use crate::render_target::RenderTargetImage;
+use crate::spatial::{Aabb, BoundsQuery, SceneBvh};
+
+pub use avalanche_utils::math::Ray;
+
+/// The ray through `position` from the near plane of a camera, in pixels of its target from the top left corner.
+///
+/// `None` for an empty viewport or a degenerate projection.
+pub fn viewport_to_world_ray(projection: Mat4, world_from_view: Mat4, viewport: URect, position: Vec2) -> Option<Ray> {
+    let size = viewport.size().as_vec2();
+    if size.x <= 0.0 || size.y <= 0.0 {
+        return None;
+    }
+    // the projections flip y, so it grows downwards like pixels do
+    let ndc = (position - viewport.min.as_vec2()) / size * 2.0 - 1.0;
+    let world_from_clip = world_from_view * projection.inverse();
+    let near = world_from_clip.project_point3(ndc.extend(0.0));
+    let far = world_from_clip.project_point3(ndc.extend(1.0));
+    let direction = (far - near).try_normalize()?;
+    Some(Ray { origin: near, direction })
+}
+
+/// Closest entity of a [`SceneBvh`] a ray hits within `max_distance`, with the distance it hits at.
+///
+/// `world_bounds` gives the exact world space bounds of an entity, the leaves of the tree are grown
+/// and only select the candidates. Entities it returns `None` for are ignored.
+pub fn cast_ray(
+    bvh: &SceneBvh,
+    ray: Ray,
+    max_distance: f32,
+    world_bounds: impl Fn(Entity) -> Option<Aabb>,
+) -> Option<(Entity, f32)> {
+    let mut closest: Option<(Entity, f32)> = None;
+    bvh.0.query_ray(ray.origin, ray.direction, max_distance, |entity, entry| {
+        let limit = closest.map_or(max_distance, |(_, distance)| distance);
+        if entry > limit {
+            return;
+        }
+        let Some(distance) = world_bounds(entity).and_then(|aabb| ray.intersect_aabb(&aabb, limit)) else {
+            return;
+        };
+        if closest.is_none_or(|(_, closest)| distance < closest) {
+            closest = Some((entity, distance));
+        }
+    });
+    closest
+}
+
+/// Position of the cursor in physical pixels of the primary window, `None` while it is outside.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+pub struct PointerPosition(pub Option<Vec2>);
+
+/// The closest entity under the pointer, sent every frame the pointer is over the bounds of one.
+///
+/// Hits are tested against the [`Aabb`] of the entities, not their triangles.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PointerHit {
+    /// Camera the ray was cast from
+    pub camera: Entity,
+    pub entity: Entity,
+    /// World space position the ray enters the bounds at
+    pub position: Vec3,
+    pub distance: f32,
+}
+
+/// Casts a ray under the pointer every frame, sending [`PointerHit`]s for object selection.
+///
+/// Only the topmost active camera rendering into the primary window whose viewport contains the pointer picks.
+pub struct PickingPlugin;
+
+impl Plugin for PickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PointerPosition>()
+            .init_resource::<SceneBvh>()
+            .add_event::<PointerHit>()
+            .add_systems(
+                PostUpdate,
+                (update_pointer_position, update_picking_bvh, pick_under_pointer)
+                    .chain()
+                    .after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+fn update_pointer_position(
+    mut pointer: ResMut<PointerPosition>,
+    mut events: EventReader<WinitWindowEvent>,
+    primary_window: Query<&WindowComponent, With<PrimaryWindowComponent>>,
+) {
+    let Ok(window) = primary_window.get_single() else {
+        events.clear();
+        return;
+    };
+    for event in events.read().filter(|event| event.window_id == window.window.id()) {
+        match event.window_event {
+            WindowEvent::CursorMoved { position, .. } => {
+                pointer.0 = Some(Vec2::new(position.x as f32, position.y as f32));
+            }
+            WindowEvent::CursorLeft { .. } => pointer.0 = None,
+            _ => {}
+        }
+    }
+}
+
+fn update_picking_bvh(mut bvh: ResMut<SceneBvh>, entities: BoundsQuery) {
+    bvh.sync(entities.iter());
+}
+
+type PickingCameraQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static Camera,
+    AnyOf<(&'static PerspectiveProjection, &'static OrthographicProjection)>,
+    &'static GlobalTransform,
+), Without<RenderTargetImage>>;
+
+fn pick_under_pointer(
+    pointer: Res<PointerPosition>,
+    primary_window: Query<&WindowComponent, With<PrimaryWindowComponent>>,
+    cameras: PickingCameraQuery,
+    bvh: Res<SceneBvh>,
+    bounds: Query<(&GlobalTransform, &Aabb)>,
+    mut hits: EventWriter<PointerHit>,
+) {
+    let (Some(position), Ok(window)) = (pointer.0, primary_window.get_single()) else {
+        return;
+    };
+    let size = window.window.inner_size();
+    let target_size = UVec2::new(size.width, size.height);
+
+    let viewport = |camera: &Camera| {
+        camera
+            .viewport
+            .map_or(URect::from_corners(UVec2::ZERO, target_size), |viewport| viewport.to_pixels(target_size))
+    };
+
+    // cameras with a higher order are drawn over the others
+    let mut cameras = cameras.iter().filter(|(_, camera, _, _)| camera.is_active).collect::<Vec<_>>();
+    cameras.sort_by_key(|(_, camera, _, _)| Reverse(camera.order));
+    let Some((camera_entity, camera, (perspective, orthographic), transform)) = cameras
+        .into_iter()
+        .find(|(_, camera, _, _)| viewport(camera).as_rect().contains(position)) else {
+        return;
+    };
+
+    let viewport = viewport(camera);
+    let Some(ray) = camera_projection(perspective, orthographic, viewport.size())
+        .and_then(|projection| viewport_to_world_ray(projection, transform.compute_matrix(), viewport, position)) else {
+        return;
+    };
+    let world_bounds = |entity| {
+        bounds
+            .get(entity)
+            .ok()
+            .map(|(transform, aabb)| aabb.transformed(&transform.compute_matrix()))
+    };
+    if let Some((entity, distance)) = cast_ray(&bvh, ray, f32::INFINITY, world_bounds) {
+        hits.send(PointerHit {
+            camera: camera_entity,
+            entity,
+            position: ray.at(distance),
+            distance,
+        });
+    }
+}
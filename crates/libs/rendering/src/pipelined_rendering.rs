@@ -0,0 +1,128 @@
+use async_channel::{Receiver, Sender};
+use bevy_app::{App, AppLabel, Main, Plugin, SubApp};
+use bevy_ecs::prelude::{Mut, Resource, World};
+use bevy_ecs::schedule::MainThreadExecutor;
+use bevy_log::debug;
+use bevy_tasks::ComputeTaskPool;
+use crate::RenderApp;
+
+/// Sub app running on the main thread while the render app is away on the render thread.
+///
+/// Its [`Main`] schedule runs after extraction, in parallel with the render schedule.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, AppLabel)]
+pub struct RenderExtractApp;
+
+/// Hands the render app over to the render thread once extraction is done.
+#[derive(Resource)]
+pub struct MainToRenderAppSender(pub Sender<SubApp>);
+
+/// Gives the render app back to the main thread once the frame is rendered.
+#[derive(Resource)]
+pub struct RenderToMainAppReceiver(pub Receiver<SubApp>);
+
+/// Moves the [`RenderApp`] to its own thread,
+/// so that rendering frame N overlaps with the simulation of frame N + 1.
+///
+/// ```text
+/// |--------------------|--------------------|--------------------|
+/// | main thread        | frame 1 simulation | frame 2 simulation |
+/// |--------------------|--------------------|--------------------|
+/// | render thread      |                    | frame 1 rendering  |
+/// |--------------------|--------------------|--------------------|
+/// ```
+///
+/// Extraction still happens on the main thread, between the two frames,
+/// the commands of the [`ExtractSchedule`](crate::ExtractSchedule) are applied on the render thread.
+///
+/// Must be added after [`RenderingPipelinePlugin`](crate::RenderingPipelinePlugin), it does nothing otherwise.
+#[derive(Default)]
+pub struct PipelinedRenderingPlugin;
+
+impl Plugin for PipelinedRenderingPlugin {
+    fn build(&self, app: &mut App) {
+        if app.get_sub_app(RenderApp).is_err() {
+            return;
+        }
+        app.insert_resource(MainThreadExecutor::new());
+
+        let mut sub_app = App::empty();
+        sub_app.init_schedule(Main);
+        app.insert_sub_app(RenderExtractApp, SubApp::new(sub_app, update_rendering));
+    }
+
+    /// The render app is moved out in `cleanup` as other plugins still access it in `finish`.
+    fn cleanup(&self, app: &mut App) {
+        if app.get_sub_app(RenderExtractApp).is_err() {
+            return;
+        }
+
+        let (app_to_render_sender, app_to_render_receiver) = async_channel::bounded::<SubApp>(1);
+        let (render_to_app_sender, render_to_app_receiver) = async_channel::bounded::<SubApp>(1);
+
+        let mut render_app = app
+            .remove_sub_app(RenderApp)
+            .expect("RenderApp was removed before PipelinedRenderingPlugin could move it to the render thread");
+
+        let executor = app.world.resource::<MainThreadExecutor>();
+        render_app.app.world.insert_resource(executor.clone());
+
+        render_to_app_sender.send_blocking(render_app).unwrap();
+
+        app.insert_resource(MainToRenderAppSender(app_to_render_sender));
+        app.insert_resource(RenderToMainAppReceiver(render_to_app_receiver));
+
+        std::thread::Builder::new()
+            .name("render thread".to_string())
+            .spawn(move || {
+                #[cfg(feature = "trace")]
+                let _span = bevy_utils::tracing::info_span!("render thread").entered();
+
+                let compute_task_pool = ComputeTaskPool::get();
+                loop {
+                    // let the compute task pool use this thread while waiting for the next frame
+                    let sent_app = compute_task_pool
+                        .scope(|s| {
+                            s.spawn(async { app_to_render_receiver.recv().await });
+                        })
+                        .pop();
+                    let Some(Ok(mut render_app)) = sent_app else {
+                        break;
+                    };
+
+                    {
+                        #[cfg(feature = "trace")]
+                        let _span = bevy_utils::tracing::info_span!("render app").entered();
+                        render_app.run();
+                    }
+
+                    if render_to_app_sender.send_blocking(render_app).is_err() {
+                        break;
+                    }
+                }
+
+                debug!("Exiting render thread");
+            })
+            .expect("Failed to spawn render thread");
+    }
+}
+
+/// Waits for the render app to come back, extracts the next frame and sends it to the render thread again.
+fn update_rendering(app_world: &mut World, _sub_app: &mut App) {
+    app_world.resource_scope(|world, main_thread_executor: Mut<MainThreadExecutor>| {
+        // keep running main thread tasks the render world could be waiting on
+        let mut render_app = ComputeTaskPool::get()
+            .scope_with_executor(true, Some(&*main_thread_executor.0), |s| {
+                s.spawn(async {
+                    let receiver = world.resource::<RenderToMainAppReceiver>();
+                    receiver.0.recv().await.unwrap()
+                });
+            })
+            .pop()
+            .unwrap();
+
+        render_app.extract(world);
+
+        let sender = world.resource::<MainToRenderAppSender>();
+        sender.0.send_blocking(render_app).unwrap();
+    });
+}
@@ -1,18 +1,22 @@
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
 use ash::vk;
-use bevy_app::{App, Plugin};
+use bevy_app::{App, Plugin, Update};
 use bevy_ecs::change_detection::Res;
-use bevy_ecs::prelude::{Entity, IntoSystemConfigs, Query, ResMut};
+use bevy_ecs::prelude::{Entity, EventReader, IntoSystemConfigs, Query, ResMut};
 use bevy_ecs::system::Resource;
-use bevy_utils::EntityHashMap;
-use log::warn;
+use bevy_utils::{EntityHashMap, EntityHashSet};
+use log::{info, warn};
 use winit::dpi::PhysicalSize;
-use avalanche_hlvk::{Surface, Swapchain};
-use avalanche_window::{HandleWrapper, PrimaryWindowComponent, WindowComponent};
+use avalanche_hlvk::{Fence, Surface, Swapchain, SwapchainOptions};
+use avalanche_window::{HandleWrapper, PrimaryWindowComponent, WindowComponent, WindowSystemSet};
+use avalanche_window::event::AppLifecycleEvent;
 use crate::{ExtractSchedule, Render, RenderApp, RenderSet};
-use crate::extract::FrameContext;
-use crate::prelude::Extract;
+use crate::color::SrgbPolicy;
+use crate::extract::{ExtractResource, ExtractResourcePlugin, FrameContext};
+use crate::prelude::{Extract, RenderingContext};
 
 pub struct WindowRenderPlugin;
 
@@ -21,6 +25,11 @@ pub struct NonSendMark;
 
 impl Plugin for WindowRenderPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<WindowResizeSettings>()
+            .init_resource::<SrgbPolicy>()
+            .add_plugins(ExtractResourcePlugin::<WindowResizeSettings>::default())
+            .add_systems(Update, window_lifecycle_system.in_set(WindowSystemSet::Update));
+
         if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app
                 .init_non_send_resource::<NonSendMark>()
@@ -31,6 +40,24 @@ impl Plugin for WindowRenderPlugin {
     }
 }
 
+/// Debounces the recreation of the swapchains while windows are resized, e.g. by dragging their border.
+///
+/// A swapchain is recreated once the size of its window didn't change for [`debounce`](Self::debounce),
+/// or right away when the swapchain is out of date and can't be presented to anymore.
+#[derive(Resource, ExtractResource, Clone, Debug)]
+pub struct WindowResizeSettings {
+    /// How long the size must be stable, zero recreates the swapchain on every change.
+    pub debounce: Duration,
+}
+
+impl Default for WindowResizeSettings {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(100),
+        }
+    }
+}
+
 pub struct ExtractedWindow {
     pub entity: Entity,
     pub handle: HandleWrapper,
@@ -39,8 +66,33 @@ pub struct ExtractedWindow {
     pub cached_physical_width: u32,
     pub cached_physical_height: u32,
     pub cached_present_mode: vk::PresentModeKHR,
+    /// Changes when the window moves to a display with another scale factor
+    pub cached_scale_factor: f64,
+    /// The size changed this frame, the swapchain keeps its extent until it is recreated.
     pub size_changed: bool,
     pub present_mode_changed: bool,
+    /// When the size last changed, while the swapchain wasn't recreated for it yet.
+    pub resize_pending_since: Option<Instant>,
+    /// The window is minimized or has no area, its swapchain is neither presented to nor recreated
+    /// and keeps the last size, see [`ExtractedWindow::restored`].
+    pub minimized: bool,
+    /// The window was restored this frame after being minimized, its swapchain is recreated right away.
+    pub restored: bool,
+    /// The swapchain image acquired for the frame, set while the render graph runs and presented afterwards.
+    pub acquired: Option<AcquiredWindowImage>,
+}
+
+/// A swapchain image of an [`ExtractedWindow`] acquired for the frame.
+///
+/// The graphics work of the frame waits until the image is acquired before any transfer, views with a
+/// [`WindowTarget`](crate::render_target::WindowTarget) are copied into it by the `WindowTargetNode`.
+pub struct AcquiredWindowImage {
+    /// Index of the image in the swapchain images
+    pub index: u32,
+    /// Whether a node wrote into the image, which is in `PRESENT_SRC_KHR` afterwards and undefined before
+    pub written: AtomicBool,
+    pub(crate) present_fence: Option<Arc<Fence>>,
+    pub(crate) present_id: Option<u64>,
 }
 
 #[derive(Default, Resource)]
@@ -49,6 +101,20 @@ pub struct ExtractedWindows {
     pub windows: EntityHashMap<Entity, ExtractedWindow>,
 }
 
+impl ExtractedWindows {
+    /// The windows which are presented to this frame, minimized ones are skipped.
+    pub fn presentable(&self) -> impl Iterator<Item = (&Entity, &ExtractedWindow)> {
+        self.windows.iter().filter(|(_, window)| !window.minimized)
+    }
+
+    /// Whether the compositor blends the primary window with its alpha, see [`ClearColor`](crate::camera::ClearColor).
+    pub fn is_primary_transparent(&self) -> bool {
+        self.primary
+            .and_then(|primary| self.windows.get(&primary))
+            .is_some_and(|window| window.swapchain.is_transparent())
+    }
+}
+
 impl Deref for ExtractedWindows {
     type Target = EntityHashMap<Entity, ExtractedWindow>;
 
@@ -67,11 +133,13 @@ fn extract_windows(
     mut extracted_windows: ResMut<ExtractedWindows>,
     windows: Extract<Query<(Entity, &WindowComponent, Option<&PrimaryWindowComponent>)>>,
 ) {
+    let mut presentable = EntityHashSet::default();
     for (entity, window_component, is_primary_window) in windows.iter() {
         if window_component.swapchain.is_none() || window_component.surface.is_none() {
-            // Window is not initialized yet
+            // Window is not initialized yet, or its surface was released while suspended
             continue;
         }
+        presentable.insert(entity);
 
         if is_primary_window.is_some() {
             extracted_windows.primary = Some(entity);
@@ -81,24 +149,48 @@ fn extract_windows(
         let swapchain = window_component.swapchain.as_ref().unwrap().clone();
         let surface = window_component.surface.as_ref().unwrap().clone();
 
+        let inner_size = window_component.window.inner_size();
+        let minimized = inner_size.width == 0
+            || inner_size.height == 0
+            || window_component.window.is_minimized().unwrap_or(false);
         let PhysicalSize {
             height: new_height,
             width: new_width,
-        } = window_component.window.inner_size().clamp(PhysicalSize::new(1, 1), PhysicalSize::new(8192, 8192));
+        } = inner_size.clamp(PhysicalSize::new(1, 1), PhysicalSize::new(8192, 8192));
         let present_mode = window_component.swapchain.as_ref().unwrap().present_mode;
+        let scale_factor = window_component.window.scale_factor();
 
         let extracted_window = extracted_windows.entry(entity).or_insert(ExtractedWindow {
             entity,
             handle,
-            swapchain,
-            surface,
+            swapchain: swapchain.clone(),
+            surface: surface.clone(),
             cached_physical_width: new_width,
             cached_physical_height: new_height,
             cached_present_mode: present_mode,
+            cached_scale_factor: scale_factor,
             size_changed: false,
             present_mode_changed: false,
+            resize_pending_since: None,
+            minimized,
+            restored: false,
+            acquired: None,
         });
 
+        if !Arc::ptr_eq(&extracted_window.swapchain, &swapchain) {
+            extracted_window.swapchain = swapchain;
+            extracted_window.surface = surface;
+        }
+
+        extracted_window.restored = extracted_window.minimized && !minimized;
+        extracted_window.minimized = minimized;
+        if minimized {
+            // the size is zero or meaningless, keep the last one until the window is restored
+            extracted_window.size_changed = false;
+            extracted_window.present_mode_changed = false;
+            continue;
+        }
+
         extracted_window.size_changed = new_width != extracted_window.cached_physical_width
             || new_height != extracted_window.cached_physical_height;
         extracted_window.present_mode_changed = extracted_window.cached_present_mode != present_mode;
@@ -106,26 +198,110 @@ fn extract_windows(
         if extracted_window.size_changed {
             extracted_window.cached_physical_width = new_width;
             extracted_window.cached_physical_height = new_height;
+            extracted_window.resize_pending_since = Some(Instant::now());
+        }
+
+        // the surface capabilities are only queried again after a resize or a display change
+        let display_changed = extracted_window.cached_scale_factor != scale_factor;
+        if extracted_window.size_changed || display_changed || extracted_window.restored {
+            extracted_window.cached_scale_factor = scale_factor;
+            extracted_window.swapchain.invalidate_surface_info();
         }
 
         if extracted_window.present_mode_changed {
             extracted_window.cached_present_mode = present_mode;
         }
     }
+
+    // drop the swapchains of closed and suspended windows
+    extracted_windows.retain(|entity, _| presentable.contains(entity));
+    if extracted_windows.primary.is_some_and(|primary| !presentable.contains(&primary)) {
+        extracted_windows.primary = None;
+    }
 }
 
-fn prepare_windows(extracted_windows: ResMut<ExtractedWindows>, frame_context: Res<FrameContext>) {
-    for (_entity, window) in extracted_windows.windows.iter() {
+/// Recreate the swapchains which are out of date, or whose window size is stable since it changed,
+/// see [`WindowResizeSettings`]. Minimized windows are skipped until they are restored.
+fn prepare_windows(
+    mut extracted_windows: ResMut<ExtractedWindows>,
+    frame_context: Res<FrameContext>,
+    settings: Res<WindowResizeSettings>,
+) {
+    for window in extracted_windows.windows.values_mut() {
+        if window.minimized {
+            // a swapchain can't have an empty extent, it is recreated once the window is restored
+            continue;
+        }
+
+        let swapchain = window.swapchain.as_ref();
+        let resize_stable = window.resize_pending_since.is_some_and(|since| {
+            window.restored || since.elapsed() >= settings.debounce
+        });
+        // a suboptimal swapchain still presents, while resizing it is recreated once the size is stable
+        let suboptimal = swapchain.is_suboptimal() && window.resize_pending_since.is_none();
+        if !swapchain.is_out_of_date() && !resize_stable && !suboptimal {
+            continue;
+        }
+
         #[cfg(feature = "trace")]
         let _span = bevy_utils::tracing::info_span!("window swapchain recreated").entered();
 
-        if window.size_changed {
-            if let Err(err) = window.swapchain
-                .as_ref()
-                .resize(frame_context.render_context(), window.cached_physical_width, window.cached_physical_height) {
-                warn!("[Window] Failed to recreate swapchain for window: {err}");
-            }
+        window.resize_pending_since = None;
+        if let Err(err) = swapchain.resize(frame_context.render_context(), window.cached_physical_width, window.cached_physical_height) {
+            warn!("[Window] Failed to recreate swapchain for window: {err}");
         }
     }
+}
+
+/// Release the surfaces of the windows when the app is suspended and create them again once resumed.
+///
+/// Native windows can be destroyed by the platform while the app is in background,
+/// the render schedule is paused in between, see [`AppLifecycle`](avalanche_window::AppLifecycle).
+fn window_lifecycle_system(
+    mut lifecycle_events: EventReader<AppLifecycleEvent>,
+    mut windows: Query<&mut WindowComponent>,
+    rendering_context: Option<Res<RenderingContext>>,
+    srgb_policy: Res<SrgbPolicy>,
+) {
+    let Some(event) = lifecycle_events.read().last() else {
+        return;
+    };
+    let Some(rendering_context) = rendering_context else {
+        return;
+    };
 
+    match event {
+        AppLifecycleEvent::Suspended => {
+            info!("[Window] App suspended, releasing window surfaces");
+            for mut window in windows.iter_mut() {
+                window.swapchain = None;
+                window.surface = None;
+            }
+        }
+        AppLifecycleEvent::Resumed => {
+            for mut window in windows.iter_mut().filter(|window| window.surface.is_none()) {
+                let handle = HandleWrapper::from(window.window.as_ref());
+                let surface = match rendering_context.context.create_surface(&handle, &handle) {
+                    Ok(surface) => Arc::new(surface),
+                    Err(err) => {
+                        warn!("[Window] Failed to create surface for resumed window: {err}");
+                        continue;
+                    }
+                };
+                let PhysicalSize { width, height } = window.window.inner_size();
+                let options = SwapchainOptions {
+                    transparent: window.transparent,
+                    srgb: srgb_policy.prefers_srgb_swapchain(),
+                };
+                match Swapchain::with_options(&rendering_context.context, surface.clone(), width, height, options) {
+                    Ok(swapchain) => {
+                        window.render_device = Some(rendering_context.context.device.clone());
+                        window.surface = Some(surface);
+                        window.swapchain = Some(Arc::new(swapchain));
+                    }
+                    Err(err) => warn!("[Window] Failed to create swapchain for resumed window: {err}"),
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{IntoSystemConfigs, Res, ResMut, Resource};
+use bevy_log::warn;
+use avalanche_hlvk::TimestampQueryPool;
+use crate::{Render, RenderApp, RenderSet};
+use crate::extract::{FrameContext, release_referenced_rendering_context};
+
+/// Number of frames [`GpuProfiler::average_frame_time`] is computed over.
+const FRAME_TIME_HISTORY: usize = 16;
+
+/// Measures how long the GPU spends on the commands of each frame with timestamp queries.
+///
+/// Lives in the render world, results are read once the frame fence was waited for.
+#[derive(Resource, Default)]
+pub struct GpuProfiler {
+    query_pool: Option<TimestampQueryPool<2>>,
+    /// The queries of the current frame were written
+    recording: bool,
+    unsupported: bool,
+    frame_times: VecDeque<Duration>,
+    /// Begin and end timestamps of the frame read this frame
+    frame_timestamps: Option<[u64; 2]>,
+}
+
+impl GpuProfiler {
+    /// GPU time of the last finished frame.
+    pub fn last_frame_time(&self) -> Option<Duration> {
+        self.frame_times.back().copied()
+    }
+
+    /// Begin and end GPU timestamps in nanoseconds of the frame whose results were read this frame.
+    ///
+    /// The GPU clock has its own origin, only differences and other GPU timestamps compare to them.
+    pub fn last_frame_timestamps(&self) -> Option<[u64; 2]> {
+        self.frame_timestamps
+    }
+
+    pub fn average_frame_time(&self) -> Option<Duration> {
+        if self.frame_times.is_empty() {
+            return None;
+        }
+        Some(self.frame_times.iter().sum::<Duration>() / self.frame_times.len() as u32)
+    }
+
+    /// Called by the render graph runner before the frame commands are submitted.
+    pub(crate) fn end_frame(&self, frame_context: &FrameContext) {
+        let (Some(query_pool), true) = (&self.query_pool, self.recording) else {
+            return;
+        };
+        if let Some(command_buffer) = frame_context.command_buffer(0) {
+            command_buffer.write_timestamp(vk::PipelineStageFlags2::BOTTOM_OF_PIPE, query_pool, 1);
+        }
+    }
+}
+
+pub struct GpuProfilerPlugin;
+
+impl Plugin for GpuProfilerPlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<GpuProfiler>()
+                .add_systems(Render, (
+                    begin_gpu_frame
+                        .in_set(RenderSet::ManageViews)
+                        .before(RenderSet::PrepareAssets),
+                    read_gpu_frame
+                        .in_set(RenderSet::Cleanup)
+                        .after(release_referenced_rendering_context),
+                ));
+        }
+    }
+}
+
+fn begin_gpu_frame(mut profiler: ResMut<GpuProfiler>, frame_context: Res<FrameContext>) {
+    if profiler.unsupported {
+        return;
+    }
+    if profiler.query_pool.is_none() {
+        match frame_context.render_context().create_timestamp_query_pool::<2>() {
+            Ok(query_pool) => profiler.query_pool = Some(query_pool),
+            Err(err) => {
+                warn!("GPU profiling disabled, failed to create timestamp queries: {err}");
+                profiler.unsupported = true;
+                return;
+            }
+        }
+    }
+
+    let Some(command_buffer) = frame_context.command_buffer(0) else {
+        return;
+    };
+    let query_pool = profiler.query_pool.as_ref().unwrap();
+    command_buffer.reset_all_timestamp_queries_from_pool(query_pool);
+    command_buffer.write_timestamp(vk::PipelineStageFlags2::TOP_OF_PIPE, query_pool, 0);
+    profiler.recording = true;
+}
+
+pub fn read_gpu_frame(mut profiler: ResMut<GpuProfiler>) {
+    profiler.frame_timestamps = None;
+    if !std::mem::take(&mut profiler.recording) {
+        return;
+    }
+    let Some(query_pool) = &profiler.query_pool else {
+        return;
+    };
+
+    match query_pool.try_get_all_results() {
+        Ok(Some([begin, end])) => {
+            if profiler.frame_times.len() == FRAME_TIME_HISTORY {
+                profiler.frame_times.pop_front();
+            }
+            profiler.frame_times.push_back(Duration::from_nanos(end.saturating_sub(begin)));
+            profiler.frame_timestamps = Some([begin, end]);
+        }
+        Ok(None) => {}
+        Err(err) => warn!("Failed to read GPU frame timestamps: {err}"),
+    }
+}
@@ -0,0 +1,45 @@
+mod blas;
+mod builder;
+mod gpu_scene;
+mod scene;
+
+pub use blas::*;
+pub use builder::*;
+pub use gpu_scene::*;
+pub use scene::*;
+
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::IntoSystemConfigs;
+use crate::{ExtractSchedule, Render, RenderApp, RenderSet};
+
+/// Acceleration structure management for the ray tracing renderers.
+///
+/// Does nothing if the context was created without ray tracing support.
+pub struct RayTracingPlugin;
+
+impl Plugin for RayTracingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AccelerationStructureBuildEvent>()
+            .register_type::<RayTracingMaterial>()
+            .register_type::<[f32; 3]>()
+            .register_type::<[f32; 4]>();
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<AccelerationStructureBuilder>()
+                .init_resource::<RayTracingScene>()
+                .init_resource::<RayTracingGpuScene>()
+                .add_systems(ExtractSchedule, (
+                    extract_acceleration_structure_events,
+                    extract_ray_tracing_scene,
+                    extract_ray_tracing_gpu_scene,
+                ))
+                .add_systems(Render, (
+                    prepare_ray_tracing_scene,
+                    prepare_ray_tracing_gpu_scene,
+                    prepare_ray_tracing_instance_motion,
+                    schedule_acceleration_structure_builds,
+                ).chain().in_set(RenderSet::PrepareResources));
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use ash::vk;
+use avalanche_utils::define_atomic_id;
+use crate::prelude::Buffer;
+
+define_atomic_id!(BlasHandle);
+
+/// Triangle geometry of a bottom level acceleration structure.
+///
+/// Buffers must be created with `SHADER_DEVICE_ADDRESS` and
+/// `ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR` usages.
+#[derive(Clone, Debug)]
+pub struct BlasGeometry {
+    pub vertex_buffer: Buffer,
+    pub vertex_format: vk::Format,
+    pub vertex_stride: vk::DeviceSize,
+    pub vertex_count: u32,
+    pub index_buffer: Option<Buffer>,
+    pub index_type: vk::IndexType,
+    pub primitive_count: u32,
+    pub opaque: bool,
+}
+
+impl BlasGeometry {
+    pub(crate) fn as_vk_geometry(&self) -> vk::AccelerationStructureGeometryKHR {
+        let index_data = self
+            .index_buffer
+            .as_ref()
+            .map(|buffer| vk::DeviceOrHostAddressConstKHR {
+                device_address: buffer.get_device_address(),
+            })
+            .unwrap_or_default();
+        let index_type = if self.index_buffer.is_some() {
+            self.index_type
+        } else {
+            vk::IndexType::NONE_KHR
+        };
+
+        let triangles = vk::AccelerationStructureGeometryTrianglesDataKHR::builder()
+            .vertex_format(self.vertex_format)
+            .vertex_data(vk::DeviceOrHostAddressConstKHR {
+                device_address: self.vertex_buffer.get_device_address(),
+            })
+            .vertex_stride(self.vertex_stride)
+            .max_vertex(self.vertex_count.saturating_sub(1))
+            .index_type(index_type)
+            .index_data(index_data)
+            .build();
+
+        let flags = if self.opaque {
+            vk::GeometryFlagsKHR::OPAQUE
+        } else {
+            vk::GeometryFlagsKHR::empty()
+        };
+
+        vk::AccelerationStructureGeometryKHR::builder()
+            .geometry_type(vk::GeometryTypeKHR::TRIANGLES)
+            .geometry(vk::AccelerationStructureGeometryDataKHR { triangles })
+            .flags(flags)
+            .build()
+    }
+
+    pub(crate) fn as_vk_range(&self) -> vk::AccelerationStructureBuildRangeInfoKHR {
+        vk::AccelerationStructureBuildRangeInfoKHR::builder()
+            .primitive_count(self.primitive_count)
+            .build()
+    }
+}
+
+/// A pending bottom level acceleration structure build.
+#[derive(Clone, Debug)]
+pub struct BlasBuildRequest {
+    pub handle: BlasHandle,
+    pub geometries: Vec<BlasGeometry>,
+    pub flags: vk::BuildAccelerationStructureFlagsKHR,
+}
+
+impl BlasBuildRequest {
+    pub fn new(geometries: Vec<BlasGeometry>) -> Self {
+        Self {
+            handle: BlasHandle::new(),
+            geometries,
+            flags: vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE,
+        }
+    }
+}
+
+/// An instance referencing a built BLAS inside the top level acceleration structure.
+#[derive(Clone, Copy, Debug)]
+pub struct TlasInstance {
+    pub blas: BlasHandle,
+    /// Row-major 3x4 object to world matrix
+    pub transform: [f32; 12],
+    pub custom_index: u32,
+    pub mask: u8,
+    pub sbt_record_offset: u32,
+    pub flags: vk::GeometryInstanceFlagsKHR,
+}
+
+impl TlasInstance {
+    pub fn new(blas: BlasHandle, transform: [f32; 12]) -> Self {
+        Self {
+            blas,
+            transform,
+            custom_index: 0,
+            mask: 0xFF,
+            sbt_record_offset: 0,
+            flags: vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE,
+        }
+    }
+
+    pub(crate) fn as_vk_instance(&self, blas_address: u64) -> vk::AccelerationStructureInstanceKHR {
+        vk::AccelerationStructureInstanceKHR {
+            transform: vk::TransformMatrixKHR { matrix: self.transform },
+            instance_custom_index_and_mask: vk::Packed24_8::new(self.custom_index, self.mask),
+            instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(
+                self.sbt_record_offset,
+                self.flags.as_raw() as u8,
+            ),
+            acceleration_structure_reference: vk::AccelerationStructureReferenceKHR {
+                device_handle: blas_address,
+            },
+        }
+    }
+}
@@ -0,0 +1,590 @@
+use std::collections::VecDeque;
+use std::mem::size_of;
+use std::sync::Arc;
+use ash::vk;
+use bevy_ecs::event::{Event, Events};
+use bevy_ecs::prelude::{Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_utils::HashMap;
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{AccelerationStructure, Buffer as VkBuffer, CommandBuffer, CommandPool, Context, Fence};
+use crate::extract::FrameContext;
+use crate::MainWorld;
+use crate::raytracing::{BlasBuildRequest, BlasHandle, TlasInstance};
+
+/// Progress of the asynchronous acceleration structure builds.
+///
+/// Sent to the main world at the beginning of the next extraction.
+#[derive(Event, Clone, Debug)]
+pub enum AccelerationStructureBuildEvent {
+    BlasBuilt(BlasHandle),
+    /// The request was dropped, enqueue it again to retry.
+    BlasFailed(BlasHandle),
+    /// A new top level acceleration structure replaced the previous one.
+    TlasSwapped { instance_count: usize },
+    Progress {
+        pending: usize,
+        building: usize,
+        built: usize,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct AccelerationStructureBuildSettings {
+    /// Upper bound of scratch memory used by builds in flight.
+    /// A single request larger than the budget is still built, but alone.
+    pub scratch_budget: vk::DeviceSize,
+    /// How many batches could be recorded before the oldest one finishes.
+    pub max_batches_in_flight: usize,
+}
+
+impl Default for AccelerationStructureBuildSettings {
+    fn default() -> Self {
+        Self {
+            scratch_budget: 32 * 1024 * 1024,
+            max_batches_in_flight: 2,
+        }
+    }
+}
+
+enum BuildBatchKind {
+    Blas {
+        requests: Vec<BlasBuildRequest>,
+        structures: Vec<AccelerationStructure>,
+    },
+    Tlas {
+        tlas: Box<BuiltTlas>,
+        instance_count: usize,
+    },
+}
+
+/// Builds recorded on the compute queue, resources are kept alive until the fence is signaled.
+struct BuildBatch {
+    command_pool: Arc<CommandPool>,
+    command_buffer: CommandBuffer,
+    fence: Fence,
+    scratch_size: vk::DeviceSize,
+    _scratch_buffer: VkBuffer,
+    kind: Option<BuildBatchKind>,
+}
+
+impl BuildBatch {
+    fn is_tlas(&self) -> bool {
+        matches!(self.kind, Some(BuildBatchKind::Tlas { .. }))
+    }
+}
+
+impl Drop for BuildBatch {
+    fn drop(&mut self) {
+        let _ = self.fence.wait(None);
+        let _ = self.command_pool.free_command_buffer(&self.command_buffer);
+    }
+}
+
+struct BuiltTlas {
+    structure: Arc<AccelerationStructure>,
+    /// The BLAS referenced by device address must outlive the TLAS
+    _dependencies: Vec<Arc<AccelerationStructure>>,
+    /// BLAS of each instance in build order, a refit must keep this layout
+    layout: Vec<BlasHandle>,
+    instance_buffer: VkBuffer,
+    /// Scratch memory for in-place updates and its aligned device address
+    update_scratch: Option<(VkBuffer, u64)>,
+}
+
+/// Schedules BLAS builds on the async compute queue so they don't block the frame.
+///
+/// Requests are batched within [`AccelerationStructureBuildSettings::scratch_budget`] and
+/// polled every frame, the TLAS submitted by [`AccelerationStructureBuilder::submit_tlas`]
+/// is only built and swapped in once all of its BLAS are ready.
+#[derive(Resource, Default)]
+pub struct AccelerationStructureBuilder {
+    pub settings: AccelerationStructureBuildSettings,
+    pending: VecDeque<BlasBuildRequest>,
+    building: Vec<BuildBatch>,
+    blases: HashMap<BlasHandle, Arc<AccelerationStructure>>,
+    pending_tlas: Option<Vec<TlasInstance>>,
+    tlas: Option<BuiltTlas>,
+    command_pool: Option<Arc<CommandPool>>,
+    events: Vec<AccelerationStructureBuildEvent>,
+    last_progress: (usize, usize, usize),
+}
+
+impl AccelerationStructureBuilder {
+    pub fn enqueue_blas(&mut self, request: BlasBuildRequest) -> BlasHandle {
+        let handle = request.handle;
+        self.pending.push_back(request);
+        handle
+    }
+
+    /// Forget a built BLAS, it is destroyed once no TLAS references it anymore.
+    pub fn remove_blas(&mut self, handle: BlasHandle) {
+        self.pending.retain(|request| request.handle != handle);
+        self.blases.remove(&handle);
+    }
+
+    pub fn get_blas(&self, handle: BlasHandle) -> Option<&Arc<AccelerationStructure>> {
+        self.blases.get(&handle)
+    }
+
+    pub fn is_built(&self, handle: BlasHandle) -> bool {
+        self.blases.contains_key(&handle)
+    }
+
+    /// Replace the instances of the next TLAS.
+    /// The current TLAS stays in use until all referenced BLAS are built.
+    pub fn submit_tlas(&mut self, instances: Vec<TlasInstance>) {
+        self.pending_tlas = Some(instances);
+    }
+
+    pub fn tlas(&self) -> Option<&Arc<AccelerationStructure>> {
+        self.tlas.as_ref().map(|tlas| &tlas.structure)
+    }
+
+    #[inline]
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    #[inline]
+    pub fn building_count(&self) -> usize {
+        self.building
+            .iter()
+            .map(|batch| match &batch.kind {
+                Some(BuildBatchKind::Blas { requests, .. }) => requests.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    fn scratch_in_use(&self) -> vk::DeviceSize {
+        self.building.iter().map(|batch| batch.scratch_size).sum()
+    }
+
+    fn command_pool(&mut self, context: &Context) -> anyhow::Result<Arc<CommandPool>> {
+        if let Some(pool) = &self.command_pool {
+            return Ok(pool.clone());
+        }
+
+        let pool = Arc::new(context.create_command_pool(
+            context.compute_queue_family,
+            Some(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
+        )?);
+        self.command_pool = Some(pool.clone());
+        Ok(pool)
+    }
+
+    fn poll_finished(&mut self) {
+        let mut index = 0;
+        while index < self.building.len() {
+            match self.building[index].fence.is_signaled() {
+                Ok(false) => index += 1,
+                Ok(true) => {
+                    let mut batch = self.building.swap_remove(index);
+                    match batch.kind.take() {
+                        Some(BuildBatchKind::Blas { requests, structures }) => {
+                            for (request, structure) in requests.into_iter().zip(structures) {
+                                self.blases.insert(request.handle, Arc::new(structure));
+                                self.events.push(AccelerationStructureBuildEvent::BlasBuilt(request.handle));
+                            }
+                        }
+                        Some(BuildBatchKind::Tlas { tlas, instance_count }) => {
+                            self.tlas = Some(*tlas);
+                            self.events.push(AccelerationStructureBuildEvent::TlasSwapped { instance_count });
+                        }
+                        None => {}
+                    }
+                }
+                Err(err) => {
+                    error!("Failed to query acceleration structure build fence: {err}");
+                    let mut batch = self.building.swap_remove(index);
+                    if let Some(BuildBatchKind::Blas { requests, .. }) = batch.kind.take() {
+                        self.events.extend(
+                            requests.iter().map(|request| AccelerationStructureBuildEvent::BlasFailed(request.handle)),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Record `record` into a fresh command buffer and submit it to the compute queue.
+    fn submit(
+        &mut self,
+        context: &Context,
+        record: impl FnOnce(&CommandBuffer),
+    ) -> anyhow::Result<(Arc<CommandPool>, CommandBuffer, Fence)> {
+        let command_pool = self.command_pool(context)?;
+        let command_buffer = command_pool.allocate_command_buffer(vk::CommandBufferLevel::PRIMARY)?;
+        command_buffer.begin(Some(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT))?;
+        record(&command_buffer);
+        command_buffer.end()?;
+
+        let fence = context.create_fence(None)?;
+        context.compute_queue.submit_1_3(&command_buffer, None, None, &fence)?;
+
+        Ok((command_pool, command_buffer, fence))
+    }
+
+    fn create_scratch_buffer(context: &Context, size: vk::DeviceSize, alignment: vk::DeviceSize) -> anyhow::Result<(VkBuffer, u64)> {
+        let buffer = VkBuffer::new(
+            context.device.clone(),
+            context.allocator.clone(),
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            MemoryLocation::GpuOnly,
+            size + alignment,
+            "acceleration structure scratch",
+        )?;
+        let address = align_up(buffer.get_device_address(), alignment);
+        Ok((buffer, address))
+    }
+
+    fn record_blas_builds(&mut self, context: &Context) -> anyhow::Result<()> {
+        let ray_tracing = context.ray_tracing.clone().unwrap();
+        let alignment = (ray_tracing.min_scratch_offset_alignment as vk::DeviceSize).max(1);
+
+        while self.building.len() < self.settings.max_batches_in_flight && !self.pending.is_empty() {
+            let budget = self.settings.scratch_budget.saturating_sub(self.scratch_in_use());
+
+            let mut requests = Vec::new();
+            let mut build_sizes = Vec::new();
+            let mut scratch_size = 0;
+            while let Some(request) = self.pending.front_mut() {
+                if context.device_features.ray_tracing_position_fetch {
+                    // hit shaders read triangle positions straight from the BLAS
+                    request.flags |= vk::BuildAccelerationStructureFlagsKHR::ALLOW_DATA_ACCESS;
+                }
+                let geometries = request.geometries.iter().map(|g| g.as_vk_geometry()).collect::<Vec<_>>();
+                let primitive_counts = request.geometries.iter().map(|g| g.primitive_count).collect::<Vec<_>>();
+                let build_info = vk::AccelerationStructureBuildGeometryInfoKHR::builder()
+                    .ty(vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
+                    .flags(request.flags)
+                    .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+                    .geometries(&geometries);
+                let sizes = ray_tracing.get_build_sizes(&build_info, &primitive_counts);
+                let request_scratch_size = align_up(sizes.build_scratch_size, alignment);
+
+                if scratch_size + request_scratch_size > budget {
+                    // oversized requests are built alone once nothing else is in flight
+                    let build_alone = requests.is_empty() && self.building.is_empty();
+                    if !build_alone {
+                        break;
+                    }
+                }
+
+                scratch_size += request_scratch_size;
+                build_sizes.push(sizes);
+                requests.push(self.pending.pop_front().unwrap());
+            }
+
+            if requests.is_empty() {
+                break;
+            }
+
+            match self.record_blas_batch(context, &requests, &build_sizes, scratch_size, alignment) {
+                Ok((mut batch, structures)) => {
+                    batch.kind = Some(BuildBatchKind::Blas { requests, structures });
+                    self.building.push(batch);
+                }
+                Err(err) => {
+                    // the requests left the queue, report them so they can be enqueued again
+                    self.events.extend(
+                        requests.iter().map(|request| AccelerationStructureBuildEvent::BlasFailed(request.handle)),
+                    );
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create the structures of `requests` and submit their builds, the batch has no kind yet.
+    fn record_blas_batch(
+        &mut self,
+        context: &Context,
+        requests: &[BlasBuildRequest],
+        build_sizes: &[vk::AccelerationStructureBuildSizesInfoKHR],
+        scratch_size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> anyhow::Result<(BuildBatch, Vec<AccelerationStructure>)> {
+        let structures = build_sizes
+            .iter()
+            .map(|sizes| context.create_acceleration_structure(
+                vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+                sizes.acceleration_structure_size,
+            ))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let (scratch_buffer, scratch_address) = Self::create_scratch_buffer(context, scratch_size, alignment)?;
+
+        let geometries = requests
+            .iter()
+            .map(|request| request.geometries.iter().map(|g| g.as_vk_geometry()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let ranges = requests
+            .iter()
+            .map(|request| request.geometries.iter().map(|g| g.as_vk_range()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        let mut scratch_offset = 0;
+        let build_infos = requests
+            .iter()
+            .zip(geometries.iter())
+            .zip(structures.iter().zip(build_sizes.iter()))
+            .map(|((request, geometries), (structure, sizes))| {
+                let info = vk::AccelerationStructureBuildGeometryInfoKHR::builder()
+                    .ty(vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL)
+                    .flags(request.flags)
+                    .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+                    .geometries(geometries)
+                    .dst_acceleration_structure(structure.handle())
+                    .scratch_data(vk::DeviceOrHostAddressKHR {
+                        device_address: scratch_address + scratch_offset,
+                    })
+                    .build();
+                scratch_offset += align_up(sizes.build_scratch_size, alignment);
+                info
+            })
+            .collect::<Vec<_>>();
+        let range_refs = ranges.iter().map(|r| r.as_slice()).collect::<Vec<_>>();
+
+        let (command_pool, command_buffer, fence) = self.submit(context, |command_buffer| {
+            command_buffer.build_acceleration_structures(&build_infos, &range_refs);
+            command_buffer.acceleration_structure_build_barrier();
+        })?;
+
+        let batch = BuildBatch {
+            command_pool,
+            command_buffer,
+            fence,
+            scratch_size,
+            _scratch_buffer: scratch_buffer,
+            kind: None,
+        };
+        Ok((batch, structures))
+    }
+
+    fn record_tlas_build(&mut self, context: &Context) -> anyhow::Result<()> {
+        // only one TLAS is built at a time, newer submissions replace the pending one meanwhile
+        if self.building.iter().any(BuildBatch::is_tlas) {
+            return Ok(());
+        }
+        let Some(instances) = &self.pending_tlas else {
+            return Ok(());
+        };
+        if !instances.iter().all(|instance| self.blases.contains_key(&instance.blas)) {
+            return Ok(());
+        }
+
+        let instances = self.pending_tlas.take().unwrap();
+        match self.record_tlas_batch(context, &instances) {
+            Ok(batch) => {
+                self.building.push(batch);
+                Ok(())
+            }
+            Err(err) => {
+                // built again with the next schedule, unless newer instances replace them meanwhile
+                self.pending_tlas = Some(instances);
+                Err(err)
+            }
+        }
+    }
+
+    fn record_tlas_batch(&mut self, context: &Context, instances: &[TlasInstance]) -> anyhow::Result<BuildBatch> {
+        let ray_tracing = context.ray_tracing.clone().unwrap();
+        let alignment = (ray_tracing.min_scratch_offset_alignment as vk::DeviceSize).max(1);
+
+        let mut dependencies: HashMap<BlasHandle, Arc<AccelerationStructure>> = HashMap::default();
+        let vk_instances = instances
+            .iter()
+            .map(|instance| {
+                let blas = self.blases[&instance.blas].clone();
+                let address = blas.address;
+                dependencies.insert(instance.blas, blas);
+                instance.as_vk_instance(address)
+            })
+            .collect::<Vec<_>>();
+
+        let instance_buffer = VkBuffer::new(
+            context.device.clone(),
+            context.allocator.clone(),
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            MemoryLocation::CpuToGpu,
+            (size_of::<vk::AccelerationStructureInstanceKHR>() * vk_instances.len().max(1)) as _,
+            "acceleration structure instances",
+        )?;
+        instance_buffer.copy_data_to_buffer(&vk_instances)?;
+
+        let geometry = tlas_geometry(&instance_buffer);
+        let mut build_info = vk::AccelerationStructureBuildGeometryInfoKHR::builder()
+            .ty(vk::AccelerationStructureTypeKHR::TOP_LEVEL)
+            .flags(TLAS_BUILD_FLAGS)
+            .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+            .geometries(std::slice::from_ref(&geometry))
+            .build();
+        let sizes = ray_tracing.get_build_sizes(&build_info, &[vk_instances.len() as u32]);
+
+        let structure = context.create_acceleration_structure(
+            vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+            sizes.acceleration_structure_size,
+        )?;
+        let scratch_size = align_up(sizes.build_scratch_size, alignment);
+        let (scratch_buffer, scratch_address) = Self::create_scratch_buffer(context, scratch_size, alignment)?;
+        build_info.dst_acceleration_structure = structure.handle();
+        build_info.scratch_data = vk::DeviceOrHostAddressKHR { device_address: scratch_address };
+        let update_scratch = (sizes.update_scratch_size > 0)
+            .then(|| Self::create_scratch_buffer(context, sizes.update_scratch_size, alignment))
+            .transpose()?;
+
+        let range = vk::AccelerationStructureBuildRangeInfoKHR::builder()
+            .primitive_count(vk_instances.len() as u32)
+            .build();
+
+        let (command_pool, command_buffer, fence) = self.submit(context, |command_buffer| {
+            command_buffer.build_acceleration_structures(
+                std::slice::from_ref(&build_info),
+                &[std::slice::from_ref(&range)],
+            );
+            command_buffer.acceleration_structure_build_barrier();
+        })?;
+
+        Ok(BuildBatch {
+            command_pool,
+            command_buffer,
+            fence,
+            scratch_size,
+            _scratch_buffer: scratch_buffer,
+            kind: Some(BuildBatchKind::Tlas {
+                tlas: Box::new(BuiltTlas {
+                    structure: Arc::new(structure),
+                    _dependencies: dependencies.into_values().collect(),
+                    layout: instances.iter().map(|instance| instance.blas).collect(),
+                    instance_buffer,
+                    update_scratch,
+                }),
+                instance_count: instances.len(),
+            }),
+        })
+    }
+
+    /// Update the current TLAS in place with new instance transforms.
+    ///
+    /// Returns `false` if a refit isn't possible because the instance layout changed
+    /// or a rebuild is still on the way, the caller should [`Self::submit_tlas`] instead.
+    pub fn refit_tlas(&self, command_buffer: &CommandBuffer, instances: &[TlasInstance]) -> anyhow::Result<bool> {
+        let Some(tlas) = &self.tlas else {
+            return Ok(false);
+        };
+        let Some((_, update_scratch_address)) = &tlas.update_scratch else {
+            return Ok(false);
+        };
+        if self.pending_tlas.is_some() || self.building.iter().any(BuildBatch::is_tlas) {
+            return Ok(false);
+        }
+        if tlas.layout.len() != instances.len()
+            || tlas.layout.iter().zip(instances).any(|(blas, instance)| *blas != instance.blas) {
+            return Ok(false);
+        }
+
+        let Some(vk_instances) = instances
+            .iter()
+            .map(|instance| self.blases.get(&instance.blas).map(|blas| instance.as_vk_instance(blas.address)))
+            .collect::<Option<Vec<_>>>() else {
+            return Ok(false);
+        };
+        tlas.instance_buffer.copy_data_to_buffer(&vk_instances)?;
+
+        let geometry = tlas_geometry(&tlas.instance_buffer);
+        let build_info = vk::AccelerationStructureBuildGeometryInfoKHR::builder()
+            .ty(vk::AccelerationStructureTypeKHR::TOP_LEVEL)
+            .flags(TLAS_BUILD_FLAGS)
+            .mode(vk::BuildAccelerationStructureModeKHR::UPDATE)
+            .geometries(std::slice::from_ref(&geometry))
+            .src_acceleration_structure(tlas.structure.handle())
+            .dst_acceleration_structure(tlas.structure.handle())
+            .scratch_data(vk::DeviceOrHostAddressKHR { device_address: *update_scratch_address })
+            .build();
+        let range = vk::AccelerationStructureBuildRangeInfoKHR::builder()
+            .primitive_count(vk_instances.len() as u32)
+            .build();
+
+        command_buffer.build_acceleration_structures(
+            std::slice::from_ref(&build_info),
+            &[std::slice::from_ref(&range)],
+        );
+        command_buffer.acceleration_structure_build_barrier();
+
+        Ok(true)
+    }
+
+    fn report_progress(&mut self) {
+        let progress = (self.pending_count(), self.building_count(), self.blases.len());
+        if progress != self.last_progress {
+            self.last_progress = progress;
+            self.events.push(AccelerationStructureBuildEvent::Progress {
+                pending: progress.0,
+                building: progress.1,
+                built: progress.2,
+            });
+        }
+    }
+}
+
+const TLAS_BUILD_FLAGS: vk::BuildAccelerationStructureFlagsKHR = vk::BuildAccelerationStructureFlagsKHR::from_raw(
+    vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE.as_raw()
+        | vk::BuildAccelerationStructureFlagsKHR::ALLOW_UPDATE.as_raw(),
+);
+
+fn tlas_geometry(instance_buffer: &VkBuffer) -> vk::AccelerationStructureGeometryKHR {
+    vk::AccelerationStructureGeometryKHR::builder()
+        .geometry_type(vk::GeometryTypeKHR::INSTANCES)
+        .geometry(vk::AccelerationStructureGeometryDataKHR {
+            instances: vk::AccelerationStructureGeometryInstancesDataKHR::builder()
+                .array_of_pointers(false)
+                .data(vk::DeviceOrHostAddressConstKHR {
+                    device_address: instance_buffer.get_device_address(),
+                })
+                .build(),
+        })
+        .build()
+}
+
+#[inline]
+fn align_up(value: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    value.div_ceil(alignment) * alignment
+}
+
+pub(crate) fn schedule_acceleration_structure_builds(
+    mut builder: ResMut<AccelerationStructureBuilder>,
+    frame_context: Res<FrameContext>,
+) {
+    let context = frame_context.render_context();
+    if context.ray_tracing.is_none() {
+        return;
+    }
+
+    builder.poll_finished();
+    if let Err(err) = builder.record_tlas_build(context) {
+        error!("Failed to record TLAS build: {err}");
+    }
+    if let Err(err) = builder.record_blas_builds(context) {
+        error!("Failed to record BLAS builds: {err}");
+    }
+    builder.report_progress();
+}
+
+pub(crate) fn extract_acceleration_structure_events(
+    mut main_world: ResMut<MainWorld>,
+    mut builder: ResMut<AccelerationStructureBuilder>,
+) {
+    if builder.events.is_empty() {
+        return;
+    }
+
+    if let Some(mut events) = main_world.get_resource_mut::<Events<AccelerationStructureBuildEvent>>() {
+        events.extend(builder.events.drain(..));
+    } else {
+        builder.events.clear();
+    }
+}
@@ -0,0 +1,336 @@
+use std::hash::{Hash, Hasher};
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::change_detection::DetectChanges;
+use bevy_ecs::prelude::{Component, Entity, Query, Ref, ReflectComponent, Res, ResMut, Resource, With};
+use bevy_log::error;
+use bevy_reflect::Reflect;
+use bevy_utils::{EntityHashMap, HashMap, HashSet};
+use avalanche_hlvk::Buffer as VkBuffer;
+use crate::color::Color;
+use crate::extract::FrameContext;
+use crate::mesh::MeshBuffers;
+use crate::parallel::par_map;
+use crate::prelude::{Extract, TypedBuffer};
+use crate::raytracing::{RayTracingInstance, RayTracingScene};
+
+/// Surface description of a ray traced instance, looked up by hit shaders through the [`RayTracingGpuScene`].
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct RayTracingMaterial {
+    pub base_color: Color,
+    /// Linear radiance, above 1 for bright surfaces
+    pub emissive: [f32; 3],
+    pub roughness: f32,
+    pub metallic: f32,
+    /// Raster passes draw back faces too, ray traced passes always do
+    pub double_sided: bool,
+}
+
+impl Default for RayTracingMaterial {
+    fn default() -> Self {
+        Self {
+            base_color: Color::linear_rgb(0.8, 0.8, 0.8),
+            emissive: [0.0; 3],
+            roughness: 0.5,
+            metallic: 0.0,
+            double_sided: false,
+        }
+    }
+}
+
+impl RayTracingMaterial {
+    fn to_gpu(self) -> GpuMaterial {
+        GpuMaterial {
+            base_color: self.base_color.to_linear_rgba(),
+            emissive: self.emissive,
+            roughness: self.roughness,
+            metallic: self.metallic,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+/// Matches `GpuMaterial` in `shaders/raytracing/scene.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuMaterial {
+    pub base_color: [f32; 4],
+    pub emissive: [f32; 3],
+    pub roughness: f32,
+    pub metallic: f32,
+    _padding: [f32; 3],
+}
+
+// SAFETY: plain `f32` fields without implicit padding
+unsafe impl Zeroable for GpuMaterial {}
+unsafe impl Pod for GpuMaterial {}
+
+impl GpuMaterial {
+    /// Bit pattern of the material, used to deduplicate and hash materials.
+    fn key(&self) -> [u32; 9] {
+        let [r, g, b, a] = self.base_color.map(f32::to_bits);
+        let [er, eg, eb] = self.emissive.map(f32::to_bits);
+        [r, g, b, a, er, eg, eb, self.roughness.to_bits(), self.metallic.to_bits()]
+    }
+}
+
+/// Matches `GpuSceneInstance` in `shaders/raytracing/scene.glsl`.
+///
+/// Addresses are zero for instances without [`MeshBuffers`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuSceneInstance {
+    pub vertex_address: u64,
+    /// Zero for non-indexed meshes
+    pub index_address: u64,
+    pub material_index: u32,
+    pub _padding: u32,
+}
+
+// SAFETY: integer fields without implicit padding
+unsafe impl Zeroable for GpuSceneInstance {}
+unsafe impl Pod for GpuSceneInstance {}
+
+/// Matches `GpuInstanceMotion` in `shaders/raytracing/scene.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GpuInstanceMotion {
+    /// Row-major 3x4 object to world matrix of the previous frame
+    pub previous_world_from_object: [f32; 12],
+}
+
+// SAFETY: plain `f32` fields without implicit padding
+unsafe impl Zeroable for GpuInstanceMotion {}
+unsafe impl Pod for GpuInstanceMotion {}
+
+struct ExtractedSceneGeometry {
+    mesh: MeshBuffers,
+    material: RayTracingMaterial,
+}
+
+/// Bindless lookup tables of the [`RayTracingScene`],
+/// rewritten when the geometries, materials or the scene order changed.
+///
+/// Instances are stored in TLAS order, so hit shaders index them with `gl_InstanceCustomIndexEXT`.
+/// Material 0 is always the default material.
+#[derive(Resource)]
+pub struct RayTracingGpuScene {
+    geometries: EntityHashMap<Entity, ExtractedSceneGeometry>,
+    instance_buffer: TypedBuffer<GpuSceneInstance>,
+    material_buffer: TypedBuffer<GpuMaterial>,
+    /// Previous frame transforms in TLAS order, rewritten every frame something moved
+    motion_buffer: TypedBuffer<GpuInstanceMotion>,
+    motions: Vec<GpuInstanceMotion>,
+    /// Transforms of the current frame, the previous ones of the next frame
+    transforms: EntityHashMap<Entity, [f32; 12]>,
+    /// Geometries changed since the last upload
+    dirty: bool,
+    /// Generation of the [`RayTracingScene`] the tables were built for
+    scene_generation: Option<u64>,
+    content_hash: u64,
+    generation: u64,
+}
+
+impl Default for RayTracingGpuScene {
+    fn default() -> Self {
+        let usage = vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS;
+        Self {
+            geometries: Default::default(),
+            instance_buffer: TypedBuffer::new(usage),
+            material_buffer: TypedBuffer::new(usage),
+            motion_buffer: TypedBuffer::new(vk::BufferUsageFlags::STORAGE_BUFFER),
+            motions: Vec::new(),
+            transforms: Default::default(),
+            dirty: false,
+            scene_generation: None,
+            content_hash: 0,
+            generation: 0,
+        }
+    }
+}
+
+impl RayTracingGpuScene {
+    #[inline]
+    pub fn instance_buffer(&self) -> Option<&VkBuffer> {
+        self.instance_buffer.buffer().map(|buffer| &**buffer)
+    }
+
+    #[inline]
+    pub fn material_buffer(&self) -> Option<&VkBuffer> {
+        self.material_buffer.buffer().map(|buffer| &**buffer)
+    }
+
+    #[inline]
+    pub fn motion_buffer(&self) -> Option<&VkBuffer> {
+        self.motion_buffer.buffer().map(|buffer| &**buffer)
+    }
+
+    #[inline]
+    pub fn instance_count(&self) -> usize {
+        self.instance_buffer.len()
+    }
+
+    #[inline]
+    pub fn material_count(&self) -> usize {
+        self.material_buffer.len()
+    }
+
+    /// Geometry and material of an instance, `None` for instances without [`MeshBuffers`].
+    pub fn geometry(&self, entity: Entity) -> Option<(&MeshBuffers, &RayTracingMaterial)> {
+        self.geometries
+            .get(&entity)
+            .map(|geometry| (&geometry.mesh, &geometry.material))
+    }
+
+    /// Changes whenever the uploaded geometry or materials change.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn build_tables(&self, scene: &RayTracingScene) -> (Vec<GpuSceneInstance>, Vec<GpuMaterial>) {
+        // addresses are looked up in parallel, materials are then deduplicated in TLAS order
+        let entities = scene.iter().map(|(entity, _)| entity).collect::<Vec<_>>();
+        let geometries = par_map(&entities, |entity| {
+            self.geometries.get(entity).map(|geometry| {
+                let index_address = geometry
+                    .mesh
+                    .index_buffer
+                    .as_ref()
+                    .map_or(0, |buffer| buffer.get_device_address());
+                (geometry.mesh.vertex_buffer.get_device_address(), index_address, geometry.material.to_gpu())
+            })
+        });
+
+        let mut materials = vec![RayTracingMaterial::default().to_gpu()];
+        let mut material_indices = HashMap::<[u32; 9], u32>::default();
+        material_indices.insert(materials[0].key(), 0);
+
+        let instances = geometries
+            .into_iter()
+            .map(|geometry| {
+                let Some((vertex_address, index_address, material)) = geometry else {
+                    return GpuSceneInstance::default();
+                };
+                let material_index = *material_indices
+                    .entry(material.key())
+                    .or_insert_with(|| {
+                        materials.push(material);
+                        materials.len() as u32 - 1
+                    });
+
+                GpuSceneInstance {
+                    vertex_address,
+                    index_address,
+                    material_index,
+                    _padding: 0,
+                }
+            })
+            .collect();
+
+        (instances, materials)
+    }
+}
+
+type ExtractSceneGeometryQuery<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, Ref<'static, MeshBuffers>, Option<Ref<'static, RayTracingMaterial>>),
+    With<RayTracingInstance>,
+>;
+
+pub(crate) fn extract_ray_tracing_gpu_scene(
+    mut gpu_scene: ResMut<RayTracingGpuScene>,
+    meshes: Extract<ExtractSceneGeometryQuery>,
+) {
+    let gpu_scene = gpu_scene.as_mut();
+    let mut alive = HashSet::with_capacity(gpu_scene.geometries.len());
+    for (entity, mesh, material) in meshes.iter() {
+        alive.insert(entity);
+        let material_changed = material.as_ref().is_some_and(|material| material.is_changed());
+        let material = material.map(|material| *material).unwrap_or_default();
+        match gpu_scene.geometries.get(&entity) {
+            // a removed material doesn't flag any change, so compare it as well
+            Some(geometry) if !mesh.is_changed() && !material_changed && geometry.material == material => continue,
+            _ => {}
+        }
+
+        gpu_scene.geometries.insert(entity, ExtractedSceneGeometry {
+            mesh: mesh.clone(),
+            material,
+        });
+        gpu_scene.dirty = true;
+    }
+
+    if alive.len() != gpu_scene.geometries.len() {
+        gpu_scene.geometries.retain(|entity, _| alive.contains(entity));
+        gpu_scene.dirty = true;
+    }
+}
+
+pub(crate) fn prepare_ray_tracing_gpu_scene(
+    mut gpu_scene: ResMut<RayTracingGpuScene>,
+    scene: Res<RayTracingScene>,
+    frame_context: Res<FrameContext>,
+) {
+    let context = frame_context.render_context();
+    if context.ray_tracing.is_none() {
+        return;
+    }
+    if !gpu_scene.dirty && gpu_scene.scene_generation == Some(scene.generation()) {
+        return;
+    }
+
+    let (instances, materials) = gpu_scene.build_tables(&scene);
+    let gpu_scene = gpu_scene.as_mut();
+    if let Err(err) = gpu_scene.instance_buffer.write(context, &instances)
+        .and_then(|_| gpu_scene.material_buffer.write(context, &materials)) {
+        error!("Failed to upload ray tracing scene tables: {err}");
+        return;
+    }
+    gpu_scene.dirty = false;
+    gpu_scene.scene_generation = Some(scene.generation());
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for instance in &instances {
+        (instance.vertex_address, instance.index_address, instance.material_index).hash(&mut hasher);
+    }
+    for material in &materials {
+        material.key().hash(&mut hasher);
+    }
+    let content_hash = hasher.finish();
+    if content_hash != gpu_scene.content_hash {
+        gpu_scene.content_hash = content_hash;
+        gpu_scene.generation = gpu_scene.generation.wrapping_add(1);
+    }
+}
+
+/// Upload the transforms every instance had in the previous frame, used to compute motion vectors.
+///
+/// Also read by the raster passes, so it runs without ray tracing support.
+pub(crate) fn prepare_ray_tracing_instance_motion(
+    mut gpu_scene: ResMut<RayTracingGpuScene>,
+    scene: Res<RayTracingScene>,
+    frame_context: Res<FrameContext>,
+) {
+    let context = frame_context.render_context();
+    let gpu_scene = gpu_scene.as_mut();
+    let instances = scene.iter().collect::<Vec<_>>();
+    let motions = par_map(&instances, |(entity, instance)| GpuInstanceMotion {
+        previous_world_from_object: gpu_scene.transforms.get(entity).copied().unwrap_or(instance.transform),
+    });
+    gpu_scene.transforms = instances
+        .iter()
+        .map(|&(entity, instance)| (entity, instance.transform))
+        .collect();
+
+    if gpu_scene.motion_buffer.buffer().is_some() && motions == gpu_scene.motions {
+        return;
+    }
+    if let Err(err) = gpu_scene.motion_buffer.write(context, &motions) {
+        error!("Failed to upload ray tracing instance motion: {err}");
+        return;
+    }
+    gpu_scene.motions = motions;
+}
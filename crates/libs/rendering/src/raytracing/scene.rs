@@ -0,0 +1,260 @@
+use bevy_ecs::change_detection::DetectChanges;
+use bevy_ecs::prelude::{Component, Entity, Query, Ref, Res, ResMut, Resource};
+use bevy_time::{Fixed, Time};
+use bevy_log::error;
+use bevy_transform::prelude::{GlobalTransform, Transform};
+use bevy_utils::{EntityHashMap, HashSet};
+use crate::extract::FrameContext;
+use crate::parallel::par_map;
+use crate::interpolation::{interpolated_transform, interpolation_alpha, TransformInterpolation};
+use crate::prelude::Extract;
+use crate::raytracing::{AccelerationStructureBuilder, BlasHandle, RayTracingMaterial, TlasInstance};
+
+/// TLAS mask bit cleared on instances with a translucent [`RayTracingMaterial`],
+/// rays which should only hit opaque geometry use it as their cull mask.
+pub const OPAQUE_INSTANCE_MASK: u8 = 0x01;
+
+/// Marks an entity to be placed in the [`RayTracingScene`] with its [`GlobalTransform`].
+#[derive(Component, Clone, Copy, Debug)]
+pub struct RayTracingInstance {
+    pub blas: BlasHandle,
+    pub mask: u8,
+    pub sbt_record_offset: u32,
+}
+
+impl RayTracingInstance {
+    pub fn new(blas: BlasHandle) -> Self {
+        Self {
+            blas,
+            mask: 0xFF,
+            sbt_record_offset: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayTracingSceneInstance {
+    /// Row-major 3x4 object to world matrix
+    pub transform: [f32; 12],
+    pub blas: BlasHandle,
+    pub mask: u8,
+    pub sbt_record_offset: u32,
+}
+
+/// How the TLAS has to be brought up to date with the scene.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlasUpdateMode {
+    #[default]
+    None,
+    /// Only transforms changed, the TLAS could be updated in place.
+    Refit,
+    /// Instances were added, removed or point to another BLAS.
+    Rebuild,
+}
+
+/// Instances of the top level acceleration structure, kept across frames in the render world.
+#[derive(Resource)]
+pub struct RayTracingScene {
+    instances: EntityHashMap<Entity, RayTracingSceneInstance>,
+    /// Stable instance order, the custom index of an instance is its position here
+    order: Vec<Entity>,
+    update_mode: TlasUpdateMode,
+    refits_since_rebuild: u32,
+    /// Bumped whenever the scene content changes
+    generation: u64,
+    /// Refits degrade the TLAS quality, rebuild after this many consecutive refits.
+    pub max_refits_before_rebuild: u32,
+}
+
+impl Default for RayTracingScene {
+    fn default() -> Self {
+        Self {
+            instances: Default::default(),
+            order: Vec::new(),
+            update_mode: TlasUpdateMode::None,
+            refits_since_rebuild: 0,
+            generation: 0,
+            max_refits_before_rebuild: 120,
+        }
+    }
+}
+
+impl RayTracingScene {
+    pub fn insert(&mut self, entity: Entity, instance: RayTracingSceneInstance) {
+        match self.instances.insert(entity, instance) {
+            None => {
+                self.order.push(entity);
+                self.mark(TlasUpdateMode::Rebuild);
+            }
+            Some(previous) if previous == instance => {}
+            Some(previous) if previous.transform != instance.transform
+                && previous.blas == instance.blas
+                && previous.mask == instance.mask
+                && previous.sbt_record_offset == instance.sbt_record_offset => {
+                self.mark(TlasUpdateMode::Refit);
+            }
+            Some(_) => self.mark(TlasUpdateMode::Rebuild),
+        }
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<RayTracingSceneInstance> {
+        let instance = self.instances.remove(&entity)?;
+        self.order.retain(|e| *e != entity);
+        self.mark(TlasUpdateMode::Rebuild);
+        Some(instance)
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&RayTracingSceneInstance> {
+        self.instances.get(&entity)
+    }
+
+    /// Instances in TLAS order.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &RayTracingSceneInstance)> {
+        self.order.iter().map(|entity| (*entity, &self.instances[entity]))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    #[inline]
+    pub fn update_mode(&self) -> TlasUpdateMode {
+        self.update_mode
+    }
+
+    /// Changes whenever an instance is added, removed or moved,
+    /// progressive renderers compare it to know when to restart.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn mark(&mut self, mode: TlasUpdateMode) {
+        self.generation = self.generation.wrapping_add(1);
+        self.update_mode = self.update_mode.max(mode);
+    }
+
+    /// Built on the [`ComputeTaskPool`](bevy_tasks::ComputeTaskPool) for large scenes.
+    pub fn tlas_instances(&self) -> Vec<TlasInstance> {
+        let indexed = self.order.iter().enumerate().collect::<Vec<_>>();
+        par_map(&indexed, |&(index, entity)| {
+            let instance = &self.instances[entity];
+            TlasInstance {
+                custom_index: index as u32,
+                mask: instance.mask,
+                sbt_record_offset: instance.sbt_record_offset,
+                ..TlasInstance::new(instance.blas, instance.transform)
+            }
+        })
+    }
+}
+
+/// Convert an affine transform into the row-major 3x4 layout vulkan expects.
+pub fn transform_to_vk_matrix(transform: &GlobalTransform) -> [f32; 12] {
+    let mut matrix = [0.0; 12];
+    matrix.copy_from_slice(&transform.compute_matrix().transpose().to_cols_array()[..12]);
+    matrix
+}
+
+type ExtractRayTracingInstanceQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        Ref<'static, RayTracingInstance>,
+        Ref<'static, GlobalTransform>,
+        Option<(&'static Transform, &'static TransformInterpolation)>,
+        Option<&'static RayTracingMaterial>,
+    ),
+>;
+
+pub(crate) fn extract_ray_tracing_scene(
+    mut scene: ResMut<RayTracingScene>,
+    instances: Extract<ExtractRayTracingInstanceQuery>,
+    fixed_time: Extract<Option<Res<Time<Fixed>>>>,
+) {
+    let alpha = interpolation_alpha(fixed_time.as_deref());
+    let mut alive = HashSet::with_capacity(scene.len());
+    for (entity, instance, transform, interpolation, material) in instances.iter() {
+        alive.insert(entity);
+        let mask = match material {
+            Some(material) if material.base_color.alpha < 1.0 => instance.mask & !OPAQUE_INSTANCE_MASK,
+            _ => instance.mask,
+        };
+        // interpolated instances move every frame until they come to rest
+        let interpolating = matches!(interpolation, Some((local, interpolation)) if interpolation.is_moving(local));
+        let extracted = scene.instances.get(&entity).is_some_and(|extracted| extracted.mask == mask);
+        if !instance.is_changed() && !transform.is_changed() && !interpolating && extracted {
+            continue;
+        }
+        scene.insert(entity, RayTracingSceneInstance {
+            transform: transform_to_vk_matrix(&interpolated_transform(&transform, interpolation, alpha)),
+            blas: instance.blas,
+            mask,
+            sbt_record_offset: instance.sbt_record_offset,
+        });
+    }
+
+    if alive.len() == scene.len() {
+        return;
+    }
+    let removed = scene
+        .order
+        .iter()
+        .filter(|entity| !alive.contains(*entity))
+        .copied()
+        .collect::<Vec<_>>();
+    for entity in removed {
+        scene.remove(entity);
+    }
+}
+
+pub(crate) fn prepare_ray_tracing_scene(
+    mut scene: ResMut<RayTracingScene>,
+    mut builder: ResMut<AccelerationStructureBuilder>,
+    frame_context: Res<FrameContext>,
+) {
+    if frame_context.render_context().ray_tracing.is_none() {
+        return;
+    }
+
+    let mut update_mode = std::mem::take(&mut scene.update_mode);
+    if update_mode == TlasUpdateMode::Refit && scene.refits_since_rebuild >= scene.max_refits_before_rebuild {
+        update_mode = TlasUpdateMode::Rebuild;
+    }
+
+    if update_mode == TlasUpdateMode::None {
+        return;
+    }
+
+    let instances = scene.tlas_instances();
+    match update_mode {
+        TlasUpdateMode::None => {}
+        TlasUpdateMode::Refit => {
+            let refitted = match frame_context.command_buffer(0) {
+                Some(command_buffer) => builder.refit_tlas(command_buffer, &instances).unwrap_or_else(|err| {
+                    error!("Failed to refit TLAS: {err}");
+                    false
+                }),
+                None => false,
+            };
+
+            if refitted {
+                scene.refits_since_rebuild += 1;
+            } else {
+                builder.submit_tlas(instances);
+                scene.refits_since_rebuild = 0;
+            }
+        }
+        TlasUpdateMode::Rebuild => {
+            builder.submit_tlas(instances);
+            scene.refits_since_rebuild = 0;
+        }
+    }
+}
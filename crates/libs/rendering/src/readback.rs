@@ -0,0 +1,197 @@
+use std::sync::{Arc, Mutex};
+use anyhow::Context as _;
+use ash::vk;
+use bevy_ecs::prelude::{Event, Events, ResMut, Resource};
+use bevy_log::error;
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{Buffer, BufferBarrier, BufferCreateInfo, Fence, Image, ImageBarrier};
+use avalanche_utils::define_atomic_id;
+use crate::extract::FrameContext;
+use crate::MainWorld;
+
+define_atomic_id!(ReadbackId: u64);
+
+/// What a [`Readback`] copies back to the host.
+#[derive(Clone, Copy)]
+pub enum ReadbackSource<'a> {
+    Buffer(&'a Buffer),
+    /// The first mip level of every layer, tightly packed, the image is left in `layout`
+    Image { image: &'a Image, layout: vk::ImageLayout },
+}
+
+impl<'a> From<&'a Buffer> for ReadbackSource<'a> {
+    fn from(buffer: &'a Buffer) -> Self {
+        Self::Buffer(buffer)
+    }
+}
+
+/// Host visible copy of a buffer or image, readable once the fence of the frame which recorded it is signaled.
+pub struct ReadbackBuffer {
+    buffer: Buffer,
+    fence: Arc<Fence>,
+}
+
+impl ReadbackBuffer {
+    /// Record the copy of `source` into the first command buffer of the frame.
+    pub fn new(frame_context: &FrameContext, source: ReadbackSource) -> anyhow::Result<Self> {
+        let context = frame_context.render_context();
+        let command_buffer = frame_context.command_buffer(0).context("The frame has no command buffer.")?;
+        let size = match source {
+            ReadbackSource::Buffer(buffer) => buffer.size,
+            ReadbackSource::Image { image, .. } => {
+                let texel_size = image.texel_size().context("Reading back images of this format isn't supported.")?;
+                let extent = image.extent;
+                texel_size as u64 * extent.width as u64 * extent.height as u64 * extent.depth as u64 * image.array_layers as u64
+            }
+        };
+        let buffer = context.create_buffer_from_info(&BufferCreateInfo {
+            usage: vk::BufferUsageFlags::TRANSFER_DST,
+            memory_location: MemoryLocation::GpuToCpu,
+            size,
+            queue_family_indices: &[],
+            name: Some("readback"),
+        })?;
+
+        match source {
+            ReadbackSource::Buffer(src) => {
+                command_buffer.pipeline_buffer_barriers(&[BufferBarrier {
+                    buffer: src,
+                    src_access_mask: vk::AccessFlags2::MEMORY_WRITE,
+                    dst_access_mask: vk::AccessFlags2::TRANSFER_READ,
+                    src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                    dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                }]);
+                command_buffer.copy_buffer(src, &buffer);
+            }
+            ReadbackSource::Image { image, layout } => {
+                let transfer_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+                command_buffer.pipeline_image_barriers(&[ImageBarrier {
+                    image,
+                    old_layout: layout,
+                    new_layout: transfer_layout,
+                    src_access_mask: vk::AccessFlags2::MEMORY_WRITE,
+                    dst_access_mask: vk::AccessFlags2::TRANSFER_READ,
+                    src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                    dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                }]);
+                command_buffer.copy_image_to_buffer(image, transfer_layout, &buffer);
+                command_buffer.pipeline_image_barriers(&[ImageBarrier {
+                    image,
+                    old_layout: transfer_layout,
+                    new_layout: layout,
+                    src_access_mask: vk::AccessFlags2::NONE,
+                    dst_access_mask: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+                    src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                    dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                }]);
+            }
+        }
+        command_buffer.pipeline_buffer_barriers(&[BufferBarrier {
+            buffer: &buffer,
+            src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags2::HOST_READ,
+            src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            dst_stage_mask: vk::PipelineStageFlags2::HOST,
+        }]);
+
+        Ok(Self {
+            buffer,
+            fence: frame_context.sync_fence(),
+        })
+    }
+
+    /// Whether the copy completed, without blocking.
+    pub fn is_ready(&self) -> bool {
+        self.fence.is_signaled().unwrap_or(false)
+    }
+
+    /// The copied bytes, `None` while the copy is pending.
+    pub fn read(&self) -> anyhow::Result<Option<Vec<u8>>> {
+        if !self.is_ready() {
+            return Ok(None);
+        }
+        self.buffer.read_data_from_buffer().map(Some)
+    }
+}
+
+type ReadbackCallback = Box<dyn FnOnce(ReadbackId, &[u8]) + Send + Sync>;
+
+struct PendingReadback {
+    id: ReadbackId,
+    buffer: ReadbackBuffer,
+    callback: Option<ReadbackCallback>,
+}
+
+/// Copies GPU buffers and images back to the host without stalling, lives in the render world.
+///
+/// Requests are recorded where they are made, e.g. at the end of a node, and complete once the GPU
+/// finished the frame. The bytes are then sent to the main world in a [`ReadbackComplete`] event,
+/// and given to the callback of the request if any.
+#[derive(Resource, Default)]
+pub struct Readback {
+    pending: Mutex<Vec<PendingReadback>>,
+    completed: Vec<ReadbackComplete>,
+}
+
+impl Readback {
+    pub fn request<'a>(&self, frame_context: &FrameContext, source: impl Into<ReadbackSource<'a>>) -> anyhow::Result<ReadbackId> {
+        self.push(frame_context, source.into(), None)
+    }
+
+    /// Request a readback and run `callback` on its bytes in the render world once it completes.
+    pub fn request_with<'a>(
+        &self,
+        frame_context: &FrameContext,
+        source: impl Into<ReadbackSource<'a>>,
+        callback: impl FnOnce(ReadbackId, &[u8]) + Send + Sync + 'static,
+    ) -> anyhow::Result<ReadbackId> {
+        self.push(frame_context, source.into(), Some(Box::new(callback)))
+    }
+
+    /// Number of readbacks waiting for the GPU.
+    pub fn pending_count(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+
+    fn push(&self, frame_context: &FrameContext, source: ReadbackSource, callback: Option<ReadbackCallback>) -> anyhow::Result<ReadbackId> {
+        let id = ReadbackId::new();
+        let buffer = ReadbackBuffer::new(frame_context, source)?;
+        self.pending.lock().unwrap().push(PendingReadback { id, buffer, callback });
+        Ok(id)
+    }
+}
+
+/// Sent in the main world with the bytes of a completed [`Readback`] request.
+#[derive(Event, Debug)]
+pub struct ReadbackComplete {
+    pub id: ReadbackId,
+    pub data: Vec<u8>,
+}
+
+/// Reads the requests whose frame completed, the pending ones are checked again next frame.
+pub(crate) fn poll_readbacks(mut readback: ResMut<Readback>) {
+    let readback = &mut *readback;
+    let pending = std::mem::take(readback.pending.get_mut().unwrap());
+    for request in pending {
+        match request.buffer.read() {
+            Ok(Some(data)) => {
+                if let Some(callback) = request.callback {
+                    callback(request.id, &data);
+                }
+                readback.completed.push(ReadbackComplete { id: request.id, data });
+            }
+            Ok(None) => readback.pending.get_mut().unwrap().push(request),
+            Err(err) => error!("Failed to read back {:?}: {err}", request.id),
+        }
+    }
+}
+
+pub(crate) fn send_readback_results(mut readback: ResMut<Readback>, mut main_world: ResMut<MainWorld>) {
+    if readback.completed.is_empty() {
+        return;
+    }
+    let completed = std::mem::take(&mut readback.completed);
+    if let Some(mut events) = main_world.get_resource_mut::<Events<ReadbackComplete>>() {
+        events.extend(completed);
+    }
+}
@@ -0,0 +1,185 @@
+use std::marker::PhantomData;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{EventReader, IntoSystemConfigs, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_utils::{HashMap, HashSet};
+use avalanche_asset::{Asset, AssetEvent, AssetId, Assets};
+use avalanche_hlvk::{Buffer as VkBuffer, CommandBuffer, Context};
+use crate::{ExtractSchedule, Render, RenderApp, RenderSet};
+use crate::extract::FrameContext;
+use crate::prelude::Extract;
+
+/// An [`Asset`] with a GPU representation, kept up to date in [`RenderAssets`] by [`RenderAssetPlugin`].
+pub trait RenderAsset: Asset {
+    /// Copy of the asset moved to the render world.
+    type ExtractedAsset: Send + Sync + 'static;
+    type PreparedAsset: Send + Sync + 'static;
+
+    fn extract_asset(&self) -> Self::ExtractedAsset;
+
+    /// Create the GPU resources during [`RenderSet::PrepareAssets`].
+    fn prepare_asset(
+        extracted_asset: Self::ExtractedAsset,
+        context: &mut RenderAssetContext,
+    ) -> anyhow::Result<Self::PreparedAsset>;
+}
+
+/// What [`RenderAsset::prepare_asset`] may use to upload its data.
+pub struct RenderAssetContext<'a> {
+    frame_context: &'a FrameContext,
+    staging_buffers: &'a mut Vec<VkBuffer>,
+}
+
+impl<'a> RenderAssetContext<'a> {
+    pub(crate) fn new(frame_context: &'a FrameContext, staging_buffers: &'a mut Vec<VkBuffer>) -> Self {
+        Self {
+            frame_context,
+            staging_buffers,
+        }
+    }
+
+    #[inline]
+    pub fn context(&self) -> &Context {
+        self.frame_context.render_context()
+    }
+
+    /// Command buffer of the frame, recorded commands run before the render graph.
+    #[inline]
+    pub fn command_buffer(&self) -> &CommandBuffer {
+        self.frame_context.command_buffer(0).expect("Frame command buffer is missing")
+    }
+
+    /// Keep a staging buffer alive until the commands of the frame completed.
+    pub fn keep_until_frame_end(&mut self, buffer: VkBuffer) {
+        self.staging_buffers.push(buffer);
+    }
+}
+
+/// Staging buffers used by uploads of the current frame, freed once the frame completed.
+#[derive(Resource, Default)]
+pub(crate) struct RenderAssetStagingBuffers(pub(crate) Vec<VkBuffer>);
+
+pub(crate) fn release_render_asset_staging_buffers(mut staging_buffers: ResMut<RenderAssetStagingBuffers>) {
+    staging_buffers.0.clear();
+}
+
+/// Assets added, modified or removed in the main world since the last extraction.
+#[derive(Resource)]
+pub struct ExtractedAssets<A: RenderAsset> {
+    pub extracted: Vec<(AssetId<A>, A::ExtractedAsset)>,
+    pub removed: Vec<AssetId<A>>,
+}
+
+impl<A: RenderAsset> Default for ExtractedAssets<A> {
+    fn default() -> Self {
+        Self {
+            extracted: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+
+/// GPU representation of every asset of type `A`.
+///
+/// Reloaded assets keep their id, so lookups made through their handles see the new resources.
+#[derive(Resource)]
+pub struct RenderAssets<A: RenderAsset>(HashMap<AssetId<A>, A::PreparedAsset>);
+
+impl<A: RenderAsset> Default for RenderAssets<A> {
+    fn default() -> Self {
+        Self(HashMap::default())
+    }
+}
+
+impl<A: RenderAsset> RenderAssets<A> {
+    #[inline]
+    pub fn get(&self, id: impl Into<AssetId<A>>) -> Option<&A::PreparedAsset> {
+        self.0.get(&id.into())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (AssetId<A>, &A::PreparedAsset)> {
+        self.0.iter().map(|(id, asset)| (*id, asset))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Extracts the changed assets of type `A` and prepares them in [`RenderSet::PrepareAssets`].
+pub struct RenderAssetPlugin<A: RenderAsset> {
+    marker: PhantomData<fn() -> A>,
+}
+
+impl<A: RenderAsset> Default for RenderAssetPlugin<A> {
+    fn default() -> Self {
+        Self {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<A: RenderAsset> Plugin for RenderAssetPlugin<A> {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ExtractedAssets<A>>()
+                .init_resource::<RenderAssets<A>>()
+                .add_systems(ExtractSchedule, extract_render_asset::<A>)
+                .add_systems(Render, prepare_render_assets::<A>.in_set(RenderSet::PrepareAssets));
+        }
+    }
+}
+
+fn extract_render_asset<A: RenderAsset>(
+    mut extracted_assets: ResMut<ExtractedAssets<A>>,
+    mut events: Extract<EventReader<AssetEvent<A>>>,
+    assets: Extract<Res<Assets<A>>>,
+) {
+    let mut changed = HashSet::default();
+    for event in events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
+                changed.insert(*id);
+            }
+            AssetEvent::Removed { id } => {
+                changed.remove(id);
+                extracted_assets.removed.push(*id);
+            }
+        }
+    }
+
+    for id in changed {
+        if let Some(asset) = assets.get(id) {
+            extracted_assets.extracted.push((id, asset.extract_asset()));
+        }
+    }
+}
+
+fn prepare_render_assets<A: RenderAsset>(
+    mut extracted_assets: ResMut<ExtractedAssets<A>>,
+    mut render_assets: ResMut<RenderAssets<A>>,
+    mut staging_buffers: ResMut<RenderAssetStagingBuffers>,
+    frame_context: Res<FrameContext>,
+) {
+    let extracted_assets = extracted_assets.as_mut();
+    for id in extracted_assets.removed.drain(..) {
+        render_assets.0.remove(&id);
+    }
+
+    let mut context = RenderAssetContext::new(&frame_context, &mut staging_buffers.0);
+    for (id, extracted_asset) in extracted_assets.extracted.drain(..) {
+        match A::prepare_asset(extracted_asset, &mut context) {
+            Ok(prepared_asset) => {
+                render_assets.0.insert(id, prepared_asset);
+            }
+            Err(err) => error!("Failed to prepare {id:?}: {err}"),
+        }
+    }
+}
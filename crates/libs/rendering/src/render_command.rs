@@ -0,0 +1,168 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{IntoSystemConfigs, Mut, Res, ResMut, resource_exists, Resource, World};
+use bevy_log::{error, warn};
+use bevy_utils::HashMap;
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::BufferCreateInfo;
+use crate::{apply_extract_commands, ExtractSchedule, Render, RenderApp, RenderSet};
+use crate::extract::FrameContext;
+use crate::graph::{find_graph_mut, RenderGraphError};
+use crate::prelude::node::Node;
+use crate::prelude::{Buffer, Extract};
+use crate::render_asset::{RenderAsset, RenderAssetContext, RenderAssetStagingBuffers};
+use crate::texture::{GpuTexture, Texture};
+
+/// A command run on the render world, see [`RenderCommandQueue`].
+pub type RenderCommand = Box<dyn FnOnce(&mut World) + Send + Sync>;
+
+/// Commands queued by main world systems for the render world, lives in the main world.
+///
+/// They run in order during [`RenderSet::ExtractCommands`] of the next rendered frame,
+/// and wait while no frame is rendered, e.g. when the app is suspended.
+/// Clones share the queue, so commands can also be queued from tasks.
+#[derive(Resource, Clone, Default)]
+pub struct RenderCommandQueue(Arc<Mutex<Vec<RenderCommand>>>);
+
+impl RenderCommandQueue {
+    pub fn push(&self, command: impl FnOnce(&mut World) + Send + Sync + 'static) {
+        self.0.lock().unwrap().push(Box::new(command));
+    }
+
+    /// Upload `data` into a host visible buffer, available in [`RenderCommandResources`] under `name`.
+    ///
+    /// A buffer already registered with the name is replaced.
+    pub fn upload_buffer(&self, name: impl Into<Cow<'static, str>>, usage: vk::BufferUsageFlags, data: Vec<u8>) {
+        let name = name.into();
+        self.push(move |world| {
+            let frame_context = world.resource::<FrameContext>();
+            let buffer = frame_context
+                .render_context()
+                .create_buffer_from_info(&BufferCreateInfo {
+                    usage,
+                    memory_location: MemoryLocation::CpuToGpu,
+                    size: data.len() as _,
+                    queue_family_indices: &[],
+                    name: Some(&name),
+                })
+                .and_then(|buffer| {
+                    buffer.copy_data_to_buffer(&data)?;
+                    Ok(buffer)
+                });
+            match buffer {
+                Ok(buffer) => {
+                    world.resource_mut::<RenderCommandResources>().buffers.insert(name, buffer.into());
+                }
+                Err(err) => error!("Failed to upload buffer {name}: {err}"),
+            }
+        });
+    }
+
+    /// Upload a texture the same way as a [`Texture`] asset, available in [`RenderCommandResources`] under `name`.
+    ///
+    /// A texture already registered with the name is replaced.
+    pub fn register_texture(&self, name: impl Into<Cow<'static, str>>, texture: Texture) {
+        let name = name.into();
+        self.push(move |world| {
+            let texture = world.resource_scope(|world, mut staging_buffers: Mut<RenderAssetStagingBuffers>| {
+                let mut context = RenderAssetContext::new(world.resource::<FrameContext>(), &mut staging_buffers.0);
+                Texture::prepare_asset(texture, &mut context)
+            });
+            match texture {
+                Ok(texture) => {
+                    world.resource_mut::<RenderCommandResources>().textures.insert(name, texture);
+                }
+                Err(err) => error!("Failed to register texture {name}: {err}"),
+            }
+        });
+    }
+
+    /// Add a node to a sub graph of the [`RenderGraph`](crate::graph::RenderGraph), ordered by `edges` like
+    /// [`add_render_graph_edges`](crate::graph::RenderGraphApp::add_render_graph_edges).
+    ///
+    /// A node already in the sub graph with the name is replaced.
+    pub fn insert_node(&self, sub_graph_name: &'static str, node_name: &'static str, node: impl Node, edges: &[&'static str]) {
+        let edges = edges.to_vec();
+        self.push(move |world| {
+            let Some(mut graph) = find_graph_mut(world, sub_graph_name.into()) else {
+                warn!("Tried inserting the render graph node {node_name} into {sub_graph_name} but the sub graph doesn't exist");
+                return;
+            };
+
+            graph.add_node(node_name, node);
+            for window in edges.windows(2) {
+                match graph.try_add_node_edge(window[0], window[1]) {
+                    Ok(()) | Err(RenderGraphError::EdgeAlreadyExists(_)) => {}
+                    Err(err) => warn!("Failed to add render graph edge {} -> {}: {err:?}", window[0], window[1]),
+                }
+            }
+        });
+    }
+}
+
+/// Resources created by the commands of the [`RenderCommandQueue`], lives in the render world.
+#[derive(Resource, Default)]
+pub struct RenderCommandResources {
+    buffers: HashMap<Cow<'static, str>, Buffer>,
+    textures: HashMap<Cow<'static, str>, GpuTexture>,
+}
+
+impl RenderCommandResources {
+    #[inline]
+    pub fn buffer(&self, name: &str) -> Option<&Buffer> {
+        self.buffers.get(name)
+    }
+
+    #[inline]
+    pub fn texture(&self, name: &str) -> Option<&GpuTexture> {
+        self.textures.get(name)
+    }
+
+    pub fn remove_buffer(&mut self, name: &str) -> Option<Buffer> {
+        self.buffers.remove(name)
+    }
+
+    pub fn remove_texture(&mut self, name: &str) -> Option<GpuTexture> {
+        self.textures.remove(name)
+    }
+}
+
+/// Commands moved out of the [`RenderCommandQueue`], kept until a frame runs them.
+#[derive(Resource, Default)]
+struct ExtractedRenderCommands(Vec<RenderCommand>);
+
+/// Lets main world systems act on the render world through the [`RenderCommandQueue`].
+pub struct RenderCommandPlugin;
+
+impl Plugin for RenderCommandPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RenderCommandQueue>();
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ExtractedRenderCommands>()
+                .init_resource::<RenderCommandResources>()
+                .add_systems(ExtractSchedule, extract_render_commands)
+                .add_systems(
+                    Render,
+                    apply_render_commands
+                        .in_set(RenderSet::ExtractCommands)
+                        .after(apply_extract_commands)
+                        .run_if(resource_exists::<FrameContext>()),
+                );
+        }
+    }
+}
+
+fn extract_render_commands(queue: Extract<Res<RenderCommandQueue>>, mut extracted: ResMut<ExtractedRenderCommands>) {
+    extracted.0.append(&mut queue.0.lock().unwrap());
+}
+
+fn apply_render_commands(world: &mut World) {
+    let commands = std::mem::take(&mut world.resource_mut::<ExtractedRenderCommands>().0);
+    for command in commands {
+        command(world);
+    }
+}
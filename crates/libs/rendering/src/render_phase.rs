@@ -0,0 +1,44 @@
+use bevy_ecs::prelude::{Component, Entity, Query};
+use crate::parallel::par_sort_by_key;
+
+/// An entity drawn by a render pass of a view, collected in a [`RenderPhase`].
+pub trait PhaseItem: Send + Sync + 'static {
+    /// Items are drawn in ascending order of their key.
+    type SortKey: Ord;
+
+    fn entity(&self) -> Entity;
+
+    fn sort_key(&self) -> Self::SortKey;
+}
+
+/// Items of a view queued in [`RenderSet::Queue`](crate::RenderSet::Queue),
+/// sorted in [`RenderSet::PhaseSort`](crate::RenderSet::PhaseSort) by [`sort_phase_system`].
+#[derive(Component)]
+pub struct RenderPhase<I: PhaseItem> {
+    pub items: Vec<I>,
+}
+
+impl<I: PhaseItem> Default for RenderPhase<I> {
+    fn default() -> Self {
+        Self { items: Vec::new() }
+    }
+}
+
+impl<I: PhaseItem> RenderPhase<I> {
+    #[inline]
+    pub fn add(&mut self, item: I) {
+        self.items.push(item);
+    }
+
+    /// Large phases are sorted on the [`ComputeTaskPool`](bevy_tasks::ComputeTaskPool).
+    pub fn sort(&mut self) {
+        par_sort_by_key(&mut self.items, |item| item.sort_key());
+    }
+}
+
+/// Sort the [`RenderPhase<I>`] of every view.
+pub fn sort_phase_system<I: PhaseItem>(mut phases: Query<&mut RenderPhase<I>>) {
+    for mut phase in phases.iter_mut() {
+        phase.sort();
+    }
+}
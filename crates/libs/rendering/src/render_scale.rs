@@ -0,0 +1,134 @@
+use std::time::Duration;
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Component, Entity, IntoSystemConfigs, Query, ReflectComponent, Res, ResMut, Resource};
+use bevy_math::UVec2;
+use bevy_reflect::Reflect;
+use bevy_utils::{EntityHashMap, HashSet};
+use crate::{Render, RenderApp, RenderSet};
+use crate::extract::{ExtractComponent, ExtractComponentPlugin};
+use crate::profiler::GpuProfiler;
+use crate::view::prepare_view_targets;
+
+/// Resolution of the [`ViewTarget`](crate::view::ViewTarget) of a camera relative to its render target.
+///
+/// The view is rendered at the scaled size and upsampled into an [`UpscaledViewTarget`](crate::view::UpscaledViewTarget)
+/// at full size by the [`ActiveUpscaler`](crate::upscaling::ActiveUpscaler).
+#[derive(Component, ExtractComponent, Reflect, Clone, Copy, Debug, PartialEq)]
+#[reflect(Component)]
+pub struct RenderScale {
+    pub scale: f32,
+    pub filter: UpscaleFilter,
+    /// Adjust `scale` every frame to keep the GPU frame time near a target.
+    pub dynamic: Option<DynamicRenderScale>,
+}
+
+impl Default for RenderScale {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            filter: UpscaleFilter::default(),
+            dynamic: None,
+        }
+    }
+}
+
+impl RenderScale {
+    pub fn render_size(&self, target_size: UVec2) -> UVec2 {
+        (target_size.as_vec2() * self.scale.max(0.0)).round().as_uvec2().max(UVec2::ONE)
+    }
+}
+
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UpscaleFilter {
+    #[default]
+    Bilinear,
+    Nearest,
+}
+
+impl From<UpscaleFilter> for vk::Filter {
+    fn from(value: UpscaleFilter) -> Self {
+        match value {
+            UpscaleFilter::Bilinear => vk::Filter::LINEAR,
+            UpscaleFilter::Nearest => vk::Filter::NEAREST,
+        }
+    }
+}
+
+/// Bounds of a dynamic [`RenderScale`], driven by the frame time of the [`GpuProfiler`].
+#[derive(Reflect, Clone, Copy, Debug, PartialEq)]
+pub struct DynamicRenderScale {
+    pub target_frame_time: Duration,
+    pub min_scale: f32,
+    pub max_scale: f32,
+}
+
+impl Default for DynamicRenderScale {
+    fn default() -> Self {
+        Self {
+            target_frame_time: Duration::from_secs_f64(1.0 / 60.0),
+            min_scale: 0.5,
+            max_scale: 1.0,
+        }
+    }
+}
+
+/// Steps the dynamic scale is rounded to, so the view target isn't recreated every frame.
+const DYNAMIC_SCALE_STEP: f32 = 0.05;
+/// How much of the estimated scale is applied per frame.
+const DYNAMIC_SCALE_SMOOTHING: f32 = 0.1;
+
+/// Unquantized scale of every view with a dynamic [`RenderScale`].
+#[derive(Resource, Default)]
+struct DynamicRenderScaleState(EntityHashMap<Entity, f32>);
+
+pub struct RenderScalePlugin;
+
+impl Plugin for RenderScalePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<RenderScale>()
+            .add_plugins(ExtractComponentPlugin::<RenderScale>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<DynamicRenderScaleState>()
+                .add_systems(
+                    Render,
+                    update_dynamic_render_scale
+                        .in_set(RenderSet::ManageViews)
+                        .before(prepare_view_targets),
+                );
+        }
+    }
+}
+
+fn update_dynamic_render_scale(
+    mut state: ResMut<DynamicRenderScaleState>,
+    mut views: Query<(Entity, &mut RenderScale)>,
+    profiler: Res<GpuProfiler>,
+) {
+    let frame_time = profiler.average_frame_time();
+    let mut alive = HashSet::default();
+
+    for (entity, mut render_scale) in views.iter_mut() {
+        let Some(dynamic) = render_scale.dynamic else {
+            continue;
+        };
+        alive.insert(entity);
+
+        let min_scale = dynamic.min_scale.min(dynamic.max_scale);
+        let scale = state.0.entry(entity).or_insert(render_scale.scale);
+        if let Some(frame_time) = frame_time.filter(|frame_time| !frame_time.is_zero()) {
+            // the cost scales with the pixel count, the square of the scale
+            let budget = dynamic.target_frame_time.as_secs_f32() / frame_time.as_secs_f32();
+            let estimate = *scale * budget.sqrt();
+            *scale += (estimate - *scale) * DYNAMIC_SCALE_SMOOTHING;
+        }
+        *scale = scale.clamp(min_scale, dynamic.max_scale);
+
+        render_scale.scale = ((*scale / DYNAMIC_SCALE_STEP).round() * DYNAMIC_SCALE_STEP)
+            .clamp(min_scale, dynamic.max_scale);
+    }
+
+    state.0.retain(|entity, _| alive.contains(entity));
+}
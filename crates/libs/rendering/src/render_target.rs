@@ -0,0 +1,321 @@
+use std::sync::atomic::Ordering;
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Component, Entity, FromWorld, ReflectComponent, World};
+use bevy_math::UVec2;
+use bevy_reflect::Reflect;
+use avalanche_asset::{Assets, Handle};
+use avalanche_hlvk::ImageBarrier;
+use crate::RenderApp;
+use crate::camera::ExtractedCamera;
+use crate::deferred::DEFERRED_GRAPH;
+use crate::extract::{ExtractComponent, ExtractComponentPlugin, FrameContext};
+use crate::graph::RenderGraphApp;
+use crate::graph::node::{ViewNode, ViewNodeRunner};
+use crate::path_tracing::PATH_TRACING_GRAPH;
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::window::ExtractedWindows;
+use crate::render_asset::RenderAssets;
+use crate::sprite::SPRITE_GRAPH;
+use crate::texture::Texture;
+use crate::tonemapping::TONEMAPPING_NODE;
+use crate::upscaling::UPSCALE_NODE;
+use crate::view::{UpscaledViewTarget, ViewTarget};
+
+/// Node copying the final image of a camera with a [`RenderTargetImage`] into its texture, see [`RenderTargetNode`].
+pub const RENDER_TARGET_NODE: &str = "render_target_copy";
+/// Node copying the final image of a camera into the swapchain image of its window, see [`WindowTargetNode`].
+pub const WINDOW_TARGET_NODE: &str = "window_target_copy";
+
+/// Render a camera into a [`Texture`] instead of the primary window, sampled like any other texture afterwards,
+/// e.g. as the [`Sprite`](crate::sprite::Sprite) of a minimap.
+///
+/// The camera target size is the texture size. Cameras are rendered by increasing
+/// [`Camera::order`](crate::camera::Camera::order), give the camera a lower order than the cameras
+/// seeing its texture, they see the image of the previous frame otherwise.
+#[derive(Component, ExtractComponent, Clone, Debug)]
+pub struct RenderTargetImage {
+    pub texture: Handle<Texture>,
+}
+
+impl RenderTargetImage {
+    /// Render into a new blank texture of `size`.
+    pub fn new(textures: &mut Assets<Texture>, size: UVec2) -> Self {
+        let texture = textures.add(Texture {
+            width: size.x,
+            height: size.y,
+            format: vk::Format::R8G8B8A8_SRGB,
+            data: vec![0; size.x as usize * size.y as usize * 4],
+        });
+        Self { texture }
+    }
+}
+
+/// Render a camera into the window `window` instead of the primary window, e.g. for a second monitor.
+///
+/// The camera target size is the window size, a [`RenderTargetImage`] on the same camera takes precedence.
+/// In the render world, every camera rendering into a window has it, with the primary window resolved.
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct WindowTarget {
+    pub window: Entity,
+}
+
+/// Required by the reflection, the placeholder entity is not a window.
+impl FromWorld for WindowTarget {
+    fn from_world(_world: &mut World) -> Self {
+        Self { window: Entity::PLACEHOLDER }
+    }
+}
+
+/// Blits the final color target of a view into its [`Viewport`](crate::camera::Viewport) of the texture of its [`RenderTargetImage`],
+/// leaving the texture in `SHADER_READ_ONLY_OPTIMAL` for the passes sampling it.
+#[derive(Default)]
+pub struct RenderTargetNode;
+
+impl ViewNode for RenderTargetNode {
+    type ViewQuery = (
+        &'static ExtractedCamera,
+        &'static ViewTarget,
+        Option<&'static UpscaledViewTarget>,
+        &'static RenderTargetImage,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (camera, target, upscaled_target, render_target): (
+            &ExtractedCamera,
+            &ViewTarget,
+            Option<&UpscaledViewTarget>,
+            &RenderTargetImage,
+        ),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(texture) = world.resource::<RenderAssets<Texture>>().get(render_target.texture.id()) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        let source = upscaled_target.map_or(&target.image, |upscaled| &upscaled.image);
+
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image: source,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::MEMORY_WRITE,
+                dst_access_mask: vk::AccessFlags2::TRANSFER_READ,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            },
+            // the previous image may still be sampled by the passes of earlier cameras,
+            // and is kept outside of the viewport for other cameras sharing the texture
+            ImageBarrier {
+                image: &texture.image,
+                old_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                src_access_mask: vk::AccessFlags2::SHADER_READ,
+                dst_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            },
+        ]);
+        // converts the color to the texture format, HDR values of untonemapped views are clamped
+        let viewport = vk::Rect2D {
+            offset: vk::Offset2D {
+                x: camera.viewport.min.x as i32,
+                y: camera.viewport.min.y as i32,
+            },
+            extent: vk::Extent2D {
+                width: camera.viewport.width(),
+                height: camera.viewport.height(),
+            },
+        };
+        command_buffer.blit_image_to_rect(
+            source,
+            vk::ImageLayout::GENERAL,
+            &texture.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            viewport,
+            vk::Filter::LINEAR,
+        );
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image: &texture.image,
+                old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                dst_access_mask: vk::AccessFlags2::SHADER_READ,
+                src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            },
+            ImageBarrier {
+                image: source,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::TRANSFER_READ,
+                dst_access_mask: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            },
+        ]);
+
+        Ok(())
+    }
+}
+
+/// Blits the final color target of a view into its [`Viewport`](crate::camera::Viewport) of the swapchain image
+/// acquired for its [`WindowTarget`], leaving the image in `PRESENT_SRC_KHR`.
+///
+/// The first view copied into an image clears it, so the area outside of the viewports is black.
+/// While the swapchain wasn't recreated for a resize yet, the viewport is scaled to the swapchain extent.
+#[derive(Default)]
+pub struct WindowTargetNode;
+
+impl ViewNode for WindowTargetNode {
+    type ViewQuery = (
+        &'static ExtractedCamera,
+        &'static ViewTarget,
+        Option<&'static UpscaledViewTarget>,
+        &'static WindowTarget,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (camera, target, upscaled_target, window_target): (
+            &ExtractedCamera,
+            &ViewTarget,
+            Option<&UpscaledViewTarget>,
+            &WindowTarget,
+        ),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(window) = world.resource::<ExtractedWindows>().get(&window_target.window) else {
+            return Ok(());
+        };
+        let Some(acquired) = &window.acquired else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        let images = window.swapchain.images.read().unwrap();
+        let Some(image) = images.get(acquired.index as usize) else {
+            return Ok(());
+        };
+        let source = upscaled_target.map_or(&target.image, |upscaled| &upscaled.image);
+        let first_write = !acquired.written.swap(true, Ordering::Relaxed);
+
+        let old_layout = if first_write { vk::ImageLayout::UNDEFINED } else { vk::ImageLayout::PRESENT_SRC_KHR };
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image: source,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::MEMORY_WRITE,
+                dst_access_mask: vk::AccessFlags2::TRANSFER_READ,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            },
+            // the frame waits for the image to be acquired at the transfer stage, which the barrier chains with
+            ImageBarrier {
+                image,
+                old_layout,
+                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                dst_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            },
+        ]);
+        if first_write {
+            command_buffer.clear_color_image(image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, [0.0; 4]);
+            command_buffer.pipeline_image_barriers(&[ImageBarrier {
+                image,
+                old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                dst_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            }]);
+        }
+
+        // the camera viewport is in the window size, which the swapchain only matches once recreated
+        let extent = image.extent;
+        let scale = UVec2::new(extent.width, extent.height).as_vec2() / camera.target_size.as_vec2();
+        let min = (camera.viewport.min.as_vec2() * scale).round().as_uvec2();
+        let max = (camera.viewport.max.as_vec2() * scale).round().as_uvec2().min(UVec2::new(extent.width, extent.height));
+        if min.x < max.x && min.y < max.y {
+            let viewport = vk::Rect2D {
+                offset: vk::Offset2D {
+                    x: min.x as i32,
+                    y: min.y as i32,
+                },
+                extent: vk::Extent2D {
+                    width: max.x - min.x,
+                    height: max.y - min.y,
+                },
+            };
+            command_buffer.blit_image_to_rect(
+                source,
+                vk::ImageLayout::GENERAL,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                viewport,
+                vk::Filter::LINEAR,
+            );
+        }
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image,
+                old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                new_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+                src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                dst_access_mask: vk::AccessFlags2::NONE,
+                src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags2::BOTTOM_OF_PIPE,
+            },
+            ImageBarrier {
+                image: source,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::TRANSFER_READ,
+                dst_access_mask: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            },
+        ]);
+
+        Ok(())
+    }
+}
+
+/// Adds the [`RenderTargetNode`] and the [`WindowTargetNode`] at the end of every built-in camera graph.
+pub struct RenderTargetPlugin;
+
+impl Plugin for RenderTargetPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<WindowTarget>()
+            .add_plugins(ExtractComponentPlugin::<RenderTargetImage>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            for (graph, last_node) in [
+                (PATH_TRACING_GRAPH, TONEMAPPING_NODE),
+                (DEFERRED_GRAPH, TONEMAPPING_NODE),
+                (SPRITE_GRAPH, UPSCALE_NODE),
+            ] {
+                render_app
+                    .add_render_graph_node::<ViewNodeRunner<RenderTargetNode>>(graph, RENDER_TARGET_NODE)
+                    .add_render_graph_node::<ViewNodeRunner<WindowTargetNode>>(graph, WINDOW_TARGET_NODE)
+                    .add_render_graph_edge(graph, last_node, RENDER_TARGET_NODE)
+                    .add_render_graph_edge(graph, last_node, WINDOW_TARGET_NODE);
+            }
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use anyhow::{bail, ensure, Context as _};
+use ash::vk;
+use bevy_app::App;
+use avalanche_asset::Handle;
+use avalanche_hlvk::{ContextBuilder, DeviceFeatures};
+use crate::context::RenderingContext;
+use crate::extract::FrameContext;
+use crate::readback::{Readback, ReadbackSource};
+use crate::render_asset::RenderAssets;
+use crate::render_command::RenderCommandQueue;
+use crate::render_target::RenderTargetImage;
+use crate::texture::Texture;
+
+/// Environment variable which makes [`RenderTest`] write the rendered images as the new goldens.
+pub const UPDATE_GOLDEN_ENV: &str = "AVALANCHE_UPDATE_GOLDEN";
+
+/// Frames rendered after the readback was requested before giving up.
+const READBACK_FRAMES: u32 = 8;
+
+/// An 8 bits RGBA image, the format golden images are stored in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn load_png(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+
+        let data = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], u8::MAX]).collect(),
+            png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]]).collect(),
+            png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, u8::MAX]).collect(),
+            png::ColorType::Indexed => bail!("Indexed PNG {} was not expanded", path.display()),
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            data,
+        })
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.data)?;
+        Ok(())
+    }
+
+    /// Convert texels read back from an image of `format`, only 8 bits RGBA and BGRA formats are supported.
+    pub fn from_texels(width: u32, height: u32, format: vk::Format, mut data: Vec<u8>) -> anyhow::Result<Self> {
+        match format {
+            vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB => {}
+            vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB => {
+                data.chunks_exact_mut(4).for_each(|texel| texel.swap(0, 2));
+            }
+            _ => bail!("Comparing images of format {format:?} isn't supported"),
+        }
+        ensure!(data.len() == width as usize * height as usize * 4, "{} bytes don't make a {width}x{height} image", data.len());
+        Ok(Self { width, height, data })
+    }
+
+    /// Compare against `expected`, returns the differences and an image of the mismatched pixels in red.
+    pub fn diff(&self, expected: &RgbaImage, tolerance: Tolerance) -> anyhow::Result<(ImageDiff, RgbaImage)> {
+        ensure!(
+            (self.width, self.height) == (expected.width, expected.height),
+            "image is {}x{} but {}x{} was expected", self.width, self.height, expected.width, expected.height,
+        );
+        let mut diff = ImageDiff {
+            pixels: self.width as usize * self.height as usize,
+            mismatched_pixels: 0,
+            max_channel_difference: 0,
+        };
+        let mut diff_image = Vec::with_capacity(self.data.len());
+        for (actual, expected) in self.data.chunks_exact(4).zip(expected.data.chunks_exact(4)) {
+            let difference = actual.iter().zip(expected).map(|(a, e)| a.abs_diff(*e)).max().unwrap_or(0);
+            diff.max_channel_difference = diff.max_channel_difference.max(difference);
+            if difference > tolerance.channel {
+                diff.mismatched_pixels += 1;
+                diff_image.extend_from_slice(&[u8::MAX, 0, 0, u8::MAX]);
+            } else {
+                // faded expected image to locate the mismatches
+                diff_image.extend(expected[..3].iter().map(|channel| channel / 4));
+                diff_image.push(u8::MAX);
+            }
+        }
+        Ok((diff, RgbaImage { width: self.width, height: self.height, data: diff_image }))
+    }
+}
+
+/// Differences allowed between a rendered image and its golden.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    /// Largest difference of a channel for a pixel to match
+    pub channel: u8,
+    /// Ratio of mismatched pixels allowed, from 0 to 1
+    pub mismatched_ratio: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            channel: 2,
+            mismatched_ratio: 0.001,
+        }
+    }
+}
+
+/// Result of [`RgbaImage::diff`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageDiff {
+    pub pixels: usize,
+    pub mismatched_pixels: usize,
+    pub max_channel_difference: u8,
+}
+
+impl ImageDiff {
+    pub fn mismatched_ratio(&self) -> f32 {
+        self.mismatched_pixels as f32 / self.pixels.max(1) as f32
+    }
+
+    pub fn passes(&self, tolerance: Tolerance) -> bool {
+        self.mismatched_ratio() <= tolerance.mismatched_ratio
+    }
+}
+
+/// Visual regression test comparing what a camera rendered into a [`RenderTargetImage`] with a golden PNG.
+///
+/// The app is updated without any window to present to, it must provide a [`RenderingContext`],
+/// e.g. [`RenderTest::headless_context`], and a camera rendering into the target.
+/// The rendered image only becomes the golden when [`UPDATE_GOLDEN_ENV`] is set, a missing golden fails the test.
+/// On failure the rendered and diff images are written to the output directory for inspection.
+///
+/// ```ignore
+/// app.insert_resource(RenderTest::headless_context()?);
+/// // add the rendering plugins, spawn a camera rendering into `render_target`...
+/// let diff = RenderTest::new("sprites")
+///     .frames(4)
+///     .run(&mut app, &render_target)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct RenderTest {
+    name: String,
+    frames: u32,
+    tolerance: Tolerance,
+    golden_dir: PathBuf,
+    output_dir: PathBuf,
+}
+
+impl RenderTest {
+    /// Goldens are looked up in `tests/golden` of the package running the test.
+    pub fn new(name: impl Into<String>) -> Self {
+        let root = std::env::var_os("CARGO_MANIFEST_DIR").map_or_else(PathBuf::new, PathBuf::from);
+        Self {
+            name: name.into(),
+            frames: 1,
+            tolerance: Tolerance::default(),
+            golden_dir: root.join("tests").join("golden"),
+            output_dir: std::env::temp_dir().join("avalanche_render_tests"),
+        }
+    }
+
+    /// Frames rendered before the image is read back, to let effects converge.
+    pub fn frames(self, frames: u32) -> Self {
+        Self { frames, ..self }
+    }
+
+    pub fn tolerance(self, tolerance: Tolerance) -> Self {
+        Self { tolerance, ..self }
+    }
+
+    pub fn golden_dir(self, golden_dir: impl Into<PathBuf>) -> Self {
+        Self { golden_dir: golden_dir.into(), ..self }
+    }
+
+    pub fn output_dir(self, output_dir: impl Into<PathBuf>) -> Self {
+        Self { output_dir: output_dir.into(), ..self }
+    }
+
+    pub fn golden_path(&self) -> PathBuf {
+        self.golden_dir.join(format!("{}.png", self.name))
+    }
+
+    /// A [`RenderingContext`] without any window or surface, to insert before the rendering plugins are added.
+    pub fn headless_context() -> anyhow::Result<RenderingContext> {
+        let context = ContextBuilder::headless()
+            .required_device_features(DeviceFeatures::full())
+            .app_name("Avalanche Render Test")
+            .vulkan_version(avalanche_utils::VERSION_1_3)
+            .build()?;
+        Ok(RenderingContext::new(context))
+    }
+
+    /// Render the frames, then compare the target with the golden, failing if they differ more than the tolerance.
+    pub fn run(&self, app: &mut App, target: &RenderTargetImage) -> anyhow::Result<ImageDiff> {
+        for _ in 0..self.frames {
+            app.update();
+        }
+        let actual = read_back_texture(app, target.texture.clone())?;
+
+        let golden_path = self.golden_path();
+        if std::env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+            actual.save_png(&golden_path)?;
+            return Ok(ImageDiff {
+                pixels: actual.width as usize * actual.height as usize,
+                mismatched_pixels: 0,
+                max_channel_difference: 0,
+            });
+        }
+
+        let actual_path = self.output_dir.join(format!("{}.actual.png", self.name));
+        if !golden_path.exists() {
+            actual.save_png(&actual_path)?;
+            bail!(
+                "{}: golden {} is missing, see {} and set {UPDATE_GOLDEN_ENV} to record it",
+                self.name, golden_path.display(), actual_path.display(),
+            );
+        }
+
+        let golden = RgbaImage::load_png(&golden_path)?;
+        let (diff, diff_image) = actual.diff(&golden, self.tolerance)?;
+        if !diff.passes(self.tolerance) {
+            let diff_path = self.output_dir.join(format!("{}.diff.png", self.name));
+            actual.save_png(&actual_path)?;
+            diff_image.save_png(&diff_path)?;
+            bail!(
+                "{}: {} of {} pixels differ from {} (max channel difference {}), see {} and {}",
+                self.name, diff.mismatched_pixels, diff.pixels, golden_path.display(),
+                diff.max_channel_difference, actual_path.display(), diff_path.display(),
+            );
+        }
+        Ok(diff)
+    }
+}
+
+/// Request a [`Readback`] of the texture and update the app until it completes.
+fn read_back_texture(app: &mut App, texture: Handle<Texture>) -> anyhow::Result<RgbaImage> {
+    let result: Arc<Mutex<Option<anyhow::Result<RgbaImage>>>> = Default::default();
+    let sender = result.clone();
+    app.world.resource::<RenderCommandQueue>().push(move |world| {
+        let frame_context = world.resource::<FrameContext>();
+        let Some(gpu_texture) = world.resource::<RenderAssets<Texture>>().get(texture.id()) else {
+            *sender.lock().unwrap() = Some(Err(anyhow::anyhow!("The render target texture isn't prepared")));
+            return;
+        };
+        let image = &gpu_texture.image;
+        let (width, height, format) = (image.extent.width, image.extent.height, image.format);
+        let source = ReadbackSource::Image {
+            image,
+            layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        };
+        let callback_sender = sender.clone();
+        let requested = world.resource::<Readback>().request_with(frame_context, source, move |_, data| {
+            *callback_sender.lock().unwrap() = Some(RgbaImage::from_texels(width, height, format, data.to_vec()));
+        });
+        if let Err(err) = requested {
+            *sender.lock().unwrap() = Some(Err(err));
+        }
+    });
+
+    for _ in 0..READBACK_FRAMES {
+        app.update();
+        if let Some(image) = result.lock().unwrap().take() {
+            return image;
+        }
+    }
+    bail!("The render target wasn't read back after {READBACK_FRAMES} frames, is a frame rendered?")
+}
+
+#[test]
+fn test_rgba_image_from_texels() {
+    let bgra = RgbaImage::from_texels(2, 1, vk::Format::B8G8R8A8_UNORM, vec![1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+    assert_eq!(bgra.data, [3, 2, 1, 4, 7, 6, 5, 8]);
+
+    let rgba = RgbaImage::from_texels(1, 2, vk::Format::R8G8B8A8_SRGB, vec![1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+    assert_eq!(rgba, RgbaImage { width: 1, height: 2, data: vec![1, 2, 3, 4, 5, 6, 7, 8] });
+
+    assert!(RgbaImage::from_texels(2, 2, vk::Format::R8G8B8A8_UNORM, vec![0; 12]).is_err());
+    assert!(RgbaImage::from_texels(1, 1, vk::Format::R16G16B16A16_SFLOAT, vec![0; 8]).is_err());
+}
+
+#[test]
+fn test_rgba_image_diff() {
+    let expected = RgbaImage { width: 2, height: 2, data: vec![100; 16] };
+    let mut actual = expected.clone();
+    // within the default channel tolerance
+    actual.data[0] = 102;
+    // mismatched pixel
+    actual.data[6] = 110;
+
+    let tolerance = Tolerance::default();
+    let (diff, diff_image) = actual.diff(&expected, tolerance).unwrap();
+    assert_eq!(diff, ImageDiff { pixels: 4, mismatched_pixels: 1, max_channel_difference: 10 });
+    assert_eq!(diff.mismatched_ratio(), 0.25);
+    assert!(!diff.passes(tolerance));
+    assert!(diff.passes(Tolerance { mismatched_ratio: 0.25, ..tolerance }));
+    assert!(actual.diff(&expected, Tolerance { channel: 10, ..tolerance }).unwrap().0.passes(tolerance));
+
+    assert_eq!(&diff_image.data[4..8], &[u8::MAX, 0, 0, u8::MAX]);
+    assert_eq!(&diff_image.data[..4], &[25, 25, 25, u8::MAX]);
+
+    let (same, _) = expected.diff(&expected, tolerance).unwrap();
+    assert_eq!(same.mismatched_pixels, 0);
+    assert!(same.passes(Tolerance { channel: 0, mismatched_ratio: 0.0 }));
+
+    let smaller = RgbaImage { width: 1, height: 1, data: vec![100; 4] };
+    assert!(smaller.diff(&expected, tolerance).is_err());
+}
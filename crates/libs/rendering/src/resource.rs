@@ -1,9 +1,11 @@
 pub mod resource_macro;
 pub mod buffer;
 pub mod image;
+pub mod typed_buffer;
 mod extract_param;
 
 pub use resource_macro::*;
 pub use buffer::*;
 pub use image::*;
+pub use typed_buffer::*;
 pub use extract_param::*;
@@ -1,8 +1,8 @@
 use std::ops::Deref;
-use avalanche_utils::define_atomic_id_usize;
+use avalanche_utils::define_atomic_id;
 use crate::render_resource_wrapper;
 
-define_atomic_id_usize!(BufferId);
+define_atomic_id!(BufferId: u64);
 render_resource_wrapper!(ErasedBuffer, avalanche_hlvk::Buffer);
 
 #[derive(Clone, Debug)]
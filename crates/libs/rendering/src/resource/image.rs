@@ -1,8 +1,8 @@
 use std::ops::Deref;
-use avalanche_utils::define_atomic_id_usize;
+use avalanche_utils::define_atomic_id;
 use crate::render_resource_wrapper;
 
-define_atomic_id_usize!(ImageId);
+define_atomic_id!(ImageId: u64);
 render_resource_wrapper!(ErasedImage, avalanche_hlvk::Image);
 
 #[derive(Clone, Debug)]
@@ -36,7 +36,7 @@ impl Deref for Image {
     }
 }
 
-define_atomic_id_usize!(ImageViewId);
+define_atomic_id!(ImageViewId: u64);
 render_resource_wrapper!(ErasedImageView, avalanche_hlvk::ImageView);
 
 #[derive(Clone, Debug)]
@@ -69,7 +69,7 @@ impl Deref for ImageView {
     }
 }
 
-define_atomic_id_usize!(SamplerId);
+define_atomic_id!(SamplerId: u64);
 render_resource_wrapper!(ErasedSampler, avalanche_hlvk::Sampler);
 
 #[derive(Clone, Debug)]
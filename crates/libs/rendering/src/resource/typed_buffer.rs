@@ -0,0 +1,108 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::panic::Location;
+use ash::vk;
+use bytemuck::Pod;
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{default_allocation_name, BufferCreateInfo, Context};
+use crate::prelude::Buffer;
+
+/// A host visible buffer holding an array of `T`, reallocated when a write doesn't fit.
+///
+/// The buffer keeps room for at least one element, descriptors can't point to empty buffers.
+pub struct TypedBuffer<T: Pod> {
+    buffer: Option<Buffer>,
+    usage: vk::BufferUsageFlags,
+    memory_location: MemoryLocation,
+    len: usize,
+    /// Name of the allocations in the leak reports
+    name: Cow<'static, str>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> TypedBuffer<T> {
+    /// Nothing is allocated until the first [`TypedBuffer::write`], the allocations are named after the caller location.
+    #[track_caller]
+    pub fn new(usage: vk::BufferUsageFlags) -> Self {
+        Self {
+            buffer: None,
+            usage,
+            memory_location: memory_location_for_usage(usage),
+            len: 0,
+            name: default_allocation_name::<Self>(Location::caller()).into(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Name the allocations in the leak reports.
+    pub fn with_name(self, name: impl Into<Cow<'static, str>>) -> Self {
+        Self { name: name.into(), ..self }
+    }
+
+    /// Replace the contents with `data`, growing the buffer to the next power of two elements when it is too small.
+    ///
+    /// Returns whether the buffer was reallocated, descriptors referencing it must be updated in that case.
+    pub fn write(&mut self, context: &Context, data: &[T]) -> anyhow::Result<bool> {
+        let required = data.len().max(1);
+        let reallocated = required > self.capacity();
+        if reallocated {
+            let capacity = required.next_power_of_two();
+            let buffer = context.create_buffer_from_info(&BufferCreateInfo {
+                usage: self.usage,
+                memory_location: self.memory_location,
+                size: (capacity * size_of::<T>()) as vk::DeviceSize,
+                queue_family_indices: &[],
+                name: Some(&self.name),
+            })?;
+            self.buffer = Some(buffer.into());
+        }
+
+        let buffer = self.buffer.as_ref().unwrap();
+        if data.is_empty() {
+            buffer.copy_data_to_buffer(bytemuck::bytes_of(&T::zeroed()))?;
+        } else {
+            buffer.copy_data_to_buffer(bytemuck::cast_slice::<T, u8>(data))?;
+        }
+        self.len = data.len();
+
+        Ok(reallocated)
+    }
+
+    /// Number of elements last written.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of elements fitting in the current allocation.
+    pub fn capacity(&self) -> usize {
+        self.buffer
+            .as_ref()
+            .map_or(0, |buffer| buffer.size as usize / size_of::<T>().max(1))
+    }
+
+    #[inline]
+    pub fn buffer(&self) -> Option<&Buffer> {
+        self.buffer.as_ref()
+    }
+}
+
+/// Buffers only used as copy destination are read back, everything else is written by the host.
+fn memory_location_for_usage(usage: vk::BufferUsageFlags) -> MemoryLocation {
+    let shader_usages = vk::BufferUsageFlags::UNIFORM_BUFFER
+        | vk::BufferUsageFlags::STORAGE_BUFFER
+        | vk::BufferUsageFlags::VERTEX_BUFFER
+        | vk::BufferUsageFlags::INDEX_BUFFER
+        | vk::BufferUsageFlags::INDIRECT_BUFFER;
+    if usage.contains(vk::BufferUsageFlags::TRANSFER_DST) && !usage.intersects(shader_usages) {
+        MemoryLocation::GpuToCpu
+    } else {
+        MemoryLocation::CpuToGpu
+    }
+}
@@ -4,24 +4,28 @@ use bevy_ecs::{prelude::Entity, world::World};
 #[cfg(feature = "trace")]
 use bevy_utils::tracing::info_span;
 use bevy_utils::{
-    HashMap,
+    HashMap, HashSet,
 };
 
-#[cfg(feature = "trace")]
-use std::ops::Deref;
 use std::{borrow::Cow, collections::VecDeque};
 use std::sync::Arc;
 use smallvec::{SmallVec, smallvec};
 use thiserror::Error;
 use avalanche_hlvk::{Device, Queue};
+use crate::error_policy::{DisabledRenderNodes, RenderGraphErrorPolicy, RenderNodeFailed};
 use crate::extract::FrameContext;
 use crate::prelude::node_slot::{SlotLabel, SlotType, SlotValue};
-use crate::prelude::{NodeRunError, RenderGraph, RenderGraphContext};
+use crate::prelude::{NodeRunError, RenderGraph, RenderGraphContext, RenderGraphs, RenderLabel};
 use crate::prelude::edge::Edge;
-use crate::prelude::node::{NodeId, NodeState};
+use crate::prelude::node::{NodeId, NodeState, QueueType};
+use crate::statistics::RenderStatistics;
 
 pub(crate) struct RenderGraphRunner;
 
+/// Colors of the debug labels around the commands of sub graphs and nodes in captures.
+const GRAPH_LABEL_COLOR: [f32; 4] = [0.9, 0.6, 0.2, 1.0];
+const NODE_LABEL_COLOR: [f32; 4] = [0.3, 0.6, 0.9, 1.0];
+
 #[derive(Error, Debug)]
 pub enum RenderGraphRunnerError {
     #[error(transparent)]
@@ -36,7 +40,7 @@ pub enum RenderGraphRunnerError {
     MissingInput {
         slot_index: usize,
         slot_name: Cow<'static, str>,
-        graph_name: Option<Cow<'static, str>>,
+        graph_name: Option<RenderLabel>,
     },
     #[error("attempted to use the wrong type for input slot")]
     MismatchedInputSlotType {
@@ -49,53 +53,156 @@ pub enum RenderGraphRunnerError {
     "node (name: '{node_name:?}') has {slot_count} input slots, but was provided {value_count} values"
     )]
     MismatchedInputCount {
-        node_name: Option<Cow<'static, str>>,
+        node_name: Option<RenderLabel>,
         slot_count: usize,
         value_count: usize,
     },
     #[error("failed to submit command buffers")]
     SubmissionError,
+    #[error("failed to schedule the node on its queue: {0}")]
+    QueueScheduling(anyhow::Error),
+    #[error("the frame was skipped after a node failed")]
+    FrameSkipped,
+}
+
+/// State of a run of the graph shared with the runs of its sub graphs.
+pub(crate) struct GraphRun<'a> {
+    pub policy: RenderGraphErrorPolicy,
+    pub disabled: &'a DisabledRenderNodes,
+    pub statistics: RenderStatistics,
+    /// Nodes that failed during the run, empty with [`RenderGraphErrorPolicy::PanicOnError`]
+    pub failures: Vec<RenderNodeFailed>,
+    /// Queue and segment of the frame each node was scheduled in, only tracked with async compute
+    segments: HashMap<NodeId, (QueueType, usize)>,
+}
+
+impl<'a> GraphRun<'a> {
+    pub fn new(policy: RenderGraphErrorPolicy, disabled: &'a DisabledRenderNodes) -> Self {
+        Self {
+            policy,
+            disabled,
+            statistics: RenderStatistics::default(),
+            failures: Vec::new(),
+            segments: HashMap::default(),
+        }
+    }
+
+    /// Schedule the node on its queue after the nodes it depends on,
+    /// waiting for the segments of the other queue they were recorded in.
+    fn schedule(
+        &mut self,
+        frame_context: &FrameContext,
+        node_state: &NodeState,
+        dependencies: &[NodeId],
+    ) -> Result<(), RenderGraphRunnerError> {
+        if !frame_context.has_async_compute() {
+            return Ok(());
+        }
+
+        let queue = node_state.queue;
+        for dependency in dependencies {
+            let Some(&(dependency_queue, segment)) = self.segments.get(dependency) else {
+                continue;
+            };
+            if dependency_queue != queue {
+                frame_context.wait_for_queue(queue, segment).map_err(RenderGraphRunnerError::QueueScheduling)?;
+            }
+        }
+        let segment = frame_context.queue_segment(queue).map_err(RenderGraphRunnerError::QueueScheduling)?;
+        self.segments.insert(node_state.id, (queue, segment));
+        Ok(())
+    }
+
+    /// Record the failure of a node, returns the error to stop the run with according to the policy.
+    fn node_failed(
+        &mut self,
+        graph_name: Option<RenderLabel>,
+        node_state: &NodeState,
+        error: RenderGraphRunnerError,
+    ) -> Result<(), RenderGraphRunnerError> {
+        if self.policy == RenderGraphErrorPolicy::PanicOnError {
+            return Err(error);
+        }
+
+        let mut message = error.to_string();
+        let mut source = std::error::Error::source(&error);
+        while let Some(error) = source {
+            message.push_str(&format!(": {error}"));
+            source = error.source();
+        }
+        self.failures.push(RenderNodeFailed {
+            node: node_state.id,
+            node_name: node_state.name,
+            graph_name,
+            error: message,
+        });
+
+        match self.policy {
+            RenderGraphErrorPolicy::SkipFrame => Err(RenderGraphRunnerError::FrameSkipped),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl RenderGraphRunner {
+    /// Run the graph with the values of its input slots and submit the frame,
+    /// what was recorded since the last run is counted in the statistics of the `run`.
+    ///
+    /// Unless errors panic, the frame is submitted after a failed run for its fence to be signaled.
     pub fn run(
         graph: &RenderGraph,
-        _render_device: Arc<Device>,
+        inputs: &[SlotValue],
+        run: &mut GraphRun,
+        render_device: Arc<Device>,
         queue: &Queue,
         world: &World,
         finalizer: impl FnOnce(&FrameContext),
     ) -> Result<(), RenderGraphRunnerError> {
         let frame_context = world.resource::<FrameContext>();
-        Self::run_graph(graph, None, frame_context, world, &[], None)?;
+        let result = Self::run_graph(graph, None, frame_context, world, inputs, None, None, run);
+        if result.is_err() && run.policy == RenderGraphErrorPolicy::PanicOnError {
+            return result;
+        }
 
         finalizer(frame_context);
 
         {
             #[cfg(feature = "trace")]
             let _span = info_span!("submit_graph_commands").entered();
-            frame_context.command_buffer(0).unwrap().end().map_err(|_err| RenderGraphRunnerError::SubmissionError)?;
             frame_context.submit(queue).map_err(|_err| RenderGraphRunnerError::SubmissionError)?;
         }
-        Ok(())
+
+        // commands recorded while preparing the frame are counted too
+        run.statistics.commands = render_device.command_counters.take();
+        result
     }
 
+    /// Run the nodes of the `graph`, a sub graph run by the `parent` node is scheduled after it.
+    #[allow(clippy::too_many_arguments)]
     fn run_graph(
         graph: &RenderGraph,
-        graph_name: Option<Cow<'static, str>>,
+        graph_name: Option<RenderLabel>,
         frame_context: &FrameContext,
         world: &World,
         inputs: &[SlotValue],
         view_entity: Option<Entity>,
+        parent: Option<NodeId>,
+        run: &mut GraphRun,
     ) -> Result<(), RenderGraphRunnerError> {
         let mut node_outputs: HashMap<NodeId, SmallVec<[SlotValue; 4]>> = HashMap::default();
+        // nodes disabled or failed, and those missing their outputs, they have no outputs
+        let mut skipped_nodes: HashSet<NodeId> = HashSet::default();
         #[cfg(feature = "trace")]
         let span = if let Some(name) = &graph_name {
-            info_span!("run_graph", name = name.deref())
+            info_span!("run_graph", name = name.as_str())
         } else {
             info_span!("run_graph", name = "main_graph")
         };
         #[cfg(feature = "trace")]
             let _guard = span.enter();
+        // the main graph isn't labeled, its nodes are the top level labels
+        let _label = graph_name
+            .and_then(|name| frame_context.scoped_label(QueueType::Graphics, name.as_str(), GRAPH_LABEL_COLOR));
 
         // Queue up nodes without inputs, which can be run immediately
         let mut node_queue: VecDeque<&NodeState> = graph
@@ -127,6 +234,9 @@ impl RenderGraphRunner {
             }
 
             node_outputs.insert(input_node.id, input_values);
+            if let Some(segment) = parent.and_then(|parent| run.segments.get(&parent).copied()) {
+                run.segments.insert(input_node.id, segment);
+            }
 
             for (_, node_state) in graph.iter_node_outputs(input_node.id).expect("node exists") {
                 node_queue.push_front(node_state);
@@ -140,6 +250,7 @@ impl RenderGraphRunner {
             }
 
             let mut slot_indices_and_inputs: SmallVec<[(usize, SlotValue); 4]> = SmallVec::new();
+            let mut missing_inputs = false;
             // check if all dependencies have finished running
             for (edge, input_node) in graph
                 .iter_node_inputs(node_state.id)
@@ -151,7 +262,9 @@ impl RenderGraphRunner {
                         input_index,
                         ..
                     } => {
-                        if let Some(outputs) = node_outputs.get(&input_node.id) {
+                        if skipped_nodes.contains(&input_node.id) {
+                            missing_inputs = true;
+                        } else if let Some(outputs) = node_outputs.get(&input_node.id) {
                             slot_indices_and_inputs
                                 .push((*input_index, outputs[*output_index].clone()));
                         } else {
@@ -168,65 +281,42 @@ impl RenderGraphRunner {
                 }
             }
 
-            // construct final sorted input list
-            slot_indices_and_inputs.sort_by_key(|(index, _)| *index);
-            let inputs: SmallVec<[SlotValue; 4]> = slot_indices_and_inputs
-                .into_iter()
-                .map(|(_, value)| value)
-                .collect();
-
-            if inputs.len() != node_state.input_slots.len() {
-                return Err(RenderGraphRunnerError::MismatchedInputCount {
-                    node_name: node_state.name.clone(),
-                    slot_count: node_state.input_slots.len(),
-                    value_count: inputs.len(),
-                });
+            // skipped nodes are scheduled too, the nodes after them wait for what they depend on
+            let mut dependencies = graph
+                .iter_node_inputs(node_state.id)
+                .expect("node is in graph")
+                .map(|(_, input_node)| input_node.id)
+                .collect::<SmallVec<[NodeId; 4]>>();
+            if dependencies.is_empty() {
+                dependencies.extend(parent);
             }
+            run.schedule(frame_context, node_state, &dependencies)?;
 
-            let mut outputs: SmallVec<[Option<SlotValue>; 4]> =
-                smallvec![None; node_state.output_slots.len()];
-            {
-                let mut context = RenderGraphContext::new(graph, node_state, &inputs, &mut outputs);
-                if let Some(view_entity) = view_entity {
-                    context.set_view_entity(view_entity);
-                }
-
-                {
-                    #[cfg(feature = "trace")]
-                        let _span = info_span!("node", name = node_state.type_name).entered();
+            if missing_inputs || !node_state.enabled || run.disabled.contains(node_state.id) {
+                skipped_nodes.insert(node_state.id);
+                node_outputs.insert(node_state.id, SmallVec::new());
+            } else {
+                // construct final sorted input list
+                slot_indices_and_inputs.sort_by_key(|(index, _)| *index);
+                let inputs: SmallVec<[SlotValue; 4]> = slot_indices_and_inputs
+                    .into_iter()
+                    .map(|(_, value)| value)
+                    .collect();
 
-                    node_state.node.run(&mut context, frame_context, world)?;
-                }
-
-                for run_sub_graph in context.finish() {
-                    let sub_graph = graph
-                        .get_sub_graph(&run_sub_graph.name)
-                        .expect("sub graph exists because it was validated when queued.");
-                    Self::run_graph(
-                        sub_graph,
-                        Some(run_sub_graph.name),
-                        frame_context,
-                        world,
-                        &run_sub_graph.inputs,
-                        run_sub_graph.view_entity,
-                    )?;
+                if inputs.len() != node_state.input_slots.len() {
+                    return Err(RenderGraphRunnerError::MismatchedInputCount {
+                        node_name: node_state.name,
+                        slot_count: node_state.input_slots.len(),
+                        value_count: inputs.len(),
+                    });
                 }
-            }
 
-            let mut values: SmallVec<[SlotValue; 4]> = SmallVec::new();
-            for (i, output) in outputs.into_iter().enumerate() {
-                if let Some(value) = output {
-                    values.push(value);
-                } else {
-                    let empty_slot = node_state.output_slots.get_slot(i).unwrap();
-                    return Err(RenderGraphRunnerError::EmptyNodeOutputSlot {
-                        type_name: node_state.type_name,
-                        slot_index: i,
-                        slot_name: empty_slot.name.clone(),
-                    });
+                let values = Self::run_node(graph, graph_name, node_state, &inputs, frame_context, world, view_entity, run)?;
+                if values.is_none() {
+                    skipped_nodes.insert(node_state.id);
                 }
+                node_outputs.insert(node_state.id, values.unwrap_or_default());
             }
-            node_outputs.insert(node_state.id, values);
 
             for (_, node_state) in graph.iter_node_outputs(node_state.id).expect("node exists") {
                 node_queue.push_front(node_state);
@@ -235,4 +325,76 @@ impl RenderGraphRunner {
 
         Ok(())
     }
+
+    /// Run a node and the sub graphs it queued, returns its outputs or `None` if it was skipped or failed.
+    #[allow(clippy::too_many_arguments)]
+    fn run_node(
+        graph: &RenderGraph,
+        graph_name: Option<RenderLabel>,
+        node_state: &NodeState,
+        inputs: &[SlotValue],
+        frame_context: &FrameContext,
+        world: &World,
+        view_entity: Option<Entity>,
+        run: &mut GraphRun,
+    ) -> Result<Option<SmallVec<[SlotValue; 4]>>, RenderGraphRunnerError> {
+        let mut outputs: SmallVec<[Option<SlotValue>; 4]> =
+            smallvec![None; node_state.output_slots.len()];
+        {
+            let mut context = RenderGraphContext::new(graph, node_state, inputs, &mut outputs);
+            if let Some(view_entity) = view_entity {
+                context.set_view_entity(view_entity);
+            }
+            if let Some(graphs) = world.get_resource::<RenderGraphs>() {
+                context.set_render_graphs(graphs);
+            }
+            if !node_state.node.should_run(&context, world) {
+                return Ok(None);
+            }
+
+            // the sub graphs the node runs are nested in its span and label
+            let name = node_state.name.map_or(node_state.type_name, |name| name.as_str());
+            #[cfg(feature = "trace")]
+                let _span = info_span!("node", name, type_name = node_state.type_name).entered();
+            let _label = frame_context.scoped_label(node_state.queue, name, NODE_LABEL_COLOR);
+
+            if let Err(err) = node_state.node.run(&mut context, frame_context, world) {
+                run.node_failed(graph_name, node_state, err.into())?;
+                return Ok(None);
+            }
+            run.statistics.nodes += 1;
+
+            for run_sub_graph in context.finish() {
+                let sub_graph = RenderGraphs::find(world.get_resource(), graph, run_sub_graph.name)
+                    .expect("sub graph exists because it was validated when queued.");
+                run.statistics.sub_graphs += 1;
+                Self::run_graph(
+                    sub_graph,
+                    Some(run_sub_graph.name),
+                    frame_context,
+                    world,
+                    &run_sub_graph.inputs,
+                    run_sub_graph.view_entity,
+                    Some(node_state.id),
+                    run,
+                )?;
+            }
+        }
+
+        let mut values: SmallVec<[SlotValue; 4]> = SmallVec::new();
+        for (i, output) in outputs.into_iter().enumerate() {
+            if let Some(value) = output {
+                values.push(value);
+            } else {
+                let empty_slot = node_state.output_slots.get_slot(i).unwrap();
+                run.node_failed(graph_name, node_state, RenderGraphRunnerError::EmptyNodeOutputSlot {
+                    type_name: node_state.type_name,
+                    slot_index: i,
+                    slot_name: empty_slot.name.clone(),
+                })?;
+                return Ok(None);
+            }
+        }
+        Ok(Some(values))
+    }
 }
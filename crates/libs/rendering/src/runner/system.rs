@@ -1,32 +1,67 @@
-use std::time::Duration;
-use bevy_ecs::prelude::{Mut, World};
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+use ash::vk;
+use bevy_ecs::prelude::{Mut, Res, World};
+use bevy_time::TimeSender;
 use bevy_log::error;
 use bevy_utils::tracing::info_span;
+use avalanche_hlvk::{PresentOptions, SemaphoreSubmitInfo};
+use crate::error_policy::{DisabledRenderNodes, RenderGraphErrorPolicy, RenderNodeFailures};
 use crate::extract::FrameContext;
-use crate::prelude::RenderGraph;
-use crate::prelude::window::ExtractedWindows;
-use crate::runner::RenderGraphRunner;
+use crate::frame_pacing::{FrameLatency, PresentTiming, QueuedPresents};
+use crate::prelude::{RenderGraph, RenderGraphInputs, RenderGraphs};
+use crate::prelude::window::{AcquiredWindowImage, ExtractedWindows};
+use crate::profiler::GpuProfiler;
+use crate::runner::{GraphRun, RenderGraphRunner, RenderGraphRunnerError};
+use crate::statistics::RenderStatistics;
 
 pub fn render_system(world: &mut World) {
+    acquire_windows(world);
+
     world.resource_scope(|world, mut graph: Mut<RenderGraph>| {
         graph.update(world);
     });
+    world.resource_scope(|world, mut graphs: Mut<RenderGraphs>| {
+        graphs.update(world);
+    });
 
-    let graph = world.resource::<RenderGraph>();
+    let graph = world
+        .resource::<RenderGraphs>()
+        .active_graph()
+        .unwrap_or_else(|| world.resource::<RenderGraph>());
     let frame_context = world.resource::<FrameContext>();
     let render_device = frame_context.device();
     let render_queue = frame_context.graphics_queue();
+    let profiler = world.get_resource::<GpuProfiler>();
+    let inputs = world.resource::<RenderGraphInputs>().values(graph);
+    let mut run = GraphRun::new(*world.resource::<RenderGraphErrorPolicy>(), world.resource::<DisabledRenderNodes>());
 
-    if let Err(err) = RenderGraphRunner::run(
+    let result = RenderGraphRunner::run(
         graph,
+        &inputs,
+        &mut run,
         render_device.clone(),
         &render_queue,
         world,
-        |_context| {}
-    ) {
-        error!("Error running render graph:");
-        {
-            let mut src: &dyn std::error::Error = &err;
+        |context| {
+            if let Some(profiler) = profiler {
+                profiler.end_frame(context);
+            }
+        }
+    );
+    let GraphRun { policy, mut statistics, failures, .. } = run;
+
+    for failure in &failures {
+        let graph_name = failure.graph_name.map_or("main graph", |name| name.as_str());
+        error!(
+            "Render graph node {:?} in {graph_name} failed and is disabled: {}",
+            failure.node_name.map_or("unnamed", |name| name.as_str()), failure.error,
+        );
+    }
+    if let Err(err) = &result {
+        if !matches!(err, RenderGraphRunnerError::FrameSkipped) {
+            error!("Error running render graph:");
+            let mut src: &dyn std::error::Error = err;
             loop {
                 error!("> {}", src);
                 match src.source() {
@@ -35,22 +70,141 @@ pub fn render_system(world: &mut World) {
                 }
             }
         }
+        if policy == RenderGraphErrorPolicy::PanicOnError {
+            panic!("Error running render graph: {err}");
+        }
+    }
 
-        panic!("Error running render graph: {err}");
+    present_windows(world);
+
+    if let Some(timing) = world.get_resource::<PresentTiming>() {
+        (statistics.present_margin, statistics.display_interval) = timing.estimates();
+    }
+    #[cfg(feature = "trace")]
+    statistics.trace();
+    *world.resource_mut::<RenderStatistics>() = statistics;
+    let mut disabled = world.resource_mut::<DisabledRenderNodes>();
+    for failure in &failures {
+        disabled.disable(failure.node);
     }
+    world.resource_mut::<RenderNodeFailures>().0.extend(failures);
+}
+
+/// Acquire an image of every window before the render graph runs, the nodes copy the views into them.
+///
+/// Waits for the presents queued earlier as configured by the [`FrameLatency`], minimized windows are skipped.
+fn acquire_windows(world: &mut World) {
+    let _span = info_span!("acquire_frames").entered();
 
-    {
-        let _span = info_span!("present_frames").entered();
-        
-        let windows = world.resource::<ExtractedWindows>();
-        for window in windows.values() {
+    world.resource_scope(|world, mut windows: Mut<ExtractedWindows>| {
+        let frame_context = world.resource::<FrameContext>();
+        let queued_presents = world.get_resource::<QueuedPresents>();
+        let present_timing = world.get_resource::<PresentTiming>();
+        let latency = world.get_resource::<FrameLatency>();
+        let primary = windows.primary;
+        for (&entity, window) in windows.iter_mut() {
+            window.acquired = None;
+            if window.minimized {
+                continue;
+            }
+            let present_fence = queued_presents.and_then(|presents| presents.wait_for_present(latency, frame_context));
+            let present_id = present_timing
+                .filter(|_| primary == Some(entity))
+                .and_then(|timing| timing.wait_for_display(latency, &window.swapchain));
+            // suboptimal images are presented too, the swapchain is recreated once its window size is stable
             if let Ok(image) = window.swapchain.acquire_next_image(Duration::from_secs_f32(0.033), None) {
-                if !image.is_suboptimal {
-                    let semaphore = frame_context.frame_finish_semaphore();
-                    let queue = frame_context.render_context().present_queue.clone();
-                    let _ = window.swapchain.queue_present(image.index, &[semaphore.as_ref()], &queue);
-                }
+                // the images are only written by transfers, the rest of the frame doesn't wait
+                frame_context.wait_semaphore(window.swapchain.current_acquire_semaphore(), vk::PipelineStageFlags2::TRANSFER);
+                window.acquired = Some(AcquiredWindowImage {
+                    index: image.index,
+                    written: AtomicBool::new(false),
+                    present_fence,
+                    present_id,
+                });
             }
         }
+    });
+}
+
+/// Present the images acquired by [`acquire_windows`] once the frame finished rendering.
+///
+/// A single batch on the present queue waits for the frame, and signals a semaphore per window,
+/// as a binary semaphore can't be waited by several presents. It also waits for the acquired images
+/// when the frame wasn't submitted. Swapchain images are shared concurrently when the present
+/// and graphics families differ, so no ownership transfer is needed.
+fn present_windows(world: &mut World) {
+    let _span = info_span!("present_frames").entered();
+
+    let window_count = world
+        .resource::<ExtractedWindows>()
+        .presentable()
+        .filter(|(_, window)| window.acquired.is_some())
+        .count();
+    if window_count == 0 {
+        return;
+    }
+    let present_semaphores = {
+        let mut frame_context = world.resource_mut::<FrameContext>();
+        (0..window_count).map(|_| frame_context.allocate_semaphore()).collect::<anyhow::Result<Vec<_>>>()
+    };
+    let present_semaphores = match present_semaphores {
+        Ok(semaphores) => semaphores,
+        Err(err) => {
+            error!("Failed to allocate present semaphores: {err}");
+            return;
+        }
+    };
+
+    let frame_context = world.resource::<FrameContext>();
+    let windows = world.resource::<ExtractedWindows>();
+    let queued_presents = world.get_resource::<QueuedPresents>();
+    let present_timing = world.get_resource::<PresentTiming>();
+
+    let queue = &frame_context.render_context().present_queue;
+    let frame_finish_semaphore = frame_context.frame_finish_semaphore();
+    let pending_waits = frame_context.take_pending_waits();
+    let wait_semaphores = std::iter::once(frame_finish_semaphore.as_ref())
+        .chain(pending_waits.iter().map(|(semaphore, _)| semaphore.as_ref()))
+        .map(|semaphore| SemaphoreSubmitInfo {
+            semaphore,
+            stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        })
+        .collect::<Vec<_>>();
+    let signal_semaphores = present_semaphores
+        .iter()
+        .map(|semaphore| SemaphoreSubmitInfo {
+            semaphore: semaphore.as_ref(),
+            stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        })
+        .collect::<Vec<_>>();
+    if let Err(err) = queue.submit_semaphores(&[], &wait_semaphores, &signal_semaphores, None) {
+        error!("Failed to chain the frame to the present queue: {err}");
+        return;
     }
+
+    let acquired = windows
+        .presentable()
+        .filter_map(|(_, window)| Some((window, window.acquired.as_ref()?)));
+    for ((window, image), semaphore) in acquired.zip(&present_semaphores) {
+        let options = PresentOptions {
+            fence: image.present_fence.as_deref(),
+            present_id: image.present_id,
+        };
+        let presented = window.swapchain.queue_present_with(image.index, &[semaphore.as_ref()], queue, options);
+        if presented.is_err() {
+            continue;
+        }
+        if let (Some(presents), Some(fence)) = (queued_presents, &image.present_fence) {
+            presents.push(fence.clone());
+        }
+        if let (Some(timing), Some(present_id)) = (present_timing, image.present_id) {
+            timing.push(present_id);
+        }
+    }
+}
+
+/// Sends the instant the frame finished to the main app, where it drives [`Time`](bevy_time::Time).
+pub fn time_system(time_sender: Res<TimeSender>) {
+    // the channel is bounded and may be full while the main app is still running the previous frame
+    let _ = time_sender.0.try_send(Instant::now());
 }
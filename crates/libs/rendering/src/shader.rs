@@ -0,0 +1,94 @@
+mod layout;
+pub use layout::*;
+
+use std::ffi::CString;
+use std::path::PathBuf;
+use std::sync::Arc;
+use anyhow::Context as _;
+use ash::vk;
+use bevy_ecs::prelude::Resource;
+use avalanche_hlvk::{Context, StagedShader};
+
+/// Environment variable overriding where compiled shaders are loaded from.
+pub const SHADER_DIR_ENV: &str = "AVALANCHE_SHADER_DIR";
+
+/// Directory holding the SPIR-V binaries compiled by the build script.
+#[derive(Resource, Clone, Debug)]
+pub struct ShaderDirectory(pub PathBuf);
+
+impl Default for ShaderDirectory {
+    fn default() -> Self {
+        if let Some(dir) = std::env::var_os(SHADER_DIR_ENV) {
+            return Self(dir.into());
+        }
+
+        match option_env!("AVALANCHE_COMPILED_SHADER_DIR") {
+            Some(dir) => Self(dir.into()),
+            None => Self(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("shaders")),
+        }
+    }
+}
+
+impl ShaderDirectory {
+    /// Load `<name>.spv`, `name` is relative to the shader directory, e.g. `path_tracing/raygen.rgen`.
+    pub fn load(&self, context: &Context, name: &str, stage: vk::ShaderStageFlags) -> anyhow::Result<StagedShader> {
+        let path = self.0.join(format!("{name}.spv"));
+        let bytes = std::fs::read(&path)
+            .with_context(|| format!("Failed to read shader {}", path.display()))?;
+        let module = context.create_shader_module(&bytes)?;
+
+        Ok(StagedShader {
+            entry_point_name: CString::new("main")?,
+            stage,
+            module: Arc::new(module),
+        })
+    }
+}
+
+#[test]
+fn test_reflect_compiled_shaders() {
+    use std::path::Path;
+    use avalanche_hlvk::{ShaderInput, ShaderReflection};
+
+    fn reflect_dir(dir: &Path, reflected: &mut Vec<(PathBuf, ShaderReflection)>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                reflect_dir(&path, reflected);
+            } else if path.extension().is_some_and(|extension| extension == "spv") {
+                let words = std::fs::read(&path)
+                    .unwrap()
+                    .chunks_exact(4)
+                    .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+                    .collect::<Vec<_>>();
+                let reflection = ShaderReflection::from_spv(&words)
+                    .unwrap_or_else(|err| panic!("Failed to reflect {}: {err:#}", path.display()));
+                reflected.push((path, reflection));
+            }
+        }
+    }
+
+    // the build script only compiles the shaders when glslc is installed
+    let Some(dir) = option_env!("AVALANCHE_COMPILED_SHADER_DIR") else {
+        return;
+    };
+    let mut reflected = Vec::new();
+    reflect_dir(Path::new(dir), &mut reflected);
+    assert!(!reflected.is_empty());
+
+    for (path, reflection) in &reflected {
+        if path.to_string_lossy().ends_with(".vert.spv") {
+            assert!(
+                reflection.inputs.iter().all(|input| input.format.is_some()),
+                "{} has a vertex input without a format", path.display(),
+            );
+        }
+    }
+
+    let (_, mesh) = reflected
+        .iter()
+        .find(|(path, _)| path.ends_with("debug/mesh.vert.spv"))
+        .unwrap();
+    assert_eq!(mesh.inputs.first(), Some(&ShaderInput { location: 0, format: Some(vk::Format::R32G32B32_SFLOAT) }));
+    assert!(mesh.push_constant_size.is_some());
+}
@@ -0,0 +1,186 @@
+use bevy_math::{IVec2, IVec3, IVec4, Mat2, Mat3, Mat4, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4};
+
+pub use avalanche_rendering_macros::ShaderType;
+
+/// Memory layout rules of a GLSL uniform or storage block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LayoutRules {
+    /// Uniform buffers, arrays and structs are aligned to 16 bytes
+    Std140,
+    /// Storage buffers and push constants
+    Std430,
+}
+
+/// Size and alignment of a type in bytes under some [`LayoutRules`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TypeLayout {
+    pub size: usize,
+    pub align: usize,
+}
+
+impl TypeLayout {
+    /// Distance between two elements of an array of the type.
+    pub fn array_stride(&self, rules: LayoutRules) -> usize {
+        round_up(self.size, array_align(self.align, rules))
+    }
+}
+
+/// A type uploaded to uniform or storage buffers with the padding GLSL expects,
+/// derive it for structs whose fields all implement it.
+///
+/// `bool` is uploaded as a 4 bytes integer like GLSL does.
+pub trait ShaderType {
+    fn layout(rules: LayoutRules) -> TypeLayout;
+
+    /// Write the value at the start of `out`, which holds at least `layout(rules).size` bytes.
+    /// Padding bytes are left untouched.
+    fn write(&self, rules: LayoutRules, out: &mut [u8]);
+
+    /// Bytes of the value in a uniform buffer.
+    fn std140_bytes(&self) -> Vec<u8> where Self: Sized {
+        self.to_bytes(LayoutRules::Std140)
+    }
+
+    /// Bytes of the value in a storage buffer.
+    fn std430_bytes(&self) -> Vec<u8> where Self: Sized {
+        self.to_bytes(LayoutRules::Std430)
+    }
+
+    fn to_bytes(&self, rules: LayoutRules) -> Vec<u8> where Self: Sized {
+        let mut bytes = vec![0; Self::layout(rules).size];
+        self.write(rules, &mut bytes);
+        bytes
+    }
+}
+
+/// Computes the offsets of the members of a struct one after another, used by the [`ShaderType`] derive.
+#[derive(Clone, Copy, Debug)]
+pub struct StructLayout {
+    rules: LayoutRules,
+    offset: usize,
+    align: usize,
+}
+
+impl StructLayout {
+    pub fn new(rules: LayoutRules) -> Self {
+        Self {
+            rules,
+            offset: 0,
+            align: 1,
+        }
+    }
+
+    /// Place the next member, returns its offset.
+    pub fn field(&mut self, layout: TypeLayout) -> usize {
+        let offset = round_up(self.offset, layout.align);
+        self.offset = offset + layout.size;
+        self.align = self.align.max(layout.align);
+        offset
+    }
+
+    pub fn finish(&self) -> TypeLayout {
+        let align = array_align(self.align, self.rules);
+        TypeLayout {
+            size: round_up(self.offset, align),
+            align,
+        }
+    }
+}
+
+#[inline]
+fn round_up(value: usize, align: usize) -> usize {
+    value.div_ceil(align) * align
+}
+
+/// Arrays and structs are aligned like a vec4 at least under std140.
+#[inline]
+fn array_align(align: usize, rules: LayoutRules) -> usize {
+    match rules {
+        LayoutRules::Std140 => round_up(align, 16),
+        LayoutRules::Std430 => align,
+    }
+}
+
+macro_rules! impl_scalar {
+    ($($t: ty),*) => {
+        $(impl ShaderType for $t {
+            fn layout(_rules: LayoutRules) -> TypeLayout {
+                TypeLayout { size: 4, align: 4 }
+            }
+
+            fn write(&self, _rules: LayoutRules, out: &mut [u8]) {
+                out[..4].copy_from_slice(&self.to_ne_bytes());
+            }
+        })*
+    };
+}
+
+impl_scalar!(f32, i32, u32);
+
+impl ShaderType for bool {
+    fn layout(rules: LayoutRules) -> TypeLayout {
+        u32::layout(rules)
+    }
+
+    fn write(&self, rules: LayoutRules, out: &mut [u8]) {
+        u32::from(*self).write(rules, out);
+    }
+}
+
+macro_rules! impl_vector {
+    ($($t: ty, $n: literal);*) => {
+        $(impl ShaderType for $t {
+            fn layout(_rules: LayoutRules) -> TypeLayout {
+                // a vec3 is aligned like a vec4
+                TypeLayout { size: 4 * $n, align: if $n == 2 { 8 } else { 16 } }
+            }
+
+            fn write(&self, rules: LayoutRules, out: &mut [u8]) {
+                for (i, component) in self.to_array().iter().enumerate() {
+                    component.write(rules, &mut out[4 * i..]);
+                }
+            }
+        })*
+    };
+}
+
+impl_vector!(
+    Vec2, 2; Vec3, 3; Vec4, 4;
+    IVec2, 2; IVec3, 3; IVec4, 4;
+    UVec2, 2; UVec3, 3; UVec4, 4
+);
+
+macro_rules! impl_matrix {
+    ($($t: ty: $column: ty, $n: literal);*) => {
+        $(impl ShaderType for $t {
+            /// Column major, laid out like an array of its columns
+            fn layout(rules: LayoutRules) -> TypeLayout {
+                <[$column; $n]>::layout(rules)
+            }
+
+            fn write(&self, rules: LayoutRules, out: &mut [u8]) {
+                let columns: [$column; $n] = std::array::from_fn(|i| self.col(i));
+                columns.write(rules, out);
+            }
+        })*
+    };
+}
+
+impl_matrix!(Mat2: Vec2, 2; Mat3: Vec3, 3; Mat4: Vec4, 4);
+
+impl<T: ShaderType, const N: usize> ShaderType for [T; N] {
+    fn layout(rules: LayoutRules) -> TypeLayout {
+        let element = T::layout(rules);
+        TypeLayout {
+            size: element.array_stride(rules) * N,
+            align: array_align(element.align, rules),
+        }
+    }
+
+    fn write(&self, rules: LayoutRules, out: &mut [u8]) {
+        let stride = T::layout(rules).array_stride(rules);
+        for (i, element) in self.iter().enumerate() {
+            element.write(rules, &mut out[stride * i..]);
+        }
+    }
+}
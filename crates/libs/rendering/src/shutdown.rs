@@ -0,0 +1,55 @@
+use std::sync::Arc;
+use bevy_app::App;
+use bevy_log::{debug, error, warn};
+use avalanche_window::WindowComponent;
+use crate::RenderApp;
+use crate::pipelined_rendering::{MainToRenderAppSender, RenderToMainAppReceiver};
+use crate::prelude::RenderingContext;
+
+/// Release every GPU resource of `app` in an order valid for Vulkan, once it stopped updating.
+///
+/// 1. The render app is taken back from the render thread, which stops once its channel is closed.
+/// 2. The device is waited for, so no submitted frame still uses the resources.
+/// 3. The render world is dropped with everything it keeps alive: staging buffers, prepared assets, pipelines.
+/// 4. Swapchains of windows are destroyed before their surfaces.
+/// 5. Buffers and images still alive are reported as leaks.
+/// 6. The [`RenderingContext`] goes last, the device and instance are destroyed with its final reference.
+pub fn shutdown_rendering(app: &mut App) {
+    let render_app = match app.world.remove_resource::<RenderToMainAppReceiver>() {
+        Some(receiver) => {
+            let render_app = receiver.0.recv_blocking().ok();
+            app.world.remove_resource::<MainToRenderAppSender>();
+            render_app
+        }
+        None => app.remove_sub_app(RenderApp),
+    };
+
+    let Some(rendering_context) = app.world.remove_resource::<RenderingContext>() else {
+        return;
+    };
+    if let Err(err) = rendering_context.context.device_wait_idle() {
+        error!("Failed to wait for the device to be idle: {err}");
+    }
+
+    drop(render_app);
+    debug!("Render world released");
+
+    let mut windows = app.world.query::<&mut WindowComponent>();
+    for mut window in windows.iter_mut(&mut app.world) {
+        window.swapchain = None;
+    }
+    for mut window in windows.iter_mut(&mut app.world) {
+        window.surface = None;
+        window.render_device = None;
+    }
+
+    let references = Arc::strong_count(&rendering_context.context);
+    if references > 1 {
+        warn!("Rendering context is still referenced {} times after shutdown, destroying the device late", references - 1);
+    }
+    let leaks = rendering_context.context.report_memory_leaks();
+    if !leaks.is_empty() {
+        warn!("GPU memory still allocated after shutdown: {leaks}");
+    }
+    drop(rendering_context);
+}
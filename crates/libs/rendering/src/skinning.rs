@@ -0,0 +1,234 @@
+mod animation;
+mod gltf;
+
+pub use animation::*;
+pub use self::gltf::*;
+
+use ash::vk;
+use bevy_app::{App, Plugin, PostUpdate};
+use bevy_ecs::prelude::{Commands, Component, Entity, IntoSystemConfigs, Query, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_math::Mat4;
+use bevy_time::Time;
+use bevy_transform::prelude::Transform;
+use bevy_utils::EntityHashMap;
+use avalanche_asset::{Asset, AssetApp, Assets, Handle};
+use crate::{ExtractSchedule, Render, RenderApp, RenderSet};
+use crate::extract::FrameContext;
+use crate::mesh::{Mesh, MeshAttributes, MeshBuffers, MeshVertexLayout};
+use crate::prelude::{Buffer, Extract};
+use crate::resource::TypedBuffer;
+
+/// A bone of a [`Skeleton`].
+#[derive(Clone, Debug)]
+pub struct Joint {
+    pub name: Option<String>,
+    /// Index of the parent joint, always lower than the index of this joint
+    pub parent: Option<usize>,
+    /// Transform relative to the parent joint when not animated
+    pub rest: Transform,
+    /// Transforms the mesh into the space of the joint in the bind pose
+    pub inverse_bind: Mat4,
+}
+
+/// Joint hierarchy deforming a skinned [`Mesh`], parents precede their children.
+#[derive(Clone, Debug, Default)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+}
+
+impl Skeleton {
+    /// Local transform of every joint at rest.
+    pub fn rest_pose(&self) -> Vec<Transform> {
+        self.joints.iter().map(|joint| joint.rest).collect()
+    }
+
+    /// Write the matrices deforming the mesh by the local joint transforms of `pose` into `palette`.
+    pub fn palette(&self, pose: &[Transform], palette: &mut Vec<Mat4>) {
+        palette.clear();
+        // model space joint transforms first, parents are computed before their children
+        for (joint, local) in self.joints.iter().zip(pose) {
+            let model_from_joint = match joint.parent {
+                Some(parent) => palette[parent] * local.compute_matrix(),
+                None => local.compute_matrix(),
+            };
+            palette.push(model_from_joint);
+        }
+        for (matrix, joint) in palette.iter_mut().zip(&self.joints) {
+            *matrix *= joint.inverse_bind;
+        }
+    }
+}
+
+/// A skinned [`Mesh`] with its [`Skeleton`] and the [`AnimationClip`]s of its joints.
+#[derive(Clone, Debug, Default)]
+pub struct SkinnedModel {
+    pub mesh: Mesh,
+    pub skeleton: Skeleton,
+    pub clips: Vec<AnimationClip>,
+}
+
+impl Asset for SkinnedModel {}
+
+impl SkinnedModel {
+    pub fn clip_index(&self, name: &str) -> Option<usize> {
+        self.clips.iter().position(|clip| clip.name.as_deref() == Some(name))
+    }
+}
+
+/// Plays an [`AnimationClip`] of a [`SkinnedModel`] on an entity, sampled into its [`SkinPose`] every frame.
+///
+/// The [`MeshBuffers`] of the entity are expected to hold the mesh of the model.
+#[derive(Component, Clone, Debug)]
+pub struct AnimationPlayer {
+    pub model: Handle<SkinnedModel>,
+    /// Index into the clips of the model, the rest pose is used when `None`
+    pub clip: Option<usize>,
+    /// Seconds since the clip started
+    pub time: f32,
+    pub speed: f32,
+    /// Start over at the end of the clip instead of holding the last pose
+    pub repeat: bool,
+    pub paused: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new(model: Handle<SkinnedModel>) -> Self {
+        Self {
+            model,
+            clip: None,
+            time: 0.0,
+            speed: 1.0,
+            repeat: true,
+            paused: false,
+        }
+    }
+
+    /// Start a clip from its beginning.
+    pub fn play(&mut self, clip: usize) -> &mut Self {
+        self.clip = Some(clip);
+        self.time = 0.0;
+        self
+    }
+}
+
+/// Joint matrices of an entity with an [`AnimationPlayer`], in the space of its mesh.
+#[derive(Component, Clone, Debug, Default)]
+pub struct SkinPose {
+    pub palette: Vec<Mat4>,
+}
+
+/// Advance every [`AnimationPlayer`] and sample its clip into the [`SkinPose`] of the entity.
+pub fn animate_skins(
+    mut commands: Commands,
+    time: Res<Time>,
+    models: Res<Assets<SkinnedModel>>,
+    mut players: Query<(Entity, &mut AnimationPlayer, Option<&mut SkinPose>)>,
+) {
+    for (entity, mut player, pose) in players.iter_mut() {
+        let Some(model) = models.get(&player.model) else {
+            continue;
+        };
+        let clip = player.clip.and_then(|clip| model.clips.get(clip));
+
+        if let Some(clip) = clip.filter(|_| !player.paused) {
+            let mut clip_time = player.time + time.delta_seconds() * player.speed;
+            if player.repeat && clip.duration > 0.0 {
+                clip_time = clip_time.rem_euclid(clip.duration);
+            } else {
+                clip_time = clip_time.clamp(0.0, clip.duration);
+            }
+            player.time = clip_time;
+        }
+
+        let mut local_pose = model.skeleton.rest_pose();
+        if let Some(clip) = clip {
+            clip.sample(player.time, &mut local_pose);
+        }
+        match pose {
+            Some(mut pose) => model.skeleton.palette(&local_pose, &mut pose.palette),
+            None => {
+                let mut pose = SkinPose::default();
+                model.skeleton.palette(&local_pose, &mut pose.palette);
+                commands.entity(entity).insert(pose);
+            }
+        }
+    }
+}
+
+/// Joint matrices of every posed entity of the frame, concatenated in one storage buffer.
+#[derive(Resource)]
+pub struct SkinPalettes {
+    matrices: Vec<[f32; 16]>,
+    offsets: EntityHashMap<Entity, u32>,
+    buffer: TypedBuffer<[f32; 16]>,
+}
+
+impl Default for SkinPalettes {
+    fn default() -> Self {
+        Self {
+            matrices: Vec::new(),
+            offsets: Default::default(),
+            buffer: TypedBuffer::new(vk::BufferUsageFlags::STORAGE_BUFFER),
+        }
+    }
+}
+
+impl SkinPalettes {
+    /// Holds at least one matrix once prepared, so it can be bound when no entity is posed.
+    #[inline]
+    pub fn buffer(&self) -> Option<&Buffer> {
+        self.buffer.buffer()
+    }
+
+    /// Index of the first joint matrix of an entity in the buffer.
+    #[inline]
+    pub fn offset(&self, entity: Entity) -> Option<u32> {
+        self.offsets.get(&entity).copied()
+    }
+
+    /// Layout an entity is drawn with, skinned meshes without a pose this frame are drawn in their bind pose.
+    pub fn vertex_layout(&self, entity: Entity, mesh: &MeshBuffers) -> MeshVertexLayout {
+        let mut layout = mesh.vertex_layout();
+        if self.offset(entity).is_none() {
+            layout.attributes.remove(MeshAttributes::SKIN);
+        }
+        layout
+    }
+}
+
+fn extract_skin_palettes(mut palettes: ResMut<SkinPalettes>, poses: Extract<Query<(Entity, &SkinPose)>>) {
+    let palettes = palettes.as_mut();
+    palettes.matrices.clear();
+    palettes.offsets.clear();
+    for (entity, pose) in poses.iter() {
+        palettes.offsets.insert(entity, palettes.matrices.len() as u32);
+        palettes.matrices.extend(pose.palette.iter().map(Mat4::to_cols_array));
+    }
+}
+
+fn prepare_skin_palettes(mut palettes: ResMut<SkinPalettes>, frame_context: Res<FrameContext>) {
+    let palettes = palettes.as_mut();
+    if let Err(err) = palettes.buffer.write(frame_context.render_context(), &palettes.matrices) {
+        error!("Failed to upload skin palettes: {err}");
+        palettes.offsets.clear();
+    }
+}
+
+/// Adds the [`SkinnedModel`] asset loaded from glTF files, and animates entities with an [`AnimationPlayer`].
+pub struct SkinningPlugin;
+
+impl Plugin for SkinningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<SkinnedModel>()
+            .register_asset_loader(GltfSkinLoader)
+            .add_systems(PostUpdate, animate_skins);
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<SkinPalettes>()
+                .add_systems(ExtractSchedule, extract_skin_palettes)
+                .add_systems(Render, prepare_skin_palettes.in_set(RenderSet::PrepareResources));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use bevy_math::{Quat, Vec3};
+use bevy_transform::prelude::Transform;
+
+/// How a value between two keyframes is computed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyframeInterpolation {
+    /// Keep the value of the previous keyframe
+    Step,
+    /// Lerp translations and scales, slerp rotations
+    #[default]
+    Linear,
+}
+
+/// Keyframe values of the joint property a channel animates.
+#[derive(Clone, Debug)]
+pub enum JointKeyframes {
+    Translation(Vec<Vec3>),
+    Rotation(Vec<Quat>),
+    Scale(Vec<Vec3>),
+}
+
+/// Keyframes of one property of one joint.
+#[derive(Clone, Debug)]
+pub struct AnimationChannel {
+    /// Index into the joints of the [`Skeleton`](crate::skinning::Skeleton)
+    pub joint: usize,
+    /// Increasing keyframe times in seconds, one per value
+    pub times: Vec<f32>,
+    pub keyframes: JointKeyframes,
+    pub interpolation: KeyframeInterpolation,
+}
+
+impl AnimationChannel {
+    /// Keyframes around `time` and the interpolation factor between them, clamped to the first and last ones.
+    fn keyframes_at(&self, time: f32) -> Option<(usize, usize, f32)> {
+        let last = self.times.len().checked_sub(1)?;
+        let next = self.times.partition_point(|keyframe| *keyframe <= time);
+        if next == 0 {
+            return Some((0, 0, 0.0));
+        }
+        if next > last {
+            return Some((last, last, 0.0));
+        }
+
+        let previous = next - 1;
+        let factor = match self.interpolation {
+            KeyframeInterpolation::Step => 0.0,
+            KeyframeInterpolation::Linear => {
+                let span = self.times[next] - self.times[previous];
+                if span > 0.0 { (time - self.times[previous]) / span } else { 0.0 }
+            }
+        };
+        Some((previous, next, factor))
+    }
+
+    /// Overwrite the animated property of `pose` with its value at `time`.
+    pub fn sample(&self, time: f32, pose: &mut Transform) {
+        let Some((previous, next, factor)) = self.keyframes_at(time) else {
+            return;
+        };
+        match &self.keyframes {
+            JointKeyframes::Translation(values) => {
+                if let (Some(a), Some(b)) = (values.get(previous), values.get(next)) {
+                    pose.translation = a.lerp(*b, factor);
+                }
+            }
+            JointKeyframes::Rotation(values) => {
+                if let (Some(a), Some(b)) = (values.get(previous), values.get(next)) {
+                    pose.rotation = a.slerp(*b, factor);
+                }
+            }
+            JointKeyframes::Scale(values) => {
+                if let (Some(a), Some(b)) = (values.get(previous), values.get(next)) {
+                    pose.scale = a.lerp(*b, factor);
+                }
+            }
+        }
+    }
+}
+
+/// Keyframed joint transforms of a [`Skeleton`](crate::skinning::Skeleton), joints without a channel keep their rest transform.
+#[derive(Clone, Debug, Default)]
+pub struct AnimationClip {
+    pub name: Option<String>,
+    /// Time of the last keyframe in seconds
+    pub duration: f32,
+    pub channels: Vec<AnimationChannel>,
+}
+
+impl AnimationClip {
+    /// Write the sampled properties into the local joint transforms of `pose`.
+    pub fn sample(&self, time: f32, pose: &mut [Transform]) {
+        for channel in &self.channels {
+            if let Some(joint) = pose.get_mut(channel.joint) {
+                channel.sample(time, joint);
+            }
+        }
+    }
+}
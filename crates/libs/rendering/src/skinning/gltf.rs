@@ -0,0 +1,224 @@
+use std::path::Path;
+use anyhow::{bail, ensure, Context};
+use bevy_math::{Mat4, Quat, Vec3};
+use bevy_transform::prelude::Transform;
+use bevy_utils::HashMap;
+use gltf::animation::util::ReadOutputs;
+use gltf::animation::Interpolation;
+use gltf::buffer::Source;
+use gltf::mesh::Mode;
+use avalanche_asset::AssetLoader;
+use crate::mesh::{Mesh, MeshSkinVertex, MeshVertex};
+use crate::skinning::{
+    AnimationChannel, AnimationClip, Joint, JointKeyframes, KeyframeInterpolation, Skeleton, SkinnedModel,
+};
+
+/// Loads the first skin of binary glTF files as a [`SkinnedModel`], with the mesh it deforms and the animations of its joints.
+///
+/// Buffers must be stored in the binary chunk. The mesh is expected in the space of the parent of the root joints,
+/// as exporters write it, and cubic spline keyframes are interpolated linearly.
+pub struct GltfSkinLoader;
+
+impl AssetLoader for GltfSkinLoader {
+    type Asset = SkinnedModel;
+
+    fn extensions(&self) -> &[&str] {
+        &["glb"]
+    }
+
+    fn load(&self, bytes: &[u8], _path: &Path) -> anyhow::Result<SkinnedModel> {
+        let gltf = gltf::Gltf::from_slice(bytes).context("Invalid glTF file")?;
+        let buffers = gltf
+            .buffers()
+            .map(|buffer| match buffer.source() {
+                Source::Bin => gltf.blob.as_deref().context("glTF binary chunk is missing"),
+                Source::Uri(uri) => bail!("External glTF buffer {uri} is not supported"),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let skin = gltf.skins().next().context("glTF file has no skin")?;
+        let mesh = gltf
+            .nodes()
+            .filter(|node| node.skin().is_some_and(|node_skin| node_skin.index() == skin.index()))
+            .find_map(|node| node.mesh())
+            .context("No glTF mesh uses the skin")?;
+
+        let (skeleton, joint_of_node, remap) = load_skeleton(&skin, &buffers)?;
+        Ok(SkinnedModel {
+            mesh: load_mesh(&mesh, &buffers, &remap)?,
+            skeleton,
+            clips: gltf
+                .animations()
+                .map(|animation| load_clip(&animation, &joint_of_node, &buffers))
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+/// The joints of a skin sorted parents first, the joint of every node and the sorted index of every skin joint.
+fn load_skeleton(skin: &gltf::Skin, buffers: &[&[u8]]) -> anyhow::Result<(Skeleton, HashMap<usize, usize>, Vec<u16>)> {
+    let nodes = skin.joints().collect::<Vec<_>>();
+    ensure!(nodes.len() <= u16::MAX as usize + 1, "glTF skin has {} joints", nodes.len());
+    let node_joints = nodes
+        .iter()
+        .enumerate()
+        .map(|(joint, node)| (node.index(), joint))
+        .collect::<HashMap<_, _>>();
+
+    let mut parents = vec![None; nodes.len()];
+    for (joint, node) in nodes.iter().enumerate() {
+        for child in node.children() {
+            if let Some(child_joint) = node_joints.get(&child.index()) {
+                parents[*child_joint] = Some(joint);
+            }
+        }
+    }
+    // sorting by depth places parents before their children
+    let depths = (0..nodes.len())
+        .map(|joint| std::iter::successors(parents[joint], |parent| parents[*parent]).take(nodes.len()).count())
+        .collect::<Vec<_>>();
+    let mut order = (0..nodes.len()).collect::<Vec<_>>();
+    order.sort_by_key(|joint| depths[*joint]);
+    let mut remap = vec![0; nodes.len()];
+    for (sorted, joint) in order.iter().enumerate() {
+        remap[*joint] = sorted as u16;
+    }
+
+    let inverse_binds = skin
+        .reader(|buffer| buffers.get(buffer.index()).copied())
+        .read_inverse_bind_matrices()
+        .map(|matrices| matrices.map(|matrix| Mat4::from_cols_array_2d(&matrix)).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let joints = order
+        .iter()
+        .map(|joint| {
+            let node = &nodes[*joint];
+            let (translation, rotation, scale) = node.transform().decomposed();
+            Joint {
+                name: node.name().map(str::to_owned),
+                parent: parents[*joint].map(|parent| remap[parent] as usize),
+                rest: Transform {
+                    translation: Vec3::from(translation),
+                    rotation: Quat::from_array(rotation),
+                    scale: Vec3::from(scale),
+                },
+                inverse_bind: inverse_binds.get(*joint).copied().unwrap_or(Mat4::IDENTITY),
+            }
+        })
+        .collect();
+
+    let joint_of_node = node_joints
+        .into_iter()
+        .map(|(node, joint)| (node, remap[joint] as usize))
+        .collect();
+    Ok((Skeleton { joints }, joint_of_node, remap))
+}
+
+/// Every triangle primitive of a mesh merged into one [`Mesh`], joints are remapped to the sorted skeleton.
+fn load_mesh(mesh: &gltf::Mesh, buffers: &[&[u8]], remap: &[u16]) -> anyhow::Result<Mesh> {
+    let mut vertices = Vec::new();
+    let mut skin = Vec::new();
+    let mut indices = Vec::new();
+
+    for primitive in mesh.primitives() {
+        ensure!(primitive.mode() == Mode::Triangles, "glTF primitive mode {:?} is not supported", primitive.mode());
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).copied());
+        let positions = reader.read_positions().context("glTF primitive has no positions")?;
+        let normals = reader.read_normals().context("glTF primitive has no normals")?;
+        let uvs = reader
+            .read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let joints = reader.read_joints(0).context("glTF primitive has no joints")?.into_u16();
+        let weights = reader.read_weights(0).context("glTF primitive has no weights")?.into_f32();
+
+        let first_vertex = vertices.len() as u32;
+        for (i, (position, normal)) in positions.zip(normals).enumerate() {
+            vertices.push(MeshVertex {
+                position,
+                normal,
+                uv: uvs.get(i).copied().unwrap_or_default(),
+            });
+        }
+        for (joints, weights) in joints.zip(weights) {
+            let mut remapped = [0; 4];
+            for (remapped, joint) in remapped.iter_mut().zip(joints) {
+                *remapped = *remap.get(joint as usize).context("glTF vertex joint out of range")?;
+            }
+            // normalized here so shaders don't have to
+            let sum = weights.iter().sum::<f32>();
+            skin.push(MeshSkinVertex {
+                joints: remapped,
+                weights: if sum > 0.0 { weights.map(|weight| weight / sum) } else { [1.0, 0.0, 0.0, 0.0] },
+            });
+        }
+        ensure!(
+            skin.len() == vertices.len(),
+            "glTF primitive has {} vertices but {} joint influences", vertices.len(), skin.len(),
+        );
+
+        match reader.read_indices() {
+            Some(primitive_indices) => indices.extend(primitive_indices.into_u32().map(|index| first_vertex + index)),
+            None => indices.extend(first_vertex..vertices.len() as u32),
+        }
+    }
+
+    Ok(Mesh {
+        vertices,
+        indices: Some(indices),
+        skin: Some(skin),
+    })
+}
+
+/// Cubic spline keyframes store their value between an in and an out tangent.
+fn keyframe_values<T>(values: impl Iterator<Item = T>, cubic_spline: bool) -> Vec<T> {
+    match cubic_spline {
+        true => values.skip(1).step_by(3).collect(),
+        false => values.collect(),
+    }
+}
+
+/// The channels of an animation targeting joints, others are skipped.
+fn load_clip(
+    animation: &gltf::Animation,
+    joint_of_node: &HashMap<usize, usize>,
+    buffers: &[&[u8]],
+) -> anyhow::Result<AnimationClip> {
+    let mut channels = Vec::new();
+    for channel in animation.channels() {
+        let Some(&joint) = joint_of_node.get(&channel.target().node().index()) else {
+            continue;
+        };
+        let reader = channel.reader(|buffer| buffers.get(buffer.index()).copied());
+        let times = reader.read_inputs().context("glTF animation channel has no keyframe times")?.collect::<Vec<_>>();
+        let (interpolation, cubic_spline) = match channel.sampler().interpolation() {
+            Interpolation::Step => (KeyframeInterpolation::Step, false),
+            Interpolation::Linear => (KeyframeInterpolation::Linear, false),
+            Interpolation::CubicSpline => (KeyframeInterpolation::Linear, true),
+        };
+        let keyframes = match reader.read_outputs().context("glTF animation channel has no keyframe values")? {
+            ReadOutputs::Translations(values) => JointKeyframes::Translation(keyframe_values(values.map(Vec3::from), cubic_spline)),
+            ReadOutputs::Rotations(values) => {
+                JointKeyframes::Rotation(keyframe_values(values.into_f32().map(Quat::from_array), cubic_spline))
+            }
+            ReadOutputs::Scales(values) => JointKeyframes::Scale(keyframe_values(values.map(Vec3::from), cubic_spline)),
+            ReadOutputs::MorphTargetWeights(_) => continue,
+        };
+
+        channels.push(AnimationChannel {
+            joint,
+            times,
+            keyframes,
+            interpolation,
+        });
+    }
+
+    Ok(AnimationClip {
+        name: animation.name().map(str::to_owned),
+        duration: channels
+            .iter()
+            .filter_map(|channel| channel.times.last().copied())
+            .fold(0.0, f32::max),
+        channels,
+    })
+}
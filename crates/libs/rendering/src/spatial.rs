@@ -0,0 +1,64 @@
+mod bvh;
+
+pub use bvh::*;
+pub use avalanche_utils::math::{Aabb, Frustum};
+
+use bevy_app::{App, Plugin};
+use bevy_ecs::change_detection::DetectChanges;
+use bevy_ecs::prelude::{Entity, Query, Ref, ResMut, Resource};
+use bevy_transform::prelude::GlobalTransform;
+use bevy_utils::HashSet;
+use crate::{ExtractSchedule, RenderApp};
+use crate::prelude::Extract;
+
+/// World space bounds of the entities with an [`Aabb`] in their local space, kept across frames.
+///
+/// Only entities whose transform or bounds changed are moved. Queue systems cull the entities a view
+/// can't see with the one of the render world, the [`PickingPlugin`](crate::picking::PickingPlugin)
+/// keeps another in the main world to cast rays against.
+#[derive(Resource, Default)]
+pub struct SceneBvh(pub Bvh<Entity>);
+
+impl SceneBvh {
+    /// Move the changed entities and drop the ones missing from `entities`.
+    pub fn sync<'a>(&mut self, entities: impl Iterator<Item = (Entity, Ref<'a, GlobalTransform>, Ref<'a, Aabb>)>) {
+        let mut alive = HashSet::with_capacity(self.0.len());
+        for (entity, transform, aabb) in entities {
+            alive.insert(entity);
+            if !transform.is_changed() && !aabb.is_changed() && self.0.contains(entity) {
+                continue;
+            }
+            self.0.insert(entity, aabb.transformed(&transform.compute_matrix()));
+        }
+
+        if alive.len() != self.0.len() {
+            self.0.retain(|entity| alive.contains(&entity));
+        }
+    }
+
+    /// Whether an entity may be seen in a frustum, entities without bounds always may.
+    pub fn may_be_visible(&self, entity: Entity, frustum: &Frustum) -> bool {
+        self.0
+            .get(entity)
+            .is_none_or(|aabb| frustum.intersects_aabb(aabb))
+    }
+}
+
+/// Keeps the [`SceneBvh`] of the render world up to date.
+pub struct SpatialPlugin;
+
+impl Plugin for SpatialPlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<SceneBvh>()
+                .add_systems(ExtractSchedule, extract_scene_bvh);
+        }
+    }
+}
+
+pub(crate) type BoundsQuery<'w, 's> = Query<'w, 's, (Entity, Ref<'static, GlobalTransform>, Ref<'static, Aabb>)>;
+
+fn extract_scene_bvh(mut bvh: ResMut<SceneBvh>, entities: Extract<BoundsQuery>) {
+    bvh.sync(entities.iter());
+}
@@ -0,0 +1,400 @@
+use std::hash::Hash;
+use bevy_math::Vec3;
+use bevy_utils::HashMap;
+use crate::spatial::{Aabb, Frustum};
+
+#[derive(Clone, Copy, Debug)]
+enum BvhNodeKind<T> {
+    Leaf(T),
+    Internal([usize; 2]),
+    Free,
+}
+
+#[derive(Clone, Debug)]
+struct BvhNode<T> {
+    /// Bounds of the children, or of the item grown by the margin on leaves
+    aabb: Aabb,
+    parent: Option<usize>,
+    kind: BvhNodeKind<T>,
+}
+
+/// A bounding volume hierarchy over items updated one at a time.
+///
+/// Leaves are grown by a margin, an item moving inside the grown bounds of its leaf keeps its place in the tree.
+/// Items are inserted next to the node their bounds grow the least, the tree is not rebalanced.
+#[derive(Clone, Debug)]
+pub struct Bvh<T> {
+    nodes: Vec<BvhNode<T>>,
+    free_nodes: Vec<usize>,
+    root: Option<usize>,
+    leaves: HashMap<T, usize>,
+    /// Distance leaves extend past the bounds of their item
+    pub margin: f32,
+}
+
+impl<T> Default for Bvh<T> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free_nodes: Vec::new(),
+            root: None,
+            leaves: HashMap::default(),
+            margin: 0.1,
+        }
+    }
+}
+
+impl<T: Copy + Eq + Hash> Bvh<T> {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    #[inline]
+    pub fn contains(&self, item: T) -> bool {
+        self.leaves.contains_key(&item)
+    }
+
+    /// Grown bounds of the leaf of an item.
+    pub fn get(&self, item: T) -> Option<&Aabb> {
+        self.leaves.get(&item).map(|leaf| &self.nodes[*leaf].aabb)
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = T> + '_ {
+        self.leaves.keys().copied()
+    }
+
+    /// Insert an item or move it, returns whether the tree changed.
+    pub fn insert(&mut self, item: T, aabb: Aabb) -> bool {
+        if let Some(&leaf) = self.leaves.get(&item) {
+            if self.nodes[leaf].aabb.contains(&aabb) {
+                return false;
+            }
+            self.detach(leaf);
+            self.nodes[leaf].aabb = aabb.expand(self.margin);
+            self.attach(leaf);
+            return true;
+        }
+
+        let leaf = self.allocate(BvhNode {
+            aabb: aabb.expand(self.margin),
+            parent: None,
+            kind: BvhNodeKind::Leaf(item),
+        });
+        self.leaves.insert(item, leaf);
+        self.attach(leaf);
+        true
+    }
+
+    pub fn remove(&mut self, item: T) -> bool {
+        let Some(leaf) = self.leaves.remove(&item) else {
+            return false;
+        };
+        self.detach(leaf);
+        self.release(leaf);
+        true
+    }
+
+    /// Keep only the items `keep` returns true for.
+    pub fn retain(&mut self, mut keep: impl FnMut(T) -> bool) {
+        let removed = self.items().filter(|item| !keep(*item)).collect::<Vec<_>>();
+        for item in removed {
+            self.remove(item);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free_nodes.clear();
+        self.root = None;
+        self.leaves.clear();
+    }
+
+    /// Visit the items of the leaves `overlaps` accepts, subtrees it rejects are skipped.
+    pub fn query(&self, mut overlaps: impl FnMut(&Aabb) -> bool, mut visit: impl FnMut(T)) {
+        let mut pending = self.root.into_iter().collect::<Vec<_>>();
+        while let Some(index) = pending.pop() {
+            let node = &self.nodes[index];
+            if !overlaps(&node.aabb) {
+                continue;
+            }
+            match node.kind {
+                BvhNodeKind::Leaf(item) => visit(item),
+                BvhNodeKind::Internal(children) => pending.extend(children),
+                BvhNodeKind::Free => {}
+            }
+        }
+    }
+
+    /// Visit the items whose leaves are at least partially inside a frustum.
+    pub fn query_frustum(&self, frustum: &Frustum, visit: impl FnMut(T)) {
+        self.query(|aabb| frustum.intersects_aabb(aabb), visit);
+    }
+
+    /// Visit the items whose leaves a ray crosses within `max_distance`, with the distance it enters the leaf at.
+    pub fn query_ray(&self, origin: Vec3, direction: Vec3, max_distance: f32, mut visit: impl FnMut(T, f32)) {
+        let mut pending = self.root.into_iter().collect::<Vec<_>>();
+        while let Some(index) = pending.pop() {
+            let node = &self.nodes[index];
+            let Some(distance) = node.aabb.intersect_ray(origin, direction, max_distance) else {
+                continue;
+            };
+            match node.kind {
+                BvhNodeKind::Leaf(item) => visit(item, distance),
+                BvhNodeKind::Internal(children) => pending.extend(children),
+                BvhNodeKind::Free => {}
+            }
+        }
+    }
+
+    fn allocate(&mut self, node: BvhNode<T>) -> usize {
+        match self.free_nodes.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn release(&mut self, index: usize) {
+        self.nodes[index].kind = BvhNodeKind::Free;
+        self.nodes[index].parent = None;
+        self.free_nodes.push(index);
+    }
+
+    /// Where adding `aabb` below a node costs the least, by the surface area the tree grows.
+    fn find_sibling(&self, aabb: &Aabb) -> usize {
+        let mut index = self.root.unwrap();
+        while let BvhNodeKind::Internal(children) = self.nodes[index].kind {
+            let node_area = self.nodes[index].aabb.surface_area();
+            let combined_area = self.nodes[index].aabb.union(aabb).surface_area();
+            // a new parent of this node and the leaf
+            let cost = 2.0 * combined_area;
+            // every node below grows to contain the leaf
+            let inherited = 2.0 * (combined_area - node_area);
+
+            let [first, second] = children.map(|child| {
+                let child = &self.nodes[child];
+                let grown = child.aabb.union(aabb).surface_area();
+                match child.kind {
+                    BvhNodeKind::Leaf(_) => grown + inherited,
+                    _ => grown - child.aabb.surface_area() + inherited,
+                }
+            });
+            if cost < first && cost < second {
+                break;
+            }
+            index = if first < second { children[0] } else { children[1] };
+        }
+        index
+    }
+
+    fn attach(&mut self, leaf: usize) {
+        if self.root.is_none() {
+            self.root = Some(leaf);
+            self.nodes[leaf].parent = None;
+            return;
+        }
+
+        let aabb = self.nodes[leaf].aabb;
+        let sibling = self.find_sibling(&aabb);
+        let old_parent = self.nodes[sibling].parent;
+        let parent = self.allocate(BvhNode {
+            aabb: self.nodes[sibling].aabb.union(&aabb),
+            parent: old_parent,
+            kind: BvhNodeKind::Internal([sibling, leaf]),
+        });
+        self.nodes[sibling].parent = Some(parent);
+        self.nodes[leaf].parent = Some(parent);
+
+        match old_parent {
+            Some(old_parent) => {
+                self.replace_child(old_parent, sibling, parent);
+                self.refit(old_parent);
+            }
+            None => self.root = Some(parent),
+        }
+    }
+
+    /// Unlink a leaf, its sibling takes the place of their parent.
+    fn detach(&mut self, leaf: usize) {
+        let Some(parent) = self.nodes[leaf].parent else {
+            self.root = None;
+            return;
+        };
+        let BvhNodeKind::Internal(children) = self.nodes[parent].kind else {
+            unreachable!("leaves only have internal parents");
+        };
+        let sibling = if children[0] == leaf { children[1] } else { children[0] };
+        let grandparent = self.nodes[parent].parent;
+
+        self.nodes[sibling].parent = grandparent;
+        match grandparent {
+            Some(grandparent) => {
+                self.replace_child(grandparent, parent, sibling);
+                self.refit(grandparent);
+            }
+            None => self.root = Some(sibling),
+        }
+        self.nodes[leaf].parent = None;
+        self.release(parent);
+    }
+
+    fn replace_child(&mut self, parent: usize, old_child: usize, new_child: usize) {
+        if let BvhNodeKind::Internal(children) = &mut self.nodes[parent].kind {
+            for child in children.iter_mut().filter(|child| **child == old_child) {
+                *child = new_child;
+            }
+        }
+    }
+
+    /// Recompute the bounds of a node and of its ancestors.
+    fn refit(&mut self, mut index: usize) {
+        loop {
+            if let BvhNodeKind::Internal([first, second]) = self.nodes[index].kind {
+                self.nodes[index].aabb = self.nodes[first].aabb.union(&self.nodes[second].aabb);
+            }
+            match self.nodes[index].parent {
+                Some(parent) => index = parent,
+                None => break,
+            }
+        }
+    }
+}
+
+/// Check the links and bounds of every node reachable from the root, returns the items of the leaves.
+#[cfg(test)]
+fn validate<T: Copy + Eq + Hash + std::fmt::Debug>(bvh: &Bvh<T>) -> Vec<T> {
+    let mut items = Vec::new();
+    let mut pending = bvh.root.into_iter().collect::<Vec<_>>();
+    if let Some(root) = bvh.root {
+        assert_eq!(bvh.nodes[root].parent, None);
+    }
+    while let Some(index) = pending.pop() {
+        let node = &bvh.nodes[index];
+        match node.kind {
+            BvhNodeKind::Leaf(item) => {
+                assert_eq!(bvh.leaves[&item], index);
+                items.push(item);
+            }
+            BvhNodeKind::Internal(children) => {
+                for child in children {
+                    assert_eq!(bvh.nodes[child].parent, Some(index));
+                    assert!(node.aabb.contains(&bvh.nodes[child].aabb), "parent {index} doesn't contain child {child}");
+                }
+                pending.extend(children);
+            }
+            BvhNodeKind::Free => panic!("free node {index} is reachable"),
+        }
+    }
+    assert_eq!(items.len(), bvh.len());
+    items
+}
+
+#[cfg(test)]
+fn query_sorted(bvh: &Bvh<u32>, region: &Aabb) -> Vec<u32> {
+    let mut found = Vec::new();
+    bvh.query(|aabb| aabb.intersects(region), |item| found.push(item));
+    found.sort();
+    found
+}
+
+/// Unit cubes on a grid, item `x + y * 8` at `(2x, 2y, 0)`.
+#[cfg(test)]
+fn grid_aabb(item: u32) -> Aabb {
+    let (x, y) = ((item % 8) as f32, (item / 8) as f32);
+    Aabb::new(Vec3::new(2.0 * x, 2.0 * y, 0.0), Vec3::new(2.0 * x + 1.0, 2.0 * y + 1.0, 1.0))
+}
+
+#[test]
+fn test_insert_keeps_parents_around_children() {
+    let mut bvh = Bvh::default();
+    for item in 0..64 {
+        assert!(bvh.insert(item, grid_aabb(item)));
+        validate(&bvh);
+    }
+
+    let mut items = validate(&bvh);
+    items.sort();
+    assert_eq!(items, (0..64).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_query_returns_overlapping_leaves() {
+    let mut bvh = Bvh {
+        margin: 0.0,
+        ..Bvh::default()
+    };
+    for item in 0..64 {
+        bvh.insert(item, grid_aabb(item));
+    }
+
+    // the region spans columns 1 and 2 of rows 0 to 2
+    let region = Aabb::new(Vec3::new(2.5, 0.5, 0.5), Vec3::new(4.5, 4.5, 0.5));
+    assert_eq!(query_sorted(&bvh, &region), vec![1, 2, 9, 10, 17, 18]);
+    let expected = (0..64).filter(|item| grid_aabb(*item).intersects(&region)).collect::<Vec<_>>();
+    assert_eq!(query_sorted(&bvh, &region), expected);
+
+    // between the cubes
+    let gap = Aabb::new(Vec3::new(1.25, 1.25, 0.0), Vec3::new(1.75, 1.75, 1.0));
+    assert!(query_sorted(&bvh, &gap).is_empty());
+}
+
+#[test]
+fn test_move_leaf() {
+    let mut bvh = Bvh::default();
+    for item in 0..64 {
+        bvh.insert(item, grid_aabb(item));
+    }
+
+    // moving within the margin keeps the leaf
+    let nudged = Aabb::new(Vec3::new(0.05, 0.0, 0.0), Vec3::new(1.05, 1.0, 1.0));
+    assert!(!bvh.insert(0, nudged));
+    assert!(bvh.get(0).unwrap().contains(&nudged));
+
+    // moving across the grid reattaches it
+    let far = Aabb::new(Vec3::new(100.0, 100.0, 0.0), Vec3::new(101.0, 101.0, 1.0));
+    assert!(bvh.insert(0, far));
+    validate(&bvh);
+    assert_eq!(bvh.len(), 64);
+    assert!(bvh.get(0).unwrap().contains(&far));
+    assert_eq!(query_sorted(&bvh, &far), vec![0]);
+    assert!(!query_sorted(&bvh, &grid_aabb(0)).contains(&0));
+}
+
+#[test]
+fn test_remove_leaf() {
+    let mut bvh = Bvh::default();
+    for item in 0..64 {
+        bvh.insert(item, grid_aabb(item));
+    }
+
+    for item in (0..64).step_by(3) {
+        assert!(bvh.remove(item));
+        validate(&bvh);
+    }
+    assert!(!bvh.remove(0));
+    assert_eq!(bvh.len(), 64 - 22);
+    assert!(query_sorted(&bvh, &grid_aabb(3)).iter().all(|item| item % 3 != 0));
+
+    // freed nodes are reused
+    let node_count = bvh.nodes.len();
+    for item in (0..64).step_by(3) {
+        bvh.insert(item, grid_aabb(item));
+    }
+    validate(&bvh);
+    assert_eq!(bvh.nodes.len(), node_count);
+
+    bvh.retain(|_| false);
+    assert!(bvh.is_empty());
+    assert_eq!(bvh.root, None);
+}
@@ -0,0 +1,85 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+use bevy_ecs::prelude::Resource;
+use bevy_log::error;
+use bevy_utils::{HashMap, HashSet};
+use avalanche_hlvk::{Context, RasterPipeline};
+use crate::shader::ShaderDirectory;
+
+/// Identifies a variant of a raster pipeline, e.g. by vertex layout, material flags and attachment formats.
+pub trait SpecializedPipelineKey: Clone + Eq + Hash + Debug + Send + Sync + 'static {
+    /// Resources shared by every variant, like the pipeline layout.
+    type Specializer;
+
+    fn specialize(
+        &self,
+        context: &Context,
+        shaders: &ShaderDirectory,
+        specializer: &Self::Specializer,
+    ) -> anyhow::Result<RasterPipeline>;
+}
+
+/// Raster pipeline variants created on demand and kept for the lifetime of the render app.
+///
+/// Variants are specialized in [`RenderSet::PrepareResources`](crate::RenderSet::PrepareResources)
+/// for the keys queued this frame, so render nodes only look them up.
+#[derive(Resource)]
+pub struct SpecializedPipelines<K: SpecializedPipelineKey> {
+    pipelines: HashMap<K, RasterPipeline>,
+    /// Keys whose creation failed, not retried
+    failed: HashSet<K>,
+}
+
+impl<K: SpecializedPipelineKey> Default for SpecializedPipelines<K> {
+    fn default() -> Self {
+        Self {
+            pipelines: Default::default(),
+            failed: Default::default(),
+        }
+    }
+}
+
+impl<K: SpecializedPipelineKey> SpecializedPipelines<K> {
+    /// Create the variant of `key` unless it exists, failures are logged once.
+    pub fn specialize(
+        &mut self,
+        context: &Context,
+        shaders: &ShaderDirectory,
+        specializer: &K::Specializer,
+        key: &K,
+    ) -> Option<&RasterPipeline> {
+        if !self.pipelines.contains_key(key) && !self.failed.contains(key) {
+            match key.specialize(context, shaders, specializer) {
+                Ok(pipeline) => {
+                    self.pipelines.insert(key.clone(), pipeline);
+                }
+                Err(err) => {
+                    error!("Failed to specialize pipeline {key:?}: {err}");
+                    self.failed.insert(key.clone());
+                }
+            }
+        }
+        self.pipelines.get(key)
+    }
+
+    #[inline]
+    pub fn get(&self, key: &K) -> Option<&RasterPipeline> {
+        self.pipelines.get(key)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    /// Drop every variant, e.g. after the shaders changed.
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
+        self.failed.clear();
+    }
+}
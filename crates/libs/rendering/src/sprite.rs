@@ -0,0 +1,410 @@
+mod node;
+mod pipeline;
+
+pub use node::*;
+pub use pipeline::*;
+
+use std::ops::Range;
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Commands, Component, Entity, IntoSystemConfigs, Query, Res, ResMut, Resource};
+use bevy_ecs::query::QueryItem;
+use bevy_log::error;
+use bevy_math::{Mat4, Rect, Vec2};
+use bevy_transform::prelude::GlobalTransform;
+use bevy_utils::{EntityHashMap, FloatOrd, HashMap, HashSet};
+use bytemuck::{Pod, Zeroable};
+use gpu_allocator::MemoryLocation;
+use avalanche_asset::{AssetId, Handle};
+use avalanche_hlvk::{Buffer, Context, DescriptorPool, DescriptorSet, WriteDescriptorSet, WriteDescriptorSetKind};
+use crate::color::Color;
+use crate::{Render, RenderApp, RenderSet};
+use crate::camera::ExtractedCamera;
+use crate::extract::{ExtractComponent, ExtractComponentPlugin, FrameContext};
+use crate::graph::{RenderGraphApp, RenderLabel};
+use crate::graph::node::ViewNodeRunner;
+use crate::parallel::par_map;
+use crate::render_asset::RenderAssets;
+use crate::render_phase::{sort_phase_system, PhaseItem, RenderPhase};
+use crate::resource::TypedBuffer;
+use crate::texture::Texture;
+
+/// Sub graph drawing the [`Sprite`]s seen by a camera,
+/// select it with [`RenderPath::Sprite2d`](crate::camera::RenderPath::Sprite2d).
+pub const SPRITE_GRAPH: RenderLabel = RenderLabel::new("sprite_2d");
+/// Node of the [`SPRITE_GRAPH`] drawing the sprite batches of a view, see [`SpriteNode`].
+pub const SPRITE_NODE: &str = "sprite_pass";
+
+/// A textured quad centered on the origin of its entity, facing `+Z`.
+///
+/// Sprites are drawn alpha blended by cameras rendered with the [`SPRITE_GRAPH`], farther sprites first.
+/// Consecutive sprites sharing a texture are drawn with a single instanced draw,
+/// pack sprites into atlases and pick them with [`rect`](Self::rect) to batch them together.
+#[derive(Component, Clone, Debug)]
+pub struct Sprite {
+    pub texture: Handle<Texture>,
+    /// Multiplied with the texture
+    pub color: Color,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Size in world units, the size of the [`rect`](Self::rect) in texels when `None`
+    pub custom_size: Option<Vec2>,
+    /// Region of the texture in texels, the whole texture when `None`
+    pub rect: Option<Rect>,
+}
+
+impl Sprite {
+    pub fn new(texture: Handle<Texture>) -> Self {
+        Self {
+            texture,
+            color: Color::WHITE,
+            flip_x: false,
+            flip_y: false,
+            custom_size: None,
+            rect: None,
+        }
+    }
+}
+
+/// A [`Sprite`] in the render world.
+#[derive(Component, Clone, Debug)]
+pub struct ExtractedSprite {
+    pub sprite: Sprite,
+    pub world_from_sprite: Mat4,
+}
+
+impl ExtractComponent for Sprite {
+    type Query = (&'static Sprite, &'static GlobalTransform);
+    type Filter = ();
+    type Out = ExtractedSprite;
+
+    fn extract_component((sprite, transform): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some(ExtractedSprite {
+            sprite: sprite.clone(),
+            world_from_sprite: transform.compute_matrix(),
+        })
+    }
+}
+
+pub struct SpritePlugin;
+
+impl Plugin for SpritePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<Sprite>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<SpritePipeline>()
+                .init_resource::<SpriteTextureBindings>()
+                .init_resource::<SpriteViews>()
+                .add_systems(
+                    Render, (
+                        queue_sprites.in_set(RenderSet::Queue),
+                        sort_phase_system::<Sprite2d>.in_set(RenderSet::PhaseSort),
+                        prepare_sprite_pipeline.in_set(RenderSet::PrepareResources),
+                        (prepare_sprite_textures, prepare_sprite_views)
+                            .in_set(RenderSet::PrepareBindGroups),
+                    )
+                )
+                .add_render_sub_graph(SPRITE_GRAPH)
+                .add_render_graph_node::<ViewNodeRunner<SpriteNode>>(SPRITE_GRAPH, SPRITE_NODE);
+        }
+    }
+}
+
+/// A sprite seen by a view.
+pub struct Sprite2d {
+    pub entity: Entity,
+    /// Position of the sprite origin along the view axis, negative in front of the camera
+    pub view_z: f32,
+    pub texture: AssetId<Texture>,
+}
+
+impl PhaseItem for Sprite2d {
+    /// Back to front, then grouped by texture so sprites at the same depth are batched
+    type SortKey = (FloatOrd, u32);
+
+    #[inline]
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    #[inline]
+    fn sort_key(&self) -> Self::SortKey {
+        (FloatOrd(self.view_z), self.texture.index())
+    }
+}
+
+fn queue_sprites(
+    mut commands: Commands,
+    sprites: Query<(Entity, &ExtractedSprite)>,
+    cameras: Query<(Entity, &ExtractedCamera)>,
+) {
+    for (entity, camera) in cameras.iter() {
+        if camera.render_graph != SPRITE_GRAPH {
+            continue;
+        }
+
+        let view_from_world = camera.world_from_view.inverse();
+        let mut phase = RenderPhase::<Sprite2d>::default();
+        for (sprite_entity, sprite) in sprites.iter() {
+            phase.add(Sprite2d {
+                entity: sprite_entity,
+                view_z: view_from_world.transform_point3(sprite.world_from_sprite.w_axis.truncate()).z,
+                texture: sprite.sprite.texture.id(),
+            });
+        }
+        commands.entity(entity).insert(phase);
+    }
+}
+
+/// Matches the instance inputs of `shaders/sprite/sprite.vert`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SpriteInstance {
+    /// Rows of the 3x4 transform of the unit quad, scaled to the sprite size
+    pub world_from_quad: [[f32; 4]; 3],
+    /// Texture coordinates of the top left corner in xy and of the bottom right corner in zw, flips applied
+    pub uv_rect: [f32; 4],
+    pub color: [f32; 4],
+}
+
+// SAFETY: plain `f32` fields without implicit padding
+unsafe impl Zeroable for SpriteInstance {}
+unsafe impl Pod for SpriteInstance {}
+
+impl SpriteInstance {
+    fn new(sprite: &ExtractedSprite, texture_size: Vec2) -> Self {
+        let rect = sprite.sprite.rect.unwrap_or(Rect::from_corners(Vec2::ZERO, texture_size));
+        let size = sprite.sprite.custom_size.unwrap_or(rect.size());
+        let world_from_quad = (sprite.world_from_sprite * Mat4::from_scale(size.extend(1.0))).transpose();
+
+        let (mut min, mut max) = (rect.min / texture_size, rect.max / texture_size);
+        if sprite.sprite.flip_x {
+            std::mem::swap(&mut min.x, &mut max.x);
+        }
+        if sprite.sprite.flip_y {
+            std::mem::swap(&mut min.y, &mut max.y);
+        }
+
+        Self {
+            world_from_quad: [
+                world_from_quad.x_axis.to_array(),
+                world_from_quad.y_axis.to_array(),
+                world_from_quad.z_axis.to_array(),
+            ],
+            uv_rect: [min.x, min.y, max.x, max.y],
+            color: sprite.sprite.color.to_linear_rgba(),
+        }
+    }
+}
+
+/// Consecutive instances of a view drawn with the same texture.
+#[derive(Clone, Debug)]
+pub struct SpriteBatch {
+    pub texture: AssetId<Texture>,
+    pub instances: Range<u32>,
+}
+
+/// Matches `SpriteViewUniform` in `shaders/sprite/sprite.vert`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SpriteViewUniform {
+    clip_from_world: [f32; 16],
+}
+
+/// Instances, batches and bindings of a camera drawing sprites, kept across frames.
+pub struct SpriteViewState {
+    pub instances: TypedBuffer<SpriteInstance>,
+    pub batches: Vec<SpriteBatch>,
+    uniform_buffer: Buffer,
+    _descriptor_pool: DescriptorPool,
+    /// Set 0 of the sprite pipeline
+    pub(crate) descriptor_set: DescriptorSet,
+}
+
+impl SpriteViewState {
+    fn new(context: &Context, pipeline: &SpritePipelineResources) -> anyhow::Result<Self> {
+        let uniform_buffer = context.create_buffer(
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of::<SpriteViewUniform>() as _,
+        )?;
+        let descriptor_pool = context.create_descriptor_pool(1, &[vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER,
+            descriptor_count: 1,
+        }])?;
+        let descriptor_set = descriptor_pool.allocate_set(pipeline.view_set_layout())?;
+        descriptor_set.update(&[WriteDescriptorSet {
+            binding: 0,
+            kind: WriteDescriptorSetKind::UniformBuffer {
+                buffer: &uniform_buffer,
+            },
+        }]);
+
+        Ok(Self {
+            instances: TypedBuffer::new(vk::BufferUsageFlags::VERTEX_BUFFER),
+            batches: Vec::new(),
+            uniform_buffer,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SpriteViews(pub(crate) EntityHashMap<Entity, SpriteViewState>);
+
+impl SpriteViews {
+    pub fn get(&self, entity: Entity) -> Option<&SpriteViewState> {
+        self.0.get(&entity)
+    }
+}
+
+/// Binding of a sprite texture, set 1 of the sprite pipeline.
+pub struct SpriteTextureBinding {
+    _descriptor_pool: DescriptorPool,
+    pub(crate) descriptor_set: DescriptorSet,
+}
+
+/// Bindings of the textures drawn by sprites this frame.
+#[derive(Resource, Default)]
+pub struct SpriteTextureBindings(pub(crate) HashMap<AssetId<Texture>, SpriteTextureBinding>);
+
+impl SpriteTextureBindings {
+    pub fn get(&self, texture: AssetId<Texture>) -> Option<&SpriteTextureBinding> {
+        self.0.get(&texture)
+    }
+}
+
+fn prepare_sprite_textures(
+    mut bindings: ResMut<SpriteTextureBindings>,
+    pipeline: Res<SpritePipeline>,
+    textures: Res<RenderAssets<Texture>>,
+    sprites: Query<&ExtractedSprite>,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(pipeline) = pipeline.resources() else {
+        bindings.0.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+
+    for sprite in sprites.iter() {
+        let id = sprite.sprite.texture.id();
+        // sprites show up once their texture is uploaded
+        let Some(texture) = textures.get(id) else {
+            continue;
+        };
+        if !alive.insert(id) {
+            continue;
+        }
+
+        if !bindings.0.contains_key(&id) {
+            let binding = context
+                .create_descriptor_pool(1, &[vk::DescriptorPoolSize {
+                    ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    descriptor_count: 1,
+                }])
+                .and_then(|pool| {
+                    let descriptor_set = pool.allocate_set(pipeline.texture_set_layout())?;
+                    Ok(SpriteTextureBinding {
+                        _descriptor_pool: pool,
+                        descriptor_set,
+                    })
+                });
+            match binding {
+                Ok(binding) => {
+                    bindings.0.insert(id, binding);
+                }
+                Err(err) => {
+                    error!("Failed to create sprite texture binding: {err}");
+                    continue;
+                }
+            }
+        }
+
+        // reloaded textures replace their image, rewrite the set every frame
+        bindings.0[&id].descriptor_set.update(&[WriteDescriptorSet {
+            binding: 0,
+            kind: WriteDescriptorSetKind::CombinedImageSampler {
+                view: &texture.view,
+                sampler: &pipeline.sampler,
+                layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            },
+        }]);
+    }
+
+    bindings.0.retain(|id, _| alive.contains(id));
+}
+
+/// Write the instances of the sorted sprites of every view, merging runs sharing a texture into batches.
+fn prepare_sprite_views(
+    mut views: ResMut<SpriteViews>,
+    pipeline: Res<SpritePipeline>,
+    textures: Res<RenderAssets<Texture>>,
+    sprites: Query<&ExtractedSprite>,
+    cameras: Query<(Entity, &ExtractedCamera, &RenderPhase<Sprite2d>)>,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(pipeline) = pipeline.resources() else {
+        views.0.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+    let mut instances = Vec::new();
+
+    for (entity, camera, phase) in cameras.iter() {
+        alive.insert(entity);
+        if !views.0.contains_key(&entity) {
+            match SpriteViewState::new(context, pipeline) {
+                Ok(state) => {
+                    views.0.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create sprite view resources: {err}");
+                    continue;
+                }
+            }
+        }
+        let state = views.0.get_mut(&entity).unwrap();
+
+        let uniform = SpriteViewUniform {
+            clip_from_world: (camera.projection * camera.world_from_view.inverse()).to_cols_array(),
+        };
+        if let Err(err) = state.uniform_buffer.copy_data_to_buffer(std::slice::from_ref(&uniform)) {
+            error!("Failed to upload sprite view uniform: {err}");
+        }
+
+        // instances are built in parallel, the batches follow the sorted order
+        let built = par_map(&phase.items, |item| {
+            let (Ok(sprite), Some(texture)) = (sprites.get(item.entity), textures.get(item.texture)) else {
+                return None;
+            };
+            Some((item.texture, SpriteInstance::new(sprite, texture.size.as_vec2())))
+        });
+
+        instances.clear();
+        state.batches.clear();
+        for (texture, instance) in built.into_iter().flatten() {
+            let index = instances.len() as u32;
+            match state.batches.last_mut() {
+                Some(batch) if batch.texture == texture => batch.instances.end = index + 1,
+                _ => state.batches.push(SpriteBatch {
+                    texture,
+                    instances: index..index + 1,
+                }),
+            }
+            instances.push(instance);
+        }
+
+        if let Err(err) = state.instances.write(context, &instances) {
+            error!("Failed to upload sprite instances: {err}");
+            state.batches.clear();
+        }
+    }
+
+    views.0.retain(|entity, _| alive.contains(entity));
+}
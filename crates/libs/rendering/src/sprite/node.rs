@@ -0,0 +1,99 @@
+use ash::vk;
+use bevy_ecs::prelude::World;
+use avalanche_hlvk::{ImageBarrier, RenderingAttachment};
+use crate::extract::FrameContext;
+use crate::globals::Globals;
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::ViewNode;
+use crate::sprite::{SpritePipeline, SpriteTextureBindings, SpriteViews};
+use crate::camera::ExtractedCamera;
+use crate::view::{ViewTarget, VIEW_TARGET_FORMAT};
+
+/// Vertices of the two triangles of a sprite quad, generated by the vertex shader.
+const QUAD_VERTEX_COUNT: u32 = 6;
+
+/// Clears the [`ViewTarget`] of a view and draws its [`SpriteBatch`](super::SpriteBatch)es in order,
+/// one instanced draw per batch.
+#[derive(Default)]
+pub struct SpriteNode;
+
+impl ViewNode for SpriteNode {
+    type ViewQuery = (&'static ExtractedCamera, &'static ViewTarget);
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (camera, target): (&ExtractedCamera, &ViewTarget),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pipeline) = world.resource::<SpritePipeline>().resources() else {
+            return Ok(());
+        };
+        let Some(state) = world.resource::<SpriteViews>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, &pipeline.layout);
+        let textures = world.resource::<SpriteTextureBindings>();
+
+        let extent = vk::Extent2D {
+            width: target.size.x,
+            height: target.size.y,
+        };
+        // the sprites are drawn over the cleared target, or over the previous contents without a clear color
+        let (load_op, old_layout, clear_color) = match camera.clear_color {
+            Some(clear_color) => (vk::AttachmentLoadOp::CLEAR, vk::ImageLayout::UNDEFINED, clear_color.for_format(VIEW_TARGET_FORMAT)),
+            None => (vk::AttachmentLoadOp::LOAD, vk::ImageLayout::GENERAL, [0.0; 4]),
+        };
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &target.image,
+            old_layout,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::MEMORY_READ,
+            dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        }]);
+        command_buffer.begin_rendering_attachments(
+            &[RenderingAttachment {
+                view: &target.view,
+                layout: vk::ImageLayout::GENERAL,
+                load_op,
+                clear_color,
+            }],
+            extent,
+        );
+
+        if let (Some(instance_buffer), false) = (state.instances.buffer(), state.batches.is_empty()) {
+            command_buffer.bind_raster_pipeline(&pipeline.pipeline);
+            command_buffer.set_viewport(extent);
+            command_buffer.set_scissor(extent);
+            command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, &pipeline.layout, 0, &[&state.descriptor_set]);
+            command_buffer.bind_vertex_buffer(instance_buffer);
+            for batch in &state.batches {
+                let Some(texture) = textures.get(batch.texture) else {
+                    continue;
+                };
+                command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, &pipeline.layout, 1, &[&texture.descriptor_set]);
+                command_buffer.draw_instanced(QUAD_VERTEX_COUNT, batch.instances.len() as u32, batch.instances.start);
+            }
+        }
+        command_buffer.end_rendering();
+
+        // later passes expect the view target written by shaders
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &target.image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+
+        Ok(())
+    }
+}
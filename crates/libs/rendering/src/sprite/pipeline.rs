@@ -0,0 +1,130 @@
+use ash::vk;
+use bevy_ecs::prelude::{Query, Res, ResMut, Resource};
+use bevy_log::error;
+use avalanche_hlvk::{
+    BlendMode, Context, DescriptorSetLayout, PipelineLayout, RasterColorAttachment, RasterPipeline,
+    RasterPipelineCreateInfo, Sampler, VertexStreamSet,
+};
+use crate::camera::ExtractedCamera;
+use crate::extract::FrameContext;
+use crate::globals;
+use crate::shader::ShaderDirectory;
+use crate::sprite::{SpriteInstance, SPRITE_GRAPH};
+use crate::view::VIEW_TARGET_FORMAT;
+
+pub(crate) const SPRITE_VERTEX_SHADER: &str = "sprite/sprite.vert";
+pub(crate) const SPRITE_FRAGMENT_SHADER: &str = "sprite/sprite.frag";
+
+pub struct SpritePipelineResources {
+    /// Reflected from the sprite shaders, set 0 binds a view and set 1 a texture
+    pub layout: PipelineLayout,
+    /// Draws a quad per [`SpriteInstance`] of the bound instance buffer
+    pub pipeline: RasterPipeline,
+    /// Samples the sprite textures
+    pub sampler: Sampler,
+}
+
+impl SpritePipelineResources {
+    #[inline]
+    pub fn view_set_layout(&self) -> &DescriptorSetLayout {
+        &self.layout.descriptor_set_layouts()[0]
+    }
+
+    #[inline]
+    pub fn texture_set_layout(&self) -> &DescriptorSetLayout {
+        &self.layout.descriptor_set_layouts()[1]
+    }
+}
+
+/// The sprite pipeline, created the first time a camera uses the sprite graph.
+#[derive(Resource, Default)]
+pub enum SpritePipeline {
+    #[default]
+    Uninitialized,
+    Ready(Box<SpritePipelineResources>),
+    /// Creation failed, usually shaders are missing.
+    Failed,
+}
+
+impl SpritePipeline {
+    pub fn resources(&self) -> Option<&SpritePipelineResources> {
+        match self {
+            SpritePipeline::Ready(resources) => Some(resources),
+            _ => None,
+        }
+    }
+}
+
+/// Every field of a [`SpriteInstance`] is a `vec4` input advanced per instance.
+fn instance_stream() -> VertexStreamSet {
+    let stride = std::mem::size_of::<SpriteInstance>() as u32;
+    (0..stride / 16).fold(VertexStreamSet::empty(), |streams, location| {
+        streams.add_stream(
+            stride,
+            vk::VertexInputRate::INSTANCE,
+            location,
+            vk::Format::R32G32B32A32_SFLOAT,
+            Some(location * 16),
+        )
+    })
+}
+
+fn create_resources(context: &Context, shaders: &ShaderDirectory) -> anyhow::Result<SpritePipelineResources> {
+    let stages = [
+        shaders.load(context, SPRITE_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX)?,
+        shaders.load(context, SPRITE_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT)?,
+    ];
+    let layout = globals::pipeline_layout(&stages)?;
+    anyhow::ensure!(globals::pass_set_count(&stages) == 2, "sprite shaders must use sets 0 and 1");
+
+    let pipeline = context.create_graphics_pipeline(&layout, RasterPipelineCreateInfo {
+        shaders: &stages,
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        vertex_stream: &instance_stream(),
+        viewport: None,
+        scissor: None,
+        color_attachments: &[RasterColorAttachment::new(VIEW_TARGET_FORMAT, BlendMode::AlphaBlend)],
+        depth_attachment: None,
+        dynamic_states: Some(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]),
+        polygon_mode: vk::PolygonMode::FILL,
+        front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+        samples: vk::SampleCountFlags::TYPE_1,
+        // flipped sprites are seen from behind
+        cull_mode: vk::CullModeFlags::NONE,
+    })?;
+
+    let sampler = context.create_sampler(
+        &vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+    )?;
+
+    Ok(SpritePipelineResources {
+        layout,
+        pipeline,
+        sampler,
+    })
+}
+
+pub(crate) fn prepare_sprite_pipeline(
+    mut pipeline: ResMut<SpritePipeline>,
+    shaders: Res<ShaderDirectory>,
+    cameras: Query<&ExtractedCamera>,
+    frame_context: Res<FrameContext>,
+) {
+    if !matches!(*pipeline, SpritePipeline::Uninitialized)
+        || !cameras.iter().any(|camera| camera.render_graph == SPRITE_GRAPH) {
+        return;
+    }
+
+    *pipeline = match create_resources(frame_context.render_context(), &shaders) {
+        Ok(resources) => SpritePipeline::Ready(Box::new(resources)),
+        Err(err) => {
+            error!("Failed to create sprite pipeline: {err}");
+            SpritePipeline::Failed
+        }
+    };
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+use bevy_ecs::prelude::Resource;
+use avalanche_hlvk::CommandStatistics;
+
+/// Work recorded by the render graph in the last frame, lives in the render world.
+///
+/// Commands are counted by the [`CommandBuffer`](avalanche_hlvk::CommandBuffer) helpers, commands recorded
+/// through the raw device are missed. With the `trace` feature the counts are also emitted as a trace event every frame.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderStatistics {
+    pub commands: CommandStatistics,
+    /// Nodes run, sub graph nodes included
+    pub nodes: u32,
+    /// Sub graphs run, one per view for the camera graphs
+    pub sub_graphs: u32,
+    /// Average time between queuing a present of the primary window and its display, lower means less latency.
+    /// Only measured with [`FrameLatency::wait_for_display`](crate::frame_pacing::FrameLatency::wait_for_display).
+    pub present_margin: Option<Duration>,
+    /// Average time between two displayed frames of the primary window, at least the refresh period of the display.
+    pub display_interval: Option<Duration>,
+}
+
+impl RenderStatistics {
+    #[cfg(feature = "trace")]
+    pub(crate) fn trace(&self) {
+        let commands = &self.commands;
+        bevy_utils::tracing::info!(
+            target: "avalanche::render_statistics",
+            draw_calls = commands.draw_calls,
+            indirect_draws = commands.indirect_draws,
+            instances = commands.instances,
+            triangles = commands.triangles,
+            dispatches = commands.dispatches,
+            trace_rays = commands.trace_rays,
+            pipeline_binds = commands.pipeline_binds,
+            descriptor_set_binds = commands.descriptor_set_binds,
+            barriers = commands.barriers,
+            nodes = self.nodes,
+            sub_graphs = self.sub_graphs,
+            present_margin_us = self.present_margin.map(|margin| margin.as_micros() as u64),
+            display_interval_us = self.display_interval.map(|interval| interval.as_micros() as u64),
+        );
+    }
+}
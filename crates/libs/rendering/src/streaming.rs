@@ -0,0 +1,401 @@
+mod source;
+
+pub use source::*;
+
+use std::mem::size_of;
+use ash::vk;
+use anyhow::ensure;
+use bevy_app::{App, First, Plugin, PostUpdate};
+use bevy_ecs::change_detection::DetectChanges;
+use bevy_ecs::prelude::{Commands, Component, Entity, IntoSystemConfigs, Local, Query, Ref, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_transform::prelude::GlobalTransform;
+use bevy_transform::TransformSystem;
+use bevy_utils::{EntityHashMap, FloatOrd, HashSet};
+use gpu_allocator::MemoryLocation;
+use avalanche_asset::{Assets, Handle};
+use avalanche_hlvk::Context;
+use crate::camera::Camera;
+use crate::mesh::{MeshBuffers, MeshVertex, MESH_BUFFER_USAGE};
+use crate::prelude::{Buffer, RenderingContext};
+use crate::texture::Texture;
+
+/// Share of the device local memory the [`GpuMemoryBudget`] allows by default,
+/// the rest is left to the driver and the other apps.
+const DEFAULT_BUDGET_SHARE: f64 = 0.8;
+
+/// An asset streamed from a [`StreamSource`] once its entity is close enough to a camera,
+/// the nearest ones first. Its [`Residency`] tells where it is.
+#[derive(Component, Clone, Debug)]
+pub enum StreamedAsset {
+    /// Uploaded into the [`MeshBuffers`] of the entity, the sources hold [`MeshVertex`]es and `u32` indices
+    Mesh {
+        vertices: StreamSource,
+        indices: Option<StreamSource>,
+    },
+    /// Inserted into the [`Assets<Texture>`] behind `handle` once every texel was read
+    Texture {
+        handle: Handle<Texture>,
+        width: u32,
+        height: u32,
+        format: vk::Format,
+        data: StreamSource,
+    },
+}
+
+impl StreamedAsset {
+    /// Bytes streamed, and allocated on the device once resident.
+    pub fn size(&self) -> u64 {
+        self.sources().map(|source| source.len() as u64).sum()
+    }
+
+    fn sources(&self) -> impl Iterator<Item = &StreamSource> {
+        match self {
+            Self::Mesh { vertices, indices } => [Some(vertices), indices.as_ref()],
+            Self::Texture { data, .. } => [Some(data), None],
+        }
+        .into_iter()
+        .flatten()
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            Self::Mesh { vertices, indices } => {
+                ensure!(
+                    !vertices.is_empty() && vertices.len() % size_of::<MeshVertex>() == 0,
+                    "Streamed vertices aren't a whole number of vertices",
+                );
+                ensure!(
+                    !indices.as_ref().is_some_and(|indices| indices.len() % size_of::<u32>() != 0),
+                    "Streamed indices aren't a whole number of `u32`",
+                );
+            }
+            Self::Texture { width, height, data, .. } => ensure!(
+                *width > 0 && *height > 0 && data.len() == *width as usize * *height as usize * 4,
+                "Streamed texture data doesn't match its {width}x{height} size",
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// Where the [`StreamedAsset`] of an entity is, kept up to date by the [`StreamingPlugin`].
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Residency {
+    #[default]
+    Unloaded,
+    /// `streamed` bytes out of `size` were read
+    Streaming { streamed: u64, size: u64 },
+    Resident,
+    /// Unloaded to relieve the memory pressure, streamed again once the memory is available
+    Evicted,
+    /// The sources don't describe a valid asset, see the logs
+    Failed,
+}
+
+/// Bytes the [`StreamingPlugin`] reads from the [`StreamSource`]s each frame.
+///
+/// Reading mapped files faults their pages in, the budget bounds the disk reads and copies of a frame.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct StreamingBudget {
+    pub bytes_per_frame: u64,
+}
+
+impl Default for StreamingBudget {
+    fn default() -> Self {
+        Self {
+            bytes_per_frame: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Device memory the app should stay within, in the main world.
+///
+/// The [`StreamingPlugin`] evicts the farthest streamed assets while the allocations exceed it.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct GpuMemoryBudget {
+    /// Bytes the allocations of the [`RenderingContext`] may use,
+    /// 80% of the device local memory unless inserted before the plugin is finished
+    pub budget: u64,
+    used: u64,
+}
+
+impl Default for GpuMemoryBudget {
+    fn default() -> Self {
+        Self::new(u64::MAX)
+    }
+}
+
+impl GpuMemoryBudget {
+    pub fn new(budget: u64) -> Self {
+        Self { budget, used: 0 }
+    }
+
+    /// Bytes allocated at the start of the frame.
+    #[inline]
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    #[inline]
+    pub fn available(&self) -> u64 {
+        self.budget.saturating_sub(self.used)
+    }
+
+    /// Whether the allocations exceed the budget.
+    #[inline]
+    pub fn under_pressure(&self) -> bool {
+        self.used > self.budget
+    }
+}
+
+/// Streams the [`StreamedAsset`]s within the [`StreamingBudget`] and the [`GpuMemoryBudget`].
+pub struct StreamingPlugin;
+
+impl Plugin for StreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StreamingBudget>()
+            .add_systems(First, update_gpu_memory_budget)
+            .add_systems(PostUpdate, stream_assets.after(TransformSystem::TransformPropagate));
+    }
+
+    fn finish(&self, app: &mut App) {
+        if app.world.contains_resource::<GpuMemoryBudget>() {
+            return;
+        }
+        let budget = match app.world.get_resource::<RenderingContext>() {
+            Some(context) => {
+                let device_local_memory = context.context.physical_device.device_local_memory();
+                GpuMemoryBudget::new((device_local_memory as f64 * DEFAULT_BUDGET_SHARE) as u64)
+            }
+            None => GpuMemoryBudget::default(),
+        };
+        app.insert_resource(budget);
+    }
+}
+
+fn update_gpu_memory_budget(context: Option<Res<RenderingContext>>, budget: Option<ResMut<GpuMemoryBudget>>) {
+    if let (Some(context), Some(mut budget)) = (context, budget) {
+        budget.used = context.context.allocated_memory();
+    }
+}
+
+/// Copy of a [`StreamedAsset`] being streamed.
+enum Upload {
+    /// Host visible buffers, the bytes are copied straight into them
+    Mesh {
+        vertex_buffer: Buffer,
+        index_buffer: Option<Buffer>,
+    },
+    Texture {
+        data: Vec<u8>,
+    },
+}
+
+impl Upload {
+    fn new(context: &Context, asset: &StreamedAsset) -> anyhow::Result<Self> {
+        let create_buffer = |source: &StreamSource| {
+            context
+                .create_buffer(MESH_BUFFER_USAGE, MemoryLocation::CpuToGpu, source.len() as _)
+                .map(Buffer::from)
+        };
+        Ok(match asset {
+            StreamedAsset::Mesh { vertices, indices } => Self::Mesh {
+                vertex_buffer: create_buffer(vertices)?,
+                index_buffer: indices
+                    .as_ref()
+                    .filter(|indices| !indices.is_empty())
+                    .map(create_buffer)
+                    .transpose()?,
+            },
+            StreamedAsset::Texture { data, .. } => Self::Texture {
+                data: Vec::with_capacity(data.len()),
+            },
+        })
+    }
+
+    /// Copy the bytes `start..end` of the sources of `asset` laid one after the other.
+    fn stream(&mut self, asset: &StreamedAsset, start: u64, end: u64) -> anyhow::Result<()> {
+        let mut offset = 0;
+        for (index, source) in asset.sources().enumerate() {
+            let source_range = offset..offset + source.len() as u64;
+            offset = source_range.end;
+            let (chunk_start, chunk_end) = (start.max(source_range.start), end.min(source_range.end));
+            if chunk_start >= chunk_end {
+                continue;
+            }
+
+            let local_start = chunk_start - source_range.start;
+            let chunk = &source.bytes()[local_start as usize..(chunk_end - source_range.start) as usize];
+            match self {
+                Self::Mesh { vertex_buffer, index_buffer } => {
+                    let buffer = if index == 0 { Some(&*vertex_buffer) } else { index_buffer.as_ref() };
+                    if let Some(buffer) = buffer {
+                        buffer.copy_data_to_buffer_at(local_start, chunk)?;
+                    }
+                }
+                Self::Texture { data } => data.extend_from_slice(chunk),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Residency of every streamed entity, in the main world.
+#[derive(Default)]
+struct StreamingState {
+    residencies: EntityHashMap<Entity, Residency>,
+    uploads: EntityHashMap<Entity, Upload>,
+}
+
+fn set_residency(commands: &mut Commands, state: &mut StreamingState, entity: Entity, residency: Residency) {
+    if state.residencies.insert(entity, residency) != Some(residency) {
+        commands.entity(entity).insert(residency);
+    }
+}
+
+/// Evict the farthest assets while the memory is under pressure,
+/// then stream the nearest ones within the frame budget.
+#[allow(clippy::too_many_arguments)]
+fn stream_assets(
+    mut commands: Commands,
+    mut state: Local<StreamingState>,
+    streaming_budget: Res<StreamingBudget>,
+    memory_budget: Option<Res<GpuMemoryBudget>>,
+    context: Option<Res<RenderingContext>>,
+    mut textures: ResMut<Assets<Texture>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    assets: Query<(Entity, Ref<StreamedAsset>, &GlobalTransform)>,
+) {
+    let Some(context) = context else {
+        return;
+    };
+    let memory_budget = memory_budget.as_deref().copied().unwrap_or_default();
+    let state = &mut *state;
+
+    let camera_positions = cameras
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .map(|(_, transform)| transform.translation())
+        .collect::<Vec<_>>();
+    let mut by_distance = assets
+        .iter()
+        .map(|(entity, asset, transform)| {
+            let distance = camera_positions
+                .iter()
+                .map(|position| position.distance(transform.translation()))
+                .fold(f32::INFINITY, f32::min);
+            (FloatOrd(distance), entity, asset.is_changed(), asset.into_inner())
+        })
+        .collect::<Vec<_>>();
+    by_distance.sort_unstable_by_key(|(distance, entity, _, _)| (*distance, *entity));
+
+    // despawned entities and removed assets free their uploads, the resident data stays where it was put
+    let alive = by_distance.iter().map(|(_, entity, _, _)| *entity).collect::<HashSet<_>>();
+    state.residencies.retain(|entity, _| alive.contains(entity));
+    state.uploads.retain(|entity, _| alive.contains(entity));
+
+    // replaced assets are streamed again from the start
+    for (_, entity, changed, _) in &by_distance {
+        if *changed {
+            state.residencies.remove(entity);
+            state.uploads.remove(entity);
+        }
+    }
+
+    if memory_budget.under_pressure() {
+        let mut excess = memory_budget.used() - memory_budget.budget;
+        for (_, entity, _, asset) in by_distance.iter().rev() {
+            if excess == 0 {
+                break;
+            }
+            let residency = state.residencies.get(entity).copied().unwrap_or_default();
+            if !matches!(residency, Residency::Resident | Residency::Streaming { .. }) {
+                continue;
+            }
+            match asset {
+                StreamedAsset::Mesh { .. } => {
+                    commands.entity(*entity).remove::<MeshBuffers>();
+                }
+                StreamedAsset::Texture { handle, .. } => {
+                    textures.remove(handle.id());
+                }
+            }
+            state.uploads.remove(entity);
+            set_residency(&mut commands, state, *entity, Residency::Evicted);
+            excess = excess.saturating_sub(asset.size());
+        }
+        return;
+    }
+
+    let mut available_memory = memory_budget.available();
+    let mut remaining_bytes = streaming_budget.bytes_per_frame;
+    for (_, entity, _, asset) in &by_distance {
+        if remaining_bytes == 0 {
+            break;
+        }
+        let size = asset.size();
+        let streamed = match state.residencies.get(entity).copied().unwrap_or_default() {
+            Residency::Resident | Residency::Failed => continue,
+            Residency::Streaming { streamed, .. } if state.uploads.contains_key(entity) => streamed,
+            _ => {
+                // farther assets may still fit
+                if size > available_memory {
+                    continue;
+                }
+                match asset.validate().and_then(|_| Upload::new(&context.context, asset)) {
+                    Ok(upload) => {
+                        state.uploads.insert(*entity, upload);
+                    }
+                    Err(err) => {
+                        error!("Failed to stream the asset of {entity:?}: {err}");
+                        set_residency(&mut commands, state, *entity, Residency::Failed);
+                        continue;
+                    }
+                }
+                available_memory -= size;
+                0
+            }
+        };
+
+        let end = (streamed + remaining_bytes).min(size);
+        let upload = state.uploads.get_mut(entity).unwrap();
+        if let Err(err) = upload.stream(asset, streamed, end) {
+            error!("Failed to stream the asset of {entity:?}: {err}");
+            state.uploads.remove(entity);
+            set_residency(&mut commands, state, *entity, Residency::Failed);
+            continue;
+        }
+        remaining_bytes -= end - streamed;
+        if end < size {
+            set_residency(&mut commands, state, *entity, Residency::Streaming { streamed: end, size });
+            continue;
+        }
+
+        match (state.uploads.remove(entity).unwrap(), asset) {
+            (Upload::Mesh { vertex_buffer, index_buffer }, StreamedAsset::Mesh { vertices, indices }) => {
+                let index_count = index_buffer
+                    .as_ref()
+                    .and(indices.as_ref())
+                    .map_or(0, |indices| indices.len() / size_of::<u32>());
+                commands.entity(*entity).insert(MeshBuffers {
+                    vertex_buffer,
+                    vertex_count: (vertices.len() / size_of::<MeshVertex>()) as _,
+                    index_buffer,
+                    index_count: index_count as _,
+                    skin_buffer: None,
+                });
+            }
+            (Upload::Texture { data }, StreamedAsset::Texture { handle, width, height, format, .. }) => {
+                textures.insert(handle.id(), Texture {
+                    width: *width,
+                    height: *height,
+                    format: *format,
+                    data,
+                });
+            }
+            _ => unreachable!("uploads are created for the kind of their asset"),
+        }
+        set_residency(&mut commands, state, *entity, Residency::Resident);
+    }
+}
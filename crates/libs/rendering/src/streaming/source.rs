@@ -0,0 +1,58 @@
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::ensure;
+use memmap2::Mmap;
+
+/// Bytes of a [`StreamedAsset`](crate::streaming::StreamedAsset) in a memory mapped file,
+/// the pages are only read from the disk once they are streamed.
+#[derive(Clone, Debug)]
+pub struct StreamSource {
+    map: Arc<Mmap>,
+    range: Range<usize>,
+}
+
+impl StreamSource {
+    /// Map the whole file at `path`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this or another process,
+    /// while the source or any slice of it is alive, see [`Mmap::map`].
+    pub unsafe fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = File::open(path)?;
+        let map = Mmap::map(&file)?;
+        Ok(Self {
+            range: 0..map.len(),
+            map: Arc::new(map),
+        })
+    }
+
+    /// Part of the source, e.g. the indices stored after the vertices of a mesh.
+    pub fn slice(&self, range: Range<usize>) -> anyhow::Result<Self> {
+        ensure!(
+            range.start <= range.end && range.end <= self.len(),
+            "Range {range:?} is out of a stream source of {} bytes", self.len(),
+        );
+        Ok(Self {
+            map: self.map.clone(),
+            range: self.range.start + range.start..self.range.start + range.end,
+        })
+    }
+
+    #[inline]
+    pub fn bytes(&self) -> &[u8] {
+        &self.map[self.range.clone()]
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.range.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+}
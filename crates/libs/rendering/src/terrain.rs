@@ -0,0 +1,627 @@
+mod heightmap;
+mod node;
+mod occlusion;
+mod pipeline;
+mod quadtree;
+
+pub use heightmap::*;
+pub use node::*;
+pub use occlusion::*;
+pub use pipeline::*;
+pub use quadtree::*;
+
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Component, Entity, IntoSystemConfigs, Query, Res, ResMut, Resource};
+use bevy_ecs::query::QueryItem;
+use bevy_log::error;
+use bevy_math::{Mat4, UVec2, Vec2};
+use bevy_transform::prelude::GlobalTransform;
+use bevy_utils::{EntityHashMap, HashMap, HashSet};
+use gpu_allocator::MemoryLocation;
+use avalanche_asset::{AssetApp, Handle};
+use avalanche_hlvk::{
+    Buffer, Context, DescriptorPool, DescriptorSet, Image, ImageView, WriteDescriptorSet, WriteDescriptorSetKind,
+};
+use crate::color::Color;
+use crate::{Render, RenderApp, RenderSet};
+use crate::camera::ExtractedCamera;
+use crate::deferred::{prepare_deferred_views, DeferredViews, DEFERRED_GBUFFER_NODE, DEFERRED_GRAPH, DEFERRED_LIGHTING_NODE};
+use crate::depth_pyramid::{prepare_depth_pyramid_views, DepthPyramidPipeline, DepthPyramidViews, DEPTH_PYRAMID_NODE};
+use crate::extract::{ExtractComponent, ExtractComponentPlugin, FrameContext};
+use crate::graph::RenderGraphApp;
+use crate::graph::node::ViewNodeRunner;
+use crate::mesh::MeshVertex;
+use crate::render_asset::{RenderAssetPlugin, RenderAssets};
+use crate::spatial::Frustum;
+use crate::texture::Texture;
+use crate::view::{OcclusionCulling, ViewTarget};
+
+pub const TERRAIN_NODE: &str = "terrain_pass";
+/// Draws the chunks of views with [`OcclusionCulling`] that turned visible, see [`TerrainOcclusionNode`].
+pub const TERRAIN_OCCLUSION_NODE: &str = "terrain_occlusion_pass";
+
+/// Quads along a side of a [`TerrainChunk`].
+pub const TERRAIN_CHUNK_RESOLUTION: u32 = 32;
+/// Deepest level of the terrain quadtree.
+pub const MAX_TERRAIN_LOD: u32 = 8;
+/// Depth formats of the terrain pass in order of preference, the depth is seeded with the distance stored in the G-buffer.
+pub const TERRAIN_DEPTH_FORMATS: &[vk::Format] = &[vk::Format::D32_SFLOAT, vk::Format::X8_D24_UNORM_PACK32];
+/// Frames a CPU tessellated chunk stays cached after it was last drawn.
+const CPU_CHUNK_CACHE_FRAMES: u64 = 120;
+
+/// Where the vertices of the [`TerrainChunk`]s are displaced by the heightmap.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TerrainTessellation {
+    /// Vertices are computed once per chunk on the CPU and cached
+    Cpu,
+    /// A shared grid is displaced by the vertex shader every frame
+    #[default]
+    Gpu,
+}
+
+/// A material blended over the terrain by a channel of the splat map.
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainLayer {
+    /// Base color, alpha is unused
+    pub base_color: Color,
+    pub roughness: f32,
+}
+
+impl Default for TerrainLayer {
+    fn default() -> Self {
+        Self {
+            base_color: Color::linear_rgb(0.5, 0.5, 0.5),
+            roughness: 0.9,
+        }
+    }
+}
+
+/// A heightmapped terrain spanning `size` along `+X` and `+Z` from the origin of its entity.
+///
+/// Drawn into the G-buffer of cameras rendered with the [`DEFERRED_GRAPH`] by the [`TerrainNode`].
+/// Terrains are lit like the meshes but are not part of the acceleration structures,
+/// so they cast no shadows and show in no reflections.
+#[derive(Component, Clone, Debug)]
+pub struct Terrain {
+    pub heightmap: Handle<Heightmap>,
+    /// World extent along `X` and `Z`
+    pub size: Vec2,
+    /// Height of a heightmap value of 1
+    pub height_scale: f32,
+    pub tessellation: TerrainTessellation,
+    /// Chunks closer to the camera than this many times their extent are split
+    pub lod_distance: f32,
+    /// Deepest quadtree level drawn, up to [`MAX_TERRAIN_LOD`]
+    pub max_lod: u32,
+    /// Weights of the [`layers`](Self::layers) in rgba, only the first layer is used without splat map
+    pub splat_map: Option<Handle<Texture>>,
+    pub layers: [TerrainLayer; 4],
+}
+
+impl Terrain {
+    pub fn new(heightmap: Handle<Heightmap>) -> Self {
+        Self {
+            heightmap,
+            size: Vec2::splat(1024.0),
+            height_scale: 128.0,
+            tessellation: TerrainTessellation::default(),
+            lod_distance: 2.0,
+            max_lod: 6,
+            splat_map: None,
+            layers: Default::default(),
+        }
+    }
+}
+
+/// A [`Terrain`] in the render world.
+#[derive(Component, Clone, Debug)]
+pub struct ExtractedTerrain {
+    pub terrain: Terrain,
+    pub world_from_terrain: Mat4,
+}
+
+impl ExtractComponent for Terrain {
+    type Query = (&'static Terrain, &'static GlobalTransform);
+    type Filter = ();
+    type Out = ExtractedTerrain;
+
+    fn extract_component((terrain, transform): QueryItem<'_, Self::Query>) -> Option<Self::Out> {
+        Some(ExtractedTerrain {
+            terrain: terrain.clone(),
+            world_from_terrain: transform.compute_matrix(),
+        })
+    }
+}
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Heightmap>()
+            .register_asset_loader(R16HeightmapLoader)
+            .add_plugins((
+                RenderAssetPlugin::<Heightmap>::default(),
+                ExtractComponentPlugin::<Terrain>::default(),
+            ));
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<TerrainPipeline>()
+                .init_resource::<TerrainInstances>()
+                .init_resource::<TerrainViews>()
+                .add_systems(
+                    Render, (
+                        (prepare_terrain_pipeline, prepare_terrains)
+                            .chain()
+                            .in_set(RenderSet::PrepareResources),
+                        prepare_terrain_views
+                            .after(prepare_deferred_views)
+                            .after(prepare_depth_pyramid_views)
+                            .in_set(RenderSet::PrepareBindGroups),
+                    )
+                )
+                .add_render_graph_node::<ViewNodeRunner<TerrainNode>>(DEFERRED_GRAPH, TERRAIN_NODE)
+                .add_render_graph_node::<ViewNodeRunner<TerrainOcclusionNode>>(DEFERRED_GRAPH, TERRAIN_OCCLUSION_NODE)
+                .add_render_graph_edges(DEFERRED_GRAPH, &[DEFERRED_GBUFFER_NODE, TERRAIN_NODE, DEFERRED_LIGHTING_NODE])
+                .add_render_graph_edges(
+                    DEFERRED_GRAPH,
+                    &[DEPTH_PYRAMID_NODE, TERRAIN_OCCLUSION_NODE, DEFERRED_LIGHTING_NODE],
+                );
+        }
+    }
+}
+
+/// Matches `TerrainUniform` in `shaders/terrain/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TerrainUniform {
+    world_from_terrain: [f32; 16],
+    /// Inverse transpose of `world_from_terrain`
+    normal_from_terrain: [f32; 16],
+    /// Base color in rgb, roughness in alpha
+    layers: [[f32; 4]; 4],
+    size: [f32; 2],
+    height_scale: f32,
+    /// Whether the splat map is bound, only the first layer is used otherwise
+    splat_map: u32,
+    heightmap_size: [u32; 2],
+    _padding: [u32; 2],
+}
+
+/// Vertices of a chunk displaced on the CPU, indexed like the shared chunk grid.
+pub struct CpuTerrainChunk {
+    pub vertex_buffer: Buffer,
+    last_used: u64,
+}
+
+/// Bindings of a [`Terrain`], kept across frames.
+pub struct TerrainState {
+    pub terrain: ExtractedTerrain,
+    uniform_buffer: Buffer,
+    _descriptor_pool: DescriptorPool,
+    /// Set 0 of the terrain pipelines
+    pub(crate) descriptor_set: DescriptorSet,
+    /// Chunks of [`TerrainTessellation::Cpu`] terrains
+    pub cpu_chunks: HashMap<TerrainChunk, CpuTerrainChunk>,
+}
+
+impl TerrainState {
+    fn new(context: &Context, pipeline: &TerrainPipelineResources, terrain: &ExtractedTerrain) -> anyhow::Result<Self> {
+        let uniform_buffer = context.create_buffer(
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of::<TerrainUniform>() as _,
+        )?;
+        let descriptor_pool = context.create_descriptor_pool(1, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+            },
+        ])?;
+        let descriptor_set = descriptor_pool.allocate_set(pipeline.terrain_set_layout())?;
+
+        Ok(Self {
+            terrain: terrain.clone(),
+            uniform_buffer,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            cpu_chunks: HashMap::default(),
+        })
+    }
+
+    /// Displace the grid of a chunk by the heightmap.
+    fn create_cpu_chunk(&self, context: &Context, heightmap: &Heightmap, chunk: &TerrainChunk) -> anyhow::Result<Buffer> {
+        let terrain = &self.terrain.terrain;
+        let (offset, extent) = chunk.uv_rect();
+        let skirt_depth = skirt_depth(terrain, extent);
+
+        let vertices = terrain_chunk_grid()
+            .0
+            .into_iter()
+            .map(|[x, y, skirt]| {
+                let uv = offset + Vec2::new(x, y) * extent;
+                let height = heightmap.sample(uv) * terrain.height_scale - skirt * skirt_depth;
+                MeshVertex {
+                    position: [uv.x * terrain.size.x, height, uv.y * terrain.size.y],
+                    normal: heightmap.normal(uv, terrain.size, terrain.height_scale).normalize().to_array(),
+                    uv: uv.to_array(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let vertex_buffer = context.create_buffer(
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of_val(vertices.as_slice()) as _,
+        )?;
+        vertex_buffer.copy_data_to_buffer(&vertices)?;
+        Ok(vertex_buffer)
+    }
+}
+
+/// How far the skirt of a chunk of a normalized `extent` hangs below its edges.
+pub(crate) fn skirt_depth(terrain: &Terrain, extent: f32) -> f32 {
+    terrain.size.max_element() * extent / TERRAIN_CHUNK_RESOLUTION as f32 * 2.0
+}
+
+#[derive(Resource, Default)]
+pub struct TerrainInstances {
+    terrains: EntityHashMap<Entity, TerrainState>,
+    /// Frames prepared, ages the CPU chunk caches
+    frame: u64,
+}
+
+impl TerrainInstances {
+    pub fn get(&self, entity: Entity) -> Option<&TerrainState> {
+        self.terrains.get(&entity)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.terrains.is_empty()
+    }
+}
+
+fn prepare_terrains(
+    mut instances: ResMut<TerrainInstances>,
+    pipeline: Res<TerrainPipeline>,
+    heightmaps: Res<RenderAssets<Heightmap>>,
+    textures: Res<RenderAssets<Texture>>,
+    terrains: Query<(Entity, &ExtractedTerrain)>,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(pipeline) = pipeline.resources() else {
+        instances.terrains.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+    instances.frame += 1;
+
+    for (entity, terrain) in terrains.iter() {
+        // terrains show up once their heightmap is uploaded
+        let Some(heightmap) = heightmaps.get(terrain.terrain.heightmap.id()) else {
+            continue;
+        };
+        alive.insert(entity);
+
+        if !instances.terrains.contains_key(&entity) {
+            match TerrainState::new(context, pipeline, terrain) {
+                Ok(state) => {
+                    instances.terrains.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create terrain resources: {err}");
+                    continue;
+                }
+            }
+        }
+        let state = instances.terrains.get_mut(&entity).unwrap();
+        // cached chunks are stale once the shape changed
+        let previous = &state.terrain.terrain;
+        if previous.heightmap != terrain.terrain.heightmap
+            || previous.size != terrain.terrain.size
+            || previous.height_scale != terrain.terrain.height_scale
+            || terrain.terrain.tessellation != TerrainTessellation::Cpu {
+            state.cpu_chunks.clear();
+        }
+        state.terrain = terrain.clone();
+
+        let splat_map = terrain
+            .terrain
+            .splat_map
+            .as_ref()
+            .and_then(|handle| textures.get(handle.id()));
+        let uniform = TerrainUniform {
+            world_from_terrain: terrain.world_from_terrain.to_cols_array(),
+            normal_from_terrain: terrain.world_from_terrain.inverse().transpose().to_cols_array(),
+            layers: terrain.terrain.layers.map(|layer| {
+                let [r, g, b] = layer.base_color.to_linear_rgb();
+                [r, g, b, layer.roughness]
+            }),
+            size: terrain.terrain.size.to_array(),
+            height_scale: terrain.terrain.height_scale,
+            splat_map: splat_map.is_some() as u32,
+            heightmap_size: [heightmap.heightmap.width, heightmap.heightmap.height],
+            _padding: [0; 2],
+        };
+        if let Err(err) = state.uniform_buffer.copy_data_to_buffer(std::slice::from_ref(&uniform)) {
+            error!("Failed to upload terrain uniform: {err}");
+        }
+
+        // reloaded assets replace their resources, rewrite the set every frame
+        state.descriptor_set.update(&[
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: &heightmap.buffer,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::CombinedImageSampler {
+                    view: splat_map.map_or(&pipeline.fallback_splat_view, |texture| &texture.view),
+                    sampler: &pipeline.sampler,
+                    layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 2,
+                kind: WriteDescriptorSetKind::UniformBuffer {
+                    buffer: &state.uniform_buffer,
+                },
+            },
+        ]);
+    }
+
+    instances.terrains.retain(|entity, _| alive.contains(entity));
+}
+
+/// Matches `TerrainViewUniform` in `shaders/terrain/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TerrainViewUniform {
+    clip_from_world: [f32; 16],
+    view_from_clip: [f32; 16],
+    clip_from_view: [f32; 16],
+    camera_position: [f32; 4],
+    previous_clip_from_world: [f32; 16],
+    view_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+/// A chunk of a terrain drawn by a view.
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainDraw {
+    pub terrain: Entity,
+    pub chunk: TerrainChunk,
+}
+
+/// Depth attachment, bindings and visible chunks of a camera drawing terrains, kept across frames.
+pub struct TerrainViewState {
+    pub depth_image: Image,
+    pub depth_view: ImageView,
+    uniform_buffer: Buffer,
+    _descriptor_pool: DescriptorPool,
+    /// Set 1 of the terrain pipelines
+    pub(crate) descriptor_set: DescriptorSet,
+    /// Chunks passing the frustum culling this frame, but the chunks culled by the [`occlusion`](Self::occlusion)
+    pub draws: Vec<TerrainDraw>,
+    /// Culling of the GPU tessellated chunks of a camera with [`OcclusionCulling`]
+    pub occlusion: Option<TerrainOcclusionState>,
+    /// Camera of the previous frame
+    previous_clip_from_world: Option<Mat4>,
+    size: UVec2,
+}
+
+impl TerrainViewState {
+    fn new(context: &Context, pipeline: &TerrainPipelineResources, size: UVec2) -> anyhow::Result<Self> {
+        let depth_image = context.create_image(
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            MemoryLocation::GpuOnly,
+            pipeline.depth_format,
+            size.x,
+            size.y,
+        )?;
+        let depth_view = depth_image.create_image_view()?;
+        let uniform_buffer = context.create_buffer(
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of::<TerrainViewUniform>() as _,
+        )?;
+        let descriptor_pool = context.create_descriptor_pool(1, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 1,
+            },
+        ])?;
+        let descriptor_set = descriptor_pool.allocate_set(pipeline.view_set_layout())?;
+
+        Ok(Self {
+            depth_image,
+            depth_view,
+            uniform_buffer,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            draws: Vec::new(),
+            occlusion: None,
+            previous_clip_from_world: None,
+            size,
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct TerrainViews(pub(crate) EntityHashMap<Entity, TerrainViewState>);
+
+impl TerrainViews {
+    pub fn get(&self, entity: Entity) -> Option<&TerrainViewState> {
+        self.0.get(&entity)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_terrain_views(
+    mut views: ResMut<TerrainViews>,
+    mut instances: ResMut<TerrainInstances>,
+    pipeline: Res<TerrainPipeline>,
+    heightmaps: Res<RenderAssets<Heightmap>>,
+    deferred_views: Res<DeferredViews>,
+    pyramid_pipeline: Res<DepthPyramidPipeline>,
+    pyramid_views: Res<DepthPyramidViews>,
+    cameras: Query<(Entity, &ExtractedCamera, &ViewTarget, Option<&OcclusionCulling>)>,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(pipeline) = pipeline.resources().filter(|_| !instances.is_empty()) else {
+        views.0.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+    let instances = instances.as_mut();
+
+    for (entity, camera, target, occlusion_culling) in cameras.iter() {
+        let Some(deferred) = deferred_views.get(entity) else {
+            continue;
+        };
+        alive.insert(entity);
+
+        if !matches!(views.0.get(&entity), Some(state) if state.size == target.size) {
+            match TerrainViewState::new(context, pipeline, target.size) {
+                Ok(state) => {
+                    views.0.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create terrain view resources: {err}");
+                    views.0.remove(&entity);
+                    continue;
+                }
+            }
+        }
+        let state = views.0.get_mut(&entity).unwrap();
+
+        let view_from_world = camera.world_from_view.inverse();
+        let clip_from_world = camera.projection * view_from_world;
+        let previous_clip_from_world = state.previous_clip_from_world.replace(clip_from_world).unwrap_or(clip_from_world);
+        let uniform = TerrainViewUniform {
+            clip_from_world: clip_from_world.to_cols_array(),
+            view_from_clip: camera.projection.inverse().to_cols_array(),
+            clip_from_view: camera.projection.to_cols_array(),
+            camera_position: camera.world_from_view.w_axis.to_array(),
+            previous_clip_from_world: previous_clip_from_world.to_cols_array(),
+            view_size: target.size.as_vec2().to_array(),
+            _padding: [0.0; 2],
+        };
+        if let Err(err) = state.uniform_buffer.copy_data_to_buffer(std::slice::from_ref(&uniform)) {
+            error!("Failed to upload terrain view uniform: {err}");
+        }
+
+        // the G-buffer is recreated with the view target, rewrite the set every frame
+        state.descriptor_set.update(&[
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::UniformBuffer {
+                    buffer: &state.uniform_buffer,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &deferred.normal.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+        ]);
+
+        // culling needs the pyramid of this frame
+        let pyramid = pyramid_views
+            .get(entity)
+            .filter(|_| occlusion_culling.is_some() && pyramid_pipeline.resources().is_some());
+        if pyramid.is_none() {
+            state.occlusion = None;
+        } else if state.occlusion.is_none() {
+            match TerrainOcclusionState::new(context, pipeline) {
+                Ok(occlusion) => state.occlusion = Some(occlusion),
+                Err(err) => error!("Failed to create terrain occlusion culling resources: {err}"),
+            }
+        }
+
+        let frustum = Frustum::from_view_projection(&clip_from_world);
+        let camera_position = camera.world_from_view.w_axis.truncate();
+        let mut chunks = Vec::new();
+        state.draws.clear();
+        if let Some(occlusion) = &mut state.occlusion {
+            occlusion.begin();
+        }
+        for (terrain_entity, terrain) in instances.terrains.iter_mut() {
+            let Some(heightmap) = heightmaps.get(terrain.terrain.terrain.heightmap.id()) else {
+                continue;
+            };
+
+            chunks.clear();
+            select_terrain_chunks(&terrain.terrain, &heightmap.bounds, camera_position, &frustum, &mut chunks);
+            if let Some(occlusion) = state
+                .occlusion
+                .as_mut()
+                .filter(|_| terrain.terrain.terrain.tessellation == TerrainTessellation::Gpu && !chunks.is_empty()) {
+                occlusion.add_batch(*terrain_entity);
+                for chunk in &chunks {
+                    let skirt_depth = skirt_depth(&terrain.terrain.terrain, chunk.uv_rect().1);
+                    occlusion.add_chunk(*chunk, skirt_depth, chunk.world_bounds(&terrain.terrain, &heightmap.bounds));
+                }
+                continue;
+            }
+
+            for chunk in &chunks {
+                if terrain.terrain.terrain.tessellation == TerrainTessellation::Cpu {
+                    if !terrain.cpu_chunks.contains_key(chunk) {
+                        match terrain.create_cpu_chunk(context, &heightmap.heightmap, chunk) {
+                            Ok(vertex_buffer) => {
+                                terrain.cpu_chunks.insert(*chunk, CpuTerrainChunk {
+                                    vertex_buffer,
+                                    last_used: 0,
+                                });
+                            }
+                            Err(err) => {
+                                error!("Failed to create terrain chunk: {err}");
+                                continue;
+                            }
+                        }
+                    }
+                    terrain.cpu_chunks.get_mut(chunk).unwrap().last_used = instances.frame;
+                }
+                state.draws.push(TerrainDraw {
+                    terrain: *terrain_entity,
+                    chunk: *chunk,
+                });
+            }
+        }
+
+        if let (Some(occlusion), Some(pyramid)) = (&mut state.occlusion, pyramid) {
+            let uploaded = occlusion.upload(context, pipeline, pyramid, clip_from_world, camera_position);
+            if let Err(err) = uploaded {
+                error!("Failed to upload terrain occlusion culling chunks: {err}");
+                state.occlusion = None;
+            }
+        }
+    }
+
+    let frame = instances.frame;
+    for terrain in instances.terrains.values_mut() {
+        terrain.cpu_chunks.retain(|_, chunk| frame - chunk.last_used <= CPU_CHUNK_CACHE_FRAMES);
+    }
+    views.0.retain(|entity, _| alive.contains(entity));
+}
@@ -0,0 +1,174 @@
+use std::path::Path;
+use ash::vk;
+use anyhow::ensure;
+use bevy_math::{Vec2, Vec3};
+use gpu_allocator::MemoryLocation;
+use avalanche_asset::{Asset, AssetLoader};
+use avalanche_hlvk::Buffer;
+use crate::render_asset::{RenderAsset, RenderAssetContext};
+use crate::terrain::MAX_TERRAIN_LOD;
+use crate::texture::Texture;
+
+/// Grid of heights in `[0, 1]` on the CPU, row by row from the `-Z` edge of the terrain.
+///
+/// Uploaded into a [`GpuHeightmap`], which keeps the heights for CPU tessellation and LOD selection.
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    pub width: u32,
+    pub height: u32,
+    pub heights: Vec<f32>,
+}
+
+impl Asset for Heightmap {}
+
+impl Heightmap {
+    /// The red channel of an 8 bit texture.
+    pub fn from_texture(texture: &Texture) -> Self {
+        Self {
+            width: texture.width,
+            height: texture.height,
+            heights: texture.data.chunks_exact(4).map(|texel| texel[0] as f32 / 255.0).collect(),
+        }
+    }
+
+    #[inline]
+    fn texel(&self, x: u32, y: u32) -> f32 {
+        let x = x.min(self.width - 1);
+        let y = y.min(self.height - 1);
+        self.heights[(y * self.width + x) as usize]
+    }
+
+    /// Bilinearly filtered height at a normalized position, clamped to the edges.
+    pub fn sample(&self, uv: Vec2) -> f32 {
+        let texel = (uv.clamp(Vec2::ZERO, Vec2::ONE) * Vec2::new(self.width as f32 - 1.0, self.height as f32 - 1.0)).max(Vec2::ZERO);
+        let (x, y) = (texel.x as u32, texel.y as u32);
+        let fraction = texel.fract();
+
+        let top = self.texel(x, y) + (self.texel(x + 1, y) - self.texel(x, y)) * fraction.x;
+        let bottom = self.texel(x, y + 1) + (self.texel(x + 1, y + 1) - self.texel(x, y + 1)) * fraction.x;
+        top + (bottom - top) * fraction.y
+    }
+
+    /// Unnormalized normal at a normalized position of a terrain of `size` scaled by `height_scale`.
+    pub fn normal(&self, uv: Vec2, size: Vec2, height_scale: f32) -> Vec3 {
+        let step = Vec2::new(1.0 / self.width as f32, 1.0 / self.height as f32);
+        let dx = (self.sample(uv + Vec2::new(step.x, 0.0)) - self.sample(uv - Vec2::new(step.x, 0.0))) * height_scale;
+        let dz = (self.sample(uv + Vec2::new(0.0, step.y)) - self.sample(uv - Vec2::new(0.0, step.y))) * height_scale;
+        Vec3::new(-dx / (2.0 * step.x * size.x), 1.0, -dz / (2.0 * step.y * size.y))
+    }
+}
+
+/// Loads square 16 bit little endian raw heightmaps, as exported by most terrain tools.
+pub struct R16HeightmapLoader;
+
+impl AssetLoader for R16HeightmapLoader {
+    type Asset = Heightmap;
+
+    fn extensions(&self) -> &[&str] {
+        &["r16"]
+    }
+
+    fn load(&self, bytes: &[u8], _path: &Path) -> anyhow::Result<Heightmap> {
+        let texels = bytes.chunks_exact(2);
+        ensure!(texels.remainder().is_empty(), "R16 heightmap has an odd size");
+        let texel_count = texels.len();
+        let size = (texel_count as f64).sqrt() as u32;
+        ensure!(size > 1 && (size * size) as usize == texel_count, "R16 heightmap of {texel_count} texels is not square");
+
+        Ok(Heightmap {
+            width: size,
+            height: size,
+            heights: texels
+                .map(|texel| u16::from_le_bytes([texel[0], texel[1]]) as f32 / u16::MAX as f32)
+                .collect(),
+        })
+    }
+}
+
+/// Lowest and highest height of every quadtree node, from the root at level 0 to [`MAX_TERRAIN_LOD`].
+#[derive(Clone, Debug)]
+pub struct HeightBounds {
+    levels: Vec<Vec<(f32, f32)>>,
+}
+
+impl HeightBounds {
+    fn new(heightmap: &Heightmap) -> Self {
+        let resolution = 1u32 << MAX_TERRAIN_LOD;
+        let mut finest = Vec::with_capacity((resolution * resolution) as usize);
+        for y in 0..resolution {
+            for x in 0..resolution {
+                // nodes share their border texels with their neighbours
+                let texels_x = (x * (heightmap.width - 1) / resolution)..=((x + 1) * (heightmap.width - 1)).div_ceil(resolution);
+                let texels_y = (y * (heightmap.height - 1) / resolution)..=((y + 1) * (heightmap.height - 1)).div_ceil(resolution);
+                let bounds = texels_y
+                    .flat_map(|texel_y| texels_x.clone().map(move |texel_x| (texel_x, texel_y)))
+                    .map(|(texel_x, texel_y)| heightmap.texel(texel_x, texel_y))
+                    .fold((f32::MAX, f32::MIN), |(min, max), height| (min.min(height), max.max(height)));
+                finest.push(bounds);
+            }
+        }
+
+        let mut levels = vec![finest];
+        for level in (0..MAX_TERRAIN_LOD).rev() {
+            let resolution = 1usize << level;
+            let children = levels.last().unwrap();
+            let parents = (0..resolution * resolution)
+                .map(|index| {
+                    let (x, y) = (index % resolution, index / resolution);
+                    [(0, 0), (1, 0), (0, 1), (1, 1)]
+                        .map(|(dx, dy)| children[(y * 2 + dy) * resolution * 2 + x * 2 + dx])
+                        .into_iter()
+                        .fold((f32::MAX, f32::MIN), |(min, max), child| (min.min(child.0), max.max(child.1)))
+                })
+                .collect();
+            levels.push(parents);
+        }
+        levels.reverse();
+
+        Self { levels }
+    }
+
+    /// Height range of the node `(x, y)` of a level.
+    #[inline]
+    pub fn get(&self, level: u32, x: u32, y: u32) -> (f32, f32) {
+        let resolution = 1 << level;
+        self.levels[level as usize][(y * resolution + x) as usize]
+    }
+}
+
+/// Heights of a [`Heightmap`] in a storage buffer of `f32`, and on the CPU.
+pub struct GpuHeightmap {
+    pub buffer: Buffer,
+    pub heightmap: Heightmap,
+    pub bounds: HeightBounds,
+}
+
+impl RenderAsset for Heightmap {
+    type ExtractedAsset = Heightmap;
+    type PreparedAsset = GpuHeightmap;
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(heightmap: Self::ExtractedAsset, context: &mut RenderAssetContext) -> anyhow::Result<Self::PreparedAsset> {
+        ensure!(heightmap.width > 1 && heightmap.height > 1, "Heightmap needs at least 2x2 heights");
+        ensure!(
+            heightmap.heights.len() == heightmap.width as usize * heightmap.height as usize,
+            "Heightmap data doesn't match its {}x{} size", heightmap.width, heightmap.height,
+        );
+
+        let buffer = context.context().create_buffer(
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of_val(heightmap.heights.as_slice()) as _,
+        )?;
+        buffer.copy_data_to_buffer(&heightmap.heights)?;
+
+        Ok(GpuHeightmap {
+            buffer,
+            bounds: HeightBounds::new(&heightmap),
+            heightmap,
+        })
+    }
+}
@@ -0,0 +1,416 @@
+use ash::vk;
+use bevy_ecs::prelude::World;
+use avalanche_hlvk::{
+    BufferBarrier, CommandBuffer, ComputePipeline, ImageBarrier, RenderingAttachment, RenderingDepthAttachment,
+};
+use crate::deferred::{DeferredViewState, DeferredViews};
+use crate::extract::FrameContext;
+use crate::globals::Globals;
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::ViewNode;
+use crate::terrain::{
+    skirt_depth, TerrainChunkPushConstants, TerrainInstances, TerrainOcclusionState, TerrainPipeline,
+    TerrainPipelineResources, TerrainTessellation, TerrainViewState, TerrainViews, TERRAIN_CULL_WORKGROUP_SIZE,
+};
+use crate::view::ViewTarget;
+
+/// List the culled chunks of a phase and make the list visible to its indirect draws.
+fn dispatch_cull(
+    command_buffer: &CommandBuffer,
+    pipeline: &TerrainPipelineResources,
+    occlusion: &TerrainOcclusionState,
+    cull_pipeline: &ComputePipeline,
+) {
+    command_buffer.bind_compute_pipeline(cull_pipeline);
+    command_buffer.bind_descriptor_sets(
+        vk::PipelineBindPoint::COMPUTE,
+        &pipeline.cull_layout,
+        0,
+        &[&occlusion.cull_descriptor_set],
+    );
+    command_buffer.push_constants(
+        &pipeline.cull_layout,
+        vk::ShaderStageFlags::COMPUTE,
+        0,
+        bytemuck::bytes_of(&occlusion.push_constants),
+    );
+    command_buffer.dispatch(occlusion.chunk_count().div_ceil(TERRAIN_CULL_WORKGROUP_SIZE), 1, 1);
+    command_buffer.pipeline_buffer_barriers(&[
+        BufferBarrier {
+            buffer: &occlusion.draws,
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            dst_access_mask: vk::AccessFlags2::INDIRECT_COMMAND_READ,
+            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::DRAW_INDIRECT,
+        },
+        BufferBarrier {
+            buffer: occlusion.draw_list(),
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::VERTEX_SHADER,
+        },
+        // the second phase updates the visibility read by the first phase of the next frame
+        BufferBarrier {
+            buffer: occlusion.visibility(),
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+        },
+    ]);
+}
+
+/// Draw the chunks listed for a phase, a draw per terrain, inside the G-buffer pass.
+fn draw_culled_chunks(
+    command_buffer: &CommandBuffer,
+    pipeline: &TerrainPipelineResources,
+    instances: &TerrainInstances,
+    state: &TerrainViewState,
+    occlusion: &TerrainOcclusionState,
+    phase: u32,
+) {
+    command_buffer.bind_raster_pipeline(&pipeline.culled_pipeline);
+    command_buffer.bind_vertex_buffer(&pipeline.grid_vertex_buffer);
+    command_buffer.bind_index_buffer(&pipeline.grid_index_buffer, vk::IndexType::UINT32);
+    for (batch, terrain) in occlusion.batches.iter().enumerate() {
+        let Some(terrain) = instances.get(*terrain) else {
+            continue;
+        };
+        command_buffer.bind_descriptor_sets(
+            vk::PipelineBindPoint::GRAPHICS,
+            &pipeline.layout,
+            0,
+            &[&terrain.descriptor_set, &state.descriptor_set, &occlusion.draw_descriptor_set],
+        );
+        command_buffer.draw_indexed_indirect(&occlusion.draws, occlusion.draw_offset(phase, batch), 1);
+    }
+}
+
+/// The lighting pass and the raster passes over the G-buffer read it as storage images.
+fn gbuffer_read_barriers(command_buffer: &CommandBuffer, deferred: &DeferredViewState) {
+    let barriers = [&deferred.albedo, &deferred.normal, &deferred.material].map(|attachment| ImageBarrier {
+        image: &attachment.image,
+        old_layout: vk::ImageLayout::GENERAL,
+        new_layout: vk::ImageLayout::GENERAL,
+        src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+        dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+        src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER | vk::PipelineStageFlags2::FRAGMENT_SHADER,
+    });
+    command_buffer.pipeline_image_barriers(&barriers);
+}
+
+/// Rasterizes the visible [`TerrainChunk`](super::TerrainChunk)s of a view into its G-buffer,
+/// between the G-buffer pass of the meshes and the lighting pass.
+///
+/// The terrain depth is first seeded with the distance stored in the G-buffer, so terrains are
+/// hidden behind the meshes and behind each other without the mesh pass knowing about terrains.
+///
+/// With [`OcclusionCulling`](crate::view::OcclusionCulling) only the GPU tessellated chunks seen last
+/// frame are drawn here, the [`TerrainOcclusionNode`] draws the rest once the depth pyramid is built.
+#[derive(Default)]
+pub struct TerrainNode;
+
+impl ViewNode for TerrainNode {
+    type ViewQuery = &'static ViewTarget;
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        target: &ViewTarget,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pipeline) = world.resource::<TerrainPipeline>().resources() else {
+            return Ok(());
+        };
+        let (Some(state), Some(deferred)) = (
+            world.resource::<TerrainViews>().get(graph.view_entity()),
+            world.resource::<DeferredViews>().get(graph.view_entity()),
+        ) else {
+            return Ok(());
+        };
+        let occlusion = state.occlusion.as_ref().filter(|occlusion| occlusion.chunk_count() > 0);
+        if state.draws.is_empty() && occlusion.is_none() {
+            return Ok(());
+        }
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        let globals = world.resource::<Globals>();
+        globals.bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.cull_layout);
+        globals.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, &pipeline.layout);
+        let instances = world.resource::<TerrainInstances>();
+
+        let extent = vk::Extent2D {
+            width: target.size.x,
+            height: target.size.y,
+        };
+        let [albedo, material, velocity] = [&deferred.albedo, &deferred.material, &deferred.velocity].map(|attachment| ImageBarrier {
+            image: &attachment.image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        });
+        command_buffer.pipeline_image_barriers(&[
+            albedo,
+            material,
+            velocity,
+            ImageBarrier {
+                image: &deferred.normal.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+                src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                dst_stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            },
+            // the depth is seeded every frame
+            ImageBarrier {
+                image: &state.depth_image,
+                old_layout: vk::ImageLayout::UNDEFINED,
+                new_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                src_access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                dst_stage_mask: vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS,
+            },
+        ]);
+
+        command_buffer.begin_rendering_with_depth(
+            &[],
+            Some(&RenderingDepthAttachment {
+                view: &state.depth_view,
+                layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                load_op: vk::AttachmentLoadOp::CLEAR,
+                store_op: vk::AttachmentStoreOp::STORE,
+                clear_depth: 1.0,
+            }),
+            extent,
+        );
+        command_buffer.bind_raster_pipeline(&pipeline.depth_pipeline);
+        command_buffer.set_viewport(extent);
+        command_buffer.set_scissor(extent);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, &pipeline.layout, 1, &[&state.descriptor_set]);
+        command_buffer.draw(3);
+        command_buffer.end_rendering();
+
+        if let Some(occlusion) = occlusion {
+            dispatch_cull(command_buffer, pipeline, occlusion, &pipeline.cull_early);
+        }
+
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image: &deferred.normal.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+                dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            },
+            ImageBarrier {
+                image: &state.depth_image,
+                old_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                new_layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                src_access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+                dst_stage_mask: vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+            },
+        ]);
+
+        let attachments = deferred.attachments().map(|attachment| RenderingAttachment {
+            view: &attachment.view,
+            layout: vk::ImageLayout::GENERAL,
+            load_op: vk::AttachmentLoadOp::LOAD,
+            clear_color: [0.0; 4],
+        });
+        command_buffer.begin_rendering_with_depth(
+            &attachments,
+            Some(&RenderingDepthAttachment {
+                view: &state.depth_view,
+                layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                load_op: vk::AttachmentLoadOp::LOAD,
+                // the second culling phase keeps testing against it
+                store_op: if occlusion.is_some() {
+                    vk::AttachmentStoreOp::STORE
+                } else {
+                    vk::AttachmentStoreOp::DONT_CARE
+                },
+                clear_depth: 1.0,
+            }),
+            extent,
+        );
+        command_buffer.set_viewport(extent);
+        command_buffer.set_scissor(extent);
+        if let Some(occlusion) = occlusion {
+            draw_culled_chunks(command_buffer, pipeline, instances, state, occlusion, 0);
+        }
+        command_buffer.bind_index_buffer(&pipeline.grid_index_buffer, vk::IndexType::UINT32);
+
+        // draws are grouped by terrain
+        let mut bound = None;
+        for draw in &state.draws {
+            let Some(terrain) = instances.get(draw.terrain) else {
+                continue;
+            };
+            let tessellation = terrain.terrain.terrain.tessellation;
+            if bound != Some(draw.terrain) {
+                match tessellation {
+                    TerrainTessellation::Gpu => {
+                        command_buffer.bind_raster_pipeline(&pipeline.gpu_pipeline);
+                        command_buffer.bind_vertex_buffer(&pipeline.grid_vertex_buffer);
+                    }
+                    TerrainTessellation::Cpu => command_buffer.bind_raster_pipeline(&pipeline.cpu_pipeline),
+                }
+                command_buffer.bind_descriptor_sets(
+                    vk::PipelineBindPoint::GRAPHICS,
+                    &pipeline.layout,
+                    0,
+                    &[&terrain.descriptor_set, &state.descriptor_set],
+                );
+                bound = Some(draw.terrain);
+            }
+
+            match tessellation {
+                TerrainTessellation::Gpu => {
+                    let (offset, extent) = draw.chunk.uv_rect();
+                    let push_constants = TerrainChunkPushConstants {
+                        offset: offset.to_array(),
+                        extent,
+                        skirt_depth: skirt_depth(&terrain.terrain.terrain, extent),
+                    };
+                    command_buffer.push_constants(
+                        &pipeline.layout,
+                        vk::ShaderStageFlags::VERTEX,
+                        0,
+                        bytemuck::bytes_of(&push_constants),
+                    );
+                }
+                TerrainTessellation::Cpu => {
+                    let Some(chunk) = terrain.cpu_chunks.get(&draw.chunk) else {
+                        continue;
+                    };
+                    command_buffer.bind_vertex_buffer(&chunk.vertex_buffer);
+                }
+            }
+            command_buffer.draw_indexed(pipeline.grid_index_count);
+        }
+        command_buffer.end_rendering();
+        gbuffer_read_barriers(command_buffer, deferred);
+
+        Ok(())
+    }
+}
+
+/// Second phase of the terrain occlusion culling of a view with [`OcclusionCulling`](crate::view::OcclusionCulling),
+/// after the [`DepthPyramidNode`](crate::depth_pyramid::DepthPyramidNode).
+///
+/// Tests every chunk against the pyramid built from the meshes and the chunks drawn by the
+/// [`TerrainNode`], then draws the visible chunks it skipped into the G-buffer over its depth.
+#[derive(Default)]
+pub struct TerrainOcclusionNode;
+
+impl ViewNode for TerrainOcclusionNode {
+    type ViewQuery = &'static ViewTarget;
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        target: &ViewTarget,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pipeline) = world.resource::<TerrainPipeline>().resources() else {
+            return Ok(());
+        };
+        let (Some(state), Some(deferred)) = (
+            world.resource::<TerrainViews>().get(graph.view_entity()),
+            world.resource::<DeferredViews>().get(graph.view_entity()),
+        ) else {
+            return Ok(());
+        };
+        let Some(occlusion) = state.occlusion.as_ref().filter(|occlusion| occlusion.chunk_count() > 0) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        let globals = world.resource::<Globals>();
+        globals.bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.cull_layout);
+        globals.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, &pipeline.layout);
+
+        // the first phase read the draws and the list this phase appends to
+        command_buffer.pipeline_buffer_barriers(&[
+            BufferBarrier {
+                buffer: &occlusion.draws,
+                src_access_mask: vk::AccessFlags2::INDIRECT_COMMAND_READ,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::DRAW_INDIRECT,
+                dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            },
+            BufferBarrier {
+                buffer: occlusion.draw_list(),
+                src_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+                dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::VERTEX_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            },
+        ]);
+        dispatch_cull(command_buffer, pipeline, occlusion, &pipeline.cull_late);
+
+        let barriers = deferred.attachments().map(|attachment| ImageBarrier {
+            image: &attachment.image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+            dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        });
+        command_buffer.pipeline_image_barriers(&barriers);
+
+        let extent = vk::Extent2D {
+            width: target.size.x,
+            height: target.size.y,
+        };
+        let attachments = deferred.attachments().map(|attachment| RenderingAttachment {
+            view: &attachment.view,
+            layout: vk::ImageLayout::GENERAL,
+            load_op: vk::AttachmentLoadOp::LOAD,
+            clear_color: [0.0; 4],
+        });
+        command_buffer.begin_rendering_with_depth(
+            &attachments,
+            Some(&RenderingDepthAttachment {
+                view: &state.depth_view,
+                layout: vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                load_op: vk::AttachmentLoadOp::LOAD,
+                store_op: vk::AttachmentStoreOp::DONT_CARE,
+                clear_depth: 1.0,
+            }),
+            extent,
+        );
+        command_buffer.set_viewport(extent);
+        command_buffer.set_scissor(extent);
+        draw_culled_chunks(
+            command_buffer,
+            pipeline,
+            world.resource::<TerrainInstances>(),
+            state,
+            occlusion,
+            1,
+        );
+        command_buffer.end_rendering();
+        gbuffer_read_barriers(command_buffer, deferred);
+
+        Ok(())
+    }
+}
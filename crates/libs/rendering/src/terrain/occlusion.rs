@@ -0,0 +1,337 @@
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::prelude::Entity;
+use bevy_math::{Mat4, Vec3};
+use bevy_utils::HashMap;
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{Buffer, Context, DescriptorPool, DescriptorSet, WriteDescriptorSet, WriteDescriptorSetKind};
+use crate::depth_pyramid::DepthPyramidViewState;
+use crate::terrain::{TerrainChunk, TerrainPipelineResources};
+
+/// Threads of a workgroup of the terrain culling shaders.
+pub const TERRAIN_CULL_WORKGROUP_SIZE: u32 = 64;
+/// Chunks, batches and slots a view is created with, grown to the next power of two when exceeded.
+const INITIAL_CAPACITY: u32 = 256;
+
+/// Matches `CulledTerrainChunk` in `shaders/terrain/culling.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct GpuCulledTerrainChunk {
+    aabb_min: [f32; 3],
+    slot: u32,
+    aabb_max: [f32; 3],
+    batch: u32,
+    offset: [f32; 2],
+    extent: f32,
+    skirt_depth: f32,
+    fresh: u32,
+    _padding: [u32; 3],
+}
+
+// SAFETY: plain 32 bit fields without implicit padding
+unsafe impl Zeroable for GpuCulledTerrainChunk {}
+unsafe impl Pod for GpuCulledTerrainChunk {}
+
+/// Matches `TerrainCullPushConstants` in `shaders/terrain/cull.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TerrainCullPushConstants {
+    pub clip_from_world: [f32; 16],
+    pub camera_position: [f32; 4],
+    pub chunk_count: u32,
+    pub batch_count: u32,
+    pub _padding: [u32; 2],
+}
+
+// SAFETY: plain 32 bit fields without implicit padding
+unsafe impl Zeroable for TerrainCullPushConstants {}
+unsafe impl Pod for TerrainCullPushConstants {}
+
+/// Occlusion culling of the GPU tessellated chunks of a view with
+/// [`OcclusionCulling`](crate::view::OcclusionCulling), kept across frames.
+///
+/// Chunks are drawn in two phases of indirect draws, a draw per terrain each. The first phase draws the
+/// chunks seen last frame, the second one the chunks passing the test against the depth pyramid built
+/// in between that the first phase skipped. A chunk keeps its visibility slot while it stays selected.
+pub struct TerrainOcclusionState {
+    chunks: Buffer,
+    draw_list: Buffer,
+    visibility: Buffer,
+    /// A `vk::DrawIndexedIndirectCommand` per batch of the first phase, then per batch of the second one
+    pub draws: Buffer,
+    chunk_capacity: u32,
+    batch_capacity: u32,
+    slot_capacity: u32,
+    slots: HashMap<(Entity, TerrainChunk), u32>,
+    previous_slots: HashMap<(Entity, TerrainChunk), u32>,
+    free_slots: Vec<u32>,
+    slot_count: u32,
+    records: Vec<GpuCulledTerrainChunk>,
+    /// Terrain drawn by each batch this frame
+    pub batches: Vec<Entity>,
+    pub push_constants: TerrainCullPushConstants,
+    _descriptor_pool: DescriptorPool,
+    /// Set 0 of the culling pipelines
+    pub(crate) cull_descriptor_set: DescriptorSet,
+    /// Set 2 of the culled terrain pipeline
+    pub(crate) draw_descriptor_set: DescriptorSet,
+}
+
+fn create_storage_buffer(
+    context: &Context,
+    usage: vk::BufferUsageFlags,
+    location: MemoryLocation,
+    size: usize,
+) -> anyhow::Result<Buffer> {
+    context.create_buffer(usage | vk::BufferUsageFlags::STORAGE_BUFFER, location, size as _)
+}
+
+fn create_visibility_buffer(context: &Context, capacity: u32) -> anyhow::Result<Buffer> {
+    let buffer = create_storage_buffer(
+        context,
+        vk::BufferUsageFlags::empty(),
+        MemoryLocation::CpuToGpu,
+        capacity as usize * std::mem::size_of::<u32>(),
+    )?;
+    // no slot was seen yet
+    buffer.copy_data_to_buffer(&vec![0u32; capacity as usize])?;
+    Ok(buffer)
+}
+
+impl TerrainOcclusionState {
+    pub(crate) fn new(context: &Context, pipeline: &TerrainPipelineResources) -> anyhow::Result<Self> {
+        let descriptor_pool = context.create_descriptor_pool(2, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 6,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1,
+            },
+        ])?;
+        let cull_descriptor_set = descriptor_pool.allocate_set(pipeline.cull_set_layout())?;
+        let draw_descriptor_set = descriptor_pool.allocate_set(pipeline.culled_set_layout())?;
+
+        Ok(Self {
+            chunks: Self::create_chunk_buffer(context, INITIAL_CAPACITY)?,
+            draw_list: Self::create_draw_list(context, INITIAL_CAPACITY)?,
+            visibility: create_visibility_buffer(context, INITIAL_CAPACITY)?,
+            draws: Self::create_draw_buffer(context, INITIAL_CAPACITY)?,
+            chunk_capacity: INITIAL_CAPACITY,
+            batch_capacity: INITIAL_CAPACITY,
+            slot_capacity: INITIAL_CAPACITY,
+            slots: HashMap::default(),
+            previous_slots: HashMap::default(),
+            free_slots: Vec::new(),
+            slot_count: 0,
+            records: Vec::new(),
+            batches: Vec::new(),
+            push_constants: TerrainCullPushConstants::default(),
+            _descriptor_pool: descriptor_pool,
+            cull_descriptor_set,
+            draw_descriptor_set,
+        })
+    }
+
+    fn create_chunk_buffer(context: &Context, capacity: u32) -> anyhow::Result<Buffer> {
+        create_storage_buffer(
+            context,
+            vk::BufferUsageFlags::empty(),
+            MemoryLocation::CpuToGpu,
+            capacity as usize * std::mem::size_of::<GpuCulledTerrainChunk>(),
+        )
+    }
+
+    /// Chunk indices of both phases, the first phase in the first `capacity` entries.
+    fn create_draw_list(context: &Context, capacity: u32) -> anyhow::Result<Buffer> {
+        create_storage_buffer(
+            context,
+            vk::BufferUsageFlags::empty(),
+            MemoryLocation::GpuOnly,
+            capacity as usize * 2 * std::mem::size_of::<u32>(),
+        )
+    }
+
+    fn create_draw_buffer(context: &Context, capacity: u32) -> anyhow::Result<Buffer> {
+        create_storage_buffer(
+            context,
+            vk::BufferUsageFlags::INDIRECT_BUFFER,
+            MemoryLocation::CpuToGpu,
+            capacity as usize * 2 * std::mem::size_of::<vk::DrawIndexedIndirectCommand>(),
+        )
+    }
+
+    /// Start collecting the chunks of a frame.
+    pub(crate) fn begin(&mut self) {
+        std::mem::swap(&mut self.slots, &mut self.previous_slots);
+        self.slots.clear();
+        self.records.clear();
+        self.batches.clear();
+    }
+
+    /// Start the batch of a terrain, the chunks added next are drawn with its bindings.
+    pub(crate) fn add_batch(&mut self, terrain: Entity) {
+        self.batches.push(terrain);
+    }
+
+    /// Add a chunk of the current batch with its world space bounds, its skirt excluded like in the frustum culling.
+    pub(crate) fn add_chunk(&mut self, chunk: TerrainChunk, skirt_depth: f32, (min, max): (Vec3, Vec3)) {
+        let Some(&terrain) = self.batches.last() else {
+            return;
+        };
+        let (slot, fresh) = match self.previous_slots.remove(&(terrain, chunk)) {
+            Some(slot) => (slot, false),
+            None => (self.free_slots.pop().unwrap_or_else(|| {
+                self.slot_count += 1;
+                self.slot_count - 1
+            }), true),
+        };
+        self.slots.insert((terrain, chunk), slot);
+
+        let (offset, extent) = chunk.uv_rect();
+        self.records.push(GpuCulledTerrainChunk {
+            aabb_min: min.to_array(),
+            slot,
+            aabb_max: max.to_array(),
+            batch: self.batches.len() as u32 - 1,
+            offset: offset.to_array(),
+            extent,
+            skirt_depth,
+            fresh: fresh as u32,
+            _padding: [0; 3],
+        });
+    }
+
+    /// Upload the chunks of the frame and bind the depth pyramid, growing the buffers as needed.
+    pub(crate) fn upload(
+        &mut self,
+        context: &Context,
+        pipeline: &TerrainPipelineResources,
+        pyramid: &DepthPyramidViewState,
+        clip_from_world: Mat4,
+        camera_position: Vec3,
+    ) -> anyhow::Result<()> {
+        // chunks no longer selected give their slot back
+        self.free_slots.extend(self.previous_slots.drain().map(|(_, slot)| slot));
+
+        let chunk_count = self.records.len() as u32;
+        let batch_count = self.batches.len() as u32;
+        if chunk_count > self.chunk_capacity {
+            self.chunk_capacity = chunk_count.next_power_of_two();
+            self.chunks = Self::create_chunk_buffer(context, self.chunk_capacity)?;
+            self.draw_list = Self::create_draw_list(context, self.chunk_capacity)?;
+        }
+        if batch_count > self.batch_capacity {
+            self.batch_capacity = batch_count.next_power_of_two();
+            self.draws = Self::create_draw_buffer(context, self.batch_capacity)?;
+        }
+        if self.slot_count > self.slot_capacity {
+            // the history is lost, every chunk is tested by the second phase this frame
+            self.slot_capacity = self.slot_count.next_power_of_two();
+            self.visibility = create_visibility_buffer(context, self.slot_capacity)?;
+        }
+
+        if !self.records.is_empty() {
+            self.chunks.copy_data_to_buffer(&self.records)?;
+        }
+        // each batch draws its chunks from the range of the draw list of its phase starting at its first chunk
+        let mut first_chunks = vec![0u32; self.batches.len()];
+        for (index, record) in self.records.iter().enumerate().rev() {
+            first_chunks[record.batch as usize] = index as u32;
+        }
+        let draws = [0, self.chunk_capacity]
+            .into_iter()
+            .flat_map(|phase_offset| first_chunks.iter().map(move |first| vk::DrawIndexedIndirectCommand {
+                index_count: pipeline.grid_index_count,
+                instance_count: 0,
+                first_index: 0,
+                vertex_offset: 0,
+                first_instance: phase_offset + first,
+            }))
+            .collect::<Vec<_>>();
+        if !draws.is_empty() {
+            self.draws.copy_data_to_buffer(&draws)?;
+        }
+
+        self.push_constants = TerrainCullPushConstants {
+            clip_from_world: clip_from_world.to_cols_array(),
+            camera_position: camera_position.extend(1.0).to_array(),
+            chunk_count,
+            batch_count,
+            _padding: [0; 2],
+        };
+
+        // buffers are grown and the pyramid is recreated with the view target, rewrite the sets every frame
+        self.cull_descriptor_set.update(&[
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: &self.chunks,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: &self.draw_list,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 2,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: &self.visibility,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 3,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: &self.draws,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 4,
+                kind: WriteDescriptorSetKind::CombinedImageSampler {
+                    view: &pyramid.view,
+                    sampler: &pipeline.pyramid_sampler,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+        ]);
+        self.draw_descriptor_set.update(&[
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: &self.chunks,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: &self.draw_list,
+                },
+            },
+        ]);
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn chunk_count(&self) -> u32 {
+        self.push_constants.chunk_count
+    }
+
+    /// Byte offset of the draw of a batch in a phase of the [`draws`](Self::draws).
+    #[inline]
+    pub fn draw_offset(&self, phase: u32, batch: usize) -> vk::DeviceSize {
+        ((phase * self.push_constants.batch_count) as usize + batch) as vk::DeviceSize
+            * std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as vk::DeviceSize
+    }
+
+    pub(crate) fn visibility(&self) -> &Buffer {
+        &self.visibility
+    }
+
+    pub(crate) fn draw_list(&self) -> &Buffer {
+        &self.draw_list
+    }
+}
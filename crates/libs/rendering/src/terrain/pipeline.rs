@@ -0,0 +1,316 @@
+use anyhow::Context as _;
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::prelude::{Query, Res, ResMut, Resource};
+use bevy_log::error;
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{
+    BlendMode, Buffer, ComputePipeline, Context, DescriptorSetLayout, Image, ImageBarrier, ImageView, PipelineLayout,
+    FormatSelector, RasterColorAttachment, RasterDepthAttachment, RasterPipeline, RasterPipelineCreateInfo, Sampler,
+    StagedShader, VertexStreamSet,
+};
+use crate::camera::ExtractedCamera;
+use crate::deferred::{DEFERRED_GRAPH, GBUFFER_ALBEDO_FORMAT, GBUFFER_MATERIAL_FORMAT, GBUFFER_NORMAL_FORMAT};
+use crate::extract::FrameContext;
+use crate::globals;
+use crate::mesh::MeshVertexLayout;
+use crate::shader::ShaderDirectory;
+use crate::terrain::{terrain_chunk_grid, TerrainGridVertex, TERRAIN_DEPTH_FORMATS};
+use crate::transparent::FULLSCREEN_VERTEX_SHADER;
+use crate::view::MOTION_VECTORS_FORMAT;
+
+pub(crate) const GPU_VERTEX_SHADER: &str = "terrain/gpu.vert";
+pub(crate) const CPU_VERTEX_SHADER: &str = "terrain/cpu.vert";
+pub(crate) const TERRAIN_FRAGMENT_SHADER: &str = "terrain/terrain.frag";
+pub(crate) const DEPTH_FRAGMENT_SHADER: &str = "terrain/depth.frag";
+pub(crate) const CULLED_VERTEX_SHADER: &str = "terrain/culled.vert";
+pub(crate) const CULL_EARLY_SHADER: &str = "terrain/cull_early.comp";
+pub(crate) const CULL_LATE_SHADER: &str = "terrain/cull_late.comp";
+
+/// Matches `TerrainChunkPushConstants` in `shaders/terrain/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TerrainChunkPushConstants {
+    /// Normalized terrain position of the `-X -Z` corner of the chunk
+    pub offset: [f32; 2],
+    /// Normalized extent of the chunk
+    pub extent: f32,
+    pub skirt_depth: f32,
+}
+
+// SAFETY: plain `f32` fields without implicit padding
+unsafe impl Zeroable for TerrainChunkPushConstants {}
+unsafe impl Pod for TerrainChunkPushConstants {}
+
+pub struct TerrainPipelineResources {
+    /// Reflected from the terrain shaders, set 0 binds a terrain, set 1 a view and set 2 its culled chunks
+    pub layout: PipelineLayout,
+    /// Displaces the chunk grid by the heightmap in the vertex shader
+    pub gpu_pipeline: RasterPipeline,
+    /// Displaces the chunk grid for the chunks listed by the culling shaders, drawn indirectly
+    pub culled_pipeline: RasterPipeline,
+    /// Draws chunks displaced on the CPU
+    pub cpu_pipeline: RasterPipeline,
+    /// Seeds the terrain depth with the distance stored in the G-buffer
+    pub depth_pipeline: RasterPipeline,
+    /// First of the [`TERRAIN_DEPTH_FORMATS`] supported by the device
+    pub depth_format: vk::Format,
+    /// [`TerrainGridVertex`]es of a chunk
+    pub grid_vertex_buffer: Buffer,
+    /// Indices of a chunk, shared by GPU and CPU tessellated chunks
+    pub grid_index_buffer: Buffer,
+    pub grid_index_count: u32,
+    /// Samples the splat maps
+    pub sampler: Sampler,
+    _fallback_splat_image: Image,
+    /// Bound while a terrain has no splat map, never read
+    pub(crate) fallback_splat_view: ImageView,
+    /// Reflected from the culling shaders, set 0 binds the culled chunks of a view
+    pub cull_layout: PipelineLayout,
+    /// Lists the chunks seen last frame
+    pub cull_early: ComputePipeline,
+    /// Tests the chunks against the depth pyramid and lists the ones turned visible
+    pub cull_late: ComputePipeline,
+    /// Fetches the depth pyramid texels
+    pub pyramid_sampler: Sampler,
+}
+
+impl TerrainPipelineResources {
+    #[inline]
+    pub fn terrain_set_layout(&self) -> &DescriptorSetLayout {
+        &self.layout.descriptor_set_layouts()[0]
+    }
+
+    #[inline]
+    pub fn view_set_layout(&self) -> &DescriptorSetLayout {
+        &self.layout.descriptor_set_layouts()[1]
+    }
+
+    #[inline]
+    pub fn culled_set_layout(&self) -> &DescriptorSetLayout {
+        &self.layout.descriptor_set_layouts()[2]
+    }
+
+    #[inline]
+    pub fn cull_set_layout(&self) -> &DescriptorSetLayout {
+        &self.cull_layout.descriptor_set_layouts()[0]
+    }
+}
+
+/// The terrain pipelines, created the first time a camera uses the deferred graph.
+#[derive(Resource, Default)]
+pub enum TerrainPipeline {
+    #[default]
+    Uninitialized,
+    Ready(Box<TerrainPipelineResources>),
+    /// Creation failed, usually shaders are missing.
+    Failed,
+}
+
+impl TerrainPipeline {
+    pub fn resources(&self) -> Option<&TerrainPipelineResources> {
+        match self {
+            TerrainPipeline::Ready(resources) => Some(resources),
+            _ => None,
+        }
+    }
+}
+
+/// Terrains write the G-buffer, depth tested against the meshes and each other.
+fn create_gbuffer_pipeline(
+    context: &Context,
+    layout: &PipelineLayout,
+    shaders: &[StagedShader],
+    vertex_stream: &VertexStreamSet,
+    depth_format: vk::Format,
+) -> anyhow::Result<RasterPipeline> {
+    context.create_graphics_pipeline(layout, RasterPipelineCreateInfo {
+        shaders,
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        vertex_stream,
+        viewport: None,
+        scissor: None,
+        color_attachments: &[
+            RasterColorAttachment::new(GBUFFER_ALBEDO_FORMAT, BlendMode::Opaque),
+            RasterColorAttachment::new(GBUFFER_NORMAL_FORMAT, BlendMode::Opaque),
+            RasterColorAttachment::new(GBUFFER_MATERIAL_FORMAT, BlendMode::Opaque),
+            RasterColorAttachment::new(MOTION_VECTORS_FORMAT, BlendMode::Opaque),
+        ],
+        depth_attachment: Some(RasterDepthAttachment::new(depth_format, vk::CompareOp::LESS, true)),
+        dynamic_states: Some(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]),
+        polygon_mode: vk::PolygonMode::FILL,
+        front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+        samples: vk::SampleCountFlags::TYPE_1,
+        // skirts are seen from both sides
+        cull_mode: vk::CullModeFlags::NONE,
+    })
+}
+
+fn create_resources(frame_context: &FrameContext, shaders: &ShaderDirectory) -> anyhow::Result<TerrainPipelineResources> {
+    let context = frame_context.render_context();
+    // the shaders of every terrain pipeline together declare the layout
+    let stages = [
+        (GPU_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX),
+        (CPU_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX),
+        (TERRAIN_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT),
+        (FULLSCREEN_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX),
+        (DEPTH_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT),
+        (CULLED_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX),
+    ];
+    let stages = stages
+        .into_iter()
+        .map(|(path, stage)| shaders.load(context, path, stage))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let layout = globals::pipeline_layout(&stages)?;
+    anyhow::ensure!(globals::pass_set_count(&stages) == 3, "terrain shaders must use sets 0, 1 and 2");
+
+    let depth_format = FormatSelector::new(TERRAIN_DEPTH_FORMATS, vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        .select(&context.physical_device)
+        .context("No terrain depth format is supported")?;
+
+    let cpu_vertex_layout = MeshVertexLayout::default();
+    cpu_vertex_layout.validate(&stages[1])?;
+    let grid_vertex_stream = VertexStreamSet::empty().add_stream(
+        std::mem::size_of::<TerrainGridVertex>() as _,
+        vk::VertexInputRate::VERTEX,
+        0,
+        vk::Format::R32G32B32_SFLOAT,
+        Some(0),
+    );
+    let gpu_pipeline = create_gbuffer_pipeline(
+        context,
+        &layout,
+        &[
+            shaders.load(context, GPU_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX)?,
+            shaders.load(context, TERRAIN_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT)?,
+        ],
+        &grid_vertex_stream,
+        depth_format,
+    )?;
+    let culled_pipeline = create_gbuffer_pipeline(
+        context,
+        &layout,
+        &[
+            shaders.load(context, CULLED_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX)?,
+            shaders.load(context, TERRAIN_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT)?,
+        ],
+        &grid_vertex_stream,
+        depth_format,
+    )?;
+    let cpu_pipeline = create_gbuffer_pipeline(context, &layout, &stages[1..3], &cpu_vertex_layout.vertex_stream(), depth_format)?;
+    let depth_pipeline = context.create_graphics_pipeline(&layout, RasterPipelineCreateInfo {
+        shaders: &stages[3..5],
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        vertex_stream: &VertexStreamSet::empty(),
+        viewport: None,
+        scissor: None,
+        color_attachments: &[],
+        depth_attachment: Some(RasterDepthAttachment::new(depth_format, vk::CompareOp::ALWAYS, true)),
+        dynamic_states: Some(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]),
+        polygon_mode: vk::PolygonMode::FILL,
+        front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+        samples: vk::SampleCountFlags::TYPE_1,
+        cull_mode: vk::CullModeFlags::NONE,
+    })?;
+
+    let (grid_vertices, grid_indices) = terrain_chunk_grid();
+    let grid_vertex_buffer = context.create_buffer(
+        vk::BufferUsageFlags::VERTEX_BUFFER,
+        MemoryLocation::CpuToGpu,
+        std::mem::size_of_val(grid_vertices.as_slice()) as _,
+    )?;
+    grid_vertex_buffer.copy_data_to_buffer(&grid_vertices)?;
+    let grid_index_buffer = context.create_buffer(
+        vk::BufferUsageFlags::INDEX_BUFFER,
+        MemoryLocation::CpuToGpu,
+        std::mem::size_of_val(grid_indices.as_slice()) as _,
+    )?;
+    grid_index_buffer.copy_data_to_buffer(&grid_indices)?;
+
+    let sampler = context.create_sampler(
+        &vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+    )?;
+    let pyramid_sampler = context.create_sampler(
+        &vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .max_lod(vk::LOD_CLAMP_NONE)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+    )?;
+    let cull_stages = [
+        shaders.load(context, CULL_EARLY_SHADER, vk::ShaderStageFlags::COMPUTE)?,
+        shaders.load(context, CULL_LATE_SHADER, vk::ShaderStageFlags::COMPUTE)?,
+    ];
+    let cull_layout = globals::pipeline_layout(&cull_stages)?;
+    anyhow::ensure!(globals::pass_set_count(&cull_stages) == 1, "terrain culling shaders must only use set 0");
+    let [cull_early, cull_late] = &cull_stages;
+    let cull_early = context.create_compute_pipeline(&cull_layout, cull_early)?;
+    let cull_late = context.create_compute_pipeline(&cull_layout, cull_late)?;
+
+    let fallback_splat_image = context.create_image(
+        vk::ImageUsageFlags::SAMPLED,
+        MemoryLocation::GpuOnly,
+        vk::Format::R8G8B8A8_UNORM,
+        1,
+        1,
+    )?;
+    let fallback_splat_view = fallback_splat_image.create_image_view()?;
+    if let Some(command_buffer) = frame_context.command_buffer(0) {
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &fallback_splat_image,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            src_access_mask: vk::AccessFlags2::NONE,
+            dst_access_mask: vk::AccessFlags2::SHADER_READ,
+            src_stage_mask: vk::PipelineStageFlags2::NONE,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+    }
+
+    Ok(TerrainPipelineResources {
+        layout,
+        gpu_pipeline,
+        culled_pipeline,
+        cpu_pipeline,
+        depth_pipeline,
+        depth_format,
+        grid_vertex_buffer,
+        grid_index_buffer,
+        grid_index_count: grid_indices.len() as _,
+        sampler,
+        _fallback_splat_image: fallback_splat_image,
+        fallback_splat_view,
+        cull_layout,
+        cull_early,
+        cull_late,
+        pyramid_sampler,
+    })
+}
+
+pub(crate) fn prepare_terrain_pipeline(
+    mut pipeline: ResMut<TerrainPipeline>,
+    shaders: Res<ShaderDirectory>,
+    cameras: Query<&ExtractedCamera>,
+    frame_context: Res<FrameContext>,
+) {
+    if !matches!(*pipeline, TerrainPipeline::Uninitialized)
+        || !cameras.iter().any(|camera| camera.render_graph == DEFERRED_GRAPH) {
+        return;
+    }
+
+    *pipeline = match create_resources(&frame_context, &shaders) {
+        Ok(resources) => TerrainPipeline::Ready(Box::new(resources)),
+        Err(err) => {
+            error!("Failed to create terrain pipeline: {err}");
+            TerrainPipeline::Failed
+        }
+    };
+}
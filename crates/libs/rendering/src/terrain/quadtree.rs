@@ -0,0 +1,126 @@
+use bevy_math::{BVec3, Vec2, Vec3};
+use crate::spatial::{Aabb, Frustum};
+use crate::terrain::{ExtractedTerrain, HeightBounds, MAX_TERRAIN_LOD, TERRAIN_CHUNK_RESOLUTION};
+
+/// A node of the terrain quadtree drawn as a grid of [`TERRAIN_CHUNK_RESOLUTION`] quads.
+///
+/// Level 0 covers the whole terrain, every level halves the extent of its nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TerrainChunk {
+    pub lod: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TerrainChunk {
+    /// Normalized terrain position of the `-X -Z` corner and extent.
+    pub fn uv_rect(&self) -> (Vec2, f32) {
+        let extent = 1.0 / (1u32 << self.lod) as f32;
+        (Vec2::new(self.x as f32, self.y as f32) * extent, extent)
+    }
+
+    fn children(&self) -> [TerrainChunk; 4] {
+        [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| TerrainChunk {
+            lod: self.lod + 1,
+            x: self.x * 2 + dx,
+            y: self.y * 2 + dy,
+        })
+    }
+
+    /// World space bounds of the chunk, its skirt excluded.
+    pub(crate) fn world_bounds(&self, terrain: &ExtractedTerrain, bounds: &HeightBounds) -> (Vec3, Vec3) {
+        let (offset, extent) = self.uv_rect();
+        let (min_height, max_height) = bounds.get(self.lod, self.x, self.y);
+        let size = terrain.terrain.size;
+        let local_min = Vec3::new(offset.x * size.x, min_height * terrain.terrain.height_scale, offset.y * size.y);
+        let local_max = Vec3::new(
+            (offset.x + extent) * size.x,
+            max_height * terrain.terrain.height_scale,
+            (offset.y + extent) * size.y,
+        );
+
+        (0..8)
+            .map(|corner| Vec3::select(
+                BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+                local_max,
+                local_min,
+            ))
+            .map(|corner| terrain.world_from_terrain.transform_point3(corner))
+            .fold((Vec3::MAX, Vec3::MIN), |(min, max), corner| (min.min(corner), max.max(corner)))
+    }
+}
+
+/// Select the chunks of a terrain seen by a camera, nodes closer than `lod_distance` times their extent are refined.
+pub fn select_terrain_chunks(
+    terrain: &ExtractedTerrain,
+    bounds: &HeightBounds,
+    camera_position: Vec3,
+    frustum: &Frustum,
+    chunks: &mut Vec<TerrainChunk>,
+) {
+    let max_lod = terrain.terrain.max_lod.min(MAX_TERRAIN_LOD);
+    let mut pending = vec![TerrainChunk { lod: 0, x: 0, y: 0 }];
+
+    while let Some(chunk) = pending.pop() {
+        let (min, max) = chunk.world_bounds(terrain, bounds);
+        if !frustum.intersects_aabb(&Aabb::new(min, max)) {
+            continue;
+        }
+
+        let distance = camera_position.clamp(min, max).distance(camera_position);
+        let extent = (max - min).x.max((max - min).z);
+        if chunk.lod < max_lod && distance < terrain.terrain.lod_distance * extent {
+            pending.extend(chunk.children());
+        } else {
+            chunks.push(chunk);
+        }
+    }
+}
+
+/// Vertex of the chunk grid, the grid position in xy and 1 in z on skirt vertices.
+pub type TerrainGridVertex = [f32; 3];
+
+/// Triangles of a chunk of [`TERRAIN_CHUNK_RESOLUTION`] quads surrounded by a skirt,
+/// which hides the cracks between neighbouring chunks of different levels.
+pub fn terrain_chunk_grid() -> (Vec<TerrainGridVertex>, Vec<u32>) {
+    let resolution = TERRAIN_CHUNK_RESOLUTION;
+    let row = resolution + 1;
+    let mut vertices = Vec::with_capacity((row * row + row * 4) as usize);
+    let mut indices = Vec::new();
+
+    for y in 0..row {
+        for x in 0..row {
+            vertices.push([x as f32 / resolution as f32, y as f32 / resolution as f32, 0.0]);
+        }
+    }
+    for y in 0..resolution {
+        for x in 0..resolution {
+            let corner = y * row + x;
+            indices.extend([corner, corner + row, corner + 1, corner + 1, corner + row, corner + row + 1]);
+        }
+    }
+
+    let edges = [
+        (0..row).collect::<Vec<_>>(),
+        (0..row).map(|x| resolution * row + x).collect(),
+        (0..row).map(|y| y * row).collect(),
+        (0..row).map(|y| y * row + resolution).collect(),
+    ];
+    for edge in edges {
+        let first_skirt = vertices.len() as u32;
+        let skirt_vertices = edge
+            .iter()
+            .map(|index| {
+                let [x, y, _] = vertices[*index as usize];
+                [x, y, 1.0]
+            })
+            .collect::<Vec<_>>();
+        vertices.extend(skirt_vertices);
+        for (i, window) in edge.windows(2).enumerate() {
+            let skirt = first_skirt + i as u32;
+            indices.extend([window[0], window[1], skirt + 1, window[0], skirt + 1, skirt]);
+        }
+    }
+
+    (vertices, indices)
+}
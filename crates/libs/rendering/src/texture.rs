@@ -0,0 +1,106 @@
+mod tga;
+
+pub use tga::*;
+
+use ash::vk;
+use anyhow::ensure;
+use bevy_app::{App, Plugin};
+use bevy_math::UVec2;
+use gpu_allocator::MemoryLocation;
+use avalanche_asset::{Asset, AssetApp};
+use avalanche_hlvk::ImageBarrier;
+use avalanche_scene::SceneApp;
+use crate::prelude::{Image, ImageView};
+use crate::render_asset::{RenderAsset, RenderAssetContext, RenderAssetPlugin};
+
+/// Single mip 2D image on the CPU, uploaded into a [`GpuTexture`].
+#[derive(Clone, Debug)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    /// Only formats with 4 bytes per texel are supported
+    pub format: vk::Format,
+    pub data: Vec<u8>,
+}
+
+impl Asset for Texture {}
+
+/// Sampled image of a [`Texture`], in `SHADER_READ_ONLY_OPTIMAL` layout.
+pub struct GpuTexture {
+    pub image: Image,
+    pub view: ImageView,
+    pub size: UVec2,
+}
+
+impl RenderAsset for Texture {
+    type ExtractedAsset = Texture;
+    type PreparedAsset = GpuTexture;
+
+    fn extract_asset(&self) -> Self::ExtractedAsset {
+        self.clone()
+    }
+
+    fn prepare_asset(texture: Self::ExtractedAsset, context: &mut RenderAssetContext) -> anyhow::Result<Self::PreparedAsset> {
+        ensure!(texture.width > 0 && texture.height > 0, "Texture is empty");
+        ensure!(
+            texture.data.len() == texture.width as usize * texture.height as usize * 4,
+            "Texture data doesn't match its {}x{} size", texture.width, texture.height,
+        );
+
+        let staging_buffer = context.context().create_buffer(
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            MemoryLocation::CpuToGpu,
+            texture.data.len() as _,
+        )?;
+        staging_buffer.copy_data_to_buffer(&texture.data)?;
+
+        let image = context.context().create_image(
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuOnly,
+            texture.format,
+            texture.width,
+            texture.height,
+        )?;
+
+        let command_buffer = context.command_buffer();
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &image,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            src_access_mask: vk::AccessFlags2::NONE,
+            dst_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::NONE,
+            dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+        }]);
+        command_buffer.copy_buffer_to_image(&staging_buffer, &image, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &image,
+            old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_READ,
+            src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+        context.keep_until_frame_end(staging_buffer);
+
+        let view = image.create_image_view()?;
+        Ok(GpuTexture {
+            image: image.into(),
+            view: view.into(),
+            size: UVec2::new(texture.width, texture.height),
+        })
+    }
+}
+
+/// Registers the [`Texture`] asset with its loaders.
+pub struct TexturePlugin;
+
+impl Plugin for TexturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Texture>()
+            .register_asset_loader(TgaLoader)
+            .register_scene_handle::<Texture>()
+            .add_plugins(RenderAssetPlugin::<Texture>::default());
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::Path;
+use anyhow::{bail, ensure};
+use ash::vk;
+use avalanche_asset::AssetLoader;
+use crate::texture::Texture;
+
+/// Loads true-color and grayscale TGA images, raw or run-length encoded, as sRGB RGBA8 [`Texture`]s.
+pub struct TgaLoader;
+
+impl AssetLoader for TgaLoader {
+    type Asset = Texture;
+
+    fn extensions(&self) -> &[&str] {
+        &["tga"]
+    }
+
+    fn load(&self, bytes: &[u8], _path: &Path) -> anyhow::Result<Texture> {
+        parse_tga(bytes)
+    }
+}
+
+const TGA_HEADER_SIZE: usize = 18;
+
+fn parse_tga(bytes: &[u8]) -> anyhow::Result<Texture> {
+    ensure!(bytes.len() >= TGA_HEADER_SIZE, "TGA header is truncated");
+    let id_length = bytes[0] as usize;
+    let color_map_type = bytes[1];
+    let image_type = bytes[2];
+    let color_map_length = u16::from_le_bytes([bytes[5], bytes[6]]) as usize;
+    let color_map_entry_bits = bytes[7] as usize;
+    let width = u16::from_le_bytes([bytes[12], bytes[13]]) as u32;
+    let height = u16::from_le_bytes([bytes[14], bytes[15]]) as u32;
+    let pixel_bits = bytes[16];
+    let top_to_bottom = bytes[17] & 0x20 != 0;
+
+    let (run_length_encoded, grayscale) = match image_type {
+        2 => (false, false),
+        3 => (false, true),
+        10 => (true, false),
+        11 => (true, true),
+        _ => bail!("unsupported TGA image type {image_type}, only true-color and grayscale images are supported"),
+    };
+    let pixel_size = match (grayscale, pixel_bits) {
+        (true, 8) => 1,
+        (false, 24) => 3,
+        (false, 32) => 4,
+        _ => bail!("unsupported TGA pixel depth of {pixel_bits} bits"),
+    };
+
+    let mut offset = TGA_HEADER_SIZE + id_length;
+    if color_map_type != 0 {
+        offset += color_map_length * color_map_entry_bits.div_ceil(8);
+    }
+    let pixel_count = width as usize * height as usize;
+    let pixels = decode_pixels(bytes.get(offset..).unwrap_or_default(), pixel_count, pixel_size, run_length_encoded)?;
+
+    let row_size = width as usize * 4;
+    let mut data = vec![0u8; pixel_count * 4];
+    for (index, pixel) in pixels.chunks_exact(pixel_size).enumerate() {
+        let (x, y) = (index % width as usize, index / width as usize);
+        let row = if top_to_bottom { y } else { height as usize - 1 - y };
+        let rgba = match *pixel {
+            [l] => [l, l, l, 255],
+            [b, g, r] => [r, g, b, 255],
+            [b, g, r, a] => [r, g, b, a],
+            _ => unreachable!(),
+        };
+        data[row * row_size + x * 4..][..4].copy_from_slice(&rgba);
+    }
+
+    Ok(Texture {
+        width,
+        height,
+        format: vk::Format::R8G8B8A8_SRGB,
+        data,
+    })
+}
+
+fn decode_pixels(bytes: &[u8], pixel_count: usize, pixel_size: usize, run_length_encoded: bool) -> anyhow::Result<Vec<u8>> {
+    let size = pixel_count * pixel_size;
+    if !run_length_encoded {
+        ensure!(bytes.len() >= size, "TGA pixel data is truncated");
+        return Ok(bytes[..size].to_vec());
+    }
+
+    let mut pixels = Vec::with_capacity(size);
+    let mut bytes = bytes.iter().copied();
+    while pixels.len() < size {
+        let Some(packet) = bytes.next() else {
+            bail!("TGA pixel data is truncated");
+        };
+        let count = (packet & 0x7F) as usize + 1;
+        if packet & 0x80 != 0 {
+            let pixel = bytes.by_ref().take(pixel_size).collect::<Vec<_>>();
+            ensure!(pixel.len() == pixel_size, "TGA pixel data is truncated");
+            for _ in 0..count {
+                pixels.extend_from_slice(&pixel);
+            }
+        } else {
+            let before = pixels.len();
+            pixels.extend(bytes.by_ref().take(count * pixel_size));
+            ensure!(pixels.len() - before == count * pixel_size, "TGA pixel data is truncated");
+        }
+    }
+    pixels.truncate(size);
+    Ok(pixels)
+}
@@ -0,0 +1,260 @@
+mod node;
+mod pipeline;
+
+pub use node::*;
+pub use pipeline::*;
+
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Component, Entity, IntoSystemConfigs, Query, Res, ResMut, Resource};
+use bevy_log::{error, warn};
+use bevy_utils::{EntityHashMap, HashSet};
+use avalanche_asset::Handle;
+use avalanche_hlvk::{Context, DescriptorPool, DescriptorSet, WriteDescriptorSet, WriteDescriptorSetKind};
+use crate::{Render, RenderApp, RenderSet};
+use crate::auto_exposure::{prepare_auto_exposure_views, AutoExposureViews};
+use crate::camera::ExtractedCamera;
+use crate::color::holds_srgb_encoded;
+use crate::deferred::DEFERRED_GRAPH;
+use crate::extract::{ExtractComponent, ExtractComponentPlugin, FrameContext};
+use crate::graph::{RenderGraphApp, RenderLabel};
+use crate::graph::node::ViewNodeRunner;
+use crate::path_tracing::PATH_TRACING_GRAPH;
+use crate::prelude::window::ExtractedWindows;
+use crate::render_asset::RenderAssets;
+use crate::render_target::{RenderTargetImage, WindowTarget};
+use crate::texture::Texture;
+use crate::upscaling::UPSCALE_NODE;
+use crate::view::{UpscaledViewTarget, ViewExposure, ViewTarget};
+
+/// Node mapping the HDR color of a view to display range, see [`TonemappingNode`].
+pub const TONEMAPPING_NODE: &str = "tonemapping";
+
+/// Graphs rendering HDR color, sprites are drawn in display range already and skip tonemapping.
+pub const TONEMAPPED_GRAPHS: [RenderLabel; 2] = [PATH_TRACING_GRAPH, DEFERRED_GRAPH];
+
+/// Threads along each axis of a workgroup of the tonemapping shader.
+pub const TONEMAPPING_WORKGROUP_SIZE: u32 = 8;
+
+/// Adjustments of the final image of a camera, applied around the tonemapping of its views.
+///
+/// Cameras without it are tonemapped with the default settings, cameras of graphs outside of
+/// [`TONEMAPPED_GRAPHS`] ignore it.
+#[derive(Component, ExtractComponent, Clone, Debug)]
+pub struct ColorGrading {
+    /// Exposure compensation in stops, added to the [`ViewExposure`] of the view
+    pub exposure: f32,
+    /// Applied to the tonemapped color, values above 1 brighten the midtones
+    pub gamma: f32,
+    /// 0 is grayscale, 1 keeps the colors
+    pub saturation: f32,
+    /// Scales the distance of the tonemapped color to middle gray
+    pub contrast: f32,
+    /// 3D lookup table applied last, laid out as `size` slices of `size x size` texels side by side,
+    /// blue selecting the slice. Indexed by the sRGB encoded graded color.
+    pub lut: Option<Handle<Texture>>,
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            gamma: 1.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            lut: None,
+        }
+    }
+}
+
+pub struct TonemappingPlugin;
+
+impl Plugin for TonemappingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<ColorGrading>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<TonemappingPipeline>()
+                .init_resource::<TonemappingViews>()
+                .add_systems(
+                    Render, (
+                        prepare_tonemapping_pipeline.in_set(RenderSet::PrepareResources),
+                        prepare_tonemapping_views
+                            .after(prepare_auto_exposure_views)
+                            .in_set(RenderSet::PrepareBindGroups),
+                    )
+                );
+            for graph in TONEMAPPED_GRAPHS {
+                render_app
+                    .add_render_graph_node::<ViewNodeRunner<TonemappingNode>>(graph, TONEMAPPING_NODE)
+                    .add_render_graph_edge(graph, UPSCALE_NODE, TONEMAPPING_NODE);
+            }
+        }
+    }
+}
+
+/// Bindings and grading of a view, the set is kept across frames.
+pub struct TonemappingViewState {
+    pub push_constants: TonemappingPushConstants,
+    _descriptor_pool: DescriptorPool,
+    pub(crate) descriptor_set: DescriptorSet,
+}
+
+impl TonemappingViewState {
+    fn new(context: &Context, pipeline: &TonemappingPipelineResources) -> anyhow::Result<Self> {
+        let descriptor_pool = context.create_descriptor_pool(1, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+            },
+        ])?;
+        let descriptor_set = descriptor_pool.allocate_set(pipeline.descriptor_set_layout())?;
+
+        Ok(Self {
+            push_constants: TonemappingPushConstants::default(),
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct TonemappingViews(pub(crate) EntityHashMap<Entity, TonemappingViewState>);
+
+impl TonemappingViews {
+    pub fn get(&self, entity: Entity) -> Option<&TonemappingViewState> {
+        self.0.get(&entity)
+    }
+}
+
+type TonemappingViewQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static ExtractedCamera,
+        &'static ViewTarget,
+        Option<&'static UpscaledViewTarget>,
+        Option<&'static ViewExposure>,
+        Option<&'static ColorGrading>,
+        Option<&'static WindowTarget>,
+        Option<&'static RenderTargetImage>,
+    ),
+>;
+
+fn prepare_tonemapping_views(
+    mut views: ResMut<TonemappingViews>,
+    pipeline: Res<TonemappingPipeline>,
+    textures: Res<RenderAssets<Texture>>,
+    auto_exposure_views: Option<Res<AutoExposureViews>>,
+    windows: Res<ExtractedWindows>,
+    cameras: TonemappingViewQuery,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(pipeline) = pipeline.resources() else {
+        views.0.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+
+    for (entity, camera, target, upscaled_target, exposure, grading, window_target, render_target) in cameras.iter() {
+        if !TONEMAPPED_GRAPHS.contains(&camera.render_graph) {
+            continue;
+        }
+        alive.insert(entity);
+        if !views.0.contains_key(&entity) {
+            match TonemappingViewState::new(context, pipeline) {
+                Ok(state) => {
+                    views.0.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create tonemapping view resources: {err}");
+                    continue;
+                }
+            }
+        }
+        let state = views.0.get_mut(&entity).unwrap();
+
+        let default_grading = ColorGrading::default();
+        let grading = grading.unwrap_or(&default_grading);
+        let lut = grading
+            .lut
+            .as_ref()
+            .and_then(|handle| textures.get(handle.id()))
+            .filter(|lut| {
+                let valid = lut.size.x == lut.size.y * lut.size.y;
+                if !valid {
+                    warn!("Color grading LUT of {}x{} texels is not a strip of square slices", lut.size.x, lut.size.y);
+                }
+                valid
+            });
+        let (view, size) = match upscaled_target {
+            Some(upscaled) => (&upscaled.view, upscaled.size),
+            None => (&target.view, target.size),
+        };
+        state.push_constants = TonemappingPushConstants {
+            exposure: exposure.map_or(1.0, |exposure| exposure.0) * grading.exposure.exp2(),
+            gamma: grading.gamma.max(f32::EPSILON),
+            saturation: grading.saturation,
+            contrast: grading.contrast,
+            size: size.to_array(),
+            lut_size: lut.map_or(0, |lut| lut.size.y),
+            encode_srgb: u32::from(encodes_srgb(&windows, &textures, window_target, render_target)),
+        };
+
+        let adapted_exposure = auto_exposure_views
+            .as_ref()
+            .and_then(|views| views.get(entity))
+            .map_or(&pipeline.fallback_exposure, |auto_exposure| &auto_exposure.exposure);
+
+        // the targets are recreated on resize and reloaded LUTs replace their image, rewrite the set every frame
+        state.descriptor_set.update(&[
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::CombinedImageSampler {
+                    view: lut.map_or(&pipeline.fallback_lut_view, |lut| &lut.view),
+                    sampler: &pipeline.sampler,
+                    layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 2,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: adapted_exposure },
+            },
+        ]);
+    }
+
+    views.0.retain(|entity, _| alive.contains(entity));
+}
+
+/// Whether the target a view is copied into holds sRGB encoded values, which the tonemapping has to encode.
+fn encodes_srgb(
+    windows: &ExtractedWindows,
+    textures: &RenderAssets<Texture>,
+    window_target: Option<&WindowTarget>,
+    render_target: Option<&RenderTargetImage>,
+) -> bool {
+    let format = match (render_target, window_target) {
+        (Some(render_target), _) => textures.get(render_target.texture.id()).map(|texture| texture.image.format),
+        (None, Some(window_target)) => windows.get(&window_target.window).map(|window| window.swapchain.format),
+        (None, None) => None,
+    };
+    format.is_some_and(holds_srgb_encoded)
+}
@@ -0,0 +1,77 @@
+use ash::vk;
+use bevy_ecs::prelude::World;
+use avalanche_hlvk::ImageBarrier;
+use crate::extract::FrameContext;
+use crate::globals::Globals;
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::ViewNode;
+use crate::tonemapping::{TonemappingPipeline, TonemappingViews, TONEMAPPING_WORKGROUP_SIZE};
+use crate::view::{UpscaledViewTarget, ViewTarget};
+
+/// Applies the [`ColorGrading`](super::ColorGrading) of a view and tonemaps its final color target in place,
+/// the [`UpscaledViewTarget`] if the view has one.
+///
+/// The tonemapped color is linear, unless the view is copied into a window or texture which
+/// [`holds_srgb_encoded`](crate::color::holds_srgb_encoded) values, then it is sRGB encoded last.
+/// This is the only place the color is encoded, see [`SrgbPolicy`](crate::color::SrgbPolicy).
+#[derive(Default)]
+pub struct TonemappingNode;
+
+impl ViewNode for TonemappingNode {
+    type ViewQuery = (&'static ViewTarget, Option<&'static UpscaledViewTarget>);
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (target, upscaled_target): (&ViewTarget, Option<&UpscaledViewTarget>),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(pipeline) = world.resource::<TonemappingPipeline>().resources() else {
+            return Ok(());
+        };
+        let Some(state) = world.resource::<TonemappingViews>().get(graph.view_entity()) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.layout);
+        let image = upscaled_target.map_or(&target.image, |upscaled| &upscaled.image);
+
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::MEMORY_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+        }]);
+        command_buffer.bind_compute_pipeline(&pipeline.pipeline);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::COMPUTE, &pipeline.layout, 0, &[&state.descriptor_set]);
+        command_buffer.push_constants(
+            &pipeline.layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            bytemuck::bytes_of(&state.push_constants),
+        );
+        let [width, height] = state.push_constants.size;
+        command_buffer.dispatch(
+            width.div_ceil(TONEMAPPING_WORKGROUP_SIZE),
+            height.div_ceil(TONEMAPPING_WORKGROUP_SIZE),
+            1,
+        );
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            dst_access_mask: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::prelude::{Query, Res, ResMut, Resource};
+use bevy_log::error;
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{
+    Buffer, ComputePipeline, DescriptorSetLayout, Image, ImageBarrier, ImageView, PipelineLayout, Sampler,
+};
+use crate::auto_exposure::GpuExposure;
+use crate::camera::ExtractedCamera;
+use crate::extract::FrameContext;
+use crate::globals;
+use crate::shader::ShaderDirectory;
+use crate::tonemapping::TONEMAPPED_GRAPHS;
+
+pub(crate) const TONEMAPPING_SHADER: &str = "tonemapping/tonemapping.comp";
+
+/// Matches `TonemappingPushConstants` in `shaders/tonemapping/tonemapping.comp`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TonemappingPushConstants {
+    /// Linear scale of the HDR color, the [`ViewExposure`](crate::view::ViewExposure) with the grading compensation
+    pub exposure: f32,
+    pub gamma: f32,
+    pub saturation: f32,
+    pub contrast: f32,
+    pub size: [u32; 2],
+    /// Texels along each axis of the bound LUT, 0 when no LUT is applied
+    pub lut_size: u32,
+    /// 1 to sRGB encode the tonemapped color, for views copied into a `_UNORM` target
+    pub encode_srgb: u32,
+}
+
+// SAFETY: plain 32 bit fields without implicit padding
+unsafe impl Zeroable for TonemappingPushConstants {}
+unsafe impl Pod for TonemappingPushConstants {}
+
+pub struct TonemappingPipelineResources {
+    /// Reflected from the tonemapping shader, set 0 binds the target, the LUT and the adapted exposure of a view
+    pub layout: PipelineLayout,
+    pub pipeline: ComputePipeline,
+    /// Interpolates the texels of a LUT slice
+    pub sampler: Sampler,
+    _fallback_lut_image: Image,
+    /// Bound while a view has no LUT, never read
+    pub(crate) fallback_lut_view: ImageView,
+    /// [`GpuExposure`] of 1 bound while a view has no [`AutoExposure`](crate::auto_exposure::AutoExposure)
+    pub(crate) fallback_exposure: Buffer,
+}
+
+impl TonemappingPipelineResources {
+    #[inline]
+    pub fn descriptor_set_layout(&self) -> &DescriptorSetLayout {
+        &self.layout.descriptor_set_layouts()[0]
+    }
+}
+
+/// The tonemapping pipeline, created the first time a camera uses one of the [`TONEMAPPED_GRAPHS`].
+#[derive(Resource, Default)]
+pub enum TonemappingPipeline {
+    #[default]
+    Uninitialized,
+    Ready(Box<TonemappingPipelineResources>),
+    /// Creation failed, usually shaders are missing.
+    Failed,
+}
+
+impl TonemappingPipeline {
+    pub fn resources(&self) -> Option<&TonemappingPipelineResources> {
+        match self {
+            TonemappingPipeline::Ready(resources) => Some(resources),
+            _ => None,
+        }
+    }
+}
+
+fn create_resources(frame_context: &FrameContext, shaders: &ShaderDirectory) -> anyhow::Result<TonemappingPipelineResources> {
+    let context = frame_context.render_context();
+    let shader = shaders.load(context, TONEMAPPING_SHADER, vk::ShaderStageFlags::COMPUTE)?;
+    let layout = globals::pipeline_layout(std::slice::from_ref(&shader))?;
+    anyhow::ensure!(globals::pass_set_count(std::slice::from_ref(&shader)) == 1, "tonemapping shader must only use set 0");
+    let pipeline = context.create_compute_pipeline(&layout, &shader)?;
+
+    let sampler = context.create_sampler(
+        &vk::SamplerCreateInfo::builder()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+    )?;
+    let fallback_lut_image = context.create_image(
+        vk::ImageUsageFlags::SAMPLED,
+        MemoryLocation::GpuOnly,
+        vk::Format::R8G8B8A8_UNORM,
+        1,
+        1,
+    )?;
+    let fallback_lut_view = fallback_lut_image.create_image_view()?;
+    let fallback_exposure = context.create_buffer(
+        vk::BufferUsageFlags::STORAGE_BUFFER,
+        MemoryLocation::CpuToGpu,
+        std::mem::size_of::<GpuExposure>() as _,
+    )?;
+    fallback_exposure.copy_data_to_buffer(&[GpuExposure::default()])?;
+    if let Some(command_buffer) = frame_context.command_buffer(0) {
+        command_buffer.pipeline_image_barriers(&[ImageBarrier {
+            image: &fallback_lut_image,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            src_access_mask: vk::AccessFlags2::NONE,
+            dst_access_mask: vk::AccessFlags2::SHADER_READ,
+            src_stage_mask: vk::PipelineStageFlags2::NONE,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+    }
+
+    Ok(TonemappingPipelineResources {
+        layout,
+        pipeline,
+        sampler,
+        _fallback_lut_image: fallback_lut_image,
+        fallback_lut_view,
+        fallback_exposure,
+    })
+}
+
+pub(crate) fn prepare_tonemapping_pipeline(
+    mut pipeline: ResMut<TonemappingPipeline>,
+    shaders: Res<ShaderDirectory>,
+    cameras: Query<&ExtractedCamera>,
+    frame_context: Res<FrameContext>,
+) {
+    if !matches!(*pipeline, TonemappingPipeline::Uninitialized)
+        || !cameras.iter().any(|camera| TONEMAPPED_GRAPHS.contains(&camera.render_graph)) {
+        return;
+    }
+
+    *pipeline = match create_resources(&frame_context, &shaders) {
+        Ok(resources) => TonemappingPipeline::Ready(Box::new(resources)),
+        Err(err) => {
+            error!("Failed to create tonemapping pipeline: {err}");
+            TonemappingPipeline::Failed
+        }
+    };
+}
@@ -0,0 +1,86 @@
+use ash::vk;
+use bevy_ecs::prelude::{Res, ResMut, Resource};
+use bevy_log::error;
+use avalanche_hlvk::{Context, DescriptorPool, DescriptorSet, DescriptorSetLayout};
+use crate::extract::FrameContext;
+use crate::INIT_COMMAND_POOL_NUM;
+
+/// Sets a pool of the [`TransientDescriptorAllocator`] holds before another one is created.
+const TRANSIENT_POOL_MAX_SETS: u32 = 256;
+
+const TRANSIENT_POOL_SIZES: [vk::DescriptorPoolSize; 5] = [
+    vk::DescriptorPoolSize { ty: vk::DescriptorType::UNIFORM_BUFFER, descriptor_count: 512 },
+    vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_BUFFER, descriptor_count: 512 },
+    vk::DescriptorPoolSize { ty: vk::DescriptorType::STORAGE_IMAGE, descriptor_count: 256 },
+    vk::DescriptorPoolSize { ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER, descriptor_count: 512 },
+    vk::DescriptorPoolSize { ty: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR, descriptor_count: 16 },
+];
+
+#[derive(Default)]
+struct TransientDescriptorPools {
+    pools: Vec<DescriptorPool>,
+    /// Pool sets are allocated from, the previous ones are full
+    active: usize,
+}
+
+/// Descriptor sets living for a single frame, lives in the render world.
+///
+/// Each frame in flight owns its pools, which are reset at once when the frame starts again,
+/// so bind groups built every frame during [`RenderSet::PrepareBindGroups`](crate::RenderSet::PrepareBindGroups)
+/// neither leak nor grow the pools. Sets of a frame must not be used after it.
+#[derive(Resource, Default)]
+pub struct TransientDescriptorAllocator {
+    frames: [TransientDescriptorPools; INIT_COMMAND_POOL_NUM],
+    current: usize,
+}
+
+impl TransientDescriptorAllocator {
+    /// Allocate a set for the current frame, another pool is created when the current ones are full.
+    pub fn allocate_set(&mut self, context: &Context, layout: &DescriptorSetLayout) -> anyhow::Result<DescriptorSet> {
+        let frame = &mut self.frames[self.current];
+        while let Some(pool) = frame.pools.get(frame.active) {
+            match pool.allocate_set(layout) {
+                Ok(set) => return Ok(set),
+                Err(err) if is_pool_exhausted(&err) => frame.active += 1,
+                Err(err) => return Err(err),
+            }
+        }
+        let pool = context.create_descriptor_pool(TRANSIENT_POOL_MAX_SETS, &TRANSIENT_POOL_SIZES)?;
+        let set = pool.allocate_set(layout)?;
+        frame.pools.push(pool);
+        Ok(set)
+    }
+
+    /// Number of pools of the current frame.
+    pub fn pool_count(&self) -> usize {
+        self.frames[self.current].pools.len()
+    }
+
+    fn reset(&mut self, frame: usize) -> anyhow::Result<()> {
+        self.current = frame % INIT_COMMAND_POOL_NUM;
+        let frame = &mut self.frames[self.current];
+        frame.active = 0;
+        for pool in &frame.pools {
+            pool.reset()?;
+        }
+        Ok(())
+    }
+}
+
+fn is_pool_exhausted(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<vk::Result>(),
+        Some(&vk::Result::ERROR_OUT_OF_POOL_MEMORY | &vk::Result::ERROR_FRAGMENTED_POOL)
+    )
+}
+
+/// The previous frame using the pools waited for its fence when released.
+pub(crate) fn reset_transient_descriptor_pools(
+    mut allocator: ResMut<TransientDescriptorAllocator>,
+    frame_context: Res<FrameContext>,
+) {
+    let frame = frame_context.current_frame();
+    if let Err(err) = allocator.reset(frame) {
+        error!("Failed to reset the transient descriptor pools of frame {frame}: {err}");
+    }
+}
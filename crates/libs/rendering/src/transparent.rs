@@ -0,0 +1,363 @@
+mod node;
+mod pipeline;
+
+pub use node::*;
+pub use pipeline::*;
+
+use std::cmp::Reverse;
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Commands, Component, Entity, IntoSystemConfigs, Query, ReflectComponent, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_math::{Mat4, UVec2, Vec3, Vec4};
+use bevy_reflect::Reflect;
+use bevy_utils::{EntityHashMap, FloatOrd, HashSet};
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{
+    Buffer, Context, DescriptorPool, DescriptorSet, Image, ImageView, WriteDescriptorSet, WriteDescriptorSetKind,
+};
+use crate::{Render, RenderApp, RenderSet};
+use crate::camera::ExtractedCamera;
+use crate::deferred::{prepare_deferred_views, DeferredViews, DEFERRED_GRAPH, DEFERRED_SKYBOX_NODE};
+use crate::extract::{ExtractComponent, ExtractComponentPlugin, FrameContext};
+use crate::graph::RenderGraphApp;
+use crate::graph::node::ViewNodeRunner;
+use crate::lod::ViewLodMeshes;
+use crate::mesh::MeshMaterialFlags;
+use crate::raytracing::{RayTracingGpuScene, RayTracingScene};
+use crate::render_phase::{sort_phase_system, PhaseItem, RenderPhase};
+use crate::skinning::SkinPalettes;
+use crate::spatial::{Frustum, SceneBvh};
+use crate::specialized_pipeline::SpecializedPipelines;
+use crate::upscaling::UPSCALE_NODE;
+use crate::view::ViewTarget;
+
+pub const TRANSPARENT_NODE: &str = "transparent_pass";
+pub const TRANSPARENT_OIT_NODE: &str = "transparent_oit_pass";
+
+/// Weighted color sum of the transparent surfaces of a pixel in rgb, weighted alpha sum in alpha.
+pub const OIT_ACCUMULATION_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// Product of `1 - alpha` of the transparent surfaces of a pixel.
+pub const OIT_REVEALAGE_FORMAT: vk::Format = vk::Format::R16_SFLOAT;
+
+/// Draws the instances with a translucent [`RayTracingMaterial`](crate::raytracing::RayTracingMaterial)
+/// over cameras rendered with the [`DEFERRED_GRAPH`].
+///
+/// Translucent instances are left out of the G-buffer and of its shadow rays, see [`OPAQUE_INSTANCE_MASK`](crate::raytracing::OPAQUE_INSTANCE_MASK).
+pub struct TransparentPlugin;
+
+impl Plugin for TransparentPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<OrderIndependentTransparency>()
+            .add_plugins(ExtractComponentPlugin::<OrderIndependentTransparency>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<TransparentPipeline>()
+                .init_resource::<SpecializedPipelines<TransparentPipelineKey>>()
+                .init_resource::<TransparentViews>()
+                .add_systems(
+                    Render, (
+                        queue_transparent_meshes.in_set(RenderSet::Queue),
+                        sort_phase_system::<Transparent3d>.in_set(RenderSet::PhaseSort),
+                        (prepare_transparent_pipeline, specialize_transparent_pipelines)
+                            .chain()
+                            .in_set(RenderSet::PrepareResources),
+                        prepare_transparent_views
+                            .after(prepare_deferred_views)
+                            .in_set(RenderSet::PrepareBindGroups),
+                    )
+                )
+                .add_render_graph_node::<ViewNodeRunner<TransparentNode>>(DEFERRED_GRAPH, TRANSPARENT_NODE)
+                .add_render_graph_node::<ViewNodeRunner<OitNode>>(DEFERRED_GRAPH, TRANSPARENT_OIT_NODE)
+                .add_render_graph_edges(
+                    DEFERRED_GRAPH,
+                    &[DEFERRED_SKYBOX_NODE, TRANSPARENT_NODE, TRANSPARENT_OIT_NODE, UPSCALE_NODE],
+                );
+        }
+    }
+}
+
+/// Blend the transparent surfaces of a camera with weighted blended order independent transparency
+/// instead of drawing them sorted back to front.
+///
+/// Cheaper for scenes with many overlapping transparent surfaces, at the cost of an approximate result.
+#[derive(Component, ExtractComponent, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct OrderIndependentTransparency;
+
+/// A transparent instance seen by a view.
+pub struct Transparent3d {
+    pub entity: Entity,
+    /// Distance of the instance origin in front of the camera
+    pub view_depth: f32,
+    pub pipeline: TransparentPipelineKey,
+}
+
+impl PhaseItem for Transparent3d {
+    /// Back to front
+    type SortKey = Reverse<FloatOrd>;
+
+    #[inline]
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    #[inline]
+    fn sort_key(&self) -> Self::SortKey {
+        Reverse(FloatOrd(self.view_depth))
+    }
+}
+
+/// Expand a row-major 3x4 instance transform.
+pub(crate) fn world_from_object(transform: &[f32; 12]) -> Mat4 {
+    Mat4::from_cols(
+        Vec4::new(transform[0], transform[4], transform[8], 0.0),
+        Vec4::new(transform[1], transform[5], transform[9], 0.0),
+        Vec4::new(transform[2], transform[6], transform[10], 0.0),
+        Vec4::new(transform[3], transform[7], transform[11], 1.0),
+    )
+}
+
+fn queue_transparent_meshes(
+    mut commands: Commands,
+    scene: Res<RayTracingScene>,
+    gpu_scene: Res<RayTracingGpuScene>,
+    palettes: Res<SkinPalettes>,
+    bvh: Res<SceneBvh>,
+    cameras: Query<(Entity, &ExtractedCamera, Option<&OrderIndependentTransparency>, Option<&ViewLodMeshes>)>,
+) {
+    for (entity, camera, oit, lods) in cameras.iter() {
+        if camera.render_graph != DEFERRED_GRAPH {
+            continue;
+        }
+
+        let view_from_world = camera.world_from_view.inverse();
+        let frustum = Frustum::from_view_projection(&(camera.projection * view_from_world));
+        let mut phase = RenderPhase::<Transparent3d>::default();
+        for (instance_entity, instance) in scene.iter() {
+            if !bvh.may_be_visible(instance_entity, &frustum) {
+                continue;
+            }
+            let Some((mesh, material)) = gpu_scene.geometry(instance_entity) else {
+                continue;
+            };
+            let mesh = lods.map_or(mesh, |lods| lods.mesh(instance_entity, mesh));
+            let material = MeshMaterialFlags::from_material(material);
+            if !material.contains(MeshMaterialFlags::ALPHA_BLEND) {
+                continue;
+            }
+
+            let origin = Vec3::new(instance.transform[3], instance.transform[7], instance.transform[11]);
+            phase.add(Transparent3d {
+                entity: instance_entity,
+                view_depth: -view_from_world.transform_point3(origin).z,
+                pipeline: TransparentPipelineKey::new(palettes.vertex_layout(instance_entity, mesh), material, oit.is_some()),
+            });
+        }
+        commands.entity(entity).insert(phase);
+    }
+}
+
+/// Matches `TransparentUniform` in `shaders/transparent/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TransparentUniform {
+    clip_from_world: [f32; 16],
+    camera_position: [f32; 4],
+}
+
+/// A color attachment of the [`OitTargets`].
+pub struct OitTarget {
+    pub image: Image,
+    pub view: ImageView,
+}
+
+impl OitTarget {
+    fn new(context: &Context, format: vk::Format, size: UVec2) -> anyhow::Result<Self> {
+        let image = context.create_image(
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::STORAGE,
+            MemoryLocation::GpuOnly,
+            format,
+            size.x,
+            size.y,
+        )?;
+        let view = image.create_image_view()?;
+        Ok(Self { image, view })
+    }
+}
+
+/// Accumulation targets of a camera with [`OrderIndependentTransparency`].
+pub struct OitTargets {
+    pub accumulation: OitTarget,
+    pub revealage: OitTarget,
+}
+
+/// Bindings of a camera drawing transparent surfaces, kept across frames.
+pub struct TransparentViewState {
+    pub oit: Option<OitTargets>,
+    uniform_buffer: Buffer,
+    _descriptor_pool: DescriptorPool,
+    pub(crate) descriptor_set: DescriptorSet,
+    size: UVec2,
+}
+
+impl TransparentViewState {
+    fn new(context: &Context, pipeline: &TransparentPipelineResources, size: UVec2, oit: bool) -> anyhow::Result<Self> {
+        let uniform_buffer = context.create_buffer(
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryLocation::CpuToGpu,
+            std::mem::size_of::<TransparentUniform>() as _,
+        )?;
+        let descriptor_pool = context.create_descriptor_pool(1, &[
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: 3,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: 1,
+            },
+        ])?;
+        let descriptor_set = descriptor_pool.allocate_set(pipeline.descriptor_set_layout())?;
+
+        let oit = match oit {
+            true => Some(OitTargets {
+                accumulation: OitTarget::new(context, OIT_ACCUMULATION_FORMAT, size)?,
+                revealage: OitTarget::new(context, OIT_REVEALAGE_FORMAT, size)?,
+            }),
+            false => None,
+        };
+
+        Ok(Self {
+            oit,
+            uniform_buffer,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            size,
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct TransparentViews(pub(crate) EntityHashMap<Entity, TransparentViewState>);
+
+impl TransparentViews {
+    pub fn get(&self, entity: Entity) -> Option<&TransparentViewState> {
+        self.0.get(&entity)
+    }
+}
+
+type TransparentViewQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static ExtractedCamera,
+        &'static ViewTarget,
+        &'static RenderPhase<Transparent3d>,
+        Option<&'static OrderIndependentTransparency>,
+    ),
+>;
+
+fn prepare_transparent_views(
+    mut views: ResMut<TransparentViews>,
+    pipeline: Res<TransparentPipeline>,
+    deferred_views: Res<DeferredViews>,
+    palettes: Res<SkinPalettes>,
+    cameras: TransparentViewQuery,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(pipeline) = pipeline.resources() else {
+        views.0.clear();
+        return;
+    };
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+
+    for (entity, camera, target, phase, oit) in cameras.iter() {
+        let Some(deferred) = deferred_views.get(entity) else {
+            continue;
+        };
+        if phase.items.is_empty() {
+            continue;
+        }
+        alive.insert(entity);
+
+        let up_to_date = matches!(
+            views.0.get(&entity),
+            Some(state) if state.size == target.size && state.oit.is_some() == oit.is_some()
+        );
+        if !up_to_date {
+            match TransparentViewState::new(context, pipeline, target.size, oit.is_some()) {
+                Ok(state) => {
+                    views.0.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create transparent view resources: {err}");
+                    views.0.remove(&entity);
+                    continue;
+                }
+            }
+        }
+        let state = views.0.get_mut(&entity).unwrap();
+
+        let uniform = TransparentUniform {
+            clip_from_world: (camera.projection * camera.world_from_view.inverse()).to_cols_array(),
+            camera_position: camera.world_from_view.w_axis.to_array(),
+        };
+        if let Err(err) = state.uniform_buffer.copy_data_to_buffer(std::slice::from_ref(&uniform)) {
+            error!("Failed to upload transparent uniform: {err}");
+        }
+
+        // the G-buffer is recreated with the view target, rewrite the set every frame
+        let mut writes = vec![
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::UniformBuffer {
+                    buffer: &state.uniform_buffer,
+                },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageImage {
+                    view: &deferred.normal.view,
+                    layout: vk::ImageLayout::GENERAL,
+                },
+            },
+        ];
+        if let Some(palette) = palettes.buffer() {
+            writes.push(WriteDescriptorSet {
+                binding: 4,
+                kind: WriteDescriptorSetKind::StorageBuffer {
+                    buffer: palette,
+                },
+            });
+        }
+        if let Some(oit) = &state.oit {
+            writes.extend([
+                WriteDescriptorSet {
+                    binding: 2,
+                    kind: WriteDescriptorSetKind::StorageImage {
+                        view: &oit.accumulation.view,
+                        layout: vk::ImageLayout::GENERAL,
+                    },
+                },
+                WriteDescriptorSet {
+                    binding: 3,
+                    kind: WriteDescriptorSetKind::StorageImage {
+                        view: &oit.revealage.view,
+                        layout: vk::ImageLayout::GENERAL,
+                    },
+                },
+            ]);
+        }
+        state.descriptor_set.update(&writes);
+    }
+
+    views.0.retain(|entity, _| alive.contains(entity));
+}
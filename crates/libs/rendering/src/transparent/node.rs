@@ -0,0 +1,292 @@
+use ash::vk;
+use bevy_ecs::prelude::World;
+use avalanche_hlvk::{CommandBuffer, Image, ImageBarrier, PipelineLayout, RenderingAttachment};
+use crate::deferred::DeferredViews;
+use crate::extract::FrameContext;
+use crate::globals::Globals;
+use crate::lod::ViewLodMeshes;
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::prelude::node::ViewNode;
+use crate::raytracing::{RayTracingGpuScene, RayTracingScene};
+use crate::render_phase::{PhaseItem, RenderPhase};
+use crate::skinning::SkinPalettes;
+use crate::specialized_pipeline::SpecializedPipelines;
+use crate::transparent::{
+    world_from_object, OrderIndependentTransparency, Transparent3d, TransparentPipeline, TransparentPipelineKey,
+    TransparentPushConstants, TransparentViews,
+};
+use crate::view::ViewTarget;
+
+/// Draw the items of a phase, binding the variant of an item only when it differs from the previous one.
+fn draw_phase(
+    command_buffer: &CommandBuffer,
+    layout: &PipelineLayout,
+    phase: &RenderPhase<Transparent3d>,
+    lods: Option<&ViewLodMeshes>,
+    world: &World,
+) {
+    let scene = world.resource::<RayTracingScene>();
+    let gpu_scene = world.resource::<RayTracingGpuScene>();
+    let pipelines = world.resource::<SpecializedPipelines<TransparentPipelineKey>>();
+    let palettes = world.resource::<SkinPalettes>();
+    let mut bound = None;
+
+    for item in &phase.items {
+        let (Some(instance), Some((mesh, material))) = (scene.get(item.entity()), gpu_scene.geometry(item.entity())) else {
+            continue;
+        };
+        let mesh = lods.map_or(mesh, |lods| lods.mesh(item.entity(), mesh));
+        // skinned variants need the joint influences and the pose the item was queued with
+        let skin = match item.pipeline.mesh.vertex_layout.is_skinned() {
+            true => match (&mesh.skin_buffer, palettes.offset(item.entity())) {
+                (Some(skin_buffer), Some(joint_offset)) => Some((skin_buffer, joint_offset)),
+                _ => continue,
+            },
+            false => None,
+        };
+        if bound != Some(item.pipeline) {
+            let Some(pipeline) = pipelines.get(&item.pipeline) else {
+                continue;
+            };
+            command_buffer.bind_raster_pipeline(pipeline);
+            bound = Some(item.pipeline);
+        }
+
+        let [r, g, b] = material.emissive;
+        let push_constants = TransparentPushConstants {
+            world_from_object: world_from_object(&instance.transform).to_cols_array(),
+            base_color: material.base_color.to_linear_rgba(),
+            emissive: [r, g, b, 0.0],
+            joint_offset: skin.map_or(0, |(_, joint_offset)| joint_offset),
+        };
+        command_buffer.push_constants(
+            layout,
+            vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+            0,
+            bytemuck::bytes_of(&push_constants),
+        );
+
+        match skin {
+            Some((skin_buffer, _)) => command_buffer.bind_vertex_buffers(0, &[&mesh.vertex_buffer, skin_buffer]),
+            None => command_buffer.bind_vertex_buffer(&mesh.vertex_buffer),
+        }
+        match &mesh.index_buffer {
+            Some(index_buffer) => {
+                command_buffer.bind_index_buffer(index_buffer, vk::IndexType::UINT32);
+                command_buffer.draw_indexed(mesh.index_count);
+            }
+            None => command_buffer.draw(mesh.vertex_count),
+        }
+    }
+}
+
+/// Barriers making the G-buffer distance and the view target shaded by compute passes available to the raster passes.
+fn begin_barriers<'a>(target: &'a ViewTarget, normal: &'a Image) -> [ImageBarrier<'a>; 2] {
+    [
+        ImageBarrier {
+            image: normal,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+        },
+        ImageBarrier {
+            image: &target.image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        },
+    ]
+}
+
+/// Later passes expect the view target written by shaders.
+fn end_barrier(target: &ViewTarget) -> ImageBarrier<'_> {
+    ImageBarrier {
+        image: &target.image,
+        old_layout: vk::ImageLayout::GENERAL,
+        new_layout: vk::ImageLayout::GENERAL,
+        src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+        dst_access_mask: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+        src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+    }
+}
+
+fn view_extent(target: &ViewTarget) -> vk::Extent2D {
+    vk::Extent2D {
+        width: target.size.x,
+        height: target.size.y,
+    }
+}
+
+/// Alpha blends the sorted [`Transparent3d`] items of a view over its [`ViewTarget`].
+///
+/// Skipped for views with [`OrderIndependentTransparency`], drawn by the [`OitNode`] instead.
+#[derive(Default)]
+pub struct TransparentNode;
+
+impl ViewNode for TransparentNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static RenderPhase<Transparent3d>,
+        Option<&'static OrderIndependentTransparency>,
+    );
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (target, phase, oit): (&ViewTarget, &RenderPhase<Transparent3d>, Option<&OrderIndependentTransparency>),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        if oit.is_some() || phase.items.is_empty() {
+            return Ok(());
+        }
+        let Some(pipeline) = world.resource::<TransparentPipeline>().resources() else {
+            return Ok(());
+        };
+        let (Some(state), Some(deferred)) = (
+            world.resource::<TransparentViews>().get(graph.view_entity()),
+            world.resource::<DeferredViews>().get(graph.view_entity()),
+        ) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, &pipeline.layout);
+
+        let extent = view_extent(target);
+        command_buffer.pipeline_image_barriers(&begin_barriers(target, &deferred.normal.image));
+        command_buffer.begin_rendering_attachments(
+            &[RenderingAttachment {
+                view: &target.view,
+                layout: vk::ImageLayout::GENERAL,
+                load_op: vk::AttachmentLoadOp::LOAD,
+                clear_color: [0.0; 4],
+            }],
+            extent,
+        );
+        command_buffer.set_viewport(extent);
+        command_buffer.set_scissor(extent);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, &pipeline.layout, 0, &[&state.descriptor_set]);
+        draw_phase(command_buffer, &pipeline.layout, phase, world.get(graph.view_entity()), world);
+        command_buffer.end_rendering();
+        command_buffer.pipeline_image_barriers(&[end_barrier(target)]);
+
+        Ok(())
+    }
+}
+
+/// Draws the [`Transparent3d`] items of a view with [`OrderIndependentTransparency`] in any order
+/// into its [`OitTargets`](super::OitTargets), then composites them over its [`ViewTarget`].
+#[derive(Default)]
+pub struct OitNode;
+
+impl ViewNode for OitNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static RenderPhase<Transparent3d>,
+        &'static OrderIndependentTransparency,
+    );
+
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (target, phase, _): (&ViewTarget, &RenderPhase<Transparent3d>, &OrderIndependentTransparency),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        if phase.items.is_empty() {
+            return Ok(());
+        }
+        let Some(pipeline) = world.resource::<TransparentPipeline>().resources() else {
+            return Ok(());
+        };
+        let (Some(state), Some(deferred)) = (
+            world.resource::<TransparentViews>().get(graph.view_entity()),
+            world.resource::<DeferredViews>().get(graph.view_entity()),
+        ) else {
+            return Ok(());
+        };
+        let Some(oit) = &state.oit else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        world.resource::<Globals>().bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, &pipeline.layout);
+
+        let extent = view_extent(target);
+        let [normal_barrier, target_barrier] = begin_barriers(target, &deferred.normal.image);
+        // the accumulation targets are cleared every frame
+        let [accumulation_barrier, revealage_barrier] = [&oit.accumulation, &oit.revealage].map(|attachment| ImageBarrier {
+            image: &attachment.image,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            dst_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        });
+        command_buffer.pipeline_image_barriers(&[normal_barrier, accumulation_barrier, revealage_barrier]);
+
+        command_buffer.begin_rendering_attachments(
+            &[
+                RenderingAttachment {
+                    view: &oit.accumulation.view,
+                    layout: vk::ImageLayout::GENERAL,
+                    load_op: vk::AttachmentLoadOp::CLEAR,
+                    clear_color: [0.0; 4],
+                },
+                RenderingAttachment {
+                    view: &oit.revealage.view,
+                    layout: vk::ImageLayout::GENERAL,
+                    load_op: vk::AttachmentLoadOp::CLEAR,
+                    clear_color: [1.0; 4],
+                },
+            ],
+            extent,
+        );
+        command_buffer.set_viewport(extent);
+        command_buffer.set_scissor(extent);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, &pipeline.layout, 0, &[&state.descriptor_set]);
+        draw_phase(command_buffer, &pipeline.layout, phase, world.get(graph.view_entity()), world);
+        command_buffer.end_rendering();
+
+        let [accumulation_barrier, revealage_barrier] = [&oit.accumulation, &oit.revealage].map(|attachment| ImageBarrier {
+            image: &attachment.image,
+            old_layout: vk::ImageLayout::GENERAL,
+            new_layout: vk::ImageLayout::GENERAL,
+            src_access_mask: vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_STORAGE_READ,
+            src_stage_mask: vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+        });
+        command_buffer.pipeline_image_barriers(&[accumulation_barrier, revealage_barrier, target_barrier]);
+
+        command_buffer.begin_rendering_attachments(
+            &[RenderingAttachment {
+                view: &target.view,
+                layout: vk::ImageLayout::GENERAL,
+                load_op: vk::AttachmentLoadOp::LOAD,
+                clear_color: [0.0; 4],
+            }],
+            extent,
+        );
+        command_buffer.bind_raster_pipeline(&pipeline.oit_composite_pipeline);
+        command_buffer.set_viewport(extent);
+        command_buffer.set_scissor(extent);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::GRAPHICS, &pipeline.layout, 0, &[&state.descriptor_set]);
+        command_buffer.draw(3);
+        command_buffer.end_rendering();
+        command_buffer.pipeline_image_barriers(&[end_barrier(target)]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::prelude::{Query, Res, ResMut, Resource};
+use bevy_log::error;
+use avalanche_hlvk::{
+    BlendMode, Context, DescriptorSetLayout, PipelineLayout, RasterColorAttachment, RasterPipeline,
+    RasterPipelineCreateInfo, StagedShader, VertexStreamSet,
+};
+use crate::camera::ExtractedCamera;
+use crate::deferred::DEFERRED_GRAPH;
+use crate::extract::FrameContext;
+use crate::globals;
+use crate::mesh::{MeshMaterialFlags, MeshPipelineKey, MeshVertexLayout};
+use crate::render_phase::RenderPhase;
+use crate::shader::ShaderDirectory;
+use crate::specialized_pipeline::{SpecializedPipelineKey, SpecializedPipelines};
+use crate::transparent::{Transparent3d, OIT_ACCUMULATION_FORMAT, OIT_REVEALAGE_FORMAT};
+use crate::view::VIEW_TARGET_FORMAT;
+
+pub(crate) const MESH_VERTEX_SHADER: &str = "transparent/mesh.vert";
+pub(crate) const SKINNED_MESH_VERTEX_SHADER: &str = "transparent/skinned_mesh.vert";
+pub(crate) const BLEND_FRAGMENT_SHADER: &str = "transparent/blend.frag";
+pub(crate) const OIT_FRAGMENT_SHADER: &str = "transparent/oit.frag";
+pub(crate) const FULLSCREEN_VERTEX_SHADER: &str = "transparent/fullscreen.vert";
+pub(crate) const OIT_COMPOSITE_FRAGMENT_SHADER: &str = "transparent/oit_composite.frag";
+
+/// Matches `TransparentPushConstants` in `shaders/transparent/common.glsl`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TransparentPushConstants {
+    pub world_from_object: [f32; 16],
+    pub base_color: [f32; 4],
+    /// Emissive radiance in rgb, alpha is unused
+    pub emissive: [f32; 4],
+    /// First matrix of the instance in the [`SkinPalettes`](crate::skinning::SkinPalettes), skinned meshes only
+    pub joint_offset: u32,
+}
+
+// SAFETY: plain `f32` and `u32` fields without implicit padding
+unsafe impl Zeroable for TransparentPushConstants {}
+unsafe impl Pod for TransparentPushConstants {}
+
+/// Layout shared by the transparent pipelines, the mesh pipelines are [`TransparentPipelineKey`] variants.
+pub struct TransparentPipelineResources {
+    /// Reflected from the transparent shaders
+    pub layout: PipelineLayout,
+    /// Resolves the [`OitTargets`](super::OitTargets) over the view target with a fullscreen triangle
+    pub oit_composite_pipeline: RasterPipeline,
+}
+
+/// The transparent pipelines, created the first time a camera uses the deferred graph.
+#[derive(Resource, Default)]
+pub enum TransparentPipeline {
+    #[default]
+    Uninitialized,
+    Ready(Box<TransparentPipelineResources>),
+    /// Creation failed, usually shaders are missing.
+    Failed,
+}
+
+impl TransparentPipelineResources {
+    #[inline]
+    pub fn descriptor_set_layout(&self) -> &DescriptorSetLayout {
+        &self.layout.descriptor_set_layouts()[0]
+    }
+}
+
+impl TransparentPipeline {
+    pub fn resources(&self) -> Option<&TransparentPipelineResources> {
+        match self {
+            TransparentPipeline::Ready(resources) => Some(resources),
+            _ => None,
+        }
+    }
+}
+
+/// A mesh pipeline variant of the transparent passes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TransparentPipelineKey {
+    pub mesh: MeshPipelineKey,
+    /// Accumulate into the [`OitTargets`](super::OitTargets) instead of blending into the view target
+    pub oit: bool,
+}
+
+impl TransparentPipelineKey {
+    pub fn new(vertex_layout: MeshVertexLayout, material: MeshMaterialFlags, oit: bool) -> Self {
+        Self {
+            mesh: MeshPipelineKey {
+                vertex_layout,
+                material,
+                samples: vk::SampleCountFlags::TYPE_1,
+                target_format: VIEW_TARGET_FORMAT,
+            },
+            oit,
+        }
+    }
+}
+
+impl SpecializedPipelineKey for TransparentPipelineKey {
+    type Specializer = TransparentPipelineResources;
+
+    fn specialize(
+        &self,
+        context: &Context,
+        shaders: &ShaderDirectory,
+        specializer: &TransparentPipelineResources,
+    ) -> anyhow::Result<RasterPipeline> {
+        let vertex_shader = match self.mesh.vertex_layout.is_skinned() {
+            true => SKINNED_MESH_VERTEX_SHADER,
+            false => MESH_VERTEX_SHADER,
+        };
+        let vertex_shader = shaders.load(context, vertex_shader, vk::ShaderStageFlags::VERTEX)?;
+        self.mesh.vertex_layout.validate(&vertex_shader)?;
+
+        if !self.oit {
+            return create_pipeline(
+                context,
+                &specializer.layout,
+                &[
+                    vertex_shader,
+                    shaders.load(context, BLEND_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT)?,
+                ],
+                &self.mesh.vertex_layout.vertex_stream(),
+                &[RasterColorAttachment::new(self.mesh.target_format, BlendMode::AlphaBlend)],
+                self.mesh.samples,
+                self.mesh.cull_mode(),
+            );
+        }
+
+        // the revealage is multiplied by `1 - alpha` of every surface
+        let revealage_blend = vk::PipelineColorBlendAttachmentState::builder()
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::ZERO)
+            .dst_color_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_COLOR)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(vk::BlendOp::ADD)
+            .color_write_mask(vk::ColorComponentFlags::R)
+            .build();
+        create_pipeline(
+            context,
+            &specializer.layout,
+            &[
+                vertex_shader,
+                shaders.load(context, OIT_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT)?,
+            ],
+            &self.mesh.vertex_layout.vertex_stream(),
+            &[
+                RasterColorAttachment::new(OIT_ACCUMULATION_FORMAT, BlendMode::Additive),
+                RasterColorAttachment::new(OIT_REVEALAGE_FORMAT, BlendMode::Custom(revealage_blend)),
+            ],
+            self.mesh.samples,
+            self.mesh.cull_mode(),
+        )
+    }
+}
+
+fn create_pipeline(
+    context: &Context,
+    layout: &PipelineLayout,
+    shaders: &[StagedShader],
+    vertex_stream: &VertexStreamSet,
+    color_attachments: &[RasterColorAttachment],
+    samples: vk::SampleCountFlags,
+    cull_mode: vk::CullModeFlags,
+) -> anyhow::Result<RasterPipeline> {
+    context.create_graphics_pipeline(layout, RasterPipelineCreateInfo {
+        shaders,
+        primitive_topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        vertex_stream,
+        viewport: None,
+        scissor: None,
+        color_attachments,
+        depth_attachment: None,
+        dynamic_states: Some(&[vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR]),
+        polygon_mode: vk::PolygonMode::FILL,
+        front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+        samples,
+        cull_mode,
+    })
+}
+
+fn create_resources(context: &Context, shaders: &ShaderDirectory) -> anyhow::Result<TransparentPipelineResources> {
+    // the shaders of every transparent pipeline together declare the layout
+    let stages = [
+        (MESH_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX),
+        (SKINNED_MESH_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX),
+        (BLEND_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT),
+        (OIT_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT),
+        (FULLSCREEN_VERTEX_SHADER, vk::ShaderStageFlags::VERTEX),
+        (OIT_COMPOSITE_FRAGMENT_SHADER, vk::ShaderStageFlags::FRAGMENT),
+    ];
+    let stages = stages
+        .into_iter()
+        .map(|(path, stage)| shaders.load(context, path, stage))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let layout = globals::pipeline_layout(&stages)?;
+    anyhow::ensure!(globals::pass_set_count(&stages) == 1, "transparent shaders must only use set 0");
+
+    let oit_composite_pipeline = create_pipeline(
+        context,
+        &layout,
+        &stages[4..],
+        &VertexStreamSet::empty(),
+        &[RasterColorAttachment::new(VIEW_TARGET_FORMAT, BlendMode::AlphaBlend)],
+        vk::SampleCountFlags::TYPE_1,
+        vk::CullModeFlags::NONE,
+    )?;
+
+    Ok(TransparentPipelineResources {
+        layout,
+        oit_composite_pipeline,
+    })
+}
+
+pub(crate) fn prepare_transparent_pipeline(
+    mut pipeline: ResMut<TransparentPipeline>,
+    shaders: Res<ShaderDirectory>,
+    cameras: Query<&ExtractedCamera>,
+    frame_context: Res<FrameContext>,
+) {
+    if !matches!(*pipeline, TransparentPipeline::Uninitialized)
+        || !cameras.iter().any(|camera| camera.render_graph == DEFERRED_GRAPH) {
+        return;
+    }
+
+    *pipeline = match create_resources(frame_context.render_context(), &shaders) {
+        Ok(resources) => TransparentPipeline::Ready(Box::new(resources)),
+        Err(err) => {
+            error!("Failed to create transparent pipeline: {err}");
+            TransparentPipeline::Failed
+        }
+    };
+}
+
+/// Create the pipeline variants of the transparent items queued this frame.
+pub(crate) fn specialize_transparent_pipelines(
+    mut pipelines: ResMut<SpecializedPipelines<TransparentPipelineKey>>,
+    pipeline: Res<TransparentPipeline>,
+    shaders: Res<ShaderDirectory>,
+    phases: Query<&RenderPhase<Transparent3d>>,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(resources) = pipeline.resources() else {
+        return;
+    };
+    let context = frame_context.render_context();
+
+    for item in phases.iter().flat_map(|phase| &phase.items) {
+        pipelines.specialize(context, &shaders, resources, &item.pipeline);
+    }
+}
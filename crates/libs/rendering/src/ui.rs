@@ -0,0 +1,331 @@
+use bevy_app::{App, Plugin, PostUpdate};
+use bevy_ecs::prelude::{Commands, Component, DetectChanges, DetectChangesMut, Entity, EventReader, IntoSystemConfigs, Query, Ref, Res, ResMut, Resource, With};
+use bevy_math::{Rect, Vec2};
+use bevy_transform::prelude::Transform;
+use bevy_transform::TransformSystem;
+use avalanche_window::{PrimaryWindowComponent, WindowComponent};
+use avalanche_window::event::WindowResizedEvent;
+use crate::sprite::Sprite;
+
+/// A point of a rectangle, e.g. the corner of the window a HUD element sticks to.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    TopCenter,
+    TopRight,
+    CenterLeft,
+    Center,
+    CenterRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+    /// In fractions of the size from the top left corner
+    Custom(Vec2),
+}
+
+impl Anchor {
+    /// The point in fractions of the size from the top left corner.
+    pub fn fraction(&self) -> Vec2 {
+        match *self {
+            Anchor::TopLeft => Vec2::new(0.0, 0.0),
+            Anchor::TopCenter => Vec2::new(0.5, 0.0),
+            Anchor::TopRight => Vec2::new(1.0, 0.0),
+            Anchor::CenterLeft => Vec2::new(0.0, 0.5),
+            Anchor::Center => Vec2::new(0.5, 0.5),
+            Anchor::CenterRight => Vec2::new(1.0, 0.5),
+            Anchor::BottomLeft => Vec2::new(0.0, 1.0),
+            Anchor::BottomCenter => Vec2::new(0.5, 1.0),
+            Anchor::BottomRight => Vec2::new(1.0, 1.0),
+            Anchor::Custom(fraction) => fraction,
+        }
+    }
+}
+
+/// A length of the UI, resolved against the area a [`UiNode`] is laid out in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Val {
+    /// Logical pixels, scaled by the scale factor of the window so elements keep their size across DPIs
+    Px(f32),
+    /// Percents of the width or height of the layout area
+    Percent(f32),
+}
+
+impl Default for Val {
+    fn default() -> Self {
+        Val::Px(0.0)
+    }
+}
+
+impl Val {
+    /// The length in physical pixels.
+    pub fn resolve(&self, area_length: f32, scale_factor: f32) -> f32 {
+        match *self {
+            Val::Px(pixels) => pixels * scale_factor,
+            Val::Percent(percent) => area_length * percent / 100.0,
+        }
+    }
+}
+
+/// An element of the HUD placed relative to the primary window, laid out again when the window is resized.
+///
+/// The node is placed so its [`pivot`](Self::pivot) lands on the [`anchor`](Self::anchor) of the layout area
+/// moved by [`offset`](Self::offset), the result is written to its [`UiLayout`].
+/// [`Sprite`]s are resized to the node, and their [`Transform`] moved to it as seen by a camera at the origin
+/// with the default [`OrthographicProjection`](crate::camera::OrthographicProjection).
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct UiNode {
+    pub anchor: Anchor,
+    /// Point of the node placed on the anchor, the anchor itself when `None`
+    pub pivot: Option<Anchor>,
+    /// From the anchor, x right and y down
+    pub offset: (Val, Val),
+    /// Width and height
+    pub size: (Val, Val),
+    /// Lay the node out inside the [`UiSafeArea`] rather than the whole window
+    pub within_safe_area: bool,
+}
+
+impl Default for UiNode {
+    fn default() -> Self {
+        Self {
+            anchor: Anchor::TopLeft,
+            pivot: None,
+            offset: (Val::Px(0.0), Val::Px(0.0)),
+            size: (Val::Px(100.0), Val::Px(100.0)),
+            within_safe_area: true,
+        }
+    }
+}
+
+impl UiNode {
+    pub fn new(anchor: Anchor, size: (Val, Val)) -> Self {
+        Self {
+            anchor,
+            size,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_offset(self, offset: (Val, Val)) -> Self {
+        Self { offset, ..self }
+    }
+
+    /// The rectangle of the node in `area`, both in physical pixels from the top left corner of the window.
+    pub fn layout(&self, area: Rect, scale_factor: f32) -> Rect {
+        let area_size = area.size();
+        let size = Vec2::new(
+            self.size.0.resolve(area_size.x, scale_factor),
+            self.size.1.resolve(area_size.y, scale_factor),
+        );
+        let offset = Vec2::new(
+            self.offset.0.resolve(area_size.x, scale_factor),
+            self.offset.1.resolve(area_size.y, scale_factor),
+        );
+        let anchor = area.min + area_size * self.anchor.fraction() + offset;
+        let min = anchor - size * self.pivot.unwrap_or(self.anchor).fraction();
+        Rect::from_corners(min, min + size)
+    }
+}
+
+/// Where a [`UiNode`] was laid out, updated when it or the [`UiViewport`] changes.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct UiLayout {
+    /// In physical pixels from the top left corner of the window
+    pub rect: Rect,
+    pub scale_factor: f32,
+}
+
+/// Margins of the window hidden by the platform, e.g. by notches, rounded corners or TV overscan.
+///
+/// In logical pixels, scaled by the scale factor of the window. Filled from the
+/// [`safe_area_insets`](WindowComponent::safe_area_insets) of the primary window when it is resized.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+pub struct UiSafeArea {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl UiSafeArea {
+    /// From left, top, right and bottom insets in physical pixels.
+    pub fn from_insets(insets: [u32; 4], scale_factor: f32) -> Self {
+        let [left, top, right, bottom] = insets.map(|inset| inset as f32 / scale_factor);
+        Self { left, top, right, bottom }
+    }
+}
+
+/// Size and scale factor of the primary window the [`UiNode`]s are laid out in.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct UiViewport {
+    /// In physical pixels
+    pub size: Vec2,
+    /// Physical pixels per logical pixel
+    pub scale_factor: f32,
+}
+
+impl Default for UiViewport {
+    fn default() -> Self {
+        Self {
+            size: Vec2::ZERO,
+            scale_factor: 1.0,
+        }
+    }
+}
+
+impl UiViewport {
+    /// The whole window in physical pixels.
+    pub fn rect(&self) -> Rect {
+        Rect::from_corners(Vec2::ZERO, self.size)
+    }
+
+    /// The window without the `safe_area`, in physical pixels.
+    pub fn safe_rect(&self, safe_area: &UiSafeArea) -> Rect {
+        let min = Vec2::new(safe_area.left, safe_area.top) * self.scale_factor;
+        let max = self.size - Vec2::new(safe_area.right, safe_area.bottom) * self.scale_factor;
+        Rect::from_corners(min, max.max(min))
+    }
+}
+
+/// Lays the [`UiNode`]s out in the primary window.
+pub struct UiLayoutPlugin;
+
+impl Plugin for UiLayoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UiViewport>()
+            .init_resource::<UiSafeArea>()
+            .add_systems(
+                PostUpdate,
+                (update_ui_viewport, layout_ui_nodes)
+                    .chain()
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+fn update_ui_viewport(
+    mut resized_events: EventReader<WindowResizedEvent>,
+    primary_window: Query<&WindowComponent, With<PrimaryWindowComponent>>,
+    mut viewport: ResMut<UiViewport>,
+    mut safe_area: ResMut<UiSafeArea>,
+) {
+    let Ok(window) = primary_window.get_single() else {
+        resized_events.clear();
+        return;
+    };
+    let Some(event) = resized_events.read().filter(|event| event.window_id == window.window.id()).last() else {
+        return;
+    };
+
+    let scale_factor = window.window.scale_factor() as f32;
+    viewport.set_if_neq(UiViewport {
+        size: Vec2::new(event.new_size.0 as f32, event.new_size.1 as f32),
+        scale_factor,
+    });
+    // system bars and cutouts move with the orientation, which resizes the window
+    safe_area.set_if_neq(UiSafeArea::from_insets(window.safe_area_insets(), scale_factor));
+}
+
+type UiNodeQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    Ref<'static, UiNode>,
+    Option<&'static mut UiLayout>,
+    Option<&'static mut Transform>,
+    Option<&'static mut Sprite>,
+)>;
+
+fn layout_ui_nodes(
+    mut commands: Commands,
+    viewport: Res<UiViewport>,
+    safe_area: Res<UiSafeArea>,
+    mut nodes: UiNodeQuery,
+) {
+    let relayout = viewport.is_changed() || safe_area.is_changed();
+    let safe_rect = viewport.safe_rect(&safe_area);
+
+    for (entity, node, layout, transform, sprite) in nodes.iter_mut() {
+        if !relayout && !node.is_changed() && layout.is_some() {
+            continue;
+        }
+
+        let area = if node.within_safe_area { safe_rect } else { viewport.rect() };
+        let rect = node.layout(area, viewport.scale_factor);
+        let new_layout = UiLayout {
+            rect,
+            scale_factor: viewport.scale_factor,
+        };
+        match layout {
+            Some(mut layout) => {
+                layout.set_if_neq(new_layout);
+            }
+            None => {
+                commands.entity(entity).insert(new_layout);
+            }
+        }
+
+        // y up around the center of the window, in pixels
+        if let Some(mut transform) = transform {
+            let center = rect.center();
+            let translation = Vec2::new(center.x - viewport.size.x * 0.5, viewport.size.y * 0.5 - center.y);
+            if transform.translation.truncate() != translation {
+                transform.translation = translation.extend(transform.translation.z);
+            }
+        }
+        if let Some(mut sprite) = sprite {
+            if sprite.custom_size != Some(rect.size()) {
+                sprite.custom_size = Some(rect.size());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_layout_corner_anchors() {
+    let area = Rect::new(0.0, 0.0, 800.0, 600.0);
+    let size = (Val::Px(100.0), Val::Px(50.0));
+
+    assert_eq!(UiNode::new(Anchor::TopLeft, size).layout(area, 1.0), Rect::new(0.0, 0.0, 100.0, 50.0));
+    assert_eq!(UiNode::new(Anchor::TopRight, size).layout(area, 1.0), Rect::new(700.0, 0.0, 800.0, 50.0));
+    assert_eq!(UiNode::new(Anchor::BottomLeft, size).layout(area, 1.0), Rect::new(0.0, 550.0, 100.0, 600.0));
+    assert_eq!(UiNode::new(Anchor::BottomRight, size).layout(area, 1.0), Rect::new(700.0, 550.0, 800.0, 600.0));
+    // logical pixels are scaled, the offset moves away from the anchor
+    let node = UiNode::new(Anchor::BottomRight, size).with_offset((Val::Px(-10.0), Val::Px(-10.0)));
+    assert_eq!(node.layout(area, 2.0), Rect::new(580.0, 480.0, 780.0, 580.0));
+}
+
+#[test]
+fn test_layout_percent_size() {
+    let area = Rect::new(0.0, 0.0, 800.0, 600.0);
+    let node = UiNode::new(Anchor::Center, (Val::Percent(50.0), Val::Percent(10.0)));
+
+    assert_eq!(node.layout(area, 2.0), Rect::new(200.0, 270.0, 600.0, 330.0));
+}
+
+#[test]
+fn test_layout_resize_reflow() {
+    let node = UiNode::new(Anchor::BottomCenter, (Val::Percent(50.0), Val::Px(20.0)));
+    let mut viewport = UiViewport {
+        size: Vec2::new(800.0, 600.0),
+        scale_factor: 1.0,
+    };
+    assert_eq!(node.layout(viewport.rect(), viewport.scale_factor), Rect::new(200.0, 580.0, 600.0, 600.0));
+
+    viewport.size = Vec2::new(400.0, 1000.0);
+    assert_eq!(node.layout(viewport.rect(), viewport.scale_factor), Rect::new(100.0, 980.0, 300.0, 1000.0));
+}
+
+#[test]
+fn test_layout_safe_area() {
+    let viewport = UiViewport {
+        size: Vec2::new(1000.0, 500.0),
+        scale_factor: 2.0,
+    };
+    let safe_area = UiSafeArea::from_insets([80, 0, 40, 20], viewport.scale_factor);
+    assert_eq!(safe_area, UiSafeArea { left: 40.0, top: 0.0, right: 20.0, bottom: 10.0 });
+
+    let safe_rect = viewport.safe_rect(&safe_area);
+    assert_eq!(safe_rect, Rect::new(80.0, 0.0, 960.0, 480.0));
+    let node = UiNode::new(Anchor::BottomRight, (Val::Px(10.0), Val::Px(10.0)));
+    assert_eq!(node.layout(safe_rect, viewport.scale_factor), Rect::new(940.0, 460.0, 960.0, 480.0));
+}
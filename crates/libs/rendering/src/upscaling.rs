@@ -0,0 +1,184 @@
+use std::sync::Arc;
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Resource, World};
+use bevy_log::error;
+use bevy_math::Vec2;
+use avalanche_hlvk::{CommandBuffer, ImageBarrier};
+use crate::RenderApp;
+use crate::extract::FrameContext;
+use crate::graph::RenderGraphApp;
+use crate::graph::node::{ViewNode, ViewNodeRunner};
+use crate::deferred::{DEFERRED_GRAPH, DEFERRED_SKYBOX_NODE};
+use crate::path_tracing::{PATH_TRACING_GRAPH, PATH_TRACING_NODE};
+use crate::sprite::{SPRITE_GRAPH, SPRITE_NODE};
+use crate::prelude::{NodeRunError, RenderGraphContext};
+use crate::render_scale::{RenderScale, UpscaleFilter};
+use crate::view::{TemporalJitter, UpscaledViewTarget, ViewDepthTarget, ViewExposure, ViewMotionVectors, ViewTarget};
+
+pub const UPSCALE_NODE: &str = "upscale";
+
+/// Everything a temporal upscaler like FSR2 consumes for one view.
+///
+/// `color` is in the `GENERAL` layout, `output` must be left in it.
+pub struct UpscalerInputs<'a> {
+    pub color: &'a ViewTarget,
+    pub depth: Option<&'a ViewDepthTarget>,
+    pub motion_vectors: Option<&'a ViewMotionVectors>,
+    pub exposure: f32,
+    pub jitter: Vec2,
+    pub filter: UpscaleFilter,
+    pub output: &'a UpscaledViewTarget,
+}
+
+/// Upsamples the [`ViewTarget`] of views rendering at a [`RenderScale`] into their [`UpscaledViewTarget`].
+///
+/// Implement it and insert it as the [`ActiveUpscaler`] of the render app to replace the built-in [`NaiveUpscaler`].
+pub trait Upscaler: Send + Sync + 'static {
+    fn upscale(
+        &self,
+        command_buffer: &CommandBuffer,
+        rendering_context: &FrameContext,
+        inputs: &UpscalerInputs,
+    ) -> anyhow::Result<()>;
+}
+
+/// The [`Upscaler`] run by the [`UPSCALE_NODE`], lives in the render world.
+#[derive(Resource, Clone)]
+pub struct ActiveUpscaler(pub Arc<dyn Upscaler>);
+
+impl ActiveUpscaler {
+    pub fn new(upscaler: impl Upscaler) -> Self {
+        Self(Arc::new(upscaler))
+    }
+}
+
+impl Default for ActiveUpscaler {
+    fn default() -> Self {
+        Self::new(NaiveUpscaler)
+    }
+}
+
+/// Spatial only upscaling with an image blit, ignores every input but the color.
+pub struct NaiveUpscaler;
+
+impl Upscaler for NaiveUpscaler {
+    fn upscale(
+        &self,
+        command_buffer: &CommandBuffer,
+        _rendering_context: &FrameContext,
+        inputs: &UpscalerInputs,
+    ) -> anyhow::Result<()> {
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image: &inputs.color.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                dst_access_mask: vk::AccessFlags2::TRANSFER_READ,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::BLIT,
+            },
+            // the output is fully overwritten every frame
+            ImageBarrier {
+                image: &inputs.output.image,
+                old_layout: vk::ImageLayout::UNDEFINED,
+                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                src_access_mask: vk::AccessFlags2::MEMORY_READ,
+                dst_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::BLIT,
+            },
+        ]);
+
+        command_buffer.blit_image(
+            &inputs.color.image,
+            vk::ImageLayout::GENERAL,
+            &inputs.output.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            inputs.filter.into(),
+        );
+
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image: &inputs.output.image,
+                old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                dst_access_mask: vk::AccessFlags2::MEMORY_READ,
+                src_stage_mask: vk::PipelineStageFlags2::BLIT,
+                dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            },
+        ]);
+
+        Ok(())
+    }
+}
+
+/// Runs the [`ActiveUpscaler`] on views with an [`UpscaledViewTarget`].
+#[derive(Default)]
+pub struct UpscaleNode;
+
+impl ViewNode for UpscaleNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static UpscaledViewTarget,
+        &'static RenderScale,
+        Option<&'static ViewDepthTarget>,
+        Option<&'static ViewMotionVectors>,
+        Option<&'static ViewExposure>,
+        Option<&'static TemporalJitter>,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (color, output, render_scale, depth, motion_vectors, exposure, jitter): (
+            &ViewTarget,
+            &UpscaledViewTarget,
+            &RenderScale,
+            Option<&ViewDepthTarget>,
+            Option<&ViewMotionVectors>,
+            Option<&ViewExposure>,
+            Option<&TemporalJitter>,
+        ),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+
+        let inputs = UpscalerInputs {
+            color,
+            depth,
+            motion_vectors,
+            exposure: exposure.map_or(1.0, |exposure| exposure.0),
+            jitter: jitter.map_or(Vec2::ZERO, |jitter| jitter.offset),
+            filter: render_scale.filter,
+            output,
+        };
+        if let Err(err) = world.resource::<ActiveUpscaler>().0.upscale(command_buffer, rendering_context, &inputs) {
+            error!("Failed to upscale view: {err}");
+        }
+
+        Ok(())
+    }
+}
+
+pub struct UpscalingPlugin;
+
+impl Plugin for UpscalingPlugin {
+    fn build(&self, app: &mut App) {
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ActiveUpscaler>()
+                .add_render_graph_node::<ViewNodeRunner<UpscaleNode>>(PATH_TRACING_GRAPH, UPSCALE_NODE)
+                .add_render_graph_edge(PATH_TRACING_GRAPH, PATH_TRACING_NODE, UPSCALE_NODE)
+                .add_render_graph_node::<ViewNodeRunner<UpscaleNode>>(DEFERRED_GRAPH, UPSCALE_NODE)
+                .add_render_graph_edge(DEFERRED_GRAPH, DEFERRED_SKYBOX_NODE, UPSCALE_NODE)
+                .add_render_graph_node::<ViewNodeRunner<UpscaleNode>>(SPRITE_GRAPH, UPSCALE_NODE)
+                .add_render_graph_edge(SPRITE_GRAPH, SPRITE_NODE, UPSCALE_NODE);
+        }
+    }
+}
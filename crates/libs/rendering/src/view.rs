@@ -0,0 +1,317 @@
+use ash::vk;
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Commands, Component, Entity, Has, IntoSystemConfigs, Query, ReflectComponent, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_math::{Mat4, UVec2, Vec2};
+use bevy_reflect::Reflect;
+use bevy_utils::{EntityHashMap, HashSet};
+use gpu_allocator::MemoryLocation;
+use crate::{Render, RenderApp, RenderSet};
+use crate::camera::{ExtractedCamera, ExtractedStereoViews};
+use crate::extract::{ExtractComponent, ExtractComponentPlugin, FrameContext};
+use crate::prelude::{Buffer, Image, ImageView, RenderingContext};
+use crate::resource::TypedBuffer;
+use crate::shader::ShaderType;
+use crate::render_scale::RenderScale;
+
+/// Format of the HDR color target every camera renders into.
+pub const VIEW_TARGET_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// Format of the [`ViewMotionVectors`] of a camera.
+pub const MOTION_VECTORS_FORMAT: vk::Format = vk::Format::R16G16_SFLOAT;
+/// Views of a camera rendered at once with multiview, the eyes of a [`StereoCamera`](crate::camera::StereoCamera).
+pub const MAX_VIEWS: usize = 2;
+
+/// The HDR color target of a camera.
+///
+/// The image is kept across frames and only recreated when the camera target is resized.
+/// Its size is the camera viewport size scaled by the [`RenderScale`] of the camera.
+#[derive(Component, Clone)]
+pub struct ViewTarget {
+    pub image: Image,
+    pub view: ImageView,
+    pub size: UVec2,
+}
+
+/// Full resolution color target of a camera rendering at a [`RenderScale`] other than 1,
+/// the [`ViewTarget`] is upscaled into it.
+#[derive(Component, Clone)]
+pub struct UpscaledViewTarget {
+    pub image: Image,
+    pub view: ImageView,
+    pub size: UVec2,
+}
+
+/// HDR color target of a camera with a [`StereoCamera`](crate::camera::StereoCamera), a layer per eye at the [`ViewTarget`] size.
+///
+/// Passes supporting multiview render both eyes at once into it, with a view mask of `0b11`, when the device
+/// enabled [`DeviceFeatures::multiview`](avalanche_hlvk::DeviceFeatures::multiview), and one layer at a time otherwise.
+#[derive(Component, Clone)]
+pub struct StereoViewTarget {
+    pub image: Image,
+    /// Views the layers as an array
+    pub view: ImageView,
+    pub size: UVec2,
+}
+
+/// Matrices of the views of a camera indexed by `gl_ViewIndex`, the views after `view_count` repeat the first one.
+///
+/// Matches `ViewUniform` in `shaders/view.glsl`, written with the std140 layout.
+#[derive(ShaderType, Clone, Copy, Debug)]
+pub struct ViewUniform {
+    pub clip_from_world: [Mat4; MAX_VIEWS],
+    pub world_from_view: [Mat4; MAX_VIEWS],
+    pub clip_from_view: [Mat4; MAX_VIEWS],
+    pub view_count: u32,
+}
+
+impl ViewUniform {
+    pub fn new(camera: &ExtractedCamera, stereo: Option<&ExtractedStereoViews>) -> Self {
+        let (world_from_view, clip_from_view, view_count) = match stereo {
+            Some(stereo) => (stereo.world_from_view, stereo.projection, 2),
+            None => ([camera.world_from_view; MAX_VIEWS], [camera.projection; MAX_VIEWS], 1),
+        };
+        Self {
+            clip_from_world: std::array::from_fn(|view| clip_from_view[view] * world_from_view[view].inverse()),
+            world_from_view,
+            clip_from_view,
+            view_count,
+        }
+    }
+}
+
+/// The [`ViewUniform`] of a camera, rewritten every frame.
+#[derive(Component, Clone)]
+pub struct ViewUniformBuffer(pub Buffer);
+
+/// Depth buffer of a view at the [`ViewTarget`] size, inserted by the passes rendering one.
+#[derive(Component, Clone)]
+pub struct ViewDepthTarget {
+    pub image: Image,
+    pub view: ImageView,
+}
+
+/// Per-pixel screen space motion of a view at the [`ViewTarget`] size, in pixels from the previous frame.
+///
+/// Created for cameras with a [`MotionVectorPrepass`].
+#[derive(Component, Clone)]
+pub struct ViewMotionVectors {
+    pub image: Image,
+    pub view: ImageView,
+}
+
+/// Request [`ViewMotionVectors`] for a camera, written by its render graph.
+#[derive(Component, ExtractComponent, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct MotionVectorPrepass;
+
+/// Cull the rasterized geometry of a camera against its [`DepthPyramidViews`](crate::depth_pyramid::DepthPyramidViews).
+///
+/// Geometry visible last frame is drawn first, the pyramid is built from it and the rest is tested
+/// against the pyramid, only what turned visible is drawn in a second pass.
+#[derive(Component, ExtractComponent, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct OcclusionCulling;
+
+/// Exposure the HDR color of a view is multiplied with before tonemapping, 1 when missing.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct ViewExposure(pub f32);
+
+/// Sub-pixel offset the projection of a view was jittered with this frame, in pixels.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub struct TemporalJitter {
+    pub offset: Vec2,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct ViewTargetCache {
+    targets: EntityHashMap<Entity, ViewTarget>,
+    upscaled_targets: EntityHashMap<Entity, UpscaledViewTarget>,
+    motion_vectors: EntityHashMap<Entity, (UVec2, ViewMotionVectors)>,
+    stereo_targets: EntityHashMap<Entity, StereoViewTarget>,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct ViewUniformCache(EntityHashMap<Entity, TypedBuffer<u8>>);
+
+pub struct ViewPlugin;
+
+impl Plugin for ViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<MotionVectorPrepass>()
+            .register_type::<OcclusionCulling>()
+            .add_plugins((
+                ExtractComponentPlugin::<MotionVectorPrepass>::default(),
+                ExtractComponentPlugin::<OcclusionCulling>::default(),
+            ));
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ViewTargetCache>()
+                .init_resource::<ViewUniformCache>()
+                .add_systems(Render, (
+                    prepare_view_targets.in_set(RenderSet::ManageViews),
+                    prepare_view_uniforms.in_set(RenderSet::PrepareResources),
+                ));
+        }
+    }
+}
+
+type ViewTargetCameraQuery<'w, 's> = Query<'w, 's, (
+    Entity,
+    &'static ExtractedCamera,
+    Option<&'static RenderScale>,
+    Option<&'static MotionVectorPrepass>,
+    Has<ExtractedStereoViews>,
+)>;
+
+pub(crate) fn prepare_view_targets(
+    mut commands: Commands,
+    mut cache: ResMut<ViewTargetCache>,
+    cameras: ViewTargetCameraQuery,
+    frame_context: Res<FrameContext>,
+) {
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+
+    for (entity, camera, render_scale, motion_vector_prepass, stereo) in cameras.iter() {
+        let viewport_size = camera.viewport.size();
+        let render_size = render_scale.map_or(viewport_size, |scale| scale.render_size(viewport_size));
+
+        let up_to_date = matches!(cache.targets.get(&entity), Some(target) if target.size == render_size);
+        if !up_to_date {
+            match create_view_image(context, VIEW_TARGET_FORMAT, render_size, "view target") {
+                Ok((image, view)) => {
+                    cache.targets.insert(entity, ViewTarget { image, view, size: render_size });
+                }
+                Err(err) => {
+                    error!("Failed to create view target: {err}");
+                    cache.targets.remove(&entity);
+                    continue;
+                }
+            }
+        }
+
+        if render_size != viewport_size {
+            let up_to_date = matches!(cache.upscaled_targets.get(&entity), Some(target) if target.size == viewport_size);
+            if !up_to_date {
+                match create_view_image(context, VIEW_TARGET_FORMAT, viewport_size, "upscaled view target") {
+                    Ok((image, view)) => {
+                        cache.upscaled_targets.insert(entity, UpscaledViewTarget { image, view, size: viewport_size });
+                    }
+                    Err(err) => {
+                        error!("Failed to create upscaled view target: {err}");
+                        cache.upscaled_targets.remove(&entity);
+                        continue;
+                    }
+                }
+            }
+            commands.entity(entity).insert(cache.upscaled_targets[&entity].clone());
+        } else {
+            cache.upscaled_targets.remove(&entity);
+        }
+
+        if motion_vector_prepass.is_some() {
+            let up_to_date = matches!(cache.motion_vectors.get(&entity), Some((size, _)) if *size == render_size);
+            if !up_to_date {
+                match create_view_image(context, MOTION_VECTORS_FORMAT, render_size, "view motion vectors") {
+                    Ok((image, view)) => {
+                        cache.motion_vectors.insert(entity, (render_size, ViewMotionVectors { image, view }));
+                    }
+                    Err(err) => {
+                        error!("Failed to create view motion vectors: {err}");
+                        cache.motion_vectors.remove(&entity);
+                        continue;
+                    }
+                }
+            }
+            commands.entity(entity).insert(cache.motion_vectors[&entity].1.clone());
+        } else {
+            cache.motion_vectors.remove(&entity);
+        }
+
+        if stereo {
+            let up_to_date = matches!(cache.stereo_targets.get(&entity), Some(target) if target.size == render_size);
+            if !up_to_date {
+                match create_stereo_view_image(context, render_size) {
+                    Ok((image, view)) => {
+                        cache.stereo_targets.insert(entity, StereoViewTarget { image, view, size: render_size });
+                    }
+                    Err(err) => {
+                        error!("Failed to create stereo view target: {err}");
+                        cache.stereo_targets.remove(&entity);
+                        continue;
+                    }
+                }
+            }
+            commands.entity(entity).insert(cache.stereo_targets[&entity].clone());
+        } else {
+            cache.stereo_targets.remove(&entity);
+        }
+
+        alive.insert(entity);
+        commands.entity(entity).insert(cache.targets[&entity].clone());
+    }
+
+    cache.targets.retain(|entity, _| alive.contains(entity));
+    cache.upscaled_targets.retain(|entity, _| alive.contains(entity));
+    cache.motion_vectors.retain(|entity, _| alive.contains(entity));
+    cache.stereo_targets.retain(|entity, _| alive.contains(entity));
+}
+
+pub(crate) fn prepare_view_uniforms(
+    mut commands: Commands,
+    mut cache: ResMut<ViewUniformCache>,
+    cameras: Query<(Entity, &ExtractedCamera, Option<&ExtractedStereoViews>)>,
+    frame_context: Res<FrameContext>,
+) {
+    let context = frame_context.render_context();
+    let mut alive = HashSet::default();
+
+    for (entity, camera, stereo) in cameras.iter() {
+        let uniform = ViewUniform::new(camera, stereo).std140_bytes();
+        let buffer = cache
+            .0
+            .entry(entity)
+            .or_insert_with(|| TypedBuffer::new(vk::BufferUsageFlags::UNIFORM_BUFFER).with_name("view uniform"));
+        if let Err(err) = buffer.write(context, &uniform) {
+            error!("Failed to write the view uniform: {err}");
+            continue;
+        }
+        alive.insert(entity);
+        commands.entity(entity).insert(ViewUniformBuffer(buffer.buffer().unwrap().clone()));
+    }
+
+    cache.0.retain(|entity, _| alive.contains(entity));
+}
+
+fn create_stereo_view_image(context: &RenderingContext, size: UVec2) -> anyhow::Result<(Image, ImageView)> {
+    let image = context.create_layered_image(
+        vk::ImageUsageFlags::STORAGE
+            | vk::ImageUsageFlags::SAMPLED
+            | vk::ImageUsageFlags::TRANSFER_SRC
+            | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        MemoryLocation::GpuOnly,
+        VIEW_TARGET_FORMAT,
+        size.x,
+        size.y,
+        MAX_VIEWS as u32,
+    )?.with_name("stereo view target");
+    let view = image.create_image_view()?;
+    Ok((image.into(), view.into()))
+}
+
+fn create_view_image(context: &RenderingContext, format: vk::Format, size: UVec2, name: &str) -> anyhow::Result<(Image, ImageView)> {
+    let image = context.create_image(
+        vk::ImageUsageFlags::STORAGE
+            | vk::ImageUsageFlags::SAMPLED
+            | vk::ImageUsageFlags::TRANSFER_SRC
+            | vk::ImageUsageFlags::TRANSFER_DST
+            | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        MemoryLocation::GpuOnly,
+        format,
+        size.x,
+        size.y,
+    )?.with_name(name);
+    let view = image.create_image_view()?;
+    Ok((image.into(), view.into()))
+}
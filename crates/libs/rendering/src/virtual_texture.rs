@@ -0,0 +1,600 @@
+mod page;
+mod pipeline;
+
+pub use page::*;
+pub use pipeline::*;
+
+use std::collections::VecDeque;
+use anyhow::{ensure, Context as _};
+use ash::vk;
+use async_channel::{Receiver, Sender};
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{Component, Entity, IntoSystemConfigs, Query, Res, ResMut, Resource};
+use bevy_log::error;
+use bevy_math::UVec2;
+use bevy_tasks::{IoTaskPool, TaskPool};
+use bevy_utils::{EntityHashMap, HashSet};
+use gpu_allocator::MemoryLocation;
+use avalanche_hlvk::{
+    Buffer, BufferBarrier, CommandBuffer, Context, DescriptorPool, DescriptorSet, Image, ImageBarrier, ImageView,
+    Sampler, WriteDescriptorSet, WriteDescriptorSetKind,
+};
+use crate::{Render, RenderApp, RenderSet};
+use crate::extract::{ExtractComponent, ExtractComponentPlugin, FrameContext};
+use crate::globals::Globals;
+use crate::readback::{ReadbackBuffer, ReadbackSource};
+use crate::render_asset::RenderAssetStagingBuffers;
+use crate::streaming::StreamSource;
+
+/// Texels along a side of a page, the unit streamed in and out of the atlas.
+pub const VIRTUAL_TEXTURE_PAGE_SIZE: u32 = 128;
+/// Pages along a side of the atlas of a virtual texture, 4096x4096 texels.
+pub const VIRTUAL_TEXTURE_ATLAS_PAGES: u32 = 32;
+/// Threads of a workgroup of the feedback analysis shader, one per page.
+pub const VIRTUAL_TEXTURE_FEEDBACK_WORKGROUP_SIZE: u32 = 64;
+/// Requested pages read back per frame, the others are requested again by the next frames.
+pub const MAX_VIRTUAL_TEXTURE_REQUESTS: u32 = 1024;
+/// Pages copied into the atlas per frame and per texture.
+const MAX_PAGE_UPLOADS_PER_FRAME: usize = 16;
+/// Pages read from the source at once per texture.
+const MAX_PAGE_LOADS_IN_FLIGHT: usize = 64;
+
+/// A texture too large to be resident, e.g. the material of a terrain, only the pages sampled recently are on the GPU.
+///
+/// Shaders sample it through `shaders/virtual_texture/common.glsl`, which also records the pages they need in
+/// a feedback buffer. The feedback is analysed on the GPU every frame and read back, the missing pages are then
+/// read from the [`StreamSource`] in the background and copied into an atlas, the least recently used pages
+/// making room. Until then, the average color of the page is sampled.
+///
+/// Only the first mip level is virtualized. Devices with [`DeviceFeatures::sparse_residency_image_2d`](avalanche_hlvk::DeviceFeatures)
+/// could bind the pages into a sparse image instead of the atlas, this isn't implemented yet.
+#[derive(Component, ExtractComponent, Clone, Debug)]
+pub struct VirtualTexture {
+    /// Tightly packed texels of 4 bytes, row after row
+    pub source: StreamSource,
+    /// Texels of the texture, multiples of [`VIRTUAL_TEXTURE_PAGE_SIZE`]
+    pub width: u32,
+    pub height: u32,
+    pub format: vk::Format,
+}
+
+impl VirtualTexture {
+    pub fn new(source: StreamSource, width: u32, height: u32) -> Self {
+        Self {
+            source,
+            width,
+            height,
+            format: vk::Format::R8G8B8A8_SRGB,
+        }
+    }
+
+    /// Pages along each side of the texture.
+    #[inline]
+    pub fn pages(&self) -> UVec2 {
+        UVec2::new(self.width, self.height) / VIRTUAL_TEXTURE_PAGE_SIZE
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.width > 0 && self.height > 0
+                && self.width.is_multiple_of(VIRTUAL_TEXTURE_PAGE_SIZE) && self.height.is_multiple_of(VIRTUAL_TEXTURE_PAGE_SIZE),
+            "Virtual texture size {}x{} isn't a multiple of the {VIRTUAL_TEXTURE_PAGE_SIZE} texels pages",
+            self.width, self.height,
+        );
+        ensure!(
+            self.source.len() == self.width as usize * self.height as usize * 4,
+            "Virtual texture data doesn't match its {}x{} size", self.width, self.height,
+        );
+        Ok(())
+    }
+
+    /// Whether `other` streams the same texels, the state of the texture is recreated otherwise.
+    fn is_same(&self, other: &Self) -> bool {
+        std::ptr::eq(self.source.bytes(), other.source.bytes())
+            && self.width == other.width
+            && self.height == other.height
+            && self.format == other.format
+    }
+}
+
+pub struct VirtualTexturePlugin;
+
+impl Plugin for VirtualTexturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<VirtualTexture>::default());
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<VirtualTexturePipeline>()
+                .init_resource::<VirtualTextures>()
+                .add_systems(
+                    Render,
+                    (prepare_virtual_texture_pipeline, prepare_virtual_textures)
+                        .chain()
+                        .in_set(RenderSet::PrepareResources),
+                );
+        }
+    }
+}
+
+/// Read by the background tasks of a texture.
+enum PageMessage {
+    Overview(Vec<u32>),
+    Page { page: u32, texels: Vec<u8> },
+}
+
+/// The page table, atlas and feedback of a [`VirtualTexture`], lost when the entity stops using it.
+pub struct VirtualTextureState {
+    /// A `R32_UINT` texel per page, see `shaders/virtual_texture/common.glsl`
+    pub page_table: Image,
+    pub page_table_view: ImageView,
+    /// [`VIRTUAL_TEXTURE_ATLAS_PAGES`] squared resident pages
+    pub atlas: Image,
+    pub atlas_view: ImageView,
+    pub sampler: Sampler,
+    /// Integer formats can't be filtered
+    pub page_table_sampler: Sampler,
+    /// A flag per page, set by the shaders sampling the texture
+    pub feedback: Buffer,
+    /// The count of requested pages followed by up to [`MAX_VIRTUAL_TEXTURE_REQUESTS`] pages
+    requests: Buffer,
+    _descriptor_pool: DescriptorPool,
+    descriptor_set: DescriptorSet,
+    texture: VirtualTexture,
+    page_source: PageSource,
+    /// Page table on the host, uploaded whole when dirty
+    entries: Vec<u32>,
+    /// Average color of every page, restored into the page table on eviction
+    overview: Vec<u32>,
+    page_table_dirty: bool,
+    cache: PageCache,
+    loading: HashSet<u32>,
+    readbacks: VecDeque<ReadbackBuffer>,
+    sender: Sender<PageMessage>,
+    receiver: Receiver<PageMessage>,
+}
+
+impl VirtualTextureState {
+    fn new(
+        context: &Context,
+        command_buffer: &CommandBuffer,
+        pipeline: &VirtualTexturePipelineResources,
+        texture: &VirtualTexture,
+    ) -> anyhow::Result<Self> {
+        texture.validate()?;
+        let pages = texture.pages();
+        let page_count = (pages.x * pages.y) as usize;
+
+        let page_table = context.create_image(
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuOnly,
+            vk::Format::R32_UINT,
+            pages.x,
+            pages.y,
+        )?;
+        let atlas_size = VIRTUAL_TEXTURE_ATLAS_PAGES * VIRTUAL_TEXTURE_PAGE_SIZE;
+        let atlas = context.create_image(
+            vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuOnly,
+            texture.format,
+            atlas_size,
+            atlas_size,
+        )?;
+        let sampler = context.create_sampler(
+            &vk::SamplerCreateInfo::builder()
+                .mag_filter(vk::Filter::LINEAR)
+                .min_filter(vk::Filter::LINEAR)
+                .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+        )?;
+        let page_table_sampler = context.create_sampler(
+            &vk::SamplerCreateInfo::builder()
+                .mag_filter(vk::Filter::NEAREST)
+                .min_filter(vk::Filter::NEAREST)
+                .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+                .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+        )?;
+        let feedback = context.create_buffer(
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuOnly,
+            (page_count * std::mem::size_of::<u32>()) as _,
+        )?;
+        let requests = context.create_buffer(
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::TRANSFER_SRC,
+            MemoryLocation::GpuOnly,
+            ((1 + MAX_VIRTUAL_TEXTURE_REQUESTS as usize) * std::mem::size_of::<u32>()) as _,
+        )?;
+
+        // the page table is written whole before its first use, the atlas pages once resident
+        let images = [&page_table, &atlas].map(|image| ImageBarrier {
+            image,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            src_access_mask: vk::AccessFlags2::NONE,
+            dst_access_mask: vk::AccessFlags2::SHADER_READ,
+            src_stage_mask: vk::PipelineStageFlags2::NONE,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        });
+        command_buffer.pipeline_image_barriers(&images);
+        command_buffer.fill_buffer(&feedback, 0, vk::WHOLE_SIZE, 0);
+        command_buffer.pipeline_buffer_barriers(&[BufferBarrier {
+            buffer: &feedback,
+            src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_READ | vk::AccessFlags2::SHADER_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+
+        let descriptor_pool = context.create_descriptor_pool(1, &[vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: 2,
+        }])?;
+        let descriptor_set = descriptor_pool.allocate_set(pipeline.descriptor_set_layout())?;
+        descriptor_set.update(&[
+            WriteDescriptorSet {
+                binding: 0,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: &feedback },
+            },
+            WriteDescriptorSet {
+                binding: 1,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: &requests },
+            },
+        ]);
+
+        let page_source = PageSource {
+            width: texture.width,
+            pages_x: pages.x,
+            pages_y: pages.y,
+        };
+        let (sender, receiver) = async_channel::unbounded();
+        {
+            let sender = sender.clone();
+            let source = texture.source.clone();
+            IoTaskPool::get_or_init(TaskPool::default)
+                .spawn(async move {
+                    // the texture may be gone already
+                    let _ = sender.try_send(PageMessage::Overview(page_source.overview(&source)));
+                })
+                .detach();
+        }
+
+        Ok(Self {
+            page_table_view: page_table.create_image_view()?,
+            page_table,
+            atlas_view: atlas.create_image_view()?,
+            atlas,
+            sampler,
+            page_table_sampler,
+            feedback,
+            requests,
+            _descriptor_pool: descriptor_pool,
+            descriptor_set,
+            texture: texture.clone(),
+            page_source,
+            entries: vec![0; page_count],
+            overview: vec![0; page_count],
+            page_table_dirty: true,
+            cache: PageCache::default(),
+            loading: HashSet::default(),
+            readbacks: VecDeque::new(),
+            sender,
+            receiver,
+        })
+    }
+
+    /// Bindings of the texture in a set of a pipeline sampling it, from `binding` on.
+    ///
+    /// Matches `VT_BINDING` in `shaders/virtual_texture/common.glsl`.
+    pub fn descriptor_writes(&self, binding: u32) -> [WriteDescriptorSet<'_>; 3] {
+        [
+            WriteDescriptorSet {
+                binding,
+                kind: WriteDescriptorSetKind::CombinedImageSampler {
+                    view: &self.page_table_view,
+                    sampler: &self.page_table_sampler,
+                    layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: binding + 1,
+                kind: WriteDescriptorSetKind::CombinedImageSampler {
+                    view: &self.atlas_view,
+                    sampler: &self.sampler,
+                    layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                },
+            },
+            WriteDescriptorSet {
+                binding: binding + 2,
+                kind: WriteDescriptorSetKind::StorageBuffer { buffer: &self.feedback },
+            },
+        ]
+    }
+
+    /// Pages of the texture resident in the atlas.
+    pub fn resident_pages(&self) -> usize {
+        self.cache.resident_count()
+    }
+
+    /// Stream the pages requested by the completed readbacks, then analyse the feedback of the last frame.
+    fn update(
+        &mut self,
+        frame_context: &FrameContext,
+        pipeline: &VirtualTexturePipelineResources,
+        globals: &Globals,
+        frame: u64,
+        staging_buffers: &mut Vec<Buffer>,
+    ) -> anyhow::Result<()> {
+        let context = frame_context.render_context();
+        let command_buffer = frame_context.command_buffer(0).context("The frame has no command buffer.")?;
+
+        while self.readbacks.front().is_some_and(ReadbackBuffer::is_ready) {
+            let Some(bytes) = self.readbacks.pop_front().unwrap().read()? else {
+                break;
+            };
+            self.request_pages(&bytes, frame);
+        }
+
+        let mut uploads = Vec::new();
+        while uploads.len() < MAX_PAGE_UPLOADS_PER_FRAME {
+            match self.receiver.try_recv() {
+                Ok(PageMessage::Overview(overview)) => {
+                    for (entry, color) in self.entries.iter_mut().zip(&overview) {
+                        if *entry & PAGE_TABLE_RESIDENT_BIT == 0 {
+                            *entry = *color;
+                        }
+                    }
+                    self.overview = overview;
+                    self.page_table_dirty = true;
+                }
+                Ok(PageMessage::Page { page, texels }) => {
+                    self.loading.remove(&page);
+                    // requested again later if every slot is in use this frame
+                    let Some((slot, evicted)) = self.cache.allocate(page, frame) else {
+                        continue;
+                    };
+                    if let Some(evicted) = evicted {
+                        self.entries[evicted as usize] = self.overview[evicted as usize];
+                    }
+                    self.entries[page as usize] = resident_entry(slot);
+                    self.page_table_dirty = true;
+                    uploads.push((slot, texels));
+                }
+                Err(_) => break,
+            }
+        }
+
+        if !uploads.is_empty() {
+            self.upload_pages(context, command_buffer, &uploads, staging_buffers)?;
+        }
+        if self.page_table_dirty {
+            let staging_buffer = context.create_buffer(
+                vk::BufferUsageFlags::TRANSFER_SRC,
+                MemoryLocation::CpuToGpu,
+                (self.entries.len() * std::mem::size_of::<u32>()) as _,
+            )?;
+            staging_buffer.copy_data_to_buffer(&self.entries)?;
+            begin_image_upload(command_buffer, &self.page_table);
+            command_buffer.copy_buffer_to_image(&staging_buffer, &self.page_table, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+            end_image_upload(command_buffer, &self.page_table);
+            staging_buffers.push(staging_buffer);
+            self.page_table_dirty = false;
+        }
+
+        self.analyse_feedback(command_buffer, pipeline, globals);
+        self.readbacks.push_back(ReadbackBuffer::new(frame_context, ReadbackSource::Buffer(&self.requests))?);
+        Ok(())
+    }
+
+    /// Read the requested pages from the source in the background, the resident ones are kept longer.
+    fn request_pages(&mut self, bytes: &[u8], frame: u64) {
+        let mut words = bytes.chunks_exact(4).map(|word| u32::from_ne_bytes(word.try_into().unwrap()));
+        let count = words.next().unwrap_or(0).min(MAX_VIRTUAL_TEXTURE_REQUESTS);
+        let page_count = self.entries.len() as u32;
+
+        for page in words.take(count as usize) {
+            if page >= page_count || self.cache.touch(page, frame) || self.loading.contains(&page) {
+                continue;
+            }
+            if self.loading.len() >= MAX_PAGE_LOADS_IN_FLIGHT {
+                // still requested by the next frames
+                continue;
+            }
+            self.loading.insert(page);
+
+            let sender = self.sender.clone();
+            let source = self.texture.source.clone();
+            let page_source = self.page_source;
+            IoTaskPool::get_or_init(TaskPool::default)
+                .spawn(async move {
+                    let texels = page_source.load_page(&source, page);
+                    let _ = sender.try_send(PageMessage::Page { page, texels });
+                })
+                .detach();
+        }
+    }
+
+    fn upload_pages(
+        &self,
+        context: &Context,
+        command_buffer: &CommandBuffer,
+        uploads: &[(u32, Vec<u8>)],
+        staging_buffers: &mut Vec<Buffer>,
+    ) -> anyhow::Result<()> {
+        let page_bytes = (VIRTUAL_TEXTURE_PAGE_SIZE * VIRTUAL_TEXTURE_PAGE_SIZE * 4) as usize;
+        let staging_buffer = context.create_buffer(
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            MemoryLocation::CpuToGpu,
+            (uploads.len() * page_bytes) as _,
+        )?;
+        staging_buffer.copy_data_to_buffer(&uploads.iter().map(|(_, texels)| texels.as_slice()).collect::<Vec<_>>().concat())?;
+
+        begin_image_upload(command_buffer, &self.atlas);
+        for (index, (slot, _)) in uploads.iter().enumerate() {
+            command_buffer.copy_buffer_to_image_region(
+                &staging_buffer,
+                (index * page_bytes) as _,
+                &self.atlas,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::Offset3D {
+                    x: (slot % VIRTUAL_TEXTURE_ATLAS_PAGES * VIRTUAL_TEXTURE_PAGE_SIZE) as i32,
+                    y: (slot / VIRTUAL_TEXTURE_ATLAS_PAGES * VIRTUAL_TEXTURE_PAGE_SIZE) as i32,
+                    z: 0,
+                },
+                vk::Extent3D {
+                    width: VIRTUAL_TEXTURE_PAGE_SIZE,
+                    height: VIRTUAL_TEXTURE_PAGE_SIZE,
+                    depth: 1,
+                },
+            );
+        }
+        end_image_upload(command_buffer, &self.atlas);
+        staging_buffers.push(staging_buffer);
+        Ok(())
+    }
+
+    /// Gather the pages flagged in the feedback into the request list, clearing the flags.
+    fn analyse_feedback(&self, command_buffer: &CommandBuffer, pipeline: &VirtualTexturePipelineResources, globals: &Globals) {
+        let page_count = self.entries.len() as u32;
+
+        // the previous readback of the request list must be done with it
+        command_buffer.pipeline_buffer_barriers(&[BufferBarrier {
+            buffer: &self.requests,
+            src_access_mask: vk::AccessFlags2::NONE,
+            dst_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+        }]);
+        command_buffer.fill_buffer(&self.requests, 0, std::mem::size_of::<u32>() as _, 0);
+        command_buffer.pipeline_buffer_barriers(&[
+            BufferBarrier {
+                buffer: &self.requests,
+                src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                dst_access_mask: vk::AccessFlags2::SHADER_READ | vk::AccessFlags2::SHADER_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            },
+            BufferBarrier {
+                buffer: &self.feedback,
+                src_access_mask: vk::AccessFlags2::SHADER_WRITE,
+                dst_access_mask: vk::AccessFlags2::SHADER_READ | vk::AccessFlags2::SHADER_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            },
+        ]);
+
+        command_buffer.bind_compute_pipeline(&pipeline.feedback);
+        command_buffer.bind_descriptor_sets(vk::PipelineBindPoint::COMPUTE, &pipeline.layout, 0, &[&self.descriptor_set]);
+        globals.bind(command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.layout);
+        command_buffer.push_constants(
+            &pipeline.layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            bytemuck::bytes_of(&VirtualTextureFeedbackPushConstants {
+                page_count,
+                max_requests: MAX_VIRTUAL_TEXTURE_REQUESTS,
+            }),
+        );
+        command_buffer.dispatch(page_count.div_ceil(VIRTUAL_TEXTURE_FEEDBACK_WORKGROUP_SIZE), 1, 1);
+
+        command_buffer.pipeline_buffer_barriers(&[BufferBarrier {
+            buffer: &self.feedback,
+            src_access_mask: vk::AccessFlags2::SHADER_WRITE,
+            dst_access_mask: vk::AccessFlags2::SHADER_READ | vk::AccessFlags2::SHADER_WRITE,
+            src_stage_mask: vk::PipelineStageFlags2::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        }]);
+    }
+}
+
+/// Make a sampled image writable by copies.
+fn begin_image_upload(command_buffer: &CommandBuffer, image: &Image) {
+    command_buffer.pipeline_image_barriers(&[ImageBarrier {
+        image,
+        old_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        src_access_mask: vk::AccessFlags2::NONE,
+        dst_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+        src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+        dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+    }]);
+}
+
+/// Make an image written by copies sampled again.
+fn end_image_upload(command_buffer: &CommandBuffer, image: &Image) {
+    command_buffer.pipeline_image_barriers(&[ImageBarrier {
+        image,
+        old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+        dst_access_mask: vk::AccessFlags2::SHADER_READ,
+        src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+        dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+    }]);
+}
+
+/// The [`VirtualTextureState`] of every entity with a [`VirtualTexture`].
+#[derive(Resource, Default)]
+pub struct VirtualTextures {
+    states: EntityHashMap<Entity, VirtualTextureState>,
+    /// Textures which failed to be created, not retried until they change
+    failed: EntityHashMap<Entity, VirtualTexture>,
+    frame: u64,
+}
+
+impl VirtualTextures {
+    pub fn get(&self, entity: Entity) -> Option<&VirtualTextureState> {
+        self.states.get(&entity)
+    }
+}
+
+pub(crate) fn prepare_virtual_textures(
+    mut textures: ResMut<VirtualTextures>,
+    pipeline: Res<VirtualTexturePipeline>,
+    globals: Res<Globals>,
+    virtual_textures: Query<(Entity, &VirtualTexture)>,
+    mut staging_buffers: ResMut<RenderAssetStagingBuffers>,
+    frame_context: Res<FrameContext>,
+) {
+    let Some(pipeline) = pipeline.resources() else {
+        textures.states.clear();
+        return;
+    };
+    let Some(command_buffer) = frame_context.command_buffer(0) else {
+        return;
+    };
+    let context = frame_context.render_context();
+    let textures = textures.as_mut();
+    textures.frame += 1;
+    let mut alive = HashSet::default();
+
+    for (entity, texture) in virtual_textures.iter() {
+        alive.insert(entity);
+        if textures.failed.get(&entity).is_some_and(|failed| failed.is_same(texture)) {
+            continue;
+        }
+        if !textures.states.get(&entity).is_some_and(|state| state.texture.is_same(texture)) {
+            match VirtualTextureState::new(context, command_buffer, pipeline, texture) {
+                Ok(state) => {
+                    textures.failed.remove(&entity);
+                    textures.states.insert(entity, state);
+                }
+                Err(err) => {
+                    error!("Failed to create virtual texture {entity:?}: {err}");
+                    textures.states.remove(&entity);
+                    textures.failed.insert(entity, texture.clone());
+                    continue;
+                }
+            }
+        }
+
+        let state = textures.states.get_mut(&entity).unwrap();
+        if let Err(err) = state.update(&frame_context, pipeline, &globals, textures.frame, &mut staging_buffers.0) {
+            error!("Failed to stream virtual texture {entity:?}: {err}");
+        }
+    }
+
+    textures.states.retain(|entity, _| alive.contains(entity));
+    textures.failed.retain(|entity, _| alive.contains(entity));
+}
@@ -0,0 +1,129 @@
+use bevy_utils::HashMap;
+use crate::streaming::StreamSource;
+use crate::virtual_texture::{VIRTUAL_TEXTURE_ATLAS_PAGES, VIRTUAL_TEXTURE_PAGE_SIZE};
+
+/// Marks the page table entries of resident pages, the others hold the average color of their page.
+pub const PAGE_TABLE_RESIDENT_BIT: u32 = 0x8000_0000;
+
+/// Texels of a virtual texture, 4 bytes each, read from its [`StreamSource`] on a background thread.
+///
+/// The size of the texture is a whole number of pages.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PageSource {
+    pub width: u32,
+    pub pages_x: u32,
+    pub pages_y: u32,
+}
+
+impl PageSource {
+    fn texel(&self, bytes: &[u8], x: u32, y: u32) -> [u8; 4] {
+        let offset = (y as usize * self.width as usize + x as usize) * 4;
+        bytes[offset..offset + 4].try_into().unwrap()
+    }
+
+    /// The texels of `page`, row after row.
+    pub fn load_page(&self, source: &StreamSource, page: u32) -> Vec<u8> {
+        let bytes = source.bytes();
+        let origin_x = (page % self.pages_x * VIRTUAL_TEXTURE_PAGE_SIZE) as usize;
+        let origin_y = (page / self.pages_x * VIRTUAL_TEXTURE_PAGE_SIZE) as usize;
+        let page_size = VIRTUAL_TEXTURE_PAGE_SIZE as usize;
+        let mut texels = Vec::with_capacity(page_size * page_size * 4);
+
+        for y in origin_y..origin_y + page_size {
+            let row = y * self.width as usize + origin_x;
+            texels.extend_from_slice(&bytes[row * 4..(row + page_size) * 4]);
+        }
+        texels
+    }
+
+    /// Average color of every page from a few texels each, shown until the pages are resident.
+    pub fn overview(&self, source: &StreamSource) -> Vec<u32> {
+        const SAMPLES: u32 = 4;
+        let bytes = source.bytes();
+        let step = VIRTUAL_TEXTURE_PAGE_SIZE / SAMPLES;
+
+        (0..self.pages_x * self.pages_y)
+            .map(|page| {
+                let origin_x = page % self.pages_x * VIRTUAL_TEXTURE_PAGE_SIZE;
+                let origin_y = page / self.pages_x * VIRTUAL_TEXTURE_PAGE_SIZE;
+                let mut sum = [0u32; 3];
+                for sample in 0..SAMPLES * SAMPLES {
+                    let x = origin_x + sample % SAMPLES * step + step / 2;
+                    let y = origin_y + sample / SAMPLES * step + step / 2;
+                    let texel = self.texel(bytes, x, y);
+                    for (sum, &value) in sum.iter_mut().zip(&texel) {
+                        *sum += value as u32;
+                    }
+                }
+                let [r, g, b] = sum.map(|channel| channel / (SAMPLES * SAMPLES));
+                // the alpha byte holds the resident bit, non resident entries are opaque
+                r | (g << 8) | (b << 16)
+            })
+            .collect()
+    }
+}
+
+/// Page table entry of a page resident in `slot` of the atlas.
+pub(crate) fn resident_entry(slot: u32) -> u32 {
+    PAGE_TABLE_RESIDENT_BIT | ((slot % VIRTUAL_TEXTURE_ATLAS_PAGES) << 16) | (slot / VIRTUAL_TEXTURE_ATLAS_PAGES)
+}
+
+/// Which page is in each slot of the atlas, the least recently requested one is evicted first.
+pub(crate) struct PageCache {
+    slots: Vec<Option<u32>>,
+    last_requested: Vec<u64>,
+    resident: HashMap<u32, u32>,
+}
+
+impl Default for PageCache {
+    fn default() -> Self {
+        let slot_count = (VIRTUAL_TEXTURE_ATLAS_PAGES * VIRTUAL_TEXTURE_ATLAS_PAGES) as usize;
+        Self {
+            slots: vec![None; slot_count],
+            last_requested: vec![0; slot_count],
+            resident: HashMap::default(),
+        }
+    }
+}
+
+impl PageCache {
+    #[inline]
+    pub fn resident_count(&self) -> usize {
+        self.resident.len()
+    }
+
+    /// Mark `page` as requested in `frame`, returns whether it is resident.
+    pub fn touch(&mut self, page: u32, frame: u64) -> bool {
+        match self.resident.get(&page) {
+            Some(&slot) => {
+                self.last_requested[slot as usize] = frame;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// A slot for `page`, with the page evicted from it if any.
+    ///
+    /// Pages requested in `frame` are never evicted, `None` when all of them are.
+    pub fn allocate(&mut self, page: u32, frame: u64) -> Option<(u32, Option<u32>)> {
+        let slot = match self.slots.iter().position(Option::is_none) {
+            Some(free) => free,
+            None => self
+                .last_requested
+                .iter()
+                .enumerate()
+                .filter(|(_, &requested)| requested < frame)
+                .min_by_key(|(_, &requested)| requested)
+                .map(|(slot, _)| slot)?,
+        };
+
+        let evicted = self.slots[slot].replace(page);
+        if let Some(evicted) = evicted {
+            self.resident.remove(&evicted);
+        }
+        self.resident.insert(page, slot as u32);
+        self.last_requested[slot] = frame;
+        Some((slot as u32, evicted))
+    }
+}
@@ -0,0 +1,87 @@
+use ash::vk;
+use bytemuck::{Pod, Zeroable};
+use bevy_ecs::prelude::{Query, Res, ResMut, Resource};
+use bevy_log::error;
+use avalanche_hlvk::{ComputePipeline, Context, DescriptorSetLayout, PipelineLayout};
+use crate::extract::FrameContext;
+use crate::globals;
+use crate::shader::ShaderDirectory;
+use crate::virtual_texture::VirtualTexture;
+
+pub(crate) const FEEDBACK_SHADER: &str = "virtual_texture/feedback.comp";
+
+/// Matches `VirtualTextureFeedbackPushConstants` in `shaders/virtual_texture/feedback.comp`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VirtualTextureFeedbackPushConstants {
+    /// Pages of the virtual texture, one feedback flag each
+    pub page_count: u32,
+    /// Capacity of the request list
+    pub max_requests: u32,
+}
+
+// SAFETY: plain 32 bit fields without implicit padding
+unsafe impl Zeroable for VirtualTextureFeedbackPushConstants {}
+unsafe impl Pod for VirtualTextureFeedbackPushConstants {}
+
+pub struct VirtualTexturePipelineResources {
+    /// Set 0 binds the feedback and request buffers of a virtual texture
+    pub layout: PipelineLayout,
+    pub feedback: ComputePipeline,
+}
+
+impl VirtualTexturePipelineResources {
+    #[inline]
+    pub fn descriptor_set_layout(&self) -> &DescriptorSetLayout {
+        &self.layout.descriptor_set_layouts()[0]
+    }
+}
+
+/// The feedback analysis pipeline, created the first time a [`VirtualTexture`] is extracted.
+#[derive(Resource, Default)]
+pub enum VirtualTexturePipeline {
+    #[default]
+    Uninitialized,
+    Ready(Box<VirtualTexturePipelineResources>),
+    /// Creation failed, usually shaders are missing.
+    Failed,
+}
+
+impl VirtualTexturePipeline {
+    pub fn resources(&self) -> Option<&VirtualTexturePipelineResources> {
+        match self {
+            VirtualTexturePipeline::Ready(resources) => Some(resources),
+            _ => None,
+        }
+    }
+}
+
+fn create_resources(context: &Context, shaders: &ShaderDirectory) -> anyhow::Result<VirtualTexturePipelineResources> {
+    let stage = shaders.load(context, FEEDBACK_SHADER, vk::ShaderStageFlags::COMPUTE)?;
+    let layout = globals::pipeline_layout(std::slice::from_ref(&stage))?;
+    anyhow::ensure!(globals::pass_set_count(std::slice::from_ref(&stage)) == 1, "virtual texture feedback shader must only use set 0");
+
+    Ok(VirtualTexturePipelineResources {
+        feedback: context.create_compute_pipeline(&layout, &stage)?,
+        layout,
+    })
+}
+
+pub(crate) fn prepare_virtual_texture_pipeline(
+    mut pipeline: ResMut<VirtualTexturePipeline>,
+    shaders: Res<ShaderDirectory>,
+    textures: Query<&VirtualTexture>,
+    frame_context: Res<FrameContext>,
+) {
+    if !matches!(*pipeline, VirtualTexturePipeline::Uninitialized) || textures.is_empty() {
+        return;
+    }
+
+    *pipeline = match create_resources(frame_context.render_context(), &shaders) {
+        Ok(resources) => VirtualTexturePipeline::Ready(Box::new(resources)),
+        Err(err) => {
+            error!("Failed to create virtual texture pipeline: {err}");
+            VirtualTexturePipeline::Failed
+        }
+    };
+}
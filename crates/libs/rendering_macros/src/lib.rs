@@ -0,0 +1,71 @@
+mod extract_component;
+mod extract_resource;
+mod shader_type;
+
+use proc_macro::TokenStream;
+
+/// Path to `avalanche_rendering`, `crate` if the derive is used inside of it.
+pub(crate) fn avalanche_rendering_path() -> syn::Path {
+    if std::env::var("CARGO_CRATE_NAME").is_ok_and(|name| name == "avalanche_rendering") {
+        syn::parse_quote!(crate)
+    } else {
+        syn::parse_quote!(::avalanche_rendering)
+    }
+}
+
+/// Implements `ExtractResource` for a resource by cloning it into the render world.
+/// The resource must implement [`Clone`].
+///
+/// See `ExtractResourcePlugin` to actually perform the extraction.
+#[proc_macro_derive(ExtractResource)]
+pub fn derive_extract_resource(input: TokenStream) -> TokenStream {
+    extract_resource::derive_extract_resource(input)
+}
+
+/// Implements `ExtractComponent` trait for a component.
+/// The component must implement [`Clone`].
+/// The component will be extracted into the render world via cloning.
+/// Note that this only enables extraction of the component, it does not execute the extraction.
+/// See `ExtractComponentPlugin` to actually perform the extraction.
+///
+/// If you only want to extract a component conditionally, you may use the `extract_component_filter` attribute.
+///
+/// # Example
+///
+/// ```ignore
+/// use bevy_ecs::prelude::{Component, With};
+/// use avalanche_rendering::extract::ExtractComponent;
+///
+/// #[derive(Component, Clone, ExtractComponent)]
+/// #[extract_component_filter(With<Camera>)]
+/// pub struct Foo {
+///     pub should_foo: bool,
+/// }
+/// ```
+#[proc_macro_derive(ExtractComponent, attributes(extract_component_filter))]
+pub fn derive_extract_component(input: TokenStream) -> TokenStream {
+    extract_component::derive_extract_component(input)
+}
+
+/// Implements `ShaderType` for a struct whose fields all implement it,
+/// computing the std140 and std430 offsets of the fields in declaration order.
+///
+/// # Example
+///
+/// ```ignore
+/// use bevy_math::{Mat4, Vec3};
+/// use avalanche_rendering::shader::ShaderType;
+///
+/// #[derive(ShaderType)]
+/// pub struct Light {
+///     pub view_projection: Mat4,
+///     pub color: Vec3,
+///     pub intensity: f32,
+/// }
+///
+/// let bytes = light.std140_bytes();
+/// ```
+#[proc_macro_derive(ShaderType)]
+pub fn derive_shader_type(input: TokenStream) -> TokenStream {
+    shader_type::derive_shader_type(input)
+}
@@ -0,0 +1,46 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Index, Member};
+
+pub fn derive_shader_type(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+    let rendering_path = crate::avalanche_rendering_path();
+    let layout_path = quote! { #rendering_path::shader };
+
+    let Data::Struct(data) = &ast.data else {
+        return syn::Error::new_spanned(&ast.ident, "ShaderType can only be derived for structs")
+            .to_compile_error()
+            .into();
+    };
+    let members = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        })
+        .collect::<Vec<_>>();
+    let types = data.fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+
+    let struct_name = &ast.ident;
+    let (impl_generics, type_generics, where_clause) = &ast.generics.split_for_impl();
+
+    TokenStream::from(quote! {
+        impl #impl_generics #layout_path::ShaderType for #struct_name #type_generics #where_clause {
+            fn layout(rules: #layout_path::LayoutRules) -> #layout_path::TypeLayout {
+                let mut layout = #layout_path::StructLayout::new(rules);
+                #(layout.field(<#types as #layout_path::ShaderType>::layout(rules));)*
+                layout.finish()
+            }
+
+            fn write(&self, rules: #layout_path::LayoutRules, out: &mut [u8]) {
+                let mut layout = #layout_path::StructLayout::new(rules);
+                #(
+                    let offset = layout.field(<#types as #layout_path::ShaderType>::layout(rules));
+                    #layout_path::ShaderType::write(&self.#members, rules, &mut out[offset..]);
+                )*
+            }
+        }
+    })
+}
@@ -0,0 +1,283 @@
+use std::any::TypeId;
+use std::path::{Path, PathBuf};
+use bevy_ecs::entity::Entity;
+use bevy_ecs::prelude::{AppTypeRegistry, ReflectComponent, Resource, World};
+use bevy_ecs::reflect::ReflectMapEntities;
+use bevy_ecs::world::{EntityRef, EntityWorldMut};
+use bevy_log::warn;
+use bevy_reflect::{Reflect, ReflectFromReflect, TypeRegistry};
+use bevy_reflect::serde::{TypedReflectDeserializer, TypedReflectSerializer};
+use bevy_hierarchy::Parent;
+use bevy_utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use avalanche_asset::{Asset, AssetServer, Handle};
+use crate::format::{self, SceneFormatError};
+
+const SCENE_MAGIC: [u8; 4] = *b"AVSN";
+const SCENE_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum SceneSpawnError {
+    #[error("component {0} is not a registered reflected component")]
+    UnregisteredComponent(String),
+    #[error("handles of {0} were not registered with `SceneApp::register_scene_handle`")]
+    UnregisteredHandle(String),
+    #[error("the scene references assets but there is no AssetServer")]
+    MissingAssetServer,
+}
+
+/// A set of entities with their reflected components, independent of any [`World`].
+///
+/// Only components registered in the [`AppTypeRegistry`] with `#[reflect(Component)]` are captured,
+/// asset handles are stored as paths and loaded again when the scene is spawned.
+#[derive(Default)]
+pub struct DynamicScene {
+    pub entities: Vec<DynamicEntity>,
+}
+
+impl Asset for DynamicScene {}
+
+pub struct DynamicEntity {
+    /// Id of the entity in the world the scene was created from, used to remap entity references.
+    pub entity: Entity,
+    pub components: Vec<Box<dyn Reflect>>,
+    pub handles: Vec<SceneHandle>,
+}
+
+/// Asset handle of a [`DynamicEntity`], see [`SceneHandleRegistry`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SceneHandle {
+    /// Type name of the asset
+    pub asset_type: String,
+    pub path: PathBuf,
+}
+
+impl DynamicScene {
+    /// Capture `entities` of `world`, missing entities are ignored.
+    ///
+    /// The [`Parent`] of entities whose parent isn't captured is left out, they become roots of the scene
+    /// and are parented to the [`SceneRoot`](crate::SceneRoot) they are spawned for. Children should be captured
+    /// with their parents.
+    pub fn from_world(world: &World, entities: impl IntoIterator<Item = Entity>) -> Self {
+        let type_registry = world.resource::<AppTypeRegistry>().read();
+        let handle_types = world.get_resource::<SceneHandleRegistry>();
+        let entities = entities.into_iter().collect::<Vec<_>>();
+        let captured = entities.iter().copied().collect::<HashSet<_>>();
+
+        let entities = entities
+            .into_iter()
+            .filter_map(|entity| world.get_entity(entity))
+            .map(|entity_ref| {
+                let outside_parent = entity_ref
+                    .get::<Parent>()
+                    .is_some_and(|parent| !captured.contains(&parent.get()));
+                let components = entity_ref
+                    .archetype()
+                    .components()
+                    .filter_map(|component_id| world.components().get_info(component_id)?.type_id())
+                    .filter(|&type_id| !(outside_parent && type_id == TypeId::of::<Parent>()))
+                    .filter_map(|type_id| {
+                        let reflect_component = type_registry.get_type_data::<ReflectComponent>(type_id)?;
+                        let component = reflect_component.reflect(entity_ref)?;
+                        // concrete values serialize the same way they are deserialized
+                        let component = type_registry
+                            .get_type_data::<ReflectFromReflect>(type_id)
+                            .and_then(|from_reflect| from_reflect.from_reflect(component))
+                            .unwrap_or_else(|| component.clone_value());
+                        Some(component)
+                    })
+                    .collect();
+                let handles = handle_types
+                    .map(|handle_types| handle_types.extract(entity_ref))
+                    .unwrap_or_default();
+
+                DynamicEntity {
+                    entity: entity_ref.id(),
+                    components,
+                    handles,
+                }
+            })
+            .collect();
+
+        Self { entities }
+    }
+
+    /// Spawn the entities of the scene into `world`.
+    ///
+    /// Scene entities already in `entity_map` are updated instead of spawned, the map is filled with the new ones.
+    /// Entity references inside components are remapped through [`ReflectMapEntities`].
+    pub fn write_to_world(&self, world: &mut World, entity_map: &mut HashMap<Entity, Entity>) -> Result<(), SceneSpawnError> {
+        let type_registry = world.resource::<AppTypeRegistry>().clone();
+        let type_registry = type_registry.read();
+        let handle_types = world.get_resource::<SceneHandleRegistry>().cloned().unwrap_or_default();
+        let asset_server = world.get_resource::<AssetServer>().cloned();
+
+        for scene_entity in &self.entities {
+            let entity = *entity_map
+                .entry(scene_entity.entity)
+                .or_insert_with(|| world.spawn_empty().id());
+            let mut entity_mut = world.entity_mut(entity);
+
+            for component in &scene_entity.components {
+                let reflect_component = component
+                    .get_represented_type_info()
+                    .and_then(|type_info| type_registry.get_type_data::<ReflectComponent>(type_info.type_id()))
+                    .ok_or_else(|| SceneSpawnError::UnregisteredComponent(component.reflect_type_path().to_string()))?;
+                reflect_component.apply_or_insert(&mut entity_mut, &**component);
+            }
+
+            for handle in &scene_entity.handles {
+                let asset_server = asset_server.as_ref().ok_or(SceneSpawnError::MissingAssetServer)?;
+                handle_types.insert(&mut entity_mut, asset_server, handle)?;
+            }
+        }
+
+        let spawned = self.entities
+            .iter()
+            .map(|scene_entity| entity_map[&scene_entity.entity])
+            .collect::<Vec<_>>();
+        for map_entities in type_registry.iter().filter_map(|registration| registration.data::<ReflectMapEntities>()) {
+            map_entities.map_entities(world, entity_map, &spawned);
+        }
+
+        Ok(())
+    }
+
+    /// Encode the scene into the binary scene file format.
+    pub fn to_bytes(&self, type_registry: &TypeRegistry) -> Result<Vec<u8>, SceneFormatError> {
+        let entities = self.entities
+            .iter()
+            .map(|entity| {
+                let components = entity.components
+                    .iter()
+                    .map(|component| {
+                        Ok(SceneFileComponent {
+                            type_path: component.reflect_type_path().to_string(),
+                            data: format::to_bytes(&TypedReflectSerializer::new(&**component, type_registry))?,
+                        })
+                    })
+                    .collect::<Result<_, SceneFormatError>>()?;
+                Ok(SceneFileEntity {
+                    entity: entity.entity.to_bits(),
+                    components,
+                    handles: entity.handles.clone(),
+                })
+            })
+            .collect::<Result<_, SceneFormatError>>()?;
+
+        format::to_bytes(&SceneFile {
+            magic: SCENE_MAGIC,
+            version: SCENE_VERSION,
+            entities,
+        })
+    }
+
+    /// Decode a scene file, components of unregistered types are skipped with a warning.
+    pub fn from_bytes(bytes: &[u8], type_registry: &TypeRegistry) -> Result<Self, SceneFormatError> {
+        let file: SceneFile = format::from_bytes(bytes)?;
+        if file.magic != SCENE_MAGIC {
+            return Err(SceneFormatError::InvalidMagic);
+        }
+        if file.version != SCENE_VERSION {
+            return Err(SceneFormatError::UnsupportedVersion(file.version));
+        }
+
+        let entities = file.entities
+            .into_iter()
+            .map(|entity| {
+                let mut components = Vec::with_capacity(entity.components.len());
+                for component in entity.components {
+                    let Some(registration) = type_registry.get_with_type_path(&component.type_path) else {
+                        warn!("Skipped component {} of a scene, its type is not registered", component.type_path);
+                        continue;
+                    };
+                    let deserializer = TypedReflectDeserializer::new(registration, type_registry);
+                    components.push(format::from_bytes_seed(deserializer, &component.data)?);
+                }
+                Ok(DynamicEntity {
+                    entity: Entity::from_bits(entity.entity),
+                    components,
+                    handles: entity.handles,
+                })
+            })
+            .collect::<Result<_, SceneFormatError>>()?;
+
+        Ok(Self { entities })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SceneFile {
+    magic: [u8; 4],
+    version: u32,
+    entities: Vec<SceneFileEntity>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SceneFileEntity {
+    entity: u64,
+    components: Vec<SceneFileComponent>,
+    handles: Vec<SceneHandle>,
+}
+
+/// Component values are encoded separately so unknown types can be skipped.
+#[derive(Serialize, Deserialize)]
+struct SceneFileComponent {
+    type_path: String,
+    data: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+struct SceneHandleType {
+    asset_type: &'static str,
+    path_of: fn(EntityRef) -> Option<PathBuf>,
+    load: fn(&mut EntityWorldMut, &AssetServer, &Path),
+}
+
+/// Asset types whose [`Handle`] components are saved in scenes, registered with
+/// [`SceneApp::register_scene_handle`](crate::SceneApp::register_scene_handle).
+///
+/// Only handles of loaded assets have a path, handles of assets added at runtime aren't saved.
+#[derive(Resource, Clone, Default)]
+pub struct SceneHandleRegistry {
+    handle_types: Vec<SceneHandleType>,
+}
+
+impl SceneHandleRegistry {
+    pub fn register<T: Asset>(&mut self) {
+        let asset_type = std::any::type_name::<T>();
+        if self.handle_types.iter().any(|handle_type| handle_type.asset_type == asset_type) {
+            return;
+        }
+
+        self.handle_types.push(SceneHandleType {
+            asset_type,
+            path_of: |entity| Some(entity.get::<Handle<T>>()?.path()?.to_path_buf()),
+            load: |entity, asset_server, path| {
+                entity.insert(asset_server.load::<T>(path));
+            },
+        });
+    }
+
+    fn extract(&self, entity: EntityRef) -> Vec<SceneHandle> {
+        self.handle_types
+            .iter()
+            .filter_map(|handle_type| {
+                Some(SceneHandle {
+                    asset_type: handle_type.asset_type.to_string(),
+                    path: (handle_type.path_of)(entity)?,
+                })
+            })
+            .collect()
+    }
+
+    fn insert(&self, entity: &mut EntityWorldMut, asset_server: &AssetServer, handle: &SceneHandle) -> Result<(), SceneSpawnError> {
+        let handle_type = self.handle_types
+            .iter()
+            .find(|handle_type| handle_type.asset_type == handle.asset_type)
+            .ok_or_else(|| SceneSpawnError::UnregisteredHandle(handle.asset_type.clone()))?;
+        (handle_type.load)(entity, asset_server, &handle.path);
+        Ok(())
+    }
+}
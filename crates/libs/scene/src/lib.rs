@@ -0,0 +1,45 @@
+mod dynamic_scene;
+mod loader;
+mod spawner;
+pub mod format;
+
+pub use dynamic_scene::*;
+pub use loader::*;
+pub use spawner::*;
+
+use bevy_app::{App, Plugin, PreUpdate};
+use bevy_ecs::prelude::{AppTypeRegistry, IntoSystemConfigs};
+use avalanche_asset::{Asset, AssetApp, AssetSet};
+
+/// Adds the [`DynamicScene`] asset, loaded from `.scene` files and spawned through [`SceneRoot`].
+pub struct ScenePlugin;
+
+impl Plugin for ScenePlugin {
+    fn build(&self, app: &mut App) {
+        let type_registry = app.world.resource::<AppTypeRegistry>().0.clone();
+
+        app.init_asset::<DynamicScene>()
+            .register_asset_loader(SceneLoader::new(type_registry))
+            .init_resource::<SceneHandleRegistry>()
+            .add_systems(
+                PreUpdate,
+                (despawn_modified_scenes, spawn_scenes)
+                    .chain()
+                    .after(AssetSet::TrackAssets),
+            );
+    }
+}
+
+pub trait SceneApp {
+    /// Save [`Handle<T>`](avalanche_asset::Handle) components in scenes by the path of their asset.
+    fn register_scene_handle<T: Asset>(&mut self) -> &mut Self;
+}
+
+impl SceneApp for App {
+    fn register_scene_handle<T: Asset>(&mut self) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(SceneHandleRegistry::default)
+            .register::<T>();
+        self
+    }
+}
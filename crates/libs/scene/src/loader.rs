@@ -0,0 +1,27 @@
+use std::path::Path;
+use bevy_reflect::TypeRegistryArc;
+use avalanche_asset::AssetLoader;
+use crate::DynamicScene;
+
+/// Loads `.scene` files written by [`DynamicScene::to_bytes`].
+pub struct SceneLoader {
+    type_registry: TypeRegistryArc,
+}
+
+impl SceneLoader {
+    pub fn new(type_registry: TypeRegistryArc) -> Self {
+        Self { type_registry }
+    }
+}
+
+impl AssetLoader for SceneLoader {
+    type Asset = DynamicScene;
+
+    fn extensions(&self) -> &[&str] {
+        &["scene"]
+    }
+
+    fn load(&self, bytes: &[u8], _path: &Path) -> anyhow::Result<DynamicScene> {
+        Ok(DynamicScene::from_bytes(bytes, &self.type_registry.read())?)
+    }
+}
@@ -0,0 +1,87 @@
+use bevy_ecs::prelude::{Commands, Component, Entity, EventReader, Mut, Query, QueryState, Without, World};
+use bevy_hierarchy::{BuildWorldChildren, DespawnRecursiveExt, Parent};
+use bevy_log::error;
+use bevy_transform::prelude::{GlobalTransform, TransformBundle};
+use bevy_utils::HashMap;
+use avalanche_asset::{AssetEvent, Assets, Handle};
+use crate::DynamicScene;
+
+/// Spawns the entities of a scene once it is loaded, and again whenever it is reloaded.
+///
+/// The root entities of the scene become children of this entity, so they follow its transform
+/// and are despawned with it by [`despawn_recursive`](DespawnRecursiveExt::despawn_recursive).
+#[derive(Component, Clone)]
+pub struct SceneRoot(pub Handle<DynamicScene>);
+
+/// Entities spawned for the [`SceneRoot`] of this entity.
+#[derive(Component, Default)]
+pub struct SceneInstance {
+    entities: Vec<Entity>,
+}
+
+impl SceneInstance {
+    #[inline]
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+}
+
+/// Despawn the instances of modified scenes, so they are spawned again with the new content.
+pub(crate) fn despawn_modified_scenes(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<DynamicScene>>,
+    roots: Query<(Entity, &SceneRoot, &SceneInstance)>,
+) {
+    for event in events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        for (root, _, instance) in roots.iter().filter(|(_, scene_root, _)| scene_root.0.id() == *id) {
+            for &entity in &instance.entities {
+                if let Some(entity) = commands.get_entity(entity) {
+                    entity.despawn_recursive();
+                }
+            }
+            commands.entity(root).remove::<SceneInstance>();
+        }
+    }
+}
+
+pub(crate) fn spawn_scenes(
+    world: &mut World,
+    pending_roots: &mut QueryState<(Entity, &SceneRoot), Without<SceneInstance>>,
+) {
+    let pending_roots = pending_roots
+        .iter(world)
+        .map(|(root, scene_root)| (root, scene_root.0.clone()))
+        .collect::<Vec<_>>();
+    if pending_roots.is_empty() {
+        return;
+    }
+
+    world.resource_scope(|world, scenes: Mut<Assets<DynamicScene>>| {
+        for (root, handle) in pending_roots {
+            let Some(scene) = scenes.get(&handle) else {
+                continue;
+            };
+
+            let mut entity_map = HashMap::default();
+            if let Err(err) = scene.write_to_world(world, &mut entity_map) {
+                error!("Failed to spawn {:?}: {err}", handle.id());
+            }
+            let entities = entity_map.into_values().collect::<Vec<_>>();
+            let scene_roots = entities
+                .iter()
+                .copied()
+                .filter(|&entity| world.get_entity(entity).is_some_and(|entity| !entity.contains::<Parent>()))
+                .collect::<Vec<_>>();
+            // a failed scene isn't retried until it is modified
+            let mut root = world.entity_mut(root);
+            root.push_children(&scene_roots).insert(SceneInstance { entities });
+            // transforms only propagate from roots with a transform
+            if !root.contains::<GlobalTransform>() {
+                root.insert(TransformBundle::default());
+            }
+        }
+    });
+}
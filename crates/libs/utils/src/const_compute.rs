@@ -9,6 +9,10 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+mod hash;
+
+pub use hash::*;
+
 #[derive(Clone, Copy)]
 pub(crate) enum ParseIntError {
     InvalidDigit,
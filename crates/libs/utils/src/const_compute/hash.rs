@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+const FNV1A_32_OFFSET: u32 = 0x811c_9dc5;
+const FNV1A_32_PRIME: u32 = 0x0100_0193;
+const FNV1A_64_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV1A_64_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 32 bit FNV-1a hash of `bytes`, usable in constants.
+pub const fn fnv1a_32(bytes: &[u8]) -> u32 {
+    let mut hash = FNV1A_32_OFFSET;
+    let mut index = 0;
+    while index < bytes.len() {
+        hash ^= bytes[index] as u32;
+        hash = hash.wrapping_mul(FNV1A_32_PRIME);
+        index += 1;
+    }
+    hash
+}
+
+/// 64 bit FNV-1a hash of `bytes`, usable in constants.
+pub const fn fnv1a_64(bytes: &[u8]) -> u64 {
+    fnv1a_64_continue(FNV1A_64_OFFSET, bytes)
+}
+
+/// Feed more `bytes` into a [`fnv1a_64`] hash, hashing parts one after the other hashes them concatenated.
+pub const fn fnv1a_64_continue(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut index = 0;
+    while index < bytes.len() {
+        hash ^= bytes[index] as u64;
+        hash = hash.wrapping_mul(FNV1A_64_PRIME);
+        index += 1;
+    }
+    hash
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE) checksum of `bytes`, usable in constants.
+pub const fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    let mut index = 0;
+    while index < bytes.len() {
+        crc = CRC32_TABLE[((crc ^ bytes[index] as u32) & 0xff) as usize] ^ (crc >> 8);
+        index += 1;
+    }
+    !crc
+}
+
+/// Id of a name, its [`fnv1a_64`] hash.
+///
+/// Two names may collide, keep the name around to tell them apart where that matters.
+#[inline]
+pub const fn str_id(name: &str) -> u64 {
+    fnv1a_64(name.as_bytes())
+}
+
+/// Mix `value` into `seed`, the result depends on the order values are combined in.
+#[inline]
+pub const fn hash_combine(seed: u64, value: u64) -> u64 {
+    // boost::hash_combine widened to 64 bits
+    seed ^ (value
+        .wrapping_add(0x9e37_79b9_7f4a_7c15)
+        .wrapping_add(seed << 6)
+        .wrapping_add(seed >> 2))
+}
+
+/// Id of a set of names whatever their order, e.g. the defines a shader is compiled with.
+pub const fn str_set_id(names: &[&str]) -> u64 {
+    let mut hash = 0u64;
+    let mut index = 0;
+    while index < names.len() {
+        // the ids are mixed before summing, so that repeated names don't cancel out
+        hash = hash.wrapping_add(hash_combine(0, str_id(names[index])));
+        index += 1;
+    }
+    hash
+}
+
+/// The [`str_id`] of a string literal, computed at compile time.
+///
+/// ```ignore
+/// const OPAQUE: u64 = const_hash!("opaque");
+/// ```
+#[macro_export]
+macro_rules! const_hash {
+    ($name:expr) => {{
+        const HASH: u64 = $crate::str_id($name);
+        HASH
+    }};
+}
+
+/// Hasher of keys that are hashes already, e.g. a [`str_id`], passing their `u64` through.
+///
+/// Only integer keys are supported, hashing anything else panics.
+#[derive(Default, Clone, Copy)]
+pub struct PassThroughHasher(u64);
+
+impl Hasher for PassThroughHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {
+        panic!("PassThroughHasher only hashes integers");
+    }
+
+    #[inline]
+    fn write_u32(&mut self, value: u32) {
+        self.0 = value as u64;
+    }
+
+    #[inline]
+    fn write_u64(&mut self, value: u64) {
+        self.0 = value;
+    }
+
+    #[inline]
+    fn write_usize(&mut self, value: usize) {
+        self.0 = value as u64;
+    }
+}
+
+/// A map keyed by precomputed hashes, which aren't hashed again.
+pub type PreHashedMap<K, V> = HashMap<K, V, BuildHasherDefault<PassThroughHasher>>;
+
+#[test]
+fn test_hashes() {
+    assert_eq!(fnv1a_32(b""), 0x811c_9dc5);
+    assert_eq!(fnv1a_32(b"a"), 0xe40c_292c);
+    assert_eq!(fnv1a_64(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(fnv1a_64(b"a"), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(fnv1a_64(b"foobar"), 0x8594_4171_f739_67e8);
+    assert_eq!(fnv1a_64_continue(fnv1a_64(b"foo"), b"bar"), fnv1a_64(b"foobar"));
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+    const OPAQUE: u64 = const_hash!("opaque");
+    assert_eq!(OPAQUE, str_id("opaque"));
+    assert_eq!(str_set_id(&["A", "B", "C"]), str_set_id(&["C", "A", "B"]));
+    assert_ne!(str_set_id(&["A", "A"]), str_set_id(&[]));
+    assert_ne!(str_set_id(&["A", "B"]), str_set_id(&["A", "C"]));
+
+    let mut map = PreHashedMap::default();
+    map.insert(OPAQUE, "opaque");
+    assert_eq!(map.get(&str_id("opaque")), Some(&"opaque"));
+}
@@ -0,0 +1,198 @@
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+
+/// An index into a slot with the generation of the slot when the id was allocated.
+///
+/// Slots are reused once freed with a new generation, ids kept after being freed never match the new occupant.
+#[derive(Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub struct GenerationalId {
+    index: u32,
+    generation: NonZeroU32,
+}
+
+impl GenerationalId {
+    #[inline]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    #[inline]
+    pub fn generation(&self) -> NonZeroU32 {
+        self.generation
+    }
+
+    /// The id packed into a `u64`, the generation in the high bits.
+    #[inline]
+    pub fn to_bits(&self) -> u64 {
+        ((self.generation.get() as u64) << 32) | self.index as u64
+    }
+
+    /// `None` for bits with a zero generation, which no id has.
+    #[inline]
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        Some(Self {
+            index: bits as u32,
+            generation: NonZeroU32::new((bits >> 32) as u32)?,
+        })
+    }
+}
+
+#[derive(Default)]
+struct AllocatorState {
+    /// Generation of the id in each slot, or of the next one for free slots
+    generations: Vec<NonZeroU32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+}
+
+/// Allocates [`GenerationalId`]s, recycling the slots of freed ids.
+///
+/// A slot whose generation would overflow is retired instead of reused.
+#[derive(Default)]
+pub struct GenerationalIdAllocator {
+    state: Mutex<AllocatorState>,
+}
+
+impl GenerationalIdAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allocate(&self) -> GenerationalId {
+        let mut state = self.state.lock().unwrap();
+        if let Some(index) = state.free.pop() {
+            state.alive[index as usize] = true;
+            return GenerationalId {
+                index,
+                generation: state.generations[index as usize],
+            };
+        }
+
+        let index = u32::try_from(state.generations.len()).expect("The system ran out of generational id slots.");
+        state.generations.push(NonZeroU32::MIN);
+        state.alive.push(true);
+        GenerationalId {
+            index,
+            generation: NonZeroU32::MIN,
+        }
+    }
+
+    /// Free an id for its slot to be reused, returns false for stale or unknown ids.
+    pub fn free(&self, id: GenerationalId) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let index = id.index as usize;
+        if !state.alive.get(index).is_some_and(|alive| *alive) || state.generations[index] != id.generation {
+            return false;
+        }
+
+        state.alive[index] = false;
+        if let Some(generation) = id.generation.checked_add(1) {
+            state.generations[index] = generation;
+            state.free.push(id.index);
+        }
+        true
+    }
+
+    /// Whether an id was allocated and not freed yet.
+    pub fn is_alive(&self, id: GenerationalId) -> bool {
+        let state = self.state.lock().unwrap();
+        let index = id.index as usize;
+        state.alive.get(index).is_some_and(|alive| *alive) && state.generations[index] == id.generation
+    }
+
+    /// Number of ids alive.
+    pub fn len(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.alive.iter().filter(|alive| **alive).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn allocate_handle<T>(&self) -> Handle<T> {
+        Handle::from_id(self.allocate())
+    }
+}
+
+/// A [`GenerationalId`] typed by what it refers to, so ids of different kinds of objects can't be mixed up.
+pub struct Handle<T> {
+    id: GenerationalId,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    #[inline]
+    pub fn from_id(id: GenerationalId) -> Self {
+        Self {
+            id,
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> GenerationalId {
+        self.id
+    }
+}
+
+// implemented by hand, deriving would require `T` to implement them too
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> Debug for Handle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Handle<{}>({}v{})",
+            std::any::type_name::<T>(),
+            self.id.index,
+            self.id.generation
+        )
+    }
+}
+
+#[test]
+fn test_recycle() {
+    let allocator = GenerationalIdAllocator::new();
+    let first = allocator.allocate();
+    let second = allocator.allocate();
+    assert_ne!(first, second);
+
+    assert!(allocator.free(first));
+    assert!(!allocator.free(first));
+    assert!(!allocator.is_alive(first));
+
+    let recycled = allocator.allocate();
+    assert_eq!(recycled.index(), first.index());
+    assert_ne!(recycled, first);
+    assert!(allocator.is_alive(recycled));
+    assert!(allocator.is_alive(second));
+    assert_eq!(allocator.len(), 2);
+
+    assert_eq!(GenerationalId::from_bits(recycled.to_bits()), Some(recycled));
+    assert_eq!(GenerationalId::from_bits(7), None);
+}
@@ -45,54 +45,101 @@ pub static ID_GENERATOR_32_STATIC: Lazy<IdGenerator32> = Lazy::new(IdGenerator32
 
 pub static ID_GENERATOR_64_STATIC: Lazy<IdGenerator64> = Lazy::new(IdGenerator64::new);
 
+/// Defines an id type whose values are taken from a global counter, unique until the counter overflows.
+///
+/// Ids are backed by a `NonZero` integer so an `Option` of them is the size of the id.
+/// The backing integer is `u32` by default, `u64` and `usize` can be given after the name.
+/// Running out of ids panics, unless `wrap` is given to restart from 1, which may reuse ids still alive.
+///
+/// ```ignore
+/// define_atomic_id!(NodeId);
+/// define_atomic_id!(ImageId: u64);
+/// define_atomic_id!(FrameTag: u32, wrap);
+/// ```
 #[macro_export]
 macro_rules! define_atomic_id {
     ($atomic_id_type:ident) => {
-        #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
-        pub struct $atomic_id_type(core::num::NonZeroU32);
+        $crate::define_atomic_id!($atomic_id_type: u32);
+    };
+    ($atomic_id_type:ident: u32 $(, $overflow:ident)?) => {
+        $crate::define_atomic_id!(@define $atomic_id_type, u32, AtomicU32, NonZeroU32 $(, $overflow)?);
+    };
+    ($atomic_id_type:ident: u64 $(, $overflow:ident)?) => {
+        $crate::define_atomic_id!(@define $atomic_id_type, u64, AtomicU64, NonZeroU64 $(, $overflow)?);
+    };
+    ($atomic_id_type:ident: usize $(, $overflow:ident)?) => {
+        $crate::define_atomic_id!(@define $atomic_id_type, usize, AtomicUsize, NonZeroUsize $(, $overflow)?);
+    };
+    (@define $atomic_id_type:ident, $int:ty, $atomic:ident, $non_zero:ident) => {
+        $crate::define_atomic_id!(@define $atomic_id_type, $int, $atomic, $non_zero, panic);
+    };
+    (@define $atomic_id_type:ident, $int:ty, $atomic:ident, $non_zero:ident, $overflow:ident) => {
+        #[derive(Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd, Debug)]
+        pub struct $atomic_id_type(core::num::$non_zero);
 
         impl $atomic_id_type {
             pub fn new() -> Self {
-                use std::sync::atomic::{AtomicU32, Ordering};
-
-                static COUNTER: AtomicU32 = AtomicU32::new(1);
-
-                let counter = COUNTER.fetch_add(1, Ordering::Relaxed);
-                Self (
-                    core::num::NonZeroU32::new(counter).unwrap_or_else(|| {
-                        panic!(
-                            "The system ran out of unique `{}`s.",
-                            stringify!($atomic_id_type)
-                        );
-                    })
-                )
+                use std::sync::atomic::{$atomic, Ordering};
+
+                static COUNTER: $atomic = $atomic::new(1);
+
+                let counter = $crate::define_atomic_id!(@next COUNTER, $atomic_id_type, $overflow);
+                // the counter starts at 1 and skips 0 when wrapping
+                Self(core::num::$non_zero::new(counter).unwrap())
+            }
+
+            #[inline]
+            pub fn get(&self) -> $int {
+                self.0.get()
             }
         }
     };
+    (@next $counter:ident, $atomic_id_type:ident, panic) => {
+        $counter
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |counter| counter.checked_add(1))
+            .unwrap_or_else(|_| {
+                panic!(
+                    "The system ran out of unique `{}`s.",
+                    stringify!($atomic_id_type)
+                );
+            })
+    };
+    (@next $counter:ident, $atomic_id_type:ident, wrap) => {
+        $counter
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |counter| Some(counter.checked_add(1).unwrap_or(1)))
+            .unwrap()
+    };
 }
 
+/// Defines a `usize` backed id, kept for callers written before `define_atomic_id!` took a backing type.
+#[deprecated(note = "use `define_atomic_id!(Name: usize)` instead")]
 #[macro_export]
 macro_rules! define_atomic_id_usize {
     ($atomic_id_type:ident) => {
-        #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
-        pub struct $atomic_id_type(core::num::NonZeroUsize);
-
-        impl $atomic_id_type {
-            pub fn new() -> Self {
-                use std::sync::atomic::{AtomicUsize, Ordering};
-
-                static COUNTER: AtomicUsize = AtomicUsize::new(1);
-
-                let counter = COUNTER.fetch_add(1, Ordering::Relaxed);
-                Self (
-                    core::num::NonZeroUsize::new(counter).unwrap_or_else(|| {
-                        panic!(
-                            "The system ran out of unique `{}`s.",
-                            stringify!($atomic_id_type)
-                        );
-                    })
-                )
-            }
-        }
+        $crate::define_atomic_id!($atomic_id_type: usize);
     };
 }
+
+#[test]
+fn test_atomic_id() {
+    define_atomic_id!(TestId);
+    define_atomic_id!(TestId64: u64, wrap);
+
+    assert_eq!(std::mem::size_of::<Option<TestId>>(), std::mem::size_of::<TestId>());
+    assert_eq!(std::mem::size_of::<Option<TestId64>>(), 8);
+
+    let first = TestId::new();
+    let second = TestId::new();
+    assert_ne!(first, second);
+    assert!(first.get() > 0 && first < second);
+    assert_ne!(TestId64::new().get(), TestId64::new().get());
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_atomic_id_usize_alias() {
+    define_atomic_id_usize!(TestIdUsize);
+
+    let id: usize = TestIdUsize::new().get();
+    assert!(id > 0);
+}
@@ -2,8 +2,12 @@ mod id_generator;
 mod version;
 mod const_compute;
 mod memory;
+mod handle;
+
+pub mod math;
 
 pub use id_generator::*;
 pub use version::*;
 pub use const_compute::*;
 pub use memory::*;
+pub use handle::*;
@@ -0,0 +1,13 @@
+//! Bounding volumes and intersection tests shared by culling, level of detail and picking.
+
+mod aabb;
+mod frustum;
+mod plane;
+mod ray;
+mod sphere;
+
+pub use aabb::*;
+pub use frustum::*;
+pub use plane::*;
+pub use ray::*;
+pub use sphere::*;
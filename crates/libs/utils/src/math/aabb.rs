@@ -0,0 +1,126 @@
+use bevy_math::{BVec3, Mat4, Vec3};
+use crate::math::Sphere;
+
+/// Axis aligned bounds.
+///
+/// A component with the `bevy_ecs` feature, the bounds of an entity in its local space.
+#[cfg_attr(feature = "bevy_ecs", derive(bevy_ecs::prelude::Component))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    #[inline]
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    #[inline]
+    pub fn from_center_half_extents(center: Vec3, half_extents: Vec3) -> Self {
+        Self::new(center - half_extents, center + half_extents)
+    }
+
+    /// The smallest box containing every point, `None` without points.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        points.into_iter().fold(None, |aabb, point| {
+            Some(match aabb {
+                Some(Aabb { min, max }) => Aabb::new(min.min(point), max.max(point)),
+                None => Aabb::new(point, point),
+            })
+        })
+    }
+
+    #[inline]
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    #[inline]
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    #[inline]
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    #[inline]
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.min).all() && self.max.cmpge(other.max).all()
+    }
+
+    #[inline]
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.min.cmple(point).all() && self.max.cmpge(point).all()
+    }
+
+    #[inline]
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+
+    #[inline]
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.closest_point(sphere.center).distance_squared(sphere.center) <= sphere.radius * sphere.radius
+    }
+
+    /// The point of the box closest to `point`, `point` itself when inside.
+    #[inline]
+    pub fn closest_point(&self, point: Vec3) -> Vec3 {
+        point.clamp(self.min, self.max)
+    }
+
+    /// Grown by `margin` on every side.
+    #[inline]
+    pub fn expand(&self, margin: f32) -> Aabb {
+        Aabb::new(self.min - margin, self.max + margin)
+    }
+
+    #[inline]
+    pub fn surface_area(&self) -> f32 {
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// The sphere through the corners.
+    #[inline]
+    pub fn bounding_sphere(&self) -> Sphere {
+        Sphere::new(self.center(), self.half_extents().length())
+    }
+
+    /// The box around the transformed corners.
+    pub fn transformed(&self, transform: &Mat4) -> Aabb {
+        let corners = (0..8).map(|corner| {
+            let corner = Vec3::select(BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0), self.max, self.min);
+            transform.transform_point3(corner)
+        });
+        Aabb::from_points(corners).unwrap()
+    }
+
+    /// Distance along the ray to the entry point, 0 from inside, `None` when the ray misses the box within `max_distance`.
+    pub fn intersect_ray(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<f32> {
+        let inverse = direction.recip();
+        let near = (self.min - origin) * inverse;
+        let far = (self.max - origin) * inverse;
+        let entry = near.min(far).max_element().max(0.0);
+        let exit = near.max(far).min_element().min(max_distance);
+        (entry <= exit).then_some(entry)
+    }
+}
+
+#[test]
+fn test_aabb_intersections() {
+    let aabb = Aabb::new(Vec3::ZERO, Vec3::ONE);
+    assert!(aabb.intersects(&Aabb::new(Vec3::splat(0.5), Vec3::splat(2.0))));
+    assert!(!aabb.intersects(&Aabb::new(Vec3::splat(1.5), Vec3::splat(2.0))));
+    assert!(aabb.intersects_sphere(&Sphere::new(Vec3::new(1.5, 0.5, 0.5), 0.6)));
+    assert!(!aabb.intersects_sphere(&Sphere::new(Vec3::splat(1.5), 0.8)));
+
+    assert_eq!(aabb.intersect_ray(Vec3::new(-1.0, 0.5, 0.5), Vec3::X, 10.0), Some(1.0));
+    assert_eq!(aabb.intersect_ray(Vec3::splat(0.5), Vec3::X, 10.0), Some(0.0));
+    assert_eq!(aabb.intersect_ray(Vec3::new(-1.0, 0.5, 0.5), Vec3::X, 0.5), None);
+    assert_eq!(aabb.intersect_ray(Vec3::new(-1.0, 2.0, 0.5), Vec3::X, 10.0), None);
+}
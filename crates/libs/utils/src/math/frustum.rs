@@ -0,0 +1,69 @@
+use bevy_math::{Mat4, Vec3};
+use crate::math::{Aabb, Plane, Sphere};
+
+/// Planes bounding the volume seen by a camera, pointing inwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Planes of a vulkan clip space with depth in `[0, 1]`, from a world to clip space matrix.
+    pub fn from_view_projection(clip_from_world: &Mat4) -> Self {
+        let rows = [0, 1, 2, 3].map(|row| clip_from_world.row(row));
+        let planes = [
+            rows[3] + rows[0],
+            rows[3] - rows[0],
+            rows[3] + rows[1],
+            rows[3] - rows[1],
+            rows[2],
+            rows[3] - rows[2],
+        ];
+        Self {
+            planes: planes.map(Plane::from_vec4),
+        }
+    }
+
+    #[inline]
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes.iter().all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    /// Whether a sphere is at least partially inside.
+    #[inline]
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.planes.iter().all(|plane| plane.signed_distance(sphere.center) >= -sphere.radius)
+    }
+
+    /// Whether an axis aligned box is at least partially inside.
+    ///
+    /// Conservative, a large box outside near a corner of the frustum may still pass.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane normal
+            let corner = Vec3::select(plane.normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            plane.signed_distance(corner) >= 0.0
+        })
+    }
+}
+
+#[test]
+fn test_frustum_culling() {
+    // looking down -z, the near plane at 1 and the far plane at 100
+    let projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 100.0);
+    let frustum = Frustum::from_view_projection(&projection);
+
+    assert!(frustum.contains_point(Vec3::new(0.0, 0.0, -10.0)));
+    assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 10.0)));
+    assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -0.5)));
+    assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -200.0)));
+    assert!(!frustum.contains_point(Vec3::new(20.0, 0.0, -10.0)));
+
+    assert!(frustum.intersects_sphere(&Sphere::new(Vec3::new(11.0, 0.0, -10.0), 2.0)));
+    assert!(!frustum.intersects_sphere(&Sphere::new(Vec3::new(20.0, 0.0, -10.0), 2.0)));
+
+    assert!(frustum.intersects_aabb(&Aabb::new(Vec3::new(9.0, -1.0, -11.0), Vec3::new(12.0, 1.0, -9.0))));
+    assert!(!frustum.intersects_aabb(&Aabb::new(Vec3::new(15.0, -1.0, -11.0), Vec3::new(18.0, 1.0, -9.0))));
+    assert!(!frustum.intersects_aabb(&Aabb::new(Vec3::new(-1.0, -1.0, 1.0), Vec3::new(1.0, 1.0, 2.0))));
+}
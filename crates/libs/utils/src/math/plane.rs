@@ -0,0 +1,37 @@
+use bevy_math::{Vec3, Vec4, Vec4Swizzles};
+
+/// The points `p` where `normal.dot(p) + distance` is 0, in front of it when positive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    /// Normalized
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    /// The plane through `point`, `normal` is normalized.
+    #[inline]
+    pub fn from_point_normal(point: Vec3, normal: Vec3) -> Self {
+        let normal = normal.normalize();
+        Self {
+            normal,
+            distance: -normal.dot(point),
+        }
+    }
+
+    /// A plane from its equation coefficients, normalized.
+    #[inline]
+    pub fn from_vec4(plane: Vec4) -> Self {
+        let plane = plane / plane.xyz().length();
+        Self {
+            normal: plane.xyz(),
+            distance: plane.w,
+        }
+    }
+
+    /// Distance from the plane, negative behind it.
+    #[inline]
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
@@ -0,0 +1,62 @@
+use bevy_math::Vec3;
+use crate::math::{Aabb, Plane, Sphere};
+
+/// A half line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Normalized
+    pub direction: Vec3,
+}
+
+impl Ray {
+    #[inline]
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// See [`Aabb::intersect_ray`].
+    #[inline]
+    pub fn intersect_aabb(&self, aabb: &Aabb, max_distance: f32) -> Option<f32> {
+        aabb.intersect_ray(self.origin, self.direction, max_distance)
+    }
+
+    /// Distance along the ray to the entry point, 0 from inside, `None` when the ray misses the sphere within `max_distance`.
+    pub fn intersect_sphere(&self, sphere: &Sphere, max_distance: f32) -> Option<f32> {
+        let to_center = sphere.center - self.origin;
+        let along = to_center.dot(self.direction);
+        let discriminant = sphere.radius * sphere.radius - (to_center.length_squared() - along * along);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let half_chord = discriminant.sqrt();
+        if along + half_chord < 0.0 {
+            return None;
+        }
+        let entry = (along - half_chord).max(0.0);
+        (entry <= max_distance).then_some(entry)
+    }
+
+    /// Distance along the ray to the plane, `None` when parallel to it or when the plane is behind.
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<f32> {
+        let denominator = plane.normal.dot(self.direction);
+        if denominator.abs() <= f32::EPSILON {
+            return None;
+        }
+        let distance = -plane.signed_distance(self.origin) / denominator;
+        (distance >= 0.0).then_some(distance)
+    }
+}
+
+#[test]
+fn test_ray_intersections() {
+    let ray = Ray { origin: Vec3::ZERO, direction: Vec3::Z };
+    assert_eq!(ray.intersect_sphere(&Sphere::new(Vec3::new(0.0, 0.0, 5.0), 1.0), 10.0), Some(4.0));
+    assert_eq!(ray.intersect_sphere(&Sphere::new(Vec3::ZERO, 1.0), 10.0), Some(0.0));
+    assert_eq!(ray.intersect_sphere(&Sphere::new(Vec3::new(0.0, 0.0, -5.0), 1.0), 10.0), None);
+    assert_eq!(ray.intersect_sphere(&Sphere::new(Vec3::new(2.0, 0.0, 5.0), 1.0), 10.0), None);
+
+    assert_eq!(ray.intersect_plane(&Plane::from_point_normal(Vec3::new(0.0, 0.0, 3.0), -Vec3::Z)), Some(3.0));
+    assert_eq!(ray.intersect_plane(&Plane::from_point_normal(Vec3::new(0.0, 0.0, -3.0), Vec3::Z)), None);
+    assert_eq!(ray.intersect_plane(&Plane::from_point_normal(Vec3::X, Vec3::X)), None);
+}
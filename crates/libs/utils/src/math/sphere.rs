@@ -0,0 +1,32 @@
+use bevy_math::{Mat4, Vec3};
+
+/// A bounding sphere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl Sphere {
+    #[inline]
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    #[inline]
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.center.distance_squared(point) <= self.radius * self.radius
+    }
+
+    #[inline]
+    pub fn intersects(&self, other: &Sphere) -> bool {
+        let radius = self.radius + other.radius;
+        self.center.distance_squared(other.center) <= radius * radius
+    }
+
+    /// The sphere around the transformed one, scaled by the largest scale of `transform`.
+    pub fn transformed(&self, transform: &Mat4) -> Sphere {
+        let scale = transform.x_axis.length().max(transform.y_axis.length()).max(transform.z_axis.length());
+        Sphere::new(transform.transform_point3(self.center), self.radius * scale)
+    }
+}
@@ -9,7 +9,7 @@ pub const CURRENT_APPLICATION_VERSION: Version = Version::new(0, parse_unwarp(en
 
 pub const CURRENT_APPLICATION_NAME: &str = "AvalancheEngine";
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Version {
     pub variant: u32,
     pub major: u32,
@@ -0,0 +1,37 @@
+use once_cell::sync::OnceCell;
+use winit::platform::android::activity::AndroidApp;
+use winit::platform::android::WindowExtAndroid;
+use winit::window::Window;
+
+static ANDROID_APP: OnceCell<AndroidApp> = OnceCell::new();
+
+/// Hand the activity received by `android_main` to the window system.
+///
+/// Must be called before the engine is created, the event loop of [`WindowManager`](crate::WindowManager) is built from it.
+pub fn set_android_app(app: AndroidApp) {
+    if ANDROID_APP.set(app).is_err() {
+        log::warn!("Android app was already set, ignoring");
+    }
+}
+
+pub(crate) fn android_app() -> AndroidApp {
+    ANDROID_APP
+        .get()
+        .cloned()
+        .expect("`set_android_app` must be called from `android_main` before creating the event loop")
+}
+
+/// Margins of the window around the content rect of the activity, e.g. system bars and display cutouts.
+///
+/// Left, top, right and bottom in physical pixels.
+pub(crate) fn content_insets(window: &Window) -> [u32; 4] {
+    let size = window.inner_size();
+    let content = window.content_rect();
+    [
+        content.left,
+        content.top,
+        size.width as i32 - content.right,
+        size.height as i32 - content.bottom,
+    ]
+    .map(|inset| inset.max(0) as u32)
+}
@@ -0,0 +1,89 @@
+use bevy_ecs::prelude::{Changed, DetectChangesMut, Query};
+use log::warn;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::window::{Icon, Window};
+use crate::WindowComponent;
+
+/// RGBA8 image shown as the icon of a window.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WindowIcon {
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Area of a window the text being composed is edited at, the IME shows its candidates next to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ImeCursorArea {
+    /// Top left corner in physical pixels
+    pub position: (i32, i32),
+    /// In physical pixels
+    pub size: (u32, u32),
+}
+
+/// Snapshot of the attributes of a [`WindowComponent`] last applied to its winit window.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct WindowAttributes {
+    pub(crate) title: String,
+    pub(crate) icon: Option<WindowIcon>,
+    pub(crate) resizable: bool,
+    pub(crate) min_size: Option<(u32, u32)>,
+    pub(crate) max_size: Option<(u32, u32)>,
+    pub(crate) ime_allowed: bool,
+    pub(crate) ime_cursor_area: Option<ImeCursorArea>,
+}
+
+impl WindowAttributes {
+    pub(crate) fn from_window(window: &Window) -> Self {
+        Self {
+            title: window.title(),
+            icon: None,
+            resizable: window.is_resizable(),
+            min_size: None,
+            max_size: None,
+            ime_allowed: false,
+            ime_cursor_area: None,
+        }
+    }
+}
+
+/// Apply the attributes modified on [`WindowComponent`]s to their winit windows.
+pub(crate) fn window_attributes_system(mut windows: Query<&mut WindowComponent, Changed<WindowComponent>>) {
+    for mut window_component in windows.iter_mut() {
+        let attributes = window_component.attributes();
+        let applied = &window_component.applied_attributes;
+        if attributes == *applied {
+            continue;
+        }
+
+        let window = &window_component.window;
+        if attributes.title != applied.title {
+            window.set_title(&attributes.title);
+        }
+        if attributes.icon != applied.icon {
+            let icon = attributes.icon.clone().and_then(|icon| {
+                Icon::from_rgba(icon.rgba, icon.width, icon.height)
+                    .map_err(|err| warn!("Invalid window icon: {err}"))
+                    .ok()
+            });
+            window.set_window_icon(icon);
+        }
+        if attributes.resizable != applied.resizable {
+            window.set_resizable(attributes.resizable);
+        }
+        if attributes.min_size != applied.min_size {
+            window.set_min_inner_size(attributes.min_size.map(|(width, height)| PhysicalSize::new(width, height)));
+        }
+        if attributes.max_size != applied.max_size {
+            window.set_max_inner_size(attributes.max_size.map(|(width, height)| PhysicalSize::new(width, height)));
+        }
+        if attributes.ime_allowed != applied.ime_allowed {
+            window.set_ime_allowed(attributes.ime_allowed);
+        }
+        if let Some(area) = attributes.ime_cursor_area.filter(|area| Some(*area) != applied.ime_cursor_area) {
+            window.set_ime_cursor_area(PhysicalPosition::new(area.position.0, area.position.1), PhysicalSize::new(area.size.0, area.size.1));
+        }
+
+        window_component.bypass_change_detection().applied_attributes = attributes;
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::Context;
+
+/// Text clipboard of the system, shared with the other applications.
+///
+/// A non-send resource: the clipboard is only accessed from the main thread, which runs the event loop.
+/// Android has no system clipboard access here, the text only lives in the app.
+#[derive(Default)]
+pub struct Clipboard {
+    /// Connected on first use, keeps ownership of the text set on X11 and Wayland
+    #[cfg(not(target_os = "android"))]
+    system: Option<arboard::Clipboard>,
+    #[cfg(target_os = "android")]
+    text: Option<String>,
+}
+
+impl Clipboard {
+    #[cfg(not(target_os = "android"))]
+    fn system(&mut self) -> anyhow::Result<&mut arboard::Clipboard> {
+        if self.system.is_none() {
+            self.system = Some(arboard::Clipboard::new().context("Failed to access the clipboard")?);
+        }
+        Ok(self.system.as_mut().unwrap())
+    }
+
+    /// The text in the clipboard, an error when it is empty or holds something else.
+    #[cfg(not(target_os = "android"))]
+    pub fn get_text(&mut self) -> anyhow::Result<String> {
+        Ok(self.system()?.get_text()?)
+    }
+
+    #[cfg(not(target_os = "android"))]
+    pub fn set_text(&mut self, text: impl Into<String>) -> anyhow::Result<()> {
+        Ok(self.system()?.set_text(text.into())?)
+    }
+
+    #[cfg(target_os = "android")]
+    pub fn get_text(&mut self) -> anyhow::Result<String> {
+        self.text.clone().context("The clipboard is empty")
+    }
+
+    #[cfg(target_os = "android")]
+    pub fn set_text(&mut self, text: impl Into<String>) -> anyhow::Result<()> {
+        self.text = Some(text.into());
+        Ok(())
+    }
+}
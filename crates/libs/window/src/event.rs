@@ -1,5 +1,5 @@
-use bevy_ecs::prelude::Event;
-use winit::event::WindowEvent;
+use bevy_ecs::prelude::{Entity, Event};
+use winit::event::{Ime, WindowEvent};
 use winit::window::WindowId;
 
 #[derive(Event)]
@@ -13,6 +13,15 @@ pub struct WindowClosedEvent {
     pub window_id: WindowId,
 }
 
+/// The user asked to close the primary window.
+///
+/// Unless [`WindowExitSettings::close_primary_when_requested`](crate::WindowExitSettings::close_primary_when_requested) is set,
+/// the window stays open and systems handling this event decide whether to despawn it, e.g. after a save dialog.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PrimaryWindowCloseRequested {
+    pub window: Entity,
+}
+
 /// ## Window resized event
 ///
 /// Delegated to application because we don't have rendering context to perform operation
@@ -23,5 +32,31 @@ pub struct WindowResizedEvent {
     pub new_size: (u32, u32),
 }
 
+/// Text composition by an input method in a window with [`ime_allowed`](crate::WindowComponent::ime_allowed).
+///
+/// The preedit text is shown in the text box until it is committed, committed text is not sent as [`ReceivedCharacter`]s.
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct ImeEvent {
+    pub window: Entity,
+    pub ime: Ime,
+}
+
+/// A character typed in a window, repeated while its key is held.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReceivedCharacter {
+    pub window: Entity,
+    pub char: char,
+}
+
 #[derive(Event)]
 pub struct WindowEventLoopClearedEvent();
+
+/// The application was suspended or resumed by the platform.
+///
+/// Native windows may be destroyed while suspended, on Android in particular,
+/// surfaces must not be used until the app is resumed.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppLifecycleEvent {
+    Suspended,
+    Resumed,
+}
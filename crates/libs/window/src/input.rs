@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+use bevy_app::{App, Plugin, Update};
+use bevy_ecs::prelude::{Event, EventReader, EventWriter, IntoSystemConfigs, ResMut, Resource};
+use bevy_utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+use crate::event::WinitWindowEvent;
+use crate::WindowSystemSet;
+
+/// Pixels of a touchpad scroll counted as one line of a mouse wheel.
+const PIXELS_PER_LINE: f32 = 20.0;
+
+/// A raw input an action is bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputBinding {
+    /// A key by its position on the keyboard, independent of the layout
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// -1 while `negative` is held, 1 while `positive` is, 0 with both
+    KeyAxis { negative: KeyCode, positive: KeyCode },
+    /// Lines scrolled vertically this frame, up is positive
+    MouseWheel,
+}
+
+impl InputBinding {
+    fn inputs(&self) -> impl Iterator<Item = RawInput> {
+        let (first, second) = match *self {
+            InputBinding::Key(key) => (RawInput::Key(key), None),
+            InputBinding::Mouse(button) => (RawInput::Mouse(button), None),
+            InputBinding::KeyAxis { negative, positive } => (RawInput::Key(negative), Some(RawInput::Key(positive))),
+            InputBinding::MouseWheel => (RawInput::MouseWheel, None),
+        };
+        std::iter::once(first).chain(second)
+    }
+
+    fn is_axis(&self) -> bool {
+        matches!(self, InputBinding::KeyAxis { .. } | InputBinding::MouseWheel)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum RawInput {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    MouseWheel,
+}
+
+/// Actions of a context by name, with the inputs triggering them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputContext {
+    pub actions: BTreeMap<String, Vec<InputBinding>>,
+}
+
+/// Bindings of every action by context, e.g. `gameplay` and `ui`, serializable to store user rebinds.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputMap {
+    pub contexts: BTreeMap<String, InputContext>,
+}
+
+impl InputMap {
+    /// Add a binding to `action` of `context`, creating both if needed.
+    pub fn bind(&mut self, context: &str, action: &str, binding: InputBinding) -> &mut Self {
+        let bindings = self
+            .contexts
+            .entry(context.to_string())
+            .or_default()
+            .actions
+            .entry(action.to_string())
+            .or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        self
+    }
+
+    /// Replace the bindings of `action` in `context`, e.g. when the user rebinds it.
+    pub fn rebind(&mut self, context: &str, action: &str, bindings: Vec<InputBinding>) -> &mut Self {
+        self.contexts
+            .entry(context.to_string())
+            .or_default()
+            .actions
+            .insert(action.to_string(), bindings);
+        self
+    }
+
+    /// Remove `action` from `context`, returns its bindings.
+    pub fn unbind(&mut self, context: &str, action: &str) -> Option<Vec<InputBinding>> {
+        self.contexts.get_mut(context)?.actions.remove(action)
+    }
+}
+
+/// What happened to an action.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ActionEventKind {
+    Pressed,
+    Released,
+    /// The value of an action bound to an axis changed
+    Axis(f32),
+}
+
+/// Sent when an action of an active context is pressed, released or its axis moves.
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct ActionEvent {
+    pub context: String,
+    pub action: String,
+    pub kind: ActionEventKind,
+}
+
+/// The [`InputMap`] with the contexts receiving input and the state of their actions.
+///
+/// Active contexts form a stack, an input bound in several of them only triggers the actions
+/// of the topmost one. Pushing a `ui` context over `gameplay` makes the keys the UI binds stop moving the player,
+/// while the others still do.
+#[derive(Resource, Default)]
+pub struct InputActions {
+    pub map: InputMap,
+    active_contexts: Vec<String>,
+    /// Value of the actions of the active contexts, by context and action
+    values: HashMap<(String, String), f32>,
+    pressed: HashSet<RawInput>,
+    wheel: f32,
+}
+
+impl InputActions {
+    pub fn new(map: InputMap) -> Self {
+        Self {
+            map,
+            ..Self::default()
+        }
+    }
+
+    /// Make `context` the topmost active one, moving it up if it already is active.
+    pub fn push_context(&mut self, context: impl Into<String>) {
+        let context = context.into();
+        self.active_contexts.retain(|active| *active != context);
+        self.active_contexts.push(context);
+    }
+
+    /// Deactivate the topmost context, its held actions are released.
+    pub fn pop_context(&mut self) -> Option<String> {
+        self.active_contexts.pop()
+    }
+
+    pub fn remove_context(&mut self, context: &str) {
+        self.active_contexts.retain(|active| active != context);
+    }
+
+    /// Active contexts, the topmost last.
+    #[inline]
+    pub fn active_contexts(&self) -> &[String] {
+        &self.active_contexts
+    }
+
+    /// Value of `action` in the topmost active context binding it, 0 if none.
+    ///
+    /// 1 while a button is held, -1 to 1 for axes.
+    pub fn value(&self, action: &str) -> f32 {
+        self.active_contexts
+            .iter()
+            .rev()
+            .find_map(|context| self.values.get(&(context.clone(), action.to_string())))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    pub fn pressed(&self, action: &str) -> bool {
+        self.value(action) != 0.0
+    }
+
+    fn handle_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => {
+                if let PhysicalKey::Code(key) = event.physical_key {
+                    self.set_pressed(RawInput::Key(key), event.state);
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => self.set_pressed(RawInput::Mouse(*button), *state),
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.wheel += match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => *lines,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+                };
+            }
+            // keys released while unfocused are never reported
+            WindowEvent::Focused(false) => self.pressed.clear(),
+            _ => {}
+        }
+    }
+
+    fn set_pressed(&mut self, input: RawInput, state: ElementState) {
+        match state {
+            ElementState::Pressed => self.pressed.insert(input),
+            ElementState::Released => self.pressed.remove(&input),
+        };
+    }
+
+    fn binding_value(&self, binding: &InputBinding) -> f32 {
+        let held = |input| if self.pressed.contains(&input) { 1.0 } else { 0.0 };
+        match *binding {
+            InputBinding::Key(key) => held(RawInput::Key(key)),
+            InputBinding::Mouse(button) => held(RawInput::Mouse(button)),
+            InputBinding::KeyAxis { negative, positive } => held(RawInput::Key(positive)) - held(RawInput::Key(negative)),
+            InputBinding::MouseWheel => self.wheel,
+        }
+    }
+
+    /// Evaluate the actions of the active contexts, sending events for the ones which changed.
+    fn update(&mut self, events: &mut EventWriter<ActionEvent>) {
+        let mut claimed = HashSet::default();
+        let mut values = HashMap::default();
+
+        for context_name in self.active_contexts.iter().rev() {
+            let Some(context) = self.map.contexts.get(context_name) else {
+                continue;
+            };
+            let mut context_inputs = Vec::new();
+            for (action, bindings) in &context.actions {
+                // the binding moved the furthest wins, inputs claimed by a context above are ignored
+                let (value, is_axis) = bindings
+                    .iter()
+                    .filter(|binding| binding.inputs().all(|input| !claimed.contains(&input)))
+                    .map(|binding| (self.binding_value(binding), binding.is_axis()))
+                    .fold((0.0f32, false), |best, current| if current.0.abs() > best.0.abs() { current } else { best });
+                context_inputs.extend(bindings.iter().flat_map(InputBinding::inputs));
+
+                let key = (context_name.clone(), action.clone());
+                let previous = self.values.get(&key).copied().unwrap_or(0.0);
+                let kind = match (previous != 0.0, value != 0.0) {
+                    (false, true) => Some(ActionEventKind::Pressed),
+                    (true, false) => Some(ActionEventKind::Released),
+                    _ if is_axis && value != previous => Some(ActionEventKind::Axis(value)),
+                    _ => None,
+                };
+                if let Some(kind) = kind {
+                    events.send(ActionEvent {
+                        context: context_name.clone(),
+                        action: action.clone(),
+                        kind,
+                    });
+                }
+                values.insert(key, value);
+            }
+            claimed.extend(context_inputs);
+        }
+
+        // actions of deactivated or unbound contexts are released
+        for ((context, action), previous) in self.values.drain() {
+            if previous != 0.0 && !values.contains_key(&(context.clone(), action.clone())) {
+                events.send(ActionEvent {
+                    context,
+                    action,
+                    kind: ActionEventKind::Released,
+                });
+            }
+        }
+        self.values = values;
+        self.wheel = 0.0;
+    }
+}
+
+/// Turns the keyboard and mouse events of the windows into [`ActionEvent`]s, see [`InputActions`].
+#[derive(Default)]
+pub struct InputActionPlugin {
+    pub map: InputMap,
+    /// Contexts active from the start, the topmost last
+    pub active_contexts: Vec<String>,
+}
+
+impl Plugin for InputActionPlugin {
+    fn build(&self, app: &mut App) {
+        let mut actions = InputActions::new(self.map.clone());
+        for context in &self.active_contexts {
+            actions.push_context(context.clone());
+        }
+        app.insert_resource(actions)
+            .add_event::<ActionEvent>()
+            .add_systems(Update, update_input_actions.in_set(WindowSystemSet::Update));
+    }
+}
+
+fn update_input_actions(
+    mut actions: ResMut<InputActions>,
+    mut window_events: EventReader<WinitWindowEvent>,
+    mut action_events: EventWriter<ActionEvent>,
+) {
+    for event in window_events.read() {
+        actions.handle_event(&event.window_event);
+    }
+    actions.update(&mut action_events);
+}
@@ -3,19 +3,30 @@
 #![feature(trivial_bounds)]
 
 pub mod event;
+pub mod input;
+mod attributes;
+mod clipboard;
+#[cfg(target_os = "android")]
+mod android;
+
+pub use attributes::*;
+pub use clipboard::*;
+#[cfg(target_os = "android")]
+pub use android::*;
 
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
-use bevy_app::{App, Plugin, Update};
-use bevy_ecs::prelude::{Commands, Component, Entity, EventReader, EventWriter, IntoSystemConfigs, IntoSystemSetConfigs, NonSend, Query, Resource, SystemSet};
+use std::time::{Duration, Instant};
+use bevy_app::{App, AppExit, Plugin, Update};
+use bevy_ecs::prelude::{Commands, Component, DetectChangesMut, Entity, EventReader, EventWriter, IntoSystemConfigs, IntoSystemSetConfigs, NonSend, Query, RemovedComponents, Res, ResMut, Resource, SystemSet, With};
 use raw_window_handle::{DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle, WindowHandle};
-use winit::event::{Event, WindowEvent};
-use winit::event_loop::{EventLoop, EventLoopBuilder};
-use winit::platform::pump_events::EventLoopExtPumpEvents;
+use winit::event::{ElementState, Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopBuilder};
+use winit::platform::pump_events::{EventLoopExtPumpEvents, PumpStatus};
 use winit::window::{Window, WindowBuilder};
 use avalanche_hlvk::{Device, Surface, Swapchain};
-use avalanche_utils::ID_GENERATOR_32_STATIC;
-use crate::event::{WindowClosedEvent, WindowEventLoopClearedEvent, WindowResizedEvent, WinitWindowEvent};
+use once_cell::sync::Lazy;
+use avalanche_utils::{GenerationalId, GenerationalIdAllocator};
+use crate::event::{AppLifecycleEvent, ImeEvent, PrimaryWindowCloseRequested, ReceivedCharacter, WindowClosedEvent, WindowEventLoopClearedEvent, WindowResizedEvent, WinitWindowEvent};
 
 #[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum WindowSystemSet {
@@ -23,24 +34,109 @@ pub enum WindowSystemSet {
     Update,
 }
 
-pub struct WindowSystemPlugin;
+/// When the app exits because of closed windows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExitCondition {
+    /// Exit once the primary window is closed.
+    #[default]
+    OnPrimaryClosed,
+    /// Exit once every window is closed.
+    OnAllClosed,
+    /// Keep running without windows, [`AppExit`] must be sent manually.
+    DontExit,
+}
+
+/// Whether the platform lets the app present, updated from [`AppLifecycleEvent`]s.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AppLifecycle {
+    #[default]
+    Running,
+    Suspended,
+}
+
+/// How the app waits for window events between updates.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowUpdateMode {
+    /// Update as soon as the next frame is due, windows are redrawn every frame.
+    #[default]
+    Continuous,
+    /// Only update once an event was received or `max_wait` elapsed, for tools and idle apps.
+    Reactive { max_wait: Duration },
+}
+
+/// Instant the next update should start at, the event loop waits for events until then.
+///
+/// Set by the frame pacer of the renderer, `None` doesn't wait.
+#[derive(Resource, Clone, Copy, Debug, Default)]
+pub struct NextFrameDeadline(pub Option<Instant>);
+
+/// How closing windows is handled, can be changed at runtime.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct WindowExitSettings {
+    pub exit_condition: ExitCondition,
+    /// Despawn the primary window as soon as closing it is requested,
+    /// otherwise only [`PrimaryWindowCloseRequested`] is sent.
+    pub close_primary_when_requested: bool,
+}
+
+impl Default for WindowExitSettings {
+    fn default() -> Self {
+        Self {
+            exit_condition: ExitCondition::default(),
+            close_primary_when_requested: true,
+        }
+    }
+}
+
+/// Windowing system used on Linux and BSD, other platforms only have one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowBackend {
+    /// Wayland when a compositor is available, X11 otherwise.
+    #[default]
+    Auto,
+    X11,
+    Wayland,
+}
+
+#[derive(Default)]
+pub struct WindowSystemPlugin {
+    pub exit_settings: WindowExitSettings,
+    pub backend: WindowBackend,
+    /// How the primary window is created, available as a resource.
+    pub primary_window: WindowDescriptor,
+}
 
 impl Plugin for WindowSystemPlugin {
     fn build(&self, app: &mut App) {
-        app.init_non_send_resource::<WindowManager>();
+        app.insert_non_send_resource(WindowManager::new(self.backend));
+        app.insert_non_send_resource(Clipboard::default());
+        app.insert_resource(self.exit_settings);
+        app.insert_resource(self.primary_window.clone());
+        app.init_resource::<AppLifecycle>();
+        app.init_resource::<WindowUpdateMode>();
+        app.init_resource::<NextFrameDeadline>();
         app.configure_sets(Update, (WindowSystemSet::EventLoop, WindowSystemSet::Update).chain());
         app.add_event::<WinitWindowEvent>();
         app.add_event::<WindowResizedEvent>();
         app.add_event::<WindowEventLoopClearedEvent>();
         app.add_event::<WindowClosedEvent>();
+        app.add_event::<PrimaryWindowCloseRequested>();
+        app.add_event::<AppLifecycleEvent>();
+        app.add_event::<ImeEvent>();
+        app.add_event::<ReceivedCharacter>();
+        app.add_event::<AppExit>();
         app.add_systems(Update, (
             winit_event_poll_worker_system
                 .before(window_update_system)
                 .in_set(WindowSystemSet::EventLoop)
             ,
             (
+                app_lifecycle_system,
                 window_close_system.before(window_update_system),
                 window_update_system,
+                window_text_input_system,
+                window_attributes_system.after(window_update_system),
+                exit_on_window_close_system.after(window_close_system),
             )
                 .in_set(WindowSystemSet::Update),
         ));
@@ -50,23 +146,94 @@ impl Plugin for WindowSystemPlugin {
 #[derive(Resource)]
 pub struct WindowManager {
     pub event_loop: RwLock<EventLoop<()>>,
+    /// Backend the event loop is connected to, [`WindowBackend::Auto`] where there is no choice.
+    pub backend: WindowBackend,
 }
 
 impl Default for WindowManager {
     fn default() -> Self {
+        Self::new(WindowBackend::Auto)
+    }
+}
+
+impl WindowManager {
+    /// Create the event loop, panics if the requested backend is not available.
+    pub fn new(backend: WindowBackend) -> Self {
+        #[allow(unused_mut)]
+        let mut builder = EventLoopBuilder::default();
+        #[cfg(target_os = "android")]
+        {
+            use winit::platform::android::EventLoopBuilderExtAndroid;
+            builder.with_android_app(android::android_app());
+        }
+        #[cfg(all(unix, not(any(target_os = "android", target_os = "macos", target_os = "ios"))))]
+        match backend {
+            WindowBackend::Auto => {}
+            WindowBackend::X11 => {
+                use winit::platform::x11::EventLoopBuilderExtX11;
+                builder.with_x11();
+            }
+            WindowBackend::Wayland => {
+                use winit::platform::wayland::EventLoopBuilderExtWayland;
+                builder.with_wayland();
+            }
+        }
+        #[cfg(not(all(unix, not(any(target_os = "android", target_os = "macos", target_os = "ios")))))]
+        if backend != WindowBackend::Auto {
+            log::warn!("Window backend {backend:?} is not available on this platform, using the native one");
+        }
+
+        let event_loop = builder.build().unwrap();
+        let backend = active_backend(&event_loop);
+        log::info!("Window backend: {backend:?}");
+
         Self {
-            event_loop: RwLock::new(
-                EventLoopBuilder::default()
-                    .build()
+            event_loop: RwLock::new(event_loop),
+            backend,
+        }
+    }
+
+    /// Block until the platform provides native windows.
+    ///
+    /// On Android window handles are only available once the activity is resumed,
+    /// elsewhere this returns immediately.
+    pub fn wait_for_native_window(&self) {
+        #[cfg(target_os = "android")]
+        {
+            let mut resumed = false;
+            while !resumed {
+                let status = self.event_loop
+                    .write()
                     .unwrap()
-            ),
+                    .pump_events(None, |event, _event_target| {
+                        if matches!(event, Event::Resumed) {
+                            resumed = true;
+                        }
+                    });
+                if let PumpStatus::Exit(_) = status {
+                    break;
+                }
+            }
         }
     }
 }
 
-#[derive(Component, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
-pub struct WindowId(u32);
+static WINDOW_IDS: Lazy<GenerationalIdAllocator> = Lazy::new(GenerationalIdAllocator::new);
+
+/// Identifies a [`WindowComponent`], the ids of closed windows are recycled with a new generation.
+#[derive(Component, Clone, Copy, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct WindowId(GenerationalId);
+
+impl WindowId {
+    /// Whether the window wasn't closed, ids of closed windows never match an open one.
+    pub fn is_open(&self) -> bool {
+        WINDOW_IDS.is_alive(self.0)
+    }
+}
 
+/// A winit window and its presentation resources.
+///
+/// Changes to the title, icon, resizable flag, size limits and IME settings are applied to the window during [`WindowSystemSet::Update`].
 #[derive(Component, Clone)]
 pub struct WindowComponent {
     pub id: WindowId,
@@ -74,16 +241,62 @@ pub struct WindowComponent {
     pub surface: Option<Arc<Surface>>,
     pub swapchain: Option<Arc<Swapchain>>,
     pub render_device: Option<Arc<Device>>,
+    pub title: String,
+    pub icon: Option<WindowIcon>,
+    pub resizable: bool,
+    /// Minimum inner size in physical pixels
+    pub min_size: Option<(u32, u32)>,
+    /// Maximum inner size in physical pixels
+    pub max_size: Option<(u32, u32)>,
+    /// Whether the window was created transparent, see [`WindowDescriptor::transparent`]
+    pub transparent: bool,
+    /// Receive composed text as [`ImeEvent`](event::ImeEvent)s, enable it while a text box has focus
+    pub ime_allowed: bool,
+    /// Where the focused text box edits text, for the IME to place its candidate window
+    pub ime_cursor_area: Option<ImeCursorArea>,
+    applied_attributes: WindowAttributes,
 }
 
 impl WindowComponent {
     pub fn new(window: Arc<Window>) -> Self {
+        let applied_attributes = WindowAttributes::from_window(&window);
         Self {
-            id: WindowId(ID_GENERATOR_32_STATIC.next_id()),
+            id: WindowId(WINDOW_IDS.allocate()),
             window,
             surface: None,
             swapchain: None,
             render_device: None,
+            title: applied_attributes.title.clone(),
+            icon: applied_attributes.icon.clone(),
+            resizable: applied_attributes.resizable,
+            min_size: applied_attributes.min_size,
+            max_size: applied_attributes.max_size,
+            transparent: false,
+            ime_allowed: applied_attributes.ime_allowed,
+            ime_cursor_area: applied_attributes.ime_cursor_area,
+            applied_attributes,
+        }
+    }
+
+    /// Margins of the window hidden by the platform, left, top, right and bottom in physical pixels.
+    ///
+    /// Only reported on Android, zero elsewhere.
+    pub fn safe_area_insets(&self) -> [u32; 4] {
+        #[cfg(target_os = "android")]
+        return android::content_insets(&self.window);
+        #[cfg(not(target_os = "android"))]
+        [0; 4]
+    }
+
+    fn attributes(&self) -> WindowAttributes {
+        WindowAttributes {
+            title: self.title.clone(),
+            icon: self.icon.clone(),
+            resizable: self.resizable,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            ime_allowed: self.ime_allowed,
+            ime_cursor_area: self.ime_cursor_area,
         }
     }
 }
@@ -91,42 +304,139 @@ impl WindowComponent {
 #[derive(Component)]
 pub struct PrimaryWindowComponent;
 
+#[cfg(all(unix, not(any(target_os = "android", target_os = "macos", target_os = "ios"))))]
+fn active_backend(event_loop: &EventLoop<()>) -> WindowBackend {
+    use winit::platform::wayland::EventLoopWindowTargetExtWayland;
+    if event_loop.is_wayland() {
+        WindowBackend::Wayland
+    } else {
+        WindowBackend::X11
+    }
+}
+
+#[cfg(not(all(unix, not(any(target_os = "android", target_os = "macos", target_os = "ios")))))]
+fn active_backend(_event_loop: &EventLoop<()>) -> WindowBackend {
+    WindowBackend::Auto
+}
+
+/// Attributes a window is created with, the ones of [`WindowComponent`] can be changed afterwards.
+#[derive(Resource, Clone, Debug)]
+pub struct WindowDescriptor {
+    pub title: String,
+    /// Let what is behind the window show through where the rendered alpha is below 1,
+    /// when the window system and the surface support it. Can't be changed once created.
+    pub transparent: bool,
+}
+
+impl Default for WindowDescriptor {
+    fn default() -> Self {
+        Self {
+            title: "[Avalanche] Default Title".to_string(),
+            transparent: false,
+        }
+    }
+}
+
 pub fn new_window_component(event_loop: &EventLoop<()>) -> anyhow::Result<WindowComponent> {
+    new_window_component_with(event_loop, &WindowDescriptor::default())
+}
+
+pub fn new_window_component_with(event_loop: &EventLoop<()>, descriptor: &WindowDescriptor) -> anyhow::Result<WindowComponent> {
     let window = WindowBuilder::default()
-        .with_title("[Avalanche] Default Title")
+        .with_title(descriptor.title.clone())
+        .with_transparent(descriptor.transparent)
         .build(event_loop)?;
 
-    Ok(WindowComponent::new(Arc::new(window)))
+    let mut window_component = WindowComponent::new(Arc::new(window));
+    window_component.transparent = descriptor.transparent;
+    Ok(window_component)
 }
 
+/// Pump the winit event loop, sleeping in it until the next frame is due.
+///
+/// Replaces a busy loop: the [`ScheduleRunnerPlugin`](bevy_app::ScheduleRunnerPlugin) runs updates back to back,
+/// so the wait for the [`NextFrameDeadline`] and for events in [`WindowUpdateMode::Reactive`] happens here.
 fn winit_event_poll_worker_system(
     window_manager: NonSend<WindowManager>,
+    update_mode: Res<WindowUpdateMode>,
+    next_frame_deadline: Res<NextFrameDeadline>,
+    windows: Query<&WindowComponent>,
     mut window_event_sender: EventWriter<WinitWindowEvent>,
-    mut close_event_sender: EventWriter<WindowClosedEvent>
+    mut close_event_sender: EventWriter<WindowClosedEvent>,
+    mut lifecycle_event_sender: EventWriter<AppLifecycleEvent>,
 ) {
     #[cfg(feature = "trace")]
     let _span = bevy_utils::tracing::info_span!("poll winit event loop").entered();
 
-    window_manager
-        .event_loop
-        .write()
-        .unwrap()
-        .pump_events(
-            Some(Duration::ZERO),
-            |event, _event_target| {
+    let start = Instant::now();
+    let frame_deadline = next_frame_deadline.0.unwrap_or(start).max(start);
+    let wake_deadline = match *update_mode {
+        WindowUpdateMode::Continuous => frame_deadline,
+        WindowUpdateMode::Reactive { max_wait } => frame_deadline.max(start + max_wait),
+    };
+
+    let mut event_loop = window_manager.event_loop.write().unwrap();
+    let mut event_received = false;
+    loop {
+        let timeout = wake_deadline.saturating_duration_since(Instant::now());
+        let status = event_loop.pump_events(
+            Some(timeout),
+            |event, event_target| {
+                    event_target.set_control_flow(ControlFlow::Wait);
                     match event {
                         Event::WindowEvent {
                             event: WindowEvent::CloseRequested,
                             window_id,
-                        } => close_event_sender.send(WindowClosedEvent { window_id }),
+                        } => {
+                            event_received = true;
+                            close_event_sender.send(WindowClosedEvent { window_id });
+                        }
                         Event::WindowEvent {
                             event: window_event,
                             window_id,
-                        } => window_event_sender.send(WinitWindowEvent {  window_event, window_id }),
+                        } => {
+                            event_received = true;
+                            window_event_sender.send(WinitWindowEvent {  window_event, window_id });
+                        }
+                        Event::Suspended => {
+                            event_received = true;
+                            lifecycle_event_sender.send(AppLifecycleEvent::Suspended);
+                        }
+                        Event::Resumed => {
+                            event_received = true;
+                            lifecycle_event_sender.send(AppLifecycleEvent::Resumed);
+                        }
                         _ => (),
                     }
                 }
         );
+
+        let now = Instant::now();
+        let frame_due = now >= frame_deadline;
+        if matches!(status, PumpStatus::Exit(_)) || now >= wake_deadline || (frame_due && event_received) {
+            break;
+        }
+    }
+
+    // once per update, a redraw wakes the event loop right away so requesting it while pumping would spin
+    if *update_mode == WindowUpdateMode::Continuous {
+        for window_component in windows.iter() {
+            window_component.window.request_redraw();
+        }
+    }
+}
+
+fn app_lifecycle_system(
+    mut lifecycle_events: EventReader<AppLifecycleEvent>,
+    mut lifecycle: ResMut<AppLifecycle>,
+) {
+    if let Some(event) = lifecycle_events.read().last() {
+        let state = match event {
+            AppLifecycleEvent::Suspended => AppLifecycle::Suspended,
+            AppLifecycleEvent::Resumed => AppLifecycle::Running,
+        };
+        lifecycle.set_if_neq(state);
+    }
 }
 
 fn window_update_system(
@@ -154,20 +464,74 @@ fn window_update_system(
     });
 }
 
+fn window_text_input_system(
+    mut events: EventReader<WinitWindowEvent>,
+    mut ime_writer: EventWriter<ImeEvent>,
+    mut character_writer: EventWriter<ReceivedCharacter>,
+    windows: Query<(Entity, &WindowComponent)>,
+) {
+    for evt in events.read() {
+        let Some((window, _)) = windows.iter().find(|(_, i)| i.window.id() == evt.window_id) else {
+            continue;
+        };
+        match &evt.window_event {
+            WindowEvent::Ime(ime) => ime_writer.send(ImeEvent { window, ime: ime.clone() }),
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                let Some(text) = &event.text else { continue };
+                // control characters of keys like backspace or escape are left to key bindings
+                for char in text.chars().filter(|char| !char.is_control()) {
+                    character_writer.send(ReceivedCharacter { window, char });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 fn window_close_system(
     mut close_reader: EventReader<WindowClosedEvent>,
-    windows: Query<(Entity, &WindowComponent)>,
+    mut primary_close_writer: EventWriter<PrimaryWindowCloseRequested>,
+    windows: Query<(Entity, &WindowComponent, Option<&PrimaryWindowComponent>)>,
+    exit_settings: Res<WindowExitSettings>,
     mut commands: Commands,
 ) {
     for evt in close_reader.read() {
-        if let Some((entity, _window)) = windows
+        if let Some((entity, window, primary)) = windows
             .iter()
-            .find(|(_entity, i)| i.window.id() == evt.window_id) {
+            .find(|(_entity, i, _)| i.window.id() == evt.window_id) {
+            if primary.is_some() {
+                primary_close_writer.send(PrimaryWindowCloseRequested { window: entity });
+                if !exit_settings.close_primary_when_requested {
+                    continue;
+                }
+            }
+            WINDOW_IDS.free(window.id.0);
             commands.entity(entity).despawn();
         }
     }
 }
 
+fn exit_on_window_close_system(
+    exit_settings: Res<WindowExitSettings>,
+    mut removed_windows: RemovedComponents<WindowComponent>,
+    mut removed_primary_windows: RemovedComponents<PrimaryWindowComponent>,
+    windows: Query<(), With<WindowComponent>>,
+    primary_windows: Query<(), With<PrimaryWindowComponent>>,
+    mut app_exit_writer: EventWriter<AppExit>,
+) {
+    let primary_window_closed = removed_primary_windows.read().count() > 0;
+    let window_closed = removed_windows.read().count() > 0;
+    let should_exit = match exit_settings.exit_condition {
+        ExitCondition::OnPrimaryClosed => primary_window_closed && primary_windows.is_empty(),
+        ExitCondition::OnAllClosed => window_closed && windows.is_empty(),
+        ExitCondition::DontExit => false,
+    };
+    if should_exit {
+        log::info!("No window left to keep the app running, exiting");
+        app_exit_writer.send(AppExit);
+    }
+}
+
 
 /// ## SAFETY
 /// Use this wrapper in main thread.
@@ -0,0 +1,98 @@
+use bevy_ecs::prelude::{Added, Commands, Component, Entity, Query, ReflectComponent, Res, ResMut, Resource, With, Without};
+use bevy_math::{Mat4, UVec2, Vec4};
+use bevy_reflect::Reflect;
+use openxr as xr;
+use avalanche_asset::Assets;
+use avalanche_rendering::camera::{PerspectiveProjection, StereoCamera, StereoEyePoses};
+use avalanche_rendering::extract::ExtractComponent;
+use avalanche_rendering::render_target::RenderTargetImage;
+use avalanche_rendering::texture::Texture;
+use crate::XrFrame;
+use crate::input::pose_to_transform;
+
+/// Renders a [`Camera`](avalanche_rendering::camera::Camera) to the headset, its transform is the origin of the stage.
+///
+/// The camera is made a [`StereoCamera`] rendering into a [`RenderTargetImage`] of the recommended eye size,
+/// the eyes are posed and projected as the runtime locates them. Only one camera is shown on the headset.
+#[derive(Component, ExtractComponent, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct XrCamera;
+
+/// Size of the images of the eyes, the size of the XR swapchain.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct XrResolution(pub UVec2);
+
+type AddedXrCameraQuery<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, Option<&'static StereoCamera>, Option<&'static RenderTargetImage>),
+    Added<XrCamera>,
+>;
+
+pub(crate) fn setup_xr_cameras(
+    mut commands: Commands,
+    cameras: AddedXrCameraQuery,
+    resolution: Res<XrResolution>,
+    mut textures: ResMut<Assets<Texture>>,
+) {
+    for (entity, stereo, target) in cameras.iter() {
+        let mut entity_commands = commands.entity(entity);
+        if stereo.is_none() {
+            entity_commands.insert(StereoCamera::default());
+        }
+        if target.is_none() {
+            entity_commands.insert(RenderTargetImage::new(&mut textures, resolution.0));
+        }
+    }
+}
+
+type UninitializedXrCameraQuery<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, Option<&'static PerspectiveProjection>),
+    (With<XrCamera>, Without<StereoEyePoses>),
+>;
+
+/// Pose and project the eyes of the [`XrCamera`]s with the views located for the frame.
+pub(crate) fn update_xr_camera_views(
+    frame: Res<XrFrame>,
+    mut cameras: Query<(&mut StereoEyePoses, Option<&PerspectiveProjection>), With<XrCamera>>,
+    uninitialized: UninitializedXrCameraQuery,
+    mut commands: Commands,
+) {
+    let Some(views) = frame.0.as_ref().map(|frame| &frame.views).filter(|views| views.len() >= 2) else {
+        return;
+    };
+    let eye_poses = |projection: Option<&PerspectiveProjection>| {
+        let projection = projection.copied().unwrap_or_default();
+        StereoEyePoses {
+            camera_from_eye: std::array::from_fn(|eye| pose_to_transform(&views[eye].pose).compute_matrix()),
+            projection: std::array::from_fn(|eye| fov_projection(views[eye].fov, projection.near, projection.far)),
+        }
+    };
+
+    for (mut poses, projection) in cameras.iter_mut() {
+        *poses = eye_poses(projection);
+    }
+    for (entity, projection) in uninitialized.iter() {
+        commands.entity(entity).insert(eye_poses(projection));
+    }
+}
+
+/// Asymmetric right handed projection of a field of view, with the vulkan clip space (y down, depth in `[0, 1]`).
+pub fn fov_projection(fov: xr::Fovf, near: f32, far: f32) -> Mat4 {
+    let left = fov.angle_left.tan();
+    let right = fov.angle_right.tan();
+    let up = fov.angle_up.tan();
+    let down = fov.angle_down.tan();
+    // the height is negated to flip y down
+    let width = right - left;
+    let height = down - up;
+
+    Mat4::from_cols(
+        Vec4::new(2.0 / width, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, 2.0 / height, 0.0, 0.0),
+        Vec4::new((right + left) / width, (up + down) / height, -far / (far - near), -1.0),
+        Vec4::new(0.0, 0.0, -far * near / (far - near), 0.0),
+    )
+}
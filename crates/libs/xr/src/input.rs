@@ -0,0 +1,103 @@
+use bevy_ecs::prelude::{Component, Query, ReflectComponent, Res, Resource};
+use bevy_log::error;
+use bevy_math::{Quat, Vec3};
+use bevy_reflect::Reflect;
+use bevy_transform::prelude::Transform;
+use openxr as xr;
+use crate::{XrFrame, XrInstance, XrSession};
+
+/// Entity following the pose of the headset or a controller, its [`Transform`] is relative to the stage.
+///
+/// Make it a child of the entity with the [`XrCamera`](crate::XrCamera) to follow the camera rig.
+#[derive(Component, Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[reflect(Component)]
+pub enum XrTracked {
+    #[default]
+    Head,
+    LeftHand,
+    RightHand,
+}
+
+/// Spaces of the headset and of the grip of the controllers.
+#[derive(Resource)]
+pub struct XrTrackedSpaces {
+    action_set: xr::ActionSet,
+    head: xr::Space,
+    left_hand: xr::Space,
+    right_hand: xr::Space,
+}
+
+impl XrTrackedSpaces {
+    /// Bind the controller poses for the simple controller profile, every runtime maps its controllers to it.
+    ///
+    /// Must be called before the session begins.
+    pub(crate) fn new(instance: &XrInstance, session: &XrSession) -> anyhow::Result<Self> {
+        let instance = &instance.instance;
+        let action_set = instance.create_action_set("avalanche", "Avalanche", 0)?;
+        let left_hand = action_set.create_action::<xr::Posef>("left_hand", "Left Hand", &[])?;
+        let right_hand = action_set.create_action::<xr::Posef>("right_hand", "Right Hand", &[])?;
+        instance.suggest_interaction_profile_bindings(
+            instance.string_to_path("/interaction_profiles/khr/simple_controller")?,
+            &[
+                xr::Binding::new(&left_hand, instance.string_to_path("/user/hand/left/input/grip/pose")?),
+                xr::Binding::new(&right_hand, instance.string_to_path("/user/hand/right/input/grip/pose")?),
+            ],
+        )?;
+        session.session.attach_action_sets(&[&action_set])?;
+
+        Ok(Self {
+            head: session.session.create_reference_space(xr::ReferenceSpaceType::VIEW, xr::Posef::IDENTITY)?,
+            left_hand: left_hand.create_space(session.session.clone(), xr::Path::NULL, xr::Posef::IDENTITY)?,
+            right_hand: right_hand.create_space(session.session.clone(), xr::Path::NULL, xr::Posef::IDENTITY)?,
+            action_set,
+        })
+    }
+
+    fn space(&self, tracked: XrTracked) -> &xr::Space {
+        match tracked {
+            XrTracked::Head => &self.head,
+            XrTracked::LeftHand => &self.left_hand,
+            XrTracked::RightHand => &self.right_hand,
+        }
+    }
+}
+
+pub(crate) fn pose_to_transform(pose: &xr::Posef) -> Transform {
+    let position = pose.position;
+    let orientation = pose.orientation;
+    Transform::from_translation(Vec3::new(position.x, position.y, position.z))
+        .with_rotation(Quat::from_xyzw(orientation.x, orientation.y, orientation.z, orientation.w))
+}
+
+/// Move the [`XrTracked`] entities to their poses at the display time of the frame,
+/// the ones whose pose isn't known, e.g. a controller out of sight, keep their last transform.
+pub(crate) fn update_xr_tracked_poses(
+    frame: Res<XrFrame>,
+    session: Res<XrSession>,
+    spaces: Res<XrTrackedSpaces>,
+    mut tracked: Query<(&XrTracked, &mut Transform)>,
+) {
+    let Some(display_time) = frame.predicted_display_time() else {
+        return;
+    };
+    if let Err(err) = session.session.sync_actions(&[xr::ActiveActionSet::new(&spaces.action_set)]) {
+        error!("[OpenXR] Failed to sync the actions: {err}");
+        return;
+    }
+
+    for (&tracked, mut transform) in tracked.iter_mut() {
+        let location = match spaces.space(tracked).locate(&session.stage, display_time) {
+            Ok(location) => location,
+            Err(err) => {
+                error!("[OpenXR] Failed to locate {tracked:?}: {err}");
+                continue;
+            }
+        };
+        let valid = xr::SpaceLocationFlags::POSITION_VALID | xr::SpaceLocationFlags::ORIENTATION_VALID;
+        if location.location_flags.contains(valid) {
+            let pose = pose_to_transform(&location.pose);
+            transform.translation = pose.translation;
+            transform.rotation = pose.rotation;
+        }
+    }
+}
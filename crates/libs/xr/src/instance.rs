@@ -0,0 +1,114 @@
+use ash::vk;
+use ash::vk::Handle;
+use bevy_ecs::prelude::Resource;
+use openxr as xr;
+use avalanche_hlvk::ContextCreateHooks;
+use avalanche_utils::Version;
+use crate::VIEW_CONFIGURATION;
+
+/// The OpenXR instance and the head mounted display the app renders to, in the main and render worlds.
+#[derive(Resource, Clone)]
+pub struct XrInstance {
+    pub instance: xr::Instance,
+    pub system: xr::SystemId,
+    /// How the runtime composes the rendered views with the real world, opaque when supported
+    pub blend_mode: xr::EnvironmentBlendMode,
+}
+
+impl XrInstance {
+    /// Load the OpenXR runtime and find a head mounted display supporting Vulkan at `vulkan_version`.
+    pub fn new(app_name: &str, vulkan_version: Version) -> anyhow::Result<Self> {
+        let entry = unsafe { xr::Entry::load()? };
+        let available_extensions = entry.enumerate_extensions()?;
+        anyhow::ensure!(available_extensions.khr_vulkan_enable2, "The OpenXR runtime doesn't support Vulkan");
+
+        let enabled_extensions = xr::ExtensionSet {
+            khr_vulkan_enable2: true,
+            ..Default::default()
+        };
+        let instance = entry.create_instance(
+            &xr::ApplicationInfo {
+                application_name: app_name,
+                application_version: 0,
+                engine_name: "Avalanche Engine",
+                engine_version: 0,
+            },
+            &enabled_extensions,
+            &[],
+        )?;
+        let system = instance.system(xr::FormFactor::HEAD_MOUNTED_DISPLAY)?;
+
+        let requirements = instance.graphics_requirements::<xr::Vulkan>(system)?;
+        let version = xr::Version::new(vulkan_version.major as u16, vulkan_version.minor as u16, 0);
+        anyhow::ensure!(
+            requirements.min_api_version_supported <= version,
+            "The OpenXR runtime requires Vulkan {}.{}",
+            requirements.min_api_version_supported.major(),
+            requirements.min_api_version_supported.minor(),
+        );
+
+        let blend_modes = instance.enumerate_environment_blend_modes(system, VIEW_CONFIGURATION)?;
+        let blend_mode = if blend_modes.contains(&xr::EnvironmentBlendMode::OPAQUE) {
+            xr::EnvironmentBlendMode::OPAQUE
+        } else {
+            *blend_modes.first().ok_or_else(|| anyhow::anyhow!("The OpenXR system has no blend mode"))?
+        };
+
+        Ok(Self {
+            instance,
+            system,
+            blend_mode,
+        })
+    }
+
+    /// Recommended size of the image of an eye.
+    pub fn recommended_resolution(&self) -> anyhow::Result<(u32, u32)> {
+        let views = self.instance.enumerate_view_configuration_views(self.system, VIEW_CONFIGURATION)?;
+        let view = views.first().ok_or_else(|| anyhow::anyhow!("The OpenXR system has no view"))?;
+        Ok((view.recommended_image_rect_width, view.recommended_image_rect_height))
+    }
+}
+
+/// Creates the Vulkan instance and device through the runtime, which enables the extensions it needs
+/// and picks the GPU the headset is connected to.
+impl ContextCreateHooks for XrInstance {
+    fn create_instance(&self, entry: &ash::Entry, create_info: &vk::InstanceCreateInfo) -> anyhow::Result<ash::Instance> {
+        let handle = unsafe {
+            self.instance.create_vulkan_instance(
+                self.system,
+                std::mem::transmute::<vk::PFN_vkGetInstanceProcAddr, xr::sys::platform::VkGetInstanceProcAddr>(
+                    entry.static_fn().get_instance_proc_addr,
+                ),
+                create_info as *const vk::InstanceCreateInfo as *const _,
+            )?
+        };
+        let handle = handle.map_err(vk::Result::from_raw)?;
+        Ok(unsafe { ash::Instance::load(entry.static_fn(), vk::Instance::from_raw(handle as u64)) })
+    }
+
+    fn physical_device(&self, instance: &ash::Instance) -> anyhow::Result<Option<vk::PhysicalDevice>> {
+        let handle = unsafe { self.instance.vulkan_graphics_device(self.system, instance.handle().as_raw() as _)? };
+        Ok(Some(vk::PhysicalDevice::from_raw(handle as u64)))
+    }
+
+    fn create_device(
+        &self,
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        create_info: &vk::DeviceCreateInfo,
+    ) -> anyhow::Result<ash::Device> {
+        let handle = unsafe {
+            self.instance.create_vulkan_device(
+                self.system,
+                std::mem::transmute::<vk::PFN_vkGetInstanceProcAddr, xr::sys::platform::VkGetInstanceProcAddr>(
+                    entry.static_fn().get_instance_proc_addr,
+                ),
+                physical_device.as_raw() as _,
+                create_info as *const vk::DeviceCreateInfo as *const _,
+            )?
+        };
+        let handle = handle.map_err(vk::Result::from_raw)?;
+        Ok(unsafe { ash::Device::load(instance.fp_v1_0(), vk::Device::from_raw(handle as u64)) })
+    }
+}
@@ -0,0 +1,114 @@
+//! OpenXR support, rendering an [`XrCamera`] to a head mounted display.
+
+mod instance;
+mod session;
+mod input;
+mod camera;
+mod render;
+
+pub use instance::*;
+pub use session::*;
+pub use input::*;
+pub use camera::*;
+pub use render::*;
+
+use std::sync::{Arc, Mutex};
+use bevy_app::{App, First, Plugin, PreUpdate};
+use bevy_ecs::prelude::IntoSystemConfigs;
+use bevy_log::{error, info};
+use bevy_math::UVec2;
+use openxr as xr;
+use avalanche_rendering::{ExtractSchedule, Render, RenderApp, RenderSet};
+use avalanche_rendering::deferred::DEFERRED_GRAPH;
+use avalanche_rendering::extract::ExtractComponentPlugin;
+use avalanche_rendering::graph::RenderGraphApp;
+use avalanche_rendering::graph::node::ViewNodeRunner;
+use avalanche_rendering::path_tracing::PATH_TRACING_GRAPH;
+use avalanche_rendering::prelude::{RenderingContext, RenderingContextHooks};
+use avalanche_rendering::render_target::RENDER_TARGET_NODE;
+use avalanche_rendering::sprite::SPRITE_GRAPH;
+use avalanche_utils::VERSION_1_3;
+
+/// The views of the headset, both eyes rendered at once.
+pub const VIEW_CONFIGURATION: xr::ViewConfigurationType = xr::ViewConfigurationType::PRIMARY_STEREO;
+
+/// Renders the [`XrCamera`] to the headset and tracks the [`XrTracked`] entities.
+///
+/// It creates the Vulkan instance and device through the runtime, so it must be added before the
+/// [`RenderingContext`] is created, e.g. before `EngineContextSetupPlugin`. Without a runtime or a
+/// headset the plugin logs why and the app runs without XR.
+///
+/// The main world waits for the frames of the runtime in [`First`], which paces the app to the headset,
+/// and the render world begins them once extracted and ends them after they were submitted.
+#[derive(Default)]
+pub struct XrPlugin;
+
+impl Plugin for XrPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<XrCamera>()
+            .register_type::<XrTracked>();
+
+        // the engine renders with Vulkan 1.3
+        let instance = match XrInstance::new("Avalanche Engine", VERSION_1_3) {
+            Ok(instance) => instance,
+            Err(err) => {
+                info!("[OpenXR] Running without XR: {err}");
+                return;
+            }
+        };
+        app.insert_resource(RenderingContextHooks(Arc::new(instance.clone())))
+            .insert_resource(instance);
+    }
+
+    fn finish(&self, app: &mut App) {
+        let (Some(instance), Some(context)) = (
+            app.world.get_resource::<XrInstance>().cloned(),
+            app.world.get_resource::<RenderingContext>().cloned(),
+        ) else {
+            return;
+        };
+        if let Err(err) = setup_session(app, instance, &context) {
+            error!("[OpenXR] Failed to create the session, running without XR: {err}");
+        }
+    }
+}
+
+fn setup_session(app: &mut App, instance: XrInstance, context: &RenderingContext) -> anyhow::Result<()> {
+    let (width, height) = instance.recommended_resolution()?;
+    let resolution = UVec2::new(width, height);
+    let (session, frame_waiter, frame_stream) = XrSession::new(&instance, context)?;
+    let tracked_spaces = XrTrackedSpaces::new(&instance, &session)?;
+    let swapchain = XrSwapchain::new(&session, context, resolution)?;
+    info!("[OpenXR] Rendering the eyes at {width}x{height}");
+
+    app.insert_resource(session.clone())
+        .insert_resource(tracked_spaces)
+        .insert_resource(XrResolution(resolution))
+        .insert_resource(XrFrameWaiter(Mutex::new(frame_waiter)))
+        .init_resource::<XrSessionState>()
+        .init_resource::<XrFrame>()
+        .add_systems(First, (poll_xr_events, wait_xr_frame).chain())
+        .add_systems(PreUpdate, (setup_xr_cameras, update_xr_camera_views, update_xr_tracked_poses));
+    // the plugins are built already
+    ExtractComponentPlugin::<XrCamera>::default().build(app);
+
+    let render_app = app.get_sub_app_mut(RenderApp).map_err(|_| anyhow::anyhow!("No render app"))?;
+    render_app
+        .insert_resource(instance)
+        .insert_resource(session)
+        .insert_resource(swapchain)
+        .insert_resource(XrFrameStream(Mutex::new(frame_stream)))
+        .init_resource::<XrRenderFrame>()
+        .add_systems(ExtractSchedule, extract_xr_frame)
+        .add_systems(Render, (
+            begin_xr_frame.in_set(RenderSet::ExtractCommands),
+            acquire_xr_image.in_set(RenderSet::ManageViews),
+            end_xr_frame.in_set(RenderSet::Cleanup),
+        ));
+    for graph in [PATH_TRACING_GRAPH, DEFERRED_GRAPH, SPRITE_GRAPH] {
+        render_app
+            .add_render_graph_node::<ViewNodeRunner<XrSwapchainNode>>(graph, XR_SWAPCHAIN_NODE)
+            .add_render_graph_edge(graph, RENDER_TARGET_NODE, XR_SWAPCHAIN_NODE);
+    }
+    Ok(())
+}
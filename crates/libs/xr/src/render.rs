@@ -0,0 +1,249 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use ash::vk;
+use ash::vk::Handle;
+use bevy_ecs::prelude::{Res, ResMut, Resource, World};
+use bevy_log::error;
+use bevy_math::UVec2;
+use openxr as xr;
+use avalanche_hlvk::{Image, ImageBarrier};
+use avalanche_rendering::MainWorld;
+use avalanche_rendering::extract::FrameContext;
+use avalanche_rendering::graph::node::ViewNode;
+use avalanche_rendering::prelude::{NodeRunError, RenderGraphContext, RenderingContext};
+use avalanche_rendering::view::{StereoViewTarget, MAX_VIEWS};
+use crate::{XrCamera, XrFrame, XrInstance, XrSession};
+use crate::session::WaitedFrame;
+
+/// Name of the [`XrSwapchainNode`] at the end of the built-in camera graphs.
+pub const XR_SWAPCHAIN_NODE: &str = "xr_swapchain_copy";
+
+/// Formats of the XR swapchain by preference, the runtime converts sRGB images to the display.
+const XR_SWAPCHAIN_FORMATS: &[vk::Format] = &[
+    vk::Format::R8G8B8A8_SRGB,
+    vk::Format::B8G8R8A8_SRGB,
+    vk::Format::R8G8B8A8_UNORM,
+    vk::Format::B8G8R8A8_UNORM,
+];
+
+/// Swapchain of the runtime with a layer per eye, in the render world.
+#[derive(Resource)]
+pub struct XrSwapchain {
+    swapchain: Mutex<xr::Swapchain<xr::Vulkan>>,
+    /// Owned by the runtime
+    pub images: Vec<Image>,
+    pub resolution: UVec2,
+}
+
+impl XrSwapchain {
+    pub(crate) fn new(session: &XrSession, context: &RenderingContext, resolution: UVec2) -> anyhow::Result<Self> {
+        let supported_formats = session.session.enumerate_swapchain_formats()?;
+        let format = XR_SWAPCHAIN_FORMATS
+            .iter()
+            .copied()
+            .find(|format| supported_formats.contains(&(format.as_raw() as u32)))
+            .ok_or_else(|| anyhow::anyhow!("The OpenXR runtime supports none of the swapchain formats"))?;
+
+        let swapchain = session.session.create_swapchain(&xr::SwapchainCreateInfo {
+            create_flags: xr::SwapchainCreateFlags::EMPTY,
+            usage_flags: xr::SwapchainUsageFlags::COLOR_ATTACHMENT | xr::SwapchainUsageFlags::TRANSFER_DST,
+            format: format.as_raw() as u32,
+            sample_count: 1,
+            width: resolution.x,
+            height: resolution.y,
+            face_count: 1,
+            array_size: MAX_VIEWS as u32,
+            mip_count: 1,
+        })?;
+        let images = swapchain
+            .enumerate_images()?
+            .into_iter()
+            .map(|image| {
+                let image = vk::Image::from_raw(image);
+                context.import_image(image, format, resolution.x, resolution.y, MAX_VIEWS as u32)
+            })
+            .collect();
+
+        Ok(Self {
+            swapchain: Mutex::new(swapchain),
+            images,
+            resolution,
+        })
+    }
+}
+
+/// Submits the rendered frames to the runtime, in the render world.
+#[derive(Resource)]
+pub(crate) struct XrFrameStream(pub(crate) Mutex<xr::FrameStream<xr::Vulkan>>);
+
+/// The frame being rendered, begun once extracted and ended after it was submitted.
+#[derive(Resource, Default)]
+pub(crate) struct XrRenderFrame {
+    frame: Option<WaitedFrame>,
+    /// Index of the swapchain image acquired for the frame
+    image_index: Option<u32>,
+    /// Whether an [`XrCamera`] was copied into the image
+    rendered: AtomicBool,
+}
+
+pub(crate) fn extract_xr_frame(mut main_world: ResMut<MainWorld>, mut frame: ResMut<XrRenderFrame>) {
+    frame.frame = main_world.resource_mut::<XrFrame>().0.take();
+}
+
+/// Every waited frame is begun and ended, even when nothing is rendered, for the runtime to keep pacing the app.
+pub(crate) fn begin_xr_frame(stream: Res<XrFrameStream>, mut frame: ResMut<XrRenderFrame>) {
+    if frame.frame.is_none() {
+        return;
+    }
+    if let Err(err) = stream.0.lock().unwrap().begin() {
+        error!("[OpenXR] Failed to begin the frame: {err}");
+        frame.frame = None;
+    }
+}
+
+pub(crate) fn acquire_xr_image(swapchain: Res<XrSwapchain>, mut frame: ResMut<XrRenderFrame>) {
+    if !frame.frame.as_ref().is_some_and(|frame| frame.state.should_render && frame.views.len() >= MAX_VIEWS) {
+        return;
+    }
+    let mut xr_swapchain = swapchain.swapchain.lock().unwrap();
+    let acquired = xr_swapchain
+        .acquire_image()
+        .and_then(|index| xr_swapchain.wait_image(xr::Duration::INFINITE).map(|_| index));
+    match acquired {
+        Ok(index) => frame.image_index = Some(index),
+        Err(err) => error!("[OpenXR] Failed to acquire a swapchain image: {err}"),
+    }
+}
+
+/// Release the image once the frame was submitted and show it on the headset if a camera was copied into it.
+pub(crate) fn end_xr_frame(
+    stream: Res<XrFrameStream>,
+    swapchain: Res<XrSwapchain>,
+    instance: Res<XrInstance>,
+    session: Res<XrSession>,
+    mut frame: ResMut<XrRenderFrame>,
+) {
+    let Some(waited) = frame.frame.take() else {
+        return;
+    };
+    let rendered = std::mem::take(frame.rendered.get_mut());
+    let mut xr_swapchain = swapchain.swapchain.lock().unwrap();
+    let acquired = frame.image_index.take().is_some();
+    if acquired {
+        if let Err(err) = xr_swapchain.release_image() {
+            error!("[OpenXR] Failed to release the swapchain image: {err}");
+        }
+    }
+
+    let mut stream = stream.0.lock().unwrap();
+    let display_time = waited.state.predicted_display_time;
+    let result = if acquired && rendered {
+        let image_rect = xr::Rect2Di {
+            offset: xr::Offset2Di { x: 0, y: 0 },
+            extent: xr::Extent2Di {
+                width: swapchain.resolution.x as i32,
+                height: swapchain.resolution.y as i32,
+            },
+        };
+        let views = [0, 1].map(|eye| {
+            xr::CompositionLayerProjectionView::new()
+                .pose(waited.views[eye].pose)
+                .fov(waited.views[eye].fov)
+                .sub_image(
+                    xr::SwapchainSubImage::new()
+                        .swapchain(&*xr_swapchain)
+                        .image_array_index(eye as u32)
+                        .image_rect(image_rect),
+                )
+        });
+        let layer = xr::CompositionLayerProjection::new().space(&session.stage).views(&views);
+        stream.end(display_time, instance.blend_mode, &[&layer])
+    } else {
+        stream.end(display_time, instance.blend_mode, &[])
+    };
+    if let Err(err) = result {
+        error!("[OpenXR] Failed to end the frame: {err}");
+    }
+}
+
+/// Blits the eyes of the [`StereoViewTarget`] of the [`XrCamera`] into the acquired image of the [`XrSwapchain`],
+/// converting them to the swapchain format. The first camera reaching it is shown, the other ones are skipped.
+#[derive(Default)]
+pub struct XrSwapchainNode;
+
+impl ViewNode for XrSwapchainNode {
+    type ViewQuery = (&'static XrCamera, &'static StereoViewTarget);
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        rendering_context: &FrameContext,
+        (_, target): (&XrCamera, &StereoViewTarget),
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let (Some(frame), Some(swapchain)) = (world.get_resource::<XrRenderFrame>(), world.get_resource::<XrSwapchain>()) else {
+            return Ok(());
+        };
+        let Some(image) = frame.image_index.map(|index| &swapchain.images[index as usize]) else {
+            return Ok(());
+        };
+        let Some(command_buffer) = rendering_context.command_buffer(0) else {
+            return Ok(());
+        };
+        if frame.rendered.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        // the runtime hands out and takes back the images as color attachments
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image: &target.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::MEMORY_WRITE,
+                dst_access_mask: vk::AccessFlags2::TRANSFER_READ,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            },
+            ImageBarrier {
+                image,
+                old_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                src_access_mask: vk::AccessFlags2::NONE,
+                dst_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+                dst_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+            },
+        ]);
+        command_buffer.blit_image_layers(
+            &target.image,
+            vk::ImageLayout::GENERAL,
+            image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            MAX_VIEWS as u32,
+            vk::Filter::LINEAR,
+        );
+        command_buffer.pipeline_image_barriers(&[
+            ImageBarrier {
+                image,
+                old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                new_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                src_access_mask: vk::AccessFlags2::TRANSFER_WRITE,
+                dst_access_mask: vk::AccessFlags2::NONE,
+                src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            },
+            ImageBarrier {
+                image: &target.image,
+                old_layout: vk::ImageLayout::GENERAL,
+                new_layout: vk::ImageLayout::GENERAL,
+                src_access_mask: vk::AccessFlags2::TRANSFER_READ,
+                dst_access_mask: vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE,
+                src_stage_mask: vk::PipelineStageFlags2::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags2::ALL_COMMANDS,
+            },
+        ]);
+
+        Ok(())
+    }
+}
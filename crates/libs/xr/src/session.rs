@@ -0,0 +1,168 @@
+use std::sync::{Arc, Mutex};
+use ash::vk::Handle;
+use bevy_app::AppExit;
+use bevy_ecs::prelude::{EventWriter, Res, ResMut, Resource};
+use bevy_log::{error, info, warn};
+use openxr as xr;
+use avalanche_rendering::prelude::RenderingContext;
+use crate::{XrInstance, VIEW_CONFIGURATION};
+
+/// The OpenXR session rendering with the device of the [`RenderingContext`], in the main and render worlds.
+#[derive(Resource, Clone)]
+pub struct XrSession {
+    pub session: xr::Session<xr::Vulkan>,
+    /// Space the poses are relative to, on the floor at the center of the play area
+    pub stage: Arc<xr::Space>,
+}
+
+impl XrSession {
+    /// Create the session with the graphics queue of the context, with the frame waiter and stream of the session.
+    pub(crate) fn new(
+        instance: &XrInstance,
+        context: &RenderingContext,
+    ) -> anyhow::Result<(Self, xr::FrameWaiter, xr::FrameStream<xr::Vulkan>)> {
+        let (session, frame_waiter, frame_stream) = unsafe {
+            instance.instance.create_session::<xr::Vulkan>(
+                instance.system,
+                &xr::vulkan::SessionCreateInfo {
+                    instance: context.instance.handle().as_raw() as _,
+                    physical_device: context.physical_device.handle().as_raw() as _,
+                    device: context.device.inner.handle().as_raw() as _,
+                    queue_family_index: context.graphics_queue_family.index,
+                    queue_index: 0,
+                },
+            )?
+        };
+        let stage = session.create_reference_space(xr::ReferenceSpaceType::STAGE, xr::Posef::IDENTITY)?;
+        Ok((
+            Self {
+                session,
+                stage: Arc::new(stage),
+            },
+            frame_waiter,
+            frame_stream,
+        ))
+    }
+}
+
+/// State of the [`XrSession`] reported by the runtime, frames are only waited and rendered while it is running.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct XrSessionState {
+    pub state: xr::SessionState,
+    pub running: bool,
+}
+
+impl Default for XrSessionState {
+    fn default() -> Self {
+        Self {
+            state: xr::SessionState::IDLE,
+            running: false,
+        }
+    }
+}
+
+/// Blocks the main world until the runtime wants the next frame, which paces the app to the display of the headset.
+#[derive(Resource)]
+pub(crate) struct XrFrameWaiter(pub(crate) Mutex<xr::FrameWaiter>);
+
+/// A frame waited by the main world, rendered with the views located at its display time.
+#[derive(Clone)]
+pub(crate) struct WaitedFrame {
+    pub state: xr::FrameState,
+    /// Eye views relative to the stage, left first
+    pub views: Vec<xr::View>,
+}
+
+/// The frame the main world simulates this update, taken by the render world when extracted.
+#[derive(Resource, Default)]
+pub struct XrFrame(pub(crate) Option<WaitedFrame>);
+
+impl XrFrame {
+    /// When the frame is predicted to be displayed, to sample poses and animations at, `None` when no frame was waited.
+    pub fn predicted_display_time(&self) -> Option<xr::Time> {
+        self.0.as_ref().map(|frame| frame.state.predicted_display_time)
+    }
+
+    /// Whether the runtime shows the frame, rendering it can be skipped otherwise.
+    pub fn should_render(&self) -> bool {
+        self.0.as_ref().is_some_and(|frame| frame.state.should_render)
+    }
+}
+
+/// Begins and ends the session as the runtime requests, exiting the app once the runtime stops it.
+pub(crate) fn poll_xr_events(
+    instance: Res<XrInstance>,
+    session: Res<XrSession>,
+    mut session_state: ResMut<XrSessionState>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    let mut buffer = xr::EventDataBuffer::new();
+    loop {
+        let event = match instance.instance.poll_event(&mut buffer) {
+            Ok(Some(event)) => event,
+            Ok(None) => break,
+            Err(err) => {
+                error!("[OpenXR] Failed to poll events: {err}");
+                break;
+            }
+        };
+        match event {
+            xr::Event::SessionStateChanged(changed) => {
+                let state = changed.state();
+                info!("[OpenXR] Session state changed to {state:?}");
+                session_state.state = state;
+                match state {
+                    xr::SessionState::READY => match session.session.begin(VIEW_CONFIGURATION) {
+                        Ok(_) => session_state.running = true,
+                        Err(err) => error!("[OpenXR] Failed to begin the session: {err}"),
+                    },
+                    xr::SessionState::STOPPING => {
+                        session_state.running = false;
+                        if let Err(err) = session.session.end() {
+                            error!("[OpenXR] Failed to end the session: {err}");
+                        }
+                    }
+                    xr::SessionState::EXITING | xr::SessionState::LOSS_PENDING => {
+                        session_state.running = false;
+                        app_exit.send(AppExit);
+                    }
+                    _ => {}
+                }
+            }
+            xr::Event::InstanceLossPending(_) => {
+                warn!("[OpenXR] The instance is about to be lost");
+                session_state.running = false;
+                app_exit.send(AppExit);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Wait for the next frame of the running session and locate the eyes at its display time.
+pub(crate) fn wait_xr_frame(
+    waiter: Res<XrFrameWaiter>,
+    session: Res<XrSession>,
+    session_state: Res<XrSessionState>,
+    mut frame: ResMut<XrFrame>,
+) {
+    frame.0 = None;
+    if !session_state.running {
+        return;
+    }
+    let state = match waiter.0.lock().unwrap().wait() {
+        Ok(state) => state,
+        Err(err) => {
+            error!("[OpenXR] Failed to wait for the frame: {err}");
+            return;
+        }
+    };
+    let views = match session.session.locate_views(VIEW_CONFIGURATION, state.predicted_display_time, &session.stage) {
+        Ok((_, views)) => views,
+        Err(err) => {
+            error!("[OpenXR] Failed to locate the views: {err}");
+            Vec::new()
+        }
+    };
+    frame.0 = Some(WaitedFrame { state, views });
+}